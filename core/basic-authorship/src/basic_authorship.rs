@@ -30,7 +30,7 @@ use codec::Decode;
 use consensus_common::{self, evaluation};
 use primitives::{H256, Blake2Hasher, ExecutionContext};
 use runtime_primitives::traits::{
-	Block as BlockT, Hash as HashT, Header as HeaderT, ProvideRuntimeApi, AuthorityIdFor
+	Block as BlockT, Hash as HashT, Header as HeaderT, ProvideRuntimeApi, AuthorityIdFor, DigestFor,
 };
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::ApplyError;
@@ -53,11 +53,13 @@ pub trait AuthoringApi: Send + Sync + ProvideRuntimeApi where
 	/// The error used by this API type.
 	type Error: std::error::Error;
 
-	/// Build a block on top of the given, with inherent extrinsics pre-pushed.
+	/// Build a block on top of the given, with inherent extrinsics pre-pushed
+	/// and the inherent digests put into the header.
 	fn build_block<F: FnMut(&mut BlockBuilder<Self::Block>) -> ()>(
 		&self,
 		at: &BlockId<Self::Block>,
 		inherent_data: InherentData,
+		inherent_digests: DigestFor<Self::Block>,
 		build_ctx: F,
 	) -> Result<Self::Block, error::Error>;
 }
@@ -92,9 +94,10 @@ impl<B, E, Block, RA> AuthoringApi for SubstrateClient<B, E, Block, RA> where
 		&self,
 		at: &BlockId<Self::Block>,
 		inherent_data: InherentData,
+		inherent_digests: DigestFor<Self::Block>,
 		mut build_ctx: F,
 	) -> Result<Self::Block, error::Error> {
-		let mut block_builder = self.new_block_at(at)?;
+		let mut block_builder = client::block_builder::BlockBuilder::at_block(at, self, false, inherent_digests)?;
 
 		let runtime_api = self.runtime_api();
 		// We don't check the API versions any further here since the dispatch compatibility
@@ -174,12 +177,15 @@ impl<Block, C, A> consensus_common::Proposer<<C as AuthoringApi>::Block> for Pro
 	type Create = Result<<C as AuthoringApi>::Block, error::Error>;
 	type Error = error::Error;
 
-	fn propose(&self, inherent_data: InherentData, max_duration: time::Duration)
-		-> Result<<C as AuthoringApi>::Block, error::Error>
-	{
+	fn propose(
+		&self,
+		inherent_data: InherentData,
+		inherent_digests: DigestFor<Block>,
+		max_duration: time::Duration,
+	) -> Result<<C as AuthoringApi>::Block, error::Error> {
 		// leave some time for evaluation and block finalization (33%)
 		let deadline = (self.now)() + max_duration - max_duration / 3;
-		self.propose_with(inherent_data, deadline)
+		self.propose_with(inherent_data, inherent_digests, deadline)
 	}
 }
 
@@ -190,19 +196,24 @@ impl<Block, C, A> Proposer<Block, C, A>	where
 	A: txpool::ChainApi<Block=Block>,
 	client::error::Error: From<<C as AuthoringApi>::Error>,
 {
-	fn propose_with(&self, inherent_data: InherentData, deadline: time::Instant)
-		-> Result<<C as AuthoringApi>::Block, error::Error>
-	{
+	fn propose_with(
+		&self,
+		inherent_data: InherentData,
+		inherent_digests: DigestFor<Block>,
+		deadline: time::Instant,
+	) -> Result<<C as AuthoringApi>::Block, error::Error> {
 		use runtime_primitives::traits::BlakeTwo256;
 
-		/// If the block is full we will attempt to push at most
-		/// this number of transactions before quitting for real.
-		/// It allows us to increase block utilization.
+		/// If the block is full (the runtime limits the total size and weight
+		/// of its extrinsics) we will attempt to push at most this number of
+		/// transactions before quitting for real. Lighter transactions may
+		/// still fit, so it allows us to increase block utilization.
 		const MAX_SKIPPED_TRANSACTIONS: usize = 8;
 
 		let block = self.client.build_block(
 			&self.parent_id,
 			inherent_data,
+			inherent_digests,
 			|block_builder| {
 				// Add inherents from the internal pool
 
@@ -215,7 +226,7 @@ impl<Block, C, A> Proposer<Block, C, A>	where
 				}
 
 				// proceed with transactions
-				let mut is_first = true;
+				let mut block_is_empty = true;
 				let mut skipped = 0;
 				let mut unqueue_invalid = Vec::new();
 				let pending_iterator = self.transaction_pool.ready();
@@ -231,9 +242,11 @@ impl<Block, C, A> Proposer<Block, C, A>	where
 					match block_builder.push_extrinsic(pending.data.clone()) {
 						Ok(()) => {
 							debug!("[{:?}] Pushed to the block.", pending.hash);
+							block_is_empty = false;
 						}
 						Err(error::Error::ApplyExtrinsicFailed(ApplyError::FullBlock)) => {
-							if is_first {
+							if block_is_empty {
+								// too large or too heavy to ever fit into a block.
 								debug!("[{:?}] Invalid transaction: FullBlock on empty block", pending.hash);
 								unqueue_invalid.push(pending.hash.clone());
 							} else if skipped < MAX_SKIPPED_TRANSACTIONS {
@@ -252,8 +265,6 @@ impl<Block, C, A> Proposer<Block, C, A>	where
 							unqueue_invalid.push(pending.hash.clone());
 						}
 					}
-
-					is_first = false;
 				}
 
 				self.transaction_pool.remove_invalid(&unqueue_invalid);
@@ -331,7 +342,7 @@ mod tests {
 			cell.replace(new)
 		});
 		let deadline = time::Duration::from_secs(3);
-		let block = proposer.propose(Default::default(), deadline).unwrap();
+		let block = proposer.propose(Default::default(), Default::default(), deadline).unwrap();
 
 		// then
 		// block should have some extrinsics although we have some more in the pool.
@@ -362,7 +373,7 @@ mod tests {
 
 		// when
 		let deadline = time::Duration::from_secs(3);
-		let block = proposer.propose(Default::default(), deadline).unwrap();
+		let block = proposer.propose(Default::default(), Default::default(), deadline).unwrap();
 
 		// then
 		assert_eq!(block.extrinsics().len(), 1);
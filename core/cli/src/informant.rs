@@ -24,7 +24,7 @@ use service::{Service, Components};
 use tokio::runtime::TaskExecutor;
 use sysinfo::{get_current_pid, ProcessExt, System, SystemExt};
 use network::{SyncState, SyncProvider};
-use client::{backend::Backend, BlockchainEvents};
+use client::{backend::{Backend, StateCacheStats, CacheCounters}, BlockchainEvents};
 use substrate_telemetry::{telemetry, SUBSTRATE_INFO};
 use log::{info, warn};
 
@@ -47,6 +47,7 @@ pub fn start<C>(service: &Service<C>, exit: ::exit_future::Exit, handle: TaskExe
 	let txpool = service.transaction_pool();
 	let mut last_number = None;
 	let mut last_update = time::Instant::now();
+	let mut last_cache_stats = StateCacheStats::default();
 
 	let mut sys = System::new();
 
@@ -91,6 +92,20 @@ pub fn start<C>(service: &Service<C>, exit: ::exit_future::Exit, handle: TaskExe
 				Some(size) => size,
 				None => 0,
 			};
+			let cache_stats = backend.state_cache_stats().unwrap_or_default();
+			let import_cache = cache_stats.import.since(&last_cache_stats.import);
+			let query_cache = cache_stats.query.since(&last_cache_stats.query);
+			if import_cache != CacheCounters::default() || query_cache != CacheCounters::default() {
+				info!(
+					target: "substrate",
+					"State cache: import {}, query {}, used {} values, {} hashes, {} child",
+					CacheCountersFormat(&import_cache),
+					CacheCountersFormat(&query_cache),
+					ByteSizeFormat(cache_stats.values_size),
+					ByteSizeFormat(cache_stats.hashes_size),
+					ByteSizeFormat(cache_stats.child_size),
+				);
+			}
 
 			// get cpu usage and memory usage of this process
 			let (cpu_usage, memory) = if sys.refresh_process(self_pid) {
@@ -119,7 +134,21 @@ pub fn start<C>(service: &Service<C>, exit: ::exit_future::Exit, handle: TaskExe
 				"bandwidth_download" => bandwidth_download,
 				"bandwidth_upload" => bandwidth_upload,
 				"used_state_cache_size" => used_state_cache_size,
+				"state_cache_import_hits" => import_cache.local_hits + import_cache.shared_hits,
+				"state_cache_import_misses" => import_cache.misses,
+				"state_cache_import_evictions" => import_cache.evictions,
+				"state_cache_query_hits" => query_cache.local_hits + query_cache.shared_hits,
+				"state_cache_query_misses" => query_cache.misses,
+				"state_cache_query_evictions" => query_cache.evictions,
+				"state_cache_hashes_size" => cache_stats.hashes_size,
+				"state_cache_child_size" => cache_stats.child_size,
+				"state_cache_local_limit" => cache_stats.local_limit.unwrap_or(0),
+				"import_queue_depth" => sync_status.import_queue.queued_blocks,
+				"import_queue_processed" => sync_status.import_queue.processed_blocks,
+				"import_queue_verification_ms" => duration_millis(sync_status.import_queue.verification_time),
+				"import_queue_import_ms" => duration_millis(sync_status.import_queue.import_time),
 			);
+			last_cache_stats = cache_stats;
 		} else {
 			warn!("Error getting best block information");
 		}
@@ -195,6 +224,10 @@ fn speed(best_number: u64, last_number: Option<u64>, last_update: time::Instant)
 	}
 }
 
+fn duration_millis(duration: time::Duration) -> u64 {
+	duration.as_secs() * 1000 + duration.subsec_millis() as u64
+}
+
 /// Contains a number of bytes per second. Implements `fmt::Display` and shows this number of bytes
 /// per second in a nice way.
 struct TransferRateFormat(u64);
@@ -218,3 +251,39 @@ impl fmt::Display for TransferRateFormat {
 		write!(f, "{:.1}MiB/s", self.0 as f64 / (1024.0 * 1024.0))
 	}
 }
+
+/// Contains state cache counters of an interval. Implements `fmt::Display` and shows the hit rate
+/// together with the number of lookups and evictions.
+struct CacheCountersFormat<'a>(&'a CacheCounters);
+impl<'a> fmt::Display for CacheCountersFormat<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let lookups = self.0.local_hits + self.0.shared_hits + self.0.misses;
+		match self.0.hit_rate() {
+			Some(rate) => write!(f, "{}% hits of {} lookups", rate, lookups)?,
+			None => write!(f, "no lookups")?,
+		}
+		if self.0.evictions != 0 {
+			write!(f, " ({} evicted)", self.0.evictions)?;
+		}
+		Ok(())
+	}
+}
+
+/// Contains a number of bytes. Implements `fmt::Display` and shows this number of bytes
+/// in a nice way.
+struct ByteSizeFormat(usize);
+impl fmt::Display for ByteSizeFormat {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		// Under 1.0 kiB, display plain bytes.
+		if self.0 < 1024 {
+			return write!(f, "{} B", self.0)
+		}
+
+		// Under 1.0 MiB, display the value in kiB.
+		if self.0 < 1024 * 1024 {
+			return write!(f, "{:.1}kiB", self.0 as f64 / 1024.0)
+		}
+
+		write!(f, "{:.1}MiB", self.0 as f64 / (1024.0 * 1024.0))
+	}
+}
@@ -26,7 +26,7 @@ pub mod error;
 pub mod informant;
 
 use client::ExecutionStrategies;
-use runtime_primitives::traits::As;
+use runtime_primitives::{generic::BlockId, traits::As};
 use service::{
 	ServiceFactory, FactoryFullConfiguration, RuntimeGenesis,
 	FactoryGenesis, PruningMode, ChainSpec,
@@ -50,6 +50,7 @@ use structopt::{StructOpt, clap::AppSettings};
 pub use structopt::clap::App;
 use params::{
 	RunCmd, PurgeChainCmd, RevertCmd, ImportBlocksCmd, ExportBlocksCmd, BuildSpecCmd,
+	ExportStateCmd, ImportStateCmd, ReplayBlockCmd, BenchmarkCmd,
 	NetworkConfigurationParams, SharedParams, MergeParameters, TransactionPoolParams,
 	NodeKeyParams, NodeKeyType
 };
@@ -150,6 +151,16 @@ fn input_err<T: Into<String>>(msg: T) -> error::Error {
 	error::ErrorKind::Input(msg.into()).into()
 }
 
+fn parse_pruning_mode(pruning: Option<String>) -> error::Result<PruningMode> {
+	Ok(match pruning {
+		Some(ref s) if s == "archive" => PruningMode::ArchiveAll,
+		None => PruningMode::default(),
+		Some(s) => PruningMode::keep_blocks(
+			s.parse().map_err(|_| input_err("Invalid pruning mode specified"))?
+		),
+	})
+}
+
 /// Check whether a node name is considered as valid
 fn is_node_name_valid(_name: &str) -> Result<(), &str> {
 	let name = _name.to_string();
@@ -236,6 +247,14 @@ where
 			export_blocks::<F, _, _>(params, spec_factory, exit, version).map(|_| None),
 		params::CoreParams::ImportBlocks(params) =>
 			import_blocks::<F, _, _>(params, spec_factory, exit, version).map(|_| None),
+		params::CoreParams::ExportState(params) =>
+			export_state::<F, _>(params, spec_factory, version).map(|_| None),
+		params::CoreParams::ImportState(params) =>
+			import_state::<F, _>(params, spec_factory, version).map(|_| None),
+		params::CoreParams::ReplayBlock(params) =>
+			replay_block::<F, _>(params, spec_factory, version).map(|_| None),
+		params::CoreParams::Benchmark(params) =>
+			benchmark::<F, _>(params, spec_factory, version).map(|_| None),
 		params::CoreParams::PurgeChain(params) =>
 			purge_chain::<F, _>(params, spec_factory, version).map(|_| None),
 		params::CoreParams::Revert(params) =>
@@ -406,13 +425,10 @@ where
 		db_path(&base_path, config.chain_spec.id()).to_string_lossy().into();
 	config.database_cache_size = cli.database_cache_size;
 	config.state_cache_size = cli.state_cache_size;
-	config.pruning = match cli.pruning {
-		Some(ref s) if s == "archive" => PruningMode::ArchiveAll,
-		None => PruningMode::default(),
-		Some(s) => PruningMode::keep_blocks(
-			s.parse().map_err(|_| input_err("Invalid pruning mode specified"))?
-		),
-	};
+	config.state_hash_cache_size = cli.state_hash_cache_size;
+	config.state_cache_child_ratio = cli.state_cache_child_ratio;
+	config.state_cache_adaptive = cli.state_cache_adaptive;
+	config.pruning = parse_pruning_mode(cli.pruning)?;
 
 	let role =
 		if cli.light {
@@ -441,6 +457,10 @@ where
 
 	config.roles = role;
 	config.disable_grandpa = cli.no_grandpa;
+	config.grandpa_observer = cli.grandpa_observer;
+	config.sync_mode = cli.sync.into();
+	config.wasm_fast_instance_reuse = cli.wasm_fast_instance_reuse;
+	config.native_disabled_for = cli.native_disabled_for;
 
 	let is_dev = cli.shared_params.dev;
 
@@ -493,6 +513,7 @@ where
 			"https://substrate-ui.parity.io".into(),
 		])
 	});
+	config.rpc_unsafe_methods = cli.rpc_unsafe_methods;
 
 	// Override telemetry
 	if cli.no_telemetry {
@@ -644,6 +665,96 @@ where
 	service::chain_ops::import_blocks::<F, _, _>(config, exit.into_exit(), file).map_err(Into::into)
 }
 
+fn export_state<F, S>(
+	cli: ExportStateCmd,
+	spec_factory: S,
+	version: &VersionInfo,
+) -> error::Result<()>
+where
+	F: ServiceFactory,
+	S: FnOnce(&str) -> Result<Option<ChainSpec<FactoryGenesis<F>>>, String>,
+{
+	let config = create_config_with_db_path::<F, _>(spec_factory, &cli.shared_params, version)?;
+
+	info!("DB path: {}", config.database_path);
+	let file: Box<Write> = match cli.output {
+		Some(filename) => Box::new(File::create(filename)?),
+		None => Box::new(stdout()),
+	};
+
+	service::chain_ops::export_state::<F, _>(config, file, cli.at.map(As::sa)).map_err(Into::into)
+}
+
+fn import_state<F, S>(
+	cli: ImportStateCmd,
+	spec_factory: S,
+	version: &VersionInfo,
+) -> error::Result<()>
+where
+	F: ServiceFactory,
+	S: FnOnce(&str) -> Result<Option<ChainSpec<FactoryGenesis<F>>>, String>,
+{
+	let mut config = create_config_with_db_path::<F, _>(spec_factory, &cli.shared_params, version)?;
+	config.pruning = parse_pruning_mode(cli.pruning)?;
+
+	let file: Box<Read> = match cli.input {
+		Some(filename) => Box::new(File::open(filename)?),
+		None => Box::new(stdin()),
+	};
+
+	service::chain_ops::import_state::<F, _>(config, file).map_err(Into::into)
+}
+
+fn replay_block<F, S>(
+	cli: ReplayBlockCmd,
+	spec_factory: S,
+	version: &VersionInfo,
+) -> error::Result<()>
+where
+	F: ServiceFactory,
+	S: FnOnce(&str) -> Result<Option<ChainSpec<FactoryGenesis<F>>>, String>,
+{
+	let config = create_config_with_db_path::<F, _>(spec_factory, &cli.shared_params, version)?;
+
+	let block = match cli.block.parse::<u64>() {
+		Ok(number) => BlockId::number(As::sa(number)),
+		Err(_) => BlockId::hash(H256::from_str(cli.block.trim_start_matches("0x"))
+			.map_err(|_| input_err("Invalid block hash or number specified"))?),
+	};
+
+	if let Some(filename) = cli.trace {
+		service::chain_ops::trace_block::<F, _>(config.clone(), File::create(filename)?, block)?;
+	}
+
+	let file: Box<Write> = match cli.output {
+		Some(filename) => Box::new(File::create(filename)?),
+		None => Box::new(stdout()),
+	};
+
+	service::chain_ops::replay_block::<F, _>(config, file, block, cli.execution.into())
+		.map_err(Into::into)
+}
+
+fn benchmark<F, S>(
+	cli: BenchmarkCmd,
+	spec_factory: S,
+	version: &VersionInfo,
+) -> error::Result<()>
+where
+	F: ServiceFactory,
+	S: FnOnce(&str) -> Result<Option<ChainSpec<FactoryGenesis<F>>>, String>,
+{
+	let config = create_config_with_db_path::<F, _>(spec_factory, &cli.shared_params, version)?;
+
+	let file: Box<Write> = match cli.output {
+		Some(filename) => Box::new(File::create(filename)?),
+		None => Box::new(stdout()),
+	};
+
+	service::chain_ops::benchmark::<F, _>(config, file, cli.module, cli.case, cli.steps, cli.repeat)
+		.map_err(Into::into)
+}
+
 fn revert_chain<F, S>(
 	cli: RevertCmd,
 	spec_factory: S,
@@ -19,6 +19,7 @@ use crate::traits::{AugmentClap, GetLogFilter};
 use std::path::PathBuf;
 use structopt::{StructOpt, clap::{arg_enum, _clap_count_exprs, App, AppSettings, SubCommand, Arg}};
 use client;
+use network;
 
 /// Auxiliary macro to implement `GetLogFilter` for all types that have the `shared_params` field.
 macro_rules! impl_get_log_filter {
@@ -40,6 +41,7 @@ arg_enum! {
 		Both,
 		NativeElseWasm,
 		NativeWhenPossible,
+		Shadow,
 	}
 }
 
@@ -51,6 +53,7 @@ impl Into<client::ExecutionStrategy> for ExecutionStrategy {
 			ExecutionStrategy::Both => client::ExecutionStrategy::Both,
 			ExecutionStrategy::NativeElseWasm => client::ExecutionStrategy::NativeElseWasm,
 			ExecutionStrategy::NativeWhenPossible => client::ExecutionStrategy::NativeWhenPossible,
+			ExecutionStrategy::Shadow => client::ExecutionStrategy::Shadow,
 		}
 	}
 }
@@ -65,6 +68,26 @@ arg_enum! {
 	}
 }
 
+arg_enum! {
+	/// How to synchronize the chain
+	#[derive(Debug, Clone)]
+	pub enum SyncMode {
+		Full,
+		Warp,
+		WarpNoHistory,
+	}
+}
+
+impl Into<network::config::SyncMode> for SyncMode {
+	fn into(self) -> network::config::SyncMode {
+		match self {
+			SyncMode::Full => network::config::SyncMode::Full,
+			SyncMode::Warp => network::config::SyncMode::Warp { backfill: true },
+			SyncMode::WarpNoHistory => network::config::SyncMode::Warp { backfill: false },
+		}
+	}
+}
+
 /// Shared parameters used by all `CoreParams`.
 #[derive(Debug, StructOpt, Clone)]
 pub struct SharedParams {
@@ -301,10 +324,17 @@ pub struct RunCmd {
 	#[structopt(long = "validator")]
 	pub validator: bool,
 
-	/// Disable GRANDPA when running in validator mode
+	/// Disable GRANDPA voter when running in validator mode, otherwise disables the GRANDPA observer
 	#[structopt(long = "no-grandpa")]
 	pub no_grandpa: bool,
 
+	/// Run GRANDPA as a non-voting observer, even when running in validator mode.
+	///
+	/// The observer validates commits, stores justifications for authority set changes
+	/// and periodically, and relays the voters' messages.
+	#[structopt(long = "grandpa-observer", conflicts_with = "no_grandpa")]
+	pub grandpa_observer: bool,
+
 	/// Experimental: Run in light client mode
 	#[structopt(long = "light")]
 	pub light: bool,
@@ -317,6 +347,18 @@ pub struct RunCmd {
 	#[structopt(long = "state-cache-size", value_name = "Bytes", default_value = "67108864")]
 	pub state_cache_size: usize,
 
+	/// Specify the storage hashes cache size
+	#[structopt(long = "state-hash-cache-size", value_name = "Bytes", default_value = "16777216")]
+	pub state_hash_cache_size: usize,
+
+	/// Reserve given percentage of the state cache for the child storage (not cached by default)
+	#[structopt(long = "state-cache-child-ratio", value_name = "PERCENT")]
+	pub state_cache_child_ratio: Option<usize>,
+
+	/// Adapt the split of the state cache between the per-block and the shared caches to the actual usage
+	#[structopt(long = "state-cache-adaptive")]
+	pub state_cache_adaptive: bool,
+
 	/// Listen to all RPC interfaces (default is local)
 	#[structopt(long = "rpc-external")]
 	pub rpc_external: bool,
@@ -341,6 +383,12 @@ pub struct RunCmd {
 	#[structopt(long = "rpc-cors", value_name = "ORIGINS", parse(try_from_str = "parse_cors"))]
 	pub rpc_cors: Option<Option<Vec<String>>>,
 
+	/// Expose RPC methods that are expensive, reveal node internals or control block authorship,
+	/// such as `state_traceBlock` and `engine_createBlock`.
+	/// Only use it on nodes whose RPC interfaces are not exposed publicly.
+	#[structopt(long = "rpc-unsafe-methods")]
+	pub rpc_unsafe_methods: bool,
+
 	/// Specify the pruning mode, a number of blocks to keep or 'archive'. Default is 256.
 	#[structopt(long = "pruning", value_name = "PRUNING_MODE")]
 	pub pruning: Option<String>,
@@ -372,6 +420,31 @@ pub struct RunCmd {
 	)]
 	pub offchain_worker: OffchainWorkerEnabled,
 
+	/// How to synchronize the chain. `Warp` downloads the state of the latest finalized block
+	/// and then the preceding blocks, `WarpNoHistory` skips the preceding blocks. Warp sync only
+	/// applies to an empty database.
+	#[structopt(
+		long = "sync",
+		value_name = "MODE",
+		raw(
+			possible_values = "&SyncMode::variants()",
+			case_insensitive = "true",
+			default_value = r#""Full""#
+		)
+	)]
+	pub sync: SyncMode,
+
+	/// Reuse the instance of the runtime Wasm code between calls, restoring only the memory
+	/// changed by the previous call instead of instantiating the code again.
+	#[structopt(long = "wasm-fast-instance-reuse")]
+	pub wasm_fast_instance_reuse: bool,
+
+	/// Never execute the runtime with the given spec version natively. May be given several times.
+	/// Runtimes whose native execution diverges from wasm with the `Shadow` strategy are never
+	/// executed natively again until the node is restarted.
+	#[structopt(long = "disable-native-for", value_name = "SPEC_VERSION")]
+	pub native_disabled_for: Vec<u32>,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub execution_strategies: ExecutionStrategies,
@@ -564,6 +637,108 @@ pub struct ImportBlocksCmd {
 
 impl_get_log_filter!(ImportBlocksCmd);
 
+/// The `export-state` command used to export the state at a finalized block.
+#[derive(Debug, StructOpt, Clone)]
+pub struct ExportStateCmd {
+	/// Output file name or stdout if unspecified.
+	#[structopt(parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	/// Specify the block number to export the state at. Last finalized block by default.
+	#[structopt(long = "at", value_name = "BLOCK")]
+	pub at: Option<u64>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl_get_log_filter!(ExportStateCmd);
+
+/// The `import-state` command used to start an empty database from a state snapshot.
+#[derive(Debug, StructOpt, Clone)]
+pub struct ImportStateCmd {
+	/// Input file or stdin if unspecified.
+	#[structopt(parse(from_os_str))]
+	pub input: Option<PathBuf>,
+
+	/// Specify the state pruning mode, a number of blocks to keep or 'archive'. Default is 256.
+	#[structopt(long = "pruning", value_name = "PRUNING_MODE")]
+	pub pruning: Option<String>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl_get_log_filter!(ImportStateCmd);
+
+/// The `replay-block` command used to re-execute a block and print the storage changes it makes.
+#[derive(Debug, StructOpt, Clone)]
+pub struct ReplayBlockCmd {
+	/// Hash or number of the block to replay.
+	pub block: String,
+
+	/// The means of execution used to replay the block. `Both` compares native and wasm executions.
+	#[structopt(
+		long = "execution",
+		value_name = "STRATEGY",
+		raw(
+			possible_values = "&ExecutionStrategy::variants()",
+			case_insensitive = "true",
+			default_value = r#""Both""#
+		)
+	)]
+	pub execution: ExecutionStrategy,
+
+	/// Output file name or stdout if unspecified.
+	#[structopt(long = "output", parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	/// Also re-execute the block in Wasm with tracing and write the calls to the host functions
+	/// and the storage accesses of every extrinsic to this file as JSON.
+	#[structopt(long = "trace", value_name = "FILE", parse(from_os_str))]
+	pub trace: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl_get_log_filter!(ReplayBlockCmd);
+
+/// The `benchmark` command used to measure the dispatchable calls of the runtime.
+///
+/// The runtime of the chain has to be built with its `runtime-benchmarks` feature.
+#[derive(Debug, StructOpt, Clone)]
+pub struct BenchmarkCmd {
+	/// Only run the benchmark cases of this module.
+	#[structopt(long = "module", value_name = "MODULE")]
+	pub module: Option<String>,
+
+	/// Only run the benchmark cases with this name.
+	#[structopt(long = "case", value_name = "CASE")]
+	pub case: Option<String>,
+
+	/// Number of values every parameter of a case is measured at.
+	#[structopt(long = "steps", value_name = "COUNT", default_value = "10")]
+	pub steps: u32,
+
+	/// Number of measurements at every combination of the parameter values.
+	#[structopt(long = "repeat", value_name = "COUNT", default_value = "5")]
+	pub repeat: u32,
+
+	/// Output file name for the generated weights or stdout if unspecified.
+	#[structopt(long = "output", parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl_get_log_filter!(BenchmarkCmd);
+
 /// The `revert` command used revert the chain to a previous state.
 #[derive(Debug, StructOpt, Clone)]
 pub struct RevertCmd {
@@ -611,6 +786,18 @@ pub enum CoreParams<CC, RP> {
 	/// Import blocks from file.
 	ImportBlocks(ImportBlocksCmd),
 
+	/// Export the state at a finalized block to a file.
+	ExportState(ExportStateCmd),
+
+	/// Initialize the database from a state snapshot file.
+	ImportState(ImportStateCmd),
+
+	/// Re-execute a block and print the storage changes it makes.
+	ReplayBlock(ReplayBlockCmd),
+
+	/// Benchmark the dispatchable calls of the runtime and print their weights.
+	Benchmark(BenchmarkCmd),
+
 	/// Revert chain to the previous state.
 	Revert(RevertCmd),
 
@@ -642,6 +829,22 @@ impl<CC, RP> StructOpt for CoreParams<CC, RP> where
 			ImportBlocksCmd::augment_clap(SubCommand::with_name("import-blocks"))
 				.about("Import blocks from file.")
 		)
+		.subcommand(
+			ExportStateCmd::augment_clap(SubCommand::with_name("export-state"))
+				.about("Export the state at a finalized block to a file.")
+		)
+		.subcommand(
+			ImportStateCmd::augment_clap(SubCommand::with_name("import-state"))
+				.about("Initialize the database from a state snapshot file.")
+		)
+		.subcommand(
+			ReplayBlockCmd::augment_clap(SubCommand::with_name("replay-block"))
+				.about("Re-execute a block and print the storage changes it makes.")
+		)
+		.subcommand(
+			BenchmarkCmd::augment_clap(SubCommand::with_name("benchmark"))
+				.about("Benchmark the dispatchable calls of the runtime and print their weights.")
+		)
 		.subcommand(
 			RevertCmd::augment_clap(SubCommand::with_name("revert"))
 				.about("Revert chain to the previous state.")
@@ -660,6 +863,14 @@ impl<CC, RP> StructOpt for CoreParams<CC, RP> where
 				CoreParams::ExportBlocks(ExportBlocksCmd::from_clap(matches)),
 			("import-blocks", Some(matches)) =>
 				CoreParams::ImportBlocks(ImportBlocksCmd::from_clap(matches)),
+			("export-state", Some(matches)) =>
+				CoreParams::ExportState(ExportStateCmd::from_clap(matches)),
+			("import-state", Some(matches)) =>
+				CoreParams::ImportState(ImportStateCmd::from_clap(matches)),
+			("replay-block", Some(matches)) =>
+				CoreParams::ReplayBlock(ReplayBlockCmd::from_clap(matches)),
+			("benchmark", Some(matches)) =>
+				CoreParams::Benchmark(BenchmarkCmd::from_clap(matches)),
 			("revert", Some(matches)) => CoreParams::Revert(RevertCmd::from_clap(matches)),
 			("purge-chain", Some(matches)) =>
 				CoreParams::PurgeChain(PurgeChainCmd::from_clap(matches)),
@@ -676,6 +887,10 @@ impl<CC, RP> GetLogFilter for CoreParams<CC, RP> where CC: GetLogFilter {
 			CoreParams::BuildSpec(c) => c.get_log_filter(),
 			CoreParams::ExportBlocks(c) => c.get_log_filter(),
 			CoreParams::ImportBlocks(c) => c.get_log_filter(),
+			CoreParams::ExportState(c) => c.get_log_filter(),
+			CoreParams::ImportState(c) => c.get_log_filter(),
+			CoreParams::ReplayBlock(c) => c.get_log_filter(),
+			CoreParams::Benchmark(c) => c.get_log_filter(),
 			CoreParams::PurgeChain(c) => c.get_log_filter(),
 			CoreParams::Revert(c) => c.get_log_filter(),
 			CoreParams::Custom(c) => c.get_log_filter(),
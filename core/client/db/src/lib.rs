@@ -44,7 +44,7 @@ use hash_db::Hasher;
 // kv DB
 use kvdb::{KeyValueDB, DBTransaction};
 use trie::{MemoryDB, PrefixedMemoryDB, prefixed_key};
-use parking_lot::{Mutex, RwLock};
+use parking_lot::RwLock;
 use primitives::{H256, Blake2Hasher, ChangesTrieConfiguration, convert_hash};
 use primitives::storage::well_known_keys;
 use runtime_primitives::{generic::BlockId, Justification, StorageOverlay, ChildrenStorageOverlay};
@@ -58,7 +58,8 @@ use client::leaves::{LeafSet, FinalizationDisplaced};
 use client::children;
 use state_db::StateDb;
 use consensus_common::well_known_cache_keys;
-use crate::storage_cache::{CachingState, SharedCache, new_shared_cache};
+use crate::storage_cache::{CachingState, SharedCache, CacheOptions, new_shared_cache};
+pub use crate::storage_cache::DEFAULT_HASHES_CACHE_SIZE;
 use log::{trace, debug, warn};
 pub use state_db::PruningMode;
 
@@ -77,6 +78,13 @@ pub struct DatabaseSettings {
 	pub cache_size: Option<usize>,
 	/// State cache size.
 	pub state_cache_size: usize,
+	/// Storage hashes cache size, in bytes.
+	pub state_hash_cache_size: usize,
+	/// Percentage of the state cache reserved for the child storage.
+	/// `None` disables child storage caching.
+	pub state_cache_child_ratio: Option<usize>,
+	/// Adapt the split of the state cache between the local and the shared caches.
+	pub state_cache_adaptive: bool,
 	/// Path to the database.
 	pub path: PathBuf,
 	/// Pruning mode.
@@ -285,11 +293,18 @@ pub struct BlockImportOperation<Block: BlockT, H: Hasher> {
 	old_state: CachingState<Blake2Hasher, DbState, Block>,
 	db_updates: PrefixedMemoryDB<H>,
 	storage_updates: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+	child_storage_updates: Vec<(Vec<u8>, Vec<(Vec<u8>, Option<Vec<u8>>)>)>,
 	changes_trie_updates: MemoryDB<H>,
 	pending_block: Option<PendingBlock<Block>>,
 	aux_ops: Vec<(Vec<u8>, Option<Vec<u8>>)>,
 	finalized_blocks: Vec<(BlockId<Block>, Option<Justification>)>,
 	set_head: Option<BlockId<Block>>,
+	/// Whether the pending block is a new base of the chain (see `mark_state_base`).
+	state_base: bool,
+	/// Whether the pending block precedes the base of the chain (see `mark_history`).
+	history: bool,
+	/// Changes trie configuration that is set by the `reset_storage` call.
+	changes_trie_config_update: Option<Option<ChangesTrieConfiguration>>,
 }
 
 impl<Block: BlockT, H: Hasher> BlockImportOperation<Block, H> {
@@ -355,6 +370,9 @@ where Block: BlockT<Hash=H256>,
 			.map(|(storage_key, child_overlay)|
 				(storage_key, child_overlay.into_iter().map(|(k, v)| (k, Some(v)))));
 
+		self.changes_trie_config_update = Some(top.get(well_known_keys::CHANGES_TRIE_CONFIG)
+			.and_then(|v| Decode::decode(&mut &v[..])));
+
 		let (root, transaction) = self.old_state.full_storage_root(
 			top.into_iter().map(|(k, v)| (k, Some(v))),
 			child_delta
@@ -381,6 +399,11 @@ where Block: BlockT<Hash=H256>,
 		Ok(())
 	}
 
+	fn update_child_storage(&mut self, update: Vec<(Vec<u8>, Vec<(Vec<u8>, Option<Vec<u8>>)>)>) -> Result<(), client::error::Error> {
+		self.child_storage_updates = update;
+		Ok(())
+	}
+
 	fn mark_finalized(&mut self, block: BlockId<Block>, justification: Option<Justification>) -> Result<(), client::error::Error> {
 		self.finalized_blocks.push((block, justification));
 		Ok(())
@@ -391,6 +414,16 @@ where Block: BlockT<Hash=H256>,
 		self.set_head = Some(block);
 		Ok(())
 	}
+
+	fn mark_state_base(&mut self) -> Result<(), client::error::Error> {
+		self.state_base = true;
+		Ok(())
+	}
+
+	fn mark_history(&mut self) -> Result<(), client::error::Error> {
+		self.history = true;
+		Ok(())
+	}
 }
 
 /*
@@ -442,10 +475,17 @@ impl state_machine::Storage<Blake2Hasher> for DbGenesisStorage {
 	}
 }
 
+/// Changes trie configuration changes: (block number, block hash, configuration that is
+/// active starting from the next block).
+type ChangesTrieConfigChanges<Block> = Vec<(u64, <Block as BlockT>::Hash, Option<ChangesTrieConfiguration>)>;
+
 pub struct DbChangesTrieStorage<Block: BlockT> {
 	db: Arc<KeyValueDB>,
 	meta: Arc<RwLock<Meta<NumberFor<Block>, Block::Hash>>>,
 	min_blocks_to_keep: Option<u64>,
+	/// All known configuration changes, ordered by block number. Changes from different forks
+	/// are kept here until they're ignored by range requests.
+	config_changes: RwLock<ChangesTrieConfigChanges<Block>>,
 	_phantom: ::std::marker::PhantomData<Block>,
 }
 
@@ -457,8 +497,37 @@ impl<Block: BlockT> DbChangesTrieStorage<Block> {
 		}
 	}
 
+	/// Record configuration change that happens at given block. The new configuration is
+	/// active starting from the next block. Returns updated list of changes, that must be
+	/// set using `set_config_changes` once the transaction is written.
+	fn note_config_change(
+		&self,
+		tx: &mut DBTransaction,
+		block_num: NumberFor<Block>,
+		block_hash: Block::Hash,
+		config: Option<ChangesTrieConfiguration>,
+	) -> ChangesTrieConfigChanges<Block> {
+		let block_num = block_num.as_();
+		let mut config_changes = self.config_changes.read().clone();
+		let pos = config_changes.iter().position(|(num, _, _)| *num > block_num).unwrap_or(config_changes.len());
+		config_changes.insert(pos, (block_num, block_hash, config));
+		tx.put(columns::META, meta_keys::CHANGES_TRIE_CONFIGS, &config_changes.encode());
+		config_changes
+	}
+
+	/// Replace in-memory list of configuration changes.
+	fn set_config_changes(&self, config_changes: ChangesTrieConfigChanges<Block>) {
+		*self.config_changes.write() = config_changes;
+	}
+
 	/// Prune obsolete changes tries.
-	pub fn prune(&self, config: &ChangesTrieConfiguration, tx: &mut DBTransaction, block_hash: Block::Hash, block_num: NumberFor<Block>) {
+	pub fn prune(
+		&self,
+		config: &state_machine::ChangesTrieConfigurationRange,
+		tx: &mut DBTransaction,
+		block_hash: Block::Hash,
+		block_num: NumberFor<Block>,
+	) {
 		// never prune on archive nodes
 		let min_blocks_to_keep = match self.min_blocks_to_keep {
 			Some(min_blocks_to_keep) => min_blocks_to_keep,
@@ -475,22 +544,73 @@ impl<Block: BlockT> DbChangesTrieStorage<Block> {
 			},
 			|node| tx.delete(columns::CHANGES_TRIE, node.as_ref()));
 	}
+
+	/// Resolve id of the block with given number that is an ancestor of the anchor block.
+	fn block_id(
+		&self,
+		anchor: &state_machine::ChangesTrieAnchorBlockId<H256>,
+		block: u64,
+	) -> Result<BlockId<Block>, String> {
+		if block <= self.meta.read().finalized_number.as_() {
+			// if block is finalized, we could just read canonical hash
+			return Ok(BlockId::Number(As::sa(block)));
+		}
+
+		// the block is not finalized
+		let mut current_num = anchor.number;
+		let mut current_hash: Block::Hash = convert_hash(&anchor.hash);
+		let maybe_anchor_header: Block::Header = utils::require_header::<Block>(
+			&*self.db, columns::KEY_LOOKUP, columns::HEADER, BlockId::Number(As::sa(current_num))
+		).map_err(|e| e.to_string())?;
+		if maybe_anchor_header.hash() == current_hash {
+			// if anchor is canonicalized, then the block is also canonicalized
+			Ok(BlockId::Number(As::sa(block)))
+		} else {
+			// else (block is not finalized + anchor is not canonicalized):
+			// => we should find the required block hash by traversing
+			// back from the anchor to the block with given number
+			while current_num != block {
+				let current_header: Block::Header = utils::require_header::<Block>(
+					&*self.db, columns::KEY_LOOKUP, columns::HEADER, BlockId::Hash(current_hash)
+				).map_err(|e| e.to_string())?;
+
+				current_hash = *current_header.parent_hash();
+				current_num = current_num - 1;
+			}
+
+			Ok(BlockId::Hash(current_hash))
+		}
+	}
 }
 
 impl<Block: BlockT> client::backend::PrunableStateChangesTrieStorage<Blake2Hasher> for DbChangesTrieStorage<Block> {
 	fn oldest_changes_trie_block(
 		&self,
-		config: &ChangesTrieConfiguration,
-		best_finalized_block: u64
-	) -> u64 {
-		match self.min_blocks_to_keep {
-			Some(min_blocks_to_keep) => state_machine::oldest_non_pruned_changes_trie(
-				config,
+		best_finalized: &state_machine::ChangesTrieAnchorBlockId<H256>,
+	) -> Result<u64, client::error::Error> {
+		let min_blocks_to_keep = match self.min_blocks_to_keep {
+			Some(min_blocks_to_keep) => min_blocks_to_keep,
+			None => return Ok(1),
+		};
+
+		let config_ranges = state_machine::ChangesTrieRootsStorage::configuration_ranges(self, best_finalized, 0)
+			.map_err(client::error::Error::ChangesTrieAccessFailed)?;
+		let mut oldest = 1;
+		for config_range in &config_ranges {
+			oldest = state_machine::oldest_non_pruned_changes_trie(
+				config_range,
 				min_blocks_to_keep,
-				best_finalized_block,
-			),
-			None => 1,
+				best_finalized.number,
+			);
+
+			// all tries of this range are pruned => look at the next range
+			match config_range.end {
+				Some(end) if oldest > end => continue,
+				_ => break,
+			}
 		}
+
+		Ok(oldest)
 	}
 }
 
@@ -502,41 +622,43 @@ impl<Block: BlockT> state_machine::ChangesTrieRootsStorage<Blake2Hasher> for DbC
 		}
 
 		// we need to get hash of the block to resolve changes trie root
-		let block_id = if block <= self.meta.read().finalized_number.as_() {
-			// if block is finalized, we could just read canonical hash
-			BlockId::Number(As::sa(block))
-		} else {
-			// the block is not finalized
-			let mut current_num = anchor.number;
-			let mut current_hash: Block::Hash = convert_hash(&anchor.hash);
-			let maybe_anchor_header: Block::Header = utils::require_header::<Block>(
-				&*self.db, columns::KEY_LOOKUP, columns::HEADER, BlockId::Number(As::sa(current_num))
-			).map_err(|e| e.to_string())?;
-			if maybe_anchor_header.hash() == current_hash {
-				// if anchor is canonicalized, then the block is also canonicalized
-				BlockId::Number(As::sa(block))
-			} else {
-				// else (block is not finalized + anchor is not canonicalized):
-				// => we should find the required block hash by traversing
-				// back from the anchor to the block with given number
-				while current_num != block {
-					let current_header: Block::Header = utils::require_header::<Block>(
-						&*self.db, columns::KEY_LOOKUP, columns::HEADER, BlockId::Hash(current_hash)
-					).map_err(|e| e.to_string())?;
-
-					current_hash = *current_header.parent_hash();
-					current_num = current_num - 1;
-				}
-
-				BlockId::Hash(current_hash)
-			}
-		};
-
+		let block_id = self.block_id(anchor, block)?;
 		Ok(utils::require_header::<Block>(&*self.db, columns::KEY_LOOKUP, columns::HEADER, block_id)
 			.map_err(|e| e.to_string())?
 			.digest().log(DigestItem::as_changes_trie_root)
 			.map(|root| H256::from_slice(root.as_ref())))
 	}
+
+	fn configuration_ranges(
+		&self,
+		anchor: &state_machine::ChangesTrieAnchorBlockId<H256>,
+		begin: u64,
+	) -> Result<Vec<state_machine::ChangesTrieConfigurationRange>, String> {
+		let config_changes = self.config_changes.read();
+		let mut anchor_chain_changes = Vec::with_capacity(config_changes.len());
+		for (block_num, block_hash, config) in config_changes.iter() {
+			if *block_num > anchor.number {
+				break;
+			}
+
+			// ignore changes that have happened on other forks
+			let anchor_chain_hash = if *block_num == anchor.number {
+				convert_hash(&anchor.hash)
+			} else {
+				match self.block_id(anchor, *block_num)? {
+					BlockId::Hash(hash) => hash,
+					block_id => utils::require_header::<Block>(
+						&*self.db, columns::KEY_LOOKUP, columns::HEADER, block_id
+					).map_err(|e| e.to_string())?.hash(),
+				}
+			};
+			if anchor_chain_hash == *block_hash {
+				anchor_chain_changes.push((*block_num, config.clone()));
+			}
+		}
+
+		Ok(state_machine::changes_trie_configuration_ranges(anchor_chain_changes, begin))
+	}
 }
 
 impl<Block: BlockT> state_machine::ChangesTrieStorage<Blake2Hasher> for DbChangesTrieStorage<Block> {
@@ -551,9 +673,6 @@ impl<Block: BlockT> state_machine::ChangesTrieStorage<Blake2Hasher> for DbChange
 pub struct Backend<Block: BlockT> {
 	storage: Arc<StorageDb<Block>>,
 	changes_tries_storage: DbChangesTrieStorage<Block>,
-	/// None<*> means that the value hasn't been cached yet. Some(*) means that the value (either None or
-	/// Some(*)) has been cached and is valid.
-	changes_trie_config: Mutex<Option<Option<ChangesTrieConfiguration>>>,
 	blockchain: BlockchainDb<Block>,
 	canonicalization_delay: u64,
 	shared_cache: SharedCache<Block, Blake2Hasher>,
@@ -573,7 +692,12 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 		*/
 		let db = open_database(&config, columns::META, "full")?;
 
-		Backend::from_kvdb(db as Arc<_>, config.pruning, canonicalization_delay, config.state_cache_size)
+		let cache_options = CacheOptions {
+			hashes_size: config.state_hash_cache_size,
+			child_ratio: config.state_cache_child_ratio,
+			adaptive: config.state_cache_adaptive,
+		};
+		Backend::from_kvdb(db as Arc<_>, config.pruning, canonicalization_delay, config.state_cache_size, cache_options)
 	}
 
 	#[cfg(any(test, feature = "test-helpers"))]
@@ -587,10 +711,17 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			PruningMode::keep_blocks(keep_blocks),
 			canonicalization_delay,
 			16777216,
+			Default::default(),
 		).expect("failed to create test-db")
 	}
 
-	fn from_kvdb(db: Arc<KeyValueDB>, pruning: PruningMode, canonicalization_delay: u64, state_cache_size: usize) -> Result<Self, client::error::Error> {
+	fn from_kvdb(
+		db: Arc<KeyValueDB>,
+		pruning: PruningMode,
+		canonicalization_delay: u64,
+		state_cache_size: usize,
+		cache_options: CacheOptions,
+	) -> Result<Self, client::error::Error> {
 		let is_archive_pruning = pruning.is_archive();
 		let blockchain = BlockchainDb::new(db.clone())?;
 		let meta = blockchain.meta.clone();
@@ -602,20 +733,25 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			db: db.clone(),
 			state_db,
 		};
+		let config_changes = match db.get(columns::META, meta_keys::CHANGES_TRIE_CONFIGS).map_err(db_err)? {
+			Some(config_changes) => Decode::decode(&mut &config_changes[..])
+				.ok_or_else(|| client::error::Error::Backend("Error decoding changes trie configurations".into()))?,
+			None => Vec::new(),
+		};
 		let changes_tries_storage = DbChangesTrieStorage {
 			db,
 			meta,
 			min_blocks_to_keep: if is_archive_pruning { None } else { Some(MIN_BLOCKS_TO_KEEP_CHANGES_TRIES_FOR) },
+			config_changes: RwLock::new(config_changes),
 			_phantom: Default::default(),
 		};
 
 		Ok(Backend {
 			storage: Arc::new(storage_db),
 			changes_tries_storage,
-			changes_trie_config: Mutex::new(None),
 			blockchain,
 			canonicalization_delay,
-			shared_cache: new_shared_cache(state_cache_size),
+			shared_cache: new_shared_cache(state_cache_size, cache_options),
 		})
 	}
 
@@ -667,26 +803,6 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 		inmem
 	}
 
-	/// Read (from storage or cache) changes trie config.
-	///
-	/// Currently changes tries configuration is set up once (at genesis) and could not
-	/// be changed. Thus, we'll actually read value once and then just use cached value.
-	fn changes_trie_config(&self, block: Block::Hash) -> Result<Option<ChangesTrieConfiguration>, client::error::Error> {
-		let mut cached_changes_trie_config = self.changes_trie_config.lock();
-		match cached_changes_trie_config.clone() {
-			Some(cached_changes_trie_config) => Ok(cached_changes_trie_config),
-			None => {
-				use client::backend::Backend;
-				let changes_trie_config = self
-					.state_at(BlockId::Hash(block))?
-					.storage(well_known_keys::CHANGES_TRIE_CONFIG)?
-					.and_then(|v| Decode::decode(&mut &*v));
-				*cached_changes_trie_config = Some(changes_trie_config.clone());
-				Ok(changes_trie_config)
-			},
-		}
-	}
-
 	/// Handle setting head within a transaction. `route_to` should be the last
 	/// block that existed in the database. `best_to` should be the best block
 	/// to be set.
@@ -857,6 +973,26 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			}
 		}
 
+		if operation.history {
+			// history blocks have no state and don't affect the head of the chain.
+			if let Some(pending_block) = operation.pending_block.take() {
+				let hash = pending_block.header.hash();
+				let number = pending_block.header.number().clone();
+				let lookup_key = utils::number_and_hash_to_lookup_key(number, hash);
+
+				utils::insert_hash_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash);
+				utils::insert_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash);
+				transaction.put(columns::HEADER, &lookup_key, &pending_block.header.encode());
+				if let Some(body) = pending_block.body {
+					transaction.put(columns::BODY, &lookup_key, &body.encode());
+				}
+				if let Some(justification) = pending_block.justification {
+					transaction.put(columns::JUSTIFICATION, &lookup_key, &justification.encode());
+				}
+			}
+		}
+
+		let mut changes_trie_config_changes = None;
 		let imported = if let Some(pending_block) = operation.pending_block {
 			let hash = pending_block.header.hash();
 			let parent_hash = *pending_block.header.parent_hash();
@@ -865,7 +1001,12 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			// blocks are keyed by number + hash.
 			let lookup_key = utils::number_and_hash_to_lookup_key(number, hash);
 
-			let (enacted, retracted) = if pending_block.leaf_state.is_best() {
+			let (enacted, retracted) = if operation.state_base {
+				// there's no route from the current best block to the new base of the chain.
+				transaction.put(columns::META, meta_keys::BEST_BLOCK, &lookup_key);
+				utils::insert_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash);
+				(Default::default(), Default::default())
+			} else if pending_block.leaf_state.is_best() {
 				self.set_head_with_transaction(&mut transaction, parent_hash, (number, hash))?
 			} else {
 				(Default::default(), Default::default())
@@ -900,8 +1041,11 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 				}
 			}
 			let number_u64 = number.as_();
-			let commit = self.storage.state_db.insert_block(&hash, number_u64, &pending_block.header.parent_hash(), changeset)
-				.map_err(|e: state_db::Error<io::Error>| client::error::Error::from(format!("State database error: {:?}", e)))?;
+			let commit = if operation.state_base {
+				self.storage.state_db.insert_base_block(&hash, number_u64, &pending_block.header.parent_hash(), changeset)
+			} else {
+				self.storage.state_db.insert_block(&hash, number_u64, &pending_block.header.parent_hash(), changeset)
+			}.map_err(|e: state_db::Error<io::Error>| client::error::Error::from(format!("State database error: {:?}", e)))?;
 			apply_state_commit(&mut transaction, commit);
 
 			// Check if need to finalize. Genesis is always finalized instantly.
@@ -913,9 +1057,28 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 
 			self.changes_tries_storage.commit(&mut transaction, changes_trie_updates);
 
+			// configuration is either set directly (genesis or base of the chain) or is signalled by
+			// the runtime. In both cases it is active starting from the next block
+			let changes_trie_config_update = operation.changes_trie_config_update.take().or_else(|| header
+				.digest()
+				.log(DigestItem::as_changes_trie_signal)
+				.and_then(|signal| signal.as_new_configuration())
+				.cloned());
+			if let Some(changes_trie_config) = changes_trie_config_update {
+				changes_trie_config_changes = Some(self.changes_tries_storage.note_config_change(
+					&mut transaction,
+					number,
+					hash,
+					changes_trie_config,
+				));
+			}
+
 			if finalized {
 				// TODO: ensure best chain contains this block.
-				self.ensure_sequential_finalization(header, Some(last_finalized_hash))?;
+				// the base of the chain has no parent in the database.
+				if !operation.state_base {
+					self.ensure_sequential_finalization(header, Some(last_finalized_hash))?;
+				}
 				self.note_finalized(
 					&mut transaction,
 					header,
@@ -984,12 +1147,17 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 				&enacted,
 				&retracted,
 				operation.storage_updates,
+				operation.child_storage_updates,
 				Some(hash),
 				Some(number),
 				|| is_best,
 			);
 		}
 
+		if let Some(changes_trie_config_changes) = changes_trie_config_changes {
+			self.changes_tries_storage.set_config_changes(changes_trie_config_changes);
+		}
+
 		for (hash, number, is_best, is_finalized) in meta_updates {
 			self.blockchain.update_meta(hash, number, is_best, is_finalized);
 		}
@@ -1022,9 +1190,22 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 				.map_err(|e: state_db::Error<io::Error>| client::error::Error::from(format!("State database error: {:?}", e)))?;
 			apply_state_commit(transaction, commit);
 
-			let changes_trie_config = self.changes_trie_config(parent_hash)?;
-			if let Some(changes_trie_config) = changes_trie_config {
-				self.changes_tries_storage.prune(&changes_trie_config, transaction, f_hash, f_num);
+			// there's nothing to prune before the base of the chain.
+			let is_state_base = !f_num.is_zero()
+				&& self.blockchain.header(BlockId::Hash(parent_hash))?.is_none();
+			if !f_num.is_zero() && !is_state_base {
+				let parent_anchor = state_machine::ChangesTrieAnchorBlockId {
+					hash: convert_hash(&parent_hash),
+					number: f_num.as_() - 1,
+				};
+				let changes_trie_config_ranges = state_machine::ChangesTrieRootsStorage::configuration_ranges(
+					&self.changes_tries_storage,
+					&parent_anchor,
+					0,
+				).map_err(client::error::Error::ChangesTrieAccessFailed)?;
+				for changes_trie_config_range in &changes_trie_config_ranges {
+					self.changes_tries_storage.prune(changes_trie_config_range, transaction, f_hash, f_num);
+				}
 			}
 		}
 
@@ -1090,10 +1271,14 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 			old_state,
 			db_updates: PrefixedMemoryDB::default(),
 			storage_updates: Default::default(),
+			child_storage_updates: Default::default(),
 			changes_trie_updates: MemoryDB::default(),
 			aux_ops: Vec::new(),
 			finalized_blocks: Vec::new(),
 			set_head: None,
+			state_base: false,
+			history: false,
+			changes_trie_config_update: None,
 		})
 	}
 
@@ -1200,6 +1385,10 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 		Some(used)
 	}
 
+	fn state_cache_stats(&self) -> Option<client::backend::StateCacheStats> {
+		Some(self.shared_cache.lock().stats())
+	}
+
 	fn state_at(&self, block: BlockId<Block>) -> Result<Self::State, client::error::Error> {
 		use client::blockchain::HeaderBackend as BcHeaderBackend;
 
@@ -1237,7 +1426,7 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 	fn destroy_state(&self, mut state: Self::State) -> Result<(), client::error::Error> {
 		if let Some(hash) = state.parent_hash.clone() {
 			let is_best = || self.blockchain.meta.read().best_hash == hash;
-			state.sync_cache(&[], &[], vec![], None, None, is_best);
+			state.sync_cache(&[], &[], vec![], vec![], None, None, is_best);
 		}
 		Ok(())
 	}
@@ -1256,7 +1445,9 @@ mod tests {
 	use client::backend::BlockImportOperation as Op;
 	use runtime_primitives::testing::{Header, Block as RawBlock, ExtrinsicWrapper};
 	use runtime_primitives::traits::{Hash, BlakeTwo256};
-	use state_machine::{TrieMut, TrieDBMut, ChangesTrieRootsStorage, ChangesTrieStorage};
+	use state_machine::{
+		TrieMut, TrieDBMut, ChangesTrieRootsStorage, ChangesTrieStorage, ChangesTrieConfigurationRange,
+	};
 	use test_client;
 
 	type Block = RawBlock<ExtrinsicWrapper<u64>>;
@@ -1284,15 +1475,31 @@ mod tests {
 		changes: Vec<(Vec<u8>, Vec<u8>)>,
 		extrinsics_root: H256,
 	) -> H256 {
-		use runtime_primitives::generic::DigestItem;
+		insert_header_with_configuration_change(backend, number, parent_hash, changes, extrinsics_root, None)
+	}
+
+	fn insert_header_with_configuration_change(
+		backend: &Backend<Block>,
+		number: u64,
+		parent_hash: H256,
+		changes: Vec<(Vec<u8>, Vec<u8>)>,
+		extrinsics_root: H256,
+		changes_trie_config_update: Option<Option<ChangesTrieConfiguration>>,
+	) -> H256 {
+		use runtime_primitives::generic::{DigestItem, ChangesTrieSignal};
 		use runtime_primitives::testing::Digest;
 
 		let (changes_root, changes_trie_update) = prepare_changes(changes);
-		let digest = Digest {
+		let mut digest = Digest {
 			logs: vec![
 				DigestItem::ChangesTrieRoot(changes_root),
 			],
 		};
+		if let Some(changes_trie_config_update) = changes_trie_config_update {
+			digest.logs.push(DigestItem::ChangesTrieSignal(
+				ChangesTrieSignal::NewConfiguration(changes_trie_config_update)
+			));
+		}
 		let header = Header {
 			number,
 			parent_hash,
@@ -1358,7 +1565,7 @@ mod tests {
 			db.storage.db.clone()
 		};
 
-		let backend = Backend::<Block>::from_kvdb(backing, PruningMode::keep_blocks(1), 0, 16777216).unwrap();
+		let backend = Backend::<Block>::from_kvdb(backing, PruningMode::keep_blocks(1), 0, 16777216, Default::default()).unwrap();
 		assert_eq!(backend.blockchain().info().unwrap().best_number, 9);
 		for i in 0..10 {
 			assert!(backend.blockchain().hash(i).unwrap().is_some())
@@ -1687,13 +1894,72 @@ mod tests {
 		assert_eq!(backend.changes_tries_storage.root(&anchor, 3), Ok(Some(changes2_2_0_root)));
 	}
 
+	#[test]
+	fn changes_trie_configuration_ranges_are_resolved_with_forks() {
+		let backend = Backend::<Block>::new_test(1000, 100);
+		let config1 = ChangesTrieConfiguration { digest_interval: 4, digest_levels: 2 };
+		let config2 = ChangesTrieConfiguration { digest_interval: 8, digest_levels: 1 };
+
+		let block0 = insert_header(&backend, 0, Default::default(), Vec::new(), Default::default());
+		let block1 = insert_header_with_configuration_change(
+			&backend, 1, block0, Vec::new(), Default::default(), Some(Some(config1.clone())),
+		);
+		let block2 = insert_header(&backend, 2, block1, Vec::new(), Default::default());
+
+		// branch1: changes tries are disabled at block 3
+		let block2_1_0 = insert_header_with_configuration_change(
+			&backend, 3, block2, Vec::new(), Default::default(), Some(None),
+		);
+		let block2_1_1 = insert_header(&backend, 4, block2_1_0, Vec::new(), Default::default());
+
+		// branch2: changes tries configuration is changed at block 3
+		let block2_2_0 = insert_header_with_configuration_change(
+			&backend, 3, block2, Vec::new(), Default::default(), Some(Some(config2.clone())),
+		);
+		let block2_2_1 = insert_header(&backend, 4, block2_2_0, Vec::new(), Default::default());
+
+		let range1 = |end| ChangesTrieConfigurationRange { config: config1.clone(), zero: 1, end };
+		let range2 = ChangesTrieConfigurationRange { config: config2.clone(), zero: 3, end: None };
+
+		let check = |backend: &Backend<Block>| {
+			let anchor = state_machine::ChangesTrieAnchorBlockId { hash: block2, number: 2 };
+			assert_eq!(backend.changes_tries_storage.configuration_ranges(&anchor, 0), Ok(vec![range1(None)]));
+
+			let anchor = state_machine::ChangesTrieAnchorBlockId { hash: block2_1_1, number: 4 };
+			assert_eq!(backend.changes_tries_storage.configuration_ranges(&anchor, 0), Ok(vec![range1(Some(3))]));
+
+			let anchor = state_machine::ChangesTrieAnchorBlockId { hash: block2_2_1, number: 4 };
+			assert_eq!(
+				backend.changes_tries_storage.configuration_ranges(&anchor, 0),
+				Ok(vec![range1(Some(3)), range2.clone()]),
+			);
+			assert_eq!(backend.changes_tries_storage.configuration_ranges(&anchor, 4), Ok(vec![range2.clone()]));
+		};
+
+		check(&backend);
+
+		// configuration changes are persisted
+		let backend = Backend::<Block>::from_kvdb(
+			backend.storage.db.clone(),
+			PruningMode::keep_blocks(1000),
+			100,
+			16777216,
+			Default::default(),
+		).unwrap();
+		check(&backend);
+	}
+
 	#[test]
 	fn changes_tries_with_digest_are_pruned_on_finalization() {
 		let mut backend = Backend::<Block>::new_test(1000, 100);
 		backend.changes_tries_storage.min_blocks_to_keep = Some(8);
-		let config = ChangesTrieConfiguration {
-			digest_interval: 2,
-			digest_levels: 2,
+		let config = ChangesTrieConfigurationRange {
+			config: ChangesTrieConfiguration {
+				digest_interval: 2,
+				digest_levels: 2,
+			},
+			zero: 0,
+			end: None,
 		};
 
 		// insert some blocks
@@ -1770,9 +2036,13 @@ mod tests {
 	fn changes_tries_without_digest_are_pruned_on_finalization() {
 		let mut backend = Backend::<Block>::new_test(1000, 100);
 		backend.changes_tries_storage.min_blocks_to_keep = Some(4);
-		let config = ChangesTrieConfiguration {
-			digest_interval: 0,
-			digest_levels: 0,
+		let config = ChangesTrieConfigurationRange {
+			config: ChangesTrieConfiguration {
+				digest_interval: 0,
+				digest_levels: 0,
+			},
+			zero: 0,
+			end: None,
 		};
 
 		// insert some blocks
@@ -1996,4 +2266,48 @@ mod tests {
 			backend.commit_operation(op).unwrap_err();
 		}
 	}
+
+	#[test]
+	fn state_base_block_starts_the_chain() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		let genesis_hash = insert_header(&backend, 0, Default::default(), Default::default(), Default::default());
+
+		let top: StorageOverlay = vec![
+			(vec![1, 3, 5], vec![2, 4, 6]),
+			(vec![1, 2, 3], vec![9, 9, 9]),
+		].into_iter().collect();
+		let header = Header {
+			number: 10,
+			parent_hash: H256::repeat_byte(1),
+			state_root: BlakeTwo256::trie_root::<_, &[u8], &[u8]>(
+				top.iter().map(|(k, v)| (&k[..], &v[..]))
+			),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let hash = header.hash();
+		{
+			let mut op = backend.begin_operation().unwrap();
+			op.reset_storage(top, Default::default()).unwrap();
+			op.set_block_data(header, None, Some(vec![1, 2, 3]), NewBlockState::Final).unwrap();
+			op.mark_state_base().unwrap();
+			backend.commit_operation(op).unwrap();
+		}
+
+		let info = backend.blockchain().info().unwrap();
+		assert_eq!(info.best_hash, hash);
+		assert_eq!(info.best_number, 10);
+		assert_eq!(info.finalized_hash, hash);
+		assert_eq!(info.genesis_hash, genesis_hash);
+		assert_eq!(backend.blockchain().justification(BlockId::Hash(hash)).unwrap(), Some(vec![1, 2, 3]));
+		assert_eq!(
+			backend.state_at(BlockId::Hash(hash)).unwrap().storage(&[1, 3, 5]).unwrap(),
+			Some(vec![2, 4, 6]),
+		);
+
+		// the chain continues from the base block
+		let child = insert_header(&backend, 11, hash, Default::default(), Default::default());
+		backend.finalize_block(BlockId::Hash(child), None).unwrap();
+		assert_eq!(backend.blockchain().info().unwrap().finalized_hash, child);
+	}
 }
@@ -17,62 +17,235 @@
 //! Global cache state.
 
 use std::collections::{VecDeque, HashSet, HashMap};
+use std::borrow::Borrow;
+use std::hash::Hash;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use parking_lot::{Mutex, RwLock, RwLockUpgradableReadGuard};
 use lru_cache::LruCache;
 use hash_db::Hasher;
 use runtime_primitives::traits::{Block, Header};
 use state_machine::{backend::Backend as StateBackend, TrieBackend};
+use client::backend::{CacheCounters, StateCacheStats};
 use log::trace;
 
 const STATE_CACHE_BLOCKS: usize = 12;
 
+/// Default size of the storage hashes cache, in bytes.
+pub const DEFAULT_HASHES_CACHE_SIZE: usize = 16 * 1024 * 1024;
+
 type StorageKey = Vec<u8>;
 type StorageValue = Vec<u8>;
+type ChildStorageKey = (Vec<u8>, Vec<u8>);
+
+/// Options of the shared state cache.
+#[derive(Debug, Clone)]
+pub struct CacheOptions {
+	/// Size of the storage hashes cache, in bytes.
+	pub hashes_size: usize,
+	/// Percentage of the state cache size reserved for the child storage.
+	/// `None` disables child storage caching.
+	pub child_ratio: Option<usize>,
+	/// Adapt the size of the local caches to their actual usage during block import,
+	/// giving the rest of the capacity to the shared cache.
+	pub adaptive: bool,
+}
+
+impl Default for CacheOptions {
+	fn default() -> Self {
+		CacheOptions {
+			hashes_size: DEFAULT_HASHES_CACHE_SIZE,
+			child_ratio: None,
+			adaptive: false,
+		}
+	}
+}
+
+/// LRU cache limited by the total size of its entries.
+struct LimitedLru<K: Eq + Hash, V> {
+	entries: LruCache<K, V>,
+	/// Maximum size of the entries, in bytes.
+	limit: usize,
+	/// Used size of the entries, in bytes.
+	used_size: usize,
+	entry_size: fn(&K, &V) -> usize,
+}
+
+impl<K: Eq + Hash, V> LimitedLru<K, V> {
+	fn new(limit: usize, entry_size: fn(&K, &V) -> usize) -> Self {
+		// we need to supply a max capacity to `LruCache`, but since
+		// we don't have any idea how large the size of each item
+		// that is stored will be we can't calculate the max amount
+		// of items properly from `limit`.
+		//
+		// what we do instead is to supply `limit` as the max upper
+		// bound capacity (this would only be reached if each item
+		// would be one byte).
+		// each time we store to the cache we verify the memory
+		// constraint and pop the lru item if space needs to be freed.
+		LimitedLru {
+			entries: LruCache::new(::std::cmp::max(limit, 1)),
+			limit,
+			used_size: 0,
+			entry_size,
+		}
+	}
+
+	fn get<Q: ?Sized + Eq + Hash>(&mut self, k: &Q) -> Option<&mut V> where K: Borrow<Q> {
+		self.entries.get_mut(k)
+	}
+
+	/// Insert new entry, returning the number of evicted entries.
+	/// Entries larger than the whole cache are not inserted.
+	fn insert(&mut self, k: K, v: V) -> u64 {
+		self.remove(&k);
+		let size = (self.entry_size)(&k, &v);
+		if size > self.limit {
+			return 0;
+		}
+		let mut evicted = 0;
+		while self.used_size + size > self.limit || self.entries.len() >= self.entries.capacity() {
+			// pop until space constraint satisfied
+			match self.entries.remove_lru() {
+				Some((popped_k, popped_v)) => {
+					self.used_size -= (self.entry_size)(&popped_k, &popped_v);
+					evicted += 1;
+				},
+				None => break,
+			}
+		}
+		self.used_size += size;
+		self.entries.insert(k, v);
+		evicted
+	}
+
+	fn remove(&mut self, k: &K) {
+		if let Some(v) = self.entries.remove(k) {
+			self.used_size -= (self.entry_size)(k, &v);
+		}
+	}
+
+	/// Change the size limit, returning the number of evicted entries.
+	fn set_limit(&mut self, limit: usize) -> u64 {
+		self.limit = limit;
+		let mut evicted = 0;
+		while self.used_size > self.limit {
+			match self.entries.remove_lru() {
+				Some((popped_k, popped_v)) => {
+					self.used_size -= (self.entry_size)(&popped_k, &popped_v);
+					evicted += 1;
+				},
+				None => break,
+			}
+		}
+		evicted
+	}
+
+	fn clear(&mut self) {
+		self.entries.clear();
+		self.used_size = 0;
+	}
+}
+
+fn value_size<K>(_: &K, v: &Option<StorageValue>) -> usize {
+	v.as_ref().map_or(0, |v| v.len())
+}
+
+fn hash_size<H: Hasher>(k: &StorageKey, _: &Option<H::Out>) -> usize {
+	k.len() + ::std::mem::size_of::<H::Out>()
+}
 
 /// Shared canonical state cache.
 pub struct Cache<B: Block, H: Hasher> {
 	/// Storage cache. `None` indicates that key is known to be missing.
-	storage: LruCache<StorageKey, Option<StorageValue>>,
+	storage: LimitedLru<StorageKey, Option<StorageValue>>,
 	/// Storage hashes cache. `None` indicates that key is known to be missing.
-	hashes: LruCache<StorageKey, Option<H::Out>>,
+	hashes: LimitedLru<StorageKey, Option<H::Out>>,
+	/// Child storage cache. `None` indicates that key is known to be missing.
+	/// The cache itself is `None` if child storage caching is disabled.
+	child_storage: Option<LimitedLru<ChildStorageKey, Option<StorageValue>>>,
 	/// Information on the modifications in recently committed blocks; specifically which keys
 	/// changed in which block. Ordered by block number.
 	modifications: VecDeque<BlockChanges<B::Header>>,
-	/// Maximum cache size available, in Bytes.
-	shared_cache_size: usize,
-	/// Used storage size, in Bytes.
-	storage_used_size: usize,
+	/// Size available to the storage values, shared and local caches together, in bytes.
+	values_size: usize,
+	/// Maximum size of the local storage values cache of a single state, in bytes.
+	/// `None` if the local caches are not limited.
+	local_limit: Option<usize>,
+	/// Usage counters of the states created for block import.
+	import_counters: CacheCounters,
+	/// Usage counters of the states created for queries.
+	query_counters: CacheCounters,
 }
 
 impl<B: Block, H: Hasher> Cache<B, H> {
 	/// Returns the used memory size of the storage cache in bytes.
 	pub fn used_storage_cache_size(&self) -> usize {
-		self.storage_used_size
+		self.storage.used_size
+	}
+
+	/// Returns usage statistics of the cache.
+	pub fn stats(&self) -> StateCacheStats {
+		StateCacheStats {
+			import: self.import_counters,
+			query: self.query_counters,
+			values_size: self.storage.used_size,
+			hashes_size: self.hashes.used_size,
+			child_size: self.child_storage.as_ref().map_or(0, |c| c.used_size),
+			shared_limit: self.storage.limit,
+			local_limit: self.local_limit,
+		}
+	}
+
+	fn clear(&mut self) {
+		self.storage.clear();
+		self.hashes.clear();
+		if let Some(ref mut child_storage) = self.child_storage {
+			child_storage.clear();
+		}
+		self.modifications.clear();
+	}
+
+	/// Size the local caches by the usage of a state used for block import,
+	/// giving the rest of the capacity to the shared cache. The local limit grows
+	/// immediately and shrinks gradually. Returns the number of evicted entries.
+	fn adapt(&mut self, local_used_size: usize) -> u64 {
+		let current = match self.local_limit {
+			Some(current) => current,
+			None => return 0,
+		};
+		let min = self.values_size / 16;
+		let max = self.values_size / 2;
+		let wanted = ::std::cmp::max(local_used_size + local_used_size / 4, current - current / 8);
+		let local_limit = ::std::cmp::min(::std::cmp::max(wanted, min), max);
+		if local_limit != current {
+			trace!("Resizing local cache limit to {} bytes", local_limit);
+		}
+		self.local_limit = Some(local_limit);
+		self.storage.set_limit(self.values_size - local_limit)
 	}
 }
 
 pub type SharedCache<B, H> = Arc<Mutex<Cache<B, H>>>;
 
 /// Create new shared cache instance with given max memory usage.
-pub fn new_shared_cache<B: Block, H: Hasher>(shared_cache_size: usize) -> SharedCache<B, H> {
-	// we need to supply a max capacity to `LruCache`, but since
-	// we don't have any idea how large the size of each item
-	// that is stored will be we can't calculate the max amount
-	// of items properly from `shared_cache_size`.
-	//
-	// what we do instead is to supply `shared_cache_size` as the
-	// max upper bound capacity (this would only be reached if each
-	// item would be one byte).
-	// each time we store to the storage cache we verify the memory
-	// constraint and pop the lru item if space needs to be freed.
+pub fn new_shared_cache<B: Block, H: Hasher>(
+	shared_cache_size: usize,
+	options: CacheOptions,
+) -> SharedCache<B, H> {
+	let child_size = options.child_ratio.map(|ratio| shared_cache_size * ::std::cmp::min(ratio, 100) / 100);
+	let values_size = shared_cache_size - child_size.unwrap_or(0);
+	let local_limit = if options.adaptive { Some(values_size / 8) } else { None };
 
 	Arc::new(Mutex::new(Cache {
-		storage: LruCache::new(shared_cache_size),
-		hashes: LruCache::new(shared_cache_size),
+		storage: LimitedLru::new(values_size - local_limit.unwrap_or(0), value_size),
+		hashes: LimitedLru::new(options.hashes_size, hash_size::<H>),
+		child_storage: child_size.map(|size| LimitedLru::new(size, value_size)),
 		modifications: VecDeque::new(),
-		shared_cache_size: shared_cache_size,
-		storage_used_size: 0,
+		values_size,
+		local_limit,
+		import_counters: Default::default(),
+		query_counters: Default::default(),
 	}))
 }
 
@@ -87,6 +260,8 @@ struct BlockChanges<B: Header> {
 	parent: B::Hash,
 	/// A set of modified storage keys.
 	storage: HashSet<StorageKey>,
+	/// A set of modified child storage keys.
+	child_storage: HashSet<ChildStorageKey>,
 	/// Block is part of the canonical chain.
 	is_canon: bool,
 }
@@ -97,6 +272,46 @@ struct LocalCache<H: Hasher> {
 	storage: HashMap<StorageKey, Option<StorageValue>>,
 	/// Storage hashes cache. `None` indicates that key is known to be missing.
 	hashes: HashMap<StorageKey, Option<H::Out>>,
+	/// Child storage cache. `None` indicates that key is known to be missing.
+	child_storage: HashMap<ChildStorageKey, Option<StorageValue>>,
+	/// Size of the cached storage and child storage values, in bytes.
+	used_size: usize,
+}
+
+impl<H: Hasher> LocalCache<H> {
+	/// Whether a value of given size fits into the local cache.
+	fn fits(&self, value: &Option<StorageValue>, limit: Option<usize>) -> bool {
+		limit.map_or(true, |limit| self.used_size + value_size(&(), value) <= limit)
+	}
+}
+
+/// Usage counters of a single state. The counters are merged into the shared cache
+/// when the state is synchronized, or into the query counters when it is dropped.
+struct LocalCounters<B: Block, H: Hasher> {
+	local_hits: AtomicUsize,
+	shared_hits: AtomicUsize,
+	misses: AtomicUsize,
+	shared_cache: SharedCache<B, H>,
+}
+
+impl<B: Block, H: Hasher> LocalCounters<B, H> {
+	fn take(&self) -> CacheCounters {
+		CacheCounters {
+			local_hits: self.local_hits.swap(0, Ordering::Relaxed) as u64,
+			shared_hits: self.shared_hits.swap(0, Ordering::Relaxed) as u64,
+			misses: self.misses.swap(0, Ordering::Relaxed) as u64,
+			evictions: 0,
+		}
+	}
+}
+
+impl<B: Block, H: Hasher> Drop for LocalCounters<B, H> {
+	fn drop(&mut self) {
+		let counters = self.take();
+		if counters != Default::default() {
+			self.shared_cache.lock().query_counters.merge(&counters);
+		}
+	}
 }
 
 /// State abstraction.
@@ -113,6 +328,8 @@ pub struct CachingState<H: Hasher, S: StateBackend<H>, B: Block> {
 	shared_cache: SharedCache<B, H>,
 	/// Local cache of values for this state.
 	local_cache: RwLock<LocalCache<H>>,
+	/// Usage counters of this state.
+	counters: LocalCounters<B, H>,
 	/// Hash of the block on top of which this instance was created or
 	/// `None` if cache is disabled
 	pub parent_hash: Option<B::Hash>,
@@ -123,53 +340,37 @@ impl<H: Hasher, S: StateBackend<H>, B: Block> CachingState<H, S, B> {
 	pub fn new(state: S, shared_cache: SharedCache<B, H>, parent_hash: Option<B::Hash>) -> CachingState<H, S, B> {
 		CachingState {
 			state,
+			counters: LocalCounters {
+				local_hits: AtomicUsize::new(0),
+				shared_hits: AtomicUsize::new(0),
+				misses: AtomicUsize::new(0),
+				shared_cache: shared_cache.clone(),
+			},
 			shared_cache,
 			local_cache: RwLock::new(LocalCache {
 				storage: Default::default(),
 				hashes: Default::default(),
+				child_storage: Default::default(),
+				used_size: 0,
 			}),
 			parent_hash: parent_hash,
 		}
 	}
 
-	fn storage_insert(cache: &mut Cache<B, H>, k: StorageValue, v: Option<StorageValue>) {
-		if let Some(v_) = &v {
-			while cache.storage_used_size + v_.len() > cache.shared_cache_size {
-				// pop until space constraint satisfied
-				match cache.storage.remove_lru() {
-					Some((_, Some(popped_v))) =>
-						cache.storage_used_size = cache.storage_used_size - popped_v.len(),
-					Some((_, None)) => continue,
-					None => break,
-				};
-			}
-			cache.storage_used_size = cache.storage_used_size + v_.len();
-		}
-		cache.storage.insert(k, v);
-	}
-
-	fn storage_remove(
-		storage: &mut LruCache<StorageKey, Option<StorageValue>>,
-		k: &StorageKey,
-		storage_used_size: &mut usize,
-	) {
-		let v = storage.remove(k);
-		if let Some(Some(v_)) = v {
-			*storage_used_size = *storage_used_size - v_.len();
-		}
-	}
-
 	/// Propagate local cache into the shared cache and synchronize
 	/// the shared cache with the best block state.
 	/// This function updates the shared cache by removing entries
 	/// that are invalidated by chain reorganization. `sync_cache`
 	/// should be called after the block has been committed and the
 	/// blockchain route has been calculated.
+	/// Usage counters of the state are accounted to the block import
+	/// if `commit_hash` is given and to the queries otherwise.
 	pub fn sync_cache<F: FnOnce() -> bool> (
 		&mut self,
 		enacted: &[B::Hash],
 		retracted: &[B::Hash],
 		changes: Vec<(StorageKey, Option<StorageValue>)>,
+		child_changes: Vec<(StorageKey, Vec<(StorageKey, Option<StorageValue>)>)>,
 		commit_hash: Option<B::Hash>,
 		commit_number: Option<<B::Header as Header>::Number>,
 		is_best: F,
@@ -178,6 +379,7 @@ impl<H: Hasher, S: StateBackend<H>, B: Block> CachingState<H, S, B> {
 		let is_best = is_best();
 		trace!("Syncing cache, id = (#{:?}, {:?}), parent={:?}, best={}", commit_number, commit_hash, self.parent_hash, is_best);
 		let cache = &mut *cache;
+		let mut counters = self.counters.take();
 
 		// Purge changes from re-enacted and retracted blocks.
 		// Filter out commiting block if any.
@@ -189,7 +391,13 @@ impl<H: Hasher, S: StateBackend<H>, B: Block> CachingState<H, S, B> {
 					m.is_canon = true;
 					for a in &m.storage {
 						trace!("Reverting enacted key {:?}", a);
-						CachingState::<H, S, B>::storage_remove(&mut cache.storage, a, &mut cache.storage_used_size);
+						cache.storage.remove(a);
+					}
+					if let Some(ref mut child_storage) = cache.child_storage {
+						for a in &m.child_storage {
+							trace!("Reverting enacted child key {:?}", a);
+							child_storage.remove(a);
+						}
 					}
 					false
 				} else {
@@ -205,7 +413,13 @@ impl<H: Hasher, S: StateBackend<H>, B: Block> CachingState<H, S, B> {
 					m.is_canon = false;
 					for a in &m.storage {
 						trace!("Retracted key {:?}", a);
-						CachingState::<H, S, B>::storage_remove(&mut cache.storage, a, &mut cache.storage_used_size);
+						cache.storage.remove(a);
+					}
+					if let Some(ref mut child_storage) = cache.child_storage {
+						for a in &m.child_storage {
+							trace!("Retracted child key {:?}", a);
+							child_storage.remove(a);
+						}
 					}
 					false
 				} else {
@@ -216,8 +430,7 @@ impl<H: Hasher, S: StateBackend<H>, B: Block> CachingState<H, S, B> {
 		if clear {
 			// We don't know anything about the block; clear everything
 			trace!("Wiping cache");
-			cache.storage.clear();
-			cache.modifications.clear();
+			cache.clear();
 		}
 
 		// Propagate cache only if committing on top of the latest canonical state
@@ -225,13 +438,27 @@ impl<H: Hasher, S: StateBackend<H>, B: Block> CachingState<H, S, B> {
 		// (contributed to canonical state cache)
 		if let Some(_) = self.parent_hash {
 			let mut local_cache = self.local_cache.write();
+			if commit_hash.is_some() {
+				counters.evictions += cache.adapt(local_cache.used_size);
+			}
 			if is_best {
-				trace!("Committing {} local, {} hashes, {} modified entries", local_cache.storage.len(), local_cache.hashes.len(), changes.len());
+				trace!(
+					"Committing {} local, {} hashes, {} child, {} modified entries",
+					local_cache.storage.len(),
+					local_cache.hashes.len(),
+					local_cache.child_storage.len(),
+					changes.len(),
+				);
 				for (k, v) in local_cache.storage.drain() {
-					CachingState::<H, S, B>::storage_insert(cache, k, v);
+					counters.evictions += cache.storage.insert(k, v);
 				}
 				for (k, v) in local_cache.hashes.drain() {
-					cache.hashes.insert(k, v);
+					counters.evictions += cache.hashes.insert(k, v);
+				}
+				if let Some(ref mut child_storage) = cache.child_storage {
+					for (k, v) in local_cache.child_storage.drain() {
+						counters.evictions += child_storage.insert(k, v);
+					}
 				}
 			}
 		}
@@ -248,12 +475,25 @@ impl<H: Hasher, S: StateBackend<H>, B: Block> CachingState<H, S, B> {
 				modifications.insert(k.clone());
 				if is_best {
 					cache.hashes.remove(&k);
-					CachingState::<H, S, B>::storage_insert(cache, k, v);
+					counters.evictions += cache.storage.insert(k, v);
+				}
+			}
+			let mut child_modifications = HashSet::new();
+			for (storage_key, child_changes) in child_changes.into_iter() {
+				for (k, v) in child_changes.into_iter() {
+					let k = (storage_key.clone(), k);
+					child_modifications.insert(k.clone());
+					if is_best {
+						if let Some(ref mut child_storage) = cache.child_storage {
+							counters.evictions += child_storage.insert(k, v);
+						}
+					}
 				}
 			}
 			// Save modified storage. These are ordered by the block number.
 			let block_changes = BlockChanges {
 				storage: modifications,
+				child_storage: child_modifications,
 				number: *number,
 				hash: hash.clone(),
 				is_canon: is_best,
@@ -270,12 +510,19 @@ impl<H: Hasher, S: StateBackend<H>, B: Block> CachingState<H, S, B> {
 				cache.modifications.push_back(block_changes);
 			}
 		}
+
+		if commit_hash.is_some() {
+			cache.import_counters.merge(&counters);
+		} else {
+			cache.query_counters.merge(&counters);
+		}
 	}
 
 	/// Check if the key can be returned from cache by matching current block parent hash against canonical
 	/// state and filtering out entries modified in later blocks.
 	fn is_allowed(
-		key: &[u8],
+		key: Option<&[u8]>,
+		child_key: Option<&ChildStorageKey>,
 		parent_hash: &Option<B::Hash>,
 		modifications:
 		&VecDeque<BlockChanges<B::Header>>
@@ -304,9 +551,17 @@ impl<H: Hasher, S: StateBackend<H>, B: Block> CachingState<H, S, B> {
 				}
 				parent = &m.parent;
 			}
-			if m.storage.contains(key) {
-				trace!("Cache lookup skipped for {:?}: modified in a later block", key);
-				return false;
+			if let Some(key) = key {
+				if m.storage.contains(key) {
+					trace!("Cache lookup skipped for {:?}: modified in a later block", key);
+					return false;
+				}
+			}
+			if let Some(child_key) = child_key {
+				if m.child_storage.contains(child_key) {
+					trace!("Cache lookup skipped for {:?}: modified in a later block", child_key);
+					return false;
+				}
 			}
 		}
 		trace!("Cache lookup skipped for {:?}: parent hash is unknown", key);
@@ -323,18 +578,25 @@ impl<H: Hasher, S: StateBackend<H>, B:Block> StateBackend<H> for CachingState<H,
 		let local_cache = self.local_cache.upgradable_read();
 		if let Some(entry) = local_cache.storage.get(key).cloned() {
 			trace!("Found in local cache: {:?}", key);
+			self.counters.local_hits.fetch_add(1, Ordering::Relaxed);
 			return Ok(entry)
 		}
 		let mut cache = self.shared_cache.lock();
-		if Self::is_allowed(key, &self.parent_hash, &cache.modifications) {
-			if let Some(entry) = cache.storage.get_mut(key).map(|a| a.clone()) {
+		if Self::is_allowed(Some(key), None, &self.parent_hash, &cache.modifications) {
+			if let Some(entry) = cache.storage.get(key).map(|a| a.clone()) {
 				trace!("Found in shared cache: {:?}", key);
+				self.counters.shared_hits.fetch_add(1, Ordering::Relaxed);
 				return Ok(entry)
 			}
 		}
 		trace!("Cache miss: {:?}", key);
+		self.counters.misses.fetch_add(1, Ordering::Relaxed);
 		let value = self.state.storage(key)?;
-		RwLockUpgradableReadGuard::upgrade(local_cache).storage.insert(key.to_vec(), value.clone());
+		if local_cache.fits(&value, cache.local_limit) {
+			let mut local_cache = RwLockUpgradableReadGuard::upgrade(local_cache);
+			local_cache.used_size += value_size(&(), &value);
+			local_cache.storage.insert(key.to_vec(), value.clone());
+		}
 		Ok(value)
 	}
 
@@ -342,23 +604,54 @@ impl<H: Hasher, S: StateBackend<H>, B:Block> StateBackend<H> for CachingState<H,
 		let local_cache = self.local_cache.upgradable_read();
 		if let Some(entry) = local_cache.hashes.get(key).cloned() {
 			trace!("Found hash in local cache: {:?}", key);
+			self.counters.local_hits.fetch_add(1, Ordering::Relaxed);
 			return Ok(entry)
 		}
 		let mut cache = self.shared_cache.lock();
-		if Self::is_allowed(key, &self.parent_hash, &cache.modifications) {
-			if let Some(entry) = cache.hashes.get_mut(key).map(|a| a.clone()) {
+		if Self::is_allowed(Some(key), None, &self.parent_hash, &cache.modifications) {
+			if let Some(entry) = cache.hashes.get(key).map(|a| a.clone()) {
 				trace!("Found hash in shared cache: {:?}", key);
+				self.counters.shared_hits.fetch_add(1, Ordering::Relaxed);
 				return Ok(entry)
 			}
 		}
 		trace!("Cache hash miss: {:?}", key);
+		self.counters.misses.fetch_add(1, Ordering::Relaxed);
 		let hash = self.state.storage_hash(key)?;
 		RwLockUpgradableReadGuard::upgrade(local_cache).hashes.insert(key.to_vec(), hash.clone());
 		Ok(hash)
 	}
 
 	fn child_storage(&self, storage_key: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
-		self.state.child_storage(storage_key, key)
+		let child_key = (storage_key.to_vec(), key.to_vec());
+		let local_cache = self.local_cache.upgradable_read();
+		if let Some(entry) = local_cache.child_storage.get(&child_key).cloned() {
+			trace!("Found child in local cache: {:?}", child_key);
+			self.counters.local_hits.fetch_add(1, Ordering::Relaxed);
+			return Ok(entry)
+		}
+		let mut cache = self.shared_cache.lock();
+		let cache = &mut *cache;
+		let child_storage = match cache.child_storage {
+			Some(ref mut child_storage) => child_storage,
+			None => return self.state.child_storage(storage_key, key),
+		};
+		if Self::is_allowed(None, Some(&child_key), &self.parent_hash, &cache.modifications) {
+			if let Some(entry) = child_storage.get(&child_key).map(|a| a.clone()) {
+				trace!("Found child in shared cache: {:?}", child_key);
+				self.counters.shared_hits.fetch_add(1, Ordering::Relaxed);
+				return Ok(entry)
+			}
+		}
+		trace!("Cache child miss: {:?}", child_key);
+		self.counters.misses.fetch_add(1, Ordering::Relaxed);
+		let value = self.state.child_storage(storage_key, key)?;
+		if local_cache.fits(&value, cache.local_limit) {
+			let mut local_cache = RwLockUpgradableReadGuard::upgrade(local_cache);
+			local_cache.used_size += value_size(&(), &value);
+			local_cache.child_storage.insert(child_key, value.clone());
+		}
+		Ok(value)
 	}
 
 	fn exists_storage(&self, key: &[u8]) -> Result<bool, Self::Error> {
@@ -366,7 +659,7 @@ impl<H: Hasher, S: StateBackend<H>, B:Block> StateBackend<H> for CachingState<H,
 	}
 
 	fn exists_child_storage(&self, storage_key: &[u8], key: &[u8]) -> Result<bool, Self::Error> {
-		self.state.exists_child_storage(storage_key, key)
+		Ok(self.child_storage(storage_key, key)?.is_some())
 	}
 
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
@@ -406,6 +699,7 @@ impl<H: Hasher, S: StateBackend<H>, B:Block> StateBackend<H> for CachingState<H,
 	}
 }
 
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -427,27 +721,27 @@ mod tests {
 		let h3a = H256::random();
 		let h3b = H256::random();
 
-		let shared = new_shared_cache::<Block, Blake2Hasher>(256*1024);
+		let shared = new_shared_cache::<Block, Blake2Hasher>(256*1024, Default::default());
 
 		// blocks  [ 3a(c) 2a(c) 2b 1b 1a(c) 0 ]
 		// state   [ 5     5     4  3  2     2 ]
 		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(root_parent.clone()));
-		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![2]))], Some(h0.clone()), Some(0), || true);
+		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![2]))], vec![], Some(h0.clone()), Some(0), || true);
 
 		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h0.clone()));
-		s.sync_cache(&[], &[], vec![], Some(h1a.clone()), Some(1), || true);
+		s.sync_cache(&[], &[], vec![], vec![], Some(h1a.clone()), Some(1), || true);
 
 		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h0.clone()));
-		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![3]))], Some(h1b.clone()), Some(1), || false);
+		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![3]))], vec![], Some(h1b.clone()), Some(1), || false);
 
 		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h1b.clone()));
-		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![4]))], Some(h2b.clone()), Some(2), || false);
+		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![4]))], vec![], Some(h2b.clone()), Some(2), || false);
 
 		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h1a.clone()));
-		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![5]))], Some(h2a.clone()), Some(2), || true);
+		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![5]))], vec![], Some(h2a.clone()), Some(2), || true);
 
 		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h2a.clone()));
-		s.sync_cache(&[], &[], vec![], Some(h3a.clone()), Some(3), || true);
+		s.sync_cache(&[], &[], vec![], vec![], Some(h3a.clone()), Some(3), || true);
 
 		let s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h3a.clone()));
 		assert_eq!(s.storage(&key).unwrap().unwrap(), vec![5]);
@@ -464,7 +758,7 @@ mod tests {
 		// reorg to 3b
 		// blocks  [ 3b(c) 3a 2a 2b(c) 1b 1a 0 ]
 		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h2b.clone()));
-		s.sync_cache(&[h1b.clone(), h2b.clone(), h3b.clone()], &[h1a.clone(), h2a.clone(), h3a.clone()], vec![], Some(h3b.clone()), Some(3), || true);
+		s.sync_cache(&[h1b.clone(), h2b.clone(), h3b.clone()], &[h1a.clone(), h2a.clone(), h3a.clone()], vec![], vec![], Some(h3b.clone()), Some(3), || true);
 		let s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h3a.clone()));
 		assert!(s.storage(&key).unwrap().is_none());
 	}
@@ -472,34 +766,138 @@ mod tests {
 	#[test]
 	fn should_track_used_size_correctly() {
 		let root_parent = H256::random();
-		let shared = new_shared_cache::<Block, Blake2Hasher>(5);
+		let shared = new_shared_cache::<Block, Blake2Hasher>(5, Default::default());
 		let h0 = H256::random();
 
 		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(root_parent.clone()));
 
 		let key = H256::random()[..].to_vec();
-		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![1, 2, 3]))], Some(h0.clone()), Some(0), || true);
+		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![1, 2, 3]))], vec![], Some(h0.clone()), Some(0), || true);
 		assert_eq!(shared.lock().used_storage_cache_size(), 3 /* bytes */);
 
 		let key = H256::random()[..].to_vec();
-		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![1, 2]))], Some(h0.clone()), Some(0), || true);
+		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![1, 2]))], vec![], Some(h0.clone()), Some(0), || true);
 		assert_eq!(shared.lock().used_storage_cache_size(), 5 /* bytes */);
 	}
 
 	#[test]
 	fn should_remove_lru_items_based_on_tracking_used_size() {
 		let root_parent = H256::random();
-		let shared = new_shared_cache::<Block, Blake2Hasher>(5);
+		let shared = new_shared_cache::<Block, Blake2Hasher>(5, Default::default());
 		let h0 = H256::random();
 
 		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(root_parent.clone()));
 
 		let key = H256::random()[..].to_vec();
-		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![1, 2, 3, 4]))], Some(h0.clone()), Some(0), || true);
+		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![1, 2, 3, 4]))], vec![], Some(h0.clone()), Some(0), || true);
 		assert_eq!(shared.lock().used_storage_cache_size(), 4 /* bytes */);
 
 		let key = H256::random()[..].to_vec();
-		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![1, 2]))], Some(h0.clone()), Some(0), || true);
+		s.sync_cache(&[], &[], vec![(key.clone(), Some(vec![1, 2]))], vec![], Some(h0.clone()), Some(0), || true);
 		assert_eq!(shared.lock().used_storage_cache_size(), 2 /* bytes */);
 	}
+
+	#[test]
+	fn should_count_hits_and_misses() {
+		let root_parent = H256::random();
+		let shared = new_shared_cache::<Block, Blake2Hasher>(256*1024, Default::default());
+		let h0 = H256::random();
+		let key = H256::random()[..].to_vec();
+		let backend = InMemory::<Blake2Hasher>::from(vec![(None, key.clone(), Some(vec![1]))]);
+
+		let mut s = CachingState::new(backend.clone(), shared.clone(), Some(root_parent.clone()));
+		assert_eq!(s.storage(&key).unwrap(), Some(vec![1]));
+		assert_eq!(s.storage(&key).unwrap(), Some(vec![1]));
+		s.sync_cache(&[], &[], vec![], vec![], Some(h0.clone()), Some(0), || true);
+		let stats = shared.lock().stats();
+		assert_eq!(stats.import, CacheCounters { local_hits: 1, shared_hits: 0, misses: 1, evictions: 0 });
+		assert_eq!(stats.query, CacheCounters::default());
+
+		{
+			let s = CachingState::new(backend.clone(), shared.clone(), Some(h0.clone()));
+			assert_eq!(s.storage(&key).unwrap(), Some(vec![1]));
+		}
+		let stats = shared.lock().stats();
+		assert_eq!(stats.query, CacheCounters { local_hits: 0, shared_hits: 1, misses: 0, evictions: 0 });
+	}
+
+	#[test]
+	fn should_limit_hashes_cache_size() {
+		let root_parent = H256::random();
+		let options = CacheOptions { hashes_size: 100, ..Default::default() };
+		let shared = new_shared_cache::<Block, Blake2Hasher>(256*1024, options);
+		let h0 = H256::random();
+		let key1 = H256::random()[..].to_vec();
+		let key2 = H256::random()[..].to_vec();
+		let backend = InMemory::<Blake2Hasher>::from(vec![
+			(None, key1.clone(), Some(vec![1])),
+			(None, key2.clone(), Some(vec![2])),
+		]);
+
+		let mut s = CachingState::new(backend, shared.clone(), Some(root_parent.clone()));
+		assert!(s.storage_hash(&key1).unwrap().is_some());
+		assert!(s.storage_hash(&key2).unwrap().is_some());
+		s.sync_cache(&[], &[], vec![], vec![], Some(h0.clone()), Some(0), || true);
+		let stats = shared.lock().stats();
+		assert_eq!(stats.hashes_size, 64 /* key and hash bytes */);
+		assert_eq!(stats.import.evictions, 1);
+	}
+
+	#[test]
+	fn should_cache_child_storage() {
+		let root_parent = H256::random();
+		let options = CacheOptions { child_ratio: Some(50), ..Default::default() };
+		let shared = new_shared_cache::<Block, Blake2Hasher>(256*1024, options);
+		let h0 = H256::random();
+		let h1 = H256::random();
+		let storage_key = b":child_storage:default:test".to_vec();
+		let key = H256::random()[..].to_vec();
+		let backend = InMemory::<Blake2Hasher>::from(vec![(Some(storage_key.clone()), key.clone(), Some(vec![1]))]);
+
+		let mut s = CachingState::new(backend, shared.clone(), Some(root_parent.clone()));
+		assert_eq!(s.child_storage(&storage_key, &key).unwrap(), Some(vec![1]));
+		s.sync_cache(&[], &[], vec![], vec![], Some(h0.clone()), Some(0), || true);
+
+		// the value is served by the shared cache
+		let s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h0.clone()));
+		assert_eq!(s.child_storage(&storage_key, &key).unwrap(), Some(vec![1]));
+
+		// but not on top of the non-canonical block modifying it
+		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h0.clone()));
+		let child_changes = vec![(storage_key.clone(), vec![(key.clone(), Some(vec![2]))])];
+		s.sync_cache(&[], &[], vec![], child_changes, Some(h1.clone()), Some(1), || false);
+		let s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h1.clone()));
+		assert!(s.child_storage(&storage_key, &key).unwrap().is_none());
+	}
+
+	#[test]
+	fn should_adapt_local_cache_limit() {
+		let root_parent = H256::random();
+		let options = CacheOptions { adaptive: true, ..Default::default() };
+		let shared = new_shared_cache::<Block, Blake2Hasher>(1600, options);
+		let h0 = H256::random();
+		let h1 = H256::random();
+		let keys: Vec<_> = (0..4).map(|_| H256::random()[..].to_vec()).collect();
+		let backend = InMemory::<Blake2Hasher>::from(
+			keys.iter().map(|key| (None, key.clone(), Some(vec![0; 100]))).collect::<Vec<_>>()
+		);
+		assert_eq!(shared.lock().stats().local_limit, Some(200));
+		assert_eq!(shared.lock().stats().shared_limit, 1400);
+
+		// only two values fit into the local cache
+		let mut s = CachingState::new(backend, shared.clone(), Some(root_parent.clone()));
+		for key in &keys {
+			assert_eq!(s.storage(key).unwrap(), Some(vec![0; 100]));
+		}
+		assert_eq!(s.local_cache.read().used_size, 200);
+		s.sync_cache(&[], &[], vec![], vec![], Some(h0.clone()), Some(0), || true);
+		assert_eq!(shared.lock().stats().local_limit, Some(250));
+		assert_eq!(shared.lock().stats().shared_limit, 1350);
+
+		// unused local cache shrinks gradually
+		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h0.clone()));
+		s.sync_cache(&[], &[], vec![], vec![], Some(h1.clone()), Some(1), || true);
+		assert_eq!(shared.lock().stats().local_limit, Some(219));
+		assert_eq!(shared.lock().stats().shared_limit, 1381);
+	}
 }
@@ -62,6 +62,8 @@ pub mod meta_keys {
 	pub const LEAF_PREFIX: &[u8; 4] = b"leaf";
 	/// Children prefix list key.
 	pub const CHILDREN_PREFIX: &[u8; 8] = b"children";
+	/// Changes trie configuration changes list key.
+	pub const CHANGES_TRIE_CONFIGS: &[u8; 9] = b"ctconfigs";
 }
 
 /// Database metadata.
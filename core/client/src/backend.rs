@@ -18,11 +18,11 @@
 
 use std::collections::HashMap;
 use crate::error;
-use primitives::ChangesTrieConfiguration;
 use runtime_primitives::{generic::BlockId, Justification, StorageOverlay, ChildrenStorageOverlay};
 use runtime_primitives::traits::{Block as BlockT, NumberFor};
 use state_machine::backend::Backend as StateBackend;
 use state_machine::ChangesTrieStorage as StateChangesTrieStorage;
+use state_machine::ChangesTrieAnchorBlockId;
 use consensus::well_known_cache_keys;
 use hash_db::Hasher;
 use trie::MemoryDB;
@@ -56,6 +56,67 @@ impl NewBlockState {
 	}
 }
 
+/// Usage counters of the state cache.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheCounters {
+	/// Lookups served by the cache local to a single state.
+	pub local_hits: u64,
+	/// Lookups served by the cache shared between the states.
+	pub shared_hits: u64,
+	/// Lookups served by the backing state.
+	pub misses: u64,
+	/// Entries evicted from the shared cache to satisfy its size limits.
+	pub evictions: u64,
+}
+
+impl CacheCounters {
+	/// Add the other counters to these ones.
+	pub fn merge(&mut self, other: &CacheCounters) {
+		self.local_hits += other.local_hits;
+		self.shared_hits += other.shared_hits;
+		self.misses += other.misses;
+		self.evictions += other.evictions;
+	}
+
+	/// Counters accumulated since the `earlier` snapshot of these counters.
+	pub fn since(&self, earlier: &CacheCounters) -> CacheCounters {
+		CacheCounters {
+			local_hits: self.local_hits.saturating_sub(earlier.local_hits),
+			shared_hits: self.shared_hits.saturating_sub(earlier.shared_hits),
+			misses: self.misses.saturating_sub(earlier.misses),
+			evictions: self.evictions.saturating_sub(earlier.evictions),
+		}
+	}
+
+	/// Percentage of the lookups served by the cache. `None` if there were no lookups.
+	pub fn hit_rate(&self) -> Option<u64> {
+		let hits = self.local_hits + self.shared_hits;
+		match hits + self.misses {
+			0 => None,
+			lookups => Some(hits * 100 / lookups),
+		}
+	}
+}
+
+/// Statistics of the state cache.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StateCacheStats {
+	/// Counters of the lookups made during block import.
+	pub import: CacheCounters,
+	/// Counters of the lookups made by the queries (runtime calls, RPC).
+	pub query: CacheCounters,
+	/// Used size of the storage values cache, in bytes.
+	pub values_size: usize,
+	/// Used size of the storage hashes cache, in bytes.
+	pub hashes_size: usize,
+	/// Used size of the child storage cache, in bytes.
+	pub child_size: usize,
+	/// Size limit of the shared storage values cache, in bytes.
+	pub shared_limit: usize,
+	/// Size limit of the local cache of a single state, in bytes. `None` if unlimited.
+	pub local_limit: Option<usize>,
+}
+
 /// Block insertion operation. Keeps hold if the inserted block state and data.
 pub trait BlockImportOperation<Block, H> where
 	Block: BlockT,
@@ -83,6 +144,8 @@ pub trait BlockImportOperation<Block, H> where
 	fn reset_storage(&mut self, top: StorageOverlay, children: ChildrenStorageOverlay) -> error::Result<H::Out>;
 	/// Set top level storage changes.
 	fn update_storage(&mut self, update: Vec<(Vec<u8>, Option<Vec<u8>>)>) -> error::Result<()>;
+	/// Set child storage changes, grouped by the child storage key.
+	fn update_child_storage(&mut self, update: Vec<(Vec<u8>, Vec<(Vec<u8>, Option<Vec<u8>>)>)>) -> error::Result<()>;
 	/// Inject changes trie data into the database.
 	fn update_changes_trie(&mut self, update: MemoryDB<H>) -> error::Result<()>;
 	/// Insert auxiliary keys. Values are `None` if should be deleted.
@@ -92,6 +155,13 @@ pub trait BlockImportOperation<Block, H> where
 	fn mark_finalized(&mut self, id: BlockId<Block>, justification: Option<Justification>) -> error::Result<()>;
 	/// Mark a block as new head. If both block import and set head are specified, set head overrides block import's best block rule.
 	fn mark_head(&mut self, id: BlockId<Block>) -> error::Result<()>;
+	/// Mark the imported block as a new base of the chain: the block is imported together with
+	/// its full state (see `reset_storage`) and its ancestors may be unknown to the backend.
+	fn mark_state_base(&mut self) -> error::Result<()>;
+	/// Mark the imported block as a part of the chain history preceding the base of the chain.
+	/// Only the block data is stored: the block has no state and doesn't affect the best
+	/// and the finalized block of the chain.
+	fn mark_history(&mut self) -> error::Result<()>;
 }
 
 /// Provides access to an auxiliary database.
@@ -143,6 +213,8 @@ pub trait Backend<Block, H>: AuxStore + Send + Sync where
 	fn blockchain(&self) -> &Self::Blockchain;
 	/// Returns the used state cache, if existent.
 	fn used_state_cache_size(&self) -> Option<usize>;
+	/// Returns the state cache statistics, if existent.
+	fn state_cache_stats(&self) -> Option<StateCacheStats>;
 	/// Returns reference to changes trie storage.
 	fn changes_trie_storage(&self) -> Option<&Self::ChangesTrieStorage>;
 	/// Returns true if state for given block is available.
@@ -178,8 +250,8 @@ pub trait Backend<Block, H>: AuxStore + Send + Sync where
 
 /// Changes trie storage that supports pruning.
 pub trait PrunableStateChangesTrieStorage<H: Hasher>: StateChangesTrieStorage<H> {
-	/// Get number block of oldest, non-pruned changes trie.
-	fn oldest_changes_trie_block(&self, config: &ChangesTrieConfiguration, best_finalized: u64) -> u64;
+	/// Get number block of oldest, non-pruned changes trie, given the best finalized block.
+	fn oldest_changes_trie_block(&self, best_finalized: &ChangesTrieAnchorBlockId<H::Out>) -> error::Result<u64>;
 }
 
 /// Mark for all Backend implementations, that are making use of state data, stored locally.
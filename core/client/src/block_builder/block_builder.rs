@@ -20,7 +20,8 @@ use parity_codec::Encode;
 use runtime_primitives::ApplyOutcome;
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::traits::{
-	Header as HeaderT, Hash, Block as BlockT, One, HashFor, ProvideRuntimeApi, ApiRef
+	Header as HeaderT, Hash, Block as BlockT, One, HashFor, ProvideRuntimeApi, ApiRef,
+	DigestFor,
 };
 use primitives::{H256, ExecutionContext};
 use crate::blockchain::HeaderBackend;
@@ -44,7 +45,7 @@ where
 	/// Create a new instance of builder from the given client, building on the latest block.
 	pub fn new(api: &'a A) -> error::Result<Self> {
 		api.info().and_then(|i|
-			Self::at_block(&BlockId::Hash(i.best_hash), api, false)
+			Self::at_block(&BlockId::Hash(i.best_hash), api, false, Default::default())
 		)
 	}
 
@@ -54,10 +55,14 @@ where
 	/// While proof recording is enabled, all accessed trie nodes are saved.
 	/// These recorded trie nodes can be used by a third party to proof the
 	/// output of this block builder without having access to the full storage.
+	///
+	/// The `inherent_digests` are put into the header before the block is
+	/// initialized, so that the runtime can read them.
 	pub fn at_block(
 		block_id: &BlockId<Block>,
 		api: &'a A,
-		proof_recording: bool
+		proof_recording: bool,
+		inherent_digests: DigestFor<Block>,
 	) -> error::Result<Self> {
 		let number = api.block_number_from_id(block_id)?
 			.ok_or_else(|| error::Error::UnknownBlock(format!("{}", block_id)))?
@@ -70,7 +75,7 @@ where
 			Default::default(),
 			Default::default(),
 			parent_hash,
-			Default::default()
+			inherent_digests,
 		);
 
 		let mut api = api.runtime_api();
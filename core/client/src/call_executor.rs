@@ -15,13 +15,15 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{sync::Arc, cmp::Ord, panic::UnwindSafe, result, cell::RefCell, rc::Rc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use log::warn;
 use parity_codec::{Encode, Decode};
 use runtime_primitives::{
 	generic::BlockId, traits::Block as BlockT,
 };
 use state_machine::{
 	self, OverlayedChanges, Ext, CodeExecutor, ExecutionManager,
-	ExecutionStrategy, NeverOffchainExt, backend::Backend as _,
+	ExecutionStrategy, ExecutionMismatch, NeverOffchainExt, backend::Backend as _,
 };
 use executor::{RuntimeVersion, RuntimeInfo, NativeVersion};
 use hash_db::Hasher;
@@ -162,6 +164,45 @@ impl<B, E> LocalCallExecutor<B, E> {
 			executor,
 		}
 	}
+
+	/// Never execute the runtime of the given state natively again. Called once its native
+	/// execution has diverged from wasm during a shadow execution.
+	fn disable_native_at<Block, S>(&self, state: &S)
+		where
+			Block: BlockT<Hash=H256>,
+			B: backend::Backend<Block, Blake2Hasher>,
+			E: RuntimeInfo,
+			S: state_machine::Backend<Blake2Hasher>,
+	{
+		let mut overlay = OverlayedChanges::default();
+		let mut ext = Ext::new(&mut overlay, state, self.backend.changes_trie_storage(), NeverOffchainExt::new());
+		match self.executor.runtime_version(&mut ext) {
+			Some(version) => {
+				warn!("Native execution of runtime spec version {} diverged from wasm, disabling it", version.spec_version);
+				self.executor.disable_native_for(version.spec_version);
+			},
+			None => warn!("Native execution diverged from wasm, but the runtime version is unknown"),
+		}
+	}
+}
+
+/// Note the mismatches of a shadow execution in the returned flag, on top of passing them to
+/// the reporter of the manager. Other managers are returned as they are.
+fn watch_mismatches<F>(manager: ExecutionManager<F>) -> (ExecutionManager<F>, Arc<AtomicBool>) {
+	let diverged = Arc::new(AtomicBool::new(false));
+	let manager = match manager {
+		ExecutionManager::Shadow(reporter) => {
+			let noted = diverged.clone();
+			ExecutionManager::Shadow(Some(Arc::new(move |mismatch: &ExecutionMismatch| {
+				noted.store(true, Ordering::Relaxed);
+				if let Some(ref reporter) = reporter {
+					reporter(mismatch);
+				}
+			})))
+		},
+		manager => manager,
+	};
+	(manager, diverged)
 }
 
 impl<B, E> Clone for LocalCallExecutor<B, E> where E: Clone {
@@ -191,6 +232,7 @@ where
 	) -> error::Result<Vec<u8>> {
 		let mut changes = OverlayedChanges::default();
 		let state = self.backend.state_at(*id)?;
+		let (manager, diverged) = watch_mismatches(strategy.get_manager());
 		let return_data = state_machine::new(
 			&state,
 			self.backend.changes_trie_storage(),
@@ -200,11 +242,15 @@ where
 			method,
 			call_data,
 		).execute_using_consensus_failure_handler::<_, NeverNativeValue, fn() -> _>(
-			strategy.get_manager(),
+			manager,
 			false,
 			None,
 		)
-		.map(|(result, _, _)| result)?;
+		.map(|(result, _, _)| result);
+		if diverged.load(Ordering::Relaxed) {
+			self.disable_native_at(&state);
+		}
+		let return_data = return_data?;
 		self.backend.destroy_state(state)?;
 		Ok(return_data.into_encoded())
 	}
@@ -242,6 +288,7 @@ where
 		}
 
 		let state = self.backend.state_at(*at)?;
+		let (execution_manager, diverged) = watch_mismatches(execution_manager);
 
 		match recorder {
 			Some(recorder) => {
@@ -256,7 +303,7 @@ where
 					recorder.clone()
 				);
 
-				state_machine::new(
+				let result = state_machine::new(
 					&backend,
 					self.backend.changes_trie_storage(),
 					side_effects_handler,
@@ -271,24 +318,35 @@ where
 					native_call,
 				)
 				.map(|(result, _, _)| result)
-				.map_err(Into::into)
+				.map_err(Into::into);
+				// the runtime version is read from the state that is not recorded
+				if diverged.load(Ordering::Relaxed) {
+					self.disable_native_at(&trie_state);
+				}
+				result
+			}
+			None => {
+				let result = state_machine::new(
+					&state,
+					self.backend.changes_trie_storage(),
+					side_effects_handler,
+					&mut *changes.borrow_mut(),
+					&self.executor,
+					method,
+					call_data,
+				)
+				.execute_using_consensus_failure_handler(
+					execution_manager,
+					false,
+					native_call,
+				)
+				.map(|(result, _, _)| result)
+				.map_err(Into::into);
+				if diverged.load(Ordering::Relaxed) {
+					self.disable_native_at(&state);
+				}
+				result
 			}
-			None => state_machine::new(
-				&state,
-				self.backend.changes_trie_storage(),
-				side_effects_handler,
-				&mut *changes.borrow_mut(),
-				&self.executor,
-				method,
-				call_data,
-			)
-			.execute_using_consensus_failure_handler(
-				execution_manager,
-				false,
-				native_call,
-			)
-			.map(|(result, _, _)| result)
-			.map_err(Into::into)
 		}
 	}
 
@@ -317,7 +375,8 @@ where
 		native_call: Option<NC>,
 		side_effects_handler: Option<&mut O>,
 	) -> error::Result<(NativeOrEncoded<R>, S::Transaction, Option<MemoryDB<Blake2Hasher>>)> {
-		state_machine::new(
+		let (manager, diverged) = watch_mismatches(manager);
+		let result = state_machine::new(
 			state,
 			self.backend.changes_trie_storage(),
 			side_effects_handler,
@@ -329,8 +388,11 @@ where
 			manager,
 			true,
 			native_call,
-		)
-		.map(|(result, storage_tx, changes_tx)| (
+		);
+		if diverged.load(Ordering::Relaxed) {
+			self.disable_native_at(state);
+		}
+		result.map(|(result, storage_tx, changes_tx)| (
 			result,
 			storage_tx.expect("storage_tx is always computed when compute_tx is true; qed"),
 			changes_tx,
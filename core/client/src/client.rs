@@ -25,26 +25,26 @@ use futures::sync::mpsc;
 use parking_lot::{Mutex, RwLock};
 use primitives::NativeOrEncoded;
 use runtime_primitives::{
-	Justification,
+	Justification, ApplyResult,
 	generic::{BlockId, SignedBlock},
 };
 use consensus::{
 	Error as ConsensusError, ErrorKind as ConsensusErrorKind, ImportBlock,
-	ImportResult, BlockOrigin, ForkChoiceStrategy,
-	well_known_cache_keys::Id as CacheKeyId,
+	ImportResult, BlockOrigin, BlockWeight, ForkChoiceStrategy,
+	well_known_cache_keys::Id as CacheKeyId, fork_choice_weight_key,
 	SelectChain, self,
 };
 use runtime_primitives::traits::{
 	Block as BlockT, Header as HeaderT, Zero, As, NumberFor, CurrentHeight,
 	BlockNumberToHash, ApiRef, ProvideRuntimeApi, Digest, DigestItem
 };
-use runtime_primitives::BuildStorage;
+use runtime_primitives::{BuildStorage, StorageOverlay, ChildrenStorageOverlay};
 use crate::runtime_api::{
 	CallRuntimeAt, ConstructRuntimeApi, Core as CoreApi, ProofRecorder,
 	InitializeBlock,
 };
 use primitives::{
-	Blake2Hasher, H256, ChangesTrieConfiguration, convert_hash,
+	Blake2Hasher, H256, convert_hash,
 	NeverNativeValue, ExecutionContext
 };
 use primitives::storage::{StorageKey, StorageData};
@@ -52,9 +52,11 @@ use primitives::storage::well_known_keys;
 use parity_codec::{Encode, Decode};
 use state_machine::{
 	DBValue, Backend as StateBackend, CodeExecutor, ChangesTrieAnchorBlockId,
-	ExecutionStrategy, ExecutionManager, prove_read, prove_child_read,
-	ChangesTrieRootsStorage, ChangesTrieStorage,
+	ExecutionStrategy, ExecutionManager, ExecutionMismatch, prove_read, prove_child_read, prove_range_read,
+	ChangesTrieRootsStorage, ChangesTrieStorage, ChangesTrieConfigurationRange,
 	key_changes, key_changes_proof, OverlayedChanges, NeverOffchainExt,
+	TrieBackend, TrieBackendStorage, MemoryDB, backend::Consolidate,
+	execution_trace::{self, ExecutionTrace, StorageKeyNames},
 };
 use hash_db::Hasher;
 
@@ -203,6 +205,106 @@ pub struct FinalityNotification<Block: BlockT> {
 	pub header: Block::Header,
 }
 
+/// Outcome of re-executing a block with `Client::replay_block`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayedBlock<Block: BlockT> {
+	/// Results of extrinsics application, in the order of block extrinsics.
+	pub extrinsic_results: Vec<ApplyResult>,
+	/// Header computed by the runtime at the end of block execution.
+	pub header: Block::Header,
+	/// Top-level storage entries changed by the block, sorted by key. `None` means removal.
+	pub storage_changes: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+	/// Changed entries of child tries, sorted by child storage key.
+	pub child_storage_changes: Vec<(Vec<u8>, Vec<(Vec<u8>, Option<Vec<u8>>)>)>,
+}
+
+/// The trie storage of the state of a block.
+type TrieStorageOf<B, Block> =
+	<<B as backend::Backend<Block, Blake2Hasher>>::State as StateBackend<Blake2Hasher>>::TrieBackendStorage;
+
+/// The trie nodes of the changes committed by a `CallSession`, on top of the storage of the
+/// state the session was started at.
+struct CallSessionStorage<S> {
+	base: S,
+	changes: MemoryDB<Blake2Hasher>,
+}
+
+impl<S: TrieBackendStorage<Blake2Hasher>> TrieBackendStorage<Blake2Hasher> for CallSessionStorage<S> {
+	type Overlay = MemoryDB<Blake2Hasher>;
+
+	fn get(&self, key: &H256, prefix: &[u8]) -> result::Result<Option<DBValue>, String> {
+		match hash_db::HashDB::get(&self.changes, key, prefix) {
+			Some(value) => Ok(Some(value)),
+			None => self.base.get(key, prefix),
+		}
+	}
+}
+
+/// Runtime calls executed in Wasm on top of the state of a block, see `Client::call_session`.
+///
+/// Every call sees the changes made by the previous calls of the session. The changes are
+/// never written to the database.
+pub struct CallSession<'a, B, E, Block> where
+	B: backend::Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	executor: &'a E,
+	state: TrieBackend<CallSessionStorage<TrieStorageOf<B, Block>>, Blake2Hasher>,
+	overlay: OverlayedChanges,
+	_marker: PhantomData<Block>,
+}
+
+impl<'a, B, E, Block> CallSession<'a, B, E, Block> where
+	B: backend::Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	/// Call a method of the runtime and keep the changes it makes for the following calls.
+	pub fn call(&mut self, method: &str, call_data: &[u8]) -> error::Result<Vec<u8>> {
+		let (result, _, _) = self.executor.call_at_state::<_, _, _, NeverNativeValue, fn() -> _>(
+			&self.state,
+			&mut self.overlay,
+			method,
+			call_data,
+			ExecutionManager::AlwaysWasm,
+			None,
+			NeverOffchainExt::new(),
+		)?;
+		self.overlay.commit_prospective();
+		Ok(result.into_encoded())
+	}
+
+	/// Call a method of the runtime the way `call` does, collecting the calls to the host
+	/// functions and the storage accesses. The trace has a single segment named after the method.
+	pub fn trace_call(&mut self, method: &str, call_data: &[u8]) -> error::Result<(Vec<u8>, ExecutionTrace)> {
+		let (result, trace) = execution_trace::trace(|| {
+			execution_trace::start_segment(method);
+			self.call(method, call_data)
+		});
+		result.map(|result| (result, trace))
+	}
+
+	/// Write the changes made so far into the trie of the session's state.
+	///
+	/// The following calls read these changes from the trie like any other storage entry,
+	/// instead of from the overlay of changes, which would be much cheaper.
+	pub fn commit(self) -> Self {
+		let CallSession { executor, state, overlay, _marker } = self;
+		let (top, children) = overlay.into_committed();
+		let (root, changes) = state.full_storage_root(top, children);
+
+		let mut storage = state.into_storage();
+		storage.changes.consolidate(changes);
+		CallSession {
+			executor,
+			state: TrieBackend::new(storage, root),
+			overlay: OverlayedChanges::default(),
+			_marker,
+		}
+	}
+}
+
 // used in importing a block, where additional changes are made after the runtime
 // executed.
 enum PrePostHeader<H> {
@@ -268,6 +370,19 @@ pub fn new_with_backend<B, E, Block, S, RA>(
 	Client::new(backend, call_executor, build_genesis_storage, Default::default())
 }
 
+/// Check storage range proof, generated by `Client::read_range_proof`, against the root of
+/// the proven trie.
+///
+/// Returns proven key-value pairs and whether there are no more pairs after them.
+pub fn check_range_proof(
+	root: H256,
+	proof: Vec<Vec<u8>>,
+	start: &[u8],
+) -> error::Result<(Vec<(Vec<u8>, Vec<u8>)>, bool)> {
+	state_machine::read_range_proof_check::<Blake2Hasher>(root, proof, start)
+		.map_err(Into::into)
+}
+
 impl<B, E, Block, RA> Client<B, E, Block, RA> where
 	B: backend::Backend<Block, Blake2Hasher>,
 	E: CallExecutor<Block, Blake2Hasher>,
@@ -350,6 +465,169 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			.map(StorageData))
 	}
 
+	/// Return the whole state at a given block: all top-level storage entries and
+	/// the entries of every child trie, keyed by child storage key.
+	pub fn storage_snapshot(&self, id: &BlockId<Block>)
+		-> error::Result<(StorageOverlay, ChildrenStorageOverlay)>
+	{
+		let state = self.state_at(id)?;
+		let mut top = StorageOverlay::new();
+		let mut children = ChildrenStorageOverlay::new();
+		for (key, value) in state.pairs() {
+			if !well_known_keys::is_child_storage_key(&key) {
+				top.insert(key, value);
+				continue;
+			}
+
+			let mut child_keys = Vec::new();
+			state.for_keys_in_child_storage(&key, |k| child_keys.push(k.to_vec()));
+			let mut child = StorageOverlay::new();
+			for child_key in child_keys {
+				let child_value = state.child_storage(&key, &child_key)
+					.map_err(|e| error::Error::from_state(Box::new(e)))?;
+				if let Some(child_value) = child_value {
+					child.insert(child_key, child_value);
+				}
+			}
+			children.insert(key, child);
+		}
+
+		Ok((top, children))
+	}
+
+	/// Re-execute a block on top of the state of its parent, without importing it.
+	///
+	/// The block is executed the way it has been built: `initialize_block`, `apply_extrinsic`
+	/// for every extrinsic and then `finalize_block`. So digest items that consensus engines
+	/// add after execution are not checked. Storage entries that are written with the same
+	/// value as in the parent state are not reported as changed.
+	pub fn replay_block(
+		&self,
+		id: &BlockId<Block>,
+		strategy: ExecutionStrategy,
+	) -> error::Result<ReplayedBlock<Block>> {
+		let (header, extrinsics) = self.block(id)?
+			.ok_or_else(|| error::Error::UnknownBlock(format!("{:?}", id)))?
+			.block
+			.deconstruct();
+		let state = self.state_at(&BlockId::Hash(*header.parent_hash()))?;
+
+		let get_execution_manager = || match strategy {
+			ExecutionStrategy::NativeElseWasm => ExecutionManager::NativeElseWasm,
+			ExecutionStrategy::AlwaysWasm => ExecutionManager::AlwaysWasm,
+			ExecutionStrategy::NativeWhenPossible => ExecutionManager::NativeWhenPossible,
+			ExecutionStrategy::Both => ExecutionManager::Both(|wasm_result, native_result| {
+				warn!("Consensus error between wasm and native block replay");
+				warn!("   Native result {:?}", native_result);
+				warn!("   Wasm result {:?}", wasm_result);
+				wasm_result
+			}),
+			ExecutionStrategy::Shadow => ExecutionManager::Shadow(None),
+		};
+
+		let mut overlay = OverlayedChanges::default();
+		let (extrinsic_results, header) = {
+			let mut call = |method: &str, call_data: &[u8]| -> error::Result<Vec<u8>> {
+				let (result, _, _) = self.executor.call_at_state::<_, _, _, NeverNativeValue, fn() -> _>(
+					&state,
+					&mut overlay,
+					method,
+					call_data,
+					get_execution_manager(),
+					None,
+					NeverOffchainExt::new(),
+				)?;
+				overlay.commit_prospective();
+				Ok(result.into_encoded())
+			};
+
+			execution_trace::start_segment("initialize_block");
+			call("Core_initialize_block", &header.encode())?;
+			let mut extrinsic_results = Vec::with_capacity(extrinsics.len());
+			for (index, extrinsic) in extrinsics.into_iter().enumerate() {
+				execution_trace::start_segment(format!("extrinsic {}", index));
+				let result = call("BlockBuilder_apply_extrinsic", &extrinsic.encode())?;
+				extrinsic_results.push(Decode::decode(&mut &result[..])
+					.ok_or(error::Error::CallResultDecode("apply_extrinsic"))?);
+			}
+			execution_trace::start_segment("finalize_block");
+			let header = call("BlockBuilder_finalize_block", &[])?;
+			execution_trace::end_segment();
+			let header = Decode::decode(&mut &header[..])
+				.ok_or(error::Error::CallResultDecode("finalize_block"))?;
+
+			(extrinsic_results, header)
+		};
+
+		let (top, children) = overlay.into_committed();
+		let mut storage_changes = Vec::new();
+		for (key, value) in top {
+			let parent_value = state.storage(&key)
+				.map_err(|e| error::Error::from_state(Box::new(e)))?;
+			if parent_value != value {
+				storage_changes.push((key, value));
+			}
+		}
+		storage_changes.sort();
+
+		let mut child_storage_changes = Vec::new();
+		for (storage_key, child) in children {
+			let mut child_changes = Vec::new();
+			for (key, value) in child {
+				let parent_value = state.child_storage(&storage_key, &key)
+					.map_err(|e| error::Error::from_state(Box::new(e)))?;
+				if parent_value != value {
+					child_changes.push((key, value));
+				}
+			}
+			if !child_changes.is_empty() {
+				child_changes.sort();
+				child_storage_changes.push((storage_key, child_changes));
+			}
+		}
+		child_storage_changes.sort();
+
+		Ok(ReplayedBlock {
+			extrinsic_results,
+			header,
+			storage_changes,
+			child_storage_changes,
+		})
+	}
+
+	/// Re-execute a block in Wasm the way `replay_block` does, collecting the calls to the host
+	/// functions and the storage accesses. The trace has a segment for the initialization of
+	/// the block, for every extrinsic and for the finalization of the block.
+	///
+	/// The storage accesses are grouped by the prefixes named in `key_names`, see
+	/// `execution_trace::record_hash`.
+	pub fn trace_block(&self, id: &BlockId<Block>, key_names: StorageKeyNames) -> error::Result<ExecutionTrace> {
+		let (result, trace) = execution_trace::trace_with_key_names(
+			key_names,
+			|| self.replay_block(id, ExecutionStrategy::AlwaysWasm),
+		);
+		result.map(|_| trace)
+	}
+
+	/// Start a session of runtime calls executed in Wasm on top of the state of a block.
+	///
+	/// Fails if the state isn't backed by a trie, e.g. on a light client.
+	pub fn call_session(&self, id: &BlockId<Block>) -> error::Result<CallSession<B, E, Block>> {
+		let state = self.state_at(id)?
+			.try_into_trie_backend()
+			.ok_or_else(|| error::Error::Msg("Call sessions need a trie backed state".into()))?;
+		let root = *state.root();
+		Ok(CallSession {
+			executor: &self.executor,
+			state: TrieBackend::new(
+				CallSessionStorage { base: state.into_storage(), changes: Default::default() },
+				root,
+			),
+			overlay: OverlayedChanges::default(),
+			_marker: PhantomData,
+		})
+	}
+
 	/// Get the code at a given block.
 	pub fn code_at(&self, id: &BlockId<Block>) -> error::Result<Vec<u8>> {
 		Ok(self.storage(id, &StorageKey(well_known_keys::CODE.to_vec()))?
@@ -388,6 +666,74 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				.map_err(Into::into))
 	}
 
+	/// Reads up to `max_entries` consecutive key-value pairs of the storage (or of the child
+	/// storage) at a given block, starting with `start`, returning range proof.
+	pub fn read_range_proof(
+		&self,
+		id: &BlockId<Block>,
+		child_storage_key: Option<&[u8]>,
+		start: &[u8],
+		max_entries: usize,
+	) -> error::Result<Vec<Vec<u8>>> {
+		self.state_at(id)
+			.and_then(|state| prove_range_read(state, child_storage_key, start, max_entries)
+				.map_err(Into::into))
+	}
+
+	/// Import a finalized block together with its full state. The block becomes a new
+	/// base of the chain, so its ancestors don't have to be known.
+	///
+	/// Fails if the state doesn't match the state root of the header.
+	pub fn import_state(
+		&self,
+		header: Block::Header,
+		justification: Option<Justification>,
+		top: StorageOverlay,
+		children: ChildrenStorageOverlay,
+	) -> error::Result<()> {
+		let hash = header.hash();
+		if self.backend.blockchain().status(BlockId::Hash(hash))? == blockchain::BlockStatus::InChain {
+			return Ok(());
+		}
+
+		self.lock_import_and_run(|operation| {
+			let state_root = operation.op.reset_storage(top, children)?;
+			if state_root != *header.state_root() {
+				return Err(error::Error::InvalidStateRoot);
+			}
+
+			info!("Importing state of #{} ({})", header.number(), hash);
+			operation.op.set_block_data(header.clone(), None, justification, backend::NewBlockState::Final)?;
+			operation.op.mark_state_base()?;
+
+			operation.notify_imported = Some((hash, BlockOrigin::NetworkInitialSync, header, true, None));
+			operation.notify_finalized = vec![hash];
+			Ok(())
+		})
+	}
+
+	/// Import a block of the chain history preceding the base of the chain set by `import_state`.
+	/// Only the block data is stored. The block must be the parent of the oldest known block
+	/// of the chain.
+	pub fn import_history(
+		&self,
+		header: Block::Header,
+		body: Option<Vec<Block::Extrinsic>>,
+		justification: Option<Justification>,
+	) -> error::Result<()> {
+		let hash = header.hash();
+		let number = *header.number();
+		let child = self.backend.blockchain().header(BlockId::Number(number + As::sa(1)))?;
+		if child.map_or(true, |child| *child.parent_hash() != hash) {
+			return Err(error::Error::UnknownBlock(format!("No child of history block {:?}", hash)));
+		}
+
+		self.lock_import_and_run(|operation| {
+			operation.op.set_block_data(header, body, justification, backend::NewBlockState::Normal)?;
+			operation.op.mark_history()
+		})
+	}
+
 	/// Execute a call to a contract on top of state in a block of given hash
 	/// AND returning execution proof.
 	///
@@ -429,8 +775,8 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		first: NumberFor<Block>,
 		last: BlockId<Block>,
 	) -> error::Result<Option<(NumberFor<Block>, BlockId<Block>)>> {
-		let (config, storage) = match self.require_changes_trie().ok() {
-			Some((config, storage)) => (config, storage),
+		let storage = match self.backend.changes_trie_storage() {
+			Some(storage) => storage,
 			None => return Ok(None),
 		};
  		let first = first.as_();
@@ -438,8 +784,19 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		if first > last_num {
 			return Err(error::Error::ChangesTrieAccessFailed("Invalid changes trie range".into()));
 		}
- 		let finalized_number = self.backend.blockchain().info()?.finalized_number;
-		let oldest = storage.oldest_changes_trie_block(&config, finalized_number.as_());
+		let last_hash = self.backend.blockchain().expect_block_hash_from_id(&last)?;
+		let config_ranges = storage.configuration_ranges(
+			&ChangesTrieAnchorBlockId { hash: convert_hash(&last_hash), number: last_num },
+			first,
+		).map_err(|err| error::Error::ChangesTrieAccessFailed(err))?;
+		if config_ranges.is_empty() {
+			return Ok(None);
+		}
+ 		let info = self.backend.blockchain().info()?;
+		let oldest = storage.oldest_changes_trie_block(&ChangesTrieAnchorBlockId {
+			hash: convert_hash(&info.finalized_hash),
+			number: info.finalized_number.as_(),
+		})?;
 		let first = As::sa(::std::cmp::max(first, oldest));
 		Ok(Some((first, last)))
 	}
@@ -452,22 +809,32 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		last: BlockId<Block>,
 		key: &StorageKey
 	) -> error::Result<Vec<(NumberFor<Block>, u32)>> {
-		let (config, storage) = self.require_changes_trie()?;
+		let storage = self.require_changes_trie()?;
 		let last_number = self.backend.blockchain().expect_block_number_from_id(&last)?.as_();
 		let last_hash = self.backend.blockchain().expect_block_hash_from_id(&last)?;
+		let anchor = ChangesTrieAnchorBlockId {
+			hash: convert_hash(&last_hash),
+			number: last_number,
+		};
+		let config_ranges = self.require_changes_trie_configs(storage, &anchor, first.as_())?;
+		let best_number = self.backend.blockchain().info()?.best_number.as_();
+
+		// changes are returned in descending order => start from the latest configuration
+		let mut result = Vec::new();
+		for config_range in config_ranges.iter().rev() {
+			let range_result = key_changes::<_, Blake2Hasher>(
+				config_range,
+				&*storage,
+				first.as_(),
+				&anchor,
+				best_number,
+				&key.0)
+			.and_then(|r| r.map(|r| r.map(|(block, tx)| (As::sa(block), tx))).collect::<Result<Vec<_>, _>>())
+			.map_err(|err| error::Error::ChangesTrieAccessFailed(err))?;
+			result.extend(range_result);
+		}
 
-		key_changes::<_, Blake2Hasher>(
-			&config,
-			&*storage,
-			first.as_(),
-			&ChangesTrieAnchorBlockId {
-				hash: convert_hash(&last_hash),
-				number: last_number,
-			},
-			self.backend.blockchain().info()?.best_number.as_(),
-			&key.0)
-		.and_then(|r| r.map(|r| r.map(|(block, tx)| (As::sa(block), tx))).collect::<Result<_, _>>())
-		.map_err(|err| error::Error::ChangesTrieAccessFailed(err))
+		Ok(result)
 	}
 
 	/// Get proof for computation of (block, extrinsic) pairs where key has been changed at given blocks range.
@@ -523,6 +890,14 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				}
 				Ok(root)
 			}
+
+			fn configuration_ranges(
+				&self,
+				anchor: &ChangesTrieAnchorBlockId<H256>,
+				begin: u64,
+			) -> Result<Vec<ChangesTrieConfigurationRange>, String> {
+				self.storage.configuration_ranges(anchor, begin)
+			}
 		}
 
 		impl<'a, Block: BlockT> ChangesTrieStorage<Blake2Hasher> for AccessedRootsRecorder<'a, Block> {
@@ -531,7 +906,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			}
 		}
 
-		let (config, storage) = self.require_changes_trie()?;
+		let storage = self.require_changes_trie()?;
 		let min_number = self.backend.blockchain().expect_block_number_from_id(&BlockId::Hash(min))?;
 
 		let recording_storage = AccessedRootsRecorder::<Block> {
@@ -545,21 +920,28 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			self.backend.blockchain().expect_block_number_from_id(&BlockId::Hash(max))?,
 		);
 
-		// fetch key changes proof
+		// fetch key changes proof (single proof for all configuration ranges)
 		let first_number = self.backend.blockchain().expect_block_number_from_id(&BlockId::Hash(first))?.as_();
 		let last_number = self.backend.blockchain().expect_block_number_from_id(&BlockId::Hash(last))?.as_();
-		let key_changes_proof = key_changes_proof::<_, Blake2Hasher>(
-			&config,
-			&recording_storage,
-			first_number,
-			&ChangesTrieAnchorBlockId {
-				hash: convert_hash(&last),
-				number: last_number,
-			},
-			max_number.as_(),
-			&key.0
-		)
-		.map_err(|err| error::Error::from(error::Error::ChangesTrieAccessFailed(err)))?;
+		let anchor = ChangesTrieAnchorBlockId {
+			hash: convert_hash(&last),
+			number: last_number,
+		};
+		let config_ranges = self.require_changes_trie_configs(storage, &anchor, first_number)?;
+		let mut key_changes_proof = HashSet::new();
+		for config_range in &config_ranges {
+			let range_proof = key_changes_proof::<_, Blake2Hasher>(
+				config_range,
+				&recording_storage,
+				first_number,
+				&anchor,
+				max_number.as_(),
+				&key.0
+			)
+			.map_err(|err| error::Error::from(error::Error::ChangesTrieAccessFailed(err)))?;
+			key_changes_proof.extend(range_proof);
+		}
+		let key_changes_proof = key_changes_proof.into_iter().collect();
 
 		// now gather proofs for all changes tries roots that were touched during key_changes_proof
 		// execution AND are unknown (i.e. replaced with CHT) to the requester
@@ -607,14 +989,27 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Ok(proof)
 	}
 
-	/// Returns changes trie configuration and storage or an error if it is not supported.
-	fn require_changes_trie(&self) -> error::Result<(ChangesTrieConfiguration, &B::ChangesTrieStorage)> {
-		let config = self.changes_trie_config()?;
-		let storage = self.backend.changes_trie_storage();
-		match (config, storage) {
-			(Some(config), Some(storage)) => Ok((config, storage)),
-			_ => Err(error::Error::ChangesTriesNotSupported.into()),
+	/// Returns changes trie storage or an error if it is not supported.
+	fn require_changes_trie(&self) -> error::Result<&B::ChangesTrieStorage> {
+		self.backend.changes_trie_storage()
+			.ok_or_else(|| error::Error::ChangesTriesNotSupported)
+	}
+
+	/// Returns changes trie configurations that are active at blocks `begin..=anchor.number`
+	/// or an error if changes tries are not supported at these blocks.
+	fn require_changes_trie_configs(
+		&self,
+		storage: &B::ChangesTrieStorage,
+		anchor: &ChangesTrieAnchorBlockId<H256>,
+		begin: u64,
+	) -> error::Result<Vec<ChangesTrieConfigurationRange>> {
+		let config_ranges = storage.configuration_ranges(anchor, begin)
+			.map_err(|err| error::Error::ChangesTrieAccessFailed(err))?;
+		if config_ranges.is_empty() {
+			return Err(error::Error::ChangesTriesNotSupported.into());
 		}
+
+		Ok(config_ranges)
 	}
 
 	/// Create a new block, built on the head of the chain.
@@ -638,7 +1033,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Self: ProvideRuntimeApi,
 		<Self as ProvideRuntimeApi>::Api: BlockBuilderAPI<Block>
 	{
-		block_builder::BlockBuilder::at_block(parent, &self, false)
+		block_builder::BlockBuilder::at_block(parent, &self, false, Default::default())
 	}
 
 	/// Create a new block, built on top of `parent` with proof recording enabled.
@@ -654,7 +1049,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Self: ProvideRuntimeApi,
 		<Self as ProvideRuntimeApi>::Api: BlockBuilderAPI<Block>
 	{
-		block_builder::BlockBuilder::at_block(parent, &self, true)
+		block_builder::BlockBuilder::at_block(parent, &self, true, Default::default())
 	}
 
 	/// Lock the import lock, and run operations inside.
@@ -786,7 +1181,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		body: Option<Vec<Block::Extrinsic>>,
 		new_cache: HashMap<CacheKeyId, Vec<u8>>,
 		finalized: bool,
-		aux: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+		mut aux: Vec<(Vec<u8>, Option<Vec<u8>>)>,
 		fork_choice: ForkChoiceStrategy,
 	) -> error::Result<ImportResult> where
 		E: CallExecutor<Block, Blake2Hasher> + Send + Sync + Clone,
@@ -821,8 +1216,19 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		// FIXME #1232: correct path logic for when to execute this function
 		let (storage_update,changes_update,storage_changes) = self.block_execution(&operation.op, &import_headers, origin, hash, body.clone())?;
 
+		// the weight is kept with the block so that descendants and `SelectChain`
+		// implementations can compare chains by it.
+		if let ForkChoiceStrategy::HeaviestChain(weight) = fork_choice {
+			aux.push((fork_choice_weight_key(&hash), Some(weight.encode())));
+		}
+
 		let is_new_best = finalized || match fork_choice {
 			ForkChoiceStrategy::LongestChain => import_headers.post().number() > &last_best_number,
+			ForkChoiceStrategy::HeaviestChain(weight) => {
+				let best_weight = load_block_weight(&*self.backend, &last_best)?.unwrap_or_default();
+				weight > best_weight
+					|| (weight == best_weight && import_headers.post().number() > &last_best_number)
+			},
 			ForkChoiceStrategy::Custom(v) => v,
 		};
 		let leaf_state = if finalized {
@@ -846,9 +1252,14 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		if let Some(storage_update) = storage_update {
 			operation.op.update_db_storage(storage_update)?;
 		}
-		if let Some(storage_changes) = storage_changes.clone() {
-			operation.op.update_storage(storage_changes)?;
-		}
+		let storage_changes = match storage_changes {
+			Some((top_changes, child_changes)) => {
+				operation.op.update_storage(top_changes.clone())?;
+				operation.op.update_child_storage(child_changes)?;
+				Some(top_changes)
+			},
+			None => None,
+		};
 		if let Some(Some(changes_update)) = changes_update {
 			operation.op.update_changes_trie(changes_update)?;
 		}
@@ -876,7 +1287,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 	) -> error::Result<(
 		Option<StorageUpdate<B, Block>>,
 		Option<Option<ChangesUpdate>>,
-		Option<Vec<(Vec<u8>, Option<Vec<u8>>)>>,
+		Option<(Vec<(Vec<u8>, Option<Vec<u8>>)>, Vec<(Vec<u8>, Vec<(Vec<u8>, Option<Vec<u8>>)>)>)>,
 	)>
 		where
 			E: CallExecutor<Block, Blake2Hasher> + Send + Sync + Clone,
@@ -902,6 +1313,16 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 							);
 							wasm_result
 						}),
+						ExecutionStrategy::Shadow => ExecutionManager::Shadow(Some(Arc::new(move |mismatch: &ExecutionMismatch| {
+							telemetry!(SUBSTRATE_INFO; "block.execute.shadow_mismatch";
+								"hash" => ?hash,
+								"origin" => ?origin,
+								"method" => mismatch.method.clone(),
+								"result_differs" => mismatch.results.is_some(),
+								"storage_mismatches" => mismatch.storage.len(),
+								"diff" => mismatch.to_string()
+							);
+						}))),
 					}
 				};
 				let (_, storage_update, changes_update) = self.executor.call_at_state::<_, _, _, NeverNativeValue, fn() -> _>(
@@ -919,7 +1340,9 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 
 				overlay.commit_prospective();
 
-				Ok((Some(storage_update), Some(changes_update), Some(overlay.into_committed().collect())))
+				let (top, children) = overlay.into_committed();
+				let children = children.map(|(sk, it)| (sk, it.collect())).collect();
+				Ok((Some(storage_update), Some(changes_update), Some((top.collect(), children))))
 			},
 			None => Ok((None, None, None))
 		}
@@ -1187,13 +1610,6 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Ok(uncles)
 	}
 
-	fn changes_trie_config(&self) -> Result<Option<ChangesTrieConfiguration>, Error> {
-		Ok(self.backend.state_at(BlockId::Number(self.backend.blockchain().info()?.best_number))?
-			.storage(well_known_keys::CHANGES_TRIE_CONFIG)
-			.map_err(|e| error::Error::from_state(Box::new(e)))?
-			.and_then(|c| Decode::decode(&mut &*c)))
-	}
-
 	/// Prepare in-memory header that is used in execution environment.
 	fn prepare_environment_block(&self, parent: &BlockId<Block>) -> error::Result<Block::Header> {
 		Ok(<<Block as BlockT>::Header as HeaderT>::new(
@@ -1604,6 +2020,236 @@ where
 	}
 }
 
+/// Load the fork-choice weight which was attached to the block with the given
+/// hash on import, if any.
+pub fn load_block_weight<A, H>(aux: &A, hash: &H) -> error::Result<Option<BlockWeight>> where
+	A: backend::AuxStore + ?Sized,
+	H: Encode,
+{
+	match aux.get_aux(&fork_choice_weight_key(hash))? {
+		None => Ok(None),
+		Some(encoded) => BlockWeight::decode(&mut &encoded[..])
+			.map(Some)
+			.ok_or_else(|| error::Error::Backend("Corrupted fork-choice weight of block".into())),
+	}
+}
+
+/// Implement Heaviest Chain Select implementation
+/// where 'heaviest' is defined as the highest fork-choice weight attached to
+/// the head of the chain on import, ties broken by the number of blocks.
+/// Only chains building on the last finalized block are considered.
+///
+/// The best chain is the one the client picked when importing its head, and
+/// the leaves are only searched if it was abandoned by finality.
+pub struct HeaviestChain<B, Block> {
+	backend: Arc<B>,
+	_phantom: PhantomData<Block>
+}
+
+impl<B, Block> Clone for HeaviestChain<B, Block> {
+	fn clone(&self) -> Self {
+		HeaviestChain {
+			backend: self.backend.clone(),
+			_phantom: Default::default()
+		}
+	}
+}
+
+impl<B, Block> HeaviestChain<B, Block>
+where
+	B: backend::Backend<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	/// Instantiate a new HeaviestChain for Backend B
+	pub fn new(backend: Arc<B>) -> Self {
+		HeaviestChain {
+			backend,
+			_phantom: Default::default()
+		}
+	}
+
+	fn header(&self, hash: Block::Hash) -> error::Result<<Block as BlockT>::Header> {
+		self.backend.blockchain().header(BlockId::Hash(hash))?
+			.ok_or_else(|| error::Error::from(format!("failed to get header for hash {}", hash)))
+	}
+
+	/// Get the leaves building on the last finalized block, heaviest first.
+	fn leaves_by_weight(&self) -> error::Result<Vec<Block::Hash>> {
+		let info = self.backend.blockchain().info()?;
+
+		let mut leaves = Vec::new();
+		for leaf_hash in self.backend.blockchain().leaves()? {
+			let leaf_header = self.header(leaf_hash)?;
+			let leaf_number = *leaf_header.number();
+			if leaf_number < info.finalized_number {
+				continue;
+			}
+
+			// go backwards to the height of the last finalized block
+			let mut current_header = leaf_header;
+			while *current_header.number() > info.finalized_number {
+				current_header = self.header(*current_header.parent_hash())?;
+			}
+
+			// the leaf is on a dead fork.
+			if current_header.hash() != info.finalized_hash {
+				continue;
+			}
+
+			let weight = load_block_weight(&*self.backend, &leaf_hash)?.unwrap_or_default();
+			leaves.push((weight, leaf_number, leaf_hash));
+		}
+
+		leaves.sort_by(|a, b| (b.0, b.1).cmp(&(a.0, a.1)));
+
+		Ok(leaves.into_iter().map(|(_, _, hash)| hash).collect())
+	}
+
+	fn best_block_header(&self) -> error::Result<<Block as BlockT>::Header> {
+		// the client compares the weights of the blocks it imports with the one of
+		// the best block, under the import lock, so the best block is the heaviest
+		// leaf unless finality moved to another fork.
+		let info = self.backend.blockchain().info()?;
+		if self.backend.blockchain().hash(info.finalized_number)? == Some(info.finalized_hash) {
+			return self.header(info.best_hash);
+		}
+
+		match self.leaves_by_weight()?.into_iter().next() {
+			Some(best_hash) => self.header(best_hash),
+			// the last finalized block is always part of a chain we consider.
+			None => self.header(self.backend.blockchain().info()?.finalized_hash),
+		}
+	}
+
+	/// Get the most recent block hash of the best (heaviest) chains
+	/// that contain block with the given `target_hash`.
+	///
+	/// If `maybe_max_block_number` is `Some(max_block_number)`
+	/// the search is limited to block `numbers <= max_block_number`.
+	/// Returns `Ok(None)` if `target_hash` is not found in search space.
+	fn best_containing(
+		&self,
+		target_hash: Block::Hash,
+		maybe_max_number: Option<NumberFor<Block>>
+	) -> error::Result<Option<Block::Hash>> {
+		let target_header = match self.backend.blockchain().header(BlockId::Hash(target_hash))? {
+			Some(x) => x,
+			// target not in blockchain
+			None => return Ok(None),
+		};
+
+		if let Some(max_number) = maybe_max_number {
+			// target outside search range
+			if target_header.number() > &max_number {
+				return Ok(None);
+			}
+		}
+
+		// for each chain. heaviest chain first. lightest last
+		for leaf_hash in self.leaves_by_weight()? {
+			let mut current_header = self.header(leaf_hash)?;
+
+			// go backwards entering the search space
+			if let Some(max_number) = maybe_max_number {
+				while current_header.number() > &max_number {
+					current_header = self.header(*current_header.parent_hash())?;
+				}
+			}
+
+			let best_hash = current_header.hash();
+
+			// go backwards through the chain until the target's block number
+			while current_header.number() > target_header.number() {
+				current_header = self.header(*current_header.parent_hash())?;
+			}
+
+			if current_header.hash() == target_hash {
+				return Ok(Some(best_hash));
+			}
+		}
+
+		Ok(None)
+	}
+}
+
+impl<B, Block> SelectChain<Block> for HeaviestChain<B, Block>
+where
+	B: backend::Backend<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+
+	fn leaves(&self) -> Result<Vec<<Block as BlockT>::Hash>, ConsensusError> {
+		HeaviestChain::leaves_by_weight(self)
+			.map_err(|e| ConsensusErrorKind::ChainLookup(e.to_string()).into())
+	}
+
+	fn best_chain(&self)
+		-> Result<<Block as BlockT>::Header, ConsensusError>
+	{
+		HeaviestChain::best_block_header(&self)
+			.map_err(|e| ConsensusErrorKind::ChainLookup(e.to_string()).into())
+	}
+
+	fn finality_target(
+		&self,
+		target_hash: Block::Hash,
+		maybe_max_number: Option<NumberFor<Block>>
+	) -> Result<Option<Block::Hash>, ConsensusError> {
+		HeaviestChain::best_containing(self, target_hash, maybe_max_number)
+			.map_err(|e| ConsensusErrorKind::ChainLookup(e.to_string()).into())
+	}
+}
+
+/// A `SelectChain` implementation picking one of the fork-choice rules
+/// provided by the client, so that it can be chosen when the service starts
+/// depending on the consensus engine in use.
+pub enum ChainSelection<B, Block> {
+	/// The longest chain building on the last finalized block.
+	Longest(LongestChain<B, Block>),
+	/// The heaviest chain by fork-choice weight building on the last finalized block.
+	Heaviest(HeaviestChain<B, Block>),
+}
+
+impl<B, Block> Clone for ChainSelection<B, Block> {
+	fn clone(&self) -> Self {
+		match self {
+			ChainSelection::Longest(inner) => ChainSelection::Longest(inner.clone()),
+			ChainSelection::Heaviest(inner) => ChainSelection::Heaviest(inner.clone()),
+		}
+	}
+}
+
+impl<B, Block> SelectChain<Block> for ChainSelection<B, Block>
+where
+	B: backend::Backend<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	fn leaves(&self) -> Result<Vec<<Block as BlockT>::Hash>, ConsensusError> {
+		match self {
+			ChainSelection::Longest(inner) => SelectChain::leaves(inner),
+			ChainSelection::Heaviest(inner) => SelectChain::leaves(inner),
+		}
+	}
+
+	fn best_chain(&self) -> Result<<Block as BlockT>::Header, ConsensusError> {
+		match self {
+			ChainSelection::Longest(inner) => inner.best_chain(),
+			ChainSelection::Heaviest(inner) => inner.best_chain(),
+		}
+	}
+
+	fn finality_target(
+		&self,
+		target_hash: Block::Hash,
+		maybe_max_number: Option<NumberFor<Block>>
+	) -> Result<Option<Block::Hash>, ConsensusError> {
+		match self {
+			ChainSelection::Longest(inner) => inner.finality_target(target_hash, maybe_max_number),
+			ChainSelection::Heaviest(inner) => inner.finality_target(target_hash, maybe_max_number),
+		}
+	}
+}
+
 impl<B, E, Block, RA> BlockBody<Block> for Client<B, E, Block, RA>
 	where
 		B: backend::Backend<Block, Blake2Hasher>,
@@ -1642,6 +2288,7 @@ impl<B, E, Block, RA> backend::AuxStore for Client<B, E, Block, RA>
 		crate::backend::AuxStore::get_aux(&*self.backend, key)
 	}
 }
+
 #[cfg(test)]
 pub(crate) mod tests {
 	use std::collections::HashMap;
@@ -1649,6 +2296,7 @@ pub(crate) mod tests {
 	use primitives::blake2_256;
 	use runtime_primitives::traits::DigestItem as DigestItemT;
 	use runtime_primitives::generic::DigestItem;
+	use runtime_primitives::{ApplyOutcome, ApplyError};
 	use test_client::{self, TestClient, AccountKeyring};
 	use consensus::{BlockOrigin, SelectChain};
 	use test_client::client::backend::Backend as TestBackend;
@@ -1792,6 +2440,97 @@ pub(crate) mod tests {
 		);
 	}
 
+	#[test]
+	fn replay_block_works() {
+		let client = test_client::new();
+
+		let mut builder = client.new_block().unwrap();
+		builder.push_transfer(Transfer {
+			from: AccountKeyring::Alice.into(),
+			to: AccountKeyring::Ferdie.into(),
+			amount: 42,
+			nonce: 0,
+		}).unwrap();
+		let block = builder.bake().unwrap();
+		let header = block.header().clone();
+		client.import(BlockOrigin::Own, block).unwrap();
+
+		let native = client.replay_block(&BlockId::Number(1), ExecutionStrategy::NativeWhenPossible).unwrap();
+		let wasm = client.replay_block(&BlockId::Number(1), ExecutionStrategy::AlwaysWasm).unwrap();
+		assert_eq!(native, wasm);
+		assert_eq!(native.extrinsic_results, vec![Ok(ApplyOutcome::Success)]);
+		assert_eq!(native.header.state_root(), header.state_root());
+		assert!(!native.storage_changes.is_empty());
+
+		// replay doesn't touch the database
+		assert_eq!(client.info().unwrap().chain.best_number, 1);
+		assert!(client.replay_block(&BlockId::Number(2), ExecutionStrategy::AlwaysWasm).is_err());
+	}
+
+	#[test]
+	fn trace_block_works() {
+		let client = test_client::new();
+
+		let mut builder = client.new_block().unwrap();
+		builder.push_transfer(Transfer {
+			from: AccountKeyring::Alice.into(),
+			to: AccountKeyring::Ferdie.into(),
+			amount: 42,
+			nonce: 0,
+		}).unwrap();
+		client.import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
+
+		let mut key_names = state_machine::execution_trace::StorageKeyNames::new();
+		key_names.insert(b"balance:".to_vec(), "balances".into());
+		let trace = client.trace_block(&BlockId::Number(1), key_names).unwrap();
+		let names: Vec<_> = trace.segments.iter().map(|segment| segment.name.as_str()).collect();
+		assert_eq!(names, vec!["initialize_block", "extrinsic 0", "finalize_block"]);
+
+		let transfer = &trace.segments[1];
+		assert_eq!(transfer.storage["balances"].reads, 2);
+		assert_eq!(transfer.storage["balances"].writes, 2);
+		assert!(transfer.storage.values().any(|stats| stats.reads > 0));
+		assert!(transfer.storage.values().any(|stats| stats.writes > 0));
+		assert!(transfer.host_functions.values().all(|stats| stats.calls > 0));
+		assert!(!transfer.host_functions.is_empty());
+	}
+
+	#[test]
+	fn call_session_keeps_changes_between_calls() {
+		let client = test_client::new();
+		let transfer = Transfer {
+			from: AccountKeyring::Alice.into(),
+			to: AccountKeyring::Ferdie.into(),
+			amount: 42,
+			nonce: 0,
+		}.into_signed_tx();
+		let header = <<Block as BlockT>::Header as HeaderT>::new(
+			1,
+			Default::default(),
+			Default::default(),
+			client.info().unwrap().chain.best_hash,
+			Default::default(),
+		);
+
+		let mut session = client.call_session(&BlockId::Number(0)).unwrap();
+		session.call("Core_initialize_block", &header.encode()).unwrap();
+		let (result, trace) = session.trace_call("BlockBuilder_apply_extrinsic", &transfer.encode()).unwrap();
+		assert_eq!(ApplyResult::decode(&mut &result[..]), Some(Ok(ApplyOutcome::Success)));
+		assert_eq!(trace.segments.len(), 1);
+		assert_eq!(trace.segments[0].name, "BlockBuilder_apply_extrinsic");
+		assert!(trace.segments[0].storage.values().any(|stats| stats.writes > 0));
+
+		// the nonce has been increased by the first transfer, also once it is committed
+		let result = session.call("BlockBuilder_apply_extrinsic", &transfer.encode()).unwrap();
+		assert_eq!(ApplyResult::decode(&mut &result[..]), Some(Err(ApplyError::Stale)));
+		let mut session = session.commit();
+		let result = session.call("BlockBuilder_apply_extrinsic", &transfer.encode()).unwrap();
+		assert_eq!(ApplyResult::decode(&mut &result[..]), Some(Err(ApplyError::Stale)));
+
+		// nothing is written to the database
+		assert_eq!(client.info().unwrap().chain.best_number, 0);
+	}
+
 	#[test]
 	fn block_builder_does_not_include_invalid() {
 		let client = test_client::new();
@@ -2363,4 +3102,123 @@ pub(crate) mod tests {
 			None,
 		);
 	}
+
+	#[test]
+	fn import_state_from_range_proofs() {
+		let client = test_client::new();
+
+		let mut builder = client.new_block().unwrap();
+		builder.push_transfer(Transfer {
+			from: AccountKeyring::Alice.into(),
+			to: AccountKeyring::Ferdie.into(),
+			amount: 42,
+			nonce: 0,
+		}).unwrap();
+		client.import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
+		let block = client.new_block().unwrap().bake().unwrap();
+		client.import(BlockOrigin::Own, block.clone()).unwrap();
+
+		let header = block.header().clone();
+		let mut top = StorageOverlay::new();
+		let mut start = Vec::new();
+		loop {
+			let proof = client.read_range_proof(&BlockId::Number(2), None, &start, 8).unwrap();
+			let (entries, complete) = check_range_proof(*header.state_root(), proof, &start).unwrap();
+			if let Some((last_key, _)) = entries.last() {
+				start = last_key.clone();
+				start.push(0);
+			}
+			top.extend(entries);
+			if complete {
+				break;
+			}
+		}
+
+		// state that doesn't match the header is rejected.
+		let other_client = test_client::new();
+		assert!(other_client.import_state(header.clone(), None, Default::default(), Default::default()).is_err());
+
+		other_client.import_state(header.clone(), None, top, Default::default()).unwrap();
+		let info = other_client.info().unwrap().chain;
+		assert_eq!(info.best_hash, header.hash());
+		assert_eq!(info.finalized_hash, header.hash());
+		assert_eq!(
+			other_client.runtime_api().balance_of(&BlockId::Number(2), AccountKeyring::Ferdie.into()).unwrap(),
+			42,
+		);
+
+		// the history preceding the imported state can be filled in.
+		let (header1, body1) = client.block(&BlockId::Number(1)).unwrap().unwrap().block.deconstruct();
+		other_client.import_history(header1.clone(), Some(body1), None).unwrap();
+		assert_eq!(other_client.header(&BlockId::Number(1)).unwrap(), Some(header1));
+		assert_eq!(other_client.info().unwrap().chain.best_number, 2);
+
+		// the chain continues on top of the imported state.
+		let block = other_client.new_block().unwrap().bake().unwrap();
+		other_client.import(BlockOrigin::Own, block).unwrap();
+		assert_eq!(other_client.info().unwrap().chain.best_number, 3);
+	}
+
+	#[test]
+	fn heaviest_chain_fork_choice() {
+		// block tree:
+		// G -> A1 -> A2 -> A3
+		//   -> B1
+		use consensus::BlockImport;
+
+		let client = test_client::new();
+
+		let import_with_weight = |block: Block, weight: BlockWeight| {
+			let (header, extrinsics) = block.deconstruct();
+			client.import_block(ImportBlock {
+				origin: BlockOrigin::Own,
+				header,
+				justification: None,
+				post_digests: vec![],
+				body: Some(extrinsics),
+				finalized: false,
+				auxiliary: Vec::new(),
+				fork_choice: ForkChoiceStrategy::HeaviestChain(weight),
+			}, HashMap::new()).unwrap();
+		};
+
+		let a1 = client.new_block().unwrap().bake().unwrap();
+		import_with_weight(a1.clone(), 1);
+		let a2 = client.new_block_at(&BlockId::Hash(a1.hash())).unwrap().bake().unwrap();
+		import_with_weight(a2.clone(), 1);
+		let a3 = client.new_block_at(&BlockId::Hash(a2.hash())).unwrap().bake().unwrap();
+		import_with_weight(a3.clone(), 2);
+
+		assert_eq!(load_block_weight(&**client.backend(), &a3.hash()).unwrap(), Some(2));
+		assert_eq!(client.info().unwrap().chain.best_hash, a3.hash());
+
+		// a shorter but heavier fork becomes the best chain.
+		let mut builder = client.new_block_at(&BlockId::Number(0)).unwrap();
+		// this push is required as otherwise B1 has the same hash as A1 and won't get imported
+		builder.push_transfer(Transfer {
+			from: AccountKeyring::Alice.into(),
+			to: AccountKeyring::Ferdie.into(),
+			amount: 41,
+			nonce: 0,
+		}).unwrap();
+		let b1 = builder.bake().unwrap();
+		import_with_weight(b1.clone(), 3);
+
+		assert_eq!(client.info().unwrap().chain.best_hash, b1.hash());
+
+		let heaviest_chain_select = HeaviestChain::new(client.backend().clone());
+		assert_eq!(heaviest_chain_select.best_chain().unwrap().hash(), b1.hash());
+		assert_eq!(heaviest_chain_select.leaves().unwrap(), vec![b1.hash(), a3.hash()]);
+
+		let genesis_hash = client.info().unwrap().chain.genesis_hash;
+		assert_eq!(heaviest_chain_select.finality_target(genesis_hash, None).unwrap(), Some(b1.hash()));
+		assert_eq!(heaviest_chain_select.finality_target(a1.hash(), None).unwrap(), Some(a3.hash()));
+		assert_eq!(heaviest_chain_select.finality_target(a1.hash(), Some(2)).unwrap(), Some(a2.hash()));
+
+		// once A1 is finalized the heavier fork is dead.
+		client.finalize_block(BlockId::Hash(a1.hash()), None, false).unwrap();
+		assert_eq!(heaviest_chain_select.best_chain().unwrap().hash(), a3.hash());
+		assert_eq!(heaviest_chain_select.leaves().unwrap(), vec![a3.hash()]);
+		assert_eq!(heaviest_chain_select.finality_target(b1.hash(), None).unwrap(), None);
+	}
 }
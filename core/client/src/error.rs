@@ -61,6 +61,9 @@ pub enum Error {
 	/// Not available on light client.
 	#[display(fmt = "This method is not currently available when running in light client mode")]
 	NotAvailableOnLightClient,
+	/// Imported state doesn't match the state root of the header.
+	#[display(fmt = "Imported state doesn't match the state root of the header")]
+	InvalidStateRoot,
 	/// Invalid remote CHT-based proof.
 	#[display(fmt = "Remote node has responded with invalid header proof")]
 	InvalidCHTProof,
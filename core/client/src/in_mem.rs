@@ -19,6 +19,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 use parking_lot::RwLock;
+use parity_codec::Decode;
 use primitives::{ChangesTrieConfiguration, storage::well_known_keys};
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, Zero,
@@ -177,6 +178,47 @@ impl<Block: BlockT> Blockchain<Block> {
 		Ok(())
 	}
 
+	/// Insert a block which becomes the new base of the chain. The block is both the best
+	/// and the last finalized block and its ancestors are not required to be known.
+	pub fn insert_state_base(
+		&self,
+		hash: Block::Hash,
+		header: <Block as BlockT>::Header,
+		justification: Option<Justification>,
+		body: Option<Vec<<Block as BlockT>::Extrinsic>>,
+	) -> crate::error::Result<()> {
+		let number = header.number().clone();
+
+		let mut storage = self.storage.write();
+		storage.leaves.import(hash.clone(), number.clone(), header.parent_hash().clone());
+		storage.blocks.insert(hash.clone(), StoredBlock::new(header, body, justification));
+		storage.hashes.insert(number.clone(), hash.clone());
+		storage.best_hash = hash.clone();
+		storage.best_number = number.clone();
+		storage.finalized_hash = hash;
+		storage.finalized_number = number;
+
+		Ok(())
+	}
+
+	/// Insert a block which precedes the base of the chain. The best and the finalized
+	/// blocks are not affected.
+	pub fn insert_history(
+		&self,
+		hash: Block::Hash,
+		header: <Block as BlockT>::Header,
+		justification: Option<Justification>,
+		body: Option<Vec<<Block as BlockT>::Extrinsic>>,
+	) -> crate::error::Result<()> {
+		let number = header.number().clone();
+
+		let mut storage = self.storage.write();
+		storage.blocks.insert(hash.clone(), StoredBlock::new(header, body, justification));
+		storage.hashes.insert(number, hash);
+
+		Ok(())
+	}
+
 	/// Get total number of blocks.
 	pub fn blocks_count(&self) -> usize {
 		self.storage.read().blocks.len()
@@ -435,9 +477,12 @@ pub struct BlockImportOperation<Block: BlockT, H: Hasher> {
 	old_state: InMemory<H>,
 	new_state: Option<InMemory<H>>,
 	changes_trie_update: Option<MemoryDB<H>>,
+	changes_trie_config_update: Option<Option<ChangesTrieConfiguration>>,
 	aux: Vec<(Vec<u8>, Option<Vec<u8>>)>,
 	finalized_blocks: Vec<(BlockId<Block>, Option<Justification>)>,
 	set_head: Option<BlockId<Block>>,
+	state_base: bool,
+	history: bool,
 }
 
 impl<Block, H> backend::BlockImportOperation<Block, H> for BlockImportOperation<Block, H>
@@ -485,6 +530,9 @@ where
 	fn reset_storage(&mut self, top: StorageOverlay, children: ChildrenStorageOverlay) -> error::Result<H::Out> {
 		check_genesis_storage(&top, &children)?;
 
+		self.changes_trie_config_update = Some(top.get(well_known_keys::CHANGES_TRIE_CONFIG)
+			.and_then(|config| Decode::decode(&mut &config[..])));
+
 		let child_delta = children.into_iter()
 			.map(|(storage_key, child_overlay)|
 				(storage_key, child_overlay.into_iter().map(|(k, v)| (k, Some(v)))));
@@ -509,6 +557,10 @@ where
 		Ok(())
 	}
 
+	fn update_child_storage(&mut self, _update: Vec<(Vec<u8>, Vec<(Vec<u8>, Option<Vec<u8>>)>)>) -> error::Result<()> {
+		Ok(())
+	}
+
 	fn mark_finalized(&mut self, block: BlockId<Block>, justification: Option<Justification>) -> error::Result<()> {
 		self.finalized_blocks.push((block, justification));
 		Ok(())
@@ -519,6 +571,16 @@ where
 		self.set_head = Some(block);
 		Ok(())
 	}
+
+	fn mark_state_base(&mut self) -> error::Result<()> {
+		self.state_base = true;
+		Ok(())
+	}
+
+	fn mark_history(&mut self) -> error::Result<()> {
+		self.history = true;
+		Ok(())
+	}
 }
 
 /// In-memory backend. Keeps all states and blocks in memory. Useful for testing.
@@ -589,9 +651,12 @@ where
 			old_state,
 			new_state: None,
 			changes_trie_update: None,
+			changes_trie_config_update: None,
 			aux: Default::default(),
 			finalized_blocks: Default::default(),
 			set_head: None,
+			state_base: false,
+			history: false,
 		})
 	}
 
@@ -613,17 +678,36 @@ where
 
 			let hash = header.hash();
 
-			self.states.write().insert(hash, operation.new_state.unwrap_or_else(|| old_state.clone()));
+			if operation.history {
+				// history blocks have no state.
+				self.blockchain.insert_history(hash, header, justification, body)?;
+			} else {
+				self.states.write().insert(hash, operation.new_state.unwrap_or_else(|| old_state.clone()));
+
+				let changes_trie_root = header.digest().log(DigestItem::as_changes_trie_root).cloned();
+				if let Some(changes_trie_root) = changes_trie_root {
+					if let Some(changes_trie_update) = operation.changes_trie_update {
+						let changes_trie_root: H::Out = changes_trie_root.into();
+						self.changes_trie_storage.0.insert(header.number().as_(), changes_trie_root, changes_trie_update);
+					}
+				}
+
+				// configuration is either set at genesis or signaled by the runtime
+				let changes_trie_config_update = operation.changes_trie_config_update.or_else(||
+					header.digest().log(DigestItem::as_changes_trie_signal)
+						.and_then(|signal| signal.as_new_configuration())
+						.cloned()
+				);
+				if let Some(changes_trie_config) = changes_trie_config_update {
+					self.changes_trie_storage.0.set_configuration(header.number().as_(), changes_trie_config);
+				}
 
-			let changes_trie_root = header.digest().log(DigestItem::as_changes_trie_root).cloned();
-			if let Some(changes_trie_root) = changes_trie_root {
-				if let Some(changes_trie_update) = operation.changes_trie_update {
-					let changes_trie_root: H::Out = changes_trie_root.into();
-					self.changes_trie_storage.0.insert(header.number().as_(), changes_trie_root, changes_trie_update);
+				if operation.state_base {
+					self.blockchain.insert_state_base(hash, header, justification, body)?;
+				} else {
+					self.blockchain.insert(hash, header, justification, body, pending_block.state)?;
 				}
 			}
-
-			self.blockchain.insert(hash, header, justification, body, pending_block.state)?;
 		}
 
 		if !operation.aux.is_empty() {
@@ -649,6 +733,10 @@ where
 		None
 	}
 
+	fn state_cache_stats(&self) -> Option<backend::StateCacheStats> {
+		None
+	}
+
 	fn changes_trie_storage(&self) -> Option<&Self::ChangesTrieStorage> {
 		Some(&self.changes_trie_storage)
 	}
@@ -695,8 +783,8 @@ where
 /// Prunable in-memory changes trie storage.
 pub struct ChangesTrieStorage<H: Hasher>(InMemoryChangesTrieStorage<H>);
 impl<H: Hasher> backend::PrunableStateChangesTrieStorage<H> for ChangesTrieStorage<H> {
-	fn oldest_changes_trie_block(&self, _config: &ChangesTrieConfiguration, _best_finalized: u64) -> u64 {
-		0
+	fn oldest_changes_trie_block(&self, _best_finalized: &ChangesTrieAnchorBlockId<H::Out>) -> error::Result<u64> {
+		Ok(0)
 	}
 }
 
@@ -704,6 +792,14 @@ impl<H: Hasher> state_machine::ChangesTrieRootsStorage<H> for ChangesTrieStorage
 	fn root(&self, anchor: &ChangesTrieAnchorBlockId<H::Out>, block: u64) -> Result<Option<H::Out>, String> {
 		self.0.root(anchor, block)
 	}
+
+	fn configuration_ranges(
+		&self,
+		anchor: &ChangesTrieAnchorBlockId<H::Out>,
+		begin: u64,
+	) -> Result<Vec<state_machine::ChangesTrieConfigurationRange>, String> {
+		self.0.configuration_ranges(anchor, begin)
+	}
 }
 
 impl<H: Hasher> state_machine::ChangesTrieStorage<H> for ChangesTrieStorage<H> {
@@ -57,15 +57,18 @@ pub use crate::call_executor::{CallExecutor, LocalCallExecutor};
 pub use crate::client::{
 	new_with_backend,
 	new_in_mem,
+	check_range_proof,
 	BlockBody, BlockStatus, ImportNotifications, FinalityNotifications, BlockchainEvents,
 	BlockImportNotification, Client, ClientInfo, ExecutionStrategies,
-	LongestChain
+	LongestChain, HeaviestChain, ChainSelection, ReplayedBlock, CallSession, load_block_weight,
 };
 #[cfg(feature = "std")]
 pub use crate::notifications::{StorageEventStream, StorageChangeSet};
 #[cfg(feature = "std")]
 pub use state_machine::ExecutionStrategy;
 #[cfg(feature = "std")]
+pub use state_machine::execution_trace::ExecutionTrace;
+#[cfg(feature = "std")]
 pub use crate::leaves::LeafSet;
 
 #[doc(inline)]
@@ -26,7 +26,9 @@ use runtime_primitives::{generic::BlockId, Justification, StorageOverlay, Childr
 use state_machine::{Backend as StateBackend, TrieBackend, backend::InMemory as InMemoryState};
 use runtime_primitives::traits::{Block as BlockT, NumberFor, Zero, Header};
 use crate::in_mem::{self, check_genesis_storage};
-use crate::backend::{AuxStore, Backend as ClientBackend, BlockImportOperation, RemoteBackend, NewBlockState};
+use crate::backend::{
+	AuxStore, Backend as ClientBackend, BlockImportOperation, RemoteBackend, NewBlockState, StateCacheStats,
+};
 use crate::blockchain::HeaderBackend as BlockchainHeaderBackend;
 use crate::error::{Error as ClientError, Result as ClientResult};
 use crate::light::blockchain::{Blockchain, Storage as BlockchainStorage};
@@ -186,6 +188,10 @@ impl<S, F, Block, H> ClientBackend<Block, H> for Backend<S, F, H> where
 		None
 	}
 
+	fn state_cache_stats(&self) -> Option<StateCacheStats> {
+		None
+	}
+
 	fn changes_trie_storage(&self) -> Option<&Self::ChangesTrieStorage> {
 		None
 	}
@@ -300,6 +306,11 @@ where
 		Ok(())
 	}
 
+	fn update_child_storage(&mut self, _update: Vec<(Vec<u8>, Vec<(Vec<u8>, Option<Vec<u8>>)>)>) -> ClientResult<()> {
+		// we're not storing anything locally => ignore changes
+		Ok(())
+	}
+
 	fn mark_finalized(&mut self, block: BlockId<Block>, _justification: Option<Justification>) -> ClientResult<()> {
 		self.finalized_blocks.push(block);
 		Ok(())
@@ -309,6 +320,15 @@ where
 		self.set_head = Some(block);
 		Ok(())
 	}
+
+	fn mark_state_base(&mut self) -> ClientResult<()> {
+		// light client keeps no state to start the chain from
+		Err(ClientError::NotAvailableOnLightClient)
+	}
+
+	fn mark_history(&mut self) -> ClientResult<()> {
+		Err(ClientError::NotAvailableOnLightClient)
+	}
 }
 
 impl<Block, S, F, H> StateBackend<H> for OnDemandState<Block, S, F>
@@ -22,9 +22,9 @@ use std::marker::PhantomData;
 use futures::IntoFuture;
 
 use hash_db::{HashDB, Hasher};
-use primitives::{ChangesTrieConfiguration, convert_hash};
+use primitives::convert_hash;
 use runtime_primitives::traits::{As, Block as BlockT, Header as HeaderT, NumberFor};
-use state_machine::{CodeExecutor, ChangesTrieRootsStorage, ChangesTrieAnchorBlockId,
+use state_machine::{CodeExecutor, ChangesTrieRootsStorage, ChangesTrieAnchorBlockId, ChangesTrieConfigurationRange,
 	TrieBackend, read_proof_check, key_changes_proof_check,
 	create_proof_check_backend_storage, read_child_proof_check};
 
@@ -90,8 +90,9 @@ pub struct RemoteReadChildRequest<Header: HeaderT> {
 /// Remote key changes read request.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RemoteChangesRequest<Header: HeaderT> {
-	/// Changes trie configuration.
-	pub changes_trie_config: ChangesTrieConfiguration,
+	/// Changes trie configurations that are active at the range of blocks
+	/// [first_block..max_block].
+	pub changes_trie_configs: Vec<ChangesTrieConfigurationRange>,
 	/// Query changes from range of blocks, starting (and including) with this hash...
 	pub first_block: (Header::Number, Header::Hash),
 	/// ...ending (and including) with this hash. Should come after first_block and
@@ -260,23 +261,35 @@ impl<E, H, B: BlockT, S: BlockchainStorage<B>, F> LightDataChecker<E, H, B, S, F
 			)?;
 		}
 
+		if request.changes_trie_configs.is_empty() {
+			return Err(ClientError::ChangesTriesNotSupported);
+		}
+
 		// and now check the key changes proof + get the changes
-		key_changes_proof_check::<_, H>(
-			&request.changes_trie_config,
-			&RootsStorage {
-				roots: (request.tries_roots.0, &request.tries_roots.2),
-				prev_roots: remote_roots,
-			},
-			remote_proof,
-			request.first_block.0.as_(),
-			&ChangesTrieAnchorBlockId {
-				hash: convert_hash(&request.last_block.1),
-				number: request.last_block.0.as_(),
-			},
-			remote_max_block.as_(),
-			&request.key)
-		.map(|pairs| pairs.into_iter().map(|(b, x)| (As::sa(b), x)).collect())
-		.map_err(|err| ClientError::ChangesTrieAccessFailed(err))
+		// (the same proof covers all configuration ranges)
+		let roots_storage = RootsStorage {
+			roots: (request.tries_roots.0, &request.tries_roots.2),
+			prev_roots: remote_roots,
+		};
+		let anchor = ChangesTrieAnchorBlockId {
+			hash: convert_hash(&request.last_block.1),
+			number: request.last_block.0.as_(),
+		};
+		let mut result = Vec::new();
+		for config_range in request.changes_trie_configs.iter().rev() {
+			let range_result = key_changes_proof_check::<_, H>(
+				config_range,
+				&roots_storage,
+				remote_proof.clone(),
+				request.first_block.0.as_(),
+				&anchor,
+				remote_max_block.as_(),
+				&request.key,
+			).map_err(|err| ClientError::ChangesTrieAccessFailed(err))?;
+			result.extend(range_result.into_iter().map(|(b, x)| (As::sa(b), x)));
+		}
+
+		Ok(result)
 	}
 
 	/// Check CHT-based proof for changes tries roots.
@@ -426,6 +439,15 @@ impl<'a, H, Number, Hash> ChangesTrieRootsStorage<H> for RootsStorage<'a, Number
 			hasher_root
 		}))
 	}
+
+	fn configuration_ranges(
+		&self,
+		_anchor: &ChangesTrieAnchorBlockId<H::Out>,
+		_begin: u64,
+	) -> Result<Vec<ChangesTrieConfigurationRange>, String> {
+		// configurations are provided by the request
+		Err("Changes trie configurations are not available in the roots storage".into())
+	}
 }
 
 #[cfg(test)]
@@ -454,6 +476,10 @@ pub mod tests {
 
 	pub type OkCallFetcher = Mutex<Vec<u8>>;
 
+	fn changes_trie_configs() -> Vec<ChangesTrieConfigurationRange> {
+		vec![ChangesTrieConfigurationRange { config: runtime::changes_trie_config(), zero: 0, end: None }]
+	}
+
 	impl Fetcher<Block> for OkCallFetcher {
 		type RemoteHeaderResult = FutureResult<Header, ClientError>;
 		type RemoteReadResult = FutureResult<Option<Vec<u8>>, ClientError>;
@@ -604,7 +630,7 @@ pub mod tests {
 			// check proof on local client
 			let local_roots_range = local_roots.clone()[(begin - 1) as usize..].to_vec();
 			let request = RemoteChangesRequest::<Header> {
-				changes_trie_config: runtime::changes_trie_config(),
+				changes_trie_configs: changes_trie_configs(),
 				first_block: (begin, begin_hash),
 				last_block: (end, end_hash),
 				max_block: (max, max_hash),
@@ -658,7 +684,7 @@ pub mod tests {
 
 		// check proof on local client
 		let request = RemoteChangesRequest::<Header> {
-			changes_trie_config: runtime::changes_trie_config(),
+			changes_trie_configs: changes_trie_configs(),
 			first_block: (1, b1),
 			last_block: (4, b4),
 			max_block: (4, b4),
@@ -698,7 +724,7 @@ pub mod tests {
 
 		let local_roots_range = local_roots.clone()[(begin - 1) as usize..].to_vec();
 		let request = RemoteChangesRequest::<Header> {
-			changes_trie_config: runtime::changes_trie_config(),
+			changes_trie_configs: changes_trie_configs(),
 			first_block: (begin, begin_hash),
 			last_block: (end, end_hash),
 			max_block: (max, max_hash),
@@ -28,7 +28,7 @@ pub use runtime_primitives::{
 		AuthorityIdFor, Block as BlockT, GetNodeBlockType, GetRuntimeBlockType,
 		Header as HeaderT, ApiRef, RuntimeApiInfo, Hash as HashT,
 	},
-	generic::BlockId, transaction_validity::TransactionValidity,
+	generic::BlockId, transaction_validity::TransactionValidity, weights::TransactionFee,
 };
 #[doc(hidden)]
 pub use primitives::{ExecutionContext, OffchainExt};
@@ -158,8 +158,10 @@ pub trait CallRuntimeAt<Block: BlockT> {
 
 decl_runtime_apis! {
 	/// The `Core` api trait that is mandatory for each runtime.
+	///
+	/// Runtimes built against version 3 or later are executed with the reclaiming heap allocator.
 	#[core_trait]
-	#[api_version(2)]
+	#[api_version(3)]
 	pub trait Core {
 		/// Returns the version of the runtime.
 		fn version() -> RuntimeVersion;
@@ -187,5 +189,23 @@ decl_runtime_apis! {
 		/// Validate the given transaction.
 		fn validate_transaction(tx: <Block as BlockT>::Extrinsic) -> TransactionValidity;
 	}
+
+	/// The `TransactionPaymentApi` api trait for querying the fees of the transactions.
+	pub trait TransactionPaymentApi {
+		/// The weight and the fee of the given transaction if it were included in the next block.
+		/// `None` if the transaction pays no fee, e.g. if it is unsigned.
+		fn query_fee(tx: <Block as BlockT>::Extrinsic) -> Option<TransactionFee>;
+	}
 }
 
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use test_client::runtime::Block;
+
+	#[test]
+	fn executor_knows_the_core_api_id() {
+		assert_eq!(executor::core_api_id(), <dyn Core<Block> as RuntimeApiInfo>::ID);
+	}
+}
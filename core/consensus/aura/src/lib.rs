@@ -33,7 +33,9 @@ use consensus_common::{self, Authorities, BlockImport, Environment, Proposer,
 	ForkChoiceStrategy, ImportBlock, BlockOrigin, Error as ConsensusError,
 	SelectChain, well_known_cache_keys
 };
-use consensus_common::import_queue::{Verifier, BasicQueue, SharedBlockImport, SharedJustificationImport};
+use consensus_common::import_queue::{
+	Verifier, BasicQueue, SharedBlockImport, SharedJustificationImport, PreVerifiedBlocks,
+};
 use client::{
 	block_builder::api::BlockBuilder as BlockBuilderApi,
 	blockchain::ProvideCache,
@@ -52,6 +54,7 @@ use authorities::AuthoritiesApi;
 
 use futures::{Future, IntoFuture, future, stream::Stream};
 use tokio::timer::Timeout;
+use parking_lot::Mutex;
 use log::{warn, debug, info, trace};
 
 use srml_aura::{
@@ -60,7 +63,8 @@ use srml_aura::{
 };
 use substrate_telemetry::{telemetry, CONSENSUS_TRACE, CONSENSUS_DEBUG, CONSENSUS_WARN, CONSENSUS_INFO};
 
-use slots::{CheckedHeader, SlotWorker, SlotInfo, SlotCompatible, slot_now};
+use slots::{CheckedHeader, SlotWorker, SlotInfo, SlotCompatible, slot_now, check_equivocation};
+use srml_consensus::{EquivocationReports, EQUIVOCATION_INHERENT_IDENTIFIER};
 
 pub use aura_primitives::*;
 pub use consensus_common::{SyncOracle, ExtraVerification};
@@ -376,7 +380,11 @@ impl<B: Block, C, E, I, P, Error, SO> SlotWorker<B> for AuraWorker<C, E, I, P, S
 				// deadline our production to approx. the end of the
 				// slot
 				Timeout::new(
-					proposer.propose(slot_info.inherent_data, remaining_duration).into_future(),
+					proposer.propose(
+						slot_info.inherent_data,
+						Default::default(),
+						remaining_duration,
+					).into_future(),
 					remaining_duration,
 				)
 			} else {
@@ -453,19 +461,26 @@ impl<B: Block, C, E, I, P, Error, SO> SlotWorker<B> for AuraWorker<C, E, I, P, S
 /// check a header has been signed by the right key. If the slot is too far in the future, an error will be returned.
 /// if it's successful, returns the pre-header and the digest item containing the seal.
 ///
+/// The signature isn't checked again if the header has been pre-verified as signed by the
+/// expected author.
+///
 /// This digest item will always return `Some` when used with `as_aura_seal`.
 //
 // FIXME #1018 needs misbehavior types
-fn check_header<B: Block, P: Pair>(
+fn check_header<C, B: Block, P: Pair>(
+	client: &C,
 	slot_now: u64,
 	mut header: B::Header,
 	hash: B::Hash,
 	authorities: &[AuthorityId<P>],
+	pre_verified_author: Option<&AuthorityId<P>>,
 	allow_old_seals: bool,
+	equivocation_reports: &EquivocationReports,
 ) -> Result<CheckedHeader<B::Header, DigestItemFor<B>>, String>
 	where DigestItemFor<B>: CompatibleDigestItem<P>,
-		P::Public: AsRef<P::Public>,
+		P::Public: Clone + Encode + Decode + PartialEq + Debug + AsRef<P::Public>,
 		P::Signature: Decode,
+		C: AuxStore,
 {
 	let digest_item = match header.digest_mut().pop() {
 		Some(x) => x,
@@ -493,11 +508,37 @@ fn check_header<B: Block, P: Pair>(
 			Some(author) => author,
 		};
 
-		let pre_hash = header.hash();
-		let to_sign = (slot_num, pre_hash).encode();
 		let public = expected_author;
 
-		if P::verify(&sig, &to_sign[..], public) {
+		if pre_verified_author == Some(public) || check_signature::<B, P>(&header, slot_num, &sig, public) {
+			// the seal is part of what the authority signed off on, so it is
+			// recorded along with the header.
+			header.digest_mut().push(digest_item);
+			if let Some(equivocation_proof) = check_equivocation(
+				client,
+				slot_now,
+				slot_num,
+				&header,
+				public,
+			).map_err(|e| e.to_string())? {
+				warn!(
+					target: "aura",
+					"Slot author {:?} is equivocating at slot {} with headers {:?} and {:?}",
+					public,
+					slot_num,
+					equivocation_proof.first_header.hash(),
+					equivocation_proof.second_header.hash(),
+				);
+				telemetry!(CONSENSUS_WARN; "aura.equivocation_detected";
+					"slot" => slot_num,
+					"first_header" => ?equivocation_proof.first_header.hash(),
+					"second_header" => ?equivocation_proof.second_header.hash()
+				);
+				equivocation_reports.push(equivocation_proof.encode());
+			}
+
+			let digest_item = header.digest_mut().pop()
+				.expect("the seal has just been pushed back; qed");
 			Ok(CheckedHeader::Checked(header, digest_item))
 		} else {
 			Err(format!("Bad signature on {:?}", hash))
@@ -505,18 +546,74 @@ fn check_header<B: Block, P: Pair>(
 	}
 }
 
+/// Check the signature of the pre-header by the author of the slot.
+fn check_signature<B: Block, P: Pair>(
+	pre_header: &B::Header,
+	slot_num: u64,
+	sig: &Signature<P>,
+	author: &AuthorityId<P>,
+) -> bool where P::Public: AsRef<P::Public> {
+	let to_sign = (slot_num, pre_header.hash()).encode();
+	P::verify(sig, &to_sign[..], author)
+}
+
+/// Check the seal of the header against the given authorities, ignoring the slot. Returns the
+/// author of the header.
+fn check_seal<B: Block, P: Pair>(
+	header: &B::Header,
+	authorities: &[AuthorityId<P>],
+) -> Option<AuthorityId<P>>
+	where DigestItemFor<B>: CompatibleDigestItem<P>,
+		P::Public: Clone + AsRef<P::Public>,
+		P::Signature: Decode,
+{
+	let mut pre_header = header.clone();
+	let (slot_num, sig) = pre_header.digest_mut().pop()?.as_aura_seal()?;
+	let author = slot_author::<P>(slot_num, authorities)?;
+	if check_signature::<B, P>(&pre_header, slot_num, &sig, author) {
+		Some(author.clone())
+	} else {
+		None
+	}
+}
+
 /// A verifier for Aura blocks.
-pub struct AuraVerifier<C, E, P> {
+pub struct AuraVerifier<C, E, P: Pair> {
 	client: Arc<C>,
 	extra: E,
 	phantom: PhantomData<P>,
 	inherent_data_providers: inherents::InherentDataProviders,
 	allow_old_seals: bool,
+	equivocation_reports: EquivocationReports,
+	/// The authorities at the parent of the last verified block, used to pre-verify blocks
+	/// whose parent isn't imported yet.
+	last_authorities: Mutex<Vec<AuthorityId<P>>>,
+	/// The (encoded) hashes of the pre-verified blocks, along with their authors.
+	pre_verified: PreVerifiedBlocks<Vec<u8>, AuthorityId<P>>,
 }
 
-impl<C, E, P> AuraVerifier<C, E, P>
+impl<C, E, P: Pair> AuraVerifier<C, E, P>
 	where P: Send + Sync + 'static
 {
+	fn new(
+		client: Arc<C>,
+		extra: E,
+		inherent_data_providers: InherentDataProviders,
+		allow_old_seals: bool,
+		equivocation_reports: EquivocationReports,
+	) -> Self {
+		AuraVerifier {
+			client,
+			extra,
+			phantom: PhantomData,
+			inherent_data_providers,
+			allow_old_seals,
+			equivocation_reports,
+			last_authorities: Mutex::new(Vec::new()),
+			pre_verified: Default::default(),
+		}
+	}
+
 	fn check_inherents<B: Block>(
 		&self,
 		block: B,
@@ -580,7 +677,7 @@ impl<B: Block> ExtraVerification<B> for NothingExtra {
 
 #[forbid(deprecated)]
 impl<B: Block, C, E, P> Verifier<B> for AuraVerifier<C, E, P> where
-	C: ProvideRuntimeApi + Send + Sync,
+	C: ProvideRuntimeApi + AuxStore + Send + Sync,
 	C::Api: BlockBuilderApi<B>,
 	DigestItemFor<B>: CompatibleDigestItem<P> + DigestItem<AuthorityId=AuthorityId<P>>,
 	E: ExtraVerification<B>,
@@ -603,6 +700,8 @@ impl<B: Block, C, E, P> Verifier<B> for AuraVerifier<C, E, P> where
 		let parent_hash = *header.parent_hash();
 		let authorities = self.authorities(&BlockId::Hash(parent_hash))
 			.map_err(|e| format!("Could not fetch authorities at {:?}: {:?}", parent_hash, e))?;
+		*self.last_authorities.lock() = authorities.clone();
+		let pre_verified_author = self.pre_verified.take(&hash.encode());
 
 		let extra_verification = self.extra.verify(
 			&header,
@@ -611,12 +710,15 @@ impl<B: Block, C, E, P> Verifier<B> for AuraVerifier<C, E, P> where
 
 		// we add one to allow for some small drift.
 		// FIXME #1019 in the future, alter this queue to allow deferring of headers
-		let checked_header = check_header::<B, P>(
+		let checked_header = check_header::<C, B, P>(
+			&*self.client,
 			slot_now + 1,
 			header,
 			hash,
 			&authorities[..],
+			pre_verified_author.as_ref(),
 			self.allow_old_seals,
+			&self.equivocation_reports,
 		)?;
 		match checked_header {
 			CheckedHeader::Checked(pre_header, seal) => {
@@ -676,9 +778,27 @@ impl<B: Block, C, E, P> Verifier<B> for AuraVerifier<C, E, P> where
 			}
 		}
 	}
+
+	fn can_pre_verify(&self) -> bool {
+		true
+	}
+
+	fn pre_verify(&self, _origin: BlockOrigin, header: &B::Header) {
+		// the parent is likely not imported yet, in which case the signature is checked
+		// against the last known authorities; `verify` checks it again if the authorities
+		// at the parent turn out to be different.
+		let authorities = match self.authorities(&BlockId::Hash(*header.parent_hash())) {
+			Ok(authorities) => authorities,
+			Err(_) => self.last_authorities.lock().clone(),
+		};
+
+		if let Some(author) = check_seal::<B, P>(header, &authorities) {
+			self.pre_verified.insert(header.hash().encode(), author);
+		}
+	}
 }
 
-impl<B, C, E, P> Authorities<B> for AuraVerifier<C, E, P> where
+impl<B, C, E, P: Pair> Authorities<B> for AuraVerifier<C, E, P> where
 	B: Block,
 	C: ProvideRuntimeApi + ProvideCache<B>,
 	C::Api: AuthoritiesApi<B>,
@@ -726,6 +846,21 @@ fn register_aura_inherent_data_provider(
 	}
 }
 
+/// Register the given equivocation reports as inherent data provider, so that
+/// block authors include them. Does nothing if reports are registered already.
+fn register_equivocation_reports(
+	inherent_data_providers: &InherentDataProviders,
+	equivocation_reports: &EquivocationReports,
+) -> Result<(), consensus_common::Error> {
+	if !inherent_data_providers.has_provider(&EQUIVOCATION_INHERENT_IDENTIFIER) {
+		inherent_data_providers
+			.register_provider(equivocation_reports.clone())
+			.map_err(inherent_to_common_error)
+	} else {
+		Ok(())
+	}
+}
+
 /// Start an import queue for the Aura consensus algorithm.
 pub fn import_queue<B, C, E, P>(
 	slot_duration: SlotDuration,
@@ -736,7 +871,7 @@ pub fn import_queue<B, C, E, P>(
 	inherent_data_providers: InherentDataProviders,
 ) -> Result<AuraImportQueue<B>, consensus_common::Error> where
 	B: Block,
-	C: 'static + ProvideRuntimeApi + ProvideCache<B> + Send + Sync,
+	C: 'static + ProvideRuntimeApi + ProvideCache<B> + AuxStore + Send + Sync,
 	C::Api: BlockBuilderApi<B> + AuthoritiesApi<B>,
 	DigestItemFor<B>: CompatibleDigestItem<P> + DigestItem<AuthorityId=AuthorityId<P>>,
 	E: 'static + ExtraVerification<B>,
@@ -745,16 +880,16 @@ pub fn import_queue<B, C, E, P>(
 	P::Signature: Encode + Decode,
 {
 	register_aura_inherent_data_provider(&inherent_data_providers, slot_duration.get())?;
-
-	let verifier = Arc::new(
-		AuraVerifier {
-			client: client.clone(),
-			extra,
-			inherent_data_providers,
-			phantom: PhantomData,
-			allow_old_seals: false,
-		}
-	);
+	let equivocation_reports = EquivocationReports::new();
+	register_equivocation_reports(&inherent_data_providers, &equivocation_reports)?;
+
+	let verifier = Arc::new(AuraVerifier::new(
+		client.clone(),
+		extra,
+		inherent_data_providers,
+		false,
+		equivocation_reports,
+	));
 	Ok(BasicQueue::new(verifier, block_import, justification_import))
 }
 
@@ -772,7 +907,7 @@ pub fn import_queue_accept_old_seals<B, C, E, P>(
 	inherent_data_providers: InherentDataProviders,
 ) -> Result<AuraImportQueue<B>, consensus_common::Error> where
 	B: Block,
-	C: 'static + ProvideRuntimeApi + ProvideCache<B> + Send + Sync,
+	C: 'static + ProvideRuntimeApi + ProvideCache<B> + AuxStore + Send + Sync,
 	C::Api: BlockBuilderApi<B> + AuthoritiesApi<B>,
 	DigestItemFor<B>: CompatibleDigestItem<P> + DigestItem<AuthorityId=AuthorityId<P>>,
 	E: 'static + ExtraVerification<B>,
@@ -781,16 +916,16 @@ pub fn import_queue_accept_old_seals<B, C, E, P>(
 	P::Signature: Encode + Decode,
 {
 	register_aura_inherent_data_provider(&inherent_data_providers, slot_duration.get())?;
-
-	let verifier = Arc::new(
-		AuraVerifier {
-			client: client.clone(),
-			extra,
-			inherent_data_providers,
-			phantom: PhantomData,
-			allow_old_seals: true,
-		}
-	);
+	let equivocation_reports = EquivocationReports::new();
+	register_equivocation_reports(&inherent_data_providers, &equivocation_reports)?;
+
+	let verifier = Arc::new(AuraVerifier::new(
+		client.clone(),
+		extra,
+		inherent_data_providers,
+		true,
+		equivocation_reports,
+	));
 	Ok(BasicQueue::new(verifier, block_import, justification_import))
 }
 
@@ -800,7 +935,7 @@ mod tests {
 	use consensus_common::NoNetwork as DummyOracle;
 	use network::test::*;
 	use network::test::{Block as TestBlock, PeersClient};
-	use runtime_primitives::traits::Block as BlockT;
+	use runtime_primitives::traits::{Block as BlockT, DigestFor};
 	use network::config::ProtocolConfig;
 	use parking_lot::Mutex;
 	use tokio::runtime::current_thread;
@@ -808,6 +943,7 @@ mod tests {
 	use primitives::sr25519;
 	use client::{LongestChain, BlockchainEvents};
 	use test_client;
+	use inherents::ProvideInherentData;
 
 	type Error = client::error::Error;
 
@@ -831,7 +967,7 @@ mod tests {
 		type Error = Error;
 		type Create = Result<TestBlock, Error>;
 
-		fn propose(&self, _: InherentData, _: Duration) -> Result<TestBlock, Error> {
+		fn propose(&self, _: InherentData, _: DigestFor<TestBlock>, _: Duration) -> Result<TestBlock, Error> {
 			self.1.new_block().unwrap().bake().map_err(|e| e.into())
 		}
 	}
@@ -869,13 +1005,13 @@ mod tests {
 			).expect("Registers aura inherent data provider");
 
 			assert_eq!(slot_duration.get(), SLOT_DURATION);
-			Arc::new(AuraVerifier {
+			Arc::new(AuraVerifier::new(
 				client,
-				extra: NothingExtra,
+				NothingExtra,
 				inherent_data_providers,
-				phantom: Default::default(),
-				allow_old_seals: false,
-			})
+				false,
+				EquivocationReports::new(),
+			))
 		}
 
 		fn peer(&self, i: usize) -> &Peer<Self::PeerData, DummySpecialization> {
@@ -981,4 +1117,108 @@ mod tests {
 			Keyring::Charlie.into()
 		]);
 	}
+
+	fn create_header(slot_num: u64, number: u64, pair: &sr25519::Pair) -> <TestBlock as BlockT>::Header {
+		let mut header = <TestBlock as BlockT>::Header::new(
+			number,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+		let to_sign = (slot_num, header.hash()).encode();
+		let signature = pair.sign(&to_sign[..]);
+		let item = <generic::DigestItem<_, _, _> as CompatibleDigestItem<sr25519::Pair>>::aura_seal(
+			slot_num,
+			signature,
+		);
+		header.digest_mut().push(item);
+		header
+	}
+
+	#[test]
+	fn check_header_reports_equivocations() {
+		let client = test_client::new();
+		let pair = Keyring::Alice.pair();
+		let authorities = vec![pair.public()];
+		let equivocation_reports = EquivocationReports::new();
+
+		let check = |header: <TestBlock as BlockT>::Header| {
+			let hash = header.hash();
+			check_header::<_, TestBlock, sr25519::Pair>(
+				&client,
+				3,
+				header,
+				hash,
+				&authorities[..],
+				None,
+				false,
+				&equivocation_reports,
+			)
+		};
+		let reports = |reports: &EquivocationReports| {
+			let mut data = InherentData::new();
+			reports.provide_inherent_data(&mut data).unwrap();
+			data.get_data::<Vec<Vec<u8>>>(&EQUIVOCATION_INHERENT_IDENTIFIER).unwrap().unwrap()
+		};
+
+		let header1 = create_header(2, 1, &pair);
+		let header2 = create_header(2, 2, &pair);
+
+		assert!(check(header1.clone()).is_ok());
+		// importing the same header again is fine.
+		assert!(check(header1.clone()).is_ok());
+		assert!(reports(&equivocation_reports).is_empty());
+
+		// the equivocating header is still imported, but reported.
+		assert!(check(header2.clone()).is_ok());
+		let proof = slots::SlotEquivocationProof { slot: 2, first_header: header1, second_header: header2 };
+		assert_eq!(reports(&equivocation_reports), vec![proof.encode()]);
+	}
+
+	#[test]
+	fn pre_verified_signatures_are_not_checked_again() {
+		let client = Arc::new(test_client::new());
+		let verifier = AuraVerifier::<_, _, sr25519::Pair>::new(
+			client,
+			NothingExtra,
+			InherentDataProviders::new(),
+			false,
+			EquivocationReports::new(),
+		);
+		let authorities = vec![Keyring::Alice.public(), Keyring::Bob.public()];
+		// the parent of the test headers is unknown, so the last authorities are used.
+		*verifier.last_authorities.lock() = authorities.clone();
+
+		let pre_verify = |header: &<TestBlock as BlockT>::Header| {
+			Verifier::<TestBlock>::pre_verify(&verifier, BlockOrigin::NetworkInitialSync, header);
+			verifier.pre_verified.take(&header.hash().encode())
+		};
+		let check = |header: <TestBlock as BlockT>::Header, pre_verified_author: Option<&sr25519::Public>| {
+			let hash = header.hash();
+			check_header::<_, TestBlock, sr25519::Pair>(
+				&*verifier.client,
+				3,
+				header,
+				hash,
+				&authorities[..],
+				pre_verified_author,
+				false,
+				&EquivocationReports::new(),
+			)
+		};
+
+		// slot 2 belongs to Alice.
+		let header = create_header(2, 1, &Keyring::Alice.pair());
+		assert_eq!(pre_verify(&header), Some(Keyring::Alice.public()));
+		assert!(check(header, Some(&Keyring::Alice.public())).is_ok());
+
+		// slot 3 belongs to Bob.
+		let header = create_header(3, 1, &Keyring::Alice.pair());
+		assert_eq!(pre_verify(&header), None);
+		assert!(check(header.clone(), None).is_err());
+
+		// the signature is checked when the block was pre-verified with other authorities.
+		assert!(check(header, Some(&Keyring::Alice.public())).is_err());
+	}
 }
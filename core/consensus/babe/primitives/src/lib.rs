@@ -18,6 +18,7 @@
 #![forbid(warnings, unsafe_code, missing_docs)]
 #![cfg_attr(not(feature = "std"), no_std)]
 
+use rstd::vec::Vec;
 use runtime_primitives::ConsensusEngineId;
 use substrate_client::decl_runtime_apis;
 
@@ -26,8 +27,91 @@ use parity_codec::{Encode, Decode};
 /// The `ConsensusEngineId` of BABE.
 pub const BABE_ENGINE_ID: ConsensusEngineId = [b'b', b'a', b'b', b'e'];
 
+/// The length of a VRF output.
+pub const VRF_OUTPUT_LENGTH: usize = 32;
+
+/// Randomness of an epoch, which goes into the VRF of its slot claims.
+pub type Randomness = [u8; VRF_OUTPUT_LENGTH];
+
+/// The identifier of a BABE authority.
+pub type AuthorityId = primitives::sr25519::Public;
+
+/// The data the block author puts into a pre-runtime digest, so that the
+/// runtime can accumulate the VRF outputs into the randomness of the epochs.
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum BabePreDigest {
+	/// The slot was claimed with the VRF. The VRF output has to be the one of
+	/// the seal of the block.
+	Primary {
+		/// The VRF output of the slot claim.
+		vrf_output: Randomness,
+		/// The claimed slot.
+		slot_number: u64,
+	},
+	/// The slot had no VRF claim, and was authored by the secondary author
+	/// of the slot, which is chosen from the randomness of the epoch.
+	Secondary {
+		/// The authored slot.
+		slot_number: u64,
+	},
+}
+
+impl BabePreDigest {
+	/// The slot of the block.
+	pub fn slot_number(&self) -> u64 {
+		match self {
+			BabePreDigest::Primary { slot_number, .. } => *slot_number,
+			BabePreDigest::Secondary { slot_number } => *slot_number,
+		}
+	}
+
+	/// Whether the slot was claimed with the VRF.
+	pub fn is_primary(&self) -> bool {
+		match self {
+			BabePreDigest::Primary { .. } => true,
+			BabePreDigest::Secondary { .. } => false,
+		}
+	}
+}
+
+/// A BABE epoch: a range of slots with the authorities and the randomness of
+/// its slot claims.
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Epoch {
+	/// The index of the epoch, the genesis epoch has index 0.
+	pub epoch_index: u64,
+	/// The first slot of the epoch.
+	pub start_slot: u64,
+	/// The number of slots of the epoch.
+	pub duration: u64,
+	/// The authorities which may claim slots of the epoch.
+	pub authorities: Vec<AuthorityId>,
+	/// The randomness which goes into the VRF of the slot claims.
+	pub randomness: Randomness,
+	/// Whether slots of the epoch without a VRF claim may be authored by a secondary author.
+	pub secondary_slots: bool,
+}
+
+impl Epoch {
+	/// The first slot after the epoch.
+	pub fn end_slot(&self) -> u64 {
+		self.start_slot.saturating_add(self.duration)
+	}
+}
+
+/// A consensus log item of BABE, deposited by the runtime as a consensus digest.
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum ConsensusLog {
+	/// The first block of an epoch announces the next epoch.
+	NextEpochData(Epoch),
+}
+
 /// Configuration data used by the BABE consensus engine.
-#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug, Encode, Decode)]
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct BabeConfiguration {
 	/// The slot duration in milliseconds for BABE. Currently, only
 	/// the value provided by this type at genesis will be used.
@@ -46,6 +130,33 @@ pub struct BabeConfiguration {
 	///
 	/// Dynamic thresholds may be supported in the future.
 	pub threshold: u64,
+
+	/// The number of slots of an epoch.
+	pub epoch_length: u64,
+
+	/// The authorities of the genesis epoch.
+	pub genesis_authorities: Vec<AuthorityId>,
+
+	/// The randomness of the genesis epoch.
+	pub randomness: Randomness,
+
+	/// Whether slots of the genesis epoch without a VRF claim may be authored by
+	/// a secondary author. The runtime announces the setting of later epochs.
+	pub secondary_slots: bool,
+}
+
+impl BabeConfiguration {
+	/// The genesis epoch, starting at the slot of the first block.
+	pub fn genesis_epoch(&self, start_slot: u64) -> Epoch {
+		Epoch {
+			epoch_index: 0,
+			start_slot,
+			duration: self.epoch_length,
+			authorities: self.genesis_authorities.clone(),
+			randomness: self.randomness,
+			secondary_slots: self.secondary_slots,
+		}
+	}
 }
 
 #[cfg(feature = "std")]
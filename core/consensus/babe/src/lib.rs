@@ -31,9 +31,10 @@ pub use consensus_common::SyncOracle;
 use consensus_common::ExtraVerification;
 use runtime_primitives::{generic, generic::BlockId, Justification};
 use runtime_primitives::traits::{
-	Block, Header, Digest, DigestItemFor, DigestItem, ProvideRuntimeApi, AuthorityIdFor,
+	Block, Header, Digest, DigestFor, DigestItemFor, DigestItem, ProvideRuntimeApi, AuthorityIdFor,
+	NumberFor, One,
 };
-use std::{sync::Arc, u64, fmt::Debug};
+use std::{sync::Arc, u64, fmt::Debug, collections::HashMap};
 use parity_codec::{Decode, Encode, Input};
 use primitives::{
 	crypto::Pair,
@@ -52,22 +53,27 @@ use schnorrkel::{
 };
 use authorities::AuthoritiesApi;
 use consensus_common::{self, Authorities, BlockImport, Environment, Proposer,
-	ForkChoiceStrategy, ImportBlock, BlockOrigin, Error as ConsensusError,
+	ForkChoiceStrategy, ImportBlock, ImportResult, BlockOrigin, Error as ConsensusError,
 };
 use srml_babe::{
 	BabeInherentData,
 	timestamp::{TimestampInherentData, InherentType as TimestampInherent}
 };
-use consensus_common::{SelectChain, well_known_cache_keys};
-use consensus_common::import_queue::{Verifier, BasicQueue};
+use consensus_common::{SelectChain, fork_choice_weight_key, well_known_cache_keys};
+use consensus_common::import_queue::{
+	Verifier, BasicQueue, SharedBlockImport, SharedJustificationImport, PreVerifiedBlocks,
+};
 use client::{
 	block_builder::api::BlockBuilder as BlockBuilderApi,
-	blockchain::ProvideCache,
+	blockchain::{self, HeaderBackend, ProvideCache},
 	runtime_api::ApiExt,
 	error::Result as CResult,
 	backend::AuxStore,
 };
-use slots::CheckedHeader;
+use slots::{CheckedHeader, check_equivocation};
+use srml_consensus::{EquivocationReports, EQUIVOCATION_INHERENT_IDENTIFIER};
+use fork_tree::ForkTree;
+use parking_lot::Mutex;
 use futures::{Future, IntoFuture, future};
 use tokio::timer::Timeout;
 use log::{error, warn, debug, info, trace};
@@ -88,6 +94,18 @@ pub struct BabeSeal {
 	slot_num: u64,
 }
 
+/// A BABE seal of a block authored in a secondary slot.  It includes:
+///
+/// * The public key
+/// * The signature
+/// * The slot number
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub struct BabeSecondarySeal {
+	signer: Public,
+	signature: Signature,
+	slot_num: u64,
+}
+
 /// The prefix used by BABE for its VRF keys.
 pub const BABE_VRF_PREFIX: &'static [u8] = b"substrate-babe-vrf";
 
@@ -161,6 +179,7 @@ impl Decode for BabeSeal {
 // FIXME: Once Rust has higher-kinded types, the duplication between this
 // and `super::aura::Config` can be eliminated.
 // https://github.com/paritytech/substrate/issues/2434
+#[derive(Clone)]
 pub struct Config(slots::SlotDuration<BabeConfiguration>);
 
 impl Config {
@@ -189,6 +208,11 @@ impl Config {
 	pub fn threshold(&self) -> u64 {
 		self.0.threshold
 	}
+
+	/// The genesis epoch, starting at the slot of the first block.
+	pub fn genesis_epoch(&self, start_slot: u64) -> Epoch {
+		self.0.genesis_epoch(start_slot)
+	}
 }
 
 fn inherent_to_common_error(err: RuntimeString) -> consensus_common::Error {
@@ -203,6 +227,22 @@ pub trait CompatibleDigestItem: Sized {
 
 	/// If this item is an Babe seal, return the slot number and signature.
 	fn as_babe_seal(&self) -> Option<BabeSeal>;
+
+	/// Construct a digest item which contains the seal of a block authored
+	/// in a secondary slot.
+	fn babe_secondary_seal(seal: BabeSecondarySeal) -> Self;
+
+	/// If this item is the seal of a block authored in a secondary slot, return it.
+	fn as_babe_secondary_seal(&self) -> Option<BabeSecondarySeal>;
+
+	/// Construct a pre-runtime digest item which contains the BABE pre-digest.
+	fn babe_pre_digest(pre_digest: BabePreDigest) -> Self;
+
+	/// If this item is a BABE pre-runtime digest, return the pre-digest.
+	fn as_babe_pre_digest(&self) -> Option<BabePreDigest>;
+
+	/// If this item is a BABE consensus log deposited by the runtime, return it.
+	fn as_babe_consensus_log(&self) -> Option<ConsensusLog>;
 }
 
 impl<T, Hash> CompatibleDigestItem for generic::DigestItem<Hash, Public, T>
@@ -232,6 +272,149 @@ impl<T, Hash> CompatibleDigestItem for generic::DigestItem<Hash, Public, T>
 			}
 		}
 	}
+
+	fn babe_secondary_seal(seal: BabeSecondarySeal) -> Self {
+		generic::DigestItem::Consensus(BABE_ENGINE_ID, seal.encode())
+	}
+
+	fn as_babe_secondary_seal(&self) -> Option<BabeSecondarySeal> {
+		match self {
+			generic::DigestItem::Consensus(BABE_ENGINE_ID, seal) => Decode::decode(&mut &seal[..]),
+			_ => None,
+		}
+	}
+
+	fn babe_pre_digest(pre_digest: BabePreDigest) -> Self {
+		generic::DigestItem::PreRuntime(BABE_ENGINE_ID, pre_digest.encode())
+	}
+
+	fn as_babe_pre_digest(&self) -> Option<BabePreDigest> {
+		match self {
+			generic::DigestItem::PreRuntime(BABE_ENGINE_ID, data) => Decode::decode(&mut &data[..]),
+			_ => None,
+		}
+	}
+
+	fn as_babe_consensus_log(&self) -> Option<ConsensusLog> {
+		match self {
+			generic::DigestItem::Consensus(BABE_ENGINE_ID, data) => Decode::decode(&mut &data[..]),
+			_ => None,
+		}
+	}
+}
+
+/// Extract the BABE pre-digest from the given header. Pre-runtime digests are
+/// mandatory, the function will return `Err` if none is found.
+fn find_pre_digest<B: Block>(header: &B::Header) -> Result<BabePreDigest, String>
+	where DigestItemFor<B>: CompatibleDigestItem,
+{
+	header.digest().logs().iter()
+		.filter_map(CompatibleDigestItem::as_babe_pre_digest)
+		.next()
+		.ok_or_else(|| format!("Header {:?} has no BABE pre-runtime digest", header.hash()))
+}
+
+/// Extract the next epoch announced by the runtime from the given pre-header.
+fn find_next_epoch_digest<B: Block>(header: &B::Header) -> Option<Epoch>
+	where DigestItemFor<B>: CompatibleDigestItem,
+{
+	header.digest().logs().iter()
+		.filter_map(CompatibleDigestItem::as_babe_consensus_log)
+		.map(|log| match log {
+			ConsensusLog::NextEpochData(epoch) => epoch,
+		})
+		.next()
+}
+
+/// The key of the auxiliary storage entry which stores the epoch changes.
+const EPOCH_CHANGES_KEY: &[u8] = b"babe_epoch_changes";
+
+/// An epoch announced by a block, along with the epoch of the block itself.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct EpochChange {
+	/// The epoch of the block which announced the next epoch.
+	pub current: Epoch,
+	/// The announced epoch.
+	pub next: Epoch,
+}
+
+/// The epoch changes of all forks, keyed by the blocks which announced them.
+pub type EpochChanges<B> = ForkTree<<B as Block>::Hash, NumberFor<B>, EpochChange>;
+
+/// Epoch changes shared between the block import, the verifier and the block authoring.
+pub type SharedEpochChanges<B> = Arc<Mutex<EpochChanges<B>>>;
+
+/// Load the epoch changes from the auxiliary storage of the client.
+pub fn load_epoch_changes<B: Block, C: AuxStore>(client: &C) -> CResult<SharedEpochChanges<B>> {
+	let epoch_changes = client.get_aux(EPOCH_CHANGES_KEY)?
+		.and_then(|encoded| Decode::decode(&mut &encoded[..]))
+		.unwrap_or_else(ForkTree::new);
+
+	Ok(Arc::new(Mutex::new(epoch_changes)))
+}
+
+/// Returns a function for checking block ancestry, the given `current`
+/// block (hash and parent hash) may not be in the backend yet.
+fn is_descendent_of<'a, B, C>(
+	client: &'a C,
+	current: Option<(&'a B::Hash, &'a B::Hash)>,
+) -> impl Fn(&B::Hash, &B::Hash) -> Result<bool, client::error::Error> + 'a
+	where B: Block, C: HeaderBackend<B>,
+{
+	move |base, hash| {
+		if base == hash { return Ok(false); }
+
+		let mut hash = hash;
+		if let Some((current_hash, current_parent_hash)) = current {
+			if base == current_hash { return Ok(false); }
+			if hash == current_hash {
+				if base == current_parent_hash {
+					return Ok(true);
+				} else {
+					hash = current_parent_hash;
+				}
+			}
+		}
+
+		let tree_route = blockchain::tree_route(
+			client,
+			BlockId::Hash(*hash),
+			BlockId::Hash(*base),
+		)?;
+
+		Ok(tree_route.common_block().hash == *base)
+	}
+}
+
+/// The epoch of a block at `slot_number` which is built on the given parent.
+///
+/// Blocks before the first epoch announcement belong to the genesis epoch.
+fn epoch_for_child_of<B, C>(
+	epoch_changes: &EpochChanges<B>,
+	client: &C,
+	config: &Config,
+	parent_hash: &B::Hash,
+	parent_number: NumberFor<B>,
+	slot_number: u64,
+) -> Result<Epoch, String>
+	where B: Block, C: HeaderBackend<B>,
+{
+	let is_descendent_of = is_descendent_of::<B, _>(client, None);
+	let change = epoch_changes.find_node_where(
+		parent_hash,
+		&parent_number,
+		&is_descendent_of,
+		&|_| true,
+	).map_err(|e| format!("Could not look up the epoch of a child of {:?}: {:?}", parent_hash, e))?;
+
+	Ok(match change {
+		Some((_, _, change)) => if slot_number >= change.next.start_slot {
+			change.next.clone()
+		} else {
+			change.current.clone()
+		},
+		None => config.genesis_epoch(slot_number),
+	})
 }
 
 struct BabeSlotCompatible;
@@ -248,7 +431,7 @@ impl SlotCompatible for BabeSlotCompatible {
 }
 
 /// Parameters for BABE.
-pub struct BabeParams<C, E, I, SO, SC, OnExit> {
+pub struct BabeParams<B: Block, C, E, I, SO, SC, OnExit> {
 
 	/// The configuration for BABE.  Includes the slot duration, threshold, and
 	/// other parameters.
@@ -266,6 +449,9 @@ pub struct BabeParams<C, E, I, SO, SC, OnExit> {
 	/// A block importer
 	pub block_import: Arc<I>,
 
+	/// The epoch changes, shared with the block import.
+	pub epoch_changes: SharedEpochChanges<B>,
+
 	/// The environment
 	pub env: Arc<E>,
 
@@ -289,17 +475,18 @@ pub fn start_babe<B, C, E, I, SO, SC, Error, OnExit>(BabeParams {
 	client,
 	select_chain,
 	block_import,
+	epoch_changes,
 	env,
 	sync_oracle,
 	on_exit,
 	inherent_data_providers,
 	force_authoring,
-}: BabeParams<C, E, I, SO, SC, OnExit>) -> Result<
+}: BabeParams<B, C, E, I, SO, SC, OnExit>) -> Result<
 	impl Future<Item=(), Error=()>,
 	consensus_common::Error,
 > where
 	B: Block,
-	C: ProvideRuntimeApi + ProvideCache<B>,
+	C: ProvideRuntimeApi + ProvideCache<B> + HeaderBackend<B>,
 	C::Api: AuthoritiesApi<B>,
 	E: Environment<B, Error=Error>,
 	E::Proposer: Proposer<B, Error=Error>,
@@ -314,12 +501,13 @@ pub fn start_babe<B, C, E, I, SO, SC, Error, OnExit>(BabeParams {
 	let worker = BabeWorker {
 		client: client.clone(),
 		block_import,
+		epoch_changes,
 		env,
 		local_key,
 		inherent_data_providers: inherent_data_providers.clone(),
 		sync_oracle: sync_oracle.clone(),
 		force_authoring,
-		threshold: config.threshold(),
+		config: config.clone(),
 	};
 	slots::start_slot_worker::<_, _, _, _, _, BabeSlotCompatible, _>(
 		config.0,
@@ -331,19 +519,47 @@ pub fn start_babe<B, C, E, I, SO, SC, Error, OnExit>(BabeParams {
 	)
 }
 
-struct BabeWorker<C, E, I, SO> {
+/// How the local authority claimed a slot.
+enum SlotClaim {
+	/// The slot was claimed with the VRF.
+	Primary(VRFOutput, VRFProof),
+	/// The local authority is the secondary author of the slot.
+	Secondary,
+}
+
+impl SlotClaim {
+	fn is_primary(&self) -> bool {
+		match self {
+			SlotClaim::Primary(..) => true,
+			SlotClaim::Secondary => false,
+		}
+	}
+
+	fn pre_digest(&self, slot_number: u64) -> BabePreDigest {
+		match self {
+			SlotClaim::Primary(vrf_output, _) => BabePreDigest::Primary {
+				vrf_output: *vrf_output.as_bytes(),
+				slot_number,
+			},
+			SlotClaim::Secondary => BabePreDigest::Secondary { slot_number },
+		}
+	}
+}
+
+struct BabeWorker<B: Block, C, E, I, SO> {
 	client: Arc<C>,
 	block_import: Arc<I>,
+	epoch_changes: SharedEpochChanges<B>,
 	env: Arc<E>,
 	local_key: Arc<sr25519::Pair>,
 	sync_oracle: SO,
 	inherent_data_providers: InherentDataProviders,
 	force_authoring: bool,
-	threshold: u64,
+	config: Config,
 }
 
-impl<B: Block, C, E, I, Error, SO> SlotWorker<B> for BabeWorker<C, E, I, SO> where
-	C: ProvideRuntimeApi + ProvideCache<B>,
+impl<B: Block, C, E, I, Error, SO> SlotWorker<B> for BabeWorker<B, C, E, I, SO> where
+	C: ProvideRuntimeApi + ProvideCache<B> + HeaderBackend<B>,
 	C::Api: AuthoritiesApi<B>,
 	E: Environment<B, Error=Error>,
 	E::Proposer: Proposer<B, Error=Error>,
@@ -375,21 +591,32 @@ impl<B: Block, C, E, I, Error, SO> SlotWorker<B> for BabeWorker<C, E, I, SO> whe
 		let (timestamp, slot_num, slot_duration) =
 			(slot_info.timestamp, slot_info.number, slot_info.duration);
 
-		let authorities = match authorities(client.as_ref(), &BlockId::Hash(chain_head.hash())) {
-			Ok(authorities) => authorities,
+		let epoch = epoch_for_child_of(
+			&*self.epoch_changes.lock(),
+			client.as_ref(),
+			&self.config,
+			&chain_head.hash(),
+			*chain_head.number(),
+			slot_num,
+		);
+		let epoch = match epoch {
+			Ok(epoch) => epoch,
 			Err(e) => {
-				error!(
-					target: "babe",
-					"Unable to fetch authorities at block {:?}: {:?}",
-					chain_head.hash(),
-					e
-				);
-				telemetry!(CONSENSUS_WARN; "babe.unable_fetching_authorities";
+				error!(target: "babe", "Unable to fetch the epoch at block {:?}: {}", chain_head.hash(), e);
+				telemetry!(CONSENSUS_WARN; "babe.unable_fetching_epoch";
 					"slot" => ?chain_head.hash(), "err" => ?e
 				);
 				return Box::new(future::ok(()));
 			}
 		};
+		let genesis_hash = match client.info() {
+			Ok(info) => info.genesis_hash,
+			Err(e) => {
+				error!(target: "babe", "Unable to fetch the genesis hash: {:?}", e);
+				return Box::new(future::ok(()));
+			}
+		};
+		let authorities = epoch.authorities;
 
 		if !self.force_authoring && self.sync_oracle.is_offline() && authorities.len() > 1 {
 			debug!(target: "babe", "Skipping proposal slot. Waiting for the network.");
@@ -399,22 +626,28 @@ impl<B: Block, C, E, I, Error, SO> SlotWorker<B> for BabeWorker<C, E, I, SO> whe
 			return Box::new(future::ok(()));
 		}
 
-		// FIXME replace the dummy empty slices with real data
-		// https://github.com/paritytech/substrate/issues/2435
-		// https://github.com/paritytech/substrate/issues/2436
-		let authoring_result = if let Some((inout, proof, _batchable_proof)) = claim_slot(
-			&[0u8; 0],
+		let claim = match claim_slot(
+			&epoch.randomness,
 			slot_info.number,
-			&[0u8; 0],
-			0,
+			genesis_hash.as_ref(),
+			epoch.epoch_index,
 			&authorities,
 			&pair,
-			self.threshold,
+			self.config.threshold(),
 		) {
+			Some((inout, proof, _batchable_proof)) => Some(SlotClaim::Primary(inout.to_output(), proof)),
+			None if epoch.secondary_slots
+				&& secondary_slot_author(slot_num, &authorities, &epoch.randomness) == Some(&pair.public()) =>
+				Some(SlotClaim::Secondary),
+			None => None,
+		};
+
+		let authoring_result = if let Some(claim) = claim {
 			debug!(
-				target: "babe", "Starting authorship at slot {}; timestamp = {}",
+				target: "babe", "Starting authorship at slot {}; timestamp = {}; primary = {}",
 				slot_num,
 				timestamp,
+				claim.is_primary(),
 			);
 			telemetry!(CONSENSUS_DEBUG; "babe.starting_authorship";
 				"slot_num" => slot_num, "timestamp" => timestamp
@@ -432,23 +665,29 @@ impl<B: Block, C, E, I, Error, SO> SlotWorker<B> for BabeWorker<C, E, I, SO> whe
 				}
 			};
 
+			// the runtime accumulates the VRF outputs into the randomness of the epochs.
+			let mut inherent_digests = <DigestFor<B> as Default>::default();
+			inherent_digests.push(<DigestItemFor<B> as CompatibleDigestItem>::babe_pre_digest(
+				claim.pre_digest(slot_num),
+			));
+
 			let remaining_duration = slot_info.remaining_duration();
 			// deadline our production to approx. the end of the
 			// slot
 			(Timeout::new(
 				proposer.propose(
 					slot_info.inherent_data,
+					inherent_digests,
 					remaining_duration,
 				).into_future(),
 				remaining_duration,
 			),
-			inout.to_output(),
-			proof)
+			claim)
 		} else {
 			return Box::new(future::ok(()));
 		};
 
-		let (proposal_work, vrf_output, proof) = authoring_result;
+		let (proposal_work, claim) = authoring_result;
 
 		Box::new(
 			proposal_work
@@ -475,17 +714,30 @@ impl<B: Block, C, E, I, Error, SO> SlotWorker<B> for BabeWorker<C, E, I, SO> whe
 
 					// sign the pre-sealed hash of the block and then
 					// add it to a digest item.
-					let to_sign = (slot_num, pre_hash, proof.to_bytes()).encode();
-					let signature = pair.sign(&to_sign[..]);
-					let item = <DigestItemFor<B> as CompatibleDigestItem>::babe_seal(BabeSeal {
-						proof,
-						signature: LocalizedSignature {
-							signature,
-							signer: pair.public(),
-						},
-						slot_num,
-						vrf_output,
-					});
+					let item = match claim {
+						SlotClaim::Primary(vrf_output, proof) => {
+							let to_sign = (slot_num, pre_hash, proof.to_bytes()).encode();
+							let signature = pair.sign(&to_sign[..]);
+							<DigestItemFor<B> as CompatibleDigestItem>::babe_seal(BabeSeal {
+								proof,
+								signature: LocalizedSignature {
+									signature,
+									signer: pair.public(),
+								},
+								slot_num,
+								vrf_output,
+							})
+						}
+						SlotClaim::Secondary => {
+							let to_sign = (slot_num, pre_hash).encode();
+							let signature = pair.sign(&to_sign[..]);
+							<DigestItemFor<B> as CompatibleDigestItem>::babe_secondary_seal(BabeSecondarySeal {
+								signer: pair.public(),
+								signature,
+								slot_num,
+							})
+						}
+					};
 
 					let import_block: ImportBlock<B> = ImportBlock {
 						origin: BlockOrigin::Own,
@@ -526,20 +778,25 @@ impl<B: Block, C, E, I, Error, SO> SlotWorker<B> for BabeWorker<C, E, I, SO> whe
 	}
 }
 
-/// check a header has been signed by the right key. If the slot is too far in
-/// the future, an error will be returned. If successful, returns the pre-header
-/// and the digest item containing the seal.
+/// check a header has been signed by the right key of the given epoch. If the
+/// slot is too far in the future, an error will be returned. If successful,
+/// returns the pre-header and the digest item containing the seal.
 ///
-/// This digest item will always return `Some` when used with `as_babe_seal`.
+/// This digest item will always return `Some` when used with `as_babe_seal`
+/// for primary blocks, and with `as_babe_secondary_seal` for secondary blocks.
 //
 // FIXME #1018 needs misbehavior types
 #[forbid(warnings)]
-fn check_header<B: Block + Sized>(
+fn check_header<B: Block + Sized, C: AuxStore>(
+	client: &C,
 	slot_now: u64,
 	mut header: B::Header,
 	hash: B::Hash,
-	authorities: &[Public],
+	epoch: &Epoch,
+	pre_verified_author: Option<&Public>,
+	genesis_hash: &[u8],
 	threshold: u64,
+	equivocation_reports: &EquivocationReports,
 ) -> Result<CheckedHeader<B::Header, DigestItemFor<B>>, String>
 	where DigestItemFor<B>: CompatibleDigestItem,
 {
@@ -549,33 +806,138 @@ fn check_header<B: Block + Sized>(
 		None => return Err(format!("Header {:?} is unsealed", hash)),
 	};
 
-	let BabeSeal {
+	let pre_digest = find_pre_digest::<B>(&header)?;
+	let slot_num = pre_digest.slot_number();
+
+	if slot_num > slot_now {
+		header.digest_mut().push(digest_item);
+		return Ok(CheckedHeader::Deferred(header, slot_num));
+	}
+
+	let author = match pre_verified_author {
+		// the seal was checked against the same epoch on a verification thread.
+		Some(author) => author.clone(),
+		None => check_seal::<B>(
+			hash,
+			header.hash(),
+			&digest_item,
+			pre_digest,
+			epoch,
+			genesis_hash,
+			threshold,
+		)?,
+	};
+
+	// the seal is part of what the author signed off on, so it is recorded
+	// along with the header.
+	header.digest_mut().push(digest_item);
+	if let Some(equivocation_proof) = check_equivocation(
+		client,
+		slot_now,
 		slot_num,
-		signature: LocalizedSignature {signer, signature },
+		&header,
+		&author,
+	).map_err(|e| e.to_string())? {
+		warn!(
+			target: "babe",
+			"Slot author {:?} is equivocating at slot {} with headers {:?} and {:?}",
+			author,
+			slot_num,
+			equivocation_proof.first_header.hash(),
+			equivocation_proof.second_header.hash(),
+		);
+		telemetry!(CONSENSUS_WARN; "babe.equivocation_detected";
+			"slot" => slot_num,
+			"first_header" => ?equivocation_proof.first_header.hash(),
+			"second_header" => ?equivocation_proof.second_header.hash()
+		);
+		equivocation_reports.push(equivocation_proof.encode());
+	}
+
+	let digest_item = header.digest_mut().pop()
+		.expect("the seal has just been pushed back; qed");
+	Ok(CheckedHeader::Checked(header, digest_item))
+}
+
+/// Check the seal of a block against the given epoch, returning the author of the block.
+///
+/// This only depends on the header and the epoch, so it can be done before the parent
+/// of the block is imported.
+fn check_seal<B: Block>(
+	hash: B::Hash,
+	pre_hash: B::Hash,
+	seal: &DigestItemFor<B>,
+	pre_digest: BabePreDigest,
+	epoch: &Epoch,
+	genesis_hash: &[u8],
+	threshold: u64,
+) -> Result<Public, String>
+	where DigestItemFor<B>: CompatibleDigestItem,
+{
+	match pre_digest {
+		BabePreDigest::Primary { vrf_output, slot_number } => check_primary_header::<B>(
+			hash,
+			pre_hash,
+			seal,
+			slot_number,
+			vrf_output,
+			epoch,
+			genesis_hash,
+			threshold,
+		),
+		BabePreDigest::Secondary { slot_number } if epoch.secondary_slots => check_secondary_header::<B>(
+			hash,
+			pre_hash,
+			seal,
+			slot_number,
+			epoch,
+		),
+		BabePreDigest::Secondary { .. } => {
+			debug!(target: "babe", "Secondary slots are disabled, rejecting {:?}", hash);
+			Err(format!("Header {:?} is authored in a secondary slot, which are disabled in its epoch", hash))
+		}
+	}
+}
+
+/// Check the seal of a block whose slot was claimed with the VRF.
+fn check_primary_header<B: Block>(
+	hash: B::Hash,
+	pre_hash: B::Hash,
+	seal: &DigestItemFor<B>,
+	slot_num: u64,
+	pre_digest_vrf_output: Randomness,
+	epoch: &Epoch,
+	genesis_hash: &[u8],
+	threshold: u64,
+) -> Result<Public, String>
+	where DigestItemFor<B>: CompatibleDigestItem,
+{
+	let BabeSeal {
+		slot_num: seal_slot_num,
+		signature: LocalizedSignature { signer, signature },
 		proof,
 		vrf_output,
-	} = digest_item.as_babe_seal().ok_or_else(|| {
+	} = seal.as_babe_seal().ok_or_else(|| {
 		debug!(target: "babe", "Header {:?} is unsealed", hash);
 		format!("Header {:?} is unsealed", hash)
 	})?;
 
-	if slot_num > slot_now {
-		header.digest_mut().push(digest_item);
-		Ok(CheckedHeader::Deferred(header, slot_num))
-	} else if !authorities.contains(&signer) {
+	if seal_slot_num != slot_num || pre_digest_vrf_output != *vrf_output.as_bytes() {
+		debug!(target: "babe", "Pre-runtime digest of {:?} doesn't match the seal", hash);
+		Err(format!("Pre-runtime digest of {:?} doesn't match the seal", hash))
+	} else if !epoch.authorities.contains(&signer) {
 		debug!(target: "babe", "Slot Author not found");
 		Err("Slot Author not found".to_string())
 	} else {
-		let pre_hash = header.hash();
 		let to_sign = (slot_num, pre_hash, proof.to_bytes()).encode();
 
 		if sr25519::Pair::verify(&signature, &to_sign[..], &signer) {
 			let (inout, _batchable_proof) = {
 				let transcript = make_transcript(
-					Default::default(),
+					&epoch.randomness,
 					slot_num,
-					Default::default(),
-					0,
+					genesis_hash,
+					epoch.epoch_index,
 				);
 				schnorrkel::PublicKey::from_bytes(signer.as_slice()).and_then(|p| {
 					p.vrf_verify(transcript, &vrf_output, &proof)
@@ -584,8 +946,8 @@ fn check_header<B: Block + Sized>(
 					format!("VRF verification failed")
 				})?
 			};
-			if check(&inout, threshold) {
-				Ok(CheckedHeader::Checked(header, digest_item))
+			if check(&inout, threshold / epoch.authorities.len() as u64) {
+				Ok(signer)
 			} else {
 				debug!(target: "babe", "VRF verification failed: threshold {} exceeded", threshold);
 				Err(format!("Validator {:?} made seal when it wasn’t its turn", signer))
@@ -597,16 +959,100 @@ fn check_header<B: Block + Sized>(
 	}
 }
 
+/// Check the seal of a block authored in a secondary slot, which has to be
+/// signed by the secondary author of the slot.
+fn check_secondary_header<B: Block>(
+	hash: B::Hash,
+	pre_hash: B::Hash,
+	seal: &DigestItemFor<B>,
+	slot_num: u64,
+	epoch: &Epoch,
+) -> Result<Public, String>
+	where DigestItemFor<B>: CompatibleDigestItem,
+{
+	let BabeSecondarySeal { signer, signature, slot_num: seal_slot_num } = seal.as_babe_secondary_seal()
+		.ok_or_else(|| {
+			debug!(target: "babe", "Header {:?} is unsealed", hash);
+			format!("Header {:?} is unsealed", hash)
+		})?;
+
+	if seal_slot_num != slot_num {
+		debug!(target: "babe", "Pre-runtime digest of {:?} doesn't match the seal", hash);
+		Err(format!("Pre-runtime digest of {:?} doesn't match the seal", hash))
+	} else if secondary_slot_author(slot_num, &epoch.authorities, &epoch.randomness) != Some(&signer) {
+		debug!(target: "babe", "Secondary slot author of {:?} is wrong", hash);
+		Err(format!("Validator {:?} isn't the secondary author of slot {}", signer, slot_num))
+	} else if sr25519::Pair::verify(&signature, &(slot_num, pre_hash).encode()[..], &signer) {
+		Ok(signer)
+	} else {
+		debug!(target: "babe", "Bad signature on {:?}", hash);
+		Err(format!("Bad signature on {:?}", hash))
+	}
+}
+
 /// A verifier for Babe blocks.
-pub struct BabeVerifier<C, E> {
+pub struct BabeVerifier<B: Block, C, E> {
 	client: Arc<C>,
 	extra: E,
 	inherent_data_providers: inherents::InherentDataProviders,
-	threshold: u64,
+	config: Config,
+	epoch_changes: SharedEpochChanges<B>,
+	equivocation_reports: EquivocationReports,
+	/// The epoch of the last verified block, used to pre-verify blocks whose parent isn't
+	/// imported yet.
+	last_epoch: Mutex<Option<Epoch>>,
+	/// The (encoded) hashes of the pre-verified blocks, along with the epoch they were
+	/// checked against and their authors.
+	pre_verified: PreVerifiedBlocks<Vec<u8>, (Epoch, Public)>,
 }
 
-impl<C, E> BabeVerifier<C, E> {
-	fn check_inherents<B: Block>(
+impl<B: Block, C, E> BabeVerifier<B, C, E> {
+	fn new(
+		client: Arc<C>,
+		extra: E,
+		inherent_data_providers: InherentDataProviders,
+		config: Config,
+		epoch_changes: SharedEpochChanges<B>,
+		equivocation_reports: EquivocationReports,
+	) -> Self {
+		BabeVerifier {
+			client,
+			extra,
+			inherent_data_providers,
+			config,
+			epoch_changes,
+			equivocation_reports,
+			last_epoch: Mutex::new(None),
+			pre_verified: Default::default(),
+		}
+	}
+
+	/// The epoch of a block at `slot_number` built on top of `parent_hash`, which may not be
+	/// imported yet.
+	fn pre_verification_epoch(
+		&self,
+		parent_hash: &B::Hash,
+		parent_number: NumberFor<B>,
+		slot_number: u64,
+	) -> Option<Epoch>
+		where C: HeaderBackend<B>
+	{
+		if let Ok(Some(_)) = self.client.header(BlockId::Hash(*parent_hash)) {
+			return epoch_for_child_of(
+				&*self.epoch_changes.lock(),
+				self.client.as_ref(),
+				&self.config,
+				parent_hash,
+				parent_number,
+				slot_number,
+			).ok();
+		}
+
+		self.last_epoch.lock().clone()
+			.filter(|epoch| epoch.start_slot <= slot_number && slot_number < epoch.end_slot())
+	}
+
+	fn check_inherents(
 		&self,
 		block: B,
 		block_id: BlockId<B>,
@@ -642,12 +1088,11 @@ impl<B: Block> ExtraVerification<B> for NothingExtra {
 	}
 }
 
-impl<B: Block, C, E> Verifier<B> for BabeVerifier<C, E> where
-	C: ProvideRuntimeApi + Send + Sync,
+impl<B: Block, C, E> Verifier<B> for BabeVerifier<B, C, E> where
+	C: ProvideRuntimeApi + HeaderBackend<B> + AuxStore + Send + Sync,
 	C::Api: BlockBuilderApi<B>,
 	DigestItemFor<B>: CompatibleDigestItem + DigestItem<AuthorityId=Public>,
 	E: ExtraVerification<B>,
-	Self: Authorities<B>,
 {
 	fn verify(
 		&self,
@@ -672,8 +1117,25 @@ impl<B: Block, C, E> Verifier<B> for BabeVerifier<C, E> where
 			.map_err(|e| format!("Could not extract timestamp and slot: {:?}", e))?;
 		let hash = header.hash();
 		let parent_hash = *header.parent_hash();
-		let authorities = self.authorities(&BlockId::Hash(parent_hash))
-			.map_err(|e| format!("Could not fetch authorities at {:?}: {:?}", parent_hash, e))?;
+		let genesis_hash = self.client.info()
+			.map_err(|e| format!("Could not fetch the genesis hash: {:?}", e))?
+			.genesis_hash;
+		let pre_digest = find_pre_digest::<B>(&header)?;
+		let epoch = epoch_for_child_of(
+			&*self.epoch_changes.lock(),
+			self.client.as_ref(),
+			&self.config,
+			&parent_hash,
+			*header.number() - One::one(),
+			pre_digest.slot_number(),
+		)?;
+		*self.last_epoch.lock() = Some(epoch.clone());
+		let pre_verified_author = self.pre_verified.take(&hash.encode())
+			.and_then(|(pre_verified_epoch, author)| if pre_verified_epoch == epoch {
+				Some(author)
+			} else {
+				None
+			});
 
 		let extra_verification = self.extra.verify(
 			&header,
@@ -683,17 +1145,20 @@ impl<B: Block, C, E> Verifier<B> for BabeVerifier<C, E> where
 		// we add one to allow for some small drift.
 		// FIXME #1019 in the future, alter this queue to allow deferring of
 		// headers
-		let checked_header = check_header::<B>(
+		let checked_header = check_header::<B, C>(
+			&*self.client,
 			slot_now + 1,
 			header,
 			hash,
-			&authorities[..],
-			self.threshold,
+			&epoch,
+			pre_verified_author.as_ref(),
+			genesis_hash.as_ref(),
+			self.config.threshold(),
+			&self.equivocation_reports,
 		)?;
 		match checked_header {
 			CheckedHeader::Checked(pre_header, seal) => {
-				let BabeSeal { slot_num, .. } = seal.as_babe_seal()
-					.expect("check_header always returns a seal digest item; qed");
+				let slot_num = pre_digest.slot_number();
 
 				// if the body is passed through, we need to use the runtime
 				// to check that the internally-set timestamp in the inherents
@@ -743,9 +1208,53 @@ impl<B: Block, C, E> Verifier<B> for BabeVerifier<C, E> where
 			}
 		}
 	}
+
+	fn can_pre_verify(&self) -> bool {
+		true
+	}
+
+	fn pre_verify(&self, _origin: BlockOrigin, header: &B::Header) {
+		// the parent is likely not imported yet, in which case the seal is checked against
+		// the epoch of the last verified block; `verify` checks it again if the epoch of
+		// the block turns out to be different.
+		let mut pre_header = header.clone();
+		let seal = match pre_header.digest_mut().pop() {
+			Some(seal) => seal,
+			None => return,
+		};
+		let pre_digest = match find_pre_digest::<B>(&pre_header) {
+			Ok(pre_digest) => pre_digest,
+			Err(_) => return,
+		};
+		let epoch = match self.pre_verification_epoch(
+			header.parent_hash(),
+			*header.number() - One::one(),
+			pre_digest.slot_number(),
+		) {
+			Some(epoch) => epoch,
+			None => return,
+		};
+		let genesis_hash = match self.client.info() {
+			Ok(info) => info.genesis_hash,
+			Err(_) => return,
+		};
+
+		let hash = header.hash();
+		if let Ok(author) = check_seal::<B>(
+			hash,
+			pre_header.hash(),
+			&seal,
+			pre_digest,
+			&epoch,
+			genesis_hash.as_ref(),
+			self.config.threshold(),
+		) {
+			self.pre_verified.insert(hash.encode(), (epoch, author));
+		}
+	}
 }
 
-impl<B, C, E> Authorities<B> for BabeVerifier<C, E> where
+impl<B, C, E> Authorities<B> for BabeVerifier<B, C, E> where
 	B: Block,
 	C: ProvideRuntimeApi + ProvideCache<B>,
 	C::Api: AuthoritiesApi<B>,
@@ -779,9 +1288,231 @@ fn authorities<B, C>(client: &C, at: &BlockId<B>) -> Result<
 		}).ok_or_else(|| consensus_common::ErrorKind::InvalidAuthoritiesSet.into())
 }
 
+/// The weight of a chain: the number of its blocks authored in primary slots.
+pub type BabeBlockWeight = consensus_common::BlockWeight;
+
+/// The key under which weights were stored before they were kept with the
+/// fork-choice weights of the client.
+fn legacy_block_weight_key<H: Encode>(block_hash: H) -> Vec<u8> {
+	(b"babe_block_weight", block_hash).encode()
+}
+
+fn load_legacy_block_weight<C: AuxStore, H: Encode>(
+	client: &C,
+	block_hash: &H,
+) -> CResult<Option<BabeBlockWeight>> {
+	match client.get_aux(&legacy_block_weight_key(block_hash))? {
+		None => Ok(None),
+		Some(encoded) => u32::decode(&mut &encoded[..])
+			.map(|weight| Some(weight.into()))
+			.ok_or_else(|| client::error::Error::Backend("Corrupted BABE block weight".into())),
+	}
+}
+
+/// Load the weight of the chain ending at the given block, `None` if the block
+/// wasn't imported by the BABE block import.
+pub fn load_block_weight<B: Block, C: AuxStore>(
+	client: &C,
+	block_hash: &B::Hash,
+) -> CResult<Option<BabeBlockWeight>> {
+	match client::load_block_weight(client, block_hash)? {
+		Some(weight) => Ok(Some(weight)),
+		None => load_legacy_block_weight(client, block_hash),
+	}
+}
+
+/// Move the weight of the given block from the legacy key to the fork-choice
+/// weights of the client, where the client compares it on import.
+fn migrate_block_weight<B: Block, C: AuxStore>(
+	client: &C,
+	block_hash: &B::Hash,
+) -> CResult<Option<BabeBlockWeight>> {
+	if let Some(weight) = client::load_block_weight(client, block_hash)? {
+		return Ok(Some(weight));
+	}
+
+	let weight = match load_legacy_block_weight(client, block_hash)? {
+		Some(weight) => weight,
+		None => return Ok(None),
+	};
+	client.insert_aux(
+		&[(&fork_choice_weight_key(block_hash)[..], &weight.encode()[..])],
+		&[&legacy_block_weight_key(block_hash)[..]],
+	)?;
+
+	Ok(Some(weight))
+}
+
+/// A block import which keeps track of the epochs announced on each fork, and
+/// of the weight of each chain for the fork choice.
+///
+/// The epoch changes and the weights are written to the auxiliary storage along
+/// with the block, and the epoch changes are reverted if the inner block import
+/// fails. The epoch changes are pruned to the finalized chain whenever a block
+/// announcing an epoch is imported.
+pub struct BabeBlockImport<B: Block, I, C> {
+	inner: Arc<I>,
+	client: Arc<C>,
+	epoch_changes: SharedEpochChanges<B>,
+	config: Config,
+}
+
+impl<B: Block, I, C> BabeBlockImport<B, I, C> {
+	/// Create a new block import wrapping the given block import.
+	pub fn new(
+		inner: Arc<I>,
+		client: Arc<C>,
+		epoch_changes: SharedEpochChanges<B>,
+		config: Config,
+	) -> Self {
+		BabeBlockImport {
+			inner,
+			client,
+			epoch_changes,
+			config,
+		}
+	}
+}
+
+impl<B, I, C> BlockImport<B> for BabeBlockImport<B, I, C> where
+	B: Block,
+	I: BlockImport<B, Error=ConsensusError>,
+	C: HeaderBackend<B> + AuxStore,
+	DigestItemFor<B>: CompatibleDigestItem,
+{
+	type Error = ConsensusError;
+
+	fn check_block(
+		&self,
+		hash: B::Hash,
+		parent_hash: B::Hash,
+	) -> Result<ImportResult, Self::Error> {
+		self.inner.check_block(hash, parent_hash)
+	}
+
+	fn import_block(
+		&self,
+		mut block: ImportBlock<B>,
+		new_cache: HashMap<well_known_cache_keys::Id, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		let hash = block.post_header().hash();
+		let number = *block.header.number();
+		let parent_hash = *block.header.parent_hash();
+		let import_err = |e: String| ConsensusError::from(consensus_common::ErrorKind::ClientImport(e));
+		let pre_digest = find_pre_digest::<B>(&block.header).map_err(import_err)?;
+
+		// the client compares the weight with the one of the best block, which
+		// might still be stored under the legacy key.
+		let best_hash = self.client.info().map_err(|e| import_err(format!("{:?}", e)))?.best_hash;
+		migrate_block_weight::<B, _>(self.client.as_ref(), &best_hash)
+			.map_err(|e| import_err(format!("{:?}", e)))?;
+
+		// only primary blocks add to the weight of a chain, so that the fork
+		// choice prefers chains with more primary blocks.
+		let parent_weight = migrate_block_weight::<B, _>(self.client.as_ref(), &parent_hash)
+			.map_err(|e| import_err(format!("{:?}", e)))?
+			.unwrap_or_default();
+		let weight = parent_weight + if pre_digest.is_primary() { 1 } else { 0 };
+		block.fork_choice = ForkChoiceStrategy::HeaviestChain(weight);
+
+		let next_epoch = match find_next_epoch_digest::<B>(&block.header) {
+			Some(next_epoch) => next_epoch,
+			None => return self.inner.import_block(block, new_cache),
+		};
+
+		let mut epoch_changes = self.epoch_changes.lock();
+		let epoch = epoch_for_child_of(
+			&*epoch_changes,
+			self.client.as_ref(),
+			&self.config,
+			&parent_hash,
+			number - One::one(),
+			pre_digest.slot_number(),
+		).map_err(import_err)?;
+
+		let old_epoch_changes = epoch_changes.clone();
+
+		// the epoch changes are written along with every announcement, so this is where
+		// the changes that can't matter anymore after finality are dropped.
+		let info = self.client.info().map_err(|e| import_err(format!("{:?}", e)))?;
+		epoch_changes.prune(
+			&info.finalized_hash,
+			&info.finalized_number,
+			&is_descendent_of::<B, _>(self.client.as_ref(), None),
+		).map_err(|e| import_err(format!("Could not prune the epoch changes: {:?}", e)))?;
+
+		let is_descendent_of = is_descendent_of::<B, _>(self.client.as_ref(), Some((&hash, &parent_hash)));
+		match epoch_changes.import(hash, number, EpochChange { current: epoch, next: next_epoch }, &is_descendent_of) {
+			Ok(_) | Err(fork_tree::Error::Duplicate) => {},
+			Err(e) => return Err(import_err(format!("Could not import the epoch change of {:?}: {:?}", hash, e))),
+		}
+
+		block.auxiliary.push((EPOCH_CHANGES_KEY.to_vec(), Some(epoch_changes.encode())));
+
+		let result = self.inner.import_block(block, new_cache);
+		match result {
+			Ok(ImportResult::Imported(_)) => {},
+			_ => {
+				debug!(target: "babe", "Restoring the epoch changes after failed import of {:?}", hash);
+				*epoch_changes = old_epoch_changes;
+			},
+		}
+
+		result
+	}
+}
+
 /// The BABE import queue type.
 pub type BabeImportQueue<B> = BasicQueue<B>;
 
+/// Start an import queue for the BABE consensus algorithm.
+///
+/// Returns the import queue, the block import which has to be used for
+/// authoring and the epoch changes which have to be passed to `start_babe`.
+pub fn import_queue<B, I, C, E>(
+	config: Config,
+	block_import: Arc<I>,
+	justification_import: Option<SharedJustificationImport<B>>,
+	client: Arc<C>,
+	extra: E,
+	inherent_data_providers: InherentDataProviders,
+) -> Result<(
+	BabeImportQueue<B>,
+	Arc<BabeBlockImport<B, I, C>>,
+	SharedEpochChanges<B>,
+), consensus_common::Error> where
+	B: Block,
+	I: BlockImport<B, Error=ConsensusError> + Send + Sync + 'static,
+	C: 'static + ProvideRuntimeApi + ProvideCache<B> + HeaderBackend<B> + AuxStore + Send + Sync,
+	C::Api: BlockBuilderApi<B> + AuthoritiesApi<B>,
+	DigestItemFor<B>: CompatibleDigestItem + DigestItem<AuthorityId=Public>,
+	E: 'static + ExtraVerification<B>,
+{
+	register_babe_inherent_data_provider(&inherent_data_providers, config.get())?;
+	let equivocation_reports = EquivocationReports::new();
+	register_equivocation_reports(&inherent_data_providers, &equivocation_reports)?;
+
+	let epoch_changes = load_epoch_changes(&*client)
+		.map_err(|e| consensus_common::ErrorKind::ClientImport(format!("{:?}", e)))?;
+	let block_import = Arc::new(BabeBlockImport::new(
+		block_import,
+		client.clone(),
+		epoch_changes.clone(),
+		config.clone(),
+	));
+	let verifier = Arc::new(BabeVerifier::new(
+		client,
+		extra,
+		inherent_data_providers,
+		config,
+		epoch_changes.clone(),
+		equivocation_reports,
+	));
+	let queue = BasicQueue::new(verifier, block_import.clone() as SharedBlockImport<B>, justification_import);
+
+	Ok((queue, block_import, epoch_changes))
+}
+
 /// Register the babe inherent data provider, if not registered already.
 fn register_babe_inherent_data_provider(
 	inherent_data_providers: &InherentDataProviders,
@@ -797,6 +1528,21 @@ fn register_babe_inherent_data_provider(
 	}
 }
 
+/// Register the given equivocation reports as inherent data provider, so that
+/// block authors include them. Does nothing if reports are registered already.
+fn register_equivocation_reports(
+	inherent_data_providers: &InherentDataProviders,
+	equivocation_reports: &EquivocationReports,
+) -> Result<(), consensus_common::Error> {
+	if !inherent_data_providers.has_provider(&EQUIVOCATION_INHERENT_IDENTIFIER) {
+		inherent_data_providers
+			.register_provider(equivocation_reports.clone())
+			.map_err(inherent_to_common_error)
+	} else {
+		Ok(())
+	}
+}
+
 fn get_keypair(q: &sr25519::Pair) -> &Keypair {
 	q.as_ref()
 }
@@ -819,6 +1565,24 @@ fn check(inout: &VRFInOut, threshold: u64) -> bool {
 	u64::from_le_bytes(inout.make_bytes::<[u8; 8]>(BABE_VRF_PREFIX)) < threshold
 }
 
+/// The author of a secondary slot, which is chosen round-robin from the
+/// authorities of the epoch, with an offset depending on the epoch randomness.
+fn secondary_slot_author<'a>(
+	slot_number: u64,
+	authorities: &'a [sr25519::Public],
+	randomness: &[u8],
+) -> Option<&'a sr25519::Public> {
+	if authorities.is_empty() {
+		return None;
+	}
+
+	let mut offset = [0u8; 8];
+	offset.copy_from_slice(&runtime_io::blake2_256(randomness)[..8]);
+	let index = u64::from_le_bytes(offset).wrapping_add(slot_number) % authorities.len() as u64;
+
+	authorities.get(index as usize)
+}
+
 /// Claim a slot if it is our turn.  Returns `None` if it is not our turn.
 ///
 /// This hashes the slot number, epoch, genesis hash, and chain randomness into
@@ -881,8 +1645,10 @@ mod tests {
 		test_client::runtime::RuntimeApi,
 	>;
 
+	type TestBlockImport = BabeBlockImport<TestBlock, PeersClient, PeersClient>;
+
 	struct DummyFactory(Arc<TestClient>);
-	struct DummyProposer(u64, Arc<TestClient>);
+	struct DummyProposer(<TestBlock as BlockT>::Hash, Arc<TestClient>);
 
 	impl Environment<TestBlock> for DummyFactory {
 		type Proposer = DummyProposer;
@@ -891,7 +1657,7 @@ mod tests {
 		fn init(&self, parent_header: &<TestBlock as BlockT>::Header, _authorities: &[Public])
 			-> Result<DummyProposer, Error>
 		{
-			Ok(DummyProposer(parent_header.number + 1, self.0.clone()))
+			Ok(DummyProposer(parent_header.hash(), self.0.clone()))
 		}
 	}
 
@@ -899,23 +1665,29 @@ mod tests {
 		type Error = Error;
 		type Create = Result<TestBlock, Error>;
 
-		fn propose(&self, _: InherentData, _: Duration) -> Result<TestBlock, Error> {
-			self.1.new_block().unwrap().bake().map_err(|e| e.into())
+		fn propose(&self, _: InherentData, digests: DigestFor<TestBlock>, _: Duration) -> Result<TestBlock, Error> {
+			client::block_builder::BlockBuilder::at_block(&BlockId::Hash(self.0), &*self.1, false, digests)
+				.and_then(|builder| builder.bake())
 		}
 	}
 
 	const SLOT_DURATION: u64 = 1;
 	const TEST_ROUTING_INTERVAL: Duration = Duration::from_millis(50);
 
+	type BabePeerData = Option<(Arc<TestBlockImport>, SharedEpochChanges<TestBlock>)>;
+
 	pub struct BabeTestNet {
-		peers: Vec<Arc<Peer<(), DummySpecialization>>>,
+		peers: Vec<Arc<Peer<BabePeerData, DummySpecialization>>>,
 		started: bool,
+		// the epoch changes of the last verifier, which are shared with the block import
+		// created right after it.
+		epoch_changes: Mutex<Option<SharedEpochChanges<TestBlock>>>,
 	}
 
 	impl TestNetFactory for BabeTestNet {
 		type Specialization = DummySpecialization;
-		type Verifier = BabeVerifier<PeersClient, NothingExtra>;
-		type PeerData = ();
+		type Verifier = BabeVerifier<TestBlock, PeersClient, NothingExtra>;
+		type PeerData = BabePeerData;
 
 		/// Create new test network with peers and given config.
 		fn from_config(_config: &ProtocolConfig) -> Self {
@@ -923,6 +1695,7 @@ mod tests {
 			BabeTestNet {
 				peers: Vec::new(),
 				started: false,
+				epoch_changes: Mutex::new(None),
 			}
 		}
 
@@ -940,12 +1713,33 @@ mod tests {
 			trace!(target: "babe", "Provider registered");
 
 			assert_eq!(config.get(), SLOT_DURATION);
-			Arc::new(BabeVerifier {
+			let epoch_changes = load_epoch_changes(&*client).expect("loads epoch changes");
+			*self.epoch_changes.lock() = Some(epoch_changes.clone());
+			Arc::new(BabeVerifier::new(
 				client,
-				extra: NothingExtra,
+				NothingExtra,
 				inherent_data_providers,
-				threshold: config.threshold(),
-			})
+				config,
+				epoch_changes,
+				EquivocationReports::new(),
+			))
+		}
+
+		fn make_block_import(&self, client: Arc<PeersClient>)
+			-> (SharedBlockImport<TestBlock>, Option<SharedJustificationImport<TestBlock>>, BabePeerData)
+		{
+			let config = Config::get_or_compute(&*client)
+				.expect("slot duration available");
+			let epoch_changes = self.epoch_changes.lock().take()
+				.expect("the verifier is made before the block import; qed");
+			let block_import = Arc::new(BabeBlockImport::new(
+				client.clone(),
+				client,
+				epoch_changes.clone(),
+				config,
+			));
+
+			(block_import.clone(), None, Some((block_import, epoch_changes)))
 		}
 
 		fn peer(&self, i: usize) -> &Peer<Self::PeerData, DummySpecialization> {
@@ -1002,6 +1796,8 @@ mod tests {
 		let mut runtime = current_thread::Runtime::new().unwrap();
 		for (peer_id, key) in peers {
 			let client = net.lock().peer(*peer_id).client().clone();
+			let (block_import, epoch_changes) = net.lock().peer(*peer_id).data.clone()
+				.expect("babe test peers have a block import");
 			let environ = Arc::new(DummyFactory(client.clone()));
 			import_notifications.push(
 				client.import_notification_stream()
@@ -1020,7 +1816,8 @@ mod tests {
 			let babe = start_babe(BabeParams {
 				config,
 				local_key: Arc::new(key.clone().into()),
-				block_import: client.clone(),
+				block_import,
+				epoch_changes,
 				select_chain: LongestChain::new(client.backend().clone(), client.import_lock().clone()),
 				client,
 				env: environ.clone(),
@@ -1091,6 +1888,183 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn epochs_are_looked_up_per_fork() {
+		use test_client::TestClient;
+
+		drop(env_logger::try_init());
+		let client = test_client::new();
+		let config = Config::get_or_compute(&client).expect("slot duration available");
+		let genesis_hash = client.info().unwrap().chain.genesis_hash;
+
+		let import_block = |parent, digests| {
+			let block = client::block_builder::BlockBuilder::at_block(&BlockId::Hash(parent), &client, false, digests)
+				.and_then(|builder| builder.bake())
+				.unwrap();
+			let hash = block.header.hash();
+			client.import(BlockOrigin::Own, block).unwrap();
+			hash
+		};
+
+		let a1 = import_block(genesis_hash, Default::default());
+		let a2 = import_block(a1, Default::default());
+		let mut fork_digests = <DigestFor<TestBlock> as Default>::default();
+		fork_digests.push(<DigestItemFor<TestBlock> as CompatibleDigestItem>::babe_pre_digest(
+			BabePreDigest::Secondary { slot_number: 11 },
+		));
+		let b1 = import_block(genesis_hash, fork_digests);
+
+		let next = Epoch {
+			epoch_index: 1,
+			start_slot: 16,
+			duration: 6,
+			authorities: vec![Keyring::Alice.into()],
+			randomness: [1; 32],
+			secondary_slots: true,
+		};
+		let mut epoch_changes = EpochChanges::<TestBlock>::new();
+		epoch_changes.import(
+			a1,
+			1,
+			EpochChange { current: config.genesis_epoch(10), next: next.clone() },
+			&is_descendent_of::<TestBlock, _>(&client, None),
+		).unwrap();
+
+		let epoch_for = |parent_hash, parent_number: u64, slot_number| epoch_for_child_of(
+			&epoch_changes,
+			&client,
+			&config,
+			&parent_hash,
+			parent_number,
+			slot_number,
+		).unwrap();
+
+		assert_eq!(epoch_for(genesis_hash, 0, 12), config.genesis_epoch(12));
+		assert_eq!(epoch_for(a1, 1, 15), config.genesis_epoch(10));
+		assert_eq!(epoch_for(a2, 2, 15), config.genesis_epoch(10));
+		assert_eq!(epoch_for(a2, 2, 16), next);
+		// the epoch change was announced on another fork.
+		assert_eq!(epoch_for(b1, 1, 16), config.genesis_epoch(16));
+
+		// finality keeps the epoch change the finalized chain depends on.
+		epoch_changes.prune(&a2, &2, &is_descendent_of::<TestBlock, _>(&client, None)).unwrap();
+		assert_eq!(epoch_changes.roots().map(|(hash, _, _)| *hash).collect::<Vec<_>>(), vec![a1]);
+		assert_eq!(
+			epoch_for_child_of(&epoch_changes, &client, &config, &a2, 2, 16).unwrap(),
+			next,
+		);
+
+		epoch_changes.prune(&b1, &1, &is_descendent_of::<TestBlock, _>(&client, None)).unwrap();
+		assert_eq!(epoch_changes.roots().count(), 0);
+	}
+
+	#[test]
+	fn secondary_slot_authors_are_round_robin() {
+		let authorities: Vec<Public> = vec![Keyring::Alice.into(), Keyring::Bob.into(), Keyring::Charlie.into()];
+		let randomness = [1; 32];
+
+		let first = secondary_slot_author(10, &authorities, &randomness).unwrap();
+		let index = authorities.iter().position(|a| a == first).unwrap();
+		for slot in 10..20 {
+			let author = secondary_slot_author(slot, &authorities, &randomness);
+			assert_eq!(author, Some(&authorities[(index + (slot as usize - 10)) % authorities.len()]));
+		}
+
+		assert_eq!(secondary_slot_author(10, &[], &randomness), None);
+	}
+
+	#[test]
+	fn secondary_blocks_are_checked_against_the_setting_of_their_epoch() {
+		let pair = sr25519::Pair::generate();
+		let pre_hash = <TestBlock as BlockT>::Hash::default();
+		let seal = <DigestItemFor<TestBlock> as CompatibleDigestItem>::babe_secondary_seal(BabeSecondarySeal {
+			signer: pair.public(),
+			signature: pair.sign(&(10u64, pre_hash).encode()[..]),
+			slot_num: 10,
+		});
+		let mut epoch = Epoch {
+			epoch_index: 1,
+			start_slot: 10,
+			duration: 6,
+			authorities: vec![pair.public()],
+			randomness: [1; 32],
+			secondary_slots: true,
+		};
+		let check = |epoch: &Epoch| check_seal::<TestBlock>(
+			pre_hash,
+			pre_hash,
+			&seal,
+			BabePreDigest::Secondary { slot_number: 10 },
+			epoch,
+			&[],
+			u64::max_value(),
+		);
+
+		assert_eq!(check(&epoch), Ok(pair.public()));
+		epoch.secondary_slots = false;
+		assert!(check(&epoch).is_err());
+	}
+
+	#[test]
+	fn fork_choice_prefers_primary_blocks() {
+		drop(env_logger::try_init());
+		let client = Arc::new(test_client::new());
+		let config = Config::get_or_compute(&*client).expect("slot duration available");
+		let epoch_changes = load_epoch_changes(&*client).expect("loads epoch changes");
+		let block_import = BabeBlockImport::new(client.clone(), client.clone(), epoch_changes, config);
+		let genesis_hash = client.info().unwrap().chain.genesis_hash;
+
+		let import_block = |parent, pre_digest| {
+			let mut digests = <DigestFor<TestBlock> as Default>::default();
+			digests.push(<DigestItemFor<TestBlock> as CompatibleDigestItem>::babe_pre_digest(pre_digest));
+			let block = client::block_builder::BlockBuilder::at_block(&BlockId::Hash(parent), &*client, false, digests)
+				.and_then(|builder| builder.bake())
+				.unwrap();
+			let (header, body) = block.deconstruct();
+			let hash = header.hash();
+			block_import.import_block(ImportBlock {
+				origin: BlockOrigin::Own,
+				header,
+				justification: None,
+				post_digests: Vec::new(),
+				body: Some(body),
+				finalized: false,
+				auxiliary: Vec::new(),
+				fork_choice: ForkChoiceStrategy::LongestChain,
+			}, Default::default()).unwrap();
+			hash
+		};
+		let best_hash = || client.info().unwrap().chain.best_hash;
+
+		let a1 = import_block(genesis_hash, BabePreDigest::Primary { vrf_output: [1; 32], slot_number: 1 });
+		assert_eq!(best_hash(), a1);
+
+		// a longer chain of secondary blocks is lighter.
+		let b1 = import_block(genesis_hash, BabePreDigest::Secondary { slot_number: 2 });
+		let b2 = import_block(b1, BabePreDigest::Secondary { slot_number: 3 });
+		assert_eq!(best_hash(), a1);
+
+		// with the same weight, the longer chain wins.
+		let b3 = import_block(b2, BabePreDigest::Primary { vrf_output: [2; 32], slot_number: 4 });
+		assert_eq!(best_hash(), b3);
+		assert_eq!(load_block_weight::<TestBlock, _>(&*client, &b3).unwrap(), Some(1));
+	}
+
+	#[test]
+	fn legacy_block_weights_are_migrated() {
+		let client = test_client::new();
+		let hash = primitives::H256::random();
+		client.insert_aux(&[(&legacy_block_weight_key(&hash)[..], &5u32.encode()[..])], &[]).unwrap();
+
+		assert_eq!(load_block_weight::<TestBlock, _>(&client, &hash).unwrap(), Some(5));
+		assert_eq!(client::load_block_weight(&client, &hash).unwrap(), None);
+
+		assert_eq!(migrate_block_weight::<TestBlock, _>(&client, &hash).unwrap(), Some(5));
+		assert_eq!(client::load_block_weight(&client, &hash).unwrap(), Some(5));
+		assert_eq!(client.get_aux(&legacy_block_weight_key(&hash)).unwrap(), None);
+		assert_eq!(load_block_weight::<TestBlock, _>(&client, &hash).unwrap(), Some(5));
+	}
+
 	#[test]
 	fn authorities_call_works() {
 		drop(env_logger::try_init());
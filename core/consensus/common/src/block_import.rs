@@ -18,6 +18,7 @@
 
 use runtime_primitives::traits::{Block as BlockT, DigestItemFor, Header as HeaderT, NumberFor};
 use runtime_primitives::Justification;
+use parity_codec::Encode;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use crate::well_known_cache_keys;
@@ -81,15 +82,28 @@ pub enum BlockOrigin {
 	File,
 }
 
+/// The accumulated fork-choice weight of the chain ending in a block.
+pub type BlockWeight = u128;
+
 /// Fork choice strategy.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ForkChoiceStrategy {
 	/// Longest chain fork choice.
 	LongestChain,
+	/// Heaviest chain fork choice, with the accumulated weight of the chain ending
+	/// in the imported block. The weight is stored as auxiliary data of the block
+	/// (see `fork_choice_weight_key`) and ties are broken by chain length.
+	HeaviestChain(BlockWeight),
 	/// Custom fork choice rule, where true indicates the new block should be the best block.
 	Custom(bool),
 }
 
+/// The auxiliary storage key under which the fork-choice weight of the block
+/// with the given hash is kept.
+pub fn fork_choice_weight_key<H: Encode>(hash: &H) -> Vec<u8> {
+	(b"fork_choice_weight", hash).encode()
+}
+
 /// Data required to import a Block
 pub struct ImportBlock<Block: BlockT> {
 	/// Origin of the Block
@@ -23,16 +23,25 @@
 //!
 //! The `ImportQueue` trait allows such verification strategies to be
 //! instantiated. The `BasicQueue` and `BasicVerifier` traits allow serial
-//! queues to be instantiated simply.
+//! queues to be instantiated simply. Verifiers which report that they can
+//! verify in parallel have their blocks checked on a pool of threads, while
+//! the import itself always happens in order. Verifiers which need the state
+//! of the parent block can still do their expensive checks, e.g. of the seal
+//! signatures, on the pool: a batch is pre-verified as a whole before its
+//! blocks are verified and imported one by one.
 
 use crate::block_import::{
 	BlockImport, BlockOrigin, ImportBlock, ImportedAux, ImportResult, JustificationImport,
 };
 use crossbeam_channel::{self as channel, Receiver, Sender};
 use parity_codec::Encode;
+use parking_lot::Mutex;
 
+use std::collections::{HashSet, VecDeque};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use runtime_primitives::traits::{
 	AuthorityIdFor, Block as BlockT, Header as HeaderT, NumberFor
@@ -51,6 +60,13 @@ const BAD_BLOCK_REPUTATION_CHANGE: i32 = -(1 << 29);
 /// Reputation change for peers which send us a block with bad justifications.
 const BAD_JUSTIFICATION_REPUTATION_CHANGE: i32 = -(1 << 16);
 
+/// Number of threads verifying blocks, for verifiers which can verify in parallel.
+const VERIFICATION_THREADS: usize = 4;
+/// Maximum number of bad blocks remembered in order to reject their descendants.
+const MAX_BAD_BLOCKS: usize = 1024;
+/// Maximum number of pre-verified blocks remembered by `PreVerifiedBlocks`.
+const MAX_PRE_VERIFIED_BLOCKS: usize = 4096;
+
 /// Shared block import struct used by the queue.
 pub type SharedBlockImport<B> = Arc<dyn BlockImport<B, Error = ConsensusError> + Send + Sync>;
 
@@ -87,6 +103,87 @@ pub trait Verifier<B: BlockT>: Send + Sync + Sized {
 		justification: Option<Justification>,
 		body: Option<Vec<B::Extrinsic>>,
 	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityIdFor<B>>>), String>;
+
+	/// Whether blocks can be verified before their ancestors are imported.
+	///
+	/// Verifiers which read the state of the parent block must return `false`,
+	/// in which case blocks are verified and imported one by one, after the
+	/// batch has been pre-verified in parallel if `can_pre_verify` is `true`.
+	fn can_verify_in_parallel(&self) -> bool {
+		false
+	}
+
+	/// Whether the verifier does any checks in `pre_verify`.
+	fn can_pre_verify(&self) -> bool {
+		false
+	}
+
+	/// Do the checks of the block which don't need its parent to be imported.
+	///
+	/// Verifiers which can't verify in parallel are called with all the blocks
+	/// of a batch on the verification threads, and the batch is verified and
+	/// imported once all of them have been pre-verified. The
+	/// verifier may remember that a block has passed the checks (see
+	/// `PreVerifiedBlocks`) and skip them in `verify`, which must still do all
+	/// the checks of the blocks that haven't passed them here.
+	fn pre_verify(&self, _origin: BlockOrigin, _header: &B::Header) {}
+}
+
+/// Bounded memory of the blocks that have passed the checks of `Verifier::pre_verify`.
+///
+/// Each block is stored along with the data the checks depended on, e.g. the
+/// authority expected to sign it, which `pre_verify` had to guess because the
+/// parent of the block wasn't imported yet. `verify` must compare it with the
+/// data read from the state of the parent before skipping the checks.
+pub struct PreVerifiedBlocks<H, D> {
+	inner: Mutex<(HashMap<H, D>, VecDeque<H>)>,
+}
+
+impl<H, D> Default for PreVerifiedBlocks<H, D> where H: ::std::hash::Hash + Eq {
+	fn default() -> Self {
+		PreVerifiedBlocks {
+			inner: Mutex::new((HashMap::new(), VecDeque::new())),
+		}
+	}
+}
+
+impl<H, D> PreVerifiedBlocks<H, D> where H: ::std::hash::Hash + Eq + Clone {
+	/// Note that the block has passed the checks, which depended on `data`.
+	pub fn insert(&self, hash: H, data: D) {
+		let mut inner = self.inner.lock();
+		let (ref mut blocks, ref mut order) = *inner;
+		if blocks.insert(hash.clone(), data).is_none() {
+			order.push_back(hash);
+			if order.len() > MAX_PRE_VERIFIED_BLOCKS {
+				if let Some(oldest) = order.pop_front() {
+					blocks.remove(&oldest);
+				}
+			}
+		}
+	}
+
+	/// Returns the data the checks of the block depended on, if the block has
+	/// passed them. The block is forgotten.
+	pub fn take(&self, hash: &H) -> Option<D> {
+		let mut inner = self.inner.lock();
+		let (ref mut blocks, ref mut order) = *inner;
+		let data = blocks.remove(hash)?;
+		order.retain(|h| h != hash);
+		Some(data)
+	}
+}
+
+/// Statistics about the work done by the import queue.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImportQueueMetrics {
+	/// Number of blocks scheduled for import and not yet processed.
+	pub queued_blocks: usize,
+	/// Number of blocks in the last processed batch.
+	pub processed_blocks: usize,
+	/// Time spent verifying the blocks of the last batch.
+	pub verification_time: Duration,
+	/// Time spent importing the blocks of the last batch.
+	pub import_time: Duration,
 }
 
 /// Blocks import queue API.
@@ -143,7 +240,9 @@ impl<B: BlockT> ImportQueueClone<B> for BasicQueue<B> {
 /// The "import block" work will be offloaded to a single "BlockImportWorker",
 /// running in another thread. Offloading the work is done via a channel,
 /// ensuring blocks in this implementation are imported sequentially and in
-/// order (as received by the "BlockImporter").
+/// order (as received by the "BlockImporter"). If the verifier supports it,
+/// the worker hands the verification of a batch to a pool of threads and
+/// imports each block as soon as it and all blocks before it are verified.
 ///
 /// As long as the "BasicQueue" is not dropped, the "BlockImporter" will keep
 /// running. The "BlockImporter" owns a sender to the "BlockImportWorker",
@@ -228,6 +327,7 @@ pub enum BlockImportWorkerMsg<B: BlockT> {
 			Result<BlockImportResult<NumberFor<B>>, BlockImportError>,
 			B::Hash,
 		)>,
+		ImportQueueMetrics,
 	),
 	#[cfg(any(test, feature = "test-helpers"))]
 	Synchronize,
@@ -244,6 +344,7 @@ struct BlockImporter<B: BlockT> {
 	worker_sender: Sender<BlockImportWorkerMsg<B>>,
 	link: Option<Box<dyn Link<B>>>,
 	justification_import: Option<SharedJustificationImport<B>>,
+	queued_blocks: usize,
 }
 
 impl<B: BlockT> BlockImporter<B> {
@@ -263,6 +364,7 @@ impl<B: BlockT> BlockImporter<B> {
 					worker_sender,
 					link: None,
 					justification_import,
+					queued_blocks: 0,
 				};
 				while importer.run() {
 					// Importing until all senders have been dropped...
@@ -323,6 +425,10 @@ impl<B: BlockT> BlockImporter<B> {
 	}
 
 	fn handle_worker_msg(&mut self, msg: BlockImportWorkerMsg<B>) -> bool {
+		if let BlockImportWorkerMsg::Imported(ref results, _) = msg {
+			self.queued_blocks = self.queued_blocks.saturating_sub(results.len());
+		}
+
 		let link = match self.link.as_ref() {
 			Some(link) => link,
 			None => {
@@ -332,7 +438,11 @@ impl<B: BlockT> BlockImporter<B> {
 		};
 
 		let results = match msg {
-			BlockImportWorkerMsg::Imported(results) => (results),
+			BlockImportWorkerMsg::Imported(results, mut metrics) => {
+				metrics.queued_blocks = self.queued_blocks;
+				link.import_queue_metrics(metrics);
+				results
+			},
 			#[cfg(any(test, feature = "test-helpers"))]
 			BlockImportWorkerMsg::Synchronize => {
 				trace!(target: "sync", "Synchronizing link");
@@ -424,6 +534,7 @@ impl<B: BlockT> BlockImporter<B> {
 
 	fn handle_import_blocks(&mut self, origin: BlockOrigin, blocks: Vec<IncomingBlock<B>>) {
 		trace!(target: "sync", "Scheduling {} blocks for import", blocks.len());
+		self.queued_blocks += blocks.len();
 		self.worker_sender
 			.send(BlockImportWorkerMsg::ImportBlocks(origin, blocks))
 			.expect("1. This is holding a sender to the worker, 2. the worker should not quit while a sender is still held; qed");
@@ -434,6 +545,8 @@ struct BlockImportWorker<B: BlockT, V: Verifier<B>> {
 	result_sender: Sender<BlockImportWorkerMsg<B>>,
 	block_import: SharedBlockImport<B>,
 	verifier: Arc<V>,
+	verification_pool: Option<Sender<VerificationJob<B>>>,
+	bad_blocks: BadBlocks<B::Hash>,
 }
 
 impl<B: BlockT, V: 'static + Verifier<B>> BlockImportWorker<B, V> {
@@ -446,10 +559,17 @@ impl<B: BlockT, V: 'static + Verifier<B>> BlockImportWorker<B, V> {
 		let _ = thread::Builder::new()
 			.name("ImportQueueWorker".into())
 			.spawn(move || {
-				let worker = BlockImportWorker {
+				let verification_pool = if verifier.can_verify_in_parallel() || verifier.can_pre_verify() {
+					Some(spawn_verification_pool(verifier.clone(), VERIFICATION_THREADS))
+				} else {
+					None
+				};
+				let mut worker = BlockImportWorker {
 					result_sender,
 					verifier,
 					block_import,
+					verification_pool,
+					bad_blocks: BadBlocks::default(),
 				};
 				for msg in port.iter() {
 					// Working until all senders have been dropped...
@@ -470,9 +590,8 @@ impl<B: BlockT, V: 'static + Verifier<B>> BlockImportWorker<B, V> {
 		sender
 	}
 
-	fn import_a_batch_of_blocks(&self, origin: BlockOrigin, blocks: Vec<IncomingBlock<B>>) {
+	fn import_a_batch_of_blocks(&mut self, origin: BlockOrigin, blocks: Vec<IncomingBlock<B>>) {
 		let count = blocks.len();
-		let mut imported = 0;
 
 		let blocks_range = match (
 			blocks.first().and_then(|b| b.header.as_ref().map(|h| h.number())),
@@ -485,36 +604,301 @@ impl<B: BlockT, V: 'static + Verifier<B>> BlockImportWorker<B, V> {
 
 		trace!(target: "sync", "Starting import of {} blocks {}", count, blocks_range);
 
-		let mut results = vec![];
+		let mut metrics = ImportQueueMetrics {
+			processed_blocks: count,
+			..Default::default()
+		};
+
+		let results = match self.verification_pool.clone() {
+			Some(ref pool) if self.verifier.can_verify_in_parallel() =>
+				self.import_verified_in_parallel(pool, origin, blocks, &mut metrics),
+			pool => self.import_sequentially(pool.as_ref(), origin, blocks, &mut metrics),
+		};
+		let imported = results.iter().filter(|(result, _)| result.is_ok()).count();
+
+		let _ = self
+			.result_sender
+			.send(BlockImportWorkerMsg::Imported(results, metrics));
 
+		trace!(target: "sync", "Imported {} of {}", imported, count);
+	}
+
+	fn import_sequentially(
+		&mut self,
+		pre_verification_pool: Option<&Sender<VerificationJob<B>>>,
+		origin: BlockOrigin,
+		blocks: Vec<IncomingBlock<B>>,
+		metrics: &mut ImportQueueMetrics,
+	) -> Vec<(Result<BlockImportResult<NumberFor<B>>, BlockImportError>, B::Hash)> {
+		let mut results = Vec::with_capacity(blocks.len());
 		let mut has_error = false;
 
+		// Pre-verify the whole batch in parallel before verifying and importing
+		// its blocks in order.
+		if let Some(pool) = pre_verification_pool {
+			let start = Instant::now();
+			let (done_sender, done_port) = channel::unbounded();
+			for block in &blocks {
+				if let Some(ref header) = block.header {
+					let _ = pool.send(VerificationJob::PreVerify {
+						origin: origin.clone(),
+						header: header.clone(),
+						done: done_sender.clone(),
+					});
+				}
+			}
+			drop(done_sender);
+			// The port is disconnected once every job has been processed.
+			for _ in done_port.iter() {}
+			metrics.verification_time += start.elapsed();
+		}
+
 		// Blocks in the response/drain should be in ascending order.
 		for block in blocks {
-			let import_result = if has_error {
+			let hash = block.hash;
+			let import_result = if let Some(error) = self.bad_blocks.reject_descendant(&block) {
+				Err(error)
+			} else if has_error {
 				Err(BlockImportError::Error)
 			} else {
-				import_single_block(
+				import_single_block_metered(
 					&*self.block_import,
 					origin.clone(),
-					block.clone(),
-					self.verifier.clone(),
+					block,
+					&*self.verifier,
+					metrics,
 				)
 			};
-			let was_ok = import_result.is_ok();
-			results.push((import_result, block.hash));
-			if was_ok {
-				imported += 1;
+			self.bad_blocks.note_result(hash, &import_result);
+			has_error |= import_result.is_err();
+			results.push((import_result, hash));
+		}
+
+		results
+	}
+
+	fn import_verified_in_parallel(
+		&mut self,
+		pool: &Sender<VerificationJob<B>>,
+		origin: BlockOrigin,
+		blocks: Vec<IncomingBlock<B>>,
+		metrics: &mut ImportQueueMetrics,
+	) -> Vec<(Result<BlockImportResult<NumberFor<B>>, BlockImportError>, B::Hash)> {
+		let cancelled = Arc::new(AtomicBool::new(false));
+		let (outcome_sender, outcome_port) = channel::unbounded();
+		let mut pending = Vec::with_capacity(blocks.len());
+
+		// Schedule verification of every block which is neither known, nor
+		// descending from a bad block. The rest is settled right away.
+		for (index, block) in blocks.into_iter().enumerate() {
+			let hash = block.hash;
+			let parent = block.header.as_ref().map(|header| header.parent_hash().clone());
+			let peer = block.origin.clone();
+			let settled = match self.bad_blocks.reject_descendant(&block) {
+				Some(error) => Some(Err(error)),
+				None => match check_incoming_block(&*self.block_import, &block) {
+					Ok(None) | Err(BlockImportError::UnknownParent) => None,
+					Ok(Some(known)) => Some(Ok(known)),
+					Err(error) => Some(Err(error)),
+				},
+			};
+
+			if settled.is_none() {
+				let _ = pool.send(VerificationJob::Verify {
+					index,
+					origin: origin.clone(),
+					block,
+					cancelled: cancelled.clone(),
+					outcome_sender: outcome_sender.clone(),
+				});
+			}
+			pending.push((hash, parent, peer, settled.map(VerificationSlot::Settled)));
+		}
+		drop(outcome_sender);
+
+		let mut results = Vec::with_capacity(pending.len());
+		let mut has_error = false;
+
+		// Import in order, waiting for the verification of each block in turn.
+		for index in 0..pending.len() {
+			let hash = pending[index].0;
+			let parent = pending[index].1;
+			let peer = pending[index].2.clone();
+
+			let import_result = if parent.map_or(false, |parent| self.bad_blocks.contains(&parent)) {
+				Err(BlockImportError::BadBlock(peer))
+			} else if has_error {
+				Err(BlockImportError::Error)
 			} else {
+				while pending[index].3.is_none() {
+					match outcome_port.recv() {
+						Ok(outcome) => {
+							metrics.verification_time += outcome.duration;
+							pending[outcome.index].3 = Some(VerificationSlot::Verified(outcome.result));
+						},
+						Err(_) => break,
+					}
+				}
+
+				match pending[index].3.take() {
+					Some(VerificationSlot::Settled(result)) => result,
+					Some(VerificationSlot::Verified(Ok(verified))) => {
+						let start = Instant::now();
+						let result = match check_verified_block(&*self.block_import, &verified) {
+							Ok(None) => import_verified_block(&*self.block_import, verified),
+							Ok(Some(known)) => Ok(known),
+							Err(error) => Err(error),
+						};
+						metrics.import_time += start.elapsed();
+						result
+					},
+					Some(VerificationSlot::Verified(Err(error))) => Err(error),
+					None => Err(BlockImportError::Error),
+				}
+			};
+
+			if import_result.is_err() && !has_error {
+				// Nothing after the first failure gets imported, so stop verifying.
 				has_error = true;
+				cancelled.store(true, Ordering::SeqCst);
 			}
+			self.bad_blocks.note_result(hash, &import_result);
+			results.push((import_result, hash));
 		}
 
-		let _ = self
-			.result_sender
-			.send(BlockImportWorkerMsg::Imported(results));
+		results
+	}
+}
 
-		trace!(target: "sync", "Imported {} of {}", imported, count);
+/// A block verification scheduled on the verification pool.
+enum VerificationJob<B: BlockT> {
+	/// Verify the block and send the outcome.
+	Verify {
+		index: usize,
+		origin: BlockOrigin,
+		block: IncomingBlock<B>,
+		cancelled: Arc<AtomicBool>,
+		outcome_sender: Sender<VerificationOutcome<B>>,
+	},
+	/// Do the checks of the block which don't need its parent, see `Verifier::pre_verify`,
+	/// and notify `done`.
+	PreVerify {
+		origin: BlockOrigin,
+		header: B::Header,
+		done: Sender<()>,
+	},
+}
+
+/// Result of a block verification done by the verification pool.
+struct VerificationOutcome<B: BlockT> {
+	index: usize,
+	result: Result<VerifiedBlock<B>, BlockImportError>,
+	duration: Duration,
+}
+
+/// State of a block of a batch imported with parallel verification.
+enum VerificationSlot<B: BlockT> {
+	/// The block does not need to be imported, or can't be.
+	Settled(Result<BlockImportResult<NumberFor<B>>, BlockImportError>),
+	/// The block has been verified.
+	Verified(Result<VerifiedBlock<B>, BlockImportError>),
+}
+
+/// Spawn `threads` threads verifying, or pre-verifying, the blocks sent to the returned sender.
+///
+/// The threads keep running until the sender is dropped.
+fn spawn_verification_pool<B: BlockT, V: 'static + Verifier<B>>(
+	verifier: Arc<V>,
+	threads: usize,
+) -> Sender<VerificationJob<B>> {
+	let (sender, port) = channel::unbounded::<VerificationJob<B>>();
+	for n in 0..threads {
+		let port = port.clone();
+		let verifier = verifier.clone();
+		let _ = thread::Builder::new()
+			.name(format!("ImportQueueVerifier{}", n))
+			.spawn(move || {
+				for job in port.iter() {
+					match job {
+						VerificationJob::Verify { index, origin, block, cancelled, outcome_sender } => {
+							if cancelled.load(Ordering::SeqCst) {
+								continue;
+							}
+							let start = Instant::now();
+							let result = verify_incoming_block(&*verifier, origin, block);
+							let _ = outcome_sender.send(VerificationOutcome {
+								index,
+								result,
+								duration: start.elapsed(),
+							});
+						},
+						VerificationJob::PreVerify { origin, header, done } => {
+							verifier.pre_verify(origin, &header);
+							let _ = done.send(());
+						},
+					}
+				}
+			})
+			.expect("ImportQueueVerifier thread spawning failed");
+	}
+	sender
+}
+
+/// Bounded set of blocks which failed verification or import.
+///
+/// Blocks building on top of one of them are rejected without being verified.
+struct BadBlocks<H> {
+	hashes: HashSet<H>,
+	order: VecDeque<H>,
+}
+
+impl<H> Default for BadBlocks<H> where H: ::std::hash::Hash + Eq {
+	fn default() -> Self {
+		BadBlocks {
+			hashes: HashSet::new(),
+			order: VecDeque::new(),
+		}
+	}
+}
+
+impl<H> BadBlocks<H> where H: ::std::hash::Hash + Eq + Clone + ::std::fmt::Debug {
+	fn contains(&self, hash: &H) -> bool {
+		self.hashes.contains(hash)
+	}
+
+	fn insert(&mut self, hash: H) {
+		if self.hashes.insert(hash.clone()) {
+			self.order.push_back(hash);
+			if self.order.len() > MAX_BAD_BLOCKS {
+				if let Some(oldest) = self.order.pop_front() {
+					self.hashes.remove(&oldest);
+				}
+			}
+		}
+	}
+
+	/// Remember the block if the result shows it is bad.
+	fn note_result<N: ::std::fmt::Debug + PartialEq>(
+		&mut self,
+		hash: H,
+		result: &Result<BlockImportResult<N>, BlockImportError>,
+	) {
+		match result {
+			Err(BlockImportError::VerificationFailed(..)) | Err(BlockImportError::BadBlock(_)) =>
+				self.insert(hash),
+			_ => (),
+		}
+	}
+
+	/// Returns an error if the parent of the block is known to be bad.
+	fn reject_descendant<B: BlockT<Hash=H>>(&self, block: &IncomingBlock<B>) -> Option<BlockImportError> {
+		let parent = block.header.as_ref().map(|header| header.parent_hash())?;
+		if self.contains(parent) {
+			debug!(target: "sync", "Rejecting block {:?} building on top of bad block {:?}", block.hash, parent);
+			Some(BlockImportError::BadBlock(block.origin.clone()))
+		} else {
+			None
+		}
 	}
 }
 
@@ -535,6 +919,8 @@ pub trait Link<B: BlockT>: Send {
 	fn report_peer(&self, _who: Origin, _reputation_change: i32) {}
 	/// Restart sync.
 	fn restart(&self) {}
+	/// Import queue statistics, reported after each processed batch of blocks.
+	fn import_queue_metrics(&self, _metrics: ImportQueueMetrics) {}
 	/// Synchronization request has been processed.
 	#[cfg(any(test, feature = "test-helpers"))]
 	fn synchronized(&self) {}
@@ -564,6 +950,16 @@ pub enum BlockImportError {
 	Error,
 }
 
+/// A block which passed verification and is ready to be imported.
+struct VerifiedBlock<B: BlockT> {
+	hash: B::Hash,
+	number: NumberFor<B>,
+	parent: B::Hash,
+	peer: Option<Origin>,
+	import_block: ImportBlock<B>,
+	new_authorities: Option<Vec<AuthorityIdFor<B>>>,
+}
+
 /// Single block import function.
 pub fn import_single_block<B: BlockT, V: Verifier<B>>(
 	import_handle: &BlockImport<B, Error = ConsensusError>,
@@ -571,50 +967,83 @@ pub fn import_single_block<B: BlockT, V: Verifier<B>>(
 	block: IncomingBlock<B>,
 	verifier: Arc<V>,
 ) -> Result<BlockImportResult<NumberFor<B>>, BlockImportError> {
-	let peer = block.origin;
+	import_single_block_metered(import_handle, block_origin, block, &*verifier, &mut Default::default())
+}
 
-	let (header, justification) = match (block.header, block.justification) {
-		(Some(header), justification) => (header, justification),
-		(None, _) => {
-			if let Some(ref peer) = peer {
-				debug!(target: "sync", "Header {} was not provided by {} ", block.hash, peer);
-			} else {
-				debug!(target: "sync", "Header {} was not provided ", block.hash);
-			}
-			return Err(BlockImportError::IncompleteHeader(peer))
-		},
+/// Single block import function, adding the time spent to the given metrics.
+fn import_single_block_metered<B: BlockT, V: Verifier<B>>(
+	import_handle: &BlockImport<B, Error = ConsensusError>,
+	block_origin: BlockOrigin,
+	block: IncomingBlock<B>,
+	verifier: &V,
+	metrics: &mut ImportQueueMetrics,
+) -> Result<BlockImportResult<NumberFor<B>>, BlockImportError> {
+	if let Some(known) = check_incoming_block(import_handle, &block)? {
+		// The block is already imported.
+		return Ok(known);
+	}
+
+	let start = Instant::now();
+	let verified = verify_incoming_block(verifier, block_origin, block);
+	metrics.verification_time += start.elapsed();
+
+	let start = Instant::now();
+	let result = import_verified_block(import_handle, verified?);
+	metrics.import_time += start.elapsed();
+	result
+}
+
+/// Check whether the block can be imported, returning the result of the import
+/// if it is already in the chain.
+fn check_incoming_block<B: BlockT>(
+	import_handle: &BlockImport<B, Error = ConsensusError>,
+	block: &IncomingBlock<B>,
+) -> Result<Option<BlockImportResult<NumberFor<B>>>, BlockImportError> {
+	let header = match block.header {
+		Some(ref header) => header,
+		None => return Err(incomplete_header(&block.hash, &block.origin)),
 	};
 
 	let number = header.number().clone();
 	let hash = header.hash();
 	let parent = header.parent_hash().clone();
+	let result = import_handle.check_block(hash, parent);
 
-	let import_error = |e| {
-		match e {
-			Ok(ImportResult::AlreadyInChain) => {
-				trace!(target: "sync", "Block already in chain {}: {:?}", number, hash);
-				Ok(BlockImportResult::ImportedKnown(number))
-			},
-			Ok(ImportResult::Imported(aux)) => Ok(BlockImportResult::ImportedUnknown(number, aux, peer.clone())),
-			Ok(ImportResult::UnknownParent) => {
-				debug!(target: "sync", "Block with unknown parent {}: {:?}, parent: {:?}", number, hash, parent);
-				Err(BlockImportError::UnknownParent)
-			},
-			Ok(ImportResult::KnownBad) => {
-				debug!(target: "sync", "Peer gave us a bad block {}: {:?}", number, hash);
-				Err(BlockImportError::BadBlock(peer.clone()))
-			},
-			Err(e) => {
-				debug!(target: "sync", "Error importing block {}: {:?}: {:?}", number, hash, e);
-				Err(BlockImportError::Error)
-			}
-		}
-	};
+	match map_import_result::<B>(result, number, hash, parent, &block.origin)? {
+		BlockImportResult::ImportedUnknown { .. } => Ok(None),
+		r => Ok(Some(r)), // Any other successful result means that the block is already imported.
+	}
+}
 
-	match import_error(import_handle.check_block(hash, parent))? {
-		BlockImportResult::ImportedUnknown { .. } => (),
-		r => return Ok(r), // Any other successful result means that the block is already imported.
+/// Same as `check_incoming_block`, for a block which has already been verified.
+fn check_verified_block<B: BlockT>(
+	import_handle: &BlockImport<B, Error = ConsensusError>,
+	block: &VerifiedBlock<B>,
+) -> Result<Option<BlockImportResult<NumberFor<B>>>, BlockImportError> {
+	let result = import_handle.check_block(block.hash, block.parent);
+
+	match map_import_result::<B>(result, block.number, block.hash, block.parent, &block.peer)? {
+		BlockImportResult::ImportedUnknown { .. } => Ok(None),
+		r => Ok(Some(r)),
 	}
+}
+
+/// Verify a block without importing it.
+fn verify_incoming_block<B: BlockT, V: Verifier<B>>(
+	verifier: &V,
+	block_origin: BlockOrigin,
+	block: IncomingBlock<B>,
+) -> Result<VerifiedBlock<B>, BlockImportError> {
+	let peer = block.origin;
+
+	let (header, justification) = match (block.header, block.justification) {
+		(Some(header), justification) => (header, justification),
+		(None, _) => return Err(incomplete_header(&block.hash, &peer)),
+	};
+
+	let number = header.number().clone();
+	let hash = header.hash();
+	let parent = header.parent_hash().clone();
 
 	let (import_block, new_authorities) = verifier.verify(block_origin, header, justification, block.body)
 		.map_err(|msg| {
@@ -626,19 +1055,67 @@ pub fn import_single_block<B: BlockT, V: Verifier<B>>(
 			BlockImportError::VerificationFailed(peer.clone(), msg)
 		})?;
 
+	Ok(VerifiedBlock { hash, number, parent, peer, import_block, new_authorities })
+}
+
+/// Import a block which passed verification.
+fn import_verified_block<B: BlockT>(
+	import_handle: &BlockImport<B, Error = ConsensusError>,
+	block: VerifiedBlock<B>,
+) -> Result<BlockImportResult<NumberFor<B>>, BlockImportError> {
 	let mut cache = HashMap::new();
-	if let Some(authorities) = new_authorities {
+	if let Some(authorities) = block.new_authorities {
 		cache.insert(crate::well_known_cache_keys::AUTHORITIES, authorities.encode());
 	}
 
-	import_error(import_handle.import_block(import_block, cache))
+	let result = import_handle.import_block(block.import_block, cache);
+	map_import_result::<B>(result, block.number, block.hash, block.parent, &block.peer)
+}
+
+fn incomplete_header<H: ::std::fmt::Display>(hash: &H, peer: &Option<Origin>) -> BlockImportError {
+	if let Some(ref peer) = peer {
+		debug!(target: "sync", "Header {} was not provided by {} ", hash, peer);
+	} else {
+		debug!(target: "sync", "Header {} was not provided ", hash);
+	}
+	BlockImportError::IncompleteHeader(peer.clone())
+}
+
+fn map_import_result<B: BlockT>(
+	result: Result<ImportResult, ConsensusError>,
+	number: NumberFor<B>,
+	hash: B::Hash,
+	parent: B::Hash,
+	peer: &Option<Origin>,
+) -> Result<BlockImportResult<NumberFor<B>>, BlockImportError> {
+	match result {
+		Ok(ImportResult::AlreadyInChain) => {
+			trace!(target: "sync", "Block already in chain {}: {:?}", number, hash);
+			Ok(BlockImportResult::ImportedKnown(number))
+		},
+		Ok(ImportResult::Imported(aux)) => Ok(BlockImportResult::ImportedUnknown(number, aux, peer.clone())),
+		Ok(ImportResult::UnknownParent) => {
+			debug!(target: "sync", "Block with unknown parent {}: {:?}, parent: {:?}", number, hash, parent);
+			Err(BlockImportError::UnknownParent)
+		},
+		Ok(ImportResult::KnownBad) => {
+			debug!(target: "sync", "Peer gave us a bad block {}: {:?}", number, hash);
+			Err(BlockImportError::BadBlock(peer.clone()))
+		},
+		Err(e) => {
+			debug!(target: "sync", "Error importing block {}: {:?}: {:?}", number, hash, e);
+			Err(BlockImportError::Error)
+		}
+	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::block_import::ForkChoiceStrategy;
 	use libp2p::PeerId;
-	use test_client::runtime::{Block, Hash};
+	use std::sync::Mutex;
+	use test_client::runtime::{Block, Extrinsic, Hash, Header};
 
 	#[derive(Debug, PartialEq)]
 	enum LinkMsg {
@@ -687,17 +1164,17 @@ mod tests {
 
 		// Send a known
 		let results = vec![(Ok(BlockImportResult::ImportedKnown(Default::default())), Default::default())];
-		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results)).ok().unwrap();
+		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results, Default::default())).ok().unwrap();
 		assert_eq!(link_port.recv(), Ok(LinkMsg::BlockImported));
 
 		// Send a second known
 		let results = vec![(Ok(BlockImportResult::ImportedKnown(Default::default())), Default::default())];
-		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results)).ok().unwrap();
+		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results, Default::default())).ok().unwrap();
 		assert_eq!(link_port.recv(), Ok(LinkMsg::BlockImported));
 
 		// Send an unknown
 		let results = vec![(Ok(BlockImportResult::ImportedUnknown(Default::default(), Default::default(), None)), Default::default())];
-		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results)).ok().unwrap();
+		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results, Default::default())).ok().unwrap();
 		assert_eq!(link_port.recv(), Ok(LinkMsg::BlockImported));
 
 		// Send an unknown with peer and bad justification
@@ -705,34 +1182,294 @@ mod tests {
 		let results = vec![(Ok(BlockImportResult::ImportedUnknown(Default::default(),
 			ImportedAux { needs_justification: true, clear_justification_requests: false, bad_justification: true },
 			Some(peer_id.clone()))), Default::default())];
-		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results)).ok().unwrap();
+		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results, Default::default())).ok().unwrap();
 		assert_eq!(link_port.recv(), Ok(LinkMsg::BlockImported));
 		assert_eq!(link_port.recv(), Ok(LinkMsg::Disconnected));
 
 		// Send an incomplete header
 		let results = vec![(Err(BlockImportError::IncompleteHeader(Some(peer_id.clone()))), Default::default())];
-		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results)).ok().unwrap();
+		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results, Default::default())).ok().unwrap();
 		assert_eq!(link_port.recv(), Ok(LinkMsg::Disconnected));
 		assert_eq!(link_port.recv(), Ok(LinkMsg::Restarted));
 
 		// Send an unknown parent
 		let results = vec![(Err(BlockImportError::UnknownParent), Default::default())];
-		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results)).ok().unwrap();
+		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results, Default::default())).ok().unwrap();
 		assert_eq!(link_port.recv(), Ok(LinkMsg::Restarted));
 
 		// Send a verification failed
 		let results = vec![(Err(BlockImportError::VerificationFailed(Some(peer_id.clone()), String::new())), Default::default())];
-		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results)).ok().unwrap();
+		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results, Default::default())).ok().unwrap();
 		assert_eq!(link_port.recv(), Ok(LinkMsg::Disconnected));
 		assert_eq!(link_port.recv(), Ok(LinkMsg::Restarted));
 
 		// Send an error
 		let results = vec![(Err(BlockImportError::Error), Default::default())];
-		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results)).ok().unwrap();
+		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results, Default::default())).ok().unwrap();
 		assert_eq!(link_port.recv(), Ok(LinkMsg::Restarted));
 
 		// Drop the importer sender first, ensuring graceful shutdown.
 		drop(importer_sender);
 	}
+
+	struct MetricsLink {
+		sender: Sender<ImportQueueMetrics>,
+	}
+
+	impl Link<Block> for MetricsLink {
+		fn import_queue_metrics(&self, metrics: ImportQueueMetrics) {
+			let _ = self.sender.send(metrics);
+		}
+	}
+
+	#[test]
+	fn reports_queue_depth_to_link() {
+		let (result_sender, result_port) = channel::unbounded();
+		let (worker_sender, worker_port) = channel::unbounded();
+		let (metrics_sender, metrics_port) = channel::unbounded();
+		let importer_sender = BlockImporter::<Block>::new(result_port, worker_sender, None);
+		let (ack_sender, start_ack_port) = channel::bounded(4);
+		let _ = importer_sender.send(BlockImportMsg::Start(Box::new(MetricsLink { sender: metrics_sender }), ack_sender));
+		let _ = start_ack_port.recv();
+
+		let peer_id = PeerId::random();
+		let blocks = chain(3).into_iter().map(|header| incoming(header, &peer_id)).collect();
+		let _ = importer_sender.send(BlockImportMsg::ImportBlocks(BlockOrigin::NetworkInitialSync, blocks));
+		match worker_port.recv() {
+			Ok(BlockImportWorkerMsg::ImportBlocks(_, blocks)) => assert_eq!(blocks.len(), 3),
+			_ => panic!("Expected blocks to be scheduled"),
+		}
+
+		// The worker processed only one of the three blocks.
+		let metrics = ImportQueueMetrics {
+			processed_blocks: 1,
+			verification_time: Duration::from_millis(5),
+			..Default::default()
+		};
+		let results = vec![(Ok(BlockImportResult::ImportedKnown(Default::default())), Default::default())];
+		let _ = result_sender.send(BlockImportWorkerMsg::Imported(results, metrics.clone()));
+		assert_eq!(metrics_port.recv(), Ok(ImportQueueMetrics { queued_blocks: 2, ..metrics }));
+
+		drop(importer_sender);
+	}
+
+	/// Block import which knows only about the blocks imported through it.
+	#[derive(Default)]
+	struct TestBlockImport {
+		imported: Mutex<Vec<Hash>>,
+	}
+
+	impl BlockImport<Block> for TestBlockImport {
+		type Error = ConsensusError;
+
+		fn check_block(&self, hash: Hash, parent_hash: Hash) -> Result<ImportResult, ConsensusError> {
+			let imported = self.imported.lock().unwrap();
+			if imported.contains(&hash) {
+				Ok(ImportResult::AlreadyInChain)
+			} else if parent_hash == Default::default() || imported.contains(&parent_hash) {
+				Ok(ImportResult::Imported(Default::default()))
+			} else {
+				Ok(ImportResult::UnknownParent)
+			}
+		}
+
+		fn import_block(
+			&self,
+			block: ImportBlock<Block>,
+			_cache: HashMap<crate::well_known_cache_keys::Id, Vec<u8>>,
+		) -> Result<ImportResult, ConsensusError> {
+			let hash = block.header.hash();
+			let result = self.check_block(hash, block.header.parent_hash().clone())?;
+			if let ImportResult::Imported(_) = result {
+				self.imported.lock().unwrap().push(hash);
+			}
+			Ok(result)
+		}
+	}
+
+	/// Verifier accepting every block but the given one.
+	struct TestVerifier {
+		parallel: bool,
+		bad: Option<Hash>,
+	}
+
+	impl Verifier<Block> for TestVerifier {
+		fn verify(
+			&self,
+			origin: BlockOrigin,
+			header: Header,
+			justification: Option<Justification>,
+			body: Option<Vec<Extrinsic>>,
+		) -> Result<(ImportBlock<Block>, Option<Vec<AuthorityIdFor<Block>>>), String> {
+			if Some(header.hash()) == self.bad {
+				return Err("Bad block".into());
+			}
+
+			Ok((ImportBlock {
+				origin,
+				header,
+				justification,
+				post_digests: Vec::new(),
+				body,
+				finalized: false,
+				auxiliary: Vec::new(),
+				fork_choice: ForkChoiceStrategy::LongestChain,
+			}, None))
+		}
+
+		fn can_verify_in_parallel(&self) -> bool {
+			self.parallel
+		}
+	}
+
+	/// Verifier accepting only the blocks which have been pre-verified along with
+	/// the last block of their batch.
+	#[derive(Default)]
+	struct PreVerifyingVerifier {
+		last: Hash,
+		pre_verified: Mutex<HashSet<Hash>>,
+	}
+
+	impl Verifier<Block> for PreVerifyingVerifier {
+		fn verify(
+			&self,
+			origin: BlockOrigin,
+			header: Header,
+			justification: Option<Justification>,
+			body: Option<Vec<Extrinsic>>,
+		) -> Result<(ImportBlock<Block>, Option<Vec<AuthorityIdFor<Block>>>), String> {
+			let pre_verified = self.pre_verified.lock().unwrap();
+			if !pre_verified.contains(&header.hash()) || !pre_verified.contains(&self.last) {
+				return Err("Not pre-verified".into());
+			}
+
+			Ok((ImportBlock {
+				origin,
+				header,
+				justification,
+				post_digests: Vec::new(),
+				body,
+				finalized: false,
+				auxiliary: Vec::new(),
+				fork_choice: ForkChoiceStrategy::LongestChain,
+			}, None))
+		}
+
+		fn can_pre_verify(&self) -> bool {
+			true
+		}
+
+		fn pre_verify(&self, _origin: BlockOrigin, header: &Header) {
+			self.pre_verified.lock().unwrap().insert(header.hash());
+		}
+	}
+
+	fn chain(len: u64) -> Vec<Header> {
+		let mut parent_hash = Default::default();
+		(1..=len).map(|number| {
+			let header = Header::new(number, Default::default(), Default::default(), parent_hash, Default::default());
+			parent_hash = header.hash();
+			header
+		}).collect()
+	}
+
+	fn incoming(header: Header, peer_id: &PeerId) -> IncomingBlock<Block> {
+		IncomingBlock {
+			hash: header.hash(),
+			header: Some(header),
+			body: Some(Vec::new()),
+			justification: None,
+			origin: Some(peer_id.clone()),
+		}
+	}
+
+	fn import_batch(
+		worker_sender: &Sender<BlockImportWorkerMsg<Block>>,
+		result_port: &Receiver<BlockImportWorkerMsg<Block>>,
+		blocks: Vec<IncomingBlock<Block>>,
+	) -> (Vec<Result<BlockImportResult<u64>, BlockImportError>>, ImportQueueMetrics) {
+		worker_sender.send(BlockImportWorkerMsg::ImportBlocks(BlockOrigin::NetworkInitialSync, blocks)).unwrap();
+		match result_port.recv() {
+			Ok(BlockImportWorkerMsg::Imported(results, metrics)) =>
+				(results.into_iter().map(|(result, _)| result).collect(), metrics),
+			_ => panic!("Expected import results"),
+		}
+	}
+
+	#[test]
+	fn parallel_verification_imports_blocks_in_order() {
+		let block_import = Arc::new(TestBlockImport::default());
+		let (result_sender, result_port) = channel::unbounded();
+		let verifier = Arc::new(TestVerifier { parallel: true, bad: None });
+		let worker_sender = BlockImportWorker::new(result_sender, verifier, block_import.clone());
+
+		let peer_id = PeerId::random();
+		let headers = chain(16);
+		let hashes: Vec<_> = headers.iter().map(|header| header.hash()).collect();
+		let blocks = headers.into_iter().map(|header| incoming(header, &peer_id)).collect();
+
+		let (results, metrics) = import_batch(&worker_sender, &result_port, blocks);
+		assert_eq!(results.len(), 16);
+		for (number, result) in (1..).zip(results) {
+			assert_eq!(result, Ok(BlockImportResult::ImportedUnknown(number, Default::default(), Some(peer_id.clone()))));
+		}
+		assert_eq!(metrics.processed_blocks, 16);
+		assert_eq!(*block_import.imported.lock().unwrap(), hashes);
+
+		// Importing the same blocks again doesn't verify them.
+		let blocks = chain(16).into_iter().map(|header| incoming(header, &peer_id)).collect();
+		let (results, metrics) = import_batch(&worker_sender, &result_port, blocks);
+		assert!(results.into_iter().all(|result| match result {
+			Ok(BlockImportResult::ImportedKnown(_)) => true,
+			_ => false,
+		}));
+		assert_eq!(metrics.verification_time, Duration::default());
+	}
+
+	#[test]
+	fn batches_are_pre_verified_before_being_imported() {
+		let headers = chain(16);
+		let hashes: Vec<_> = headers.iter().map(|header| header.hash()).collect();
+		let block_import = Arc::new(TestBlockImport::default());
+		let (result_sender, result_port) = channel::unbounded();
+		let verifier = Arc::new(PreVerifyingVerifier { last: hashes[15], ..Default::default() });
+		let worker_sender = BlockImportWorker::new(result_sender, verifier, block_import.clone());
+
+		let peer_id = PeerId::random();
+		let blocks = headers.into_iter().map(|header| incoming(header, &peer_id)).collect();
+		let (results, _) = import_batch(&worker_sender, &result_port, blocks);
+		for (number, result) in (1..).zip(results) {
+			assert_eq!(result, Ok(BlockImportResult::ImportedUnknown(number, Default::default(), Some(peer_id.clone()))));
+		}
+		assert_eq!(*block_import.imported.lock().unwrap(), hashes);
+	}
+
+	#[test]
+	fn descendants_of_bad_blocks_are_rejected() {
+		for &parallel in &[false, true] {
+			let headers = chain(4);
+			let bad = headers[1].hash();
+			let block_import = Arc::new(TestBlockImport::default());
+			let (result_sender, result_port) = channel::unbounded();
+			let verifier = Arc::new(TestVerifier { parallel, bad: Some(bad) });
+			let worker_sender = BlockImportWorker::new(result_sender, verifier, block_import.clone());
+
+			let peer_id = PeerId::random();
+			let mut blocks: Vec<_> = headers.into_iter().map(|header| incoming(header, &peer_id)).collect();
+			let last = blocks.pop().unwrap();
+
+			let (results, _) = import_batch(&worker_sender, &result_port, blocks);
+			assert_eq!(results, vec![
+				Ok(BlockImportResult::ImportedUnknown(1, Default::default(), Some(peer_id.clone()))),
+				Err(BlockImportError::VerificationFailed(Some(peer_id.clone()), "Bad block".into())),
+				Err(BlockImportError::BadBlock(Some(peer_id.clone()))),
+			]);
+
+			// A later descendant is rejected as well.
+			let (results, _) = import_batch(&worker_sender, &result_port, vec![last]);
+			assert_eq!(results, vec![Err(BlockImportError::BadBlock(Some(peer_id.clone())))]);
+			assert_eq!(block_import.imported.lock().unwrap().len(), 1);
+		}
+	}
 }
 
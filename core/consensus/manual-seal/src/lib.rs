@@ -0,0 +1,439 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! # Manual and instant seal
+//!
+//! Consensus for development and testing chains, where blocks are authored on
+//! request instead of in slots:
+//!
+//! - `run_instant_seal` authors a block whenever a transaction enters the
+//!   transaction pool.
+//! - `run_manual_seal` authors and finalizes blocks on the commands received
+//!   through the `engine_createBlock` and `engine_finalizeBlock` RPC calls.
+//!   A block may be built on any known parent, to create forks on purpose.
+//!
+//! Blocks are built by the given proposer factory, usually the one of
+//! `basic-authorship`, and imported through the given block import, usually
+//! the client. They carry no seal, so anyone can author them: never use this
+//! for a chain with untrusted peers.
+//!
+//! # Stability
+//!
+//! This crate is highly unstable and experimental. Breaking changes may
+//! happen at any point.
+#![forbid(unsafe_code, missing_docs)]
+#![deny(warnings)]
+
+use std::{fmt::Debug, sync::Arc, time::Duration};
+use parking_lot::Mutex;
+use client::{Client, CallExecutor, backend::Backend};
+use consensus_common::{
+	BlockOrigin, Environment, ForkChoiceStrategy, ImportBlock, ImportResult, Proposer, SelectChain,
+};
+use consensus_common::import_queue::{
+	BasicQueue, SharedBlockImport, SharedJustificationImport, Verifier,
+};
+use inherents::{InherentData, InherentDataProviders, InherentIdentifier, ProvideInherentData, RuntimeString};
+use primitives::{Blake2Hasher, H256};
+use runtime_primitives::{Justification, generic::BlockId};
+use runtime_primitives::traits::{AuthorityIdFor, Block as BlockT, DigestFor, Header as HeaderT};
+use futures::{Future, IntoFuture, Stream};
+use log::{info, warn};
+
+pub use rpc::engine::{CreatedBlock, EngineCommand, EngineCommandStream};
+
+/// Maximum time spent building a block.
+const BUILD_TIME: Duration = Duration::from_secs(1);
+
+/// Authors blocks on request.
+pub struct ManualSeal<B, E, Block: BlockT, RA, Env, SC> {
+	client: Arc<Client<B, E, Block, RA>>,
+	block_import: SharedBlockImport<Block>,
+	env: Arc<Env>,
+	select_chain: SC,
+	inherent_data_providers: InherentDataProviders,
+}
+
+impl<B, E, Block: BlockT, RA, Env, SC: Clone> Clone for ManualSeal<B, E, Block, RA, Env, SC> {
+	fn clone(&self) -> Self {
+		ManualSeal {
+			client: self.client.clone(),
+			block_import: self.block_import.clone(),
+			env: self.env.clone(),
+			select_chain: self.select_chain.clone(),
+			inherent_data_providers: self.inherent_data_providers.clone(),
+		}
+	}
+}
+
+impl<B, E, Block, RA, Env, SC> ManualSeal<B, E, Block, RA, Env, SC> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher> + Clone + Send + Sync,
+	Block: BlockT<Hash=H256>,
+	Env: Environment<Block>,
+	Env::Error: Debug,
+	SC: SelectChain<Block>,
+{
+	/// Create a new instance, building blocks with the proposers of `env` and
+	/// importing them through `block_import`.
+	pub fn new(
+		client: Arc<Client<B, E, Block, RA>>,
+		block_import: SharedBlockImport<Block>,
+		env: Arc<Env>,
+		select_chain: SC,
+		inherent_data_providers: InherentDataProviders,
+	) -> Self {
+		ManualSeal {
+			client,
+			block_import,
+			env,
+			select_chain,
+			inherent_data_providers,
+		}
+	}
+
+	/// Author a block on top of the given parent, or the best block, and
+	/// import it, finalized if requested.
+	pub fn create_block(
+		&self,
+		parent_hash: Option<Block::Hash>,
+		finalize: bool,
+	) -> Result<CreatedBlock<Block::Hash>, String> {
+		let parent = match parent_hash {
+			Some(hash) => self.client.header(&BlockId::Hash(hash))
+				.map_err(|e| e.to_string())?
+				.ok_or_else(|| format!("Unknown parent block {:?}", hash))?,
+			None => self.select_chain.best_chain().map_err(|e| e.to_string())?,
+		};
+
+		let proposer = self.env.init(&parent, &[])
+			.map_err(|e| format!("Could not create proposer: {:?}", e))?;
+		let inherent_data = self.inherent_data_providers.create_inherent_data()
+			.map_err(String::from)?;
+		let block = proposer.propose(inherent_data, DigestFor::<Block>::default(), BUILD_TIME)
+			.into_future()
+			.wait()
+			.map_err(|e| format!("Could not propose a block: {:?}", e))?;
+
+		let (header, body) = block.deconstruct();
+		let hash = header.hash();
+		let number = *header.number();
+		let import_block = ImportBlock {
+			origin: BlockOrigin::Own,
+			header,
+			justification: None,
+			post_digests: Vec::new(),
+			body: Some(body),
+			finalized: finalize,
+			auxiliary: Vec::new(),
+			fork_choice: ForkChoiceStrategy::LongestChain,
+		};
+
+		match self.block_import.import_block(import_block, Default::default()) {
+			Ok(ImportResult::Imported(_)) => {
+				info!(target: "manual-seal", "Sealed block #{} ({:?})", number, hash);
+				Ok(CreatedBlock { hash })
+			},
+			Ok(res) => Err(format!("Block {:?} was not imported: {:?}", hash, res)),
+			Err(e) => Err(format!("Error importing block {:?}: {}", hash, e)),
+		}
+	}
+
+	/// Finalize the given block, and all its ancestors.
+	pub fn finalize_block(&self, hash: Block::Hash) -> Result<(), String> {
+		self.client.finalize_block(BlockId::Hash(hash), None, true)
+			.map_err(|e| e.to_string())?;
+
+		info!(target: "manual-seal", "Finalized block {:?}", hash);
+		Ok(())
+	}
+}
+
+/// Author and finalize blocks on the received commands, usually the ones of
+/// the `engine` RPC.
+pub fn run_manual_seal<B, E, Block, RA, Env, SC, S>(
+	seal: ManualSeal<B, E, Block, RA, Env, SC>,
+	commands: S,
+) -> impl Future<Item=(), Error=()> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher> + Clone + Send + Sync,
+	Block: BlockT<Hash=H256>,
+	Env: Environment<Block>,
+	Env::Error: Debug,
+	SC: SelectChain<Block>,
+	S: Stream<Item=EngineCommand<Block::Hash>, Error=()>,
+{
+	commands.for_each(move |command| {
+		match command {
+			EngineCommand::CreateBlock { parent_hash, finalize, sender } => {
+				let res = seal.create_block(parent_hash, finalize);
+				if let Err(ref e) = res {
+					warn!(target: "manual-seal", "Creating block failed: {}", e);
+				}
+				let _ = sender.send(res);
+			},
+			EngineCommand::FinalizeBlock { hash, sender } => {
+				let res = seal.finalize_block(hash);
+				if let Err(ref e) = res {
+					warn!(target: "manual-seal", "Finalizing block {:?} failed: {}", hash, e);
+				}
+				let _ = sender.send(res);
+			},
+		}
+
+		Ok(())
+	})
+}
+
+/// Author a block on top of the best block whenever a notification is
+/// received, usually the import notifications of the transaction pool.
+pub fn run_instant_seal<B, E, Block, RA, Env, SC, S>(
+	seal: ManualSeal<B, E, Block, RA, Env, SC>,
+	pool_imports: S,
+) -> impl Future<Item=(), Error=()> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher> + Clone + Send + Sync,
+	Block: BlockT<Hash=H256>,
+	Env: Environment<Block>,
+	Env::Error: Debug,
+	SC: SelectChain<Block>,
+	S: Stream<Item=(), Error=()>,
+{
+	pool_imports.for_each(move |()| {
+		if let Err(e) = seal.create_block(None, false) {
+			warn!(target: "manual-seal", "Creating block failed: {}", e);
+		}
+
+		Ok(())
+	})
+}
+
+/// A verifier which accepts blocks as they are, as manually sealed blocks
+/// carry no seal to check.
+pub struct ManualSealVerifier;
+
+impl<B: BlockT> Verifier<B> for ManualSealVerifier {
+	fn verify(
+		&self,
+		origin: BlockOrigin,
+		header: B::Header,
+		justification: Option<Justification>,
+		body: Option<Vec<B::Extrinsic>>,
+	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityIdFor<B>>>), String> {
+		let import_block = ImportBlock {
+			origin,
+			header,
+			justification,
+			post_digests: Vec::new(),
+			body,
+			finalized: false,
+			auxiliary: Vec::new(),
+			fork_choice: ForkChoiceStrategy::LongestChain,
+		};
+
+		Ok((import_block, None))
+	}
+
+	fn can_verify_in_parallel(&self) -> bool {
+		true
+	}
+}
+
+/// The manual seal import queue type.
+pub type ManualSealImportQueue<B> = BasicQueue<B>;
+
+/// Start an import queue for blocks authored by manual or instant seal.
+pub fn import_queue<B: BlockT>(
+	block_import: SharedBlockImport<B>,
+	justification_import: Option<SharedJustificationImport<B>>,
+) -> ManualSealImportQueue<B> {
+	BasicQueue::new(Arc::new(ManualSealVerifier), block_import, justification_import)
+}
+
+/// Provides timestamps which advance by a fixed period on every block
+/// instead of following the clock, starting from the current time.
+///
+/// Runtimes which require the timestamp to advance by a minimum period per
+/// block, e.g. to be in a later Aura slot, reject blocks authored in quick
+/// succession with the clock's timestamps.
+pub struct IncrementingTimestampProvider {
+	next: Mutex<srml_timestamp::InherentType>,
+	period: srml_timestamp::InherentType,
+}
+
+impl IncrementingTimestampProvider {
+	/// Create a new instance, advancing the timestamp by `period` per block.
+	pub fn new(period: srml_timestamp::InherentType) -> Result<Self, RuntimeString> {
+		let mut inherent_data = InherentData::new();
+		srml_timestamp::InherentDataProvider.provide_inherent_data(&mut inherent_data)?;
+		let now = inherent_data.get_data(&srml_timestamp::INHERENT_IDENTIFIER)?
+			.ok_or_else(|| RuntimeString::from("Timestamp inherent data not provided"))?;
+
+		Ok(IncrementingTimestampProvider {
+			next: Mutex::new(now),
+			period,
+		})
+	}
+}
+
+impl ProvideInherentData for IncrementingTimestampProvider {
+	fn inherent_identifier(&self) -> &'static InherentIdentifier {
+		&srml_timestamp::INHERENT_IDENTIFIER
+	}
+
+	fn provide_inherent_data(&self, inherent_data: &mut InherentData) -> Result<(), RuntimeString> {
+		let mut next = self.next.lock();
+		inherent_data.put_data(srml_timestamp::INHERENT_IDENTIFIER, &*next)?;
+		*next += self.period;
+		Ok(())
+	}
+
+	fn error_to_string(&self, error: &[u8]) -> Option<String> {
+		srml_timestamp::InherentDataProvider.error_to_string(error)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use basic_authorship::ProposerFactory;
+	use client::LongestChain;
+	use consensus_common::BlockImport;
+	use futures::sync::{mpsc, oneshot};
+	use test_client::{self, runtime::{Block, Extrinsic, RuntimeApi, Transfer}, AccountKeyring};
+	use transaction_pool::txpool::Pool;
+
+	type TestClient = Client<test_client::Backend, test_client::Executor, Block, RuntimeApi>;
+	type TestPoolApi = transaction_pool::ChainApi<TestClient, Block>;
+	type TestSeal = ManualSeal<
+		test_client::Backend,
+		test_client::Executor,
+		Block,
+		RuntimeApi,
+		ProposerFactory<TestClient, TestPoolApi>,
+		LongestChain<test_client::Backend, Block>,
+	>;
+
+	fn extrinsic(nonce: u64) -> Extrinsic {
+		Transfer {
+			amount: Default::default(),
+			nonce,
+			from: AccountKeyring::Alice.into(),
+			to: Default::default(),
+		}.into_signed_tx()
+	}
+
+	fn seal() -> (Arc<TestClient>, Arc<Pool<TestPoolApi>>, TestSeal) {
+		let client = Arc::new(test_client::new());
+		let pool = Arc::new(Pool::new(Default::default(), transaction_pool::ChainApi::new(client.clone())));
+		let env = Arc::new(ProposerFactory {
+			client: client.clone(),
+			transaction_pool: pool.clone(),
+			inherents_pool: Default::default(),
+		});
+		let select_chain = LongestChain::new(client.backend().clone(), client.import_lock());
+		let block_import: SharedBlockImport<Block> = client.clone();
+		let seal = ManualSeal::new(client.clone(), block_import, env, select_chain, Default::default());
+
+		(client, pool, seal)
+	}
+
+	#[test]
+	fn instant_seal_authors_block_on_pool_import() {
+		let (client, pool, seal) = seal();
+		let (sink, stream) = mpsc::unbounded();
+
+		pool.submit_one(&BlockId::number(0), extrinsic(0)).unwrap();
+		sink.unbounded_send(()).unwrap();
+		drop(sink);
+		run_instant_seal(seal, stream).wait().unwrap();
+
+		let info = client.info().unwrap().chain;
+		assert_eq!(info.best_number, 1);
+		let body = client.body(&BlockId::number(1)).unwrap().unwrap();
+		assert_eq!(body, vec![extrinsic(0)]);
+	}
+
+	#[test]
+	fn manual_seal_creates_forks_and_finalizes() {
+		let (client, _pool, seal) = seal();
+		let (sink, stream) = mpsc::unbounded();
+
+		let genesis = client.info().unwrap().chain.genesis_hash;
+		let (tx1, rx1) = oneshot::channel();
+		sink.unbounded_send(EngineCommand::CreateBlock { parent_hash: None, finalize: false, sender: tx1 }).unwrap();
+		let (tx2, rx2) = oneshot::channel();
+		sink.unbounded_send(EngineCommand::CreateBlock { parent_hash: None, finalize: false, sender: tx2 }).unwrap();
+		let (tx3, rx3) = oneshot::channel();
+		sink.unbounded_send(EngineCommand::CreateBlock { parent_hash: Some(genesis), finalize: false, sender: tx3 }).unwrap();
+		drop(sink);
+		run_manual_seal(seal.clone(), stream).wait().unwrap();
+
+		let block1 = rx1.wait().unwrap().unwrap().hash;
+		let block2 = rx2.wait().unwrap().unwrap().hash;
+		let fork = rx3.wait().unwrap().unwrap().hash;
+		assert_ne!(block1, fork);
+		assert_eq!(client.header(&BlockId::Hash(fork)).unwrap().unwrap().parent_hash, genesis);
+		assert_eq!(client.info().unwrap().chain.best_hash, block2);
+
+		// the fork can no longer be finalized once its sibling is.
+		seal.finalize_block(block1).unwrap();
+		assert_eq!(client.info().unwrap().chain.finalized_hash, block1);
+		assert!(seal.finalize_block(fork).is_err());
+
+		let unknown = H256::repeat_byte(1);
+		assert!(seal.create_block(Some(unknown), false).is_err());
+	}
+
+	#[test]
+	fn created_blocks_can_be_imported_through_the_verifier() {
+		let (client, _pool, seal) = seal();
+		let hash = seal.create_block(None, false).unwrap().hash;
+		let header = client.header(&BlockId::Hash(hash)).unwrap().unwrap();
+		let body = client.body(&BlockId::Hash(hash)).unwrap();
+
+		let other = test_client::new();
+		let (import_block, _) = Verifier::<Block>::verify(
+			&ManualSealVerifier,
+			BlockOrigin::NetworkBroadcast,
+			header,
+			None,
+			body,
+		).unwrap();
+		match other.import_block(import_block, Default::default()).unwrap() {
+			ImportResult::Imported(_) => {},
+			res => panic!("unexpected import result {:?}", res),
+		}
+		assert_eq!(other.info().unwrap().chain.best_hash, hash);
+	}
+
+	#[test]
+	fn timestamps_advance_by_period() {
+		let provider = IncrementingTimestampProvider::new(10).unwrap();
+		let timestamp = || {
+			let mut data = InherentData::new();
+			provider.provide_inherent_data(&mut data).unwrap();
+			data.get_data::<srml_timestamp::InherentType>(&srml_timestamp::INHERENT_IDENTIFIER)
+				.unwrap()
+				.unwrap()
+		};
+
+		let first = timestamp();
+		assert_eq!(timestamp(), first + 10);
+		assert_eq!(timestamp(), first + 20);
+	}
+}
@@ -0,0 +1,42 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Primitives for proof-of-work consensus.
+#![forbid(warnings, unsafe_code, missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use rstd::vec::Vec;
+use runtime_primitives::ConsensusEngineId;
+use substrate_client::decl_runtime_apis;
+
+/// The `ConsensusEngineId` of proof-of-work.
+pub const POW_ENGINE_ID: ConsensusEngineId = [b'p', b'o', b'w', b'_'];
+
+/// The seal of a block, as produced by a proof-of-work algorithm.
+pub type Seal = Vec<u8>;
+
+/// The difficulty a block has to meet. The accumulated difficulty of a chain
+/// is its weight for the fork choice.
+pub type Difficulty = u128;
+
+decl_runtime_apis! {
+	/// API necessary for proof-of-work block authorship and verification.
+	pub trait DifficultyApi {
+		/// Return the difficulty which the seal of a block built on top of
+		/// the current block has to meet.
+		fn difficulty() -> Difficulty;
+	}
+}
@@ -0,0 +1,676 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! # Proof-of-work consensus
+//!
+//! Permissionless block authorship: anyone may author a block by finding a
+//! seal for it. The seal is computed by a pluggable `PowAlgorithm` over the
+//! hash of the header without the seal (the "pre-hash"), and must meet the
+//! difficulty which the runtime returns at the parent block through the
+//! `DifficultyApi`.
+//!
+//! The seal is the last digest item of a header. The chain with the most
+//! accumulated difficulty is the best one, which `PowBlockImport` makes the
+//! client aware of by importing blocks with the `HeaviestChain` fork choice.
+//!
+//! `Blake2Algorithm` is a reference algorithm, mostly useful for test chains.
+//!
+//! # Stability
+//!
+//! This crate is highly unstable and experimental. Breaking changes may
+//! happen at any point.
+#![forbid(unsafe_code, missing_docs)]
+#![deny(warnings)]
+
+pub use pow_primitives::*;
+
+use std::{collections::HashMap, fmt::Debug, marker::PhantomData, sync::Arc, thread, time::Duration};
+use std::sync::atomic::{AtomicBool, Ordering};
+use parity_codec::{Decode, Encode};
+use primitives::{blake2_256, U256};
+use runtime_primitives::{generic, generic::BlockId, Justification};
+use runtime_primitives::traits::{
+	AuthorityIdFor, Block, Digest, DigestFor, DigestItemFor, Header, ProvideRuntimeApi,
+};
+use consensus_common::{
+	BlockImport, BlockOrigin, Environment, Error as ConsensusError, ErrorKind as ConsensusErrorKind,
+	ForkChoiceStrategy, ImportBlock, ImportResult, Proposer, SelectChain, SyncOracle,
+	well_known_cache_keys,
+};
+use consensus_common::import_queue::{
+	BasicQueue, SharedBlockImport, SharedJustificationImport, Verifier, PreVerifiedBlocks,
+};
+use client::{
+	ImportNotifications,
+	backend::AuxStore,
+	block_builder::api::BlockBuilder as BlockBuilderApi,
+	runtime_api::ApiExt,
+};
+use inherents::{InherentDataProviders, RuntimeString};
+use futures::{Future, IntoFuture, Stream};
+use parking_lot::Mutex;
+use log::{debug, info, warn};
+
+/// Number of nonces the reference algorithm tries in a single round of mining.
+const NONCES_PER_ROUND: u64 = 10_000;
+
+/// A proof-of-work algorithm, which seals blocks and checks their seals.
+pub trait PowAlgorithm<B: Block> {
+	/// The difficulty the seal of a block built on top of `parent` has to meet.
+	fn difficulty(&self, parent: &BlockId<B>) -> Result<Difficulty, String>;
+
+	/// Check that the seal is valid for the pre-hash and meets the difficulty.
+	fn verify(
+		&self,
+		parent: &BlockId<B>,
+		pre_hash: &B::Hash,
+		seal: &Seal,
+		difficulty: Difficulty,
+	) -> Result<bool, String>;
+
+	/// Do a single round of mining, returning the seal if one was found.
+	///
+	/// Rounds are numbered from zero for every pre-hash, and should each be
+	/// short enough for the worker to react quickly to a new best block.
+	fn mine(
+		&self,
+		parent: &BlockId<B>,
+		pre_hash: &B::Hash,
+		difficulty: Difficulty,
+		round: u32,
+	) -> Result<Option<Seal>, String>;
+}
+
+/// Reference proof-of-work algorithm, taking the difficulty from the runtime.
+///
+/// The seal is a nonce such that the Blake2 hash of the pre-hash and the
+/// nonce, read as a big-endian number, times the difficulty fits in 256 bits.
+pub struct Blake2Algorithm<C> {
+	client: Arc<C>,
+}
+
+impl<C> Blake2Algorithm<C> {
+	/// Create a new instance, fetching the difficulty through the given client.
+	pub fn new(client: Arc<C>) -> Self {
+		Blake2Algorithm { client }
+	}
+}
+
+impl<C> Clone for Blake2Algorithm<C> {
+	fn clone(&self) -> Self {
+		Blake2Algorithm { client: self.client.clone() }
+	}
+}
+
+impl<B: Block, C> PowAlgorithm<B> for Blake2Algorithm<C> where
+	C: ProvideRuntimeApi,
+	C::Api: DifficultyApi<B>,
+{
+	fn difficulty(&self, parent: &BlockId<B>) -> Result<Difficulty, String> {
+		self.client.runtime_api().difficulty(parent)
+			.map_err(|e| format!("Fetching the difficulty at {:?} failed: {:?}", parent, e))
+	}
+
+	fn verify(
+		&self,
+		_parent: &BlockId<B>,
+		pre_hash: &B::Hash,
+		seal: &Seal,
+		difficulty: Difficulty,
+	) -> Result<bool, String> {
+		Ok(blake2_verify(pre_hash, seal, difficulty))
+	}
+
+	fn mine(
+		&self,
+		_parent: &BlockId<B>,
+		pre_hash: &B::Hash,
+		difficulty: Difficulty,
+		round: u32,
+	) -> Result<Option<Seal>, String> {
+		Ok(blake2_mine(pre_hash, difficulty, round))
+	}
+}
+
+/// Whether the hash, read as a big-endian number, times the difficulty fits
+/// in 256 bits. On average, one hash in `difficulty` meets the difficulty.
+pub fn meets_difficulty(hash: &[u8; 32], difficulty: Difficulty) -> bool {
+	let hash = U256::from_big_endian(hash);
+	let difficulty = U256::from_little_endian(&difficulty.to_le_bytes());
+	!hash.overflowing_mul(difficulty).1
+}
+
+fn blake2_seal_hash<H: Encode>(pre_hash: &H, nonce: u64) -> [u8; 32] {
+	blake2_256(&(pre_hash, nonce).encode())
+}
+
+fn blake2_verify<H: Encode>(pre_hash: &H, seal: &Seal, difficulty: Difficulty) -> bool {
+	let nonce = match u64::decode(&mut &seal[..]) {
+		Some(nonce) => nonce,
+		None => return false,
+	};
+
+	meets_difficulty(&blake2_seal_hash(pre_hash, nonce), difficulty)
+}
+
+fn blake2_mine<H: Encode>(pre_hash: &H, difficulty: Difficulty, round: u32) -> Option<Seal> {
+	let start = u64::from(round) * NONCES_PER_ROUND;
+	(start..start + NONCES_PER_ROUND)
+		.find(|nonce| meets_difficulty(&blake2_seal_hash(pre_hash, *nonce), difficulty))
+		.map(|nonce| nonce.encode())
+}
+
+/// A digest item which is usable with proof-of-work consensus.
+pub trait CompatibleDigestItem: Sized {
+	/// Construct a digest item which contains the seal.
+	fn pow_seal(seal: Seal) -> Self;
+
+	/// If this item is a proof-of-work seal, return it.
+	fn as_pow_seal(&self) -> Option<Seal>;
+}
+
+impl<Hash, AuthorityId, SealSignature> CompatibleDigestItem
+	for generic::DigestItem<Hash, AuthorityId, SealSignature>
+{
+	fn pow_seal(seal: Seal) -> Self {
+		generic::DigestItem::Consensus(POW_ENGINE_ID, seal)
+	}
+
+	fn as_pow_seal(&self) -> Option<Seal> {
+		match self {
+			generic::DigestItem::Consensus(POW_ENGINE_ID, seal) => Some(seal.clone()),
+			_ => None,
+		}
+	}
+}
+
+/// Check that the seal digest item of a block meets the given difficulty.
+fn check_seal<B: Block, A: PowAlgorithm<B>>(
+	algorithm: &A,
+	parent: &BlockId<B>,
+	pre_hash: &B::Hash,
+	seal: &DigestItemFor<B>,
+	difficulty: Difficulty,
+) -> Result<(), String> where
+	DigestItemFor<B>: CompatibleDigestItem,
+{
+	let seal = seal.as_pow_seal()
+		.ok_or_else(|| format!("Header {:?} has no proof-of-work seal", pre_hash))?;
+
+	if algorithm.verify(parent, pre_hash, &seal, difficulty)? {
+		Ok(())
+	} else {
+		Err(format!("Header {:?} has an invalid proof-of-work seal", pre_hash))
+	}
+}
+
+/// A block import which checks the proof-of-work seal and makes the chain
+/// with the most accumulated difficulty the best one.
+///
+/// Blocks have to be imported through it, both by the import queue and by the
+/// mining worker.
+pub struct PowBlockImport<B, I, C, Algorithm> {
+	inner: Arc<I>,
+	client: Arc<C>,
+	algorithm: Algorithm,
+	_phantom: PhantomData<B>,
+}
+
+impl<B, I, C, Algorithm> PowBlockImport<B, I, C, Algorithm> {
+	/// Wrap the given block import, usually the client.
+	pub fn new(inner: Arc<I>, client: Arc<C>, algorithm: Algorithm) -> Self {
+		PowBlockImport {
+			inner,
+			client,
+			algorithm,
+			_phantom: PhantomData,
+		}
+	}
+}
+
+impl<B, I, C, Algorithm> BlockImport<B> for PowBlockImport<B, I, C, Algorithm> where
+	B: Block,
+	I: BlockImport<B, Error=ConsensusError>,
+	C: AuxStore,
+	Algorithm: PowAlgorithm<B>,
+	DigestItemFor<B>: CompatibleDigestItem,
+{
+	type Error = ConsensusError;
+
+	fn check_block(
+		&self,
+		hash: B::Hash,
+		parent_hash: B::Hash,
+	) -> Result<ImportResult, Self::Error> {
+		self.inner.check_block(hash, parent_hash)
+	}
+
+	fn import_block(
+		&self,
+		mut block: ImportBlock<B>,
+		new_cache: HashMap<well_known_cache_keys::Id, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		let parent_hash = *block.header.parent_hash();
+		let pre_hash = block.header.hash();
+
+		let difficulty = {
+			let seal = block.post_digests.last().ok_or_else(|| {
+				ConsensusErrorKind::ClientImport(format!("Header {:?} is unsealed", pre_hash))
+			})?;
+			let parent = BlockId::Hash(parent_hash);
+			let difficulty = self.algorithm.difficulty(&parent)
+				.map_err(ConsensusErrorKind::ClientImport)?;
+			check_seal::<B, _>(&self.algorithm, &parent, &pre_hash, seal, difficulty)
+				.map_err(ConsensusErrorKind::ClientImport)?;
+			difficulty
+		};
+
+		// The genesis block has no weight.
+		let parent_weight = client::load_block_weight(&*self.client, &parent_hash)
+			.map_err(|e| ConsensusErrorKind::ClientImport(e.to_string()))?
+			.unwrap_or(0);
+		block.fork_choice = ForkChoiceStrategy::HeaviestChain(parent_weight.saturating_add(difficulty));
+
+		self.inner.import_block(block, new_cache)
+	}
+}
+
+/// A verifier for proof-of-work blocks.
+pub struct PowVerifier<C, Algorithm> {
+	client: Arc<C>,
+	algorithm: Algorithm,
+	inherent_data_providers: InherentDataProviders,
+	/// The difficulty of the last verified block, used to pre-verify blocks whose parent
+	/// isn't imported yet.
+	last_difficulty: Mutex<Option<Difficulty>>,
+	/// The (encoded) hashes of the pre-verified blocks, along with the difficulty their
+	/// seals meet.
+	pre_verified: PreVerifiedBlocks<Vec<u8>, Difficulty>,
+}
+
+impl<C, Algorithm> PowVerifier<C, Algorithm> {
+	fn new(client: Arc<C>, algorithm: Algorithm, inherent_data_providers: InherentDataProviders) -> Self {
+		PowVerifier {
+			client,
+			algorithm,
+			inherent_data_providers,
+			last_difficulty: Mutex::new(None),
+			pre_verified: Default::default(),
+		}
+	}
+
+	fn check_inherents<B: Block>(
+		&self,
+		block: B,
+		block_id: BlockId<B>,
+	) -> Result<(), String>
+		where C: ProvideRuntimeApi, C::Api: BlockBuilderApi<B>
+	{
+		let inherent_data = self.inherent_data_providers.create_inherent_data()
+			.map_err(String::from)?;
+
+		let inherent_res = self.client.runtime_api().check_inherents(
+			&block_id,
+			block,
+			inherent_data,
+		).map_err(|e| format!("{:?}", e))?;
+
+		if inherent_res.ok() {
+			Ok(())
+		} else {
+			inherent_res
+				.into_errors()
+				.try_for_each(|(i, e)| Err(self.inherent_data_providers.error_to_string(&i, &e)))
+		}
+	}
+}
+
+impl<B: Block, C, Algorithm> Verifier<B> for PowVerifier<C, Algorithm> where
+	C: ProvideRuntimeApi + Send + Sync,
+	C::Api: BlockBuilderApi<B>,
+	Algorithm: PowAlgorithm<B> + Send + Sync,
+	DigestItemFor<B>: CompatibleDigestItem,
+{
+	fn verify(
+		&self,
+		origin: BlockOrigin,
+		mut header: B::Header,
+		justification: Option<Justification>,
+		mut body: Option<Vec<B::Extrinsic>>,
+	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityIdFor<B>>>), String> {
+		let hash = header.hash();
+		let parent_hash = *header.parent_hash();
+
+		let seal = match header.digest_mut().pop() {
+			Some(seal) => seal,
+			None => return Err(format!("Header {:?} is unsealed", hash)),
+		};
+		let pre_hash = header.hash();
+		let parent = BlockId::Hash(parent_hash);
+		let difficulty = self.algorithm.difficulty(&parent)?;
+		*self.last_difficulty.lock() = Some(difficulty);
+		if self.pre_verified.take(&hash.encode()) != Some(difficulty) {
+			check_seal::<B, _>(&self.algorithm, &parent, &pre_hash, &seal, difficulty)?;
+		}
+
+		if let Some(inner_body) = body.take() {
+			let block = B::new(header.clone(), inner_body);
+
+			// skip the inherents verification if the runtime API is old.
+			if self.client
+				.runtime_api()
+				.has_api_with::<BlockBuilderApi<B>, _>(&BlockId::Hash(parent_hash), |v| v >= 2)
+				.map_err(|e| format!("{:?}", e))?
+			{
+				self.check_inherents(block.clone(), BlockId::Hash(parent_hash))?;
+			}
+
+			let (_, inner_body) = block.deconstruct();
+			body = Some(inner_body);
+		}
+
+		debug!(target: "pow", "Checked {:?}; importing.", hash);
+
+		let import_block = ImportBlock {
+			origin,
+			header,
+			post_digests: vec![seal],
+			body,
+			finalized: false,
+			justification,
+			auxiliary: Vec::new(),
+			// replaced with the accumulated difficulty by `PowBlockImport`.
+			fork_choice: ForkChoiceStrategy::LongestChain,
+		};
+
+		Ok((import_block, None))
+	}
+
+	fn can_pre_verify(&self) -> bool {
+		true
+	}
+
+	fn pre_verify(&self, _origin: BlockOrigin, header: &B::Header) {
+		// the parent is likely not imported yet, in which case the seal is checked against
+		// the difficulty of the last verified block; `verify` checks it again if the
+		// difficulty at the parent turns out to be different.
+		let parent = BlockId::Hash(*header.parent_hash());
+		let difficulty = match self.algorithm.difficulty(&parent) {
+			Ok(difficulty) => difficulty,
+			Err(_) => match *self.last_difficulty.lock() {
+				Some(difficulty) => difficulty,
+				None => return,
+			},
+		};
+
+		let mut pre_header = header.clone();
+		let seal = match pre_header.digest_mut().pop() {
+			Some(seal) => seal,
+			None => return,
+		};
+		if check_seal::<B, _>(&self.algorithm, &parent, &pre_header.hash(), &seal, difficulty).is_ok() {
+			self.pre_verified.insert(header.hash().encode(), difficulty);
+		}
+	}
+}
+
+/// Register the timestamp inherent data provider, if not registered already.
+fn register_pow_inherent_data_provider(
+	inherent_data_providers: &InherentDataProviders,
+) -> Result<(), consensus_common::Error> {
+	if !inherent_data_providers.has_provider(&srml_timestamp::INHERENT_IDENTIFIER) {
+		inherent_data_providers
+			.register_provider(srml_timestamp::InherentDataProvider)
+			.map_err(inherent_to_common_error)
+	} else {
+		Ok(())
+	}
+}
+
+fn inherent_to_common_error(err: RuntimeString) -> consensus_common::Error {
+	ConsensusErrorKind::InherentData(err.into()).into()
+}
+
+/// The proof-of-work import queue type.
+pub type PowImportQueue<B> = BasicQueue<B>;
+
+/// Start an import queue for proof-of-work consensus.
+///
+/// The `block_import` is expected to be a `PowBlockImport`, so that the fork
+/// choice follows the accumulated difficulty.
+pub fn import_queue<B, C, Algorithm>(
+	block_import: SharedBlockImport<B>,
+	justification_import: Option<SharedJustificationImport<B>>,
+	client: Arc<C>,
+	algorithm: Algorithm,
+	inherent_data_providers: InherentDataProviders,
+) -> Result<PowImportQueue<B>, consensus_common::Error> where
+	B: Block,
+	C: 'static + ProvideRuntimeApi + Send + Sync,
+	C::Api: BlockBuilderApi<B>,
+	Algorithm: 'static + PowAlgorithm<B> + Send + Sync,
+	DigestItemFor<B>: CompatibleDigestItem,
+{
+	register_pow_inherent_data_provider(&inherent_data_providers)?;
+
+	let verifier = Arc::new(PowVerifier::new(client, algorithm, inherent_data_providers));
+	Ok(BasicQueue::new(verifier, block_import, justification_import))
+}
+
+/// Start the mining worker on a new thread.
+///
+/// The worker builds blocks with the proposers of `env` on top of the best
+/// block of `select_chain`, spending at most `build_time` on each, seals them
+/// with `algorithm` and imports them through `block_import`, which is expected
+/// to be a `PowBlockImport`. A new block is started whenever `import_notifications`
+/// announce a new best block. The returned future watches the notifications and
+/// resolves, stopping the worker, on `on_exit`.
+pub fn start_mine<B, E, S, SO, Algorithm, OnExit>(
+	block_import: SharedBlockImport<B>,
+	select_chain: S,
+	import_notifications: ImportNotifications<B>,
+	algorithm: Algorithm,
+	env: Arc<E>,
+	sync_oracle: SO,
+	inherent_data_providers: InherentDataProviders,
+	build_time: Duration,
+	on_exit: OnExit,
+) -> Result<impl Future<Item=(), Error=()>, consensus_common::Error> where
+	B: Block,
+	S: 'static + SelectChain<B>,
+	Algorithm: 'static + PowAlgorithm<B> + Send,
+	E: 'static + Environment<B> + Send + Sync,
+	E::Error: Debug,
+	SO: 'static + SyncOracle + Send,
+	OnExit: Future<Item=(), Error=()>,
+	DigestItemFor<B>: CompatibleDigestItem,
+{
+	register_pow_inherent_data_provider(&inherent_data_providers)?;
+
+	let stop = Arc::new(AtomicBool::new(false));
+	let best_changed = Arc::new(AtomicBool::new(false));
+	let worker = MiningWorker {
+		block_import,
+		select_chain,
+		best_changed: best_changed.clone(),
+		algorithm,
+		env,
+		sync_oracle,
+		inherent_data_providers,
+		build_time,
+		stop: stop.clone(),
+		_phantom: PhantomData,
+	};
+
+	thread::Builder::new()
+		.name("pow-mining".into())
+		.spawn(move || worker.run())
+		.map_err(|e| ConsensusErrorKind::Other(Box::new(e)))?;
+
+	let watch_best = import_notifications
+		.filter(|notification| notification.is_new_best)
+		.for_each(move |_| {
+			best_changed.store(true, Ordering::SeqCst);
+			Ok(())
+		});
+
+	Ok(watch_best.select(on_exit).then(move |_| {
+		stop.store(true, Ordering::SeqCst);
+		Ok(())
+	}))
+}
+
+struct MiningWorker<B, E, S, SO, Algorithm> {
+	block_import: SharedBlockImport<B>,
+	select_chain: S,
+	/// Set when a new best block is imported, cleared when mining on top of it starts.
+	best_changed: Arc<AtomicBool>,
+	algorithm: Algorithm,
+	env: Arc<E>,
+	sync_oracle: SO,
+	inherent_data_providers: InherentDataProviders,
+	build_time: Duration,
+	stop: Arc<AtomicBool>,
+	_phantom: PhantomData<B>,
+}
+
+impl<B, E, S, SO, Algorithm> MiningWorker<B, E, S, SO, Algorithm> where
+	B: Block,
+	S: SelectChain<B>,
+	Algorithm: PowAlgorithm<B>,
+	E: Environment<B>,
+	E::Error: Debug,
+	SO: SyncOracle,
+	DigestItemFor<B>: CompatibleDigestItem,
+{
+	fn run(mut self) {
+		while !self.stop.load(Ordering::SeqCst) {
+			if self.sync_oracle.is_major_syncing() {
+				debug!(target: "pow", "Skipping mining while major syncing.");
+				thread::sleep(Duration::from_secs(1));
+				continue;
+			}
+
+			if let Err(e) = self.mine_block() {
+				warn!(target: "pow", "Mining block failed with {}. Retrying in a second.", e);
+				thread::sleep(Duration::from_secs(1));
+			}
+		}
+	}
+
+	/// Build a block on top of the best block and try to seal it, until
+	/// another best block is imported.
+	fn mine_block(&mut self) -> Result<(), String> {
+		self.best_changed.store(false, Ordering::SeqCst);
+		let best_header = self.select_chain.best_chain().map_err(|e| format!("{:?}", e))?;
+		let best_hash = best_header.hash();
+		let parent = BlockId::Hash(best_hash);
+
+		let proposer = self.env.init(&best_header, &[])
+			.map_err(|e| format!("Could not create proposer: {:?}", e))?;
+		let inherent_data = self.inherent_data_providers.create_inherent_data()
+			.map_err(String::from)?;
+		let block = proposer.propose(inherent_data, DigestFor::<B>::default(), self.build_time)
+			.into_future()
+			.wait()
+			.map_err(|e| format!("Could not propose a block: {:?}", e))?;
+
+		let (header, body) = block.deconstruct();
+		let pre_hash = header.hash();
+		let difficulty = self.algorithm.difficulty(&parent)?;
+
+		let mut round = 0;
+		let seal = loop {
+			if self.stop.load(Ordering::SeqCst) {
+				return Ok(());
+			}
+
+			if let Some(seal) = self.algorithm.mine(&parent, &pre_hash, difficulty, round)? {
+				break seal;
+			}
+			round += 1;
+
+			if self.best_changed.load(Ordering::SeqCst) {
+				debug!(target: "pow", "New best block imported while mining on {:?}, restarting.", best_hash);
+				return Ok(());
+			}
+		};
+
+		let number = *header.number();
+		let import_block = ImportBlock {
+			origin: BlockOrigin::Own,
+			header,
+			justification: None,
+			post_digests: vec![<DigestItemFor<B> as CompatibleDigestItem>::pow_seal(seal)],
+			body: Some(body),
+			finalized: false,
+			auxiliary: Vec::new(),
+			// replaced with the accumulated difficulty by `PowBlockImport`.
+			fork_choice: ForkChoiceStrategy::LongestChain,
+		};
+
+		self.block_import.import_block(import_block, Default::default())
+			.map_err(|e| format!("Error importing mined block: {:?}", e))?;
+
+		info!(target: "pow", "Mined block #{} ({:?}) at difficulty {}", number, pre_hash, difficulty);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use primitives::H256;
+
+	type DigestItem = generic::DigestItem<H256, u64, u64>;
+
+	#[test]
+	fn difficulty_is_met_by_low_hashes() {
+		assert!(meets_difficulty(&[0xff; 32], 1));
+		assert!(!meets_difficulty(&[0xff; 32], 2));
+
+		let mut half = [0; 32];
+		half[0] = 0x7f;
+		assert!(meets_difficulty(&half, 2));
+		assert!(!meets_difficulty(&half, 3));
+	}
+
+	#[test]
+	fn mined_seals_are_verified() {
+		let pre_hash = H256::random();
+		let difficulty = 1000;
+
+		let seal = (0..)
+			.filter_map(|round| blake2_mine(&pre_hash, difficulty, round))
+			.next()
+			.unwrap();
+		assert!(blake2_verify(&pre_hash, &seal, difficulty));
+
+		// the seal doesn't hold for another header, with overwhelming probability.
+		assert!(!blake2_verify(&H256::random(), &seal, difficulty * 1_000_000));
+		assert!(!blake2_verify(&pre_hash, &Vec::new(), difficulty));
+	}
+
+	#[test]
+	fn seal_digest_items_round_trip() {
+		let seal = vec![1, 2, 3];
+		let item = DigestItem::pow_seal(seal.clone());
+		assert_eq!(item.as_pow_seal(), Some(seal));
+
+		assert_eq!(DigestItem::Consensus(*b"babe", vec![1]).as_pow_seal(), None);
+		assert_eq!(DigestItem::Other(vec![1]).as_pow_seal(), None);
+	}
+}
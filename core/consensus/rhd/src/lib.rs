@@ -37,7 +37,7 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{self, Instant, Duration};
 
-use parity_codec::{Decode, Encode};
+use parity_codec::{Compact, Decode, Encode};
 use consensus::offline_tracker::OfflineTracker;
 use consensus::error::{ErrorKind as CommonErrorKind};
 use consensus::{Authorities, BlockImport, Environment, Proposer as BaseProposer};
@@ -1261,14 +1261,15 @@ impl<C, A> LocalProposer<<C as AuthoringApi>::Block> for Proposer<C, A> where
 				next_index,
 				Call::Consensus(ConsensusCall::report_misbehavior(report)),
 				Era::immortal(),
-				self.client.genesis_hash()
+				self.client.genesis_hash(),
+				Compact(0u128),
 			);
 			let signature = self.local_key.sign(&payload.encode()).into();
 			next_index += 1;
 
 			let local_id = self.local_key.public().0.into();
 			let extrinsic = UncheckedExtrinsic {
-				signature: Some((node_runtime::RawAddress::Id(local_id), signature, payload.0, Era::immortal())),
+				signature: Some((node_runtime::RawAddress::Id(local_id), signature, payload.0, Era::immortal(), payload.4)),
 				function: payload.1,
 			};
 			let uxt: <<C as AuthoringApi>::Block as BlockT>::Extrinsic = Decode::decode(
@@ -23,8 +23,11 @@
 #![forbid(warnings, unsafe_code, missing_docs)]
 
 mod slots;
+mod aux_schema;
 
 pub use slots::{slot_now, SlotInfo, Slots};
+pub use aux_schema::{check_equivocation, MAX_SLOT_CAPACITY};
+pub use srml_consensus::SlotEquivocationProof;
 
 use codec::{Decode, Encode};
 use consensus_common::{SyncOracle, SelectChain};
@@ -14,10 +14,21 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-//! This module implements a freeing-bump allocator.
-//! See more details at https://github.com/paritytech/substrate/issues/1615.
-
+//! This module implements the allocators of the runtime heap.
+//!
+//! The freeing-bump allocator is the original one: it rounds allocations up to power-of-two
+//! buckets and never gives memory back to the bump region. See more details at
+//! https://github.com/paritytech/substrate/issues/1615. It is still used for runtimes that
+//! were built before the reclaiming allocator existed, since the allocator in use decides
+//! whether a runtime call runs out of heap and thus is a part of consensus.
+//!
+//! The reclaiming allocator hands out blocks of the requested size (rounded up to the alignment)
+//! and coalesces freed blocks with their free neighbours, so memory can be reused by allocations
+//! of any size. The bookkeeping is kept on the host side.
+
+use std::collections::{BTreeMap, HashMap};
 use crate::wasm_utils::UserError;
+use runtime_version::RuntimeVersion;
 use log::trace;
 use wasmi::Error;
 use wasmi::MemoryRef;
@@ -37,6 +48,140 @@ const MAX_POSSIBLE_ALLOCATION: u32 = 16777216; // 2^24 bytes
 pub const OUT_OF_SPACE: &str = "Requested allocation size does not fit into remaining heap space";
 pub const REQUESTED_SIZE_TOO_LARGE: &str = "Requested size to allocate is too large";
 
+/// The name of the `Core` runtime API trait. `decl_runtime_apis!` derives the id of an api
+/// from the name of its trait, see `core_api_id`.
+const CORE_API_NAME: &[u8] = b"Core";
+/// The first version of the `Core` runtime API whose runtimes are executed with
+/// the reclaiming allocator.
+const RECLAIMING_ALLOCATOR_CORE_VERSION: u32 = 3;
+
+/// The allocator implementation used for the runtime heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocatorKind {
+	/// The original allocator with power-of-two buckets.
+	FreeingBump,
+	/// The allocator coalescing freed blocks.
+	Reclaiming,
+}
+
+/// Configuration of the runtime heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapConfig {
+	/// The allocator to use.
+	pub kind: AllocatorKind,
+	/// The largest allocation the reclaiming allocator accepts. The freeing-bump allocator
+	/// always uses its own limit of 16 MiB.
+	///
+	/// Whether an allocation fails decides the outcome of a runtime call, so the limit is a
+	/// part of the chain: it is read from the `:max_allocation_size` storage entry.
+	pub max_allocation_size: u32,
+}
+
+/// The id `decl_runtime_apis!` generates for the `Core` runtime API: the Blake2 64-bit hash
+/// of the trait name.
+pub fn core_api_id() -> [u8; 8] {
+	primitives::blake2_64(CORE_API_NAME)
+}
+
+impl AllocatorKind {
+	/// The allocator for executing the runtime with the given version.
+	///
+	/// Runtimes built against an older `Core` runtime API (or without a version at all)
+	/// keep the freeing-bump allocator, so that re-executing their blocks gives the same results.
+	pub fn for_runtime(version: Option<&RuntimeVersion>) -> Self {
+		let core_api_id = core_api_id();
+		let reclaiming = version.map_or(false, |version| version.apis.iter().any(|(id, version)|
+			id == &core_api_id && *version >= RECLAIMING_ALLOCATOR_CORE_VERSION
+		));
+		if reclaiming {
+			AllocatorKind::Reclaiming
+		} else {
+			AllocatorKind::FreeingBump
+		}
+	}
+}
+
+impl Default for HeapConfig {
+	fn default() -> Self {
+		HeapConfig {
+			kind: AllocatorKind::FreeingBump,
+			max_allocation_size: MAX_POSSIBLE_ALLOCATION,
+		}
+	}
+}
+
+/// Statistics of the heap usage during a single runtime call.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllocationStats {
+	/// Number of successful allocations.
+	pub allocations: u64,
+	/// Number of bytes currently allocated, including the overhead of the allocator.
+	pub bytes_allocated: u32,
+	/// The largest value of `bytes_allocated` seen.
+	pub bytes_allocated_peak: u32,
+	/// Number of bytes of the heap that have been used by the allocator at some point.
+	pub address_space_used: u32,
+	/// Size of the heap in bytes.
+	pub heap_size: u32,
+}
+
+/// The heap of a runtime call, backed by one of the allocators.
+pub enum Heap {
+	FreeingBump(FreeingBumpHeapAllocator),
+	Reclaiming(ReclaimingHeapAllocator),
+}
+
+impl Heap {
+	/// Creates a new heap in the unused part of `mem`.
+	pub fn new(mem: MemoryRef, config: HeapConfig) -> Self {
+		match config.kind {
+			AllocatorKind::FreeingBump => Heap::FreeingBump(FreeingBumpHeapAllocator::new(mem)),
+			AllocatorKind::Reclaiming =>
+				Heap::Reclaiming(ReclaimingHeapAllocator::new(mem, config.max_allocation_size)),
+		}
+	}
+
+	/// Gets requested number of bytes to allocate and returns a pointer.
+	pub fn allocate(&mut self, size: u32) -> Result<u32, UserError> {
+		match self {
+			Heap::FreeingBump(heap) => heap.allocate(size),
+			Heap::Reclaiming(heap) => heap.allocate(size),
+		}
+	}
+
+	/// Deallocates the space which was allocated for a pointer.
+	pub fn deallocate(&mut self, ptr: u32) -> Result<(), UserError> {
+		match self {
+			Heap::FreeingBump(heap) => heap.deallocate(ptr),
+			Heap::Reclaiming(heap) => heap.deallocate(ptr),
+		}
+	}
+
+	/// Returns the statistics of the heap usage so far.
+	pub fn stats(&self) -> AllocationStats {
+		match self {
+			Heap::FreeingBump(heap) => heap.stats(),
+			Heap::Reclaiming(heap) => heap.stats.clone(),
+		}
+	}
+}
+
+/// Returns the offset of the first aligned byte after the used part of `mem`
+/// and the number of bytes available from there on.
+fn heap_bounds(mem: &MemoryRef) -> (u32, u32) {
+	let current_size: Bytes = mem.current_size().into();
+	let current_size = current_size.0 as u32;
+	let used_size = mem.used_size().0 as u32;
+
+	let mut ptr_offset = used_size;
+	let padding = ptr_offset % ALIGNMENT;
+	if padding != 0 {
+		ptr_offset += ALIGNMENT - padding;
+	}
+
+	(ptr_offset, current_size - used_size)
+}
+
 pub struct FreeingBumpHeapAllocator {
 	bumper: u32,
 	heads: [u32; N],
@@ -44,6 +189,8 @@ pub struct FreeingBumpHeapAllocator {
 	max_heap_size: u32,
 	ptr_offset: u32,
 	total_size: u32,
+	allocations: u64,
+	peak_size: u32,
 }
 
 impl FreeingBumpHeapAllocator {
@@ -64,16 +211,7 @@ impl FreeingBumpHeapAllocator {
 	///   used as the heap.
 	///
 	pub fn new(mem: MemoryRef) -> Self {
-		let current_size: Bytes = mem.current_size().into();
-		let current_size = current_size.0 as u32;
-		let used_size = mem.used_size().0 as u32;
-		let heap_size = current_size - used_size;
-
-		let mut ptr_offset = used_size;
-		let padding = ptr_offset % ALIGNMENT;
-		if padding != 0 {
-			ptr_offset += ALIGNMENT - padding;
-		}
+		let (ptr_offset, heap_size) = heap_bounds(&mem);
 
 		FreeingBumpHeapAllocator {
 			bumper: 0,
@@ -82,6 +220,8 @@ impl FreeingBumpHeapAllocator {
 			max_heap_size: heap_size,
 			ptr_offset: ptr_offset,
 			total_size: 0,
+			allocations: 0,
+			peak_size: 0,
 		}
 	}
 
@@ -120,6 +260,8 @@ impl FreeingBumpHeapAllocator {
 			.map_err(|_| UserError("Unable to write byte into heap at pointer prefix"))?;
 
 		self.total_size = self.total_size + item_size + 8;
+		self.allocations += 1;
+		self.peak_size = self.peak_size.max(self.total_size);
 		trace!(target: "wasm-heap", "Heap size is {} bytes after allocation", self.total_size);
 
 		Ok(self.ptr_offset + ptr)
@@ -156,6 +298,17 @@ impl FreeingBumpHeapAllocator {
 		Ok(())
 	}
 
+	/// Returns the statistics of the heap usage so far.
+	pub fn stats(&self) -> AllocationStats {
+		AllocationStats {
+			allocations: self.allocations,
+			bytes_allocated: self.total_size,
+			bytes_allocated_peak: self.peak_size,
+			address_space_used: self.bumper,
+			heap_size: self.max_heap_size,
+		}
+	}
+
 	fn bump(&mut self, n: u32) -> u32 {
 		let res = self.bumper;
 		self.bumper += n;
@@ -199,6 +352,117 @@ impl FreeingBumpHeapAllocator {
 
 }
 
+/// An allocator which reuses freed memory for allocations of any size.
+///
+/// Offsets are relative to `ptr_offset`. The first `ALIGNMENT` bytes of the heap are never
+/// handed out, so that no allocation starts at a null pointer. Free regions are kept ordered
+/// by their offset and are merged with their neighbours on deallocation. A free region ending
+/// at the bumper is given back to the bump region.
+pub struct ReclaimingHeapAllocator {
+	ptr_offset: u32,
+	max_allocation_size: u32,
+	bumper: u32,
+	free_regions: BTreeMap<u32, u32>,
+	allocated: HashMap<u32, u32>,
+	stats: AllocationStats,
+}
+
+impl ReclaimingHeapAllocator {
+
+	/// Creates a new allocation heap in the unused part of `mem`, which accepts allocations
+	/// up to `max_allocation_size` bytes.
+	pub fn new(mem: MemoryRef, max_allocation_size: u32) -> Self {
+		let (ptr_offset, heap_size) = heap_bounds(&mem);
+
+		ReclaimingHeapAllocator {
+			ptr_offset,
+			max_allocation_size,
+			bumper: ALIGNMENT,
+			free_regions: BTreeMap::new(),
+			allocated: HashMap::new(),
+			stats: AllocationStats {
+				heap_size,
+				..Default::default()
+			},
+		}
+	}
+
+	/// Gets requested number of bytes to allocate and returns a pointer.
+	pub fn allocate(&mut self, size: u32) -> Result<u32, UserError> {
+		if size > self.max_allocation_size {
+			return Err(UserError(REQUESTED_SIZE_TOO_LARGE));
+		}
+
+		let size = match size.max(ALIGNMENT).checked_add(ALIGNMENT - 1) {
+			Some(size) => size & !(ALIGNMENT - 1),
+			None => return Err(UserError(REQUESTED_SIZE_TOO_LARGE)),
+		};
+
+		// First fit among the free regions.
+		let region = self.free_regions.iter()
+			.find(|(_, region_size)| **region_size >= size)
+			.map(|(offset, region_size)| (*offset, *region_size));
+
+		let offset = match region {
+			Some((offset, region_size)) => {
+				self.free_regions.remove(&offset);
+				if region_size > size {
+					self.free_regions.insert(offset + size, region_size - size);
+				}
+				offset
+			},
+			None => {
+				if self.bumper as u64 + size as u64 > self.stats.heap_size as u64 {
+					return Err(UserError(OUT_OF_SPACE));
+				}
+				let offset = self.bumper;
+				self.bumper += size;
+				offset
+			},
+		};
+
+		self.allocated.insert(offset, size);
+		self.stats.allocations += 1;
+		self.stats.bytes_allocated += size;
+		self.stats.bytes_allocated_peak = self.stats.bytes_allocated_peak.max(self.stats.bytes_allocated);
+		self.stats.address_space_used = self.stats.address_space_used.max(self.bumper);
+		trace!(target: "wasm-heap", "Heap size is {} bytes after allocation", self.stats.bytes_allocated);
+
+		Ok(self.ptr_offset + offset)
+	}
+
+	/// Deallocates the space which was allocated for a pointer.
+	pub fn deallocate(&mut self, ptr: u32) -> Result<(), UserError> {
+		let mut offset = ptr.checked_sub(self.ptr_offset)
+			.ok_or_else(|| UserError("Invalid pointer for deallocation"))?;
+		let mut size = self.allocated.remove(&offset)
+			.ok_or_else(|| UserError("Invalid pointer for deallocation"))?;
+		self.stats.bytes_allocated -= size;
+
+		let previous = self.free_regions.range(..offset).next_back()
+			.map(|(previous, previous_size)| (*previous, *previous_size));
+		if let Some((previous, previous_size)) = previous {
+			if previous + previous_size == offset {
+				self.free_regions.remove(&previous);
+				offset = previous;
+				size += previous_size;
+			}
+		}
+		if let Some(next_size) = self.free_regions.remove(&(offset + size)) {
+			size += next_size;
+		}
+
+		if offset + size == self.bumper {
+			self.bumper = offset;
+		} else {
+			self.free_regions.insert(offset, size);
+		}
+		trace!(target: "wasm-heap", "Heap size is {} bytes after deallocation", self.stats.bytes_allocated);
+
+		Ok(())
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -503,4 +767,173 @@ mod tests {
 		assert_eq!(item_size as u32, MAX_POSSIBLE_ALLOCATION);
 	}
 
+	fn runtime_version(apis: Vec<([u8; 8], u32)>) -> RuntimeVersion {
+		RuntimeVersion {
+			spec_name: "test".into(),
+			impl_name: "test".into(),
+			authoring_version: 1,
+			spec_version: 1,
+			impl_version: 1,
+			apis: apis.into(),
+		}
+	}
+
+	#[test]
+	fn should_select_allocator_by_core_api_version() {
+		assert_eq!(AllocatorKind::for_runtime(None), AllocatorKind::FreeingBump);
+		assert_eq!(
+			AllocatorKind::for_runtime(Some(&runtime_version(vec![]))),
+			AllocatorKind::FreeingBump,
+		);
+		assert_eq!(
+			AllocatorKind::for_runtime(Some(&runtime_version(vec![(core_api_id(), 2), ([1; 8], 3)]))),
+			AllocatorKind::FreeingBump,
+		);
+		assert_eq!(
+			AllocatorKind::for_runtime(Some(&runtime_version(vec![(core_api_id(), 3)]))),
+			AllocatorKind::Reclaiming,
+		);
+	}
+
+	fn reclaiming_heap(mem: MemoryRef) -> ReclaimingHeapAllocator {
+		ReclaimingHeapAllocator::new(mem, MAX_POSSIBLE_ALLOCATION)
+	}
+
+	#[test]
+	fn reclaiming_should_allocate_aligned_pointers() {
+		// given
+		let mem = MemoryInstance::alloc(Pages(1), None).unwrap();
+		set_offset(mem.clone(), 13);
+		let mut heap = reclaiming_heap(mem);
+
+		// when
+		let ptr1 = heap.allocate(1).unwrap();
+		let ptr2 = heap.allocate(9).unwrap();
+
+		// then
+		// the padded offset of 16 + the reserved first 8 bytes
+		assert_eq!(ptr1, 24);
+		// sizes are rounded up to the alignment only
+		assert_eq!(ptr2, 24 + 8);
+		assert_eq!(heap.stats.bytes_allocated, 8 + 16);
+	}
+
+	#[test]
+	fn reclaiming_should_coalesce_freed_neighbours() {
+		// given
+		let mem = MemoryInstance::alloc(Pages(1), None).unwrap();
+		let mut heap = reclaiming_heap(mem);
+		let ptr1 = heap.allocate(16).unwrap();
+		let ptr2 = heap.allocate(16).unwrap();
+		let ptr3 = heap.allocate(16).unwrap();
+		let _guard = heap.allocate(16).unwrap();
+
+		// when
+		heap.deallocate(ptr1).unwrap();
+		heap.deallocate(ptr3).unwrap();
+		heap.deallocate(ptr2).unwrap();
+
+		// then
+		let mut expected = BTreeMap::new();
+		expected.insert(ptr1, 48);
+		assert_eq!(heap.free_regions, expected);
+
+		// a larger block fits into the merged region
+		assert_eq!(heap.allocate(40).unwrap(), ptr1);
+		assert_eq!(heap.free_regions.get(&(ptr1 + 40)), Some(&8));
+	}
+
+	#[test]
+	fn reclaiming_should_give_memory_back_to_bump_region() {
+		// given
+		let mem = MemoryInstance::alloc(Pages(1), None).unwrap();
+		let mut heap = reclaiming_heap(mem);
+
+		// when
+		for size in 1..100 {
+			let ptr1 = heap.allocate(size * 8).unwrap();
+			let ptr2 = heap.allocate(size * 16).unwrap();
+			heap.deallocate(ptr1).unwrap();
+			heap.deallocate(ptr2).unwrap();
+		}
+
+		// then
+		assert_eq!(heap.bumper, ALIGNMENT);
+		assert!(heap.free_regions.is_empty());
+		assert_eq!(heap.stats.bytes_allocated, 0);
+		assert_eq!(heap.stats.allocations, 198);
+	}
+
+	#[test]
+	fn reclaiming_should_reuse_memory_of_other_sizes() {
+		// given
+		let mem = MemoryInstance::alloc(Pages(1), Some(Pages(1))).unwrap();
+		let mut heap = reclaiming_heap(mem.clone());
+		let mut bump_heap = FreeingBumpHeapAllocator::new(mem);
+
+		// when
+		// blocks of different sizes are allocated and freed again
+		for i in 4..15 {
+			let ptr = heap.allocate(1 << i).unwrap();
+			heap.deallocate(ptr).unwrap();
+			let ptr = bump_heap.allocate(1 << i).unwrap();
+			bump_heap.deallocate(ptr).unwrap();
+		}
+
+		heap.allocate(PAGE_SIZE / 2).unwrap();
+		bump_heap.allocate(PAGE_SIZE / 2).unwrap();
+
+		// then
+		// the freeing-bump allocator can only reuse the freed blocks for the same sizes,
+		// so the last block doesn't fit into the memory anymore
+		assert!(heap.stats.address_space_used <= PAGE_SIZE);
+		assert!(bump_heap.stats().address_space_used > PAGE_SIZE);
+	}
+
+	#[test]
+	fn reclaiming_should_respect_max_allocation_size() {
+		// given
+		let mem = MemoryInstance::alloc(Pages(1), None).unwrap();
+		let mut heap = ReclaimingHeapAllocator::new(mem, 1024);
+
+		// when
+		let ptr = heap.allocate(1025);
+
+		// then
+		assert_eq!(ptr, Err(UserError(REQUESTED_SIZE_TOO_LARGE)));
+		assert!(heap.allocate(1024).is_ok());
+	}
+
+	#[test]
+	fn reclaiming_should_not_allocate_if_full() {
+		// given
+		let mem = MemoryInstance::alloc(Pages(1), Some(Pages(1))).unwrap();
+		let mut heap = reclaiming_heap(mem);
+		heap.allocate(PAGE_SIZE / 2).unwrap();
+
+		// when
+		let ptr = heap.allocate(PAGE_SIZE / 2);
+
+		// then
+		// the first 8 bytes of the heap are never used
+		assert_eq!(ptr, Err(UserError(OUT_OF_SPACE)));
+		assert!(heap.allocate(PAGE_SIZE / 2 - 8).is_ok());
+	}
+
+	#[test]
+	fn reclaiming_should_reject_unknown_pointers() {
+		// given
+		let mem = MemoryInstance::alloc(Pages(1), None).unwrap();
+		let mut heap = reclaiming_heap(mem);
+		let ptr = heap.allocate(8).unwrap();
+
+		// when
+		heap.deallocate(ptr).unwrap();
+
+		// then
+		assert!(heap.deallocate(ptr).is_err());
+		assert!(heap.deallocate(ptr + 1).is_err());
+		assert!(heap.deallocate(0).is_err());
+	}
+
 }
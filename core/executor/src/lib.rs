@@ -35,10 +35,12 @@ mod wasm_executor;
 mod native_executor;
 mod sandbox;
 mod allocator;
+mod wasm_runtime;
 
 pub mod error;
 pub use wasmi;
 pub use wasm_executor::WasmExecutor;
+pub use allocator::core_api_id;
 pub use native_executor::{with_native_environment, NativeExecutor, NativeExecutionDispatch};
 pub use state_machine::Externalities;
 pub use runtime_version::{RuntimeVersion, NativeVersion};
@@ -56,4 +58,8 @@ pub trait RuntimeInfo {
 		&self,
 		ext: &mut E,
 	) -> Option<RuntimeVersion>;
+
+	/// Never execute the on-chain runtime with the given spec version natively, even if the
+	/// native runtime can call it.
+	fn disable_native_for(&self, spec_version: u32);
 }
@@ -17,10 +17,11 @@
 use std::{borrow::BorrowMut, result, cell::{RefMut, RefCell}};
 use crate::error::{Error, ErrorKind, Result};
 use state_machine::{CodeExecutor, Externalities};
-use crate::wasm_executor::WasmExecutor;
-use wasmi::{Module as WasmModule, ModuleRef as WasmModuleInstanceRef};
+use crate::wasm_runtime::{WasmRuntime, create_wasm_runtime};
+use crate::allocator::{AllocatorKind, HeapConfig};
 use runtime_version::{NativeVersion, RuntimeVersion};
-use std::{collections::HashMap, panic::UnwindSafe};
+use std::{collections::{HashMap, HashSet}, panic::UnwindSafe, sync::Arc};
+use parking_lot::RwLock;
 use parity_codec::{Decode, Encode};
 use crate::RuntimeInfo;
 use primitives::{Blake2Hasher, NativeOrEncoded};
@@ -35,10 +36,18 @@ const DEFAULT_HEAP_PAGES: u64 = 1024;
 
 enum RuntimePreproc {
 	InvalidCode,
-	ValidCode(WasmModuleInstanceRef, Option<RuntimeVersion>),
+	ValidCode(Box<dyn WasmRuntime>, Option<RuntimeVersion>),
 }
 
-type CacheType = HashMap<[u8; 32], RuntimePreproc>;
+/// Everything the prepared runtime depends on.
+#[derive(PartialEq, Eq, Hash)]
+struct RuntimeCacheKey {
+	fast_instance_reuse: bool,
+	max_allocation_size: u32,
+	code_hash: [u8; 32],
+}
+
+type CacheType = HashMap<RuntimeCacheKey, RuntimePreproc>;
 
 thread_local! {
 	static RUNTIMES_CACHE: RefCell<CacheType> = RefCell::new(HashMap::new());
@@ -48,17 +57,27 @@ thread_local! {
 /// the runtime version entry for `code`, determines whether `Compatibility::IsCompatible`
 /// can be used by comparing returned RuntimeVersion to `ref_version`
 fn fetch_cached_runtime_version<'a, E: Externalities<Blake2Hasher>>(
-	wasm_executor: &WasmExecutor,
+	fast_instance_reuse: bool,
 	cache: &'a mut RefMut<CacheType>,
 	ext: &mut E,
 	default_heap_pages: Option<u64>,
-) -> Result<(&'a WasmModuleInstanceRef, &'a Option<RuntimeVersion>)> {
+) -> Result<(&'a mut dyn WasmRuntime, &'a Option<RuntimeVersion>)> {
 	let code_hash = match ext.original_storage_hash(well_known_keys::CODE) {
 		Some(code_hash) => code_hash,
 		None => return Err(ErrorKind::InvalidCode(vec![]).into()),
 	};
+	// the limit changes the outcome of calls, so it is taken from the chain rather than
+	// from the node configuration.
+	let max_allocation_size = ext.storage(well_known_keys::MAX_ALLOCATION_SIZE)
+		.and_then(|size| u32::decode(&mut &size[..]))
+		.unwrap_or(HeapConfig::default().max_allocation_size);
 
-	let maybe_runtime_preproc = cache.borrow_mut().entry(code_hash.into())
+	let key = RuntimeCacheKey {
+		fast_instance_reuse,
+		max_allocation_size,
+		code_hash: code_hash.into(),
+	};
+	let maybe_runtime_preproc = cache.borrow_mut().entry(key)
 		.or_insert_with(|| {
 			let code = match ext.original_storage(well_known_keys::CODE) {
 				Some(code) => code,
@@ -68,15 +87,17 @@ fn fetch_cached_runtime_version<'a, E: Externalities<Blake2Hasher>>(
 				.and_then(|pages| u64::decode(&mut &pages[..]))
 				.or(default_heap_pages)
 				.unwrap_or(DEFAULT_HEAP_PAGES);
-			match WasmModule::from_buffer(code)
-				.map_err(|_| ErrorKind::InvalidCode(vec![]).into())
-				.and_then(|module| wasm_executor.prepare_module(ext, heap_pages as usize, &module))
-			{
-				Ok(module) => {
-					let version = wasm_executor.call_in_wasm_module(ext, &module, "Core_version", &[])
+			let heap_config = HeapConfig {
+				max_allocation_size,
+				..Default::default()
+			};
+			match create_wasm_runtime(ext, &code, heap_pages, fast_instance_reuse, heap_config) {
+				Ok(mut runtime) => {
+					let version = runtime.call(ext, "Core_version", &[])
 						.ok()
 						.and_then(|v| RuntimeVersion::decode(&mut v.as_slice()));
-					RuntimePreproc::ValidCode(module, version)
+					runtime.set_allocator(AllocatorKind::for_runtime(version.as_ref()));
+					RuntimePreproc::ValidCode(runtime, version)
 				}
 				Err(e) => {
 					trace!(target: "executor", "Invalid code presented to executor ({:?})", e);
@@ -90,8 +111,8 @@ fn fetch_cached_runtime_version<'a, E: Externalities<Blake2Hasher>>(
 			let code = ext.original_storage(well_known_keys::CODE).unwrap_or(vec![]);
 			Err(ErrorKind::InvalidCode(code).into())
 		},
-		RuntimePreproc::ValidCode(m, v) => {
-			Ok((m, v))
+		RuntimePreproc::ValidCode(r, v) => {
+			Ok((&mut **r, v))
 		}
 	}
 }
@@ -136,12 +157,15 @@ pub trait NativeExecutionDispatch: Send + Sync {
 pub struct NativeExecutor<D: NativeExecutionDispatch> {
 	/// Dummy field to avoid the compiler complaining about us not using `D`.
 	_dummy: ::std::marker::PhantomData<D>,
-	/// The fallback executor in case native isn't available.
-	fallback: WasmExecutor,
+	/// Whether the memory of the Wasm instance is restored from a snapshot between calls.
+	fast_instance_reuse: bool,
 	/// Native runtime version info.
 	native_version: NativeVersion,
 	/// The default number of 64KB pages to allocate for Wasm execution.
 	default_heap_pages: Option<u64>,
+	/// Spec versions of the on-chain runtime that are always executed in Wasm. Shared between
+	/// the clones of the executor.
+	native_disabled_for: Arc<RwLock<HashSet<u32>>>,
 }
 
 impl<D: NativeExecutionDispatch> NativeExecutor<D> {
@@ -149,20 +173,34 @@ impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 	pub fn new(default_heap_pages: Option<u64>) -> Self {
 		NativeExecutor {
 			_dummy: Default::default(),
-			fallback: WasmExecutor::new(),
+			fast_instance_reuse: false,
 			native_version: D::native_version(),
 			default_heap_pages,
+			native_disabled_for: Default::default(),
 		}
 	}
+
+	/// Reuse the Wasm instance between calls, restoring only the changed parts of its memory
+	/// instead of preparing the whole instance again.
+	pub fn with_fast_instance_reuse(mut self, fast_instance_reuse: bool) -> Self {
+		self.fast_instance_reuse = fast_instance_reuse;
+		self
+	}
+
+	fn can_call_natively(&self, onchain_version: &RuntimeVersion) -> bool {
+		onchain_version.can_call_with(&self.native_version.runtime_version)
+			&& !self.native_disabled_for.read().contains(&onchain_version.spec_version)
+	}
 }
 
 impl<D: NativeExecutionDispatch> Clone for NativeExecutor<D> {
 	fn clone(&self) -> Self {
 		NativeExecutor {
 			_dummy: Default::default(),
-			fallback: self.fallback.clone(),
+			fast_instance_reuse: self.fast_instance_reuse,
 			native_version: D::native_version(),
 			default_heap_pages: self.default_heap_pages,
+			native_disabled_for: self.native_disabled_for.clone(),
 		}
 	}
 }
@@ -177,10 +215,20 @@ impl<D: NativeExecutionDispatch> RuntimeInfo for NativeExecutor<D> {
 		ext: &mut E,
 	) -> Option<RuntimeVersion> {
 		RUNTIMES_CACHE.with(|c|
-			fetch_cached_runtime_version(&self.fallback, &mut c.borrow_mut(), ext, self.default_heap_pages)
+			fetch_cached_runtime_version(
+				self.fast_instance_reuse,
+				&mut c.borrow_mut(),
+				ext,
+				self.default_heap_pages,
+			)
 				.ok()?.1.clone()
 		)
 	}
+
+	/// Applies to all the clones of the executor.
+	fn disable_native_for(&self, spec_version: u32) {
+		self.native_disabled_for.write().insert(spec_version);
+	}
 }
 
 impl<D: NativeExecutionDispatch> CodeExecutor<Blake2Hasher> for NativeExecutor<D> {
@@ -201,16 +249,20 @@ impl<D: NativeExecutionDispatch> CodeExecutor<Blake2Hasher> for NativeExecutor<D
 	) -> (Result<NativeOrEncoded<R>>, bool) {
 		RUNTIMES_CACHE.with(|c| {
 			let mut c = c.borrow_mut();
-			let (module, onchain_version) = match fetch_cached_runtime_version(
-				&self.fallback, &mut c, ext, self.default_heap_pages) {
-					Ok((module, onchain_version)) => (module, onchain_version),
+			let (runtime, onchain_version) = match fetch_cached_runtime_version(
+				self.fast_instance_reuse,
+				&mut c,
+				ext,
+				self.default_heap_pages,
+			) {
+					Ok((runtime, onchain_version)) => (runtime, onchain_version),
 					Err(e) => return (Err(e), false),
 			};
 			match (
 				use_native,
 				onchain_version
 					.as_ref()
-					.map_or(false, |v| v.can_call_with(&self.native_version.runtime_version)),
+					.map_or(false, |v| self.can_call_natively(v)),
 				native_call,
 			) {
 				(_, false, _) => {
@@ -223,16 +275,16 @@ impl<D: NativeExecutionDispatch> CodeExecutor<Blake2Hasher> for NativeExecutor<D
 							.map_or_else(||"<None>".into(), |v| format!("{}", v))
 					);
 					(
-						self.fallback
-							.call_in_wasm_module(ext, module, method, data)
+						runtime
+							.call(ext, method, data)
 							.map(NativeOrEncoded::Encoded),
 						false
 					)
 				}
 				(false, _, _) => {
 					(
-						self.fallback
-							.call_in_wasm_module(ext, module, method, data)
+						runtime
+							.call(ext, method, data)
 							.map(NativeOrEncoded::Encoded),
 						false
 					)
@@ -21,12 +21,14 @@
 use std::collections::HashMap;
 use std::rc::Rc;
 use parity_codec::{Decode, Encode};
-use primitives::sandbox as sandbox_primitives;
+use primitives::sandbox::{
+	self as sandbox_primitives, FuelMeter, InstanceLimits, ModuleRejection, TrapReason, FUEL_FUNC_INDEX,
+};
 use crate::wasm_utils::UserError;
 use wasmi;
 use wasmi::memory_units::Pages;
 use wasmi::{
-	Externals, FuncRef, ImportResolver, MemoryInstance, MemoryRef, Module, ModuleInstance,
+	Externals, FuncRef, ImportResolver, MemoryInstance, MemoryRef, ModuleInstance,
 	ModuleRef, RuntimeArgs, RuntimeValue, Trap, TrapKind
 };
 
@@ -76,6 +78,10 @@ impl ImportResolver for Imports {
 		field_name: &str,
 		signature: &::wasmi::Signature,
 	) -> Result<FuncRef, ::wasmi::Error> {
+		if let Some(fuel_func) = sandbox_primitives::fuel_func(module_name, field_name, signature) {
+			return Ok(fuel_func);
+		}
+
 		let key = (
 			module_name.as_bytes().to_owned(),
 			field_name.as_bytes().to_owned(),
@@ -187,6 +193,7 @@ pub struct GuestExternals<'a, FE: SandboxCapabilities + Externals + 'a> {
 	supervisor_externals: &'a mut FE,
 	sandbox_instance: &'a SandboxInstance,
 	state: u32,
+	fuel: FuelMeter,
 }
 
 fn trap(msg: &'static str) -> Trap {
@@ -213,6 +220,10 @@ impl<'a, FE: SandboxCapabilities + Externals + 'a> Externals for GuestExternals<
 		index: usize,
 		args: RuntimeArgs,
 	) -> Result<Option<RuntimeValue>, Trap> {
+		if index == FUEL_FUNC_INDEX {
+			return self.fuel.charge(args);
+		}
+
 		// Make `index` typesafe again.
 		let index = GuestFuncIndex(index);
 
@@ -295,6 +306,7 @@ where
 		supervisor_externals,
 		sandbox_instance,
 		state,
+		fuel: FuelMeter::new(&sandbox_instance.limits),
 	};
 	f(&mut guest_externals)
 }
@@ -315,6 +327,7 @@ pub struct SandboxInstance {
 	instance: ModuleRef,
 	dispatch_thunk: FuncRef,
 	guest_to_supervisor_mapping: GuestToSupervisorFunctionMapping,
+	limits: InstanceLimits,
 }
 
 impl SandboxInstance {
@@ -325,13 +338,15 @@ impl SandboxInstance {
 	///
 	/// The `state` parameter can be used to provide custom data for
 	/// these syscall implementations.
+	///
+	/// Every invocation gets the fuel given by the limits of the instance.
 	pub fn invoke<FE: SandboxCapabilities + Externals>(
 		&self,
 		export_name: &str,
 		args: &[RuntimeValue],
 		supervisor_externals: &mut FE,
 		state: u32,
-	) -> Result<Option<wasmi::RuntimeValue>, wasmi::Error> {
+	) -> Result<Option<wasmi::RuntimeValue>, TrapReason> {
 		with_guest_externals(
 			supervisor_externals,
 			self,
@@ -340,7 +355,7 @@ impl SandboxInstance {
 				self.instance
 					.invoke_export(export_name, args, guest_externals)
 			},
-		)
+		).map_err(|error| sandbox_primitives::trap_reason(&self.instance, &self.limits, &error))
 	}
 }
 
@@ -356,7 +371,11 @@ pub enum InstantiationError {
 	Instantiation,
 	/// Module is well-formed, instantiated and linked, but while executing the start function
 	/// a trap was generated.
-	StartTrapped,
+	StartTrapped(TrapReason),
+	/// Module has an import denied by the limits of the instance.
+	ForbiddenImport,
+	/// Module uses a WebAssembly feature denied by the limits of the instance.
+	ForbiddenFeature,
 }
 
 fn decode_environment_definition(
@@ -404,7 +423,8 @@ fn decode_environment_definition(
 ///
 /// The guest module's code is specified in `wasm`. Environment that will be available to
 /// guest module is specified in `raw_env_def` (serialized version of [`EnvironmentDefinition`]).
-/// `dispatch_thunk` is used as function that handle calls from guests.
+/// `dispatch_thunk` is used as function that handle calls from guests. The module is checked
+/// against `limits`, which are then enforced on every invocation of the instance.
 ///
 /// # Errors
 ///
@@ -412,6 +432,8 @@ fn decode_environment_definition(
 ///
 /// - `raw_env_def` can't be deserialized as a [`EnvironmentDefinition`].
 /// - Module in `wasm` is invalid or couldn't be instantiated.
+/// - Module in `wasm` has an import or uses a feature denied by the `limits`.
+/// - The start function of the module trapped.
 ///
 /// [`EnvironmentDefinition`]: ../../sandbox/struct.EnvironmentDefinition.html
 pub fn instantiate<FE: SandboxCapabilities + Externals>(
@@ -419,12 +441,17 @@ pub fn instantiate<FE: SandboxCapabilities + Externals>(
 	dispatch_thunk: FuncRef,
 	wasm: &[u8],
 	raw_env_def: &[u8],
+	limits: InstanceLimits,
 	state: u32,
 ) -> Result<u32, InstantiationError> {
 	let (imports, guest_to_supervisor_mapping) =
 		decode_environment_definition(raw_env_def, &supervisor_externals.store().memories)?;
 
-	let module = Module::from_buffer(wasm).map_err(|_| InstantiationError::ModuleDecoding)?;
+	let module = sandbox_primitives::prepare_module(wasm, &limits).map_err(|rejection| match rejection {
+		ModuleRejection::ForbiddenImport(_) => InstantiationError::ForbiddenImport,
+		ModuleRejection::ForbiddenFeature(_) => InstantiationError::ForbiddenFeature,
+		ModuleRejection::Invalid | ModuleRejection::Instrumentation => InstantiationError::ModuleDecoding,
+	})?;
 	let instance = ModuleInstance::new(&module, &imports).map_err(|_| InstantiationError::Instantiation)?;
 
 	let sandbox_instance = Rc::new(SandboxInstance {
@@ -434,6 +461,7 @@ pub fn instantiate<FE: SandboxCapabilities + Externals>(
 		instance: instance.not_started_instance().clone(),
 		dispatch_thunk,
 		guest_to_supervisor_mapping,
+		limits,
 	});
 
 	with_guest_externals(
@@ -443,7 +471,11 @@ pub fn instantiate<FE: SandboxCapabilities + Externals>(
 		|guest_externals| {
 			instance
 				.run_start(guest_externals)
-				.map_err(|_| InstantiationError::StartTrapped)
+				.map_err(|trap| InstantiationError::StartTrapped(sandbox_primitives::trap_reason(
+					&sandbox_instance.instance,
+					&sandbox_instance.limits,
+					&trap.into(),
+				)))
 		},
 	)?;
 
@@ -825,4 +857,109 @@ mod tests {
 			vec![2],
 		);
 	}
+
+	#[test]
+	fn instructions_consume_fuel() {
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
+
+		let within_fuel = wabt::wat2wasm(r#"
+		(module
+			(import "env" "nop" (func $nop))
+
+			(func (export "call")
+				(call $nop)
+				(call $nop)
+			)
+		)
+		"#).unwrap();
+		// Every iteration of the loop executes at least six instructions.
+		let out_of_fuel = wabt::wat2wasm(r#"
+		(module
+			(import "env" "nop" (func $nop))
+
+			(func (export "call") (local $n i32)
+				(set_local $n
+					(i32.const 5)
+				)
+				(loop $continue
+					(call $nop)
+					(br_if $continue
+						(tee_local $n
+							(i32.sub
+								(get_local $n)
+								(i32.const 1)
+							)
+						)
+					)
+				)
+			)
+		)
+		"#).unwrap();
+
+		assert_eq!(
+			WasmExecutor::new().call(&mut ext, 8, &test_code[..], "test_sandbox_limits", &within_fuel).unwrap(),
+			vec![0],
+		);
+		assert_eq!(
+			WasmExecutor::new().call(&mut ext, 8, &test_code[..], "test_sandbox_limits", &out_of_fuel).unwrap(),
+			vec![4],
+		);
+	}
+
+	#[test]
+	fn stack_height_is_limited() {
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
+
+		let code = wabt::wat2wasm(r#"
+		(module
+			(func $recurse (local i32)
+				(call $recurse)
+			)
+			(func (export "call")
+				(call $recurse)
+			)
+		)
+		"#).unwrap();
+
+		assert_eq!(
+			WasmExecutor::new().call(&mut ext, 8, &test_code[..], "test_sandbox_limits", &code).unwrap(),
+			vec![5],
+		);
+	}
+
+	#[test]
+	fn denied_imports_and_features_are_rejected() {
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
+
+		let forbidden_import = wabt::wat2wasm(r#"
+		(module
+			(import "env" "forbidden" (func $forbidden))
+
+			(func (export "call")
+				(call $forbidden)
+			)
+		)
+		"#).unwrap();
+		let forbidden_feature = wabt::wat2wasm(r#"
+		(module
+			(func (export "call")
+				(drop
+					(f32.const 1)
+				)
+			)
+		)
+		"#).unwrap();
+
+		assert_eq!(
+			WasmExecutor::new().call(&mut ext, 8, &test_code[..], "test_sandbox_limits", &forbidden_import).unwrap(),
+			vec![6],
+		);
+		assert_eq!(
+			WasmExecutor::new().call(&mut ext, 8, &test_code[..], "test_sandbox_limits", &forbidden_feature).unwrap(),
+			vec![7],
+		);
+	}
 }
@@ -25,7 +25,7 @@ use wasmi::{
 };
 use wasmi::RuntimeValue::{I32, I64, self};
 use wasmi::memory_units::{Pages};
-use state_machine::{Externalities, ChildStorageKey};
+use state_machine::{Externalities, ChildStorageKey, execution_trace};
 use crate::error::{Error, ErrorKind, Result};
 use crate::wasm_utils::UserError;
 use primitives::{blake2_128, blake2_256, twox_64, twox_128, twox_256, ed25519, sr25519, Pair};
@@ -34,8 +34,8 @@ use primitives::sandbox as sandbox_primitives;
 use primitives::{H256, Blake2Hasher};
 use trie::ordered_trie_root;
 use crate::sandbox;
-use crate::allocator;
-use log::trace;
+use crate::allocator::{self, HeapConfig};
+use log::{debug, trace};
 
 #[cfg(feature="wasm-extern-trace")]
 macro_rules! debug_trace {
@@ -46,20 +46,51 @@ macro_rules! debug_trace {
 	( $( $x:tt )* ) => ()
 }
 
-struct FunctionExecutor<'e, E: Externalities<Blake2Hasher> + 'e> {
+struct FunctionExecutor<'e, E: Externalities<Blake2Hasher> + ?Sized + 'e> {
 	sandbox_store: sandbox::Store,
-	heap: allocator::FreeingBumpHeapAllocator,
+	heap: allocator::Heap,
 	memory: MemoryRef,
 	table: Option<TableRef>,
 	ext: &'e mut E,
 	hash_lookup: HashMap<Vec<u8>, Vec<u8>>,
 }
 
-impl<'e, E: Externalities<Blake2Hasher>> FunctionExecutor<'e, E> {
-	fn new(m: MemoryRef, t: Option<TableRef>, e: &'e mut E) -> Result<Self> {
+impl<'e, E: Externalities<Blake2Hasher> + ?Sized> FunctionExecutor<'e, E> {
+	/// Instantiate a sandboxed module, returning the index of the instance or an FFI error code.
+	fn sandbox_instantiate(
+		&mut self,
+		dispatch_thunk_idx: u32,
+		wasm: &[u8],
+		raw_env_def: &[u8],
+		limits: sandbox_primitives::InstanceLimits,
+		state: u32,
+	) -> std::result::Result<u32, UserError> {
+		// Extract a dispatch thunk from instance's table by the specified index.
+		let dispatch_thunk = {
+			let table = self.table.as_ref()
+				.ok_or_else(|| UserError("Runtime doesn't have a table; sandbox is unavailable"))?;
+			table.get(dispatch_thunk_idx)
+				.map_err(|_| UserError("dispatch_thunk_idx is out of the table bounds"))?
+				.ok_or_else(|| UserError("dispatch_thunk_idx points on an empty table entry"))?
+				.clone()
+		};
+
+		let instance_idx_or_err_code =
+			match sandbox::instantiate(self, dispatch_thunk, wasm, raw_env_def, limits, state) {
+				Ok(instance_idx) => instance_idx,
+				Err(sandbox::InstantiationError::StartTrapped(reason)) => trap_reason_code(reason),
+				Err(sandbox::InstantiationError::ForbiddenImport) => sandbox_primitives::ERR_FORBIDDEN_IMPORT,
+				Err(sandbox::InstantiationError::ForbiddenFeature) => sandbox_primitives::ERR_FORBIDDEN_FEATURE,
+				Err(_) => sandbox_primitives::ERR_MODULE,
+			};
+
+		Ok(instance_idx_or_err_code as u32)
+	}
+
+	fn new(m: MemoryRef, t: Option<TableRef>, e: &'e mut E, heap_config: HeapConfig) -> Result<Self> {
 		Ok(FunctionExecutor {
 			sandbox_store: sandbox::Store::new(),
-			heap: allocator::FreeingBumpHeapAllocator::new(m.clone()),
+			heap: allocator::Heap::new(m.clone(), heap_config),
 			memory: m,
 			table: t,
 			ext: e,
@@ -68,7 +99,7 @@ impl<'e, E: Externalities<Blake2Hasher>> FunctionExecutor<'e, E> {
 	}
 }
 
-impl<'e, E: Externalities<Blake2Hasher>> sandbox::SandboxCapabilities for FunctionExecutor<'e, E> {
+impl<'e, E: Externalities<Blake2Hasher> + ?Sized> sandbox::SandboxCapabilities for FunctionExecutor<'e, E> {
 	fn store(&self) -> &sandbox::Store {
 		&self.sandbox_store
 	}
@@ -113,6 +144,15 @@ impl ReadPrimitive<u32> for MemoryInstance {
 	}
 }
 
+/// The FFI error code of a trap of a sandboxed instance.
+fn trap_reason_code(reason: sandbox_primitives::TrapReason) -> u32 {
+	match reason {
+		sandbox_primitives::TrapReason::OutOfFuel => sandbox_primitives::ERR_OUT_OF_FUEL,
+		sandbox_primitives::TrapReason::StackOverflow => sandbox_primitives::ERR_STACK_OVERFLOW,
+		sandbox_primitives::TrapReason::Other => sandbox_primitives::ERR_EXECUTION,
+	}
+}
+
 impl_function_executor!(this: FunctionExecutor<'e, E>,
 	ext_print_utf8(utf8_data: *const u8, utf8_len: u32) => {
 		if let Ok(utf8) = this.memory.get(utf8_data, utf8_len as usize) {
@@ -234,6 +274,20 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		this.ext.clear_prefix(&prefix);
 		Ok(())
 	},
+	ext_storage_start_transaction() => {
+		this.ext.storage_start_transaction();
+		Ok(())
+	},
+	ext_storage_rollback_transaction() => {
+		this.ext.storage_rollback_transaction()
+			.map_err(|_| UserError("ext_storage_rollback_transaction: there is no open storage transaction"))?;
+		Ok(())
+	},
+	ext_storage_commit_transaction() => {
+		this.ext.storage_commit_transaction()
+			.map_err(|_| UserError("ext_storage_commit_transaction: there is no open storage transaction"))?;
+		Ok(())
+	},
 	ext_kill_child_storage(storage_key_data: *const u8, storage_key_len: u32) => {
 		let storage_key = this.memory.get(
 			storage_key_data,
@@ -465,6 +519,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 				},
 				HexDisplay::from(&hashed_key)
 			);
+			execution_trace::record_hash(&key, &hashed_key);
 			this.hash_lookup.insert(hashed_key.to_vec(), key);
 			hashed_key
 		};
@@ -489,6 +544,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 				},
 				HexDisplay::from(&hashed_key)
 			);
+			execution_trace::record_hash(&key, &hashed_key);
 			this.hash_lookup.insert(hashed_key.to_vec(), key);
 			hashed_key
 		};
@@ -500,7 +556,10 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		let result: [u8; 32] = if len == 0 {
 			twox_256(&[0u8; 0])
 		} else {
-			twox_256(&this.memory.get(data, len as usize).map_err(|_| UserError("Invalid attempt to get data in ext_twox_256"))?)
+			let data = this.memory.get(data, len as usize).map_err(|_| UserError("Invalid attempt to get data in ext_twox_256"))?;
+			let hashed = twox_256(&data);
+			execution_trace::record_hash(&data, &hashed);
+			hashed
 		};
 		this.memory.set(out, &result).map_err(|_| UserError("Invalid attempt to set result in ext_twox_256"))?;
 		Ok(())
@@ -513,6 +572,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		} else {
 			let key = this.memory.get(data, len as usize).map_err(|_| UserError("Invalid attempt to get key in ext_blake2_128"))?;
 			let hashed_key = blake2_128(&key);
+			execution_trace::record_hash(&key, &hashed_key);
 			this.hash_lookup.insert(hashed_key.to_vec(), key);
 			hashed_key
 		};
@@ -524,7 +584,10 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		let result: [u8; 32] = if len == 0 {
 			blake2_256(&[0u8; 0])
 		} else {
-			blake2_256(&this.memory.get(data, len as usize).map_err(|_| UserError("Invalid attempt to get data in ext_blake2_256"))?)
+			let data = this.memory.get(data, len as usize).map_err(|_| UserError("Invalid attempt to get data in ext_blake2_256"))?;
+			let hashed = blake2_256(&data);
+			execution_trace::record_hash(&data, &hashed);
+			hashed
 		};
 		this.memory.set(out, &result).map_err(|_| UserError("Invalid attempt to set result in ext_blake2_256"))?;
 		Ok(())
@@ -611,24 +674,32 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		let raw_env_def = this.memory.get(imports_ptr, imports_len as usize)
 			.map_err(|_| UserError("OOB while ext_sandbox_instantiate: imports"))?;
 
-		// Extract a dispatch thunk from instance's table by the specified index.
-		let dispatch_thunk = {
-			let table = this.table.as_ref()
-				.ok_or_else(|| UserError("Runtime doesn't have a table; sandbox is unavailable"))?;
-			table.get(dispatch_thunk_idx)
-				.map_err(|_| UserError("dispatch_thunk_idx is out of the table bounds"))?
-				.ok_or_else(|| UserError("dispatch_thunk_idx points on an empty table entry"))?
-				.clone()
-		};
+		Ok(this.sandbox_instantiate(dispatch_thunk_idx, &wasm, &raw_env_def, Default::default(), state)?)
+	},
+	ext_sandbox_instantiate_with_limits(
+		dispatch_thunk_idx: usize,
+		wasm_ptr: *const u8,
+		wasm_len: usize,
+		imports_ptr: *const u8,
+		imports_len: usize,
+		limits_ptr: *const u8,
+		limits_len: usize,
+		state: usize
+	) -> u32 => {
+		use parity_codec::Decode;
 
-		let instance_idx_or_err_code =
-			match sandbox::instantiate(this, dispatch_thunk, &wasm, &raw_env_def, state) {
-				Ok(instance_idx) => instance_idx,
-				Err(sandbox::InstantiationError::StartTrapped) => sandbox_primitives::ERR_EXECUTION,
-				Err(_) => sandbox_primitives::ERR_MODULE,
-			};
+		let wasm = this.memory.get(wasm_ptr, wasm_len as usize)
+			.map_err(|_| UserError("OOB while ext_sandbox_instantiate_with_limits: wasm"))?;
+		let raw_env_def = this.memory.get(imports_ptr, imports_len as usize)
+			.map_err(|_| UserError("OOB while ext_sandbox_instantiate_with_limits: imports"))?;
+		let raw_limits = this.memory.get(limits_ptr, limits_len as usize)
+			.map_err(|_| UserError("OOB while ext_sandbox_instantiate_with_limits: limits"))?;
+		let limits = match sandbox_primitives::InstanceLimits::decode(&mut &raw_limits[..]) {
+			Some(limits) => limits,
+			None => return Ok(sandbox_primitives::ERR_MODULE),
+		};
 
-		Ok(instance_idx_or_err_code as u32)
+		Ok(this.sandbox_instantiate(dispatch_thunk_idx, &wasm, &raw_env_def, limits, state)?)
 	},
 	ext_sandbox_instance_teardown(instance_idx: u32) => {
 		this.sandbox_store.instance_teardown(instance_idx)?;
@@ -671,7 +742,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 					Ok(sandbox_primitives::ERR_OK)
 				})
 			}
-			Err(_) => Ok(sandbox_primitives::ERR_EXECUTION),
+			Err(reason) => Ok(trap_reason_code(reason)),
 		}
 	},
 	ext_sandbox_memory_new(initial: u32, maximum: u32) -> u32 => {
@@ -710,20 +781,36 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		this.sandbox_store.memory_teardown(memory_idx)?;
 		Ok(())
 	},
-	=> <'e, E: Externalities<Blake2Hasher> + 'e>
+	=> <'e, E: Externalities<Blake2Hasher> + ?Sized + 'e>
 );
 
 /// Wasm rust executor for contracts.
 ///
 /// Executes the provided code in a sandboxed wasm runtime.
 #[derive(Debug, Clone)]
-pub struct WasmExecutor;
+pub struct WasmExecutor {
+	heap_config: HeapConfig,
+}
 
 impl WasmExecutor {
 
 	/// Create a new instance.
 	pub fn new() -> Self {
-		WasmExecutor
+		WasmExecutor {
+			heap_config: Default::default(),
+		}
+	}
+
+	/// Create a new instance which uses the given configuration for the runtime heap.
+	pub(crate) fn with_heap_config(heap_config: HeapConfig) -> Self {
+		WasmExecutor {
+			heap_config,
+		}
+	}
+
+	/// The configuration of the runtime heap used by this executor.
+	pub(crate) fn heap_config(&self) -> HeapConfig {
+		self.heap_config
 	}
 
 	/// Call a given method in the given code.
@@ -731,7 +818,7 @@ impl WasmExecutor {
 	/// Signature of this method needs to be `(I32, I32) -> I64`.
 	///
 	/// This should be used for tests only.
-	pub fn call<E: Externalities<Blake2Hasher>>(
+	pub fn call<E: Externalities<Blake2Hasher> + ?Sized>(
 		&self,
 		ext: &mut E,
 		heap_pages: usize,
@@ -748,7 +835,7 @@ impl WasmExecutor {
 	///
 	/// This should be used for tests only.
 	pub fn call_with_custom_signature<
-		E: Externalities<Blake2Hasher>,
+		E: Externalities<Blake2Hasher> + ?Sized,
 		F: FnOnce(&mut FnMut(&[u8]) -> Result<u32>) -> Result<Vec<RuntimeValue>>,
 		FR: FnOnce(Option<RuntimeValue>, &MemoryRef) -> Result<Option<R>>,
 		R,
@@ -763,10 +850,10 @@ impl WasmExecutor {
 	) -> Result<R> {
 		let module = wasmi::Module::from_buffer(code)?;
 		let module = self.prepare_module(ext, heap_pages, &module)?;
-		self.call_in_wasm_module_with_custom_signature(ext, &module, method, create_parameters, filter_result)
+		self.call_in_wasm_module_with_custom_signature(ext, &module, method, create_parameters, filter_result, true)
 	}
 
-	fn get_mem_instance(module: &ModuleRef) -> Result<MemoryRef> {
+	pub(crate) fn get_mem_instance(module: &ModuleRef) -> Result<MemoryRef> {
 		Ok(module
 			.export_by_name("memory")
 			.ok_or_else(|| Error::from(ErrorKind::InvalidMemoryReference))?
@@ -776,12 +863,36 @@ impl WasmExecutor {
 	}
 
 	/// Call a given method in the given wasm-module runtime.
-	pub fn call_in_wasm_module<E: Externalities<Blake2Hasher>>(
+	pub fn call_in_wasm_module<E: Externalities<Blake2Hasher> + ?Sized>(
+		&self,
+		ext: &mut E,
+		module_instance: &ModuleRef,
+		method: &str,
+		data: &[u8],
+	) -> Result<Vec<u8>> {
+		self.call_in_wasm_module_impl(ext, module_instance, method, data, true)
+	}
+
+	/// Call a given method in the given wasm-module runtime, leaving the memory of the module
+	/// instance as the call has left it. The caller is responsible for restoring the memory
+	/// before the next call.
+	pub(crate) fn call_in_wasm_module_without_cleanup<E: Externalities<Blake2Hasher> + ?Sized>(
 		&self,
 		ext: &mut E,
 		module_instance: &ModuleRef,
 		method: &str,
 		data: &[u8],
+	) -> Result<Vec<u8>> {
+		self.call_in_wasm_module_impl(ext, module_instance, method, data, false)
+	}
+
+	fn call_in_wasm_module_impl<E: Externalities<Blake2Hasher> + ?Sized>(
+		&self,
+		ext: &mut E,
+		module_instance: &ModuleRef,
+		method: &str,
+		data: &[u8],
+		cleanup: bool,
 	) -> Result<Vec<u8>> {
 		self.call_in_wasm_module_with_custom_signature(
 			ext,
@@ -799,13 +910,14 @@ impl WasmExecutor {
 				} else {
 					Ok(None)
 				}
-			}
+			},
+			cleanup,
 		)
 	}
 
 	/// Call a given method in the given wasm-module runtime.
 	fn call_in_wasm_module_with_custom_signature<
-		E: Externalities<Blake2Hasher>,
+		E: Externalities<Blake2Hasher> + ?Sized,
 		F: FnOnce(&mut FnMut(&[u8]) -> Result<u32>) -> Result<Vec<RuntimeValue>>,
 		FR: FnOnce(Option<RuntimeValue>, &MemoryRef) -> Result<Option<R>>,
 		R,
@@ -816,6 +928,7 @@ impl WasmExecutor {
 		method: &str,
 		create_parameters: F,
 		filter_result: FR,
+		cleanup: bool,
 	) -> Result<R> {
 		// extract a reference to a linear memory, optional reference to a table
 		// and then initialize FunctionExecutor.
@@ -826,7 +939,7 @@ impl WasmExecutor {
 
 		let low = memory.lowest_used();
 		let used_mem = memory.used_size();
-		let mut fec = FunctionExecutor::new(memory.clone(), table, ext)?;
+		let mut fec = FunctionExecutor::new(memory.clone(), table, ext, self.heap_config)?;
 		let parameters = create_parameters(&mut |data: &[u8]| {
 			let offset = fec.heap.allocate(data.len() as u32).map_err(|_| ErrorKind::Runtime)?;
 			memory.set(offset, &data)?;
@@ -845,22 +958,25 @@ impl WasmExecutor {
 			},
 			Err(e) => {
 				trace!(target: "wasm-executor", "Failed to execute code with {} pages", memory.current_size().0);
+				debug!(target: "wasm-heap", "Heap usage of the failed call to {}: {:?}", method, fec.heap.stats());
 				Err(e.into())
 			},
 		};
 
 		// cleanup module instance for next use
-		let new_low = memory.lowest_used();
-		if new_low < low {
-			memory.zero(new_low as usize, (low - new_low) as usize)?;
-			memory.reset_lowest_used(low);
+		if cleanup {
+			let new_low = memory.lowest_used();
+			if new_low < low {
+				memory.zero(new_low as usize, (low - new_low) as usize)?;
+				memory.reset_lowest_used(low);
+			}
+			memory.with_direct_access_mut(|buf| buf.resize(used_mem.0, 0));
 		}
-		memory.with_direct_access_mut(|buf| buf.resize(used_mem.0, 0));
 		result
 	}
 
 	/// Prepare module instance
-	pub fn prepare_module<E: Externalities<Blake2Hasher>>(
+	pub fn prepare_module<E: Externalities<Blake2Hasher> + ?Sized>(
 		&self,
 		ext: &mut E,
 		heap_pages: usize,
@@ -882,7 +998,7 @@ impl WasmExecutor {
 			.not_started_instance()
 			.export_by_name("__indirect_function_table")
 			.and_then(|e| e.as_table().cloned());
-		let mut fec = FunctionExecutor::new(memory.clone(), table, ext)?;
+		let mut fec = FunctionExecutor::new(memory.clone(), table, ext, self.heap_config)?;
 
 		// finish instantiation by running 'start' function (if any).
 		Ok(intermediate_instance.run_start(&mut fec)?)
@@ -965,6 +1081,20 @@ mod tests {
 		assert_eq!(expected, ext);
 	}
 
+	#[test]
+	fn storage_transactions_should_work() {
+		let mut ext = TestExternalities::default();
+		let test_code = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
+
+		let output = WasmExecutor::new().call(&mut ext, 8, &test_code[..], "test_storage_transactions", &[]).unwrap();
+
+		assert_eq!(output, b"all ok!".to_vec());
+		let expected: TestExternalities<_> = map![
+			b"kept".to_vec() => b"2".to_vec()
+		];
+		assert_eq!(expected, ext);
+	}
+
 	#[test]
 	fn blake2_256_should_work() {
 		let mut ext = TestExternalities::default();
@@ -0,0 +1,244 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The runtime Wasm code prepared for execution.
+//!
+//! The runtime code is prepared once (the prepared runtime is cached by the `NativeExecutor`
+//! by the code hash) and then executes calls against it. Every call starts with a fresh heap
+//! of the same size.
+
+use wasmi::{Module as WasmModule, ModuleRef as WasmModuleInstanceRef};
+use state_machine::Externalities;
+use primitives::Blake2Hasher;
+use crate::error::{ErrorKind, Result};
+use crate::wasm_executor::WasmExecutor;
+use crate::allocator::{AllocatorKind, HeapConfig};
+use log::trace;
+
+/// The runtime code prepared for execution.
+pub trait WasmRuntime {
+	/// Call a method of the runtime. `ext` backs the host functions used by the runtime.
+	fn call(&mut self, ext: &mut dyn Externalities<Blake2Hasher>, method: &str, data: &[u8]) -> Result<Vec<u8>>;
+
+	/// Number of 64KB pages of the heap that is given to every call.
+	fn heap_pages(&self) -> u64;
+
+	/// Use the given allocator for the heap of the following calls.
+	fn set_allocator(&mut self, kind: AllocatorKind);
+}
+
+/// The part of the linear memory of a freshly prepared instance which calls may change.
+///
+/// `wasmi` allocates the memory lazily: the buffer only extends up to the highest address
+/// touched so far and the instance tracks the lowest one. A call can therefore only have
+/// changed the memory from its lowest used address up to the end of the buffer; everything
+/// above the initial end of the buffer (e.g. the heap) is dropped, and only the range between
+/// the lowest used address and the initial end is restored.
+///
+/// The globals are not part of the snapshot. The only mutable one of the runtimes is the
+/// stack pointer, which a call that returns brings back to its initial value. After a trap
+/// the instance is prepared again instead.
+struct InstanceSnapshot {
+	/// The memory from `lowest_used` up to the initial end of the buffer.
+	data: Vec<u8>,
+	lowest_used: u32,
+}
+
+impl InstanceSnapshot {
+	fn take(instance: &WasmModuleInstanceRef) -> Result<Self> {
+		let memory = WasmExecutor::get_mem_instance(instance)?;
+		let lowest_used = memory.lowest_used() as usize;
+		let (lowest_used, data) = memory.with_direct_access(|buf| {
+			let lowest_used = lowest_used.min(buf.len());
+			(lowest_used as u32, buf[lowest_used..].to_vec())
+		});
+
+		Ok(InstanceSnapshot { data, lowest_used })
+	}
+
+	/// Bring the memory of the instance back to the snapshot. Returns the number of restored bytes.
+	fn restore(&self, instance: &WasmModuleInstanceRef) -> Result<usize> {
+		let memory = WasmExecutor::get_mem_instance(instance)?;
+		let new_low = memory.lowest_used().min(self.lowest_used) as usize;
+		let low = self.lowest_used as usize;
+		memory.reset_lowest_used(self.lowest_used);
+		Ok(memory.with_direct_access_mut(|buf| {
+			buf.resize(low + self.data.len(), 0);
+			for byte in &mut buf[new_low..low] {
+				*byte = 0;
+			}
+			buf[low..].copy_from_slice(&self.data);
+			buf.len() - new_low
+		}))
+	}
+}
+
+/// The runtime executed by the `wasmi` interpreter.
+///
+/// With fast instance reuse the instance is prepared only once. Its memory is saved right
+/// after the preparation and restored after every successful call. A failed call may have
+/// left the globals in any state, so the instance is prepared again.
+pub struct InterpretedRuntime {
+	executor: WasmExecutor,
+	module: WasmModule,
+	instance: WasmModuleInstanceRef,
+	heap_pages: u64,
+	snapshot: Option<InstanceSnapshot>,
+}
+
+impl InterpretedRuntime {
+	/// Prepare the runtime `code` for execution. `fast_instance_reuse` enables restoring only
+	/// the changed state of the instance between calls.
+	pub fn new(
+		ext: &mut dyn Externalities<Blake2Hasher>,
+		code: &[u8],
+		heap_pages: u64,
+		fast_instance_reuse: bool,
+		heap_config: HeapConfig,
+	) -> Result<Self> {
+		let executor = WasmExecutor::with_heap_config(heap_config);
+		let module = WasmModule::from_buffer(code)
+			.map_err(|_| ErrorKind::InvalidCode(vec![]))?;
+		let instance = executor.prepare_module(ext, heap_pages as usize, &module)?;
+		let snapshot = if fast_instance_reuse {
+			Some(InstanceSnapshot::take(&instance)?)
+		} else {
+			None
+		};
+		Ok(InterpretedRuntime {
+			executor,
+			module,
+			instance,
+			heap_pages,
+			snapshot,
+		})
+	}
+}
+
+impl WasmRuntime for InterpretedRuntime {
+	fn call(&mut self, ext: &mut dyn Externalities<Blake2Hasher>, method: &str, data: &[u8]) -> Result<Vec<u8>> {
+		if self.snapshot.is_none() {
+			return self.executor.call_in_wasm_module(ext, &self.instance, method, data);
+		}
+
+		let result = self.executor.call_in_wasm_module_without_cleanup(ext, &self.instance, method, data);
+		if result.is_ok() {
+			if let Some(ref snapshot) = self.snapshot {
+				let restored = snapshot.restore(&self.instance)?;
+				trace!(target: "wasm-runtime", "Restored {} bytes of memory after calling {}", restored, method);
+			}
+		} else {
+			trace!(target: "wasm-runtime", "Preparing the instance again after the failed call to {}", method);
+			self.instance = self.executor.prepare_module(ext, self.heap_pages as usize, &self.module)?;
+			self.snapshot = Some(InstanceSnapshot::take(&self.instance)?);
+		}
+
+		result
+	}
+
+	fn heap_pages(&self) -> u64 {
+		self.heap_pages
+	}
+
+	fn set_allocator(&mut self, kind: AllocatorKind) {
+		self.executor = WasmExecutor::with_heap_config(HeapConfig {
+			kind,
+			..self.executor.heap_config()
+		});
+	}
+}
+
+/// Prepare the runtime `code` for execution by the `wasmi` interpreter.
+///
+/// `fast_instance_reuse` enables restoring only the changed state of the instance between calls.
+pub fn create_wasm_runtime(
+	ext: &mut dyn Externalities<Blake2Hasher>,
+	code: &[u8],
+	heap_pages: u64,
+	fast_instance_reuse: bool,
+	heap_config: HeapConfig,
+) -> Result<Box<dyn WasmRuntime>> {
+	InterpretedRuntime::new(ext, code, heap_pages, fast_instance_reuse, heap_config)
+		.map(|runtime| Box::new(runtime) as Box<dyn WasmRuntime>)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use state_machine::TestExternalities;
+
+	const TEST_CODE: &[u8] = include_bytes!("../wasm/target/wasm32-unknown-unknown/release/runtime_test.compact.wasm");
+
+	#[test]
+	fn interpreted_runtime_is_reusable() {
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+		for fast_instance_reuse in vec![false, true] {
+			let mut runtime = create_wasm_runtime(
+				&mut ext, TEST_CODE, 8, fast_instance_reuse, Default::default(),
+			).unwrap();
+			assert_eq!(runtime.heap_pages(), 8);
+
+			for _ in 0..2 {
+				assert_eq!(runtime.call(&mut ext, "test_conditional_panic", &[]).unwrap(), vec![0u8; 0]);
+				assert!(runtime.call(&mut ext, "test_conditional_panic", &[2]).is_err());
+			}
+		}
+	}
+
+	#[test]
+	fn fast_instance_reuse_restores_memory() {
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+		let mut runtime = InterpretedRuntime::new(&mut ext, TEST_CODE, 8, true, Default::default()).unwrap();
+		let memory = WasmExecutor::get_mem_instance(&runtime.instance).unwrap();
+		let initial_memory = memory.with_direct_access(|buf| buf.to_vec());
+
+		let data = b"Hello world".to_vec();
+		for _ in 0..2 {
+			assert_eq!(runtime.call(&mut ext, "test_data_in", &data).unwrap(), b"all ok!".to_vec());
+			assert!(memory.with_direct_access(|buf| buf == &initial_memory[..]));
+		}
+	}
+
+	#[test]
+	fn fast_instance_reuse_prepares_the_instance_again_after_trap() {
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+		let mut runtime = InterpretedRuntime::new(&mut ext, TEST_CODE, 8, true, Default::default()).unwrap();
+		let memory = |runtime: &InterpretedRuntime| WasmExecutor::get_mem_instance(&runtime.instance)
+			.unwrap()
+			.with_direct_access(|buf| buf.to_vec());
+		let initial_memory = memory(&runtime);
+
+		assert!(runtime.call(&mut ext, "test_conditional_panic", &[2]).is_err());
+		assert_eq!(memory(&runtime), initial_memory);
+
+		assert_eq!(runtime.call(&mut ext, "test_conditional_panic", &[]).unwrap(), vec![0u8; 0]);
+		assert_eq!(memory(&runtime), initial_memory);
+	}
+
+	#[test]
+	fn interpreted_runtime_works_with_reclaiming_allocator() {
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+		let mut runtime = create_wasm_runtime(
+			&mut ext, TEST_CODE, 8, false, Default::default(),
+		).unwrap();
+		runtime.set_allocator(AllocatorKind::Reclaiming);
+
+		let data = b"Hello world".to_vec();
+		for _ in 0..2 {
+			assert_eq!(runtime.call(&mut ext, "test_data_in", &data).unwrap(), b"all ok!".to_vec());
+		}
+	}
+}
@@ -18,7 +18,8 @@
 
 use wasmi::{ValueType, RuntimeValue, HostError};
 use wasmi::nan_preserving_float::{F32, F64};
-use std::fmt;
+use std::{fmt, time::Instant};
+use state_machine::execution_trace;
 
 #[derive(Debug, PartialEq)]
 pub struct UserError(pub &'static str);
@@ -108,6 +109,35 @@ macro_rules! unmarshall_args {
 	})
 }
 
+/// Reports the time spent in a host function to the execution trace when dropped.
+///
+/// Doesn't read the clock if tracing is disabled.
+pub struct HostFunctionTimer {
+	started: Option<(&'static str, Instant)>,
+}
+
+impl HostFunctionTimer {
+	/// Start timing a call to the host function `name`.
+	#[inline]
+	pub fn start(name: &'static str) -> Self {
+		HostFunctionTimer {
+			started: if execution_trace::is_enabled() {
+				Some((name, Instant::now()))
+			} else {
+				None
+			},
+		}
+	}
+}
+
+impl Drop for HostFunctionTimer {
+	fn drop(&mut self) {
+		if let Some((name, started)) = self.started.take() {
+			execution_trace::record_host_function(name, started.elapsed());
+		}
+	}
+}
+
 /// Since we can't specify the type of closure directly at binding site:
 ///
 /// ```nocompile
@@ -156,6 +186,7 @@ macro_rules! dispatch_fn {
 
 	( @iter $index:expr, $index_ident:ident, $objectname:ident, $args_iter:ident, $name:ident ( $( $names:ident : $params:ty ),* ) $( -> $returns:ty )* => $body:tt $($tail:tt)*) => (
 		if $index_ident == $index {
+			let _timer = $crate::wasm_utils::HostFunctionTimer::start(stringify!($name));
 			{ marshall!($args_iter, $objectname, ( $( $names : $params ),* ) $( -> $returns )* => $body) }
 		}
 		dispatch_fn!( @iter $index + 1, $index_ident, $objectname, $args_iter $($tail)*)
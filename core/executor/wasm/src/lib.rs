@@ -6,8 +6,9 @@ use alloc::vec::Vec;
 use alloc::slice;
 
 use runtime_io::{
-	set_storage, storage, clear_prefix, print, blake2_128, blake2_256,
-	twox_128, twox_256, ed25519_verify, sr25519_verify, enumerated_trie_root
+	set_storage, storage, clear_storage, clear_prefix, print, blake2_128, blake2_256,
+	twox_128, twox_256, ed25519_verify, sr25519_verify, enumerated_trie_root,
+	start_transaction, commit_transaction, rollback_transaction,
 };
 
 macro_rules! impl_stubs {
@@ -57,6 +58,17 @@ impl_stubs!(
 		clear_prefix(input);
 		b"all ok!".to_vec()
 	},
+	test_storage_transactions => |_| {
+		set_storage(b"kept", b"1");
+		start_transaction();
+		set_storage(b"kept", b"2");
+		start_transaction();
+		set_storage(b"rolled back", b"3");
+		clear_storage(b"kept");
+		rollback_transaction();
+		commit_transaction();
+		b"all ok!".to_vec()
+	},
 	test_empty_return => |_| Vec::new(),
 	test_exhaust_heap => |_| Vec::with_capacity(16777216),
 	test_panic => |_| panic!("test panic"),
@@ -121,14 +133,49 @@ impl_stubs!(
 		let env_builder = sandbox::EnvironmentDefinitionBuilder::new();
 		let code = match sandbox::Instance::new(code, &env_builder, &mut ()) {
 			Ok(_) => 0,
-			Err(sandbox::Error::Module) => 1,
-			Err(sandbox::Error::Execution) => 2,
-			Err(sandbox::Error::OutOfBounds) => 3,
+			Err(err) => sandbox_error_code(err),
+		};
+		[code].to_vec()
+	},
+	test_sandbox_limits => |code: &[u8]| {
+		fn env_nop(_e: &mut (), _args: &[sandbox::TypedValue]) -> Result<sandbox::ReturnValue, sandbox::HostError> {
+			Ok(sandbox::ReturnValue::Unit)
+		}
+
+		let mut env_builder = sandbox::EnvironmentDefinitionBuilder::new();
+		env_builder.add_host_func("env", "nop", env_nop);
+		env_builder.add_host_func("env", "forbidden", env_nop);
+		env_builder.set_limits(sandbox::InstanceLimits {
+			fuel: Some(20),
+			max_stack_height: Some(64),
+			denied_features: [sandbox::WasmFeature::FloatingPoint].to_vec(),
+			denied_imports: [sandbox::ImportName {
+				module_name: b"env".to_vec(),
+				field_name: b"forbidden".to_vec(),
+			}].to_vec(),
+		});
+		let code = match sandbox::Instance::new(code, &env_builder, &mut ())
+			.and_then(|mut instance| instance.invoke(b"call", &[], &mut ()))
+		{
+			Ok(_) => 0,
+			Err(err) => sandbox_error_code(err),
 		};
 		[code].to_vec()
 	}
 );
 
+fn sandbox_error_code(err: sandbox::Error) -> u8 {
+	match err {
+		sandbox::Error::Module => 1,
+		sandbox::Error::Execution => 2,
+		sandbox::Error::OutOfBounds => 3,
+		sandbox::Error::OutOfFuel => 4,
+		sandbox::Error::StackOverflow => 5,
+		sandbox::Error::ForbiddenImport => 6,
+		sandbox::Error::ForbiddenFeature => 7,
+	}
+}
+
 fn execute_sandboxed(code: &[u8], args: &[sandbox::TypedValue]) -> Result<sandbox::ReturnValue, sandbox::HostError> {
 	struct State {
 		counter: u32,
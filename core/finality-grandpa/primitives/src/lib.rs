@@ -39,6 +39,52 @@ pub struct ScheduledChange<N> {
 	pub delay: N,
 }
 
+/// A vote of a GRANDPA voter, as it is signed.
+///
+/// This encodes like the prevote and precommit variants of the finality-grandpa
+/// `Message`, so signatures of votes seen on the network can be checked against it.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+pub enum Vote<H, N> {
+	/// A prevote for the given target hash and number.
+	Prevote(H, N),
+	/// A precommit for the given target hash and number.
+	Precommit(H, N),
+}
+
+impl<H, N> Vote<H, N> {
+	/// Whether both votes were cast in the same voting step.
+	pub fn same_kind(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Vote::Prevote(..), Vote::Prevote(..)) | (Vote::Precommit(..), Vote::Precommit(..)) => true,
+			_ => false,
+		}
+	}
+}
+
+/// Two conflicting votes signed by the same voter in the same round.
+///
+/// This is what the runtime expects to decode from a GRANDPA misbehavior report.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+pub struct EquivocationReport<H, N, Id, Signature> {
+	/// The id of the authority set the votes were cast in.
+	pub set_id: u64,
+	/// The round in which the votes were cast.
+	pub round: u64,
+	/// The voter which equivocated.
+	pub offender: Id,
+	/// The first vote seen, along with its signature.
+	pub first: (Vote<H, N>, Signature),
+	/// The conflicting vote, along with its signature.
+	pub second: (Vote<H, N>, Signature),
+}
+
+/// The payload signed by voters for a message in the given round and set.
+pub fn localized_payload<E: Encode>(round: u64, set_id: u64, message: &E) -> Vec<u8> {
+	(message, round, set_id).encode()
+}
+
 /// WASM function call to check for pending changes.
 pub const PENDING_CHANGE_CALL: &str = "grandpa_pending_change";
 /// WASM function call to get current GRANDPA authorities.
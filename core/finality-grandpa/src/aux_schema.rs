@@ -38,6 +38,7 @@ const VERSION_KEY: &[u8] = b"grandpa_schema_version";
 const SET_STATE_KEY: &[u8] = b"grandpa_completed_round";
 const AUTHORITY_SET_KEY: &[u8] = b"grandpa_voters";
 const CONSENSUS_CHANGES_KEY: &[u8] = b"grandpa_consensus_changes";
+const AUTHORITY_SET_CHANGES_KEY: &[u8] = b"grandpa_authority_set_changes";
 
 const CURRENT_VERSION: u32 = 2;
 
@@ -427,11 +428,42 @@ pub(crate) fn update_consensus_changes<H, N, F, R>(
 	write_aux(&[(CONSENSUS_CHANGES_KEY, set.encode().as_slice())])
 }
 
+/// Load the current authority set.
+pub(crate) fn load_authority_set<B: AuxStore, H: Decode, N: Decode>(backend: &B)
+	-> ClientResult<Option<AuthoritySet<H, N>>> {
+	load_decode::<_, AuthoritySet<H, N>>(backend, AUTHORITY_SET_KEY)
+}
+
+/// Load the finalized blocks which have enacted standard changes of the authorities set,
+/// together with the id of the enacted set, ordered by the set id.
+pub(crate) fn load_authority_set_changes<B: AuxStore, H: Decode, N: Decode>(backend: &B)
+	-> ClientResult<Vec<(u64, H, N)>> {
+	load_decode::<_, Vec<(u64, H, N)>>(backend, AUTHORITY_SET_CHANGES_KEY)
+		.map(|changes| changes.unwrap_or_default())
+}
+
+/// Note that the given finalized block has enacted a standard change to the authorities set
+/// with the given id.
+pub(crate) fn note_authority_set_change<B, H, N, F, R>(
+	backend: &B,
+	set_id: u64,
+	block: (H, N),
+	write_aux: F,
+) -> ClientResult<R> where
+	B: AuxStore,
+	H: Encode + Decode,
+	N: Encode + Decode,
+	F: FnOnce(&[(&'static [u8], &[u8])]) -> R,
+{
+	let mut changes = load_authority_set_changes::<_, H, N>(backend)?;
+	changes.push((set_id, block.0, block.1));
+	Ok(write_aux(&[(AUTHORITY_SET_CHANGES_KEY, changes.encode().as_slice())]))
+}
+
 #[cfg(test)]
 pub(crate) fn load_authorities<B: AuxStore, H: Decode, N: Decode>(backend: &B)
 	-> Option<AuthoritySet<H, N>> {
-	load_decode::<_, AuthoritySet<H, N>>(backend, AUTHORITY_SET_KEY)
-		.expect("backend error")
+	load_authority_set(backend).expect("backend error")
 }
 
 #[cfg(test)]
@@ -58,6 +58,17 @@
 //! Sending a commit is polite when it may finalize something that the receiving peer
 //! was not aware of.
 //!
+//! #### Catch Up
+//!
+//! These allow a voter which has fallen behind to skip ahead to the current
+//! round. A voter seeing a neighbor packet from a peer which is at least
+//! `CATCH_UP_THRESHOLD` rounds ahead in the same set sends it a catch-up request,
+//! which is answered with the prevotes and precommits of the last round completed
+//! by the peer. Requests are rate-limited on both ends.
+//!
+//! It is impolite to send catch-up requests too often and to send catch-ups that
+//! weren't requested. It is especially impolite to send catch-ups which are invalid.
+//!
 //! ## Expiration
 //!
 //! We keep some amount of recent rounds' messages, but do not accept new ones from rounds
@@ -77,13 +88,16 @@ use log::{trace, debug, warn};
 use futures::prelude::*;
 use futures::sync::mpsc;
 
-use crate::{CompactCommit, SignedMessage};
+use crate::{CatchUp, CompactCommit, SignedMessage};
 use super::{cost, benefit, Round, SetId};
 
 use std::collections::{HashMap, VecDeque};
 use std::time::{Duration, Instant};
 
 const REBROADCAST_AFTER: Duration = Duration::from_secs(60 * 5);
+const CATCH_UP_REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const MIN_CATCH_UP_REQUEST_INTERVAL: Duration = Duration::from_secs(10);
+const CATCH_UP_THRESHOLD: u64 = 2;
 
 /// An outcome of examining a message.
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -227,6 +241,10 @@ pub(super) enum GossipMessage<Block: BlockT> {
 	Commit(FullCommitMessage<Block>),
 	/// A neighbor packet. Not repropagated.
 	Neighbor(VersionedNeighborPacket<NumberFor<Block>>),
+	/// Grandpa catch up request message with round and set info. Not repropagated.
+	CatchUpRequest(CatchUpRequestMessage),
+	/// Grandpa catch up message with set info. Not repropagated.
+	CatchUp(FullCatchUpMessage<Block>),
 }
 
 impl<Block: BlockT> From<NeighborPacket<NumberFor<Block>>> for GossipMessage<Block> {
@@ -257,6 +275,25 @@ pub(super) struct FullCommitMessage<Block: BlockT> {
 	pub(super) message: CompactCommit<Block>,
 }
 
+/// A catch-up request for rounds completed after the given round, in the
+/// given set.
+#[derive(Debug, Encode, Decode, Clone)]
+pub(super) struct CatchUpRequestMessage {
+	/// The round the requesting voter is currently at.
+	pub(super) round: Round,
+	/// The voter set ID the requesting voter is currently at.
+	pub(super) set_id: SetId,
+}
+
+/// Network level catch-up message with topic information.
+#[derive(Debug, Encode, Decode)]
+pub(super) struct FullCatchUpMessage<Block: BlockT> {
+	/// The voter set ID this message is from.
+	pub(super) set_id: SetId,
+	/// The catch-up message itself.
+	pub(super) message: CatchUp<Block>,
+}
+
 /// V1 neighbor packet. Neighbor packets are sent from nodes to their peers
 /// and are not repropagated. These contain information about the node's state.
 #[derive(Debug, Encode, Decode, Clone)]
@@ -297,6 +334,10 @@ pub(super) enum Misbehavior {
 		blocks_loaded: i32,
 		equivocations_caught: i32,
 	},
+	// Bad catch up message.
+	BadCatchUpMessage {
+		signatures_checked: i32,
+	},
 	// A message received that's from the future relative to our view.
 	// always misbehavior.
 	FutureMessage,
@@ -318,6 +359,9 @@ impl Misbehavior {
 
 				(benefit as i32).saturating_add(cost as i32)
 			},
+			BadCatchUpMessage { signatures_checked } =>
+				cost::PER_SIGNATURE_CHECKED.saturating_mul(signatures_checked)
+					.saturating_add(cost::BAD_SIGNATURE),
 			FutureMessage => cost::FUTURE_MESSAGE,
 		}
 	}
@@ -325,12 +369,14 @@ impl Misbehavior {
 
 struct PeerInfo<N> {
 	view: View<N>,
+	last_catch_up_request: Option<Instant>,
 }
 
 impl<N> PeerInfo<N> {
 	fn new() -> Self {
 		PeerInfo {
 			view: View::default(),
+			last_catch_up_request: None,
 		}
 	}
 }
@@ -417,12 +463,30 @@ pub(super) enum Action<H>  {
 	Discard(i32),
 }
 
+/// The state of a catch-up request we've issued.
+enum PendingCatchUp {
+	/// No pending catch up requests.
+	None,
+	/// Pending catch up request which has not been answered yet.
+	Requesting {
+		who: PeerId,
+		request: CatchUpRequestMessage,
+		instant: Instant,
+	},
+	/// A catch up answer which is being processed by the voter.
+	Processing {
+		instant: Instant,
+	},
+}
+
 struct Inner<Block: BlockT> {
 	local_view: View<NumberFor<Block>>,
 	peers: Peers<NumberFor<Block>>,
 	live_topics: KeepTopics<Block>,
 	config: crate::Config,
 	next_rebroadcast: Instant,
+	pending_catch_up: PendingCatchUp,
+	last_completed_round: Option<(SetId, CatchUp<Block>)>,
 }
 
 type MaybeMessage<Block> = Option<(Vec<PeerId>, NeighborPacket<NumberFor<Block>>)>;
@@ -434,6 +498,8 @@ impl<Block: BlockT> Inner<Block> {
 			peers: Peers::default(),
 			live_topics: KeepTopics::new(),
 			next_rebroadcast: Instant::now() + REBROADCAST_AFTER,
+			pending_catch_up: PendingCatchUp::None,
+			last_completed_round: None,
 			config,
 		}
 	}
@@ -476,6 +542,20 @@ impl<Block: BlockT> Inner<Block> {
 		}
 	}
 
+	/// Note that we've completed a round, keeping its votes to answer
+	/// catch-up requests.
+	fn note_completed_round(&mut self, set_id: SetId, catch_up: CatchUp<Block>) {
+		let is_newer = match self.last_completed_round {
+			None => true,
+			Some((last_set_id, ref last)) =>
+				(set_id, catch_up.round_number) > (last_set_id, last.round_number),
+		};
+
+		if is_newer {
+			self.last_completed_round = Some((set_id, catch_up));
+		}
+	}
+
 	fn consider_vote(&self, round: Round, set_id: SetId) -> Consider {
 		self.local_view.consider_vote(round, set_id)
 	}
@@ -546,18 +626,163 @@ impl<Block: BlockT> Inner<Block> {
 		Action::ProcessAndDiscard(topic, benefit::BASIC_VALIDATED_COMMIT)
 	}
 
+	fn validate_catch_up_message(&mut self, who: &PeerId, full: &FullCatchUpMessage<Block>)
+		-> Action<Block::Hash>
+	{
+		match self.pending_catch_up {
+			PendingCatchUp::Requesting { who: ref peer, ref request, .. } => {
+				if peer != who {
+					return Action::Discard(cost::UNSOLICITED_CATCH_UP);
+				}
+
+				if request.set_id != full.set_id || full.message.round_number < request.round.0 {
+					return Action::Discard(cost::MALFORMED_CATCH_UP);
+				}
+			},
+			_ => return Action::Discard(cost::UNSOLICITED_CATCH_UP),
+		}
+
+		if full.message.votes.is_empty() {
+			debug!(target: "afg", "Malformed catch up");
+			return Action::Discard(cost::MALFORMED_CATCH_UP);
+		}
+
+		// check signatures on all contained prevotes and precommits.
+		for (i, signed) in full.message.votes.iter().enumerate() {
+			if let grandpa::Message::PrimaryPropose(_) = signed.message {
+				return Action::Discard(cost::MALFORMED_CATCH_UP);
+			}
+
+			if let Err(()) = super::check_message_sig::<Block>(
+				&signed.message,
+				&signed.id,
+				&signed.signature,
+				full.message.round_number,
+				full.set_id.0,
+			) {
+				debug!(target: "afg", "Bad catch up message signature {}", signed.id);
+				telemetry!(CONSENSUS_DEBUG; "afg.bad_catch_up_msg_signature"; "id" => ?signed.id);
+
+				let cost = Misbehavior::BadCatchUpMessage {
+					signatures_checked: i as i32,
+				}.cost();
+
+				return Action::Discard(cost);
+			}
+		}
+
+		// the voter checks that the round was actually completed.
+		self.pending_catch_up = PendingCatchUp::Processing { instant: Instant::now() };
+
+		let topic = super::global_topic::<Block>(full.set_id.0);
+		Action::ProcessAndDiscard(topic, benefit::BASIC_VALIDATED_CATCH_UP)
+	}
+
+	fn handle_catch_up_request(&mut self, who: &PeerId, request: CatchUpRequestMessage)
+		-> (Option<GossipMessage<Block>>, Action<Block::Hash>)
+	{
+		if request.set_id < self.local_view.set_id {
+			return (None, Action::Discard(self.cost_past_rejection(who, request.round, request.set_id)));
+		}
+
+		if request.set_id > self.local_view.set_id {
+			return (None, Action::Discard(Misbehavior::FutureMessage.cost()));
+		}
+
+		let peer = match self.peers.inner.get_mut(who) {
+			None => return (None, Action::Discard(0)),
+			Some(peer) => peer,
+		};
+
+		let now = Instant::now();
+		if let Some(last) = peer.last_catch_up_request {
+			if now < last + MIN_CATCH_UP_REQUEST_INTERVAL {
+				return (None, Action::Discard(cost::CATCH_UP_REQUEST_TOO_FREQUENT));
+			}
+		}
+
+		peer.last_catch_up_request = Some(now);
+
+		let catch_up = match self.last_completed_round {
+			Some((set_id, ref catch_up))
+				if set_id == request.set_id && catch_up.round_number >= request.round.0 =>
+			{
+				trace!(target: "afg", "Replying to catch-up request for round {} from {} with round {}",
+					request.round.0, who, catch_up.round_number);
+
+				Some(GossipMessage::CatchUp(FullCatchUpMessage {
+					set_id,
+					message: catch_up.clone(),
+				}))
+			},
+			_ => None,
+		};
+
+		(catch_up, Action::Discard(0))
+	}
+
+	// issue a catch-up request to the given peer if it is far enough ahead of
+	// us in the same set and we're not waiting on a previous request.
+	fn try_catch_up(&mut self, who: &PeerId) -> Option<GossipMessage<Block>> {
+		// only voters take part in rounds and need to catch up.
+		if self.config.local_key.is_none() {
+			return None;
+		}
+
+		let now = Instant::now();
+		match self.pending_catch_up {
+			PendingCatchUp::Requesting { instant, .. } | PendingCatchUp::Processing { instant } =>
+				if now < instant + CATCH_UP_REQUEST_TIMEOUT {
+					return None;
+				},
+			PendingCatchUp::None => {},
+		}
+
+		let is_ahead = match self.peers.peer(who) {
+			Some(peer) => peer.view.set_id == self.local_view.set_id
+				&& peer.view.round.0 >= self.local_view.round.0.saturating_add(CATCH_UP_THRESHOLD),
+			None => false,
+		};
+
+		if !is_ahead {
+			return None;
+		}
+
+		let request = CatchUpRequestMessage {
+			round: self.local_view.round,
+			set_id: self.local_view.set_id,
+		};
+
+		debug!(target: "afg", "Voter {} requesting catch up from {} at round {:?}",
+			self.config.name(), who, request.round);
+
+		self.pending_catch_up = PendingCatchUp::Requesting {
+			who: who.clone(),
+			request: request.clone(),
+			instant: now,
+		};
+
+		Some(GossipMessage::CatchUpRequest(request))
+	}
+
 	fn import_neighbor_message(&mut self, who: &PeerId, update: NeighborPacket<NumberFor<Block>>)
-		-> (Vec<Block::Hash>, Action<Block::Hash>)
+		-> (Vec<Block::Hash>, Action<Block::Hash>, Option<GossipMessage<Block>>)
 	{
 		let (cb, topics) = match self.peers.update_peer_state(who, update) {
 			Ok(view) => (100i32, view.map(|view| neighbor_topics::<Block>(view))),
 			Err(misbehavior) => (misbehavior.cost(), None)
 		};
 
+		let catch_up_request = if topics.is_some() {
+			self.try_catch_up(who)
+		} else {
+			None
+		};
+
 		let neighbor_topics = topics.unwrap_or_default();
 
 		// always discard, it's valid for one hop.
-		(neighbor_topics, Action::Discard(cb))
+		(neighbor_topics, Action::Discard(cb), catch_up_request)
 	}
 
 	fn multicast_neighbor_packet(&self) -> MaybeMessage<Block> {
@@ -620,28 +845,43 @@ impl<Block: BlockT> GossipValidator<Block> {
 		}
 	}
 
+	/// Note that we've completed a round in the given set.
+	pub(super) fn note_completed_round(&self, set_id: SetId, catch_up: CatchUp<Block>) {
+		self.inner.write().note_completed_round(set_id, catch_up);
+	}
+
 	fn report(&self, who: PeerId, cost_benefit: i32) {
 		let _ = self.report_sender.unbounded_send(PeerReport { who, cost_benefit });
 	}
 
 	pub(super) fn do_validate(&self, who: &PeerId, mut data: &[u8])
-		-> (Action<Block::Hash>, Vec<Block::Hash>)
+		-> (Action<Block::Hash>, Vec<Block::Hash>, Option<GossipMessage<Block>>)
 	{
 		let mut broadcast_topics = Vec::new();
+		let mut reply = None;
 		let action = {
 			match GossipMessage::<Block>::decode(&mut data) {
 				Some(GossipMessage::VoteOrPrecommit(ref message))
 					=> self.inner.write().validate_round_message(who, message),
 				Some(GossipMessage::Commit(ref message)) => self.inner.write().validate_commit_message(who, message),
 				Some(GossipMessage::Neighbor(update)) => {
-					let (topics, action) = self.inner.write().import_neighbor_message(
+					let (topics, action, catch_up_request) = self.inner.write().import_neighbor_message(
 						who,
 						update.into_neighbor_packet(),
 					);
 
 					broadcast_topics = topics;
+					reply = catch_up_request;
 					action
 				}
+				Some(GossipMessage::CatchUpRequest(request)) => {
+					let (catch_up, action) = self.inner.write().handle_catch_up_request(who, request);
+
+					reply = catch_up;
+					action
+				}
+				Some(GossipMessage::CatchUp(ref message))
+					=> self.inner.write().validate_catch_up_message(who, message),
 				None => {
 					debug!(target: "afg", "Error decoding message");
 					telemetry!(CONSENSUS_DEBUG; "afg.err_decoding_msg"; "" => "");
@@ -652,7 +892,7 @@ impl<Block: BlockT> GossipValidator<Block> {
 			}
 		};
 
-		(action, broadcast_topics)
+		(action, broadcast_topics, reply)
 	}
 }
 
@@ -680,13 +920,17 @@ impl<Block: BlockT> network_gossip::Validator<Block> for GossipValidator<Block>
 	fn validate(&self, context: &mut ValidatorContext<Block>, who: &PeerId, data: &[u8])
 		-> network_gossip::ValidationResult<Block::Hash>
 	{
-		let (action, broadcast_topics) = self.do_validate(who, data);
+		let (action, broadcast_topics, reply) = self.do_validate(who, data);
 
 		// not with lock held!
 		for topic in broadcast_topics {
 			context.send_topic(who, topic, false);
 		}
 
+		if let Some(reply) = reply {
+			context.send_message(who, reply.encode());
+		}
+
 		match action {
 			Action::Keep(topic, cb) => {
 				self.report(who.clone(), cb);
@@ -757,6 +1001,8 @@ impl<Block: BlockT> network_gossip::Validator<Block> for GossipValidator<Block>
 					&& Some(full.message.target_number) > peer_best_commit
 				}
 				Some(GossipMessage::Neighbor(_)) => false,
+				Some(GossipMessage::CatchUpRequest(_)) => false,
+				Some(GossipMessage::CatchUp(_)) => false,
 				Some(GossipMessage::VoteOrPrecommit(_)) => false, // should not be the case.
 			}
 		})
@@ -847,6 +1093,7 @@ mod tests {
 	use super::*;
 	use network_gossip::Validator as GossipValidatorT;
 	use network::test::Block;
+	use substrate_primitives::Pair;
 
 	// some random config (not really needed)
 	fn config() -> crate::Config {
@@ -858,6 +1105,48 @@ mod tests {
 		}
 	}
 
+	fn voter_config() -> crate::Config {
+		crate::Config {
+			local_key: Some(std::sync::Arc::new(keyring::ed25519::Keyring::Alice.pair())),
+			..config()
+		}
+	}
+
+	fn neighbor_packet_data(round: u64, set_id: u64) -> Vec<u8> {
+		GossipMessage::<Block>::from(NeighborPacket {
+			round: Round(round),
+			set_id: SetId(set_id),
+			commit_finalized_height: 1,
+		}).encode()
+	}
+
+	fn signed_precommit(round: u64, set_id: u64) -> SignedMessage<Block> {
+		let keyring = keyring::ed25519::Keyring::Alice;
+		let message = grandpa::Message::Precommit(grandpa::Precommit {
+			target_hash: Default::default(),
+			target_number: 1,
+		});
+
+		let payload = super::super::localized_payload(round, set_id, &message);
+		SignedMessage::<Block> {
+			message,
+			signature: keyring.sign(&payload[..]),
+			id: keyring.pair().public(),
+		}
+	}
+
+	fn catch_up_data(round: u64, set_id: u64) -> Vec<u8> {
+		GossipMessage::<Block>::CatchUp(FullCatchUpMessage {
+			set_id: SetId(set_id),
+			message: CatchUp {
+				round_number: round,
+				base_hash: Default::default(),
+				base_number: 0,
+				votes: vec![signed_precommit(round, set_id)],
+			},
+		}).encode()
+	}
+
 	#[test]
 	fn view_vote_rules() {
 		let view = View { round: Round(100), set_id: SetId(1), last_commit: Some(1000u64) };
@@ -1024,4 +1313,131 @@ mod tests {
 			}
 		}
 	}
+
+	#[test]
+	fn requests_catch_up_from_peers_ahead() {
+		let (val, _) = GossipValidator::<Block>::new(voter_config());
+		val.note_round(Round(1), SetId(0), |_, _| {});
+
+		let peer_a = PeerId::random();
+		let peer_b = PeerId::random();
+		val.inner.write().peers.new_peer(peer_a.clone());
+		val.inner.write().peers.new_peer(peer_b.clone());
+
+		// a peer in the next round isn't far enough ahead.
+		let (_, _, reply) = val.do_validate(&peer_a, &neighbor_packet_data(2, 0));
+		assert!(reply.is_none());
+
+		// a peer in another set can't help us either.
+		let (_, _, reply) = val.do_validate(&peer_b, &neighbor_packet_data(1, 1));
+		assert!(reply.is_none());
+
+		let (_, _, reply) = val.do_validate(&peer_a, &neighbor_packet_data(3, 0));
+		match reply {
+			Some(GossipMessage::CatchUpRequest(request)) => {
+				assert_eq!(request.round, Round(1));
+				assert_eq!(request.set_id, SetId(0));
+			},
+			_ => panic!("expected catch up request"),
+		}
+
+		// only one request is pending at a time.
+		let (_, _, reply) = val.do_validate(&peer_a, &neighbor_packet_data(4, 0));
+		assert!(reply.is_none());
+	}
+
+	#[test]
+	fn non_voters_do_not_request_catch_up() {
+		let (val, _) = GossipValidator::<Block>::new(config());
+		val.note_round(Round(1), SetId(0), |_, _| {});
+
+		let peer = PeerId::random();
+		val.inner.write().peers.new_peer(peer.clone());
+
+		let (_, _, reply) = val.do_validate(&peer, &neighbor_packet_data(10, 0));
+		assert!(reply.is_none());
+	}
+
+	#[test]
+	fn answers_catch_up_requests_with_rate_limit() {
+		let (val, _) = GossipValidator::<Block>::new(config());
+		val.note_round(Round(5), SetId(0), |_, _| {});
+		val.note_completed_round(SetId(0), CatchUp {
+			round_number: 4,
+			base_hash: Default::default(),
+			base_number: 0,
+			votes: vec![signed_precommit(4, 0)],
+		});
+
+		let peer = PeerId::random();
+		val.inner.write().peers.new_peer(peer.clone());
+
+		let request = GossipMessage::<Block>::CatchUpRequest(CatchUpRequestMessage {
+			round: Round(2),
+			set_id: SetId(0),
+		}).encode();
+
+		let (_, _, reply) = val.do_validate(&peer, &request);
+		match reply {
+			Some(GossipMessage::CatchUp(full)) => {
+				assert_eq!(full.set_id, SetId(0));
+				assert_eq!(full.message.round_number, 4);
+			},
+			_ => panic!("expected catch up"),
+		}
+
+		// asking again right away is impolite.
+		let (action, _, reply) = val.do_validate(&peer, &request);
+		assert!(reply.is_none());
+		match action {
+			Action::Discard(cost) => assert_eq!(cost, cost::CATCH_UP_REQUEST_TOO_FREQUENT),
+			_ => panic!("expected discard"),
+		}
+	}
+
+	#[test]
+	fn unsolicited_catch_up_is_penalized() {
+		let (val, _) = GossipValidator::<Block>::new(voter_config());
+		val.note_round(Round(1), SetId(0), |_, _| {});
+
+		let peer_a = PeerId::random();
+		let peer_b = PeerId::random();
+		val.inner.write().peers.new_peer(peer_a.clone());
+		val.inner.write().peers.new_peer(peer_b.clone());
+
+		// nothing was requested.
+		let (action, _, _) = val.do_validate(&peer_a, &catch_up_data(3, 0));
+		match action {
+			Action::Discard(cost) => assert_eq!(cost, cost::UNSOLICITED_CATCH_UP),
+			_ => panic!("expected discard"),
+		}
+
+		// request a catch up from peer a.
+		let (_, _, reply) = val.do_validate(&peer_a, &neighbor_packet_data(4, 0));
+		assert!(reply.is_some());
+
+		// peer b wasn't asked.
+		let (action, _, _) = val.do_validate(&peer_b, &catch_up_data(3, 0));
+		match action {
+			Action::Discard(cost) => assert_eq!(cost, cost::UNSOLICITED_CATCH_UP),
+			_ => panic!("expected discard"),
+		}
+
+		// peer a answers with a catch up for another set.
+		let (action, _, _) = val.do_validate(&peer_a, &catch_up_data(3, 1));
+		match action {
+			Action::Discard(cost) => assert_eq!(cost, cost::MALFORMED_CATCH_UP),
+			_ => panic!("expected discard"),
+		}
+
+		// peer a answers properly.
+		let (action, _, _) = val.do_validate(&peer_a, &catch_up_data(3, 0));
+		match action {
+			Action::ProcessAndDiscard(topic, benefit) => {
+				assert_eq!(topic, crate::communication::global_topic::<Block>(0));
+				assert_eq!(benefit, benefit::BASIC_VALIDATED_CATCH_UP);
+			},
+			_ => panic!("expected process and discard"),
+		}
+	}
 }
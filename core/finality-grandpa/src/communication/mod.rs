@@ -38,14 +38,16 @@ use parity_codec::{Encode, Decode};
 use substrate_primitives::{ed25519, Pair};
 use substrate_telemetry::{telemetry, CONSENSUS_DEBUG, CONSENSUS_INFO};
 use runtime_primitives::ConsensusEngineId;
-use runtime_primitives::traits::{Block as BlockT, Hash as HashT, Header as HeaderT};
+use runtime_primitives::traits::{Block as BlockT, Hash as HashT, Header as HeaderT, NumberFor};
 use network::{consensus_gossip as network_gossip, Service as NetworkService};
 use network_gossip::ConsensusMessage;
 
-use crate::{Error, Message, SignedMessage, Commit, CompactCommit};
+use fg_primitives::localized_payload;
+
+use crate::{CatchUp, Error, Message, SignedMessage, Commit, CompactCommit};
 use crate::environment::HasVoted;
 use gossip::{
-	GossipMessage, FullCommitMessage, VoteOrPrecommitMessage, GossipValidator
+	GossipMessage, FullCatchUpMessage, FullCommitMessage, VoteOrPrecommitMessage, GossipValidator
 };
 use substrate_primitives::ed25519::{Public as AuthorityId, Signature as AuthoritySignature};
 
@@ -70,6 +72,10 @@ mod cost {
 	pub(super) const PER_SIGNATURE_CHECKED: i32 = -25;
 	pub(super) const PER_BLOCK_LOADED: i32 = -10;
 	pub(super) const INVALID_COMMIT: i32 = -5000;
+	pub(super) const MALFORMED_CATCH_UP: i32 = -1000;
+	pub(super) const INVALID_CATCH_UP: i32 = -5000;
+	pub(super) const UNSOLICITED_CATCH_UP: i32 = -500;
+	pub(super) const CATCH_UP_REQUEST_TOO_FREQUENT: i32 = -500;
 }
 
 // benefit scalars for reporting peers.
@@ -77,6 +83,7 @@ mod benefit {
 	pub(super) const ROUND_MESSAGE: i32 = 100;
 	pub(super) const BASIC_VALIDATED_COMMIT: i32 = 100;
 	pub(super) const PER_EQUIVOCATION: i32 = 10;
+	pub(super) const BASIC_VALIDATED_CATCH_UP: i32 = 200;
 }
 
 /// A handle to the network. This is generally implemented by providing some
@@ -202,6 +209,13 @@ pub(crate) enum CommitProcessingOutcome {
 	Bad,
 }
 
+/// The result of processing a catch-up.
+pub(crate) enum CatchUpProcessingOutcome {
+	Good,
+	Bad,
+	Useless,
+}
+
 /// Bridge between the underlying network service, gossiping consensus messages and Grandpa
 pub(crate) struct NetworkBridge<B: BlockT, N: Network<B>> {
 	service: N,
@@ -241,6 +255,46 @@ impl<B: BlockT, N: Network<B>> NetworkBridge<B, N> {
 		(bridge, startup_work)
 	}
 
+	/// Note that the voters of the given set have moved on to the given round,
+	/// without taking part in it. This keeps their round messages flowing
+	/// through the gossip validator.
+	pub(crate) fn note_round(&self, round: Round, set_id: SetId) {
+		self.validator.note_round(
+			round,
+			set_id,
+			|to, neighbor| self.service.send_message(
+				to,
+				GossipMessage::<B>::from(neighbor).encode()
+			),
+		);
+	}
+
+	/// Note that we've completed a round in the given set with the given votes.
+	/// The prevotes and precommits are kept to answer catch-up requests of
+	/// lagging peers.
+	pub(crate) fn note_completed_round(
+		&self,
+		round: Round,
+		set_id: SetId,
+		base: (B::Hash, NumberFor<B>),
+		votes: &[SignedMessage<B>],
+	) {
+		let votes = votes.iter()
+			.filter(|signed| match signed.message {
+				Prevote(_) | Precommit(_) => true,
+				PrimaryPropose(_) => false,
+			})
+			.cloned()
+			.collect();
+
+		self.validator.note_completed_round(set_id, CatchUp {
+			round_number: round.0,
+			base_hash: base.0,
+			base_number: base.1,
+			votes,
+		});
+	}
+
 	/// Get the round messages for a round in a given set ID. These are signature-checked.
 	pub(crate) fn round_communication(
 		&self,
@@ -253,14 +307,7 @@ impl<B: BlockT, N: Network<B>> NetworkBridge<B, N> {
 		impl Stream<Item=SignedMessage<B>,Error=Error>,
 		impl Sink<SinkItem=Message<B>,SinkError=Error>,
 	) {
-		self.validator.note_round(
-			round,
-			set_id,
-			|to, neighbor| self.service.send_message(
-				to,
-				GossipMessage::<B>::from(neighbor).encode()
-			),
-		);
+		self.note_round(round, set_id);
 
 		let locals = local_key.and_then(|pair| {
 			let public = pair.public();
@@ -372,6 +419,44 @@ impl<B: BlockT, N: Network<B>> NetworkBridge<B, N> {
 
 		(incoming, outgoing)
 	}
+
+	/// Get a stream of the catch-up messages we've requested for the given set.
+	/// Their signatures are checked, but the caller must check that they prove
+	/// a completed round and report the outcome through the given callback.
+	pub(crate) fn catch_up_communication(
+		&self,
+		set_id: SetId,
+	) -> impl Stream<Item = (CatchUp<B>, impl FnMut(CatchUpProcessingOutcome)), Error = Error> {
+		let service = self.service.clone();
+		let topic = global_topic::<B>(set_id.0);
+
+		self.service.messages_for(topic)
+			.filter_map(|notification| {
+				let decoded = GossipMessage::<B>::decode(&mut &notification.message[..]);
+				if decoded.is_none() {
+					trace!(target: "afg", "Skipping malformed catch-up message {:?}", notification);
+				}
+				decoded.map(move |d| (notification, d))
+			})
+			.filter_map(move |(mut notification, msg)| match msg {
+				GossipMessage::CatchUp(FullCatchUpMessage { message, .. }) => {
+					let service = service.clone();
+					let cb = move |outcome| match outcome {
+						CatchUpProcessingOutcome::Bad => {
+							if let Some(who) = notification.sender.take() {
+								service.report(who, cost::INVALID_CATCH_UP);
+							}
+						}
+						CatchUpProcessingOutcome::Good | CatchUpProcessingOutcome::Useless => {},
+					};
+
+					Some((message, cb))
+				},
+				// other global messages are handled by the commit stream.
+				_ => None,
+			})
+			.map_err(|()| Error::Network(format!("Failed to receive message on unbounded stream")))
+	}
 }
 
 fn incoming_global<B: BlockT, N: Network<B>>(
@@ -464,10 +549,6 @@ impl<B: BlockT, N: Network<B>> Clone for NetworkBridge<B, N> {
 	}
 }
 
-fn localized_payload<E: Encode>(round: u64, set_id: u64, message: &E) -> Vec<u8> {
-	(message, round, set_id).encode()
-}
-
 /// Type-safe wrapper around u64 when indicating that it's a round number.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Encode, Decode)]
 pub struct Round(pub u64);
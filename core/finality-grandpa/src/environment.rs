@@ -38,6 +38,7 @@ use runtime_primitives::traits::{
 };
 use substrate_primitives::{Blake2Hasher, ed25519, H256, Pair};
 use substrate_telemetry::{telemetry, CONSENSUS_INFO};
+use fg_primitives::{EquivocationReport, Vote as ReportedVote};
 
 use crate::{
 	CommandOrError, Commit, Config, Error, Network, Precommit, Prevote,
@@ -274,9 +275,33 @@ pub(crate) struct Environment<B, E, Block: BlockT, N: Network<Block>, RA, SC> {
 	pub(crate) network: crate::communication::NetworkBridge<Block, N>,
 	pub(crate) set_id: u64,
 	pub(crate) voter_set_state: SharedVoterSetState<Block>,
+	pub(crate) equivocation_reports: srml_grandpa::EquivocationReports,
 }
 
 impl<B, E, Block: BlockT, N: Network<Block>, RA, SC> Environment<B, E, Block, N, RA, SC> {
+	/// Queue a report of the given equivocation, to be included in the next
+	/// block authored by this node.
+	fn report_equivocation<V, F>(
+		&self,
+		equivocation: Equivocation<AuthorityId, V, ed25519::Signature>,
+		to_vote: F,
+	) where F: Fn(V) -> ReportedVote<Block::Hash, NumberFor<Block>> {
+		let Equivocation { round_number, identity, first, second } = equivocation;
+
+		telemetry!(CONSENSUS_INFO; "afg.equivocation_reported";
+			"set_id" => ?self.set_id, "round" => ?round_number, "offender" => ?identity,
+		);
+
+		let report = EquivocationReport {
+			set_id: self.set_id,
+			round: round_number,
+			offender: identity,
+			first: (to_vote(first.0), first.1),
+			second: (to_vote(second.0), second.1),
+		};
+		self.equivocation_reports.push(report.encode());
+	}
+
 	/// Updates the voter set state using the given closure. The write lock is
 	/// held during evaluation of the closure and the environment's voter set
 	/// state is set to its result if successful.
@@ -621,6 +646,14 @@ where
 			state.finalized.as_ref().map(|e| e.1),
 		);
 
+		// keep the votes around to help lagging peers catch up.
+		self.network.note_completed_round(
+			crate::communication::Round(round),
+			crate::communication::SetId(self.set_id),
+			base,
+			&votes,
+		);
+
 		self.update_voter_set_state(|voter_set_state| {
 			let mut completed_rounds = voter_set_state.completed_rounds();
 
@@ -692,7 +725,7 @@ where
 		equivocation: ::grandpa::Equivocation<Self::Id, Prevote<Block>, Self::Signature>
 	) {
 		warn!(target: "afg", "Detected prevote equivocation in the finality worker: {:?}", equivocation);
-		// nothing yet; this could craft misbehavior reports of some kind.
+		self.report_equivocation(equivocation, |prevote| ReportedVote::Prevote(prevote.target_hash, prevote.target_number));
 	}
 
 	fn precommit_equivocation(
@@ -701,7 +734,7 @@ where
 		equivocation: Equivocation<Self::Id, Precommit<Block>, Self::Signature>
 	) {
 		warn!(target: "afg", "Detected precommit equivocation in the finality worker: {:?}", equivocation);
-		// nothing yet
+		self.report_equivocation(equivocation, |precommit| ReportedVote::Precommit(precommit.target_hash, precommit.target_number));
 	}
 }
 
@@ -874,6 +907,24 @@ pub(crate) fn finalize_block<B, Block: BlockT<Hash=H256>, E, RA>(
 			}
 		}
 
+		if let Some(ref new_authorities) = new_authorities {
+			// the finalized block has a justification of the previous set, which proves the
+			// change to nodes that don't know the new set yet.
+			let write_result = crate::aux_schema::note_authority_set_change(
+				client,
+				new_authorities.set_id,
+				(hash, number),
+				|insert| client.apply_aux(import_op, insert, &[]),
+			).and_then(|result| result);
+
+			if let Err(e) = write_result {
+				warn!(target: "finality", "Failed to write authority set change to disk. Bailing.");
+				warn!(target: "finality", "Node is in a potentially inconsistent state.");
+
+				return Err(e.into());
+			}
+		}
+
 		Ok(new_authorities.map(VoterCommand::ChangeAuthorities))
 	});
 
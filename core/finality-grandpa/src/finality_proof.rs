@@ -28,24 +28,37 @@
 //!
 //! The caller should track the `set_id`. The most straightforward way is to fetch finality
 //! proofs ONLY for blocks on the tip of the chain and track the latest known `set_id`.
+//!
+//! The warp sync can't track the `set_id`, since it skips the blocks in between. Its proofs
+//! (`FinalityProofProvider`) additionally carry the proofs of all standard authorities set
+//! changes up to the proven block: the justification of every block that has enacted a change,
+//! signed by the previous set, and the proof of the new set at that block. The checker walks
+//! them from the set it knows to the set that has finalized the proven block.
+
+use std::sync::Arc;
 
 use grandpa::voter_set::VoterSet;
 
 use client::{
+	Client, CallExecutor,
+	backend::Backend,
 	blockchain::Backend as BlockchainBackend,
 	error::{Error as ClientError, Result as ClientResult},
-	light::fetcher::RemoteCallRequest,
+	light::{call_executor::check_execution_proof, fetcher::RemoteCallRequest},
 };
 use parity_codec::{Encode, Decode};
 use grandpa::BlockNumberOps;
+use runtime_primitives::Justification;
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::traits::{
-	NumberFor, Block as BlockT, Header as HeaderT, One,
+	NumberFor, Block as BlockT, Header as HeaderT, One, Zero,
 };
-use substrate_primitives::{ed25519, H256};
+use state_machine::CodeExecutor;
+use substrate_primitives::{ed25519, H256, Blake2Hasher};
 use ed25519::Public as AuthorityId;
 use substrate_telemetry::{telemetry, CONSENSUS_INFO};
 
+use crate::aux_schema;
 use crate::justification::GrandpaJustification;
 
 /// Prepare proof-of-finality for the given block.
@@ -62,7 +75,7 @@ pub fn prove_finality<Block: BlockT, B, G>(
 		G: Fn(&BlockId<Block>, &str, &[u8]) -> ClientResult<Vec<Vec<u8>>>,
 {
 	let block_id = BlockId::Hash(block);
-	let mut block_number = blockchain.expect_block_number_from_id(&block_id)?;
+	let block_number = blockchain.expect_block_number_from_id(&block_id)?;
 
 	// early-return if we sure that the block isn't finalized yet
 	let info = blockchain.info()?;
@@ -84,7 +97,7 @@ pub fn prove_finality<Block: BlockT, B, G>(
 	// BUT since `GrandpaApi::grandpa_authorities` call returns the set that becames actual
 	// at the next block, the proof-of execution is generated using parent block' state
 	// (this will fail if we're trying to prove genesis finality, but such the call itself is redundant)
-	let mut current_header = blockchain.expect_header(BlockId::Hash(block))?;
+	let current_header = blockchain.expect_header(BlockId::Hash(block))?;
 	let parent_block_id = BlockId::Hash(*current_header.parent_hash());
 	let authorities_proof = generate_execution_proof(
 		&parent_block_id,
@@ -93,17 +106,38 @@ pub fn prove_finality<Block: BlockT, B, G>(
 	)?;
 
 	// search for earliest post-block (inclusive) justification
+	let (finalization_path, justification) = finalization_path(
+		blockchain,
+		current_header,
+		info.finalized_number,
+	)?;
+	Ok(Some(FinalityProof {
+		finalization_path,
+		justification,
+		authorities_proof,
+	}.encode()))
+}
+
+/// Search for the earliest justification of the given finalized block or of its descendants.
+///
+/// Returns the headers from the block (inclusive) to the justified block (inclusive) and the
+/// justification.
+fn finalization_path<Block: BlockT, B>(
+	blockchain: &B,
+	mut current_header: Block::Header,
+	finalized_number: NumberFor<Block>,
+) -> ClientResult<(Vec<Block::Header>, Justification)>
+	where
+		B: BlockchainBackend<Block>,
+{
+	let mut block_number = *current_header.number();
 	let mut finalization_path = Vec::new();
 	loop {
 		finalization_path.push(current_header);
 
 		match blockchain.justification(BlockId::Number(block_number))? {
-			Some(justification) => return Ok(Some(FinalityProof {
-				finalization_path,
-				justification,
-				authorities_proof,
-			}.encode())),
-			None if block_number == info.finalized_number => break,
+			Some(justification) => return Ok((finalization_path, justification)),
+			None if block_number == finalized_number => break,
 			None => {
 				block_number = block_number + One::one();
 				current_header = blockchain.expect_header(BlockId::Number(block_number))?;
@@ -157,10 +191,173 @@ fn do_check_finality_proof<Block: BlockT<Hash=H256>, C, J>(
 	// decode finality proof
 	let proof = FinalityProof::<Block::Header, J>::decode(&mut &remote_proof[..])
 		.ok_or_else(|| ClientError::BadJustification("failed to decode finality proof".into()))?;
+	let just_block = proof.justification.target_block();
+	check_finalization_path(&proof.finalization_path, block, just_block)?;
+
+	// check authorities set proof && get grandpa authorities that should have signed justification
+	let grandpa_authorities = check_execution_proof(&RemoteCallRequest {
+		block: just_block.1,
+		header: parent_header,
+		method: "GrandpaApi_grandpa_authorities".into(),
+		call_data: vec![],
+		retry_count: None,
+	})?;
+	let grandpa_authorities: Vec<(AuthorityId, u64)> = Decode::decode(&mut &grandpa_authorities[..])
+		.ok_or_else(|| ClientError::BadJustification("failed to decode GRANDPA authorities set proof".into()))?;
 
+	// and now check justification
+	proof.justification.verify(set_id, &grandpa_authorities.into_iter().collect())?;
+
+	telemetry!(CONSENSUS_INFO; "afg.finality_proof_ok";
+		"set_id" => ?set_id, "finalized_header_hash" => ?block.1);
+	Ok(proof.finalization_path)
+}
+
+/// Prepare the warp sync proof of finality of the given block.
+///
+/// `set_changes` are the finalized blocks which have enacted standard changes of the
+/// authorities set, along with the ids of the enacted sets, ordered by the set id.
+fn prove_warp_finality<Block: BlockT, B, G>(
+	blockchain: &B,
+	generate_execution_proof: G,
+	set_changes: Vec<(u64, Block::Hash, NumberFor<Block>)>,
+	block: Block::Hash,
+) -> ClientResult<Option<Vec<u8>>>
+	where
+		B: BlockchainBackend<Block>,
+		G: Fn(&BlockId<Block>, &str, &[u8]) -> ClientResult<Vec<Vec<u8>>>,
+{
+	let header = blockchain.expect_header(BlockId::Hash(block))?;
+	let info = blockchain.info()?;
+	if info.finalized_number < *header.number() {
+		return Ok(None);
+	}
+
+	let canonical_block = blockchain.expect_block_hash_from_id(&BlockId::Number(*header.number()))?;
+	if block != canonical_block {
+		return Err(ClientError::Backend(
+			"Cannot generate finality proof for non-canonical block".into()
+		).into());
+	}
+
+	// the sets which have been enacted before the block are proven, starting with the first
+	// change from the genesis set
+	let mut set_changes_proof = Vec::new();
+	for (index, (set_id, hash, number)) in set_changes.into_iter().enumerate() {
+		if number >= *header.number() {
+			break;
+		}
+		if set_id != index as u64 + 1 {
+			return Err(ClientError::Backend(
+				format!("cannot prove the change to the authorities set {}", index + 1)
+			).into());
+		}
+
+		let justification = blockchain.justification(BlockId::Hash(hash))?
+			.ok_or_else(|| ClientError::Backend(
+				format!("missing justification of the change to the authorities set {}", set_id)
+			))?;
+		set_changes_proof.push(AuthoritySetChangeProof {
+			header: blockchain.expect_header(BlockId::Hash(hash))?,
+			justification,
+			// the new set is returned by the runtime from the state of the block enacting it
+			authorities_proof: generate_execution_proof(
+				&BlockId::Hash(hash),
+				"GrandpaApi_grandpa_authorities",
+				&[],
+			)?,
+		});
+	}
+
+	let (finalization_path, justification) = finalization_path(
+		blockchain,
+		header,
+		info.finalized_number,
+	)?;
+
+	Ok(Some(WarpFinalityProof {
+		set_changes: set_changes_proof,
+		finalization_path,
+		justification,
+	}.encode()))
+}
+
+/// Check the warp sync proof of finality of the given block.
+///
+/// `set_id` and `authorities` are the authorities set known to the caller. The proofs of the
+/// changes to the later sets are checked one by one, and the block must be finalized by the
+/// last set.
+fn do_check_warp_finality_proof<Block: BlockT<Hash=H256>, C, J>(
+	check_execution_proof: C,
+	block: (NumberFor<Block>, Block::Hash),
+	mut set_id: u64,
+	mut authorities: VoterSet<AuthorityId>,
+	remote_proof: Vec<u8>,
+) -> ClientResult<()>
+	where
+		C: Fn(&RemoteCallRequest<Block::Header>, Vec<Vec<u8>>) -> ClientResult<Vec<u8>>,
+		J: ProvableJustification<Block::Header>,
+{
+	let proof = WarpFinalityProof::<Block::Header>::decode(&mut &remote_proof[..])
+		.ok_or_else(|| ClientError::BadJustification("failed to decode finality proof".into()))?;
+
+	// the proof starts with the change to the set 1, the changes to the sets that are known to
+	// the caller are skipped
+	for change in proof.set_changes.into_iter().skip(set_id as usize) {
+		let justification = decode_justification::<Block::Header, J>(&change.justification)?;
+		let just_block = justification.target_block();
+		if *change.header.number() != just_block.0 || change.header.hash() != just_block.1 {
+			return Err(ClientError::BadJustification(
+				"finality proof: authorities set change is not justified".into()
+			).into());
+		}
+		if just_block.0 >= block.0 {
+			return Err(ClientError::BadJustification(
+				"finality proof: authorities set change is not before the block".into()
+			).into());
+		}
+		justification.verify(set_id, &authorities)?;
+
+		let new_authorities = check_execution_proof(&RemoteCallRequest {
+			block: just_block.1,
+			header: change.header,
+			method: "GrandpaApi_grandpa_authorities".into(),
+			call_data: vec![],
+			retry_count: None,
+		}, change.authorities_proof)?;
+		let new_authorities: Vec<(AuthorityId, u64)> = Decode::decode(&mut &new_authorities[..])
+			.ok_or_else(|| ClientError::BadJustification("failed to decode GRANDPA authorities set proof".into()))?;
+
+		set_id += 1;
+		authorities = new_authorities.into_iter().collect();
+	}
+
+	let justification = decode_justification::<Block::Header, J>(&proof.justification)?;
+	check_finalization_path(&proof.finalization_path, block, justification.target_block())?;
+	justification.verify(set_id, &authorities)?;
+
+	telemetry!(CONSENSUS_INFO; "afg.finality_proof_ok";
+		"set_id" => ?set_id, "finalized_header_hash" => ?block.1);
+	Ok(())
+}
+
+fn decode_justification<Header: HeaderT, J: ProvableJustification<Header>>(
+	justification: &[u8],
+) -> ClientResult<J> {
+	J::decode(&mut &justification[..])
+		.ok_or_else(|| ClientError::BadJustification("failed to decode justification".into()).into())
+}
+
+/// Check that the finalization path starts with the given block, ends with the justification
+/// target block and that its headers form a chain.
+fn check_finalization_path<Header: HeaderT>(
+	finalization_path: &[Header],
+	block: (Header::Number, Header::Hash),
+	just_block: (Header::Number, Header::Hash),
+) -> ClientResult<()> {
 	// check that the first header in finalization path is the block itself
 	{
-		let finalized_header = proof.finalization_path.first()
+		let finalized_header = finalization_path.first()
 			.ok_or_else(|| ClientError::from(ClientError::BadJustification(
 				"finality proof: finalized path is empty".into()
 			)))?;
@@ -172,10 +369,9 @@ fn do_check_finality_proof<Block: BlockT<Hash=H256>, C, J>(
 	}
 
 	// check that the last header in finalization path is the justification target block
-	let just_block = proof.justification.target_block();
 	{
-		let finalized_header = proof.finalization_path.last()
-			.expect("checked above that proof.finalization_path is not empty; qed");
+		let finalized_header = finalization_path.last()
+			.expect("checked above that finalization_path is not empty; qed");
 		if *finalized_header.number() != just_block.0 || finalized_header.hash() != just_block.1 {
 			return Err(ClientError::BadJustification(
 				"finality proof: target justification block is not a part of finalized path".into()
@@ -183,23 +379,88 @@ fn do_check_finality_proof<Block: BlockT<Hash=H256>, C, J>(
 		}
 	}
 
-	// check authorities set proof && get grandpa authorities that should have signed justification
-	let grandpa_authorities = check_execution_proof(&RemoteCallRequest {
-		block: just_block.1,
-		header: parent_header,
-		method: "GrandpaApi_grandpa_authorities".into(),
-		call_data: vec![],
-		retry_count: None,
-	})?;
-	let grandpa_authorities: Vec<(AuthorityId, u64)> = Decode::decode(&mut &grandpa_authorities[..])
-		.ok_or_else(|| ClientError::BadJustification("failed to decode GRANDPA authorities set proof".into()))?;
+	// check that headers of the finalization path are linked
+	for headers in finalization_path.windows(2) {
+		if *headers[1].parent_hash() != headers[0].hash() {
+			return Err(ClientError::BadJustification(
+				"finality proof: finalized path is not a chain".into()
+			).into());
+		}
+	}
 
-	// and now check justification
-	proof.justification.verify(set_id, &grandpa_authorities.into_iter().collect())?;
+	Ok(())
+}
 
-	telemetry!(CONSENSUS_INFO; "afg.finality_proof_ok";
-		"set_id" => ?set_id, "finalized_header_hash" => ?block.1);
-	Ok(proof.finalization_path)
+/// Provides and checks GRANDPA finality proofs for the warp sync of the network.
+///
+/// Proofs are checked starting with the authorities set stored in the local database, or with
+/// the genesis set if the GRANDPA voter has never been started on it. Changes of the set can
+/// only be proven by nodes which have finalized the blocks enacting them, and forced changes
+/// can't be proven at all.
+pub struct FinalityProofProvider<B, E, Block: BlockT<Hash=H256>, RA> {
+	client: Arc<Client<B, E, Block, RA>>,
+	check_execution_proof: Box<
+		dyn Fn(&RemoteCallRequest<Block::Header>, Vec<Vec<u8>>) -> ClientResult<Vec<u8>> + Send + Sync
+	>,
+}
+
+impl<B, E, Block: BlockT<Hash=H256>, RA> FinalityProofProvider<B, E, Block, RA> {
+	/// Create new finality proof provider using the given client. The `executor` checks the
+	/// proofs of the authorities sets.
+	pub fn new<Exec>(client: Arc<Client<B, E, Block, RA>>, executor: Exec) -> Self
+		where Exec: CodeExecutor<Blake2Hasher> + Send + Sync + 'static,
+	{
+		FinalityProofProvider {
+			client,
+			check_execution_proof: Box::new(move |request, proof|
+				check_execution_proof::<_, _, Blake2Hasher>(&executor, request, proof)
+			),
+		}
+	}
+}
+
+impl<B, E, Block, RA> network::FinalityProofProvider<Block> for FinalityProofProvider<B, E, Block, RA>
+	where
+		B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+		E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+		Block: BlockT<Hash=H256>,
+		RA: Send + Sync,
+		NumberFor<Block>: BlockNumberOps,
+{
+	fn prove_finality(&self, block: Block::Hash) -> ClientResult<Option<Vec<u8>>> {
+		prove_warp_finality(
+			&*self.client.backend().blockchain(),
+			|block, method, call_data| self.client.execution_proof(block, method, call_data)
+				.map(|(_, proof)| proof),
+			aux_schema::load_authority_set_changes(&*self.client)?,
+			block,
+		)
+	}
+
+	fn check_finality_proof(&self, header: &Block::Header, proof: Vec<u8>) -> ClientResult<()> {
+		let authority_set = aux_schema::load_authority_set::<_, Block::Hash, NumberFor<Block>>(&*self.client)?;
+		let (set_id, authorities) = match authority_set {
+			Some(authority_set) => {
+				let (set_id, authorities) = authority_set.current();
+				(set_id, authorities.to_vec())
+			},
+			None => {
+				let authorities = self.client.call_session(&BlockId::Number(Zero::zero()))?
+					.call("GrandpaApi_grandpa_authorities", &[])?;
+				let authorities: Vec<(AuthorityId, u64)> = Decode::decode(&mut &authorities[..])
+					.ok_or_else(|| ClientError::Backend("failed to decode genesis GRANDPA authorities".into()))?;
+				(0, authorities)
+			},
+		};
+
+		do_check_warp_finality_proof::<Block, _, GrandpaJustification<Block>>(
+			&*self.check_execution_proof,
+			(*header.number(), header.hash()),
+			set_id,
+			authorities.into_iter().collect(),
+			proof,
+		)
+	}
 }
 
 /// Proof of finality.
@@ -220,6 +481,35 @@ struct FinalityProof<Header, Justification> {
 	pub authorities_proof: Vec<Vec<u8>>,
 }
 
+/// Proof of finality for the warp sync.
+///
+/// Finality of block B is proved by providing:
+/// 1) proofs of the authorities set changes enacted before the block B;
+/// 2) valid headers sub-chain from the block B to the block F;
+/// 3) valid (with respect to the last proven authorities set) GRANDPA justification of the block F.
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct WarpFinalityProof<Header> {
+	/// Proofs of the authorities set changes, ordered by the set id, starting with the change
+	/// from the genesis set.
+	pub set_changes: Vec<AuthoritySetChangeProof<Header>>,
+	/// Headers-path (ordered by block number, ascending) from the block we're gathering proof for
+	/// (inclusive) to the target block of the justification (inclusive).
+	pub finalization_path: Vec<Header>,
+	/// Justification (finalization) of the last block from the `finalization_path`.
+	pub justification: Justification,
+}
+
+/// Proof of a standard change of the authorities set.
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct AuthoritySetChangeProof<Header> {
+	/// Header of the block which has enacted the change.
+	pub header: Header,
+	/// Justification of the block by the previous authorities set.
+	pub justification: Justification,
+	/// Proof of `GrandpaApi::grandpa_authorities` call execution at the block.
+	pub authorities_proof: Vec<Vec<u8>>,
+}
+
 /// Justification used to prove block finality.
 trait ProvableJustification<Header: HeaderT>: Encode + Decode {
 	/// Get target block of this justification.
@@ -247,6 +537,7 @@ mod tests {
 	use test_client::runtime::{Block, Header};
 	use test_client::client::backend::NewBlockState;
 	use test_client::client::in_mem::Blockchain as InMemoryBlockchain;
+	use test_client::client::blockchain::HeaderBackend;
 	use super::*;
 
 	type FinalityProof = super::FinalityProof<Header, Vec<u8>>;
@@ -413,6 +704,119 @@ mod tests {
 		).is_err(), true);
 	}
 
+	/// Justification of the block with the given number by the given set.
+	#[derive(Encode, Decode)]
+	struct SetJustification(u64, u64);
+
+	impl ProvableJustification<Header> for SetJustification {
+		fn target_block(&self) -> (u64, H256) { (self.0, header(self.0).hash()) }
+
+		fn verify(&self, set_id: u64, authorities: &VoterSet<AuthorityId>) -> ClientResult<()> {
+			if set_id == self.1 && authorities == &set_authorities(set_id) {
+				Ok(())
+			} else {
+				Err(ClientError::BadJustification("signed by another set".into()))
+			}
+		}
+	}
+
+	fn set_authorities_list(set_id: u64) -> Vec<(AuthorityId, u64)> {
+		vec![(AuthorityId([set_id as u8 + 1; 32]), 1u64)]
+	}
+
+	fn set_authorities(set_id: u64) -> VoterSet<AuthorityId> {
+		set_authorities_list(set_id).into_iter().collect()
+	}
+
+	/// Blockchain with the set 1 enacted at block 2 and the set 2 enacted at block 4.
+	fn warp_test_blockchain() -> (InMemoryBlockchain<Block>, Vec<(u64, H256, u64)>) {
+		let blockchain = InMemoryBlockchain::<Block>::new();
+		blockchain.insert(header(0).hash(), header(0), None, None, NewBlockState::Final).unwrap();
+		blockchain.insert(header(1).hash(), header(1), None, None, NewBlockState::Final).unwrap();
+		blockchain.insert(header(2).hash(), header(2), Some(SetJustification(2, 0).encode()), None, NewBlockState::Final).unwrap();
+		blockchain.insert(header(3).hash(), header(3), None, None, NewBlockState::Final).unwrap();
+		blockchain.insert(header(4).hash(), header(4), Some(SetJustification(4, 1).encode()), None, NewBlockState::Final).unwrap();
+		blockchain.insert(header(5).hash(), header(5), None, None, NewBlockState::Final).unwrap();
+		blockchain.insert(header(6).hash(), header(6), Some(SetJustification(6, 2).encode()), None, NewBlockState::Final).unwrap();
+		(blockchain, vec![(1, header(2).hash(), 2), (2, header(4).hash(), 4)])
+	}
+
+	/// Proves the set enacted at the block by returning the block number as the proof.
+	fn prove_warp_test_finality(block: u64) -> Vec<u8> {
+		let (blockchain, set_changes) = warp_test_blockchain();
+		prove_warp_finality(
+			&blockchain,
+			|block, _, _| Ok(vec![vec![blockchain.expect_block_number_from_id(block).unwrap() as u8]]),
+			set_changes,
+			header(block).hash(),
+		).unwrap().unwrap()
+	}
+
+	fn check_warp_test_finality(block: u64, set_id: u64, proof: Vec<u8>) -> ClientResult<()> {
+		do_check_warp_finality_proof::<Block, _, SetJustification>(
+			|request, proof| {
+				assert_eq!(proof, vec![vec![*request.header.number() as u8]]);
+				let set_id = *request.header.number() / 2;
+				Ok(set_authorities_list(set_id).encode())
+			},
+			(block, header(block).hash()),
+			set_id,
+			set_authorities(set_id),
+			proof,
+		)
+	}
+
+	#[test]
+	fn warp_finality_proof_walks_set_changes() {
+		// block 5 is finalized by the set 2, after the changes at blocks 2 and 4
+		let proof_of_5 = prove_warp_test_finality(5);
+		let decoded: WarpFinalityProof<Header> = Decode::decode(&mut &proof_of_5[..]).unwrap();
+		assert_eq!(decoded.set_changes.iter().map(|change| change.header.clone()).collect::<Vec<_>>(),
+			vec![header(2), header(4)]);
+		assert_eq!(decoded.finalization_path, vec![header(5), header(6)]);
+
+		assert!(check_warp_test_finality(5, 0, proof_of_5.clone()).is_ok());
+		assert!(check_warp_test_finality(5, 1, proof_of_5.clone()).is_ok());
+		assert!(check_warp_test_finality(5, 2, proof_of_5.clone()).is_ok());
+
+		// proof of block 5 doesn't prove finality of block 3
+		assert!(check_warp_test_finality(3, 0, proof_of_5).is_err());
+	}
+
+	#[test]
+	fn warp_finality_proof_of_set_change_block_is_checked_with_previous_set() {
+		// block 4 enacts the set 2, but it is finalized by the set 1
+		let proof_of_4 = prove_warp_test_finality(4);
+		let decoded: WarpFinalityProof<Header> = Decode::decode(&mut &proof_of_4[..]).unwrap();
+		assert_eq!(decoded.set_changes.len(), 1);
+
+		assert!(check_warp_test_finality(4, 0, proof_of_4.clone()).is_ok());
+		assert!(check_warp_test_finality(4, 1, proof_of_4).is_ok());
+	}
+
+	#[test]
+	fn warp_finality_proof_check_fails_without_set_changes() {
+		let mut proof_of_5: WarpFinalityProof<Header> = Decode::decode(&mut &prove_warp_test_finality(5)[..]).unwrap();
+		proof_of_5.set_changes.pop();
+
+		// block 6 is justified by the set 2, which isn't proven anymore
+		assert!(check_warp_test_finality(5, 0, proof_of_5.encode()).is_err());
+	}
+
+	#[test]
+	fn warp_finality_proof_fails_on_set_changes_gap() {
+		let (blockchain, mut set_changes) = warp_test_blockchain();
+		set_changes.remove(0);
+
+		// the change to the set 1 is unknown, e.g. it was forced
+		assert!(prove_warp_finality(
+			&blockchain,
+			|_, _, _| Ok(vec![vec![42]]),
+			set_changes,
+			header(5).hash(),
+		).is_err());
+	}
+
 	#[test]
 	fn finality_proof_check_works() {
 		let proof_of_2 = prove_finality(&test_blockchain(), |_, _, _| Ok(vec![vec![42]]), header(2).hash())
@@ -62,7 +62,7 @@ use client::{
 	error::Error as ClientError,
 };
 use client::blockchain::HeaderBackend;
-use parity_codec::Encode;
+use parity_codec::{Encode, Decode};
 use runtime_primitives::traits::{
 	NumberFor, Block as BlockT, DigestFor, ProvideRuntimeApi,
 };
@@ -101,7 +101,7 @@ mod service_integration;
 #[cfg(feature="service-integration")]
 pub use service_integration::{LinkHalfForService, BlockImportForService};
 pub use communication::Network;
-pub use finality_proof::{prove_finality, check_finality_proof};
+pub use finality_proof::{prove_finality, check_finality_proof, FinalityProofProvider};
 pub use observer::run_grandpa_observer;
 
 use aux_schema::PersistentData;
@@ -147,6 +147,20 @@ pub type CompactCommit<Block> = grandpa::CompactCommit<
 	AuthorityId
 >;
 
+/// A catch-up message for this chain's block type, carrying the prevotes and
+/// precommits of a round which was completed.
+#[derive(Debug, Clone, Encode, Decode)]
+pub(crate) struct CatchUp<Block: BlockT> {
+	/// The number of the completed round.
+	pub(crate) round_number: u64,
+	/// The base block hash of the completed round.
+	pub(crate) base_hash: Block::Hash,
+	/// The base block number of the completed round.
+	pub(crate) base_number: NumberFor<Block>,
+	/// The prevotes and precommits observed in the round.
+	pub(crate) votes: Vec<SignedMessage<Block>>,
+}
+
 /// Configuration for the GRANDPA service.
 #[derive(Clone)]
 pub struct Config {
@@ -226,13 +240,24 @@ pub(crate) struct NewAuthoritySet<H, N> {
 	pub(crate) authorities: Vec<(AuthorityId, u64)>,
 }
 
+/// A round of the current set which was completed by the other voters,
+/// as proven by a validated catch-up message.
+#[derive(Debug)]
+pub(crate) struct CaughtUpRound<H, N> {
+	pub(crate) number: u64,
+	pub(crate) state: RoundState<H, N>,
+	pub(crate) base: (H, N),
+}
+
 /// Commands issued to the voter.
 #[derive(Debug)]
 pub(crate) enum VoterCommand<H, N> {
 	/// Pause the voter for given reason.
 	Pause(String),
 	/// New authorities.
-	ChangeAuthorities(NewAuthoritySet<H, N>)
+	ChangeAuthorities(NewAuthoritySet<H, N>),
+	/// Skip ahead to the round following a completed round.
+	CatchUp(CaughtUpRound<H, N>),
 }
 
 impl<H, N> fmt::Display for VoterCommand<H, N> {
@@ -240,6 +265,7 @@ impl<H, N> fmt::Display for VoterCommand<H, N> {
 		match *self {
 			VoterCommand::Pause(ref reason) => write!(f, "Pausing voter: {}", reason),
 			VoterCommand::ChangeAuthorities(_) => write!(f, "Changing authorities"),
+			VoterCommand::CatchUp(ref round) => write!(f, "Catching up to round {}", round.number),
 		}
 	}
 }
@@ -406,6 +432,39 @@ fn global_communication<Block: BlockT<Hash=H256>, B, E, N, RA>(
 		voter::CommunicationIn::Commit(round, commit, callback)
 	});
 
+	// catch-ups are only requested by voters. a valid one restarts the voter
+	// at the round following the one it proves as completed.
+	let catch_up_voters = voters.clone();
+	let catch_up_client = client.clone();
+	let catch_ups_in = network.catch_up_communication(communication::SetId(set_id))
+		.map_err(CommandOrError::from)
+		.filter(move |_| is_voter)
+		.filter_map(move |(catch_up, mut callback)| {
+			match validate_catch_up(&catch_up, &catch_up_voters, &catch_up_client) {
+				Ok(Some(state)) => {
+					callback(communication::CatchUpProcessingOutcome::Good);
+					Some(CaughtUpRound {
+						number: catch_up.round_number,
+						state,
+						base: (catch_up.base_hash, catch_up.base_number),
+					})
+				},
+				Ok(None) => {
+					callback(communication::CatchUpProcessingOutcome::Useless);
+					None
+				},
+				Err(()) => {
+					callback(communication::CatchUpProcessingOutcome::Bad);
+					None
+				},
+			}
+		})
+		.and_then(|caught_up| -> Result<voter::CommunicationIn<_, _, _, _>, _> {
+			Err(CommandOrError::VoterCommand(VoterCommand::CatchUp(caught_up)))
+		});
+
+	let global_in = global_in.select(catch_ups_in);
+
 	// NOTE: eventually this will also handle catch-up requests
 	let global_out = commits_out.with(|global| match global {
 		voter::CommunicationOut::Commit(round, commit) => Ok((round, commit)),
@@ -415,6 +474,76 @@ fn global_communication<Block: BlockT<Hash=H256>, B, E, N, RA>(
 	(global_in, global_out)
 }
 
+/// Validate a catch-up message against the given voter set by importing its
+/// votes into a fresh round. Returns the state of the round if the catch-up
+/// proves that it was completed, `None` if some of the voted blocks are not
+/// imported yet and an error if the catch-up is invalid.
+fn validate_catch_up<Block: BlockT<Hash=H256>, B, E, RA>(
+	catch_up: &CatchUp<Block>,
+	voters: &VoterSet<AuthorityId>,
+	client: &Client<B, E, Block, RA>,
+) -> Result<Option<RoundState<H256, NumberFor<Block>>>, ()> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync,
+	RA: Send + Sync,
+	NumberFor<Block>: BlockNumberOps,
+{
+	use grandpa::round::{Round as VotingRound, RoundParams};
+
+	// votes on blocks we don't know of can't be checked yet, we'll make progress
+	// through another catch-up once we've synced further.
+	for signed in &catch_up.votes {
+		let target = signed.message.target().0.clone();
+		match client.block_number_from_id(&BlockId::Hash(target)) {
+			Ok(Some(_)) => {},
+			_ => return Ok(None),
+		}
+	}
+
+	let mut round = VotingRound::new(RoundParams {
+		round_number: catch_up.round_number,
+		voters: voters.clone(),
+		base: (catch_up.base_hash, catch_up.base_number),
+	});
+
+	let chain = observer::ObserverChain(client);
+	for signed in &catch_up.votes {
+		if !voters.contains_key(&signed.id) {
+			debug!(target: "afg", "Catch-up contains vote from unknown voter {}", signed.id);
+			return Err(());
+		}
+
+		let imported = match signed.message {
+			grandpa::Message::Prevote(ref prevote) => round.import_prevote(
+				&chain,
+				prevote.clone(),
+				signed.id.clone(),
+				signed.signature.clone(),
+			).map(|_| ()),
+			grandpa::Message::Precommit(ref precommit) => round.import_precommit(
+				&chain,
+				precommit.clone(),
+				signed.id.clone(),
+				signed.signature.clone(),
+			).map(|_| ()),
+			grandpa::Message::PrimaryPropose(_) => return Err(()),
+		};
+
+		if let Err(e) = imported {
+			debug!(target: "afg", "Failed to import catch-up vote: {:?}", e);
+			return Err(());
+		}
+	}
+
+	let state = round.state();
+	if !state.completable || state.prevote_ghost.is_none() {
+		debug!(target: "afg", "Catch-up for round {} doesn't complete the round", catch_up.round_number);
+		return Err(());
+	}
+
+	Ok(Some(state))
+}
+
 /// Register the finality tracker inherent data provider (which is used by
 /// GRANDPA), if not registered already.
 fn register_finality_tracker_inherent_data_provider<B, E, Block: BlockT<Hash=H256>, RA>(
@@ -445,6 +574,21 @@ fn register_finality_tracker_inherent_data_provider<B, E, Block: BlockT<Hash=H25
 	}
 }
 
+/// Register the store of equivocation reports found by the voter, if not
+/// registered already.
+fn register_equivocation_reports(
+	inherent_data_providers: &InherentDataProviders,
+	equivocation_reports: &srml_grandpa::EquivocationReports,
+) -> Result<(), consensus_common::Error> {
+	if !inherent_data_providers.has_provider(&srml_grandpa::EQUIVOCATION_INHERENT_IDENTIFIER) {
+		inherent_data_providers
+			.register_provider(equivocation_reports.clone())
+			.map_err(|err| consensus_common::ErrorKind::InherentData(err.into()).into())
+	} else {
+		Ok(())
+	}
+}
+
 /// Parameters used to run Grandpa.
 pub struct GrandpaParams<'a, B, E, Block: BlockT<Hash=H256>, N, RA, SC, X> {
 	/// Configuration for the GRANDPA service.
@@ -459,6 +603,9 @@ pub struct GrandpaParams<'a, B, E, Block: BlockT<Hash=H256>, N, RA, SC, X> {
 	pub on_exit: X,
 	/// If supplied, can be used to hook on telemetry connection established events.
 	pub telemetry_on_connect: Option<TelemetryOnConnect<'a>>,
+	/// If supplied, every equivocation found by the voter is also submitted with it,
+	/// e.g. as a signed transaction, besides being provided to block authors.
+	pub submit_equivocation_report: Option<srml_grandpa::SubmitEquivocationReport>,
 }
 
 /// Run a GRANDPA voter as a task. Provide configuration and a link to a
@@ -484,6 +631,7 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, X>(
 		inherent_data_providers,
 		on_exit,
 		telemetry_on_connect,
+		submit_equivocation_report,
 	} = grandpa_params;
 
 	use futures::future::{self, Loop as FutureLoop};
@@ -500,6 +648,14 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, X>(
 
 	register_finality_tracker_inherent_data_provider(client.clone(), &inherent_data_providers)?;
 
+	let mut equivocation_reports = srml_grandpa::EquivocationReports::with_identifier(
+		&srml_grandpa::EQUIVOCATION_INHERENT_IDENTIFIER,
+	);
+	if let Some(submit) = submit_equivocation_report {
+		equivocation_reports = equivocation_reports.with_submit(submit);
+	}
+	register_equivocation_reports(&inherent_data_providers, &equivocation_reports)?;
+
 	if let Some(telemetry_on_connect) = telemetry_on_connect {
 		let authorities = authority_set.clone();
 		let events = telemetry_on_connect.telemetry_connection_sinks
@@ -533,6 +689,7 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, X>(
 		authority_set: authority_set.clone(),
 		consensus_changes: consensus_changes.clone(),
 		voter_set_state: set_state.clone(),
+		equivocation_reports: equivocation_reports.clone(),
 	});
 
 	initial_environment.update_voter_set_state(|voter_set_state| {
@@ -616,6 +773,7 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, X>(
 		let select_chain = select_chain.clone();
 		let authority_set = authority_set.clone();
 		let consensus_changes = consensus_changes.clone();
+		let equivocation_reports = equivocation_reports.clone();
 
 		let handle_voter_command = move |command: VoterCommand<_, _>, voter_commands_rx| {
 			match command {
@@ -659,6 +817,7 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, X>(
 						authority_set,
 						consensus_changes,
 						voter_set_state: set_state,
+						equivocation_reports,
 					});
 
 					Ok(FutureLoop::Continue((env, voter_commands_rx)))
@@ -674,6 +833,42 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, X>(
 						Ok(Some(set_state))
 					})?;
 
+					Ok(FutureLoop::Continue((env, voter_commands_rx)))
+				},
+				VoterCommand::CatchUp(caught_up) => {
+					info!(target: "afg", "Catching up to round {} in set {}", caught_up.number, env.set_id);
+					telemetry!(CONSENSUS_INFO; "afg.voter_command_catch_up";
+						"round" => ?caught_up.number,
+						"set_id" => ?env.set_id,
+					);
+
+					// not racing because old voter is shut down.
+					env.update_voter_set_state(|voter_set_state| {
+						let mut completed_rounds = match voter_set_state {
+							VoterSetState::Live { completed_rounds, .. } => completed_rounds.clone(),
+							// a paused voter stays paused.
+							VoterSetState::Paused { .. } => return Ok(None),
+						};
+
+						// the voter might have completed the round on its own in the meantime.
+						if !completed_rounds.push(CompletedRound {
+							number: caught_up.number,
+							state: caught_up.state,
+							base: caught_up.base,
+							votes: Vec::new(),
+						}) {
+							return Ok(None);
+						}
+
+						let set_state = VoterSetState::Live {
+							completed_rounds,
+							current_round: HasVoted::No,
+						};
+
+						aux_schema::write_voter_set_state(&**client.backend(), &set_state)?;
+						Ok(Some(set_state))
+					})?;
+
 					Ok(FutureLoop::Continue((env, voter_commands_rx)))
 				},
 			}
@@ -736,3 +931,17 @@ pub fn run_grandpa<B, E, Block: BlockT<Hash=H256>, N, RA, SC, X>(
 {
 	run_grandpa_voter(grandpa_params)
 }
+
+/// When GRANDPA is not initialized we still need to register the finality
+/// tracker inherent provider which might be expected by the runtime for block
+/// authoring.
+pub fn setup_disabled_grandpa<B, E, Block: BlockT<Hash=H256>, RA>(
+	client: Arc<Client<B, E, Block, RA>>,
+	inherent_data_providers: &InherentDataProviders,
+) -> Result<(), consensus_common::Error> where
+	B: Backend<Block, Blake2Hasher> + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+{
+	register_finality_tracker_inherent_data_provider(client, inherent_data_providers)
+}
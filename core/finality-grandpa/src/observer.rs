@@ -26,7 +26,7 @@ use log::{debug, info, warn};
 
 use consensus_common::SelectChain;
 use client::{CallExecutor, Client, backend::Backend};
-use runtime_primitives::traits::{NumberFor, Block as BlockT};
+use runtime_primitives::traits::{As, NumberFor, Block as BlockT};
 use substrate_primitives::{ed25519::Public as AuthorityId, H256, Blake2Hasher};
 
 use crate::{
@@ -34,11 +34,11 @@ use crate::{
 	Error, LinkHalf, Network, aux_schema::PersistentData, VoterCommand, VoterSetState,
 };
 use crate::authorities::SharedAuthoritySet;
-use crate::communication::NetworkBridge;
+use crate::communication::{NetworkBridge, Round, SetId};
 use crate::consensus_changes::SharedConsensusChanges;
 use crate::environment::{CompletedRound, CompletedRounds, HasVoted};
 
-struct ObserverChain<'a, Block: BlockT, B, E, RA>(&'a Client<B, E, Block, RA>);
+pub(crate) struct ObserverChain<'a, Block: BlockT, B, E, RA>(pub(crate) &'a Client<B, E, Block, RA>);
 
 impl<'a, Block: BlockT<Hash=H256>, B, E, RA> grandpa::Chain<Block::Hash, NumberFor<Block>>
 	for ObserverChain<'a, Block, B, E, RA> where
@@ -56,10 +56,12 @@ impl<'a, Block: BlockT<Hash=H256>, B, E, RA> grandpa::Chain<Block::Hash, NumberF
 	}
 }
 
-fn grandpa_observer<B, E, Block: BlockT<Hash=H256>, RA, S>(
+fn grandpa_observer<B, E, Block: BlockT<Hash=H256>, N, RA, S>(
 	client: &Arc<Client<B, E, Block, RA>>,
 	authority_set: &SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
 	consensus_changes: &SharedConsensusChanges<Block::Hash, NumberFor<Block>>,
+	network: &NetworkBridge<Block, N>,
+	justification_period: NumberFor<Block>,
 	voters: &Arc<VoterSet<AuthorityId>>,
 	last_finalized_number: NumberFor<Block>,
 	commits: S,
@@ -67,6 +69,7 @@ fn grandpa_observer<B, E, Block: BlockT<Hash=H256>, RA, S>(
 	NumberFor<Block>: BlockNumberOps,
 	B: Backend<Block, Blake2Hasher>,
 	E: CallExecutor<Block, Blake2Hasher> + Send + Sync,
+	N: Network<Block>,
 	RA: Send + Sync,
 	S: Stream<
 		Item = voter::CommunicationIn<H256, NumberFor<Block>, AuthoritySignature, AuthorityId>,
@@ -76,7 +79,9 @@ fn grandpa_observer<B, E, Block: BlockT<Hash=H256>, RA, S>(
 	let authority_set = authority_set.clone();
 	let consensus_changes = consensus_changes.clone();
 	let client = client.clone();
+	let network = network.clone();
 	let voters = voters.clone();
+	let set_id = authority_set.set_id();
 
 	let observer = commits.fold(last_finalized_number, move |last_finalized_number, global| {
 		let (round, commit, callback) = match global {
@@ -109,12 +114,14 @@ fn grandpa_observer<B, E, Block: BlockT<Hash=H256>, RA, S>(
 			let finalized_hash = commit.target_hash;
 			let finalized_number = commit.target_number;
 
-			// commit is valid, finalize the block it targets
+			// commit is valid, finalize the block it targets. justifications are
+			// kept for authority set changes and every `justification_period`
+			// blocks, so that they can be served to light clients.
 			match environment::finalize_block(
 				&client,
 				&authority_set,
 				&consensus_changes,
-				None,
+				Some(justification_period),
 				finalized_hash,
 				finalized_number,
 				(round, commit).into(),
@@ -125,6 +132,10 @@ fn grandpa_observer<B, E, Block: BlockT<Hash=H256>, RA, S>(
 
 			grandpa::process_commit_validation_result(validation_result, callback);
 
+			// the voters have moved on to the next round, follow them so that
+			// we keep relaying their messages.
+			network.note_round(Round(round + 1), SetId(set_id));
+
 			// proceed processing with new finalized block number
 			future::ok(finalized_number)
 		} else {
@@ -142,8 +153,10 @@ fn grandpa_observer<B, E, Block: BlockT<Hash=H256>, RA, S>(
 
 /// Run a GRANDPA observer as a task, the observer will finalize blocks only by
 /// listening for and validating GRANDPA commits instead of following the full
-/// protocol. Provide configuration and a link to a block import worker that has
-/// already been instantiated with `block_import`.
+/// protocol. Justifications are stored for authority set changes and every
+/// `justification_period` blocks, and round messages of the voters are relayed.
+/// Provide configuration and a link to a block import worker that has already
+/// been instantiated with `block_import`.
 pub fn run_grandpa_observer<B, E, Block: BlockT<Hash=H256>, N, RA, SC>(
 	config: Config,
 	link: LinkHalf<B, E, Block, RA, SC>,
@@ -169,6 +182,7 @@ pub fn run_grandpa_observer<B, E, Block: BlockT<Hash=H256>, N, RA, SC>(
 	let initial_state = (authority_set, consensus_changes, set_state, voter_commands_rx.into_future());
 
 	let (network, network_startup) = NetworkBridge::new(network, config.clone(), on_exit.clone());
+	let justification_period = As::sa(config.justification_period);
 
 	let observer_work = future::loop_fn(initial_state, move |state| {
 		let (authority_set, consensus_changes, set_state, voter_commands_rx) = state;
@@ -197,6 +211,8 @@ pub fn run_grandpa_observer<B, E, Block: BlockT<Hash=H256>, N, RA, SC>(
 			&client,
 			&authority_set,
 			&consensus_changes,
+			&network,
+			justification_period,
 			&voters,
 			last_finalized_number,
 			global_in,
@@ -236,6 +252,11 @@ pub fn run_grandpa_observer<B, E, Block: BlockT<Hash=H256>, N, RA, SC>(
 
 					set_state
 				},
+				VoterCommand::CatchUp(_) => {
+					// the observer follows the rounds from commits and never
+					// requests catch-ups.
+					return Ok(FutureLoop::Continue((authority_set, consensus_changes, set_state, voter_commands_rx)));
+				},
 			};
 
 			Ok(FutureLoop::Continue((authority_set, consensus_changes, set_state.into(), voter_commands_rx)))
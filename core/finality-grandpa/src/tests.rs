@@ -96,6 +96,7 @@ impl TestNetFactory for GrandpaTestNet {
 		// the authority role ensures gossip hits all nodes here.
 		ProtocolConfig {
 			roles: Roles::AUTHORITY,
+			..Default::default()
 		}
 	}
 
@@ -447,6 +448,7 @@ fn run_to_completion_with<F>(
 			inherent_data_providers: InherentDataProviders::new(),
 			on_exit: Exit,
 			telemetry_on_connect: None,
+			submit_equivocation_report: None,
 		};
 		let voter = run_grandpa_voter(grandpa_params).expect("all in order with client and network");
 
@@ -548,6 +550,7 @@ fn finalize_3_voters_1_full_observer() {
 			inherent_data_providers: InherentDataProviders::new(),
 			on_exit: Exit,
 			telemetry_on_connect: None,
+			submit_equivocation_report: None,
 		};
 		let voter = run_grandpa_voter(grandpa_params).expect("all in order with client and network");
 
@@ -713,6 +716,7 @@ fn transition_3_voters_twice_1_full_observer() {
 			inherent_data_providers: InherentDataProviders::new(),
 			on_exit: Exit,
 			telemetry_on_connect: None,
+			submit_equivocation_report: None,
 		};
 		let voter = run_grandpa_voter(grandpa_params).expect("all in order with client and network");
 
@@ -1117,6 +1121,7 @@ fn voter_persists_its_votes() {
 				inherent_data_providers: InherentDataProviders::new(),
 				on_exit: Exit,
 				telemetry_on_connect: None,
+				submit_equivocation_report: None,
 			};
 			let mut voter = run_grandpa_voter(grandpa_params).expect("all in order with client and network");
 
@@ -1302,3 +1307,64 @@ fn finalize_3_voters_1_light_observer() {
 		Some(Box::new(finality_notifications.map(|_| ())))
 	});
 }
+
+#[test]
+fn observer_generates_justifications_periodically() {
+	let _ = env_logger::try_init();
+	let authorities = &[AuthorityKeyring::Alice, AuthorityKeyring::Bob, AuthorityKeyring::Charlie];
+	let voters = make_ids(authorities);
+
+	let mut net = GrandpaTestNet::new(TestApi::new(voters), 4);
+	net.peer(0).push_blocks(32, false);
+	net.sync();
+
+	let net = Arc::new(Mutex::new(net));
+	let link = net.lock().peer(3).data.lock().take().expect("link initialized on startup; qed");
+
+	let finality_notifications = net.lock().peer(3).client().finality_notification_stream()
+		.take_while(|n| Ok(n.header.number() < &32))
+		.collect();
+
+	run_to_completion_with(32, net.clone(), authorities, |executor| {
+		executor.spawn(
+			run_grandpa_observer(
+				Config {
+					gossip_duration: TEST_GOSSIP_DURATION,
+					justification_period: 32,
+					local_key: None,
+					name: Some("observer".to_string()),
+				},
+				link,
+				MessageRouting::new(net.clone(), 3),
+				Exit,
+			).unwrap()
+		).unwrap();
+
+		Some(Box::new(finality_notifications.map(|_| ())))
+	});
+
+	// the observer keeps a justification for block#32 (justification_period)
+	// so that it can prove its finality to light clients.
+	assert!(net.lock().peer(3).client().backend().blockchain()
+		.justification(BlockId::Number(32)).unwrap().is_some());
+}
+
+#[test]
+fn equivocation_report_votes_are_signed_like_messages() {
+	use fg_primitives::{localized_payload, Vote};
+
+	let target_hash = Hash::repeat_byte(1);
+	let target_number = 5;
+
+	let prevote = grandpa::Message::Prevote(grandpa::Prevote::<Hash, BlockNumber> { target_hash, target_number });
+	assert_eq!(
+		localized_payload(3, 7, &prevote),
+		localized_payload(3, 7, &Vote::Prevote(target_hash, target_number)),
+	);
+
+	let precommit = grandpa::Message::Precommit(grandpa::Precommit::<Hash, BlockNumber> { target_hash, target_number });
+	assert_eq!(
+		localized_payload(3, 7, &precommit),
+		localized_payload(3, 7, &Vote::Precommit(target_hash, target_number)),
+	);
+}
@@ -22,7 +22,7 @@ use client::light::fetcher::ChangesProof;
 use consensus::{BlockImport, Error as ConsensusError};
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT};
 use runtime_primitives::generic::{BlockId};
-use runtime_primitives::Justification;
+use runtime_primitives::{Justification, StorageOverlay, ChildrenStorageOverlay};
 use primitives::{H256, Blake2Hasher, storage::StorageKey};
 
 /// Local client abstraction for the network.
@@ -66,6 +66,49 @@ pub trait Client<Block: BlockT>: Send + Sync {
 
 	/// Returns `true` if the given `block` is a descendent of `base`.
 	fn is_descendent_of(&self, base: &Block::Hash, block: &Block::Hash) -> Result<bool, Error>;
+
+	/// Get storage range proof of up to `max_entries` key-value pairs, starting with `start`.
+	fn state_range_proof(
+		&self,
+		block: &Block::Hash,
+		child_storage_key: Option<&[u8]>,
+		start: &[u8],
+		max_entries: usize,
+	) -> Result<Vec<Vec<u8>>, Error>;
+
+	/// Check storage range proof against the root of the proven trie.
+	fn check_state_range_proof(
+		&self,
+		root: Block::Hash,
+		proof: Vec<Vec<u8>>,
+		start: &[u8],
+	) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Error>;
+
+	/// Import finalized block with its full state as a new base of the chain.
+	fn import_state(
+		&self,
+		header: Block::Header,
+		justification: Option<Justification>,
+		top: StorageOverlay,
+		children: ChildrenStorageOverlay,
+	) -> Result<(), Error>;
+
+	/// Import block of the chain history preceding the base of the chain.
+	fn import_history(
+		&self,
+		header: Block::Header,
+		body: Option<Vec<Block::Extrinsic>>,
+		justification: Option<Justification>,
+	) -> Result<(), Error>;
+}
+
+/// Finality proofs provider used by the state sync.
+pub trait FinalityProofProvider<Block: BlockT>: Send + Sync {
+	/// Prove finality of the given block. Returns `None` if the block isn't finalized yet.
+	fn prove_finality(&self, block: Block::Hash) -> Result<Option<Vec<u8>>, Error>;
+
+	/// Check proof of finality of the given block.
+	fn check_finality_proof(&self, header: &Block::Header, proof: Vec<u8>) -> Result<(), Error>;
 }
 
 impl<B, E, Block, RA> Client<Block> for SubstrateClient<B, E, Block, RA> where
@@ -135,4 +178,42 @@ impl<B, E, Block, RA> Client<Block> for SubstrateClient<B, E, Block, RA> where
 
 		Ok(tree_route.common_block().hash == *base)
 	}
+
+	fn state_range_proof(
+		&self,
+		block: &Block::Hash,
+		child_storage_key: Option<&[u8]>,
+		start: &[u8],
+		max_entries: usize,
+	) -> Result<Vec<Vec<u8>>, Error> {
+		(self as &SubstrateClient<B, E, Block, RA>).read_range_proof(&BlockId::Hash(block.clone()), child_storage_key, start, max_entries)
+	}
+
+	fn check_state_range_proof(
+		&self,
+		root: Block::Hash,
+		proof: Vec<Vec<u8>>,
+		start: &[u8],
+	) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Error> {
+		client::check_range_proof(root, proof, start)
+	}
+
+	fn import_state(
+		&self,
+		header: Block::Header,
+		justification: Option<Justification>,
+		top: StorageOverlay,
+		children: ChildrenStorageOverlay,
+	) -> Result<(), Error> {
+		(self as &SubstrateClient<B, E, Block, RA>).import_state(header, justification, top, children)
+	}
+
+	fn import_history(
+		&self,
+		header: Block::Header,
+		body: Option<Vec<Block::Extrinsic>>,
+		justification: Option<Justification>,
+	) -> Result<(), Error> {
+		(self as &SubstrateClient<B, E, Block, RA>).import_history(header, body, justification)
+	}
 }
@@ -19,7 +19,7 @@
 pub use network_libp2p::{NonReservedPeerMode, NetworkConfiguration, NodeKeyConfig, Secret};
 
 use bitflags::bitflags;
-use crate::chain::Client;
+use crate::chain::{Client, FinalityProofProvider};
 use parity_codec;
 use crate::on_demand::OnDemandService;
 use runtime_primitives::traits::{Block as BlockT};
@@ -36,6 +36,8 @@ pub struct Params<B: BlockT, S, H: ExHashT> {
 	pub chain: Arc<Client<B>>,
 	/// On-demand service reference.
 	pub on_demand: Option<Arc<OnDemandService<B>>>,
+	/// Provider of finality proofs used by the state sync.
+	pub finality_proof_provider: Option<Arc<FinalityProofProvider<B>>>,
 	/// Transaction pool.
 	pub transaction_pool: Arc<TransactionPool<H, B>>,
 	/// Protocol specialization.
@@ -47,16 +49,32 @@ pub struct Params<B: BlockT, S, H: ExHashT> {
 pub struct ProtocolConfig {
 	/// Assigned roles.
 	pub roles: Roles,
+	/// Chain synchronization mode.
+	pub sync_mode: SyncMode,
 }
 
 impl Default for ProtocolConfig {
 	fn default() -> ProtocolConfig {
 		ProtocolConfig {
 			roles: Roles::FULL,
+			sync_mode: SyncMode::Full,
 		}
 	}
 }
 
+/// Chain synchronization mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncMode {
+	/// Download and import every block of the chain.
+	Full,
+	/// Download the latest finalized block proven by its finality proof together with its
+	/// state, then continue with full sync from this block. Requires a finality proof provider.
+	Warp {
+		/// Also download the blocks preceding the finalized block, without their state.
+		backfill: bool,
+	},
+}
+
 bitflags! {
 	/// Bitmask of the roles that a node fulfills.
 	pub struct Roles: u8 {
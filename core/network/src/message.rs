@@ -23,7 +23,7 @@ pub use self::generic::{
 	BlockAnnounce, RemoteCallRequest, RemoteReadRequest,
 	RemoteHeaderRequest, RemoteHeaderResponse,
 	RemoteChangesRequest, RemoteChangesResponse,
-	FromBlock, RemoteReadChildRequest,
+	FromBlock, RemoteReadChildRequest, StateRequest,
 };
 
 /// A unique ID of a request.
@@ -63,6 +63,9 @@ pub type BlockResponse<B> = generic::BlockResponse<
 	<B as BlockT>::Extrinsic,
 >;
 
+/// Type alias for using the FinalityProofResponse type using block type parameters.
+pub type FinalityProofResponse<B> = generic::FinalityProofResponse<<B as BlockT>::Header>;
+
 /// A set of transactions.
 pub type Transactions<E> = Vec<E>;
 
@@ -122,6 +125,22 @@ pub struct RemoteReadResponse {
 	pub proof: Vec<Vec<u8>>,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// State response.
+pub struct StateResponse {
+	/// Id of a request this response was made for.
+	pub id: RequestId,
+	/// Range proof of the requested storage entries.
+	pub proof: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Request for the latest finalized block of the peer, with the proof of its finality.
+pub struct FinalityProofRequest {
+	/// Unique request id.
+	pub id: RequestId,
+}
+
 /// Generic types.
 pub mod generic {
 	use parity_codec::{Encode, Decode};
@@ -131,6 +150,7 @@ pub mod generic {
 	use super::{
 		RemoteReadResponse, Transactions, Direction,
 		RequestId, BlockAttributes, RemoteCallResponse, ConsensusEngineId,
+		StateResponse, FinalityProofRequest,
 	};
 	/// Consensus is mostly opaque to us
 	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
@@ -200,6 +220,14 @@ pub mod generic {
 		RemoteChangesResponse(RemoteChangesResponse<Number, Hash>),
 		/// Remote child storage read request.
 		RemoteReadChildRequest(RemoteReadChildRequest<Hash>),
+		/// State request.
+		StateRequest(StateRequest<Hash>),
+		/// State response.
+		StateResponse(StateResponse),
+		/// Finality proof request.
+		FinalityProofRequest(FinalityProofRequest),
+		/// Finality proof response.
+		FinalityProofResponse(FinalityProofResponse<Header>),
 		/// Chain-specific message
 		#[codec(index = "255")]
 		ChainSpecific(Vec<u8>),
@@ -359,4 +387,30 @@ pub mod generic {
 		/// Missing changes tries roots proof.
 		pub roots_proof: Vec<Vec<u8>>,
 	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Request for the storage entries of a block.
+	pub struct StateRequest<H> {
+		/// Unique request id.
+		pub id: RequestId,
+		/// Block which state is requested.
+		pub block: H,
+		/// Child storage key if the entries of a child storage are requested.
+		pub child_storage_key: Option<Vec<u8>>,
+		/// Key of the first requested entry.
+		pub start: Vec<u8>,
+	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Finality proof response.
+	pub struct FinalityProofResponse<Header> {
+		/// Id of a request this response was made for.
+		pub id: RequestId,
+		/// Header of the proven block. None if the peer can't prove finality of any block.
+		pub header: Option<Header>,
+		/// Justification of the proven block.
+		pub justification: Option<Justification>,
+		/// Finality proof.
+		pub proof: Vec<u8>,
+	}
 }
@@ -17,7 +17,7 @@
 use futures::{prelude::*, sync::mpsc};
 use network_libp2p::PeerId;
 use primitives::storage::StorageKey;
-use consensus::{import_queue::IncomingBlock, import_queue::Origin, BlockOrigin};
+use consensus::{import_queue::IncomingBlock, import_queue::ImportQueueMetrics, import_queue::Origin, BlockOrigin};
 use runtime_primitives::{generic::BlockId, ConsensusEngineId, Justification};
 use runtime_primitives::traits::{As, Block as BlockT, Header as HeaderT, NumberFor, Zero};
 use crate::message::{self, BlockRequest as BlockRequestMessage, Message};
@@ -34,7 +34,7 @@ use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::{cmp, num::NonZeroUsize, time};
 use log::{trace, debug, warn, error};
-use crate::chain::Client;
+use crate::chain::{Client, FinalityProofProvider};
 use client::light::fetcher::ChangesProof;
 use crate::{error, util::LruHashSet};
 
@@ -51,6 +51,11 @@ pub(crate) const MIN_VERSION: u32 = 2;
 
 // Maximum allowed entries in `BlockResponse`
 const MAX_BLOCK_DATA_RESPONSE: u32 = 128;
+// Maximum storage entries proven by a single `StateResponse`
+const MAX_STATE_RESPONSE_ENTRIES: usize = 1024;
+/// Maximum number of finalized blocks to look through when searching for a block with
+/// justification to prove finality of.
+const MAX_FINALITY_PROOF_SEARCH_DEPTH: u64 = 4096;
 /// When light node connects to the full node and the full node is behind light node
 /// for at least `LIGHT_MAXIMAL_BLOCKS_DIFFERENCE` blocks, we consider it unuseful
 /// and disconnect to free connection slot.
@@ -80,6 +85,7 @@ pub struct Protocol<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
 	propagate_timeout: tokio::timer::Interval,
 	config: ProtocolConfig,
 	on_demand: Option<Arc<OnDemandService<B>>>,
+	finality_proof_provider: Option<Arc<FinalityProofProvider<B>>>,
 	genesis_hash: B::Hash,
 	sync: ChainSync<B>,
 	specialization: S,
@@ -91,6 +97,8 @@ pub struct Protocol<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
 	// similar to context_data.peers but shared with the SyncProvider.
 	connected_peers: Arc<RwLock<HashMap<PeerId, ConnectedPeer<B>>>>,
 	transaction_pool: Arc<TransactionPool<H, B>>,
+	/// Latest statistics reported by the import queue.
+	import_queue_metrics: ImportQueueMetrics,
 }
 
 /// A peer from whom we have received a Status message.
@@ -114,6 +122,8 @@ pub struct ProtocolStatus<B: BlockT> {
 	pub num_peers: usize,
 	/// Total number of active peers.
 	pub num_active_peers: usize,
+	/// Latest import queue statistics.
+	pub import_queue: ImportQueueMetrics,
 }
 
 /// Peer information
@@ -168,6 +178,12 @@ pub trait Context<B: BlockT> {
 
 	/// Send a chain-specific message to a peer.
 	fn send_chain_specific(&mut self, who: PeerId, message: Vec<u8>);
+
+	/// Request a chunk of the state from a peer.
+	fn send_state_request(&mut self, who: PeerId, request: message::StateRequest<B::Hash>);
+
+	/// Request proof of the latest finalized block from a peer.
+	fn send_finality_proof_request(&mut self, who: PeerId, request: message::FinalityProofRequest);
 }
 
 /// Protocol context.
@@ -216,6 +232,18 @@ impl<'a, B: BlockT + 'a, H: ExHashT + 'a> Context<B> for ProtocolContext<'a, B,
 			GenericMessage::ChainSpecific(message)
 		)
 	}
+
+	fn send_state_request(&mut self, who: PeerId, request: message::StateRequest<B::Hash>) {
+		send_message(&mut self.context_data.peers, &self.network_chan, who,
+			GenericMessage::StateRequest(request)
+		)
+	}
+
+	fn send_finality_proof_request(&mut self, who: PeerId, request: message::FinalityProofRequest) {
+		send_message(&mut self.context_data.peers, &self.network_chan, who,
+			GenericMessage::FinalityProofRequest(request)
+		)
+	}
 }
 
 /// Data necessary to create a context.
@@ -263,6 +291,8 @@ pub enum ProtocolMsg<B: BlockT, S: NetworkSpecialization<B>> {
 	RequestJustification(B::Hash, NumberFor<B>),
 	/// Inform protocol whether a justification was successfully imported.
 	JustificationImportResult(B::Hash, NumberFor<B>, bool),
+	/// Latest statistics of the import queue.
+	ImportQueueMetrics(ImportQueueMetrics),
 	/// Propagate a block to peers.
 	AnnounceBlock(B::Hash),
 	/// A block has been imported (sent by the client).
@@ -291,12 +321,13 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		config: ProtocolConfig,
 		chain: Arc<Client<B>>,
 		on_demand: Option<Arc<OnDemandService<B>>>,
+		finality_proof_provider: Option<Arc<FinalityProofProvider<B>>>,
 		transaction_pool: Arc<TransactionPool<H, B>>,
 		specialization: S,
 	) -> error::Result<(Protocol<B, S, H>, mpsc::UnboundedSender<ProtocolMsg<B, S>>)> {
 		let (protocol_sender, port) = mpsc::unbounded();
 		let info = chain.info()?;
-		let sync = ChainSync::new(config.roles, &info);
+		let sync = ChainSync::new(config.roles, &info, config.sync_mode, finality_proof_provider.clone());
 		let protocol = Protocol {
 			network_chan,
 			port,
@@ -308,6 +339,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				chain,
 			},
 			on_demand,
+			finality_proof_provider,
 			genesis_hash: info.chain.genesis_hash,
 			sync,
 			specialization: specialization,
@@ -315,6 +347,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			handshaking_peers: HashMap::new(),
 			connected_peers,
 			transaction_pool: transaction_pool,
+			import_queue_metrics: Default::default(),
 		};
 
 		Ok((protocol, protocol_sender))
@@ -331,6 +364,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				.values()
 				.filter(|p| p.block_request.is_some())
 				.count(),
+			import_queue: self.import_queue_metrics.clone(),
 		}
 	}
 
@@ -408,6 +442,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				self.sync.request_justification(&hash, number, &mut context);
 			},
 			ProtocolMsg::JustificationImportResult(hash, number, success) => self.sync.justification_import_result(hash, number, success),
+			ProtocolMsg::ImportQueueMetrics(metrics) => self.import_queue_metrics = metrics,
 			ProtocolMsg::PropagateExtrinsics => self.propagate_extrinsics(),
 			#[cfg(any(test, feature = "test-helpers"))]
 			ProtocolMsg::Tick => self.tick(),
@@ -476,6 +511,10 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			GenericMessage::RemoteHeaderResponse(response) => self.on_remote_header_response(who, response),
 			GenericMessage::RemoteChangesRequest(request) => self.on_remote_changes_request(who, request),
 			GenericMessage::RemoteChangesResponse(response) => self.on_remote_changes_response(who, response),
+			GenericMessage::StateRequest(request) => self.on_state_request(who, request),
+			GenericMessage::StateResponse(response) => self.on_state_response(who, response),
+			GenericMessage::FinalityProofRequest(request) => self.on_finality_proof_request(who, request),
+			GenericMessage::FinalityProofResponse(response) => self.on_finality_proof_response(who, response),
 			GenericMessage::Consensus(msg) => {
 				if self.context_data.peers.get(&who).map_or(false, |peer| peer.info.protocol_version > 2) {
 					self.consensus_gossip.on_incoming(
@@ -1099,6 +1138,103 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			.as_ref()
 			.map(|s| s.on_remote_changes_response(who, response));
 	}
+
+	fn on_state_request(
+		&mut self,
+		who: PeerId,
+		request: message::StateRequest<B::Hash>,
+	) {
+		trace!(target: "sync", "State request {} from {} ({} at {})",
+			request.id, who, request.start.to_hex::<String>(), request.block);
+		let proof = match self.context_data.chain.state_range_proof(
+			&request.block,
+			request.child_storage_key.as_ref().map(|key| &key[..]),
+			&request.start,
+			MAX_STATE_RESPONSE_ENTRIES,
+		) {
+			Ok(proof) => proof,
+			Err(error) => {
+				trace!(target: "sync", "State request {} from {} ({} at {}) failed with: {}",
+					request.id, who, request.start.to_hex::<String>(), request.block, error);
+				Default::default()
+			}
+		};
+		self.send_message(
+			who,
+			GenericMessage::StateResponse(message::StateResponse {
+				id: request.id,
+				proof,
+			}),
+		);
+	}
+
+	fn on_state_response(&mut self, who: PeerId, response: message::StateResponse) {
+		trace!(target: "sync", "State response {} from {}", response.id, who);
+		self.sync.on_state_response(
+			&mut ProtocolContext::new(&mut self.context_data, &self.network_chan),
+			who,
+			response,
+		);
+	}
+
+	fn on_finality_proof_request(&mut self, who: PeerId, request: message::FinalityProofRequest) {
+		trace!(target: "sync", "Finality proof request {} from {}", request.id, who);
+		let proof = match self.finality_proof_provider {
+			Some(ref provider) => prove_latest_finality(&*self.context_data.chain, &**provider),
+			None => Ok(None),
+		};
+		let (header, justification, proof) = match proof {
+			Ok(Some((header, justification, proof))) => (Some(header), justification, proof),
+			Ok(None) => (None, None, Vec::new()),
+			Err(error) => {
+				trace!(target: "sync", "Finality proof request {} from {} failed with: {}",
+					request.id, who, error);
+				(None, None, Vec::new())
+			}
+		};
+		self.send_message(
+			who,
+			GenericMessage::FinalityProofResponse(message::generic::FinalityProofResponse {
+				id: request.id,
+				header,
+				justification,
+				proof,
+			}),
+		);
+	}
+
+	fn on_finality_proof_response(&mut self, who: PeerId, response: message::FinalityProofResponse<B>) {
+		trace!(target: "sync", "Finality proof response {} from {}", response.id, who);
+		self.sync.on_finality_proof_response(
+			&mut ProtocolContext::new(&mut self.context_data, &self.network_chan),
+			who,
+			response,
+		);
+	}
+}
+
+/// Prove finality of the latest finalized block that has a justification.
+fn prove_latest_finality<B: BlockT>(
+	chain: &Client<B>,
+	provider: &FinalityProofProvider<B>,
+) -> Result<Option<(B::Header, Option<Justification>, Vec<u8>)>, client::error::Error> {
+	let info = chain.info()?;
+	let mut hash = info.chain.finalized_hash;
+	for _ in 0..MAX_FINALITY_PROOF_SEARCH_DEPTH {
+		let header = match chain.header(&BlockId::Hash(hash))? {
+			Some(header) => header,
+			None => return Ok(None),
+		};
+		if header.number().is_zero() {
+			return Ok(None);
+		}
+		let justification = chain.justification(&BlockId::Hash(hash))?;
+		if justification.is_some() {
+			return Ok(provider.prove_finality(hash)?.map(|proof| (header, justification, proof)));
+		}
+		hash = *header.parent_hash();
+	}
+	Ok(None)
 }
 
 /// Outcome of an incoming custom message.
@@ -26,7 +26,7 @@ use network_libp2p::{ProtocolId, NetworkConfiguration};
 use network_libp2p::{start_service, parse_str_addr, Service as NetworkService, ServiceEvent as NetworkServiceEvent};
 use network_libp2p::{RegisteredProtocol, NetworkState};
 use peerset::PeersetHandle;
-use consensus::import_queue::{ImportQueue, Link};
+use consensus::import_queue::{ImportQueue, ImportQueueMetrics, Link};
 use runtime_primitives::{traits::{Block as BlockT, NumberFor}, ConsensusEngineId};
 
 use crate::consensus_gossip::{ConsensusGossip, MessageRecipient as GossipMessageRecipient};
@@ -122,6 +122,10 @@ impl<B: BlockT, S: NetworkSpecialization<B>> Link<B> for NetworkLink<B, S> {
 	fn restart(&self) {
 		let _ = self.protocol_sender.unbounded_send(ProtocolMsg::RestartSync);
 	}
+
+	fn import_queue_metrics(&self, metrics: ImportQueueMetrics) {
+		let _ = self.protocol_sender.unbounded_send(ProtocolMsg::ImportQueueMetrics(metrics));
+	}
 }
 
 /// A cloneable handle for reporting cost/benefits of peers.
@@ -180,6 +184,7 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>> Service<B, S> {
 			params.config,
 			params.chain,
 			params.on_demand,
+			params.finality_proof_provider,
 			params.transaction_pool,
 			params.specialization,
 		)?;
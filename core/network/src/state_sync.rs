@@ -0,0 +1,259 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! State of the warp sync.
+//!
+//! The warp sync downloads the latest finalized header together with the proof of its
+//! finality, then downloads the state of this block in chunks. Every chunk is a range proof
+//! that is checked against the state root of the header (or against the root of the child
+//! trie). Once the state is imported, the normal sync continues from this block, optionally
+//! downloading the preceding blocks (without their state) in the background.
+
+use std::collections::{HashSet, VecDeque};
+use std::time::Instant;
+use log::debug;
+use network_libp2p::PeerId;
+use primitives::storage::well_known_keys;
+use runtime_primitives::{Justification, StorageOverlay, ChildrenStorageOverlay};
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor};
+use crate::chain::Client;
+use crate::message::{self, RequestId};
+
+/// Result of the import of a state response.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ImportResult {
+	/// The response is valid, but there are more entries to download.
+	Continue,
+	/// The whole state has been downloaded.
+	Complete,
+	/// The response is invalid.
+	BadResponse,
+}
+
+/// Download of the state of a single block.
+pub(crate) struct StateSync<B: BlockT> {
+	header: B::Header,
+	justification: Option<Justification>,
+	/// Child storage which entries are being downloaded, with its root.
+	/// `None` while downloading the top storage.
+	child: Option<(Vec<u8>, B::Hash)>,
+	/// Key of the next entry to download.
+	next_key: Vec<u8>,
+	top: StorageOverlay,
+	children: ChildrenStorageOverlay,
+	/// Child storages found in the top storage, which are not downloaded yet.
+	pending_children: VecDeque<(Vec<u8>, B::Hash)>,
+}
+
+impl<B: BlockT> StateSync<B> {
+	/// Create new state download of the given (proven finalized) block.
+	pub fn new(header: B::Header, justification: Option<Justification>) -> Self {
+		StateSync {
+			header,
+			justification,
+			child: None,
+			next_key: Vec::new(),
+			top: Default::default(),
+			children: Default::default(),
+			pending_children: Default::default(),
+		}
+	}
+
+	/// Number of the block which state is downloaded.
+	pub fn number(&self) -> NumberFor<B> {
+		*self.header.number()
+	}
+
+	/// Request for the next chunk of the state.
+	pub fn next_request(&self, id: RequestId) -> message::StateRequest<B::Hash> {
+		message::generic::StateRequest {
+			id,
+			block: self.header.hash(),
+			child_storage_key: self.child.as_ref().map(|(key, _)| key.clone()),
+			start: self.next_key.clone(),
+		}
+	}
+
+	/// Check and import a range proof received in response to `next_request`.
+	pub fn import(&mut self, client: &Client<B>, proof: Vec<Vec<u8>>) -> ImportResult {
+		let root = match self.child {
+			Some((_, root)) => root,
+			None => *self.header.state_root(),
+		};
+		let (entries, complete) = match client.check_state_range_proof(root, proof, &self.next_key) {
+			Ok(result) => result,
+			Err(e) => {
+				debug!(target: "sync", "Invalid state range proof: {:?}", e);
+				return ImportResult::BadResponse;
+			},
+		};
+		if entries.is_empty() && !complete {
+			debug!(target: "sync", "Empty state range proof");
+			return ImportResult::BadResponse;
+		}
+
+		if let Some((last_key, _)) = entries.last() {
+			self.next_key = last_key.clone();
+			self.next_key.push(0);
+		}
+		match self.child {
+			Some((ref key, _)) => {
+				self.children.entry(key.clone()).or_insert_with(Default::default).extend(entries);
+			},
+			None => for (key, value) in entries {
+				if well_known_keys::is_child_storage_key(&key) {
+					let mut child_root = B::Hash::default();
+					if child_root.as_ref().len() != value.len() {
+						debug!(target: "sync", "Invalid child trie root in the state");
+						return ImportResult::BadResponse;
+					}
+					child_root.as_mut().copy_from_slice(&value);
+					self.pending_children.push_back((key.clone(), child_root));
+				}
+				self.top.insert(key, value);
+			},
+		}
+
+		if !complete {
+			return ImportResult::Continue;
+		}
+		self.next_key = Vec::new();
+		self.child = self.pending_children.pop_front();
+		match self.child {
+			Some(_) => ImportResult::Continue,
+			None => ImportResult::Complete,
+		}
+	}
+
+	/// Destructure into the header, justification and the downloaded state.
+	pub fn into_parts(self) -> (B::Header, Option<Justification>, StorageOverlay, ChildrenStorageOverlay) {
+		(self.header, self.justification, self.top, self.children)
+	}
+}
+
+/// Current phase of the warp sync.
+pub(crate) enum WarpPhase<B: BlockT> {
+	/// Waiting for the proof of the latest finalized block.
+	FinalityProof,
+	/// Downloading the state of the proven block.
+	State(StateSync<B>),
+	/// Downloading the blocks preceding the imported state. Holds number and hash of the
+	/// next expected block.
+	Backfill(NumberFor<B>, B::Hash),
+}
+
+/// State of the warp sync.
+pub(crate) struct WarpSync<B: BlockT> {
+	/// Current phase.
+	pub phase: WarpPhase<B>,
+	/// Whether to download the blocks preceding the imported state.
+	pub backfill: bool,
+	/// Outstanding finality proof or state request: peer, request id and time it was sent.
+	pub request: Option<(PeerId, RequestId, Instant)>,
+	/// Peers that have failed to answer a request.
+	pub failed_peers: HashSet<PeerId>,
+	next_request_id: RequestId,
+}
+
+impl<B: BlockT> WarpSync<B> {
+	/// Create new warp sync waiting for a finality proof.
+	pub fn new(backfill: bool) -> Self {
+		WarpSync {
+			phase: WarpPhase::FinalityProof,
+			backfill,
+			request: None,
+			failed_peers: HashSet::new(),
+			next_request_id: 0,
+		}
+	}
+
+	/// Whether the normal sync must wait until the state is downloaded.
+	pub fn is_downloading_state(&self) -> bool {
+		match self.phase {
+			WarpPhase::FinalityProof | WarpPhase::State(_) => true,
+			WarpPhase::Backfill(..) => false,
+		}
+	}
+
+	/// Register new request to the given peer, returning its id.
+	pub fn new_request(&mut self, who: PeerId) -> RequestId {
+		let id = self.next_request_id;
+		self.next_request_id += 1;
+		self.request = Some((who, id, Instant::now()));
+		id
+	}
+
+	/// Clear the outstanding request if it matches given peer and request id.
+	/// Returns `false` if the response is unexpected.
+	pub fn on_response(&mut self, who: &PeerId, id: RequestId) -> bool {
+		match self.request {
+			Some((ref peer, request_id, _)) if peer == who && request_id == id => {
+				self.request = None;
+				true
+			},
+			_ => false,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use consensus::BlockOrigin;
+	use runtime_primitives::generic::BlockId;
+	use test_client::{self, TestClient};
+	use test_client::runtime::Block;
+
+	#[test]
+	fn state_is_downloaded_in_chunks() {
+		let client = test_client::new();
+		let block = client.new_block().unwrap().bake().unwrap();
+		client.import(BlockOrigin::Own, block.clone()).unwrap();
+		let header = block.header().clone();
+
+		let mut sync = StateSync::<Block>::new(header.clone(), None);
+		let mut requests = 0;
+		loop {
+			let request = sync.next_request(requests);
+			requests += 1;
+			let proof = Client::<Block>::state_range_proof(
+				&client,
+				&request.block,
+				request.child_storage_key.as_ref().map(|key| &key[..]),
+				&request.start,
+				4,
+			).unwrap();
+			match sync.import(&client, proof) {
+				ImportResult::Continue => (),
+				ImportResult::Complete => break,
+				ImportResult::BadResponse => panic!("valid state response is rejected"),
+			}
+		}
+		assert!(requests > 1);
+
+		// invalid proof is rejected
+		let mut other_sync = StateSync::<Block>::new(header.clone(), None);
+		assert_eq!(other_sync.import(&client, vec![vec![42]]), ImportResult::BadResponse);
+
+		let (header, justification, top, children) = sync.into_parts();
+		let other_client = test_client::new();
+		Client::<Block>::import_state(&other_client, header.clone(), justification, top, children).unwrap();
+		assert_eq!(
+			other_client.header(&BlockId::Number(1)).unwrap(),
+			Some(header),
+		);
+	}
+}
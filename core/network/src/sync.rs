@@ -32,8 +32,10 @@
 
 use std::cmp::max;
 use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use log::{debug, trace, info, warn};
+use parity_codec::Encode;
 use crate::protocol::Context;
 use fork_tree::ForkTree;
 use network_libp2p::PeerId;
@@ -42,10 +44,12 @@ use consensus::{BlockOrigin, import_queue::IncomingBlock};
 use client::error::Error as ClientError;
 use crate::blocks::BlockCollection;
 use runtime_primitives::Justification;
-use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, As, NumberFor, Zero, CheckedSub};
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, Hash as HashT, As, NumberFor, Zero, CheckedSub};
 use runtime_primitives::generic::BlockId;
 use crate::message;
-use crate::config::Roles;
+use crate::config::{Roles, SyncMode};
+use crate::chain::FinalityProofProvider;
+use crate::state_sync::{WarpSync, WarpPhase, StateSync, ImportResult as StateImportResult};
 use std::collections::HashSet;
 
 // Maximum blocks to request in a single packet.
@@ -66,6 +70,12 @@ const BLOCKCHAIN_STATUS_READ_ERROR_REPUTATION_CHANGE: i32 = -(1 << 16);
 const ANCESTRY_BLOCK_ERROR_REPUTATION_CHANGE: i32 = -(1 << 9);
 /// Reputation change when a peer sent us a status message with a different genesis than us.
 const GENESIS_MISMATCH_REPUTATION_CHANGE: i32 = i32::min_value() + 1;
+// Time to wait for an answer to a finality proof, state or history request.
+const WARP_REQUEST_TIMEOUT: Duration = Duration::from_secs(40);
+/// Reputation change when a peer doesn't answer a finality proof, state or history request in time.
+const WARP_REQUEST_TIMEOUT_REPUTATION_CHANGE: i32 = -(1 << 10);
+/// Reputation change when a peer sent us an invalid finality proof, state or history block.
+const BAD_WARP_RESPONSE_REPUTATION_CHANGE: i32 = -(1 << 12);
 
 #[derive(Debug)]
 struct PeerSync<B: BlockT> {
@@ -102,6 +112,7 @@ enum PeerSyncState<B: BlockT> {
 	DownloadingNew(NumberFor<B>),
 	DownloadingStale(B::Hash),
 	DownloadingJustification(B::Hash),
+	DownloadingHistory(B::Hash, Instant),
 }
 
 /// Pending justification request for the given block (hash and number).
@@ -373,6 +384,8 @@ pub struct ChainSync<B: BlockT> {
 	justifications: PendingJustifications<B>,
 	queue_blocks: HashSet<B::Hash>,
 	best_importing_number: NumberFor<B>,
+	warp: Option<WarpSync<B>>,
+	finality_proof_provider: Option<Arc<FinalityProofProvider<B>>>,
 }
 
 /// Reported sync state.
@@ -413,15 +426,34 @@ impl<B: BlockT> Status<B> {
 
 impl<B: BlockT> ChainSync<B> {
 	/// Create a new instance. Pass the initial known state of the chain.
+	///
+	/// Warp sync is only started by full nodes that are still at genesis.
 	pub(crate) fn new(
 		role: Roles,
 		info: &ClientInfo<B>,
+		sync_mode: SyncMode,
+		finality_proof_provider: Option<Arc<FinalityProofProvider<B>>>,
 	) -> Self {
 		let mut required_block_attributes = message::BlockAttributes::HEADER | message::BlockAttributes::JUSTIFICATION;
 		if role.intersects(Roles::FULL | Roles::AUTHORITY) {
 			required_block_attributes |= message::BlockAttributes::BODY;
 		}
 
+		let warp = match sync_mode {
+			SyncMode::Warp { backfill } if role.intersects(Roles::FULL | Roles::AUTHORITY)
+				&& info.chain.best_number == Zero::zero() =>
+			{
+				if finality_proof_provider.is_some() {
+					info!("Starting warp sync");
+					Some(WarpSync::new(backfill))
+				} else {
+					warn!("Warp sync requires finality proofs support, falling back to full sync");
+					None
+				}
+			},
+			_ => None,
+		};
+
 		ChainSync {
 			genesis_hash: info.chain.genesis_hash,
 			peers: HashMap::new(),
@@ -432,6 +464,8 @@ impl<B: BlockT> ChainSync<B> {
 			required_block_attributes,
 			queue_blocks: Default::default(),
 			best_importing_number: Zero::zero(),
+			warp,
+			finality_proof_provider,
 		}
 	}
 
@@ -440,6 +474,9 @@ impl<B: BlockT> ChainSync<B> {
 	}
 
 	fn state(&self, best_seen: &Option<NumberFor<B>>) -> SyncState {
+		if self.warp.as_ref().map_or(false, |warp| warp.is_downloading_state()) {
+			return SyncState::Downloading;
+		}
 		match best_seen {
 			&Some(n) if n > self.best_queued_number && n - self.best_queued_number > As::sa(5) => SyncState::Downloading,
 			_ => SyncState::Idle,
@@ -535,6 +572,7 @@ impl<B: BlockT> ChainSync<B> {
 					});
 				}
 			}
+			self.dispatch_warp_request(protocol);
 		}
 	}
 
@@ -664,6 +702,11 @@ impl<B: BlockT> ChainSync<B> {
 						vec![]
 					}
 				},
+				PeerSyncState::DownloadingHistory(..) => {
+					peer.state = PeerSyncState::Available;
+					Self::import_history(&mut self.warp, protocol, who, blocks);
+					Vec::new()
+				},
 				PeerSyncState::Available | PeerSyncState::DownloadingJustification(..) => Vec::new(),
 			}
 		} else {
@@ -762,12 +805,107 @@ impl<B: BlockT> ChainSync<B> {
 			self.download_new(protocol, peer);
 		}
 		self.justifications.dispatch(&mut self.peers, protocol);
+		self.dispatch_warp_request(protocol);
 	}
 
 	/// Called periodically to perform any time-based actions. Must be called at a regular
 	/// interval.
 	pub fn tick(&mut self, protocol: &mut Context<B>) {
 		self.justifications.dispatch(&mut self.peers, protocol);
+		self.dispatch_warp_request(protocol);
+	}
+
+	/// Handle a response from the remote to a finality proof request that we made.
+	pub(crate) fn on_finality_proof_response(
+		&mut self,
+		protocol: &mut Context<B>,
+		who: PeerId,
+		response: message::FinalityProofResponse<B>,
+	) {
+		let warp = match self.warp {
+			Some(ref mut warp) if warp.on_response(&who, response.id) => warp,
+			_ => {
+				trace!(target: "sync", "Unexpected finality proof response {} from {}", response.id, who);
+				return;
+			},
+		};
+		if let WarpPhase::FinalityProof = warp.phase {
+			match response.header {
+				Some(ref header) if *header.number() > self.best_queued_number => {
+					let provider = self.finality_proof_provider.as_ref()
+						.expect("warp sync is only started with finality proof provider; qed");
+					match provider.check_finality_proof(header, response.proof) {
+						Ok(()) => {
+							info!("Downloading state of finalized block #{} ({})", header.number(), header.hash());
+							warp.failed_peers.clear();
+							warp.phase = WarpPhase::State(StateSync::new(header.clone(), response.justification));
+						},
+						Err(e) => {
+							debug!(target: "sync", "Invalid finality proof from {}: {:?}", who, e);
+							protocol.report_peer(who.clone(), BAD_WARP_RESPONSE_REPUTATION_CHANGE);
+							warp.failed_peers.insert(who);
+						},
+					}
+				},
+				_ => {
+					trace!(target: "sync", "Peer {} can't prove finality of any block ahead of us", who);
+					warp.failed_peers.insert(who);
+				},
+			}
+		}
+		self.dispatch_warp_request(protocol);
+	}
+
+	/// Handle a response from the remote to a state request that we made.
+	pub(crate) fn on_state_response(
+		&mut self,
+		protocol: &mut Context<B>,
+		who: PeerId,
+		response: message::StateResponse,
+	) {
+		let warp = match self.warp {
+			Some(ref mut warp) if warp.on_response(&who, response.id) => warp,
+			_ => {
+				trace!(target: "sync", "Unexpected state response {} from {}", response.id, who);
+				return;
+			},
+		};
+		let result = match warp.phase {
+			WarpPhase::State(ref mut state) => state.import(protocol.client(), response.proof),
+			_ => return,
+		};
+		match result {
+			StateImportResult::Continue => (),
+			StateImportResult::BadResponse => {
+				protocol.report_peer(who.clone(), BAD_WARP_RESPONSE_REPUTATION_CHANGE);
+				warp.failed_peers.insert(who);
+			},
+			StateImportResult::Complete => {
+				let state = match ::std::mem::replace(&mut warp.phase, WarpPhase::FinalityProof) {
+					WarpPhase::State(state) => state,
+					_ => unreachable!("phase is checked above; qed"),
+				};
+				let (header, justification, top, children) = state.into_parts();
+				let (number, parent_hash) = (*header.number(), *header.parent_hash());
+				match protocol.client().import_state(header, justification, top, children) {
+					Ok(()) if warp.backfill && number > As::sa(1) => {
+						info!("State of #{} imported, downloading preceding blocks", number);
+						warp.phase = WarpPhase::Backfill(number - As::sa(1), parent_hash);
+					},
+					Ok(()) => {
+						info!("State of #{} imported", number);
+						self.warp = None;
+					},
+					Err(e) => {
+						warn!("Error importing downloaded state: {:?}, falling back to full sync", e);
+						self.warp = None;
+					},
+				}
+				self.restart(protocol);
+				return;
+			},
+		}
+		self.dispatch_warp_request(protocol);
 	}
 
 	/// Request a justification for the given block.
@@ -916,6 +1054,11 @@ impl<B: BlockT> ChainSync<B> {
 	pub(crate) fn peer_disconnected(&mut self, protocol: &mut Context<B>, who: PeerId) {
 		self.blocks.clear_peer_download(&who);
 		self.peers.remove(&who);
+		if let Some(ref mut warp) = self.warp {
+			if warp.request.as_ref().map_or(false, |(peer, _, _)| *peer == who) {
+				warp.request = None;
+			}
+		}
 		self.justifications.peer_disconnected(who);
 		self.maintain_sync(protocol);
 	}
@@ -987,6 +1130,10 @@ impl<B: BlockT> ChainSync<B> {
 
 	// Issue a request for a peer to download new blocks, if any are available
 	fn download_new(&mut self, protocol: &mut Context<B>, who: PeerId) {
+		// new blocks can't be imported until the warp sync has imported the state
+		if self.warp.as_ref().map_or(false, |warp| warp.is_downloading_state()) {
+			return;
+		}
 		if let Some(ref mut peer) = self.peers.get_mut(&who) {
 			// when there are too many blocks in the queue => do not try to download new blocks
 			if self.queue_blocks.len() > MAX_IMPORTING_BLOCKS {
@@ -1017,6 +1164,149 @@ impl<B: BlockT> ChainSync<B> {
 		}
 	}
 
+	// Issue the next finality proof, state or history request of the warp sync, if required.
+	fn dispatch_warp_request(&mut self, protocol: &mut Context<B>) {
+		let warp = match self.warp {
+			Some(ref mut warp) => warp,
+			None => return,
+		};
+
+		if let WarpPhase::Backfill(number, hash) = warp.phase {
+			// history is downloaded from a single peer at a time, and from another
+			// one once the request times out.
+			let mut downloading = false;
+			for (who, peer) in self.peers.iter_mut() {
+				if let PeerSyncState::DownloadingHistory(_, sent) = peer.state {
+					if sent.elapsed() < WARP_REQUEST_TIMEOUT {
+						downloading = true;
+						break;
+					}
+					debug!(target: "sync", "History request to {} timed out", who);
+					protocol.report_peer(who.clone(), WARP_REQUEST_TIMEOUT_REPUTATION_CHANGE);
+					warp.failed_peers.insert(who.clone());
+					protocol.disconnect_peer(who.clone());
+					peer.state = PeerSyncState::Available;
+				}
+			}
+			if downloading {
+				return;
+			}
+			let failed_peers = &warp.failed_peers;
+			let available = self.peers.iter_mut()
+				.find(|(who, peer)| peer.state == PeerSyncState::Available
+					&& peer.best_number >= number
+					&& !failed_peers.contains(*who));
+			if let Some((who, peer)) = available {
+				trace!(target: "sync", "Requesting history blocks from {} starting with #{} ({})", who, number, hash);
+				peer.state = PeerSyncState::DownloadingHistory(hash, Instant::now());
+				protocol.send_block_request(who.clone(), message::generic::BlockRequest {
+					id: 0,
+					fields: self.required_block_attributes.clone(),
+					from: message::FromBlock::Hash(hash),
+					to: None,
+					direction: message::Direction::Descending,
+					max: Some(MAX_BLOCKS_TO_REQUEST as u32),
+				});
+			}
+			return;
+		}
+
+		if let Some((who, _, sent)) = warp.request.clone() {
+			if sent.elapsed() < WARP_REQUEST_TIMEOUT {
+				return;
+			}
+			debug!(target: "sync", "Warp sync request to {} timed out", who);
+			protocol.report_peer(who.clone(), WARP_REQUEST_TIMEOUT_REPUTATION_CHANGE);
+			warp.failed_peers.insert(who);
+			warp.request = None;
+		}
+
+		let min_number = match warp.phase {
+			WarpPhase::State(ref state) => state.number(),
+			_ => As::sa(1),
+		};
+		let eligible: Vec<PeerId> = self.peers.iter()
+			.filter(|(_, peer)| peer.best_number >= min_number)
+			.map(|(who, _)| who.clone())
+			.collect();
+		if eligible.is_empty() {
+			trace!(target: "sync", "No peers to continue warp sync with");
+			return;
+		}
+		let who = match eligible.into_iter().find(|who| !warp.failed_peers.contains(who)) {
+			Some(who) => who,
+			None => {
+				warn!("Warp sync has failed with all peers, falling back to full sync");
+				self.warp = None;
+				self.maintain_sync(protocol);
+				return;
+			},
+		};
+
+		let id = warp.new_request(who.clone());
+		match warp.phase {
+			WarpPhase::FinalityProof => {
+				trace!(target: "sync", "Requesting finality proof from {}", who);
+				protocol.send_finality_proof_request(who, message::FinalityProofRequest { id });
+			},
+			WarpPhase::State(ref state) => {
+				trace!(target: "sync", "Requesting state of #{} from {}", state.number(), who);
+				protocol.send_state_request(who, state.next_request(id));
+			},
+			WarpPhase::Backfill(..) => (),
+		}
+	}
+
+	// Check and import the history blocks preceding the state imported by the warp sync.
+	// `blocks` must be ordered by ascending number.
+	fn import_history(
+		warp: &mut Option<WarpSync<B>>,
+		protocol: &mut Context<B>,
+		who: PeerId,
+		blocks: Vec<message::BlockData<B>>,
+	) {
+		let (mut number, mut hash) = match warp {
+			Some(WarpSync { phase: WarpPhase::Backfill(number, hash), .. }) => (*number, *hash),
+			_ => return,
+		};
+
+		for block in blocks.into_iter().rev() {
+			let valid = match (&block.header, &block.body) {
+				(Some(header), Some(body)) => block.hash == hash
+					&& header.hash() == hash
+					&& *header.number() == number
+					&& *header.extrinsics_root() == <<B::Header as HeaderT>::Hashing as HashT>::ordered_trie_root(
+						body.iter().map(Encode::encode)
+					),
+				_ => false,
+			};
+			if !valid {
+				debug!(target: "sync", "Invalid history block #{} from {}", number, who);
+				protocol.report_peer(who, BAD_WARP_RESPONSE_REPUTATION_CHANGE);
+				break;
+			}
+
+			let header = block.header.expect("checked above; qed");
+			let parent_hash = *header.parent_hash();
+			if let Err(e) = protocol.client().import_history(header, block.body, block.justification) {
+				warn!("Error importing history block #{}: {:?}", number, e);
+				*warp = None;
+				return;
+			}
+			if number == As::sa(1) {
+				info!("History download is complete");
+				*warp = None;
+				return;
+			}
+			number = number - As::sa(1);
+			hash = parent_hash;
+		}
+
+		if let Some(ref mut warp) = warp {
+			warp.phase = WarpPhase::Backfill(number, hash);
+		}
+	}
+
 	fn request_ancestry(protocol: &mut Context<B>, who: PeerId, block: NumberFor<B>) {
 		trace!(target: "sync", "Requesting ancestry block #{} from {}", block, who);
 		let request = message::generic::BlockRequest {
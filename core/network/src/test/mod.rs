@@ -80,6 +80,10 @@ impl<B: BlockT> Verifier<B> for PassThroughVerifier {
 			fork_choice: ForkChoiceStrategy::LongestChain,
 		}, new_authorities))
 	}
+
+	fn can_verify_in_parallel(&self) -> bool {
+		true
+	}
 }
 
 /// A link implementation that does nothing.
@@ -654,6 +658,7 @@ pub trait TestNetFactory: Sized {
 			config.clone(),
 			client.clone(),
 			None,
+			None,
 			tx_pool,
 			specialization,
 		).unwrap();
@@ -40,10 +40,13 @@ impl ChangesTrieConfiguration {
 	}
 
 	/// Do we need to build digest at given block?
-	pub fn is_digest_build_required_at_block(&self, block: u64) -> bool {
-		block != 0
+	///
+	/// `zero` is the number of the block where this configuration has been set. Digests
+	/// are built relative to this block (`zero` is 0 for the genesis configuration).
+	pub fn is_digest_build_required_at_block(&self, zero: u64, block: u64) -> bool {
+		block > zero
 			&& self.is_digest_build_enabled()
-			&& block % self.digest_interval == 0
+			&& (block - zero) % self.digest_interval == 0
 	}
 
 	/// Returns max digest interval. One if digests are not created at all.
@@ -63,11 +66,12 @@ impl ChangesTrieConfiguration {
 	///  digest interval (in blocks)
 	///  step between blocks we're interested in when digest is built
 	/// )
-	pub fn digest_level_at_block(&self, block: u64) -> Option<(u32, u64, u64)> {
-		if !self.is_digest_build_required_at_block(block) {
+	pub fn digest_level_at_block(&self, zero: u64, block: u64) -> Option<(u32, u64, u64)> {
+		if !self.is_digest_build_required_at_block(zero, block) {
 			return None;
 		}
 
+		let block = block - zero;
 		let mut digest_interval = self.digest_interval;
 		let mut current_level = 1u32;
 		let mut digest_step = 1u64;
@@ -112,31 +116,40 @@ mod tests {
 
 	#[test]
 	fn is_digest_build_required_at_block_works() {
-		assert!(!config(8, 4).is_digest_build_required_at_block(0));
-		assert!(!config(8, 4).is_digest_build_required_at_block(1));
-		assert!(!config(8, 4).is_digest_build_required_at_block(2));
-		assert!(!config(8, 4).is_digest_build_required_at_block(4));
-		assert!(config(8, 4).is_digest_build_required_at_block(8));
-		assert!(!config(8, 4).is_digest_build_required_at_block(9));
-		assert!(config(8, 4).is_digest_build_required_at_block(64));
-		assert!(config(8, 4).is_digest_build_required_at_block(64));
-		assert!(config(8, 4).is_digest_build_required_at_block(512));
-		assert!(config(8, 4).is_digest_build_required_at_block(4096));
-		assert!(!config(8, 4).is_digest_build_required_at_block(4103));
-		assert!(config(8, 4).is_digest_build_required_at_block(4104));
-		assert!(!config(8, 4).is_digest_build_required_at_block(4108));
+		assert!(!config(8, 4).is_digest_build_required_at_block(0, 0));
+		assert!(!config(8, 4).is_digest_build_required_at_block(0, 1));
+		assert!(!config(8, 4).is_digest_build_required_at_block(0, 2));
+		assert!(!config(8, 4).is_digest_build_required_at_block(0, 4));
+		assert!(config(8, 4).is_digest_build_required_at_block(0, 8));
+		assert!(!config(8, 4).is_digest_build_required_at_block(0, 9));
+		assert!(config(8, 4).is_digest_build_required_at_block(0, 64));
+		assert!(config(8, 4).is_digest_build_required_at_block(0, 64));
+		assert!(config(8, 4).is_digest_build_required_at_block(0, 512));
+		assert!(config(8, 4).is_digest_build_required_at_block(0, 4096));
+		assert!(!config(8, 4).is_digest_build_required_at_block(0, 4103));
+		assert!(config(8, 4).is_digest_build_required_at_block(0, 4104));
+		assert!(!config(8, 4).is_digest_build_required_at_block(0, 4108));
 	}
 
 	#[test]
 	fn digest_level_at_block_works() {
-		assert_eq!(config(8, 4).digest_level_at_block(0), None);
-		assert_eq!(config(8, 4).digest_level_at_block(7), None);
-		assert_eq!(config(8, 4).digest_level_at_block(63), None);
-		assert_eq!(config(8, 4).digest_level_at_block(8), Some((1, 8, 1)));
-		assert_eq!(config(8, 4).digest_level_at_block(64), Some((2, 64, 8)));
-		assert_eq!(config(8, 4).digest_level_at_block(512), Some((3, 512, 64)));
-		assert_eq!(config(8, 4).digest_level_at_block(4096), Some((4, 4096, 512)));
-		assert_eq!(config(8, 4).digest_level_at_block(4112), Some((1, 8, 1)));
+		assert_eq!(config(8, 4).digest_level_at_block(0, 0), None);
+		assert_eq!(config(8, 4).digest_level_at_block(0, 7), None);
+		assert_eq!(config(8, 4).digest_level_at_block(0, 63), None);
+		assert_eq!(config(8, 4).digest_level_at_block(0, 8), Some((1, 8, 1)));
+		assert_eq!(config(8, 4).digest_level_at_block(0, 64), Some((2, 64, 8)));
+		assert_eq!(config(8, 4).digest_level_at_block(0, 512), Some((3, 512, 64)));
+		assert_eq!(config(8, 4).digest_level_at_block(0, 4096), Some((4, 4096, 512)));
+		assert_eq!(config(8, 4).digest_level_at_block(0, 4112), Some((1, 8, 1)));
+	}
+
+	#[test]
+	fn digest_is_built_relative_to_configuration_zero() {
+		assert!(!config(8, 4).is_digest_build_required_at_block(5, 5));
+		assert!(!config(8, 4).is_digest_build_required_at_block(5, 8));
+		assert!(config(8, 4).is_digest_build_required_at_block(5, 13));
+		assert_eq!(config(8, 4).digest_level_at_block(5, 13), Some((1, 8, 1)));
+		assert_eq!(config(8, 4).digest_level_at_block(5, 69), Some((2, 64, 8)));
 	}
 
 	#[test]
@@ -147,6 +147,60 @@ pub struct Entry {
 	pub entity: ExternEntity,
 }
 
+/// A feature of WebAssembly that the sandboxed code can be denied.
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum WasmFeature {
+	/// Floating point value types and instructions.
+	#[codec(index = "1")]
+	FloatingPoint,
+
+	/// The `grow_memory` instruction.
+	#[codec(index = "2")]
+	GrowMemory,
+
+	/// A `start` function.
+	#[codec(index = "3")]
+	StartFunction,
+
+	/// Linear memories declared by the module itself rather than imported.
+	#[codec(index = "4")]
+	InternalMemory,
+
+	/// Tables, element segments and indirect calls.
+	#[codec(index = "5")]
+	Tables,
+}
+
+/// Two-level name of an import.
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ImportName {
+	/// Module name of the import.
+	pub module_name: Vec<u8>,
+	/// Field name of the import.
+	pub field_name: Vec<u8>,
+}
+
+/// Limits of a sandboxed instance, enforced by the executor.
+///
+/// They are passed to `ext_sandbox_instantiate_with_limits` next to the `EnvironmentDefinition`.
+/// The default imposes no limits.
+#[derive(Clone, Default, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct InstanceLimits {
+	/// Fuel available to each invocation of the instance (and to the `start` function).
+	/// Every executed instruction consumes a unit of fuel. `None` disables the metering.
+	pub fuel: Option<u64>,
+	/// Maximum height of the stack of the sandboxed code, in the units of the `pwasm-utils`
+	/// stack limiter. `None` leaves only the limit of the executor itself.
+	pub max_stack_height: Option<u32>,
+	/// Features the module is not allowed to use.
+	pub denied_features: Vec<WasmFeature>,
+	/// Imports the module is not allowed to have, even if the environment defines them.
+	pub denied_imports: Vec<ImportName>,
+}
+
 /// Definition of runtime that could be used by sandboxed code.
 #[derive(Clone, PartialEq, Eq, Encode, Decode)]
 #[cfg_attr(feature = "std", derive(Debug))]
@@ -180,6 +234,347 @@ pub const ERR_OUT_OF_BOUNDS: u32 = -2i32 as u32;
 /// For FFI purposes.
 pub const ERR_EXECUTION: u32 = -3i32 as u32;
 
+/// Execution ran out of the fuel given by `InstanceLimits::fuel`.
+///
+/// For FFI purposes.
+pub const ERR_OUT_OF_FUEL: u32 = -4i32 as u32;
+
+/// Execution exceeded the stack height limit.
+///
+/// For FFI purposes.
+pub const ERR_STACK_OVERFLOW: u32 = -5i32 as u32;
+
+/// The module has an import denied by `InstanceLimits::denied_imports`.
+///
+/// For FFI purposes.
+pub const ERR_FORBIDDEN_IMPORT: u32 = -6i32 as u32;
+
+/// The module uses a feature denied by `InstanceLimits::denied_features`.
+///
+/// For FFI purposes.
+pub const ERR_FORBIDDEN_FEATURE: u32 = -7i32 as u32;
+
+#[cfg(feature = "std")]
+pub use self::limits::{prepare_module, ModuleRejection, FuelMeter, TrapReason, trap_reason, fuel_func, FUEL_FUNC_INDEX};
+
+/// Enforcement of `InstanceLimits` shared by the executor and the native sandbox.
+#[cfg(feature = "std")]
+mod limits {
+	use std::fmt;
+	use parity_wasm::elements::{self, External, Instruction, Internal, ValueType};
+	use wasmi::{FuncInstance, FuncRef, ModuleRef, NopExternals, RuntimeArgs, RuntimeValue, Signature, Trap, TrapKind};
+	use super::{InstanceLimits, ImportName, WasmFeature};
+
+	/// Name of the function exported from the modules with a stack height limit. It returns
+	/// the current stack height and resets it to zero.
+	const TAKE_STACK_HEIGHT_EXPORT: &str = "__sandbox_take_stack_height";
+
+	/// Module and field name of the function the metered code calls to consume fuel. Modules
+	/// importing anything else from this module are refused.
+	const FUEL_MODULE: &str = "__sandbox";
+	const FUEL_FIELD: &str = "fuel";
+
+	/// Host function index of the fuel function, reserved in the index space of the functions
+	/// resolved for the sandboxed code.
+	pub const FUEL_FUNC_INDEX: usize = usize::max_value();
+
+	/// Reason to refuse a module.
+	#[derive(Debug, PartialEq)]
+	pub enum ModuleRejection {
+		/// The module isn't a valid WebAssembly binary.
+		Invalid,
+		/// The module uses a denied feature.
+		ForbiddenFeature(WasmFeature),
+		/// The module has a denied import.
+		ForbiddenImport(ImportName),
+		/// The module couldn't be instrumented for the stack height limit.
+		Instrumentation,
+	}
+
+	/// Reason of a trap of a sandboxed instance.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub enum TrapReason {
+		/// The fuel given by the limits ran out.
+		OutOfFuel,
+		/// The stack height limit was exceeded.
+		StackOverflow,
+		/// Any other trap.
+		Other,
+	}
+
+	/// Host error the sandboxed code traps with when it runs out of fuel.
+	#[derive(Debug)]
+	struct OutOfFuel;
+
+	impl fmt::Display for OutOfFuel {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			write!(f, "Sandboxed code ran out of fuel")
+		}
+	}
+
+	impl wasmi::HostError for OutOfFuel {}
+
+	/// Fuel left to an invocation of a sandboxed instance.
+	pub struct FuelMeter {
+		left: Option<u64>,
+	}
+
+	impl FuelMeter {
+		/// A meter with all the fuel given by `limits`.
+		pub fn new(limits: &InstanceLimits) -> Self {
+			FuelMeter {
+				left: limits.fuel,
+			}
+		}
+
+		/// Handle a call of the metered code to the fuel function, which passes the number
+		/// of instructions in the block it's about to execute. Traps if there isn't enough
+		/// fuel left.
+		pub fn charge(&mut self, args: RuntimeArgs) -> Result<Option<RuntimeValue>, Trap> {
+			let amount: u32 = args.nth_checked(0)?;
+			if let Some(left) = self.left {
+				match left.checked_sub(amount as u64) {
+					Some(left) => self.left = Some(left),
+					None => {
+						self.left = Some(0);
+						return Err(TrapKind::Host(Box::new(OutOfFuel)).into());
+					}
+				}
+			}
+			Ok(None)
+		}
+	}
+
+	/// The function resolved for the import of the fuel function, if the import is one.
+	///
+	/// The resolved function has the `FUEL_FUNC_INDEX`, which must be dispatched to the
+	/// `FuelMeter` of the invocation.
+	pub fn fuel_func(module_name: &str, field_name: &str, signature: &Signature) -> Option<FuncRef> {
+		if module_name != FUEL_MODULE || field_name != FUEL_FIELD {
+			return None;
+		}
+		if signature.params() != &[wasmi::ValueType::I32][..] || signature.return_type().is_some() {
+			return None;
+		}
+		Some(FuncInstance::alloc_host(signature.clone(), FUEL_FUNC_INDEX))
+	}
+
+	/// Check the module in `wasm` against `limits` and instrument it for the fuel metering
+	/// and the stack height limit.
+	pub fn prepare_module(wasm: &[u8], limits: &InstanceLimits) -> Result<wasmi::Module, ModuleRejection> {
+		if *limits == InstanceLimits::default() {
+			return wasmi::Module::from_buffer(wasm).map_err(|_| ModuleRejection::Invalid);
+		}
+
+		let module = elements::deserialize_buffer::<elements::Module>(wasm)
+			.map_err(|_| ModuleRejection::Invalid)?;
+		for feature in &limits.denied_features {
+			if uses_feature(&module, *feature) {
+				return Err(ModuleRejection::ForbiddenFeature(*feature));
+			}
+		}
+		for import in module.import_section().map(|section| section.entries()).unwrap_or(&[]) {
+			let denied = limits.denied_imports.iter().find(|denied|
+				denied.module_name == import.module().as_bytes() && denied.field_name == import.field().as_bytes()
+			);
+			if let Some(denied) = denied {
+				return Err(ModuleRejection::ForbiddenImport(denied.clone()));
+			}
+			if import.module() == FUEL_MODULE {
+				return Err(ModuleRejection::Invalid);
+			}
+		}
+
+		let module = match limits.fuel {
+			Some(_) => {
+				// The injector expects a valid module.
+				wasmi::Module::from_parity_wasm_module(module.clone()).map_err(|_| ModuleRejection::Invalid)?;
+				let module = pwasm_utils::inject_gas_counter(module, &Default::default())
+					.map_err(|_| ModuleRejection::Instrumentation)?;
+				rename_fuel_import(module)?
+			}
+			None => module,
+		};
+		let module = match limits.max_stack_height {
+			Some(max_stack_height) => {
+				// The limiter expects a valid module.
+				wasmi::Module::from_parity_wasm_module(module.clone()).map_err(|_| ModuleRejection::Invalid)?;
+				let module = pwasm_utils::stack_height::inject_limiter(module, max_stack_height)
+					.map_err(|_| ModuleRejection::Instrumentation)?;
+				export_take_stack_height(module)?
+			}
+			None => module,
+		};
+		wasmi::Module::from_parity_wasm_module(module).map_err(|_| ModuleRejection::Invalid)
+	}
+
+	/// The reason of `error` returned by an invocation of `instance` created with `limits`.
+	///
+	/// Also resets the stack height of the instance, which the trap leaves behind.
+	pub fn trap_reason(instance: &ModuleRef, limits: &InstanceLimits, error: &wasmi::Error) -> TrapReason {
+		let stack_height = match limits.max_stack_height {
+			Some(_) => instance.invoke_export(TAKE_STACK_HEIGHT_EXPORT, &[], &mut NopExternals).ok(),
+			None => None,
+		};
+		let trap = match error {
+			wasmi::Error::Trap(trap) => trap,
+			_ => return TrapReason::Other,
+		};
+		match trap.kind() {
+			TrapKind::Host(host_error) if host_error.downcast_ref::<OutOfFuel>().is_some() =>
+				TrapReason::OutOfFuel,
+			TrapKind::StackOverflow => TrapReason::StackOverflow,
+			TrapKind::Unreachable => match (stack_height, limits.max_stack_height) {
+				// The limiter traps with `unreachable` once the height goes over the limit.
+				(Some(Some(RuntimeValue::I32(height))), Some(max)) if height as u32 > max =>
+					TrapReason::StackOverflow,
+				_ => TrapReason::Other,
+			},
+			_ => TrapReason::Other,
+		}
+	}
+
+	/// Move the function imported by the gas counter injection, which every metered block
+	/// calls with its number of instructions, to the reserved fuel module, so that it can't
+	/// clash with the imports of the module.
+	fn rename_fuel_import(mut module: elements::Module) -> Result<elements::Module, ModuleRejection> {
+		// The injection appends the import to the import section.
+		let entry = module.import_section_mut()
+			.and_then(|section| section.entries_mut().last_mut())
+			.ok_or(ModuleRejection::Instrumentation)?;
+		let signature = match entry.external() {
+			External::Function(signature) => *signature,
+			_ => return Err(ModuleRejection::Instrumentation),
+		};
+		*entry = elements::ImportEntry::new(FUEL_MODULE.into(), FUEL_FIELD.into(), External::Function(signature));
+		Ok(module)
+	}
+
+	/// Export a function returning the stack height global injected by the limiter and
+	/// resetting it, so that the overflow can be told from the other traps.
+	fn export_take_stack_height(mut module: elements::Module) -> Result<elements::Module, ModuleRejection> {
+		// The limiter appends the global to the globals of the module. The sandbox can't
+		// import globals, so that's the last index of the global index space.
+		let stack_height_global = module.global_section()
+			.and_then(|section| section.entries().len().checked_sub(1))
+			.ok_or(ModuleRejection::Instrumentation)? as u32;
+
+		let imported_functions = count_imports(&module, |external| match external {
+			External::Function(_) => true,
+			_ => false,
+		});
+		let functions = module.function_section().map(|section| section.entries().len()).unwrap_or(0);
+		let function_index = (imported_functions + functions) as u32;
+
+		let mut builder = parity_wasm::builder::from_module(module);
+		builder.push_function(
+			parity_wasm::builder::function()
+				.signature().with_return_type(Some(ValueType::I32)).build()
+				.body()
+					.with_instructions(elements::Instructions::new(vec![
+						Instruction::GetGlobal(stack_height_global),
+						Instruction::I32Const(0),
+						Instruction::SetGlobal(stack_height_global),
+						Instruction::End,
+					]))
+					.build()
+				.build()
+		);
+		module = builder.build();
+
+		let export = elements::ExportEntry::new(
+			TAKE_STACK_HEIGHT_EXPORT.into(),
+			Internal::Function(function_index),
+		);
+		match module.export_section_mut() {
+			Some(section) => section.entries_mut().push(export),
+			None => module.sections_mut().push(
+				elements::Section::Export(elements::ExportSection::with_entries(vec![export]))
+			),
+		}
+		Ok(module)
+	}
+
+	fn count_imports(module: &elements::Module, kind: impl Fn(&External) -> bool) -> usize {
+		module.import_section()
+			.map(|section| section.entries().iter().filter(|entry| kind(entry.external())).count())
+			.unwrap_or(0)
+	}
+
+	fn uses_feature(module: &elements::Module, feature: WasmFeature) -> bool {
+		let instructions = || module.code_section()
+			.map(|section| section.bodies())
+			.unwrap_or(&[])
+			.iter()
+			.flat_map(|body| body.code().elements());
+		match feature {
+			WasmFeature::FloatingPoint => uses_float_types(module) || instructions().any(is_float_instruction),
+			WasmFeature::GrowMemory => instructions().any(|instruction| match instruction {
+				Instruction::GrowMemory(_) => true,
+				_ => false,
+			}),
+			WasmFeature::StartFunction => module.start_section().is_some(),
+			WasmFeature::InternalMemory => module.memory_section()
+				.map_or(false, |section| !section.entries().is_empty()),
+			WasmFeature::Tables =>
+				module.table_section().map_or(false, |section| !section.entries().is_empty())
+					|| module.elements_section().map_or(false, |section| !section.entries().is_empty())
+					|| count_imports(module, |external| match external {
+						External::Table(_) => true,
+						_ => false,
+					}) > 0
+					|| instructions().any(|instruction| match instruction {
+						Instruction::CallIndirect(_, _) => true,
+						_ => false,
+					}),
+		}
+	}
+
+	fn uses_float_types(module: &elements::Module) -> bool {
+		let is_float = |value_type: &ValueType| match value_type {
+			ValueType::F32 | ValueType::F64 => true,
+			_ => false,
+		};
+		let signatures = module.type_section().map(|section| section.types()).unwrap_or(&[])
+			.iter()
+			.any(|elements::Type::Function(signature)|
+				signature.params().iter().any(is_float) || signature.return_type().as_ref().map_or(false, is_float)
+			);
+		let locals = module.code_section().map(|section| section.bodies()).unwrap_or(&[])
+			.iter()
+			.any(|body| body.locals().iter().any(|local| is_float(&local.value_type())));
+		let globals = module.global_section().map(|section| section.entries()).unwrap_or(&[])
+			.iter()
+			.any(|global| is_float(&global.global_type().content_type()));
+		let imported_globals = module.import_section().map(|section| section.entries()).unwrap_or(&[])
+			.iter()
+			.any(|entry| match entry.external() {
+				External::Global(global_type) => is_float(&global_type.content_type()),
+				_ => false,
+			});
+		signatures || locals || globals || imported_globals
+	}
+
+	fn is_float_instruction(instruction: &Instruction) -> bool {
+		use self::Instruction::*;
+		match instruction {
+			F32Load(_, _) | F64Load(_, _) | F32Store(_, _) | F64Store(_, _) | F32Const(_) | F64Const(_) |
+			F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge |
+			F64Eq | F64Ne | F64Lt | F64Gt | F64Le | F64Ge |
+			F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt |
+			F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Copysign |
+			F64Abs | F64Neg | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt |
+			F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign |
+			I32TruncSF32 | I32TruncUF32 | I32TruncSF64 | I32TruncUF64 |
+			I64TruncSF32 | I64TruncUF32 | I64TruncSF64 | I64TruncUF64 |
+			F32ConvertSI32 | F32ConvertUI32 | F32ConvertSI64 | F32ConvertUI64 | F32DemoteF64 |
+			F64ConvertSI32 | F64ConvertUI32 | F64ConvertSI64 | F64ConvertUI64 | F64PromoteF32 |
+			I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => true,
+			_ => false,
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -217,4 +612,21 @@ mod tests {
 			],
 		});
 	}
+
+	#[test]
+	fn limits_roundtrip() {
+		roundtrip(InstanceLimits::default());
+
+		roundtrip(InstanceLimits {
+			fuel: Some(1_000),
+			max_stack_height: Some(64 * 1024),
+			denied_features: vec![WasmFeature::FloatingPoint, WasmFeature::Tables],
+			denied_imports: vec![
+				ImportName {
+					module_name: b"env"[..].into(),
+					field_name: b"ext_println"[..].into(),
+				},
+			],
+		});
+	}
 }
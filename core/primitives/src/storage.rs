@@ -58,6 +58,11 @@ pub mod well_known_keys {
 	/// The type of this value is encoded `u64`.
 	pub const HEAP_PAGES: &'static [u8] = b":heappages";
 
+	/// The largest allocation the runtime may request from the reclaiming heap allocator.
+	///
+	/// The type of this value is encoded `u32`. Defaults to 16 MiB.
+	pub const MAX_ALLOCATION_SIZE: &'static [u8] = b":max_allocation_size";
+
 	/// Number of authorities.
 	///
 	/// The type of this value is encoded `u32`. Required by substrate.
@@ -33,11 +33,14 @@ pub type HttpServer = http::Server;
 pub type WsServer = ws::Server;
 
 /// Construct rpc `IoHandler`
-pub fn rpc_handler<Block: BlockT, ExHash, S, C, A, Y>(
+///
+/// The `engine` API is only registered if given.
+pub fn rpc_handler<Block: BlockT, ExHash, S, C, A, Y, E>(
 	state: S,
 	chain: C,
 	author: A,
 	system: Y,
+	engine: Option<E>,
 ) -> RpcHandler where
 	Block: BlockT + 'static,
 	ExHash: Send + Sync + 'static + sr_primitives::Serialize + sr_primitives::DeserializeOwned,
@@ -45,12 +48,16 @@ pub fn rpc_handler<Block: BlockT, ExHash, S, C, A, Y>(
 	C: apis::chain::ChainApi<NumberFor<Block>, Block::Hash, Block::Header, SignedBlock<Block>, Metadata=Metadata>,
 	A: apis::author::AuthorApi<ExHash, Block::Hash, Metadata=Metadata>,
 	Y: apis::system::SystemApi<Block::Hash, NumberFor<Block>>,
+	E: apis::engine::EngineApi<Block::Hash>,
 {
 	let mut io = pubsub::PubSubHandler::default();
 	io.extend_with(state.to_delegate());
 	io.extend_with(chain.to_delegate());
 	io.extend_with(author.to_delegate());
 	io.extend_with(system.to_delegate());
+	if let Some(engine) = engine {
+		io.extend_with(engine.to_delegate());
+	}
 	io
 }
 
@@ -19,7 +19,7 @@
 use std::sync::Arc;
 
 use log::warn;
-use client::{self, Client};
+use client::{self, Client, runtime_api::TransactionPaymentApi};
 use parity_codec::{Encode, Decode};
 use transaction_pool::{
 	txpool::{
@@ -35,7 +35,7 @@ use jsonrpc_derive::rpc;
 use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
 use primitives::{Bytes, Blake2Hasher, H256};
 use crate::rpc::futures::{Sink, Stream, Future};
-use runtime_primitives::{generic, traits};
+use runtime_primitives::{generic, traits::{self, ProvideRuntimeApi}, weights::TransactionFee};
 use crate::subscriptions::Subscriptions;
 
 pub mod error;
@@ -59,6 +59,11 @@ pub trait AuthorApi<Hash, BlockHash> {
 	#[rpc(name = "author_pendingExtrinsics")]
 	fn pending_extrinsics(&self) -> Result<Vec<Bytes>>;
 
+	/// Returns the weight and the fee of a hex-encoded extrinsic if it were included in the block
+	/// after the given one (the best block by default). `None` if the extrinsic pays no fee.
+	#[rpc(name = "author_queryFee")]
+	fn query_fee(&self, extrinsic: Bytes, hash: Option<BlockHash>) -> Result<Option<TransactionFee>>;
+
 	/// Submit an extrinsic to watch.
 	#[pubsub(subscription = "author_extrinsicUpdate", subscribe, name = "author_submitAndWatchExtrinsic")]
 	fn watch_extrinsic(&self, metadata: Self::Metadata, subscriber: Subscriber<Status<Hash, BlockHash>>, bytes: Bytes);
@@ -99,7 +104,10 @@ impl<B, E, P, RA> AuthorApi<ExHash<P>, BlockHash<P>> for Author<B, E, P, RA> whe
 	P: PoolChainApi + Sync + Send + 'static,
 	P::Block: traits::Block<Hash=H256>,
 	P::Error: 'static,
-	RA: Send + Sync + 'static
+	RA: Send + Sync + 'static,
+	Client<B, E, <P as PoolChainApi>::Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, <P as PoolChainApi>::Block, RA> as ProvideRuntimeApi>::Api:
+		TransactionPaymentApi<<P as PoolChainApi>::Block>,
 {
 	type Metadata = crate::metadata::Metadata;
 
@@ -118,6 +126,18 @@ impl<B, E, P, RA> AuthorApi<ExHash<P>, BlockHash<P>> for Author<B, E, P, RA> whe
 		Ok(self.pool.ready().map(|tx| tx.data.encode().into()).collect())
 	}
 
+	fn query_fee(&self, ext: Bytes, hash: Option<BlockHash<P>>) -> Result<Option<TransactionFee>> {
+		let xt = <<P as PoolChainApi>::Block as traits::Block>::Extrinsic::decode(&mut &ext[..])
+			.ok_or(error::Error::from(error::ErrorKind::BadFormat))?;
+		let block_hash = match hash {
+			Some(hash) => hash,
+			None => self.client.info()?.chain.best_hash,
+		};
+		self.client.runtime_api()
+			.query_fee(&generic::BlockId::hash(block_hash), xt)
+			.map_err(Into::into)
+	}
+
 	fn watch_extrinsic(&self, _metadata: Self::Metadata, subscriber: Subscriber<Status<ExHash<P>, BlockHash<P>>>, xt: Bytes) {
 		let submit = || -> Result<_> {
 			let best_block_hash = self.client.info()?.chain.best_hash;
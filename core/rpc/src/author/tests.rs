@@ -137,3 +137,36 @@ fn should_return_pending_extrinsics() {
 		Ok(ref expected) if *expected == vec![Bytes(ex.encode())]
 	);
 }
+
+#[test]
+fn query_fee_should_reject_bad_format() {
+	let runtime = runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let p = Author {
+		client: client.clone(),
+		pool: Arc::new(Pool::new(Default::default(), ChainApi::new(client))),
+		subscriptions: Subscriptions::new(runtime.executor()),
+	};
+
+	assert_matches!(
+		AuthorApi::query_fee(&p, vec![0xff].into(), None),
+		Err(error::Error(error::ErrorKind::BadFormat, _))
+	);
+}
+
+#[test]
+fn query_fee_should_call_the_runtime_api() {
+	let runtime = runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let p = Author {
+		client: client.clone(),
+		pool: Arc::new(Pool::new(Default::default(), ChainApi::new(client))),
+		subscriptions: Subscriptions::new(runtime.executor()),
+	};
+
+	// the test runtime doesn't charge fees.
+	assert_matches!(
+		AuthorApi::query_fee(&p, uxt(AccountKeyring::Alice, 0).encode().into(), None),
+		Ok(None)
+	);
+}
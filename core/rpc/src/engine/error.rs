@@ -0,0 +1,57 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Engine RPC module errors.
+
+use error_chain::*;
+
+use crate::rpc;
+
+error_chain! {
+	errors {
+		/// The node doesn't run a consensus engine which takes commands.
+		NotEnabled {
+			description("manual sealing is not enabled"),
+			display("The node does not run a manual-seal consensus engine"),
+		}
+		/// The engine failed to carry out the command.
+		Sealing(e: String) {
+			description("sealing error"),
+			display("Sealing error: {}", e),
+		}
+	}
+}
+
+/// Base code for all engine errors.
+const BASE_ERROR: i64 = 3000;
+
+impl From<Error> for rpc::Error {
+	fn from(e: Error) -> Self {
+		match e {
+			Error(ErrorKind::NotEnabled, _) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 1),
+				message: "Manual sealing is not enabled".into(),
+				data: None,
+			},
+			Error(ErrorKind::Sealing(e), _) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 2),
+				message: e,
+				data: None,
+			},
+			e => crate::errors::internal(e),
+		}
+	}
+}
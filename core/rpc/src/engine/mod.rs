@@ -0,0 +1,157 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Substrate consensus engine API, controlling block authorship directly.
+//!
+//! The calls are forwarded to a manual-seal consensus engine, if the node runs
+//! one, and fail otherwise. The API is only exposed with the unsafe RPC methods.
+
+pub mod error;
+
+#[cfg(test)]
+mod tests;
+
+use std::sync::Arc;
+use jsonrpc_derive::rpc;
+use parking_lot::Mutex;
+use serde::{Serialize, Deserialize};
+use crate::rpc::{BoxFuture, futures::{Future, sync::{mpsc, oneshot}}};
+
+use self::error::{Error, ErrorKind};
+
+/// Sender of the result of an engine command.
+pub type EngineSender<T> = oneshot::Sender<::std::result::Result<T, String>>;
+
+/// A command for the consensus engine, as received through the RPC.
+pub enum EngineCommand<Hash> {
+	/// Author a new block.
+	CreateBlock {
+		/// The block to build on. The best block if `None`.
+		parent_hash: Option<Hash>,
+		/// Whether to finalize the block right away.
+		finalize: bool,
+		/// Sender of the outcome.
+		sender: EngineSender<CreatedBlock<Hash>>,
+	},
+	/// Finalize a block, and all its ancestors.
+	FinalizeBlock {
+		/// The block to finalize.
+		hash: Hash,
+		/// Sender of the outcome.
+		sender: EngineSender<()>,
+	},
+}
+
+/// Stream of engine commands.
+pub type EngineCommandStream<Hash> = mpsc::UnboundedReceiver<EngineCommand<Hash>>;
+
+/// A block authored on an `engine_createBlock` call.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedBlock<Hash> {
+	/// Hash of the block.
+	pub hash: Hash,
+}
+
+/// Shared sink of engine commands, connecting the RPC with the engine.
+pub struct EngineCommands<Hash> {
+	sink: Arc<Mutex<Option<mpsc::UnboundedSender<EngineCommand<Hash>>>>>,
+}
+
+impl<Hash> Clone for EngineCommands<Hash> {
+	fn clone(&self) -> Self {
+		EngineCommands { sink: self.sink.clone() }
+	}
+}
+
+impl<Hash> Default for EngineCommands<Hash> {
+	fn default() -> Self {
+		EngineCommands { sink: Default::default() }
+	}
+}
+
+impl<Hash> EngineCommands<Hash> {
+	/// Return the stream of commands for the engine.
+	///
+	/// There is only a single engine: commands are sent to the stream which was
+	/// returned last.
+	pub fn stream(&self) -> EngineCommandStream<Hash> {
+		let (sink, stream) = mpsc::unbounded();
+		*self.sink.lock() = Some(sink);
+		stream
+	}
+
+	fn send<T: Send + 'static>(
+		&self,
+		command: impl FnOnce(EngineSender<T>) -> EngineCommand<Hash>,
+	) -> BoxFuture<T> {
+		let (sender, receiver) = oneshot::channel();
+		let sent = match *self.sink.lock() {
+			Some(ref sink) => sink.unbounded_send(command(sender)).is_ok(),
+			None => false,
+		};
+
+		if !sent {
+			return Box::new(crate::rpc::futures::future::err(Error::from(ErrorKind::NotEnabled).into()));
+		}
+
+		Box::new(receiver.then(|res| match res {
+			Ok(Ok(res)) => Ok(res),
+			Ok(Err(e)) => Err(Error::from(ErrorKind::Sealing(e)).into()),
+			// the engine was stopped before answering.
+			Err(_) => Err(Error::from(ErrorKind::NotEnabled).into()),
+		}))
+	}
+}
+
+/// Substrate consensus engine RPC API
+#[rpc]
+pub trait EngineApi<Hash> {
+	/// Author a new block on top of the given one, or the best block, and
+	/// finalize it if requested.
+	#[rpc(name = "engine_createBlock")]
+	fn create_block(&self, parent_hash: Option<Hash>, finalize: Option<bool>) -> BoxFuture<CreatedBlock<Hash>>;
+
+	/// Finalize the given block. Returns `true` on success.
+	#[rpc(name = "engine_finalizeBlock")]
+	fn finalize_block(&self, hash: Hash) -> BoxFuture<bool>;
+}
+
+/// Consensus engine API
+pub struct Engine<Hash> {
+	commands: EngineCommands<Hash>,
+}
+
+impl<Hash> Engine<Hash> {
+	/// Create new instance of the engine API, forwarding calls to the given commands sink.
+	pub fn new(commands: EngineCommands<Hash>) -> Self {
+		Engine { commands }
+	}
+}
+
+impl<Hash: Send + 'static> EngineApi<Hash> for Engine<Hash> {
+	fn create_block(&self, parent_hash: Option<Hash>, finalize: Option<bool>) -> BoxFuture<CreatedBlock<Hash>> {
+		self.commands.send(|sender| EngineCommand::CreateBlock {
+			parent_hash,
+			finalize: finalize.unwrap_or(false),
+			sender,
+		})
+	}
+
+	fn finalize_block(&self, hash: Hash) -> BoxFuture<bool> {
+		Box::new(self.commands.send(|sender| EngineCommand::FinalizeBlock { hash, sender }).map(|()| true))
+	}
+}
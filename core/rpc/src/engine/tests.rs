@@ -0,0 +1,67 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::*;
+
+use assert_matches::assert_matches;
+use crate::rpc::{self, futures::Stream};
+use primitives::H256;
+
+#[test]
+fn should_fail_without_engine() {
+	let engine = Engine::<H256>::new(Default::default());
+
+	assert_matches!(
+		engine.create_block(None, None).wait(),
+		Err(ref e) if e.code == rpc::ErrorCode::ServerError(3001)
+	);
+	assert_matches!(
+		engine.finalize_block(H256::repeat_byte(1)).wait(),
+		Err(ref e) if e.code == rpc::ErrorCode::ServerError(3001)
+	);
+}
+
+#[test]
+fn should_forward_commands_to_engine() {
+	let commands = EngineCommands::default();
+	let engine = Engine::new(commands.clone());
+	let mut stream = commands.stream().wait();
+
+	let parent = H256::repeat_byte(1);
+	let created = engine.create_block(Some(parent), Some(true));
+	match stream.next() {
+		Some(Ok(EngineCommand::CreateBlock { parent_hash, finalize, sender })) => {
+			assert_eq!(parent_hash, Some(parent));
+			assert!(finalize);
+			sender.send(Ok(CreatedBlock { hash: H256::repeat_byte(2) })).unwrap();
+		},
+		_ => panic!("expected a create block command"),
+	}
+	assert_eq!(created.wait().unwrap(), CreatedBlock { hash: H256::repeat_byte(2) });
+
+	let finalized = engine.finalize_block(parent);
+	match stream.next() {
+		Some(Ok(EngineCommand::FinalizeBlock { hash, sender })) => {
+			assert_eq!(hash, parent);
+			sender.send(Err("unknown block".into())).unwrap();
+		},
+		_ => panic!("expected a finalize block command"),
+	}
+	assert_matches!(
+		finalized.wait(),
+		Err(ref e) if e.code == rpc::ErrorCode::ServerError(3002) && e.message == "unknown block"
+	);
+}
@@ -25,6 +25,14 @@ pub fn unimplemented() -> rpc::Error {
 	}
 }
 
+pub fn unsafe_method(name: &str) -> rpc::Error {
+	rpc::Error {
+		code: rpc::ErrorCode::MethodNotFound,
+		message: format!("Method {} is unsafe and unsafe RPC methods are disabled", name),
+		data: None,
+	}
+}
+
 pub fn internal<E: ::std::fmt::Debug>(e: E) -> rpc::Error {
 	warn!("Unknown error: {:?}", e);
 	rpc::Error {
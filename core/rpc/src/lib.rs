@@ -26,6 +26,7 @@ pub use subscriptions::Subscriptions;
 
 pub mod author;
 pub mod chain;
+pub mod engine;
 pub mod metadata;
 pub mod state;
 pub mod system;
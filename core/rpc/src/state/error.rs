@@ -35,6 +35,11 @@ error_chain! {
 			description("not implemented yet"),
 			display("Method Not Implemented"),
 		}
+		/// Unsafe method called while unsafe methods are disabled.
+		UnsafeMethod(name: String) {
+			description("unsafe method"),
+			display("Method {} is unsafe and unsafe RPC methods are disabled", name),
+		}
 	}
 }
 
@@ -42,6 +47,7 @@ impl From<Error> for rpc::Error {
 	fn from(e: Error) -> Self {
 		match e {
 			Error(ErrorKind::Unimplemented, _) => errors::unimplemented(),
+			Error(ErrorKind::UnsafeMethod(name), _) => errors::unsafe_method(&name),
 			e => errors::internal(e),
 		}
 	}
@@ -23,8 +23,8 @@ use std::{
 };
 
 use error_chain::bail;
-use log::{warn, trace};
-use client::{self, Client, CallExecutor, BlockchainEvents, runtime_api::Metadata};
+use log::{debug, warn, trace};
+use client::{self, Client, CallExecutor, BlockchainEvents, ExecutionTrace, runtime_api::Metadata};
 use jsonrpc_derive::rpc;
 use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
 use primitives::{H256, Blake2Hasher, Bytes};
@@ -35,7 +35,9 @@ use crate::rpc::futures::{stream, Future, Sink, Stream};
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::traits::{Block as BlockT, Header, ProvideRuntimeApi, As, NumberFor};
 use runtime_version::RuntimeVersion;
-use state_machine::{self, ExecutionStrategy};
+use state_machine::{self, ExecutionStrategy, execution_trace::StorageKeyNames};
+use srml_metadata::{RuntimeMetadata, RuntimeMetadataPrefixed, DecodeDifferent, StorageFunctionType};
+use parity_codec::Decode;
 
 use crate::subscriptions::Subscriptions;
 
@@ -86,6 +88,13 @@ pub trait StateApi<Hash> {
 	#[rpc(name = "state_queryStorage")]
 	fn query_storage(&self, keys: Vec<StorageKey>, block: Hash, hash: Option<Hash>) -> Result<Vec<StorageChangeSet<Hash>>>;
 
+	/// Re-execute a block and return the calls to the host functions and the storage accesses
+	/// of every extrinsic.
+	///
+	/// Unsafe: only available if unsafe RPC methods are enabled.
+	#[rpc(name = "state_traceBlock")]
+	fn trace_block(&self, hash: Hash) -> Result<ExecutionTrace>;
+
 	/// New runtime version subscription
 	#[pubsub(
 		subscription = "state_runtimeVersion",
@@ -119,6 +128,8 @@ pub struct State<B, E, Block: BlockT, RA> {
 	client: Arc<Client<B, E, Block, RA>>,
 	/// Current subscriptions.
 	subscriptions: Subscriptions,
+	/// Whether expensive methods that expose node internals may be called.
+	unsafe_methods: bool,
 }
 
 /// Ranges to query in state_queryStorage.
@@ -145,9 +156,16 @@ impl<B, E, Block: BlockT, RA> State<B, E, Block, RA> where
 		Self {
 			client,
 			subscriptions,
+			unsafe_methods: false,
 		}
 	}
 
+	/// Allow calling the unsafe methods, e.g. `state_traceBlock`.
+	pub fn with_unsafe_methods(mut self, unsafe_methods: bool) -> Self {
+		self.unsafe_methods = unsafe_methods;
+		self
+	}
+
 	/// Splits the `query_storage` block range into 'filtered' and 'unfiltered' subranges.
 	/// Blocks that contain changes within filtered subrange could be filtered using changes tries.
 	/// Blocks that contain changes within unfiltered subrange must be filtered manually.
@@ -281,6 +299,37 @@ impl<B, E, Block, RA> State<B, E, Block, RA> where
 	}
 }
 
+impl<B, E, Block, RA> State<B, E, Block, RA> where
+	Block: BlockT<Hash=H256>,
+	B: client::backend::Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: Metadata<Block>,
+{
+	/// The names of the storage entries of the runtime executing the given block, see
+	/// `storage_key_names`. Empty if the metadata of the runtime can't be read.
+	fn storage_key_names(&self, block: Block::Hash) -> StorageKeyNames {
+		let parent_hash = match self.client.header(&BlockId::Hash(block)) {
+			Ok(Some(header)) => *header.parent_hash(),
+			_ => return Default::default(),
+		};
+		let metadata = match self.client.runtime_api().metadata(&BlockId::Hash(parent_hash)) {
+			Ok(metadata) => metadata,
+			Err(e) => {
+				debug!(target: "rpc", "Storage accesses of {:?} are traced without names: {:?}", block, e);
+				return Default::default();
+			},
+		};
+		match storage_key_names(&metadata) {
+			Some(key_names) => key_names,
+			None => {
+				debug!(target: "rpc", "Storage accesses of {:?} are traced without names: bad metadata", block);
+				Default::default()
+			},
+		}
+	}
+}
+
 impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA> where
 	Block: BlockT<Hash=H256> + 'static,
 	B: client::backend::Backend<Block, Blake2Hasher> + Send + Sync + 'static,
@@ -342,6 +391,15 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA> where
 		Ok(changes)
 	}
 
+	fn trace_block(&self, block: Block::Hash) -> Result<ExecutionTrace> {
+		if !self.unsafe_methods {
+			bail!(error::ErrorKind::UnsafeMethod("state_traceBlock".into()));
+		}
+		trace!(target: "rpc", "Tracing block {:?}", block);
+		let key_names = self.storage_key_names(block);
+		Ok(self.client.trace_block(&BlockId::Hash(block), key_names)?)
+	}
+
 	fn subscribe_storage(
 		&self,
 		_meta: Self::Metadata,
@@ -473,3 +531,45 @@ fn invalid_block_range<H: Header>(from: Option<&H>, to: Option<&H>, reason: Stri
 
 	error::ErrorKind::InvalidBlockRange(to_string(from), to_string(to), reason)
 }
+
+/// The names of the storage entries declared in the encoded metadata, by the prefix their keys
+/// are hashed from (e.g. `"Balances FreeBalance"`), for tracing the execution of a block. `None`
+/// if the metadata can't be decoded.
+///
+/// The heads of the linked maps are named as well. The entries of the double maps aren't, since
+/// only the first half of their keys is hashed from the prefix.
+pub fn storage_key_names(metadata: &[u8]) -> Option<StorageKeyNames> {
+	fn decoded<B, O>(value: &DecodeDifferent<B, O>) -> Option<&O> {
+		match value {
+			DecodeDifferent::Decoded(value) => Some(value),
+			DecodeDifferent::Encode(_) => None,
+		}
+	}
+
+	let metadata = RuntimeMetadataPrefixed::decode(&mut &metadata[..])?;
+	let modules = match metadata.1 {
+		RuntimeMetadata::V4(ref metadata) => decoded(&metadata.modules),
+		_ => None,
+	};
+
+	let mut key_names = StorageKeyNames::new();
+	for module in modules.into_iter().flatten() {
+		let prefix = match decoded(&module.prefix) {
+			Some(prefix) => prefix,
+			None => continue,
+		};
+		let storage = module.storage.as_ref().and_then(decoded);
+		for item in storage.into_iter().flatten() {
+			let name = match decoded(&item.name) {
+				Some(name) => format!("{} {}", prefix, name),
+				None => continue,
+			};
+			if let StorageFunctionType::Map { is_linked: true, .. } = item.ty {
+				let head = format!("head of {}", name);
+				key_names.insert(head.as_bytes().to_vec(), head);
+			}
+			key_names.insert(name.as_bytes().to_vec(), name);
+		}
+	}
+	Some(key_names)
+}
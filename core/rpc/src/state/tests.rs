@@ -211,6 +211,32 @@ fn should_split_ranges() {
 	assert_eq!(split_range(100, Some(99)), (0..99, Some(99..100)));
 }
 
+#[test]
+fn should_trace_block_only_if_unsafe_methods_are_allowed() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let mut builder = client.new_block().unwrap();
+	builder.push_transfer(runtime::Transfer {
+		from: AccountKeyring::Alice.into(),
+		to: AccountKeyring::Ferdie.into(),
+		amount: 42,
+		nonce: 0,
+	}).unwrap();
+	let block = builder.bake().unwrap();
+	let hash = block.header.hash();
+	client.import(BlockOrigin::Own, block).unwrap();
+
+	let api = State::new(client.clone(), Subscriptions::new(core.executor()));
+	assert_matches!(
+		api.trace_block(hash),
+		Err(Error(ErrorKind::UnsafeMethod(_), _))
+	);
+
+	let api = api.with_unsafe_methods(true);
+	let trace = api.trace_block(hash).unwrap();
+	assert_eq!(trace.segments.len(), 3);
+	assert_eq!(trace.segments[1].name, "extrinsic 0");
+}
 
 #[test]
 fn should_return_runtime_version() {
@@ -221,7 +247,7 @@ fn should_return_runtime_version() {
 
 	assert_eq!(
 		::serde_json::to_string(&api.runtime_version(None.into()).unwrap()).unwrap(),
-		r#"{"specName":"test","implName":"parity-test","authoringVersion":1,"specVersion":1,"implVersion":1,"apis":[["0xdf6acb689907609b",2],["0x37e397fc7c91f5e4",1],["0xd2bc9897eed08f15",1],["0x40fe3ad401f8959a",3],["0xc6e9a76309f39b09",1],["0xdd718d5cc53262d4",1],["0xcbca25e39f142387",1],["0xf78b278be53f454c",1],["0x7801759919ee83e5",1]]}"#
+		r#"{"specName":"test","implName":"parity-test","authoringVersion":1,"specVersion":1,"implVersion":1,"apis":[["0xdf6acb689907609b",3],["0x37e397fc7c91f5e4",1],["0xd2bc9897eed08f15",1],["0x37c8bb1350a9a2a8",1],["0x40fe3ad401f8959a",3],["0xc6e9a76309f39b09",1],["0xdd718d5cc53262d4",1],["0xcbca25e39f142387",1],["0xf78b278be53f454c",1],["0x7801759919ee83e5",1]]}"#
 	);
 }
 
@@ -247,3 +273,49 @@ fn should_notify_on_runtime_version_initially() {
 	assert_eq!(core.block_on(next.into_future()).unwrap().0, None);
 }
 
+
+#[test]
+fn should_name_storage_entries_by_prefix() {
+	use parity_codec::Encode;
+	use srml_metadata::{
+		RuntimeMetadataV4, ModuleMetadata, StorageFunctionMetadata, StorageFunctionModifier,
+		StorageHasher, META_RESERVED,
+	};
+
+	let item = |name: &str, ty| StorageFunctionMetadata {
+		name: DecodeDifferent::Decoded(name.into()),
+		modifier: StorageFunctionModifier::Default,
+		ty,
+		default: DecodeDifferent::Decoded(Vec::new()),
+		documentation: DecodeDifferent::Decoded(Vec::new()),
+	};
+	let map = |is_linked| StorageFunctionType::Map {
+		hasher: StorageHasher::Blake2_256,
+		key: DecodeDifferent::Decoded("AccountId".into()),
+		value: DecodeDifferent::Decoded("Balance".into()),
+		is_linked,
+	};
+	let metadata = RuntimeMetadataPrefixed(META_RESERVED, RuntimeMetadata::V4(RuntimeMetadataV4 {
+		modules: DecodeDifferent::Decoded(vec![ModuleMetadata {
+			name: DecodeDifferent::Decoded("balances".into()),
+			prefix: DecodeDifferent::Decoded("Balances".into()),
+			storage: Some(DecodeDifferent::Decoded(vec![
+				item("TotalIssuance", StorageFunctionType::Plain(DecodeDifferent::Decoded("Balance".into()))),
+				item("FreeBalance", map(false)),
+				item("Locks", map(true)),
+			])),
+			calls: None,
+			event: None,
+		}]),
+	}));
+
+	let mut names: Vec<_> = storage_key_names(&metadata.encode()).unwrap().into_iter().collect();
+	names.sort();
+	assert_eq!(names, vec![
+		(b"Balances FreeBalance".to_vec(), "Balances FreeBalance".to_string()),
+		(b"Balances Locks".to_vec(), "Balances Locks".to_string()),
+		(b"Balances TotalIssuance".to_vec(), "Balances TotalIssuance".to_string()),
+		(b"head of Balances Locks".to_vec(), "head of Balances Locks".to_string()),
+	]);
+	assert!(storage_key_names(&[0xff]).is_none());
+}
@@ -0,0 +1,346 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Measurement of the benchmark cases declared by the runtime and linear models of the results.
+
+use std::{cmp::Ordering, iter, io::{self, Write}};
+use client::{Client, CallExecutor, backend::Backend};
+use parity_codec::{Decode, Encode};
+use primitives::{Blake2Hasher, H256};
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::Block as BlockT;
+use srml_benchmarking::{
+	BenchmarkCase, BENCHMARK_CASES_CALL, SETUP_BENCHMARK_CALL, DISPATCH_BENCHMARK_CALL,
+};
+use crate::error;
+
+/// Pivots smaller than this are treated as zero when solving the normal equations.
+const SINGULAR_PIVOT: f64 = 1e-9;
+
+/// Measurement of a single dispatch of a benchmarked call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+	/// Values of the parameters of the case.
+	pub params: Vec<u32>,
+	/// Execution time of the dispatch, in nanoseconds.
+	pub time_ns: u64,
+	/// Number of storage reads.
+	pub reads: u64,
+	/// Number of storage writes.
+	pub writes: u64,
+}
+
+/// A linear model `base + Σ slopes[i] * params[i]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearModel {
+	/// Value with all parameters at zero.
+	pub base: f64,
+	/// Increase of the value per unit of every parameter.
+	pub slopes: Vec<f64>,
+}
+
+impl LinearModel {
+	/// Least squares fit of the values to the parameters of the samples.
+	///
+	/// Parameters that have the same value in all samples get a zero slope. Returns `None` if
+	/// there are no samples or the parameters are linearly dependent.
+	pub fn fit(samples: &[(&[u32], f64)]) -> Option<Self> {
+		let first = samples.first()?.0;
+		let varying: Vec<usize> = (0..first.len())
+			.filter(|&i| samples.iter().any(|(params, _)| params[i] != first[i]))
+			.collect();
+
+		// normal equations of the least squares problem, augmented with the right side
+		let size = varying.len() + 1;
+		let mut equations = vec![vec![0f64; size + 1]; size];
+		for (params, value) in samples {
+			let row: Vec<f64> = iter::once(1.0)
+				.chain(varying.iter().map(|&i| params[i] as f64))
+				.collect();
+			for (equation, &coefficient) in equations.iter_mut().zip(&row) {
+				for (sum, &other) in equation.iter_mut().zip(&row) {
+					*sum += coefficient * other;
+				}
+				equation[size] += coefficient * value;
+			}
+		}
+
+		for column in 0..size {
+			let pivot = (column..size).max_by(|&a, &b| equations[a][column].abs()
+				.partial_cmp(&equations[b][column].abs())
+				.unwrap_or(Ordering::Equal))?;
+			if equations[pivot][column].abs() < SINGULAR_PIVOT {
+				return None;
+			}
+			equations.swap(column, pivot);
+			let pivot_row = equations[column].clone();
+			for (index, row) in equations.iter_mut().enumerate() {
+				if index != column {
+					let factor = row[column] / pivot_row[column];
+					for (value, pivot_value) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+						*value -= factor * pivot_value;
+					}
+				}
+			}
+		}
+
+		let mut slopes = vec![0.0; first.len()];
+		for (k, &i) in varying.iter().enumerate() {
+			slopes[i] = equations[k + 1][size] / equations[k + 1][k + 1];
+		}
+		Some(LinearModel {
+			base: equations[0][size] / equations[0][0],
+			slopes,
+		})
+	}
+}
+
+/// Linear models of the dispatch of a benchmarked call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseWeights {
+	/// The benchmark case.
+	pub case: BenchmarkCase,
+	/// Execution time, in nanoseconds.
+	pub time: LinearModel,
+	/// Number of storage reads.
+	pub reads: LinearModel,
+	/// Number of storage writes.
+	pub writes: LinearModel,
+}
+
+impl CaseWeights {
+	/// Fit the models to the samples of the case.
+	pub fn fit(case: BenchmarkCase, samples: &[Sample]) -> Option<Self> {
+		let fit = |value: fn(&Sample) -> u64| {
+			let samples: Vec<_> = samples.iter()
+				.map(|sample| (&sample.params[..], value(sample) as f64))
+				.collect();
+			LinearModel::fit(&samples)
+		};
+		Some(CaseWeights {
+			time: fit(|sample| sample.time_ns)?,
+			reads: fit(|sample| sample.reads)?,
+			writes: fit(|sample| sample.writes)?,
+			case,
+		})
+	}
+}
+
+/// Values of the parameters to measure the case at.
+///
+/// Every parameter takes `steps` values evenly spread over its range (both ends included)
+/// while the others are at their lowest values.
+pub fn sample_points(case: &BenchmarkCase, steps: u32) -> Vec<Vec<u32>> {
+	let steps = steps.max(2);
+	let lowest: Vec<u32> = case.params.iter().map(|param| param.min).collect();
+	let mut points = vec![lowest.clone()];
+	for (index, param) in case.params.iter().enumerate() {
+		let range = u64::from(param.max.saturating_sub(param.min));
+		for step in 1..steps {
+			let mut point = lowest.clone();
+			point[index] = param.min + (range * u64::from(step) / u64::from(steps - 1)) as u32;
+			if !points.contains(&point) {
+				points.push(point);
+			}
+		}
+	}
+	points
+}
+
+/// The benchmark cases declared by the runtime at the given block.
+pub fn benchmark_cases<B, E, Block, RA>(
+	client: &Client<B, E, Block, RA>,
+	at: &BlockId<Block>,
+) -> error::Result<Vec<BenchmarkCase>> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	let cases = client.call_session(at)?.call(BENCHMARK_CASES_CALL, &[])
+		.map_err(|e| format!(
+			"Error getting the benchmark cases, is the runtime built with `runtime-benchmarks`? {}", e
+		))?;
+	Decode::decode(&mut &cases[..])
+		.ok_or_else(|| "Error decoding the benchmark cases".into())
+}
+
+/// Prepare the case with the given values of the parameters on top of the state of the block
+/// and measure the dispatch of the call.
+///
+/// The state prepared by the setup is committed to the trie before the dispatch, so that the
+/// dispatch reads it like any other storage. The time includes the overhead of calling into
+/// the runtime. The accesses to the well known keys, e.g. reading of the runtime code by the
+/// executor, aren't counted.
+pub fn measure<B, E, Block, RA>(
+	client: &Client<B, E, Block, RA>,
+	at: &BlockId<Block>,
+	case: &BenchmarkCase,
+	params: &[u32],
+) -> error::Result<Sample> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	let mut session = client.call_session(at)?;
+	let setup = session.call(
+		SETUP_BENCHMARK_CALL,
+		&(&case.module, &case.name, params).encode(),
+	)?;
+	let call = decode_runtime_result::<Vec<u8>>(&setup, "setup")?;
+
+	let mut session = session.commit();
+	let (dispatched, trace) = session.trace_call(DISPATCH_BENCHMARK_CALL, &call.encode())?;
+	decode_runtime_result::<()>(&dispatched, "dispatch")?;
+
+	let mut sample = Sample {
+		params: params.to_vec(),
+		time_ns: 0,
+		reads: 0,
+		writes: 0,
+	};
+	for segment in trace.segments {
+		sample.time_ns += segment.time_ns;
+		for (group, stats) in segment.storage {
+			if !group.starts_with(':') {
+				sample.reads += stats.reads;
+				sample.writes += stats.writes;
+			}
+		}
+	}
+	Ok(sample)
+}
+
+fn decode_runtime_result<T: Decode>(result: &[u8], stage: &str) -> error::Result<T> {
+	match Decode::decode(&mut &result[..]) {
+		Some(Ok(value)) => Ok(value),
+		Some(Err(reason)) => {
+			let reason: Vec<u8> = reason;
+			Err(format!("Benchmark {} has failed: {}", stage, String::from_utf8_lossy(&reason)).into())
+		},
+		None => Err(format!("Error decoding the result of the benchmark {}", stage).into()),
+	}
+}
+
+/// Write the models as a Rust module with a submodule of constants for every case.
+///
+/// The constants are rounded to integers and negative ones are clamped to zero.
+pub fn write_weights<W: Write>(output: &mut W, weights: &[CaseWeights]) -> io::Result<()> {
+	writeln!(output, "// This file was generated by the `benchmark` command.")?;
+	writeln!(output)?;
+	writeln!(output, "//! Weights of the dispatchable calls.")?;
+	writeln!(output, "//!")?;
+	writeln!(output, "//! Every call has linear models of the execution time in nanoseconds and of the number")?;
+	writeln!(output, "//! of storage reads and writes: `BASE_<X> + Σ <X>_PER_<PARAM> * <param>`.")?;
+
+	for weights in weights {
+		let module = String::from_utf8_lossy(&weights.case.module);
+		let name = String::from_utf8_lossy(&weights.case.name);
+		let ranges: Vec<_> = weights.case.params.iter()
+			.map(|param| format!("`{}` in {}..={}", String::from_utf8_lossy(&param.name), param.min, param.max))
+			.collect();
+
+		writeln!(output)?;
+		if ranges.is_empty() {
+			writeln!(output, "/// `{}::{}`.", module, name)?;
+		} else {
+			writeln!(output, "/// `{}::{}`, measured with {}.", module, name, ranges.join(", "))?;
+		}
+		writeln!(output, "pub mod {}_{} {{", module, name)?;
+		for (kind, model) in &[("TIME", &weights.time), ("READS", &weights.reads), ("WRITES", &weights.writes)] {
+			writeln!(output, "\tpub const BASE_{}: u64 = {};", kind, to_constant(model.base))?;
+			for (param, slope) in weights.case.params.iter().zip(&model.slopes) {
+				let param = String::from_utf8_lossy(&param.name).to_uppercase();
+				writeln!(output, "\tpub const {}_PER_{}: u64 = {};", kind, param, to_constant(*slope))?;
+			}
+		}
+		writeln!(output, "}}")?;
+	}
+
+	Ok(())
+}
+
+fn to_constant(value: f64) -> u64 {
+	value.max(0.0).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use srml_benchmarking::BenchmarkParameter;
+
+	fn case(params: &[(&str, u32, u32)]) -> BenchmarkCase {
+		BenchmarkCase {
+			module: b"staking".to_vec(),
+			name: b"nominate".to_vec(),
+			params: params.iter().map(|&(name, min, max)| BenchmarkParameter {
+				name: name.as_bytes().to_vec(),
+				min,
+				max,
+			}).collect(),
+		}
+	}
+
+	#[test]
+	fn sample_points_vary_one_parameter_at_a_time() {
+		assert_eq!(sample_points(&case(&[]), 10), vec![Vec::<u32>::new()]);
+		assert_eq!(
+			sample_points(&case(&[("n", 1, 16), ("m", 0, 2), ("k", 5, 5)]), 4),
+			vec![
+				vec![1, 0, 5],
+				vec![6, 0, 5],
+				vec![11, 0, 5],
+				vec![16, 0, 5],
+				vec![1, 1, 5],
+				vec![1, 2, 5],
+			],
+		);
+	}
+
+	#[test]
+	fn linear_model_is_recovered() {
+		let points = sample_points(&case(&[("n", 1, 16), ("m", 0, 2), ("k", 5, 5)]), 5);
+		let samples: Vec<_> = points.iter()
+			.map(|params| (&params[..], 100.0 + 7.0 * params[0] as f64 + 0.5 * params[1] as f64))
+			.collect();
+		let model = LinearModel::fit(&samples).unwrap();
+		assert!((model.base - 100.0).abs() < 1e-6);
+		assert!((model.slopes[0] - 7.0).abs() < 1e-6);
+		assert!((model.slopes[1] - 0.5).abs() < 1e-6);
+		assert_eq!(model.slopes[2], 0.0);
+
+		assert_eq!(LinearModel::fit(&[]), None);
+		assert_eq!(LinearModel::fit(&[(&[][..], 3.0), (&[][..], 5.0)]), Some(LinearModel { base: 4.0, slopes: vec![] }));
+	}
+
+	#[test]
+	fn weights_are_written_as_constants() {
+		let samples: Vec<_> = (1..=16).map(|n| Sample {
+			params: vec![n],
+			time_ns: 1000 + 100 * u64::from(n),
+			reads: 2,
+			writes: 1 + u64::from(n),
+		}).collect();
+		let weights = CaseWeights::fit(case(&[("n", 1, 16)]), &samples).unwrap();
+
+		let mut output = Vec::new();
+		write_weights(&mut output, &[weights]).unwrap();
+		let output = String::from_utf8(output).unwrap();
+		assert!(output.contains("/// `staking::nominate`, measured with `n` in 1..=16.\npub mod staking_nominate {\n"));
+		assert!(output.contains("\tpub const BASE_TIME: u64 = 1000;\n\tpub const TIME_PER_N: u64 = 100;\n"));
+		assert!(output.contains("\tpub const BASE_READS: u64 = 2;\n\tpub const READS_PER_N: u64 = 0;\n"));
+		assert!(output.contains("\tpub const BASE_WRITES: u64 = 1;\n\tpub const WRITES_PER_N: u64 = 1;\n"));
+	}
+}
@@ -16,22 +16,29 @@
 
 //! Chain utilities.
 
-use std::{self, io::{Read, Write}};
+use std::{self, collections::BTreeMap, io::{self, Read, Write}};
 use futures::Future;
 use log::{info, warn};
 
+use runtime_primitives::{Justification, ChildrenStorageOverlay};
 use runtime_primitives::generic::{SignedBlock, BlockId};
 use runtime_primitives::traits::{As, Block, Header, NumberFor};
+use primitives::hexdisplay::HexDisplay;
+use client::{ExecutionStrategy, ReplayedBlock};
 use consensus_common::import_queue::{ImportQueue, IncomingBlock, Link};
 use network::message;
 
 use consensus_common::BlockOrigin;
 use crate::components::{self, Components, ServiceFactory, FactoryFullConfiguration, FactoryBlockNumber, RuntimeGenesis};
 use crate::new_client;
+use crate::benchmark;
 use parity_codec::{Decode, Encode};
 use crate::error;
 use crate::chain_spec::ChainSpec;
 
+/// The runtime call returning the metadata of the runtime.
+const METADATA_CALL: &str = "Metadata_metadata";
+
 /// Export a range of blocks to a binary stream.
 pub fn export_blocks<F, E, W>(
 	config: FactoryFullConfiguration<F>,
@@ -191,6 +198,367 @@ pub fn import_blocks<F, E, R>(
 	Ok(())
 }
 
+/// Version of the state snapshot format produced by `export_state`.
+const STATE_SNAPSHOT_VERSION: u32 = 2;
+
+/// Export the state at a finalized block to a binary stream.
+///
+/// The snapshot contains the genesis hash of the chain, the header and justification
+/// of the block, the proof of its finality, all top-level storage entries and all child
+/// tries. The last finalized block is used by default.
+pub fn export_state<F, W>(
+	config: FactoryFullConfiguration<F>,
+	mut output: W,
+	at: Option<FactoryBlockNumber<F>>,
+) -> error::Result<()>
+	where F: ServiceFactory, W: Write,
+{
+	let client = new_client::<F>(&config)?;
+	let info = client.info()?.chain;
+
+	let number = at.unwrap_or(info.finalized_number);
+	if number > info.finalized_number {
+		return Err(format!(
+			"Block #{} is not finalized, last finalized is #{}", number, info.finalized_number
+		).into());
+	}
+
+	let id = BlockId::number(number);
+	let header = client.header(&id)?
+		.ok_or_else(|| format!("Unknown block #{}", number))?;
+	let justification = client.justification(&id)?;
+	let finality_proof = match F::build_finality_proof_provider(client.clone())? {
+		Some(provider) => provider.prove_finality(header.hash())?,
+		None => None,
+	};
+	if finality_proof.is_none() {
+		warn!("Finality of block #{} can't be proven, the snapshot can't be verified by importers.", number);
+	}
+
+	info!("Exporting state at #{} ({})", number, header.hash());
+	let (top, children) = client.storage_snapshot(&id)?;
+
+	output.write_all(&STATE_SNAPSHOT_VERSION.encode())?;
+	output.write_all(&info.genesis_hash.encode())?;
+	output.write_all(&header.encode())?;
+	output.write_all(&justification.encode())?;
+	output.write_all(&finality_proof.encode())?;
+	output.write_all(&top.into_iter().collect::<Vec<_>>().encode())?;
+	output.write_all(&(children.len() as u32).encode())?;
+	for (storage_key, child) in children {
+		output.write_all(&storage_key.encode())?;
+		output.write_all(&child.into_iter().collect::<Vec<_>>().encode())?;
+	}
+
+	Ok(())
+}
+
+/// Import a state snapshot produced by `export_state` from a binary stream.
+///
+/// The database must not contain any block but the genesis of the chain. The finality of
+/// the snapshot block is checked with the finality proof of the snapshot and its state
+/// against the state root of the header. The chain then continues from that block, which
+/// becomes the base of the chain like after a warp sync.
+pub fn import_state<F, R>(
+	config: FactoryFullConfiguration<F>,
+	mut input: R,
+) -> error::Result<()>
+	where F: ServiceFactory, R: Read,
+{
+	let version: u32 = Decode::decode(&mut input).ok_or("Error reading snapshot version")?;
+	if version != STATE_SNAPSHOT_VERSION {
+		return Err(format!("Unsupported state snapshot version {}", version).into());
+	}
+
+	let genesis_hash = <F::Block as Block>::Hash::decode(&mut input)
+		.ok_or("Error reading genesis hash")?;
+	let header = <F::Block as Block>::Header::decode(&mut input)
+		.ok_or("Error reading header")?;
+	let justification: Option<Justification> = Decode::decode(&mut input)
+		.ok_or("Error reading justification")?;
+	let finality_proof: Option<Vec<u8>> = Decode::decode(&mut input)
+		.ok_or("Error reading finality proof")?;
+	let top: Vec<(Vec<u8>, Vec<u8>)> = Decode::decode(&mut input)
+		.ok_or("Error reading storage")?;
+
+	let children_count: u32 = Decode::decode(&mut input).ok_or("Error reading child tries")?;
+	let mut children = ChildrenStorageOverlay::new();
+	for _ in 0..children_count {
+		let storage_key: Vec<u8> = Decode::decode(&mut input).ok_or("Error reading child trie")?;
+		let child: Vec<(Vec<u8>, Vec<u8>)> = Decode::decode(&mut input).ok_or("Error reading child trie")?;
+		children.insert(storage_key, child.into_iter().collect());
+	}
+
+	let client = new_client::<F>(&config)?;
+	let info = client.info()?.chain;
+	if info.genesis_hash != genesis_hash {
+		return Err(format!(
+			"The snapshot is of another chain: genesis {} expected, found {}", info.genesis_hash, genesis_hash
+		).into());
+	}
+	if info.best_number != As::sa(0) {
+		return Err(format!("Cannot import state snapshot, the database already has blocks up to #{}", info.best_number).into());
+	}
+
+	match (F::build_finality_proof_provider(client.clone())?, finality_proof) {
+		(Some(provider), Some(proof)) => provider.check_finality_proof(&header, proof)
+			.map_err(|e| format!("Invalid finality proof of #{}: {}", header.number(), e))?,
+		(Some(_), None) => return Err(format!("The snapshot has no finality proof of #{}", header.number()).into()),
+		(None, _) => warn!("The chain doesn't provide finality proofs, the finality of #{} is not checked.", header.number()),
+	}
+
+	info!("Importing state of #{} ({}): {} entries, {} child tries",
+		header.number(), header.hash(), top.len(), children.len());
+	client.import_state(header, justification, top.into_iter().collect(), children)?;
+
+	let info = client.info()?.chain;
+	info!("Imported state snapshot. Best: #{} ({})", info.best_number, info.best_hash);
+
+	Ok(())
+}
+
+/// Re-execute a block on top of the state of its parent and write the storage changes it makes.
+///
+/// Nothing is imported: the block and the parent state are read from the local database.
+/// With the `Both` strategy the block is executed twice, natively and in wasm, and all
+/// differences between the two executions are reported.
+pub fn replay_block<F, W>(
+	config: FactoryFullConfiguration<F>,
+	mut output: W,
+	block: BlockId<F::Block>,
+	strategy: ExecutionStrategy,
+) -> error::Result<()>
+	where F: ServiceFactory, W: Write,
+{
+	let client = new_client::<F>(&config)?;
+	let header = client.header(&block)?
+		.ok_or_else(|| format!("Unknown block {}", block))?;
+
+	info!("Replaying block #{} ({}) with {:?} execution", header.number(), header.hash(), strategy);
+	match strategy {
+		ExecutionStrategy::Both => {
+			let native = client.replay_block(&block, ExecutionStrategy::NativeWhenPossible);
+			let wasm = client.replay_block(&block, ExecutionStrategy::AlwaysWasm);
+			match (native, wasm) {
+				(Ok(native), Ok(wasm)) => {
+					write_replayed_block(&mut output, &header, &wasm)?;
+					if native == wasm {
+						writeln!(output, "Native and wasm executions match")?;
+					} else {
+						warn!("Native and wasm executions of block #{} differ", header.number());
+						write_replay_mismatch(&mut output, &native, &wasm)?;
+					}
+				},
+				(Ok(native), Err(wasm_err)) => {
+					write_replayed_block(&mut output, &header, &native)?;
+					writeln!(output, "Wasm execution has failed, native has succeeded: {}", wasm_err)?;
+				},
+				(Err(native_err), Ok(wasm)) => {
+					write_replayed_block(&mut output, &header, &wasm)?;
+					writeln!(output, "Native execution has failed, wasm has succeeded: {}", native_err)?;
+				},
+				(Err(native_err), Err(wasm_err)) => {
+					writeln!(output, "Native execution has failed: {}", native_err)?;
+					writeln!(output, "Wasm execution has failed: {}", wasm_err)?;
+				},
+			}
+		},
+		strategy => {
+			let replayed = client.replay_block(&block, strategy)?;
+			write_replayed_block(&mut output, &header, &replayed)?;
+		},
+	}
+
+	Ok(())
+}
+
+/// Re-execute a block in Wasm and write the calls to the host functions and the storage
+/// accesses of every extrinsic as JSON.
+///
+/// The storage accesses are grouped by the entries named in the metadata of the runtime, if it
+/// provides it.
+pub fn trace_block<F, W>(
+	config: FactoryFullConfiguration<F>,
+	output: W,
+	block: BlockId<F::Block>,
+) -> error::Result<()>
+	where F: ServiceFactory, W: Write,
+{
+	let client = new_client::<F>(&config)?;
+	info!("Tracing block {}", block);
+	// the metadata is read through a call by name, the way the benchmarks are run, since the
+	// `Metadata` runtime api isn't required of the runtimes of a service.
+	let metadata = client.header(&block)?
+		.map(|header| BlockId::hash(*header.parent_hash()))
+		.ok_or_else(|| format!("Unknown block {}", block))
+		.and_then(|parent| client.call_session(&parent)
+			.and_then(|mut session| session.call(METADATA_CALL, &[]))
+			.map_err(|e| e.to_string())
+		)
+		.and_then(|metadata| Vec::<u8>::decode(&mut &metadata[..]).ok_or_else(|| "Bad metadata".into()));
+	let key_names = match metadata.map(|metadata| rpc::apis::state::storage_key_names(&metadata)) {
+		Ok(Some(key_names)) => key_names,
+		Ok(None) => {
+			warn!("Storage accesses are traced without names: bad metadata");
+			Default::default()
+		},
+		Err(e) => {
+			warn!("Storage accesses are traced without names: {}", e);
+			Default::default()
+		},
+	};
+	let trace = client.trace_block(&block, key_names)?;
+	serde_json::to_writer_pretty(output, &trace)
+		.map_err(|e| format!("Error writing the trace of block {}: {}", block, e))?;
+	Ok(())
+}
+
+/// Run the benchmark cases declared by the runtime on top of the state of the best block and
+/// write linear models of the results as Rust code, see `benchmark::write_weights`.
+///
+/// Only the cases of `module` (and named `case`) are run if specified. Every case is measured
+/// `repeat` times at every combination of the parameters given by `benchmark::sample_points`.
+pub fn benchmark<F, W>(
+	config: FactoryFullConfiguration<F>,
+	mut output: W,
+	module: Option<String>,
+	case: Option<String>,
+	steps: u32,
+	repeat: u32,
+) -> error::Result<()>
+	where F: ServiceFactory, W: Write,
+{
+	let client = new_client::<F>(&config)?;
+	let info = client.info()?.chain;
+	let at = BlockId::Hash(info.best_hash);
+	info!("Benchmarking on top of block #{} ({})", info.best_number, info.best_hash);
+
+	let cases = benchmark::benchmark_cases(&*client, &at)?.into_iter()
+		.filter(|c| module.as_ref().map_or(true, |module| module.as_bytes() == &c.module[..]))
+		.filter(|c| case.as_ref().map_or(true, |case| case.as_bytes() == &c.name[..]));
+
+	let mut weights = Vec::new();
+	for case in cases {
+		let name = format!("{}::{}", String::from_utf8_lossy(&case.module), String::from_utf8_lossy(&case.name));
+		let mut samples = Vec::new();
+		for params in benchmark::sample_points(&case, steps) {
+			for _ in 0..repeat {
+				samples.push(benchmark::measure(&*client, &at, &case, &params)
+					.map_err(|e| format!("Error benchmarking {} with {:?}: {}", name, params, e))?);
+			}
+		}
+		let case_weights = benchmark::CaseWeights::fit(case, &samples)
+			.ok_or_else(|| format!("Error fitting the weights of {}", name))?;
+		info!("Benchmarked {}: base time {:.0}ns, {:.1} reads, {:.1} writes",
+			name, case_weights.time.base, case_weights.reads.base, case_weights.writes.base);
+		weights.push(case_weights);
+	}
+
+	if weights.is_empty() {
+		warn!("No benchmark cases to run");
+	}
+	benchmark::write_weights(&mut output, &weights)?;
+	Ok(())
+}
+
+fn write_storage_change<W: Write>(output: &mut W, key: &[u8], value: Option<&[u8]>) -> io::Result<()> {
+	match value {
+		Some(value) => writeln!(output, "  0x{} = 0x{}", HexDisplay::from(&key), HexDisplay::from(&value)),
+		None => writeln!(output, "  0x{} removed", HexDisplay::from(&key)),
+	}
+}
+
+fn write_replayed_block<W: Write, B: Block>(
+	output: &mut W,
+	header: &B::Header,
+	replayed: &ReplayedBlock<B>,
+) -> io::Result<()> {
+	writeln!(output, "Block #{} ({})", header.number(), header.hash())?;
+	for (index, result) in replayed.extrinsic_results.iter().enumerate() {
+		writeln!(output, "Extrinsic #{}: {:?}", index, result)?;
+	}
+
+	let state_root = replayed.header.state_root();
+	if state_root == header.state_root() {
+		writeln!(output, "State root: {}", state_root)?;
+	} else {
+		writeln!(output, "State root mismatch: computed {}, header has {}", state_root, header.state_root())?;
+	}
+
+	writeln!(output, "Storage changes:")?;
+	for (key, value) in &replayed.storage_changes {
+		write_storage_change(output, key, value.as_ref().map(AsRef::as_ref))?;
+	}
+	for (storage_key, changes) in &replayed.child_storage_changes {
+		writeln!(output, "Child storage changes of 0x{}:", HexDisplay::from(storage_key))?;
+		for (key, value) in changes {
+			write_storage_change(output, key, value.as_ref().map(AsRef::as_ref))?;
+		}
+	}
+
+	Ok(())
+}
+
+fn write_replay_mismatch<W: Write, B: Block>(
+	output: &mut W,
+	native: &ReplayedBlock<B>,
+	wasm: &ReplayedBlock<B>,
+) -> io::Result<()> {
+	// (child storage key, key) => value, for every changed entry
+	fn changes<B: Block>(replayed: &ReplayedBlock<B>) -> BTreeMap<(Option<&[u8]>, &[u8]), Option<&[u8]>> {
+		let top = replayed.storage_changes.iter()
+			.map(|(key, value)| ((None, &key[..]), value.as_ref().map(AsRef::as_ref)));
+		let children = replayed.child_storage_changes.iter()
+			.flat_map(|(storage_key, changes)| changes.iter()
+				.map(move |(key, value)| ((Some(&storage_key[..]), &key[..]), value.as_ref().map(AsRef::as_ref))));
+		top.chain(children).collect()
+	}
+
+	writeln!(output, "Native and wasm executions differ:")?;
+	let results_count = ::std::cmp::max(native.extrinsic_results.len(), wasm.extrinsic_results.len());
+	for index in 0..results_count {
+		let native_result = native.extrinsic_results.get(index);
+		let wasm_result = wasm.extrinsic_results.get(index);
+		if native_result != wasm_result {
+			writeln!(output, "Extrinsic #{}: native {:?}, wasm {:?}", index, native_result, wasm_result)?;
+		}
+	}
+	if native.header != wasm.header {
+		writeln!(output, "Native header: {:?}", native.header)?;
+		writeln!(output, "Wasm header: {:?}", wasm.header)?;
+	}
+
+	let native_changes = changes(native);
+	let wasm_changes = changes(wasm);
+	let keys = native_changes.keys().chain(wasm_changes.keys()).collect::<::std::collections::BTreeSet<_>>();
+	for key in keys {
+		// a missing entry means that the value has not been changed by the execution
+		let native_change = native_changes.get(key);
+		let wasm_change = wasm_changes.get(key);
+		if native_change == wasm_change {
+			continue;
+		}
+
+		let (storage_key, key) = key;
+		match storage_key {
+			Some(storage_key) => writeln!(output, "Child storage of 0x{}:", HexDisplay::from(storage_key))?,
+			None => writeln!(output, "Storage:")?,
+		}
+		writeln!(output, " native:")?;
+		match native_change {
+			Some(value) => write_storage_change(output, key, *value)?,
+			None => writeln!(output, "  0x{} unchanged", HexDisplay::from(key))?,
+		}
+		writeln!(output, " wasm:")?;
+		match wasm_change {
+			Some(value) => write_storage_change(output, key, *value)?,
+			None => writeln!(output, "  0x{} unchanged", HexDisplay::from(key))?,
+		}
+	}
+
+	Ok(())
+}
+
 /// Revert the chain.
 pub fn revert_chain<F>(
 	config: FactoryFullConfiguration<F>,
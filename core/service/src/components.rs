@@ -144,14 +144,17 @@ pub trait StartRPC<C: Components> {
 		rpc_http: Option<SocketAddr>,
 		rpc_ws: Option<SocketAddr>,
 		rpc_cors: Option<Vec<String>>,
+		rpc_unsafe_methods: bool,
 		task_executor: TaskExecutor,
 		transaction_pool: Arc<TransactionPool<C::TransactionPoolApi>>,
+		engine_commands: rpc::apis::engine::EngineCommands<<ComponentBlock<C> as BlockT>::Hash>,
 	) -> error::Result<Self::ServersHandle>;
 }
 
 impl<C: Components> StartRPC<Self> for C where
 	ComponentClient<C>: ProvideRuntimeApi,
-	<ComponentClient<C> as ProvideRuntimeApi>::Api: runtime_api::Metadata<ComponentBlock<C>>,
+	<ComponentClient<C> as ProvideRuntimeApi>::Api: runtime_api::Metadata<ComponentBlock<C>>
+		+ runtime_api::TransactionPaymentApi<ComponentBlock<C>>,
 {
 	type ServersHandle = (Option<rpc::HttpServer>, Option<Mutex<rpc::WsServer>>);
 
@@ -163,25 +166,35 @@ impl<C: Components> StartRPC<Self> for C where
 		rpc_http: Option<SocketAddr>,
 		rpc_ws: Option<SocketAddr>,
 		rpc_cors: Option<Vec<String>>,
+		rpc_unsafe_methods: bool,
 		task_executor: TaskExecutor,
 		transaction_pool: Arc<TransactionPool<C::TransactionPoolApi>>,
+		engine_commands: rpc::apis::engine::EngineCommands<<ComponentBlock<C> as BlockT>::Hash>,
 	) -> error::Result<Self::ServersHandle> {
 		let handler = || {
 			let client = client.clone();
 			let subscriptions = rpc::apis::Subscriptions::new(task_executor.clone());
 			let chain = rpc::apis::chain::Chain::new(client.clone(), subscriptions.clone());
-			let state = rpc::apis::state::State::new(client.clone(), subscriptions.clone());
+			let state = rpc::apis::state::State::new(client.clone(), subscriptions.clone())
+				.with_unsafe_methods(rpc_unsafe_methods);
 			let author = rpc::apis::author::Author::new(
 				client.clone(), transaction_pool.clone(), subscriptions
 			);
 			let system = rpc::apis::system::System::new(
 				rpc_system_info.clone(), network.clone(), should_have_peers
 			);
-			rpc::rpc_handler::<ComponentBlock<C>, ComponentExHash<C>, _, _, _, _>(
+			// Authoring blocks on request is only for development nodes.
+			let engine = if rpc_unsafe_methods {
+				Some(rpc::apis::engine::Engine::new(engine_commands.clone()))
+			} else {
+				None
+			};
+			rpc::rpc_handler::<ComponentBlock<C>, ComponentExHash<C>, _, _, _, _, _>(
 				state,
 				chain,
 				author,
 				system,
+				engine,
 			)
 		};
 
@@ -328,6 +341,14 @@ pub trait ServiceFactory: 'static + Sized {
 		client: Arc<FullClient<Self>>, 
 	) -> Result<Self::SelectChain, error::Error>;
 
+	/// Build provider of finality proofs used by the warp sync of the full client.
+	/// Warp sync is not supported unless the chain provides finality proofs.
+	fn build_finality_proof_provider(
+		_client: Arc<FullClient<Self>>
+	) -> Result<Option<Arc<network::FinalityProofProvider<Self::Block>>>, error::Error> {
+		Ok(None)
+	}
+
 	/// Build full service.
 	fn new_full(config: FactoryFullConfiguration<Self>, executor: TaskExecutor)
 		-> Result<Self::FullService, error::Error>;
@@ -419,6 +440,11 @@ pub trait Components: Sized + 'static {
 		client: Arc<ComponentClient<Self>>
 	) -> Result<Self::SelectChain, error::Error>;
 
+	/// Build finality proof provider for the warp sync
+	fn build_finality_proof_provider(
+		client: Arc<ComponentClient<Self>>
+	) -> Result<Option<Arc<network::FinalityProofProvider<FactoryBlock<Self::Factory>>>>, error::Error>;
+
 }
 
 /// A struct that implement `Components` for the full client.
@@ -481,6 +507,9 @@ impl<Factory: ServiceFactory> Components for FullComponents<Factory> {
 		let db_settings = client_db::DatabaseSettings {
 			cache_size: config.database_cache_size.map(|u| u as usize),
 			state_cache_size: config.state_cache_size,
+			state_hash_cache_size: config.state_hash_cache_size,
+			state_cache_child_ratio: config.state_cache_child_ratio,
+			state_cache_adaptive: config.state_cache_adaptive,
 			path: config.database_path.as_str().into(),
 			pruning: config.pruning.clone(),
 		};
@@ -518,7 +547,12 @@ impl<Factory: ServiceFactory> Components for FullComponents<Factory> {
 	) -> Result<Self::SelectChain, error::Error> {
 		Self::Factory::build_select_chain(config, client)
 	}
-	
+
+	fn build_finality_proof_provider(
+		client: Arc<ComponentClient<Self>>
+	) -> Result<Option<Arc<network::FinalityProofProvider<FactoryBlock<Self::Factory>>>>, error::Error> {
+		Factory::build_finality_proof_provider(client)
+	}
 }
 
 /// A struct that implement `Components` for the light client.
@@ -575,6 +609,9 @@ impl<Factory: ServiceFactory> Components for LightComponents<Factory> {
 		let db_settings = client_db::DatabaseSettings {
 			cache_size: None,
 			state_cache_size: config.state_cache_size,
+			state_hash_cache_size: config.state_hash_cache_size,
+			state_cache_child_ratio: config.state_cache_child_ratio,
+			state_cache_adaptive: config.state_cache_adaptive,
 			path: config.database_path.as_str().into(),
 			pruning: config.pruning.clone(),
 		};
@@ -609,6 +646,12 @@ impl<Factory: ServiceFactory> Components for LightComponents<Factory> {
 		Err("Fork choice doesn't happen on light clients.".into())
 	}
 
+	fn build_finality_proof_provider(
+		_client: Arc<ComponentClient<Self>>
+	) -> Result<Option<Arc<network::FinalityProofProvider<FactoryBlock<Self::Factory>>>>, error::Error> {
+		// light clients don't download the state
+		Ok(None)
+	}
 }
 
 #[cfg(test)]
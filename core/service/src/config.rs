@@ -21,7 +21,7 @@ use transaction_pool;
 use crate::chain_spec::ChainSpec;
 pub use client::ExecutionStrategies;
 pub use client_db::PruningMode;
-pub use network::config::{NetworkConfiguration, Roles};
+pub use network::config::{NetworkConfiguration, Roles, SyncMode};
 use runtime_primitives::BuildStorage;
 use serde::{Serialize, de::DeserializeOwned};
 use target_info::Target;
@@ -50,6 +50,12 @@ pub struct Configuration<C, G: Serialize + DeserializeOwned + BuildStorage> {
 	pub database_cache_size: Option<u32>,
 	/// Size of internal state cache in Bytes
 	pub state_cache_size: usize,
+	/// Size of internal storage hashes cache in Bytes
+	pub state_hash_cache_size: usize,
+	/// Percentage of the state cache reserved for the child storage, `None` to not cache it
+	pub state_cache_child_ratio: Option<usize>,
+	/// Adapt the split of the state cache between the local and the shared caches
+	pub state_cache_adaptive: bool,
 	/// Pruning settings.
 	pub pruning: PruningMode,
 	/// Additional key seeds.
@@ -68,16 +74,26 @@ pub struct Configuration<C, G: Serialize + DeserializeOwned + BuildStorage> {
 	pub rpc_ws: Option<SocketAddr>,
 	/// CORS settings for HTTP & WS servers. `None` if all origins are allowed.
 	pub rpc_cors: Option<Vec<String>>,
+	/// Expose RPC methods that are expensive or reveal node internals.
+	pub rpc_unsafe_methods: bool,
 	/// Telemetry service URL. `None` if disabled.
 	pub telemetry_endpoints: Option<TelemetryEndpoints>,
 	/// The default number of 64KB pages to allocate for Wasm execution
 	pub default_heap_pages: Option<u64>,
+	/// Reuse the Wasm instance between calls, restoring only its changed memory.
+	pub wasm_fast_instance_reuse: bool,
+	/// Spec versions of the runtime that are never executed natively.
+	pub native_disabled_for: Vec<u32>,
 	/// Should offchain workers be executed.
 	pub offchain_worker: bool,
 	/// Enable authoring even when offline.
 	pub force_authoring: bool,
-	/// Disable GRANDPA when running in validator mode
+	/// Disable the GRANDPA voter when running in validator mode, otherwise disable the observer.
 	pub disable_grandpa: bool,
+	/// Follow GRANDPA as an observer, even when an authority key is available.
+	pub grandpa_observer: bool,
+	/// Chain synchronization mode.
+	pub sync_mode: SyncMode,
 }
 
 impl<C: Default, G: Serialize + DeserializeOwned + BuildStorage> Configuration<C, G> {
@@ -96,6 +112,9 @@ impl<C: Default, G: Serialize + DeserializeOwned + BuildStorage> Configuration<C
 			database_path: Default::default(),
 			database_cache_size: Default::default(),
 			state_cache_size: Default::default(),
+			state_hash_cache_size: Default::default(),
+			state_cache_child_ratio: Default::default(),
+			state_cache_adaptive: Default::default(),
 			keys: Default::default(),
 			custom: Default::default(),
 			pruning: PruningMode::default(),
@@ -103,11 +122,16 @@ impl<C: Default, G: Serialize + DeserializeOwned + BuildStorage> Configuration<C
 			rpc_http: None,
 			rpc_ws: None,
 			rpc_cors: Some(vec![]),
+			rpc_unsafe_methods: false,
 			telemetry_endpoints: None,
 			default_heap_pages: None,
+			wasm_fast_instance_reuse: false,
+			native_disabled_for: Vec::new(),
 			offchain_worker: Default::default(),
 			force_authoring: false,
 			disable_grandpa: false,
+			grandpa_observer: false,
+			sync_mode: SyncMode::Full,
 		};
 		configuration.network.boot_nodes = configuration.chain_spec.boot_nodes().to_vec();
 
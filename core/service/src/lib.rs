@@ -24,6 +24,7 @@ mod error;
 mod chain_spec;
 pub mod config;
 pub mod chain_ops;
+mod benchmark;
 
 use std::io;
 use std::net::SocketAddr;
@@ -41,7 +42,7 @@ use parity_codec::{Encode, Decode};
 use primitives::Pair;
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::traits::{Header, As};
-use substrate_executor::NativeExecutor;
+use substrate_executor::{NativeExecutor, RuntimeInfo};
 use consensus_common::SelectChain;
 use tel::{telemetry, SUBSTRATE_INFO};
 
@@ -65,7 +66,7 @@ use components::{StartRPC, MaintainTransactionPool, OffchainWorker};
 #[doc(hidden)]
 pub use std::{ops::Deref, result::Result, sync::Arc};
 #[doc(hidden)]
-pub use network::OnDemand;
+pub use network::{OnDemand, FinalityProofProvider};
 #[doc(hidden)]
 pub use tokio::runtime::TaskExecutor;
 
@@ -83,6 +84,7 @@ pub struct Service<Components: components::Components> {
 	signal: Option<Signal>,
 	/// Configuration of this Service
 	pub config: FactoryFullConfiguration<Components::Factory>,
+	engine_commands: rpc::apis::engine::EngineCommands<<ComponentBlock<Components> as BlockT>::Hash>,
 	_rpc: Box<::std::any::Any + Send + Sync>,
 	_telemetry: Option<Arc<tel::Telemetry>>,
 	_offchain_workers: Option<Arc<offchain::OffchainWorkers<ComponentClient<Components>, ComponentBlock<Components>>>>,
@@ -93,7 +95,11 @@ pub struct Service<Components: components::Components> {
 pub fn new_client<Factory: components::ServiceFactory>(config: &FactoryFullConfiguration<Factory>)
 	-> Result<Arc<ComponentClient<components::FullComponents<Factory>>>, error::Error>
 {
-	let executor = NativeExecutor::new(config.default_heap_pages);
+	let executor = NativeExecutor::new(config.default_heap_pages)
+		.with_fast_instance_reuse(config.wasm_fast_instance_reuse);
+	for spec_version in &config.native_disabled_for {
+		executor.disable_native_for(*spec_version);
+	}
 	let (client, _) = components::FullComponents::<Factory>::build_client(
 		config,
 		executor,
@@ -122,6 +128,12 @@ impl<Components: components::Components> Service<Components> {
 		stream
 	}
 
+	/// Get the stream of commands for the consensus engine, received through the
+	/// `engine` RPC. Only the stream which was requested last receives commands.
+	pub fn engine_command_stream(&self) -> rpc::apis::engine::EngineCommandStream<<ComponentBlock<Components> as BlockT>::Hash> {
+		self.engine_commands.stream()
+	}
+
 	/// Creates a new service.
 	pub fn new(
 		mut config: FactoryFullConfiguration<Components::Factory>,
@@ -130,7 +142,11 @@ impl<Components: components::Components> Service<Components> {
 		let (signal, exit) = ::exit_future::signal();
 
 		// Create client
-		let executor = NativeExecutor::new(config.default_heap_pages);
+		let executor = NativeExecutor::new(config.default_heap_pages)
+			.with_fast_instance_reuse(config.wasm_fast_instance_reuse);
+		for spec_version in &config.native_disabled_for {
+			executor.disable_native_for(*spec_version);
+		}
 
 		let mut keystore = Keystore::open(config.keystore_path.as_str().into())?;
 
@@ -174,11 +190,16 @@ impl<Components: components::Components> Service<Components> {
 			client: client.clone(),
 		 });
 
+		let finality_proof_provider = Components::build_finality_proof_provider(client.clone())?;
 		let network_params = network::config::Params {
-			config: network::config::ProtocolConfig { roles: config.roles },
+			config: network::config::ProtocolConfig {
+				roles: config.roles,
+				sync_mode: config.sync_mode,
+			},
 			network_config: config.network.clone(),
 			chain: client.clone(),
 			on_demand: on_demand.as_ref().map(|d| d.clone() as _),
+			finality_proof_provider,
 			transaction_pool: transaction_pool_adapter.clone() as _,
 			specialization: network_protocol,
 		};
@@ -323,9 +344,11 @@ impl<Components: components::Components> Service<Components> {
 			impl_version: config.impl_version.into(),
 			properties: config.chain_spec.properties(),
 		};
+		let engine_commands = rpc::apis::engine::EngineCommands::default();
 		let rpc = Components::RuntimeServices::start_rpc(
 			client.clone(), network.clone(), has_bootnodes, system_info, config.rpc_http,
-			config.rpc_ws, config.rpc_cors.clone(), task_executor.clone(), transaction_pool.clone(),
+			config.rpc_ws, config.rpc_cors.clone(), config.rpc_unsafe_methods, task_executor.clone(),
+			transaction_pool.clone(), engine_commands.clone(),
 		)?;
 
 		let telemetry_connection_sinks: Arc<Mutex<Vec<mpsc::UnboundedSender<()>>>> = Default::default();
@@ -371,6 +394,7 @@ impl<Components: components::Components> Service<Components> {
 			keystore,
 			config,
 			exit,
+			engine_commands,
 			_rpc: Box::new(rpc),
 			_telemetry: telemetry,
 			_offchain_workers: offchain_workers,
@@ -568,6 +592,8 @@ impl<C: Components> network::TransactionPool<ComponentExHash<C>, ComponentBlock<
 /// 		ImportQueue = BasicQueue<Block, NoneVerifier>
 /// 			{ |_, client| Ok(BasicQueue::new(Arc::new(NoneVerifier {}, client))) }
 /// 			{ |_, client| Ok(BasicQueue::new(Arc::new(NoneVerifier {}, client))) },
+///         // Optionally, provide finality proofs to support the warp sync.
+/// 		FinalityProofProvider = { |client| Ok(None) },
 /// 	}
 /// }
 /// ```
@@ -601,6 +627,7 @@ macro_rules! construct_service_factory {
 				{ $( $light_import_queue_init:tt )* },
 			SelectChain = $select_chain:ty
 				{ $( $select_chain_init:tt )* },
+			$( FinalityProofProvider = { $( $finality_proof_provider_init:tt )* }, )?
 		}
 	) => {
 		$( #[$attr] )*
@@ -665,6 +692,14 @@ macro_rules! construct_service_factory {
 			) -> Result<Self::LightImportQueue, $crate::Error> {
 				( $( $light_import_queue_init )* ) (config, client)
 			}
+
+			$(
+				fn build_finality_proof_provider(
+					client: Arc<$crate::FullClient<Self>>
+				) -> Result<Option<Arc<$crate::FinalityProofProvider<Self::Block>>>, $crate::Error> {
+					( $( $finality_proof_provider_init )* ) (client)
+				}
+			)?
 //			定义了创建 light node 的 fn (Polkadot中用到)
 			fn new_light(
 				config: $crate::FactoryFullConfiguration<Self>,
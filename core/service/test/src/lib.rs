@@ -113,6 +113,9 @@ fn node_config<F: ServiceFactory> (
 		database_path: root.join("db").to_str().unwrap().into(),
 		database_cache_size: None,
 		state_cache_size: 16777216,
+		state_hash_cache_size: 16777216,
+		state_cache_child_ratio: None,
+		state_cache_adaptive: false,
 		pruning: Default::default(),
 		keys: keys,
 		chain_spec: (*spec).clone(),
@@ -122,11 +125,16 @@ fn node_config<F: ServiceFactory> (
 		rpc_http: None,
 		rpc_ws: None,
 		rpc_cors: None,
+		rpc_unsafe_methods: false,
 		telemetry_endpoints: None,
 		default_heap_pages: None,
+		wasm_fast_instance_reuse: false,
+		native_disabled_for: Vec::new(),
 		offchain_worker: false,
 		force_authoring: false,
 		disable_grandpa: false,
+		grandpa_observer: false,
+		sync_mode: network::config::SyncMode::Full,
 	}
 }
 
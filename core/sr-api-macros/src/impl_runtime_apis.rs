@@ -26,7 +26,7 @@ use proc_macro2::{Span, TokenStream};
 use quote::quote;
 
 use syn::{
-	spanned::Spanned, parse_macro_input, Ident, Type, ItemImpl, MethodSig, Path,
+	spanned::Spanned, parse_macro_input, Ident, Type, ItemImpl, MethodSig, Path, Attribute,
 	ImplItem, parse::{Parse, ParseStream, Result, Error}, PathArguments, GenericArgument, TypePath,
 	fold::{self, Fold}, parse_quote
 };
@@ -136,11 +136,21 @@ fn extract_runtime_block_ident(trait_: &Path) -> Result<&TypePath> {
 	}
 }
 
-/// Generate all the implementation calls for the given functions.
+/// The `#[cfg(..)]` attributes of an implementation, which also apply to everything generated
+/// for it.
+fn extract_cfg_attrs(impl_: &ItemImpl) -> Vec<Attribute> {
+	impl_.attrs.iter()
+		.filter(|attr| attr.path.segments.len() == 1 && attr.path.segments[0].ident == "cfg")
+		.cloned()
+		.collect()
+}
+
+/// Generate all the implementation calls for the given functions, along with the `#[cfg(..)]`
+/// attributes of their implementation.
 fn generate_impl_calls(
 	impls: &[ItemImpl],
 	input: &Ident
-) -> Result<Vec<(Ident, Ident, TokenStream)>> {
+) -> Result<Vec<(Ident, Ident, TokenStream, Vec<Attribute>)>> {
 	let mut impl_calls = Vec::new();
 
 	for impl_ in impls {
@@ -152,6 +162,7 @@ fn generate_impl_calls(
 			.ok_or_else(|| Error::new(impl_trait_path.span(), "Empty trait path not possible!"))?
 			.value()
 			.ident;
+		let cfg_attrs = extract_cfg_attrs(impl_);
 
 		for item in &impl_.items {
 			if let ImplItem::Method(method) = item {
@@ -163,7 +174,7 @@ fn generate_impl_calls(
 				)?;
 
 				impl_calls.push(
-					(impl_trait_ident.clone(), method.sig.ident.clone(), impl_call)
+					(impl_trait_ident.clone(), method.sig.ident.clone(), impl_call, cfg_attrs.clone())
 				);
 			}
 		}
@@ -177,9 +188,9 @@ fn generate_dispatch_function(impls: &[ItemImpl]) -> Result<TokenStream> {
 	let data = Ident::new("data", Span::call_site());
 	let impl_calls = generate_impl_calls(impls, &data)?
 		.into_iter()
-		.map(|(trait_, fn_name, impl_)| {
+		.map(|(trait_, fn_name, impl_, attrs)| {
 			let name = prefix_function_with_trait(&trait_, &fn_name);
-			quote!( #name => Some({ #impl_ }), )
+			quote!( #( #attrs )* #name => Some({ #impl_ }), )
 		});
 
 	Ok(quote!(
@@ -199,13 +210,14 @@ fn generate_wasm_interface(impls: &[ItemImpl]) -> Result<TokenStream> {
 	let c = generate_crate_access(HIDDEN_INCLUDES_ID);
 	let impl_calls = generate_impl_calls(impls, &input)?
 		.into_iter()
-		.map(|(trait_, fn_name, impl_)| {
+		.map(|(trait_, fn_name, impl_, attrs)| {
 			let fn_name = Ident::new(
 				&prefix_function_with_trait(&trait_, &fn_name),
 				Span::call_site()
 			);
 
 			quote!(
+				#( #attrs )*
 				#[cfg(not(feature = "std"))]
 				#[no_mangle]
 				pub fn #fn_name(input_data: *mut u8, input_len: usize) -> u64 {
@@ -618,8 +630,9 @@ fn generate_runtime_api_versions(impls: &[ItemImpl]) -> Result<TokenStream> {
 
 		let id: Path = parse_quote!( #path ID );
 		let version: Path = parse_quote!( #path VERSION );
+		let attrs = extract_cfg_attrs(impl_);
 
-		result.push(quote!( (#id, #version) ));
+		result.push(quote!( #( #attrs )* (#id, #version) ));
 	}
 
 	let c = generate_crate_access(HIDDEN_INCLUDES_ID);
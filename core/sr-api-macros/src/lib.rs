@@ -42,6 +42,9 @@ mod compile_fail_tests;
 /// `RUNTIME_API_VERSIONS` is generated. This constant should be used to instantiate the `apis`
 /// field of `RuntimeVersion`.
 ///
+/// An implementation can be put behind a feature with `#[cfg(feature = "..")]`, which then
+/// applies to everything generated for it, including its entry in `RUNTIME_API_VERSIONS`.
+///
 /// # Example
 ///
 /// ```rust
@@ -41,6 +41,10 @@ decl_runtime_apis! {
 		#[changed_in(2)]
 		fn same_name() -> String;
 	}
+
+	pub trait ApiDisabledByCfg {
+		fn disabled();
+	}
 }
 
 impl_runtime_apis! {
@@ -64,6 +68,11 @@ impl_runtime_apis! {
 		fn same_name() {}
 	}
 
+	#[cfg(any())]
+	impl self::ApiDisabledByCfg<Block> for Runtime {
+		fn disabled() {}
+	}
+
 	impl runtime_api::Core<Block> for Runtime {
 		fn version() -> runtime_api::RuntimeVersion {
 			unimplemented!()
@@ -126,3 +135,8 @@ fn check_runtime_api_versions() {
 	check_runtime_api_versions_contains::<ApiWithCustomVersion<Block>>();
 	check_runtime_api_versions_contains::<runtime_api::Core<Block>>();
 }
+
+#[test]
+fn check_runtime_api_versions_skip_disabled_impls() {
+	assert!(!RUNTIME_API_VERSIONS.iter().any(|v| v.0 == ApiDisabledByCfg::<Block>::ID));
+}
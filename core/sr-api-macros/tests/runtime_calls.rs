@@ -23,11 +23,11 @@ use runtime_primitives::{
 	traits::{ProvideRuntimeApi, Header as HeaderT, Hash as HashT},
 };
 use state_machine::{
-	ExecutionStrategy, create_proof_check_backend,
+	ExecutionStrategy, NeverOffchainExt, create_proof_check_backend,
 	execution_proof_check_on_trie_backend,
 };
 
-use client::LongestChain;
+use client::{LongestChain, CallExecutor};
 use consensus_common::SelectChain;
 use codec::Encode;
 
@@ -120,6 +120,25 @@ fn calling_with_native_else_wasm_and_fail_on_native_should_work() {
 	assert_eq!(runtime_api.fail_on_native(&block_id).unwrap(), 1);
 }
 
+#[test]
+fn calling_with_shadow_strategy_and_fail_on_native_disables_native() {
+	let client = test_client::new_with_execution_strategy(ExecutionStrategy::Shadow);
+	let runtime_api = client.runtime_api();
+	let block_id = BlockId::Number(client.info().unwrap().chain.best_number);
+	let call_natively = || client.executor().call(
+		&block_id,
+		"TestAPI_fail_on_native",
+		&[],
+		ExecutionStrategy::NativeWhenPossible,
+		NeverOffchainExt::new(),
+	);
+	assert!(call_natively().is_err());
+
+	// wasm is authoritative, the native failure is a mismatch that disables native execution
+	assert_eq!(runtime_api.fail_on_native(&block_id).unwrap(), 1);
+	assert_eq!(call_natively().unwrap(), 1u64.encode());
+}
+
 #[test]
 fn use_trie_function() {
 	let client = test_client::new_with_execution_strategy(ExecutionStrategy::AlwaysWasm);
@@ -131,6 +131,19 @@ export_api! {
 		/// Clear the storage entries with a key that starts with the given prefix.
 		fn clear_prefix(prefix: &[u8]);
 
+		/// Start a new storage transaction, nested in the open ones. The changes to the
+		/// storage made until the transaction is closed can be rolled back as a whole.
+		fn start_transaction();
+
+		/// Discard the changes to the storage made since the start of the innermost open
+		/// storage transaction and close it. Panics if there's no open transaction.
+		fn rollback_transaction();
+
+		/// Keep the changes to the storage made since the start of the innermost open storage
+		/// transaction and close it. The changes can still be rolled back with the enclosing
+		/// transaction. Panics if there's no open transaction.
+		fn commit_transaction();
+
 		/// "Commit" all existing operations and compute the resultant storage root.
 		fn storage_root() -> [u8; 32];
 
@@ -145,6 +145,26 @@ impl StorageApi for () {
 		);
 	}
 
+	fn start_transaction() {
+		ext::with(|ext|
+			ext.storage_start_transaction()
+		).expect("start_transaction cannot be called outside of an Externalities-provided environment.")
+	}
+
+	fn rollback_transaction() {
+		ext::with(|ext|
+			ext.storage_rollback_transaction()
+		).expect("rollback_transaction cannot be called outside of an Externalities-provided environment.")
+			.expect("there is no open storage transaction to roll back")
+	}
+
+	fn commit_transaction() {
+		ext::with(|ext|
+			ext.storage_commit_transaction()
+		).expect("commit_transaction cannot be called outside of an Externalities-provided environment.")
+			.expect("there is no open storage transaction to commit")
+	}
+
 	fn storage_root() -> [u8; 32] {
 		ext::with(|ext|
 			ext.storage_root()
@@ -212,6 +212,14 @@ pub mod ext {
 		fn ext_exists_storage(key_data: *const u8, key_len: u32) -> u32;
 		/// Remove storage entries which key starts with given prefix.
 		fn ext_clear_prefix(prefix_data: *const u8, prefix_len: u32);
+		/// Start a new storage transaction, nested in the open ones.
+		fn ext_storage_start_transaction();
+		/// Discard the changes made since the start of the innermost open storage transaction
+		/// and close it. Traps if there's no open transaction.
+		fn ext_storage_rollback_transaction();
+		/// Keep the changes made since the start of the innermost open storage transaction and
+		/// close it. Traps if there's no open transaction.
+		fn ext_storage_commit_transaction();
 		/// Gets the value of the given key from storage.
 		///
 		/// The host allocates the memory for storing the value.
@@ -466,6 +474,24 @@ impl StorageApi for () {
 		}
 	}
 
+	fn start_transaction() {
+		unsafe {
+			ext_storage_start_transaction.get()();
+		}
+	}
+
+	fn rollback_transaction() {
+		unsafe {
+			ext_storage_rollback_transaction.get()();
+		}
+	}
+
+	fn commit_transaction() {
+		unsafe {
+			ext_storage_commit_transaction.get()();
+		}
+	}
+
 	fn kill_child_storage(storage_key: &[u8]) {
 		unsafe {
 			ext_kill_child_storage.get()(
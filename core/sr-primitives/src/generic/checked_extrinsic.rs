@@ -25,9 +25,10 @@ use crate::traits::{self, Member, SimpleArithmetic, MaybeDisplay};
 #[derive(PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "std", derive(Debug))]
 pub struct CheckedExtrinsic<AccountId, Index, Call> {
-	/// Who this purports to be from and the number of extrinsics have come before
-	/// from the same signer, if anyone (note this is not a signature).
-	pub signed: Option<(AccountId, Index)>,
+	/// Who this purports to be from, the number of extrinsics have come before
+	/// from the same signer and the tip offered on top of the fee, if anyone (note
+	/// this is not a signature).
+	pub signed: Option<(AccountId, Index, u128)>,
 	/// The function that should be called.
 	pub function: Call,
 }
@@ -51,6 +52,14 @@ where
 		self.signed.as_ref().map(|x| &x.0)
 	}
 
+	fn tip(&self) -> u128 {
+		self.signed.as_ref().map_or(0, |x| x.2)
+	}
+
+	fn call(&self) -> &Self::Call {
+		&self.function
+	}
+
 	fn deconstruct(self) -> (Self::Call, Option<Self::AccountId>) {
 		(self.function, self.signed.map(|x| x.0))
 	}
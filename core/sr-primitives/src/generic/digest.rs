@@ -24,6 +24,7 @@ use rstd::prelude::*;
 use crate::ConsensusEngineId;
 use crate::codec::{Decode, Encode, Codec, Input};
 use crate::traits::{self, Member, DigestItem as DigestItemT, MaybeHash};
+use substrate_primitives::ChangesTrieConfiguration;
 
 /// Generic header digest.
 #[derive(PartialEq, Eq, Clone, Encode, Decode)]
@@ -79,10 +80,34 @@ pub enum DigestItem<Hash, AuthorityId, SealSignature> {
 	Seal(u64, SealSignature),
 	/// Put a Seal on it
 	Consensus(ConsensusEngineId, Vec<u8>),
+	/// A pre-runtime digest: data put into the header by the block author
+	/// before the block is executed, which the runtime can read back.
+	PreRuntime(ConsensusEngineId, Vec<u8>),
+	/// System digest item that signals changes trie related events (like
+	/// configuration change), that are applied starting from the next block.
+	ChangesTrieSignal(ChangesTrieSignal),
 	/// Any 'non-system' digest item, opaque to the native code.
 	Other(Vec<u8>),
 }
 
+/// Available changes trie signals.
+#[derive(PartialEq, Eq, Clone, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize))]
+pub enum ChangesTrieSignal {
+	/// New changes trie configuration is enacted, starting from the next block.
+	/// `None` means that changes tries are disabled.
+	NewConfiguration(Option<ChangesTrieConfiguration>),
+}
+
+impl ChangesTrieSignal {
+	/// Returns Some if the signal is `NewConfiguration`.
+	pub fn as_new_configuration(&self) -> Option<&Option<ChangesTrieConfiguration>> {
+		match *self {
+			ChangesTrieSignal::NewConfiguration(ref config) => Some(config),
+		}
+	}
+}
+
 #[cfg(feature = "std")]
 impl<Hash: Encode, AuthorityId: Encode, SealSignature: Encode> ::serde::Serialize for DigestItem<Hash, AuthorityId, SealSignature> {
 	fn serialize<S>(&self, seq: S) -> Result<S::Ok, S::Error> where S: ::serde::Serializer {
@@ -108,6 +133,10 @@ pub enum DigestItemRef<'a, Hash: 'a, AuthorityId: 'a, SealSignature: 'a> {
 	Seal(&'a u64, &'a SealSignature),
 	/// A sealed signature for testing
 	Consensus(&'a ConsensusEngineId, &'a [u8]),
+	/// Reference to `DigestItem::PreRuntime`.
+	PreRuntime(&'a ConsensusEngineId, &'a [u8]),
+	/// Reference to `DigestItem::ChangesTrieSignal`.
+	ChangesTrieSignal(&'a ChangesTrieSignal),
 	/// Any 'non-system' digest item, opaque to the native code.
 	/// Reference to `DigestItem::Other`.
 	Other(&'a [u8]),
@@ -125,6 +154,8 @@ enum DigestItemType {
 	ChangesTrieRoot = 2,
 	Seal = 3,
 	Consensus = 4,
+	ChangesTrieSignal = 5,
+	PreRuntime = 6,
 }
 
 impl<Hash, AuthorityId, SealSignature> DigestItem<Hash, AuthorityId, SealSignature> {
@@ -144,6 +175,8 @@ impl<Hash, AuthorityId, SealSignature> DigestItem<Hash, AuthorityId, SealSignatu
 			DigestItem::ChangesTrieRoot(ref v) => DigestItemRef::ChangesTrieRoot(v),
 			DigestItem::Seal(ref v, ref s) => DigestItemRef::Seal(v, s),
 			DigestItem::Consensus(ref v, ref s) => DigestItemRef::Consensus(v, s),
+			DigestItem::PreRuntime(ref v, ref s) => DigestItemRef::PreRuntime(v, s),
+			DigestItem::ChangesTrieSignal(ref s) => DigestItemRef::ChangesTrieSignal(s),
 			DigestItem::Other(ref v) => DigestItemRef::Other(v),
 		}
 	}
@@ -164,6 +197,18 @@ impl<
 	fn as_changes_trie_root(&self) -> Option<&Self::Hash> {
 		self.dref().as_changes_trie_root()
 	}
+
+	fn as_changes_trie_signal(&self) -> Option<&ChangesTrieSignal> {
+		self.dref().as_changes_trie_signal()
+	}
+
+	fn as_pre_runtime(&self) -> Option<(ConsensusEngineId, &[u8])> {
+		self.dref().as_pre_runtime()
+	}
+
+	fn as_consensus(&self) -> Option<(ConsensusEngineId, &[u8])> {
+		self.dref().as_consensus()
+	}
 }
 
 impl<Hash: Encode, AuthorityId: Encode, SealSignature: Encode> Encode for DigestItem<Hash, AuthorityId, SealSignature> {
@@ -191,6 +236,13 @@ impl<Hash: Decode, AuthorityId: Decode, SealSignature: Decode> Decode for Digest
 				let vals: (ConsensusEngineId, Vec<u8>) = Decode::decode(input)?;
 				Some(DigestItem::Consensus(vals.0, vals.1))
 			}
+			DigestItemType::PreRuntime => {
+				let vals: (ConsensusEngineId, Vec<u8>) = Decode::decode(input)?;
+				Some(DigestItem::PreRuntime(vals.0, vals.1))
+			}
+			DigestItemType::ChangesTrieSignal => Some(DigestItem::ChangesTrieSignal(
+				Decode::decode(input)?,
+			)),
 			DigestItemType::Other => Some(DigestItem::Other(
 				Decode::decode(input)?,
 			)),
@@ -214,6 +266,30 @@ impl<'a, Hash: Codec + Member, AuthorityId: Codec + Member, SealSignature: Codec
 			_ => None,
 		}
 	}
+
+	/// Cast this digest item into `ChangesTrieSignal`.
+	pub fn as_changes_trie_signal(&self) -> Option<&'a ChangesTrieSignal> {
+		match *self {
+			DigestItemRef::ChangesTrieSignal(ref changes_trie_signal) => Some(changes_trie_signal),
+			_ => None,
+		}
+	}
+
+	/// Cast this digest item into `PreRuntime`.
+	pub fn as_pre_runtime(&self) -> Option<(ConsensusEngineId, &'a [u8])> {
+		match *self {
+			DigestItemRef::PreRuntime(engine_id, data) => Some((*engine_id, data)),
+			_ => None,
+		}
+	}
+
+	/// Cast this digest item into `Consensus`.
+	pub fn as_consensus(&self) -> Option<(ConsensusEngineId, &'a [u8])> {
+		match *self {
+			DigestItemRef::Consensus(engine_id, data) => Some((*engine_id, data)),
+			_ => None,
+		}
+	}
 }
 
 #[allow(deprecated)]
@@ -238,6 +314,14 @@ impl<'a, Hash: Encode, AuthorityId: Encode, SealSignature: Encode> Encode for Di
 				DigestItemType::Consensus.encode_to(&mut v);
 				(val, sig).encode_to(&mut v);
 			},
+			DigestItemRef::PreRuntime(engine_id, data) => {
+				DigestItemType::PreRuntime.encode_to(&mut v);
+				(engine_id, data).encode_to(&mut v);
+			},
+			DigestItemRef::ChangesTrieSignal(changes_trie_signal) => {
+				DigestItemType::ChangesTrieSignal.encode_to(&mut v);
+				changes_trie_signal.encode_to(&mut v);
+			},
 			DigestItemRef::Other(val) => {
 				DigestItemType::Other.encode_to(&mut v);
 				val.encode_to(&mut v);
@@ -270,4 +354,38 @@ mod tests {
 			r#"{"logs":["0x010401000000","0x0204000000","0x0301000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000f","0x000c010203"]}"#
 		);
 	}
+
+	#[test]
+	fn changes_trie_signal_is_encoded_and_decoded() {
+		let item: DigestItem<u32, u32, Signature> = DigestItem::ChangesTrieSignal(
+			ChangesTrieSignal::NewConfiguration(Some(ChangesTrieConfiguration {
+				digest_interval: 4,
+				digest_levels: 2,
+			})),
+		);
+
+		let encoded = item.encode();
+		assert_eq!(
+			::serde_json::to_string(&item).unwrap(),
+			r#""0x050001040000000000000002000000""#
+		);
+		assert_eq!(DigestItem::decode(&mut &encoded[..]), Some(item.clone()));
+		assert_eq!(
+			item.dref().as_changes_trie_signal().and_then(ChangesTrieSignal::as_new_configuration),
+			Some(&Some(ChangesTrieConfiguration { digest_interval: 4, digest_levels: 2 })),
+		);
+	}
+
+	#[test]
+	fn pre_runtime_digest_is_encoded_and_decoded() {
+		let item: DigestItem<u32, u32, Signature> = DigestItem::PreRuntime(*b"babe", vec![1, 2, 3]);
+
+		let encoded = item.encode();
+		assert_eq!(
+			::serde_json::to_string(&item).unwrap(),
+			r#""0x06626162650c010203""#
+		);
+		assert_eq!(DigestItem::decode(&mut &encoded[..]), Some(item.clone()));
+		assert_eq!(traits::DigestItem::as_pre_runtime(&item), Some((*b"babe", &[1u8, 2, 3][..])));
+	}
 }
@@ -36,7 +36,7 @@ pub use self::era::Era;
 pub use self::checked_extrinsic::CheckedExtrinsic;
 pub use self::header::Header;
 pub use self::block::{Block, SignedBlock, BlockId};
-pub use self::digest::{Digest, DigestItem, DigestItemRef};
+pub use self::digest::{Digest, DigestItem, DigestItemRef, ChangesTrieSignal};
 
 use crate::codec::Encode;
 use rstd::prelude::*;
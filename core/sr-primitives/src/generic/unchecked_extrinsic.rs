@@ -96,7 +96,7 @@ where
 					return Err(crate::BAD_SIGNATURE)
 				}
 				CheckedExtrinsic {
-					signed: Some((signed, payload.0)),
+					signed: Some((signed, payload.0, 0)),
 					function: payload.1,
 				}
 			}
@@ -39,6 +39,7 @@ pub mod testing;
 pub mod traits;
 pub mod generic;
 pub mod transaction_validity;
+pub mod weights;
 
 /// A message indicating an invalid signature in extrinsic.
 pub const BAD_SIGNATURE: &str = "bad signature in extrinsic";
@@ -542,7 +543,9 @@ macro_rules! impl_outer_config {
 /// expose a `Log` and `RawLog` enums.
 ///
 /// Generated enum is binary-compatible with and could be interpreted
-/// as `generic::DigestItem`.
+/// as `generic::DigestItem`. Pre-runtime and consensus digests are always
+/// supported, so that the runtime can read the items put into the header by
+/// the block author and announce changes to the consensus engines.
 ///
 /// Runtime requirements:
 /// 1) binary representation of all supported 'system' log items should stay
@@ -578,6 +581,10 @@ macro_rules! impl_outer_log {
 			$(
 				$module($module::Log<$trait $(, $instance)? >),
 			)*
+			/// A pre-runtime digest, put into the header by the block author.
+			PreRuntime($crate::ConsensusEngineId, Vec<u8>),
+			/// A consensus digest, for the consensus engine with the given id.
+			Consensus($crate::ConsensusEngineId, Vec<u8>),
 		}
 
 		impl $name {
@@ -591,6 +598,10 @@ macro_rules! impl_outer_log {
 					$internal::$module($module::RawLog::$sitem(ref v)) =>
 						Some($crate::generic::DigestItemRef::$sitem(v)),
 					)*)*
+					$internal::PreRuntime(ref engine_id, ref data) =>
+						Some($crate::generic::DigestItemRef::PreRuntime(engine_id, data)),
+					$internal::Consensus(ref engine_id, ref data) =>
+						Some($crate::generic::DigestItemRef::Consensus(engine_id, data)),
 					_ => None,
 				}
 			}
@@ -607,6 +618,18 @@ macro_rules! impl_outer_log {
 			fn as_changes_trie_root(&self) -> Option<&Self::Hash> {
 				self.dref().and_then(|dref| dref.as_changes_trie_root())
 			}
+
+			fn as_changes_trie_signal(&self) -> Option<&$crate::generic::ChangesTrieSignal> {
+				self.dref().and_then(|dref| dref.as_changes_trie_signal())
+			}
+
+			fn as_pre_runtime(&self) -> Option<($crate::ConsensusEngineId, &[u8])> {
+				self.dref().and_then(|dref| dref.as_pre_runtime())
+			}
+
+			fn as_consensus(&self) -> Option<($crate::ConsensusEngineId, &[u8])> {
+				self.dref().and_then(|dref| dref.as_consensus())
+			}
 		}
 
 		impl From<$crate::generic::DigestItem<$($genarg),*>> for $name {
@@ -622,6 +645,10 @@ macro_rules! impl_outer_log {
 					$crate::generic::DigestItem::$sitem(value) =>
 						$name($internal::$module($module::RawLog::$sitem(value))),
 					)*)*
+					$crate::generic::DigestItem::PreRuntime(engine_id, data) =>
+						$name($internal::PreRuntime(engine_id, data)),
+					$crate::generic::DigestItem::Consensus(engine_id, data) =>
+						$name($internal::Consensus(engine_id, data)),
 					_ => gen.as_other()
 						.and_then(|value| $crate::codec::Decode::decode(&mut &value[..]))
 						.map($name)
@@ -771,6 +798,19 @@ mod tests {
 
 		// check that as-style methods are not working with regular items
 		assert!(b1.as_authorities_change().is_none());
+
+		// pre-runtime digests are supported without being listed
+		let pre_runtime = super::generic::DigestItem::<H256, u64, H512>::PreRuntime(*b"test", vec![1, 2, 3]);
+		let encoded_pre_runtime = pre_runtime.encode();
+		let decoded_pre_runtime: Log = Decode::decode(&mut &encoded_pre_runtime[..]).unwrap();
+		assert_eq!(decoded_pre_runtime.as_pre_runtime(), Some((*b"test", &[1u8, 2, 3][..])));
+		assert_eq!(decoded_pre_runtime.encode(), encoded_pre_runtime);
+
+		// and so are consensus digests
+		let consensus = super::generic::DigestItem::<H256, u64, H512>::Consensus(*b"test", vec![4, 5, 6]);
+		let encoded_consensus = consensus.encode();
+		let decoded_consensus: Log = Decode::decode(&mut &encoded_consensus[..]).unwrap();
+		assert_eq!(decoded_consensus.encode(), encoded_consensus);
 	}
 
 	#[test]
@@ -671,6 +671,10 @@ pub trait Applyable: Sized + Send + Sync {
 	fn index(&self) -> Option<&Self::Index>;
 	/// Returns a reference to the sender if any.
 	fn sender(&self) -> Option<&Self::AccountId>;
+	/// Returns the tip the sender offers on top of the fee, zero if unsigned.
+	fn tip(&self) -> u128;
+	/// Returns a reference to the function call.
+	fn call(&self) -> &Self::Call;
 	/// Deconstructs into function call and sender.
 	fn deconstruct(self) -> (Self::Call, Option<Self::AccountId>);
 }
@@ -713,6 +717,15 @@ pub trait DigestItem: Codec + Member + MaybeSerializeDebugButNotDeserialize {
 
 	/// Returns Some if the entry is the `ChangesTrieRoot` entry.
 	fn as_changes_trie_root(&self) -> Option<&Self::Hash>;
+
+	/// Returns Some if the entry is the `ChangesTrieSignal` entry.
+	fn as_changes_trie_signal(&self) -> Option<&crate::generic::ChangesTrieSignal>;
+
+	/// Returns the engine id and the data if the entry is a `PreRuntime` entry.
+	fn as_pre_runtime(&self) -> Option<(crate::ConsensusEngineId, &[u8])>;
+
+	/// Returns the engine id and the data if the entry is a `Consensus` entry.
+	fn as_consensus(&self) -> Option<(crate::ConsensusEngineId, &[u8])>;
 }
 
 /// Auxiliary wrapper that holds an api instance and binds it to the given lifetime.
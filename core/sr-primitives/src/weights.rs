@@ -0,0 +1,106 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Weights of the dispatchable calls.
+//!
+//! The weight of a call is a measure of the resources (mostly the execution time) its
+//! dispatch consumes. The weights of the extrinsics of a block are limited by the runtime
+//! and are a part of the fees of the transactions.
+
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
+use crate::codec::{Encode, Decode};
+
+/// Numeric range of a weight.
+pub type Weight = u32;
+
+/// The weight of a call and other information known before its dispatch.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct DispatchInfo {
+	/// Weight of the call.
+	pub weight: Weight,
+}
+
+/// Compute the weight of a call from its arguments `T`.
+pub trait WeighData<T> {
+	/// The weight of the call with the given arguments.
+	fn weigh_data(&self, target: T) -> Weight;
+}
+
+/// A dispatchable whose weight is known before the dispatch.
+///
+/// Implemented by the `Call` of the modules declared with `decl_module!` and by the outer
+/// `Call` of the runtime.
+pub trait GetDispatchInfo {
+	/// Information on the dispatch of `self`.
+	fn get_dispatch_info(&self) -> DispatchInfo;
+}
+
+/// A weight that doesn't depend on the arguments of the call.
+///
+/// The default of the calls without a `#[weight = ...]` annotation.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum SimpleDispatchInfo {
+	/// The given fixed weight.
+	Fixed(Weight),
+	/// The maximum weight: the call fills a block by itself.
+	Max,
+	/// No weight: the call doesn't count towards the block limit and its fee is only the
+	/// base and the length fee.
+	Free,
+}
+
+impl<T> WeighData<T> for SimpleDispatchInfo {
+	fn weigh_data(&self, _: T) -> Weight {
+		match self {
+			SimpleDispatchInfo::Fixed(weight) => *weight,
+			SimpleDispatchInfo::Max => Weight::max_value(),
+			SimpleDispatchInfo::Free => 0,
+		}
+	}
+}
+
+impl Default for SimpleDispatchInfo {
+	fn default() -> Self {
+		SimpleDispatchInfo::Fixed(10_000)
+	}
+}
+
+/// The weight and the fee of a transaction, as reported to the clients.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct TransactionFee {
+	/// Weight of the transaction, as counted towards the block limit.
+	pub weight: Weight,
+	/// The fee the transaction pays for its inclusion.
+	pub fee: u128,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn simple_weights_ignore_arguments() {
+		assert_eq!(SimpleDispatchInfo::default().weigh_data(&(1u32, 2u64)), 10_000);
+		assert_eq!(SimpleDispatchInfo::Fixed(5).weigh_data(()), 5);
+		assert_eq!(SimpleDispatchInfo::Max.weigh_data(()), Weight::max_value());
+		assert_eq!(SimpleDispatchInfo::Free.weigh_data(()), 0);
+	}
+}
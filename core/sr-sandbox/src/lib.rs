@@ -40,7 +40,7 @@
 
 use rstd::prelude::*;
 
-pub use primitives::sandbox::{TypedValue, ReturnValue, HostError};
+pub use primitives::sandbox::{TypedValue, ReturnValue, HostError, InstanceLimits, WasmFeature, ImportName};
 
 mod imp {
 	#[cfg(feature = "std")]
@@ -64,6 +64,24 @@ pub enum Error {
 
 	/// Failed to invoke an exported function for some reason.
 	Execution,
+
+	/// Execution ran out of the fuel given by the [`InstanceLimits`].
+	///
+	/// [`InstanceLimits`]: struct.InstanceLimits.html
+	OutOfFuel,
+
+	/// Execution exceeded the stack height limit.
+	StackOverflow,
+
+	/// Module has an import denied by the [`InstanceLimits`].
+	///
+	/// [`InstanceLimits`]: struct.InstanceLimits.html
+	ForbiddenImport,
+
+	/// Module uses a WebAssembly feature denied by the [`InstanceLimits`].
+	///
+	/// [`InstanceLimits`]: struct.InstanceLimits.html
+	ForbiddenFeature,
 }
 
 impl From<Error> for HostError {
@@ -161,6 +179,13 @@ impl<T> EnvironmentDefinitionBuilder<T> {
 	{
 		self.inner.add_memory(module, field, mem.inner);
 	}
+
+	/// Set the limits of the instances created with this environment definition.
+	///
+	/// By default there are no limits.
+	pub fn set_limits(&mut self, limits: InstanceLimits) {
+		self.inner.set_limits(limits);
+	}
 }
 
 /// Sandboxed instance of a wasm module.
@@ -176,8 +201,10 @@ impl<T> Instance<T> {
 	/// run the `start` function with the given `state`.
 	///
 	/// Returns `Err(Error::Module)` if this module can't be instantiated with the given
-	/// environment. If execution of `start` function generated a trap, then `Err(Error::Execution)` will
-	/// be returned.
+	/// environment and `Err(Error::ForbiddenImport)` or `Err(Error::ForbiddenFeature)` if the limits
+	/// of the environment deny it. If execution of `start` function generated a trap, then
+	/// `Err(Error::Execution)` will be returned, or `Err(Error::OutOfFuel)` or `Err(Error::StackOverflow)`
+	/// if the trap was caused by the limits.
 	///
 	/// [`EnvironmentDefinitionBuilder`]: struct.EnvironmentDefinitionBuilder.html
 	pub fn new(code: &[u8], env_def_builder: &EnvironmentDefinitionBuilder<T>, state: &mut T) -> Result<Instance<T>, Error> {
@@ -197,6 +224,9 @@ impl<T> Instance<T> {
 	/// - If types of the arguments passed to the function doesn't match function signature
 	///   then trap occurs (as if the exported function was called via call_indirect),
 	/// - Trap occured at the execution time.
+	///
+	/// Returns `Err(Error::OutOfFuel)` or `Err(Error::StackOverflow)` if the trap was caused by
+	/// the limits of the environment.
 	pub fn invoke(
 		&mut self,
 		name: &[u8],
@@ -19,11 +19,12 @@ use rstd::fmt;
 
 use wasmi::{
 	Externals, FuncInstance, FuncRef, GlobalDescriptor, GlobalRef, ImportResolver,
-	MemoryDescriptor, MemoryInstance, MemoryRef, Module, ModuleInstance, ModuleRef,
+	MemoryDescriptor, MemoryInstance, MemoryRef, ModuleInstance, ModuleRef,
 	RuntimeArgs, RuntimeValue, Signature, TableDescriptor, TableRef, Trap, TrapKind
 };
 use wasmi::memory_units::Pages;
-use super::{Error, TypedValue, ReturnValue, HostFuncType, HostError};
+use primitives::sandbox::{self as sandbox_primitives, FuelMeter, ModuleRejection, TrapReason, FUEL_FUNC_INDEX};
+use super::{Error, TypedValue, ReturnValue, HostFuncType, HostError, InstanceLimits};
 
 #[derive(Clone)]
 pub struct Memory {
@@ -113,6 +114,7 @@ fn to_runtime_value(v: TypedValue) -> RuntimeValue {
 struct GuestExternals<'a, T: 'a> {
 	state: &'a mut T,
 	defined_host_functions: &'a DefinedHostFunctions<T>,
+	fuel: FuelMeter,
 }
 
 impl<'a, T> Externals for GuestExternals<'a, T> {
@@ -121,6 +123,10 @@ impl<'a, T> Externals for GuestExternals<'a, T> {
 		index: usize,
 		args: RuntimeArgs,
 	) -> Result<Option<RuntimeValue>, Trap> {
+		if index == FUEL_FUNC_INDEX {
+			return self.fuel.charge(args);
+		}
+
 		let args = args.as_ref()
 			.iter()
 			.cloned()
@@ -146,6 +152,7 @@ enum ExternVal {
 pub struct EnvironmentDefinitionBuilder<T> {
 	map: BTreeMap<(Vec<u8>, Vec<u8>), ExternVal>,
 	defined_host_functions: DefinedHostFunctions<T>,
+	limits: InstanceLimits,
 }
 
 impl<T> EnvironmentDefinitionBuilder<T> {
@@ -153,6 +160,7 @@ impl<T> EnvironmentDefinitionBuilder<T> {
 		EnvironmentDefinitionBuilder {
 			map: BTreeMap::new(),
 			defined_host_functions: DefinedHostFunctions::new(),
+			limits: Default::default(),
 		}
 	}
 
@@ -174,6 +182,18 @@ impl<T> EnvironmentDefinitionBuilder<T> {
 		self.map
 			.insert((module.into(), field.into()), ExternVal::Memory(mem));
 	}
+
+	pub fn set_limits(&mut self, limits: InstanceLimits) {
+		self.limits = limits;
+	}
+}
+
+fn trap_error(reason: TrapReason) -> Error {
+	match reason {
+		TrapReason::OutOfFuel => Error::OutOfFuel,
+		TrapReason::StackOverflow => Error::StackOverflow,
+		TrapReason::Other => Error::Execution,
+	}
 }
 
 impl<T> ImportResolver for EnvironmentDefinitionBuilder<T> {
@@ -183,6 +203,10 @@ impl<T> ImportResolver for EnvironmentDefinitionBuilder<T> {
 		field_name: &str,
 		signature: &Signature,
 	) -> Result<FuncRef, wasmi::Error> {
+		if let Some(fuel_func) = sandbox_primitives::fuel_func(module_name, field_name, signature) {
+			return Ok(fuel_func);
+		}
+
 		let key = (
 			module_name.as_bytes().to_owned(),
 			field_name.as_bytes().to_owned(),
@@ -253,12 +277,18 @@ impl<T> ImportResolver for EnvironmentDefinitionBuilder<T> {
 pub struct Instance<T> {
 	instance: ModuleRef,
 	defined_host_functions: DefinedHostFunctions<T>,
+	limits: InstanceLimits,
 	_marker: ::std::marker::PhantomData<T>,
 }
 
 impl<T> Instance<T> {
 	pub fn new(code: &[u8], env_def_builder: &EnvironmentDefinitionBuilder<T>, state: &mut T) -> Result<Instance<T>, Error> {
-		let module = Module::from_buffer(code).map_err(|_| Error::Module)?;
+		let limits = env_def_builder.limits.clone();
+		let module = sandbox_primitives::prepare_module(code, &limits).map_err(|rejection| match rejection {
+			ModuleRejection::ForbiddenImport(_) => Error::ForbiddenImport,
+			ModuleRejection::ForbiddenFeature(_) => Error::ForbiddenFeature,
+			ModuleRejection::Invalid | ModuleRejection::Instrumentation => Error::Module,
+		})?;
 		let not_started_instance = ModuleInstance::new(&module, env_def_builder)
 			.map_err(|_| Error::Module)?;
 
@@ -268,14 +298,19 @@ impl<T> Instance<T> {
 			let mut externals = GuestExternals {
 				state,
 				defined_host_functions: &defined_host_functions,
+				fuel: FuelMeter::new(&limits),
 			};
-			let instance = not_started_instance.run_start(&mut externals).map_err(|_| Error::Execution)?;
+			let not_started = not_started_instance.not_started_instance().clone();
+			let instance = not_started_instance.run_start(&mut externals).map_err(|trap|
+				trap_error(sandbox_primitives::trap_reason(&not_started, &limits, &trap.into()))
+			)?;
 			instance
 		};
 
 		Ok(Instance {
 			instance,
 			defined_host_functions,
+			limits,
 			_marker: ::std::marker::PhantomData::<T>,
 		})
 	}
@@ -292,6 +327,7 @@ impl<T> Instance<T> {
 		let mut externals = GuestExternals {
 			state,
 			defined_host_functions: &self.defined_host_functions,
+			fuel: FuelMeter::new(&self.limits),
 		};
 		let result = self.instance
 			.invoke_export(&name, &args, &mut externals);
@@ -299,7 +335,7 @@ impl<T> Instance<T> {
 		match result {
 			Ok(None) => Ok(ReturnValue::Unit),
 			Ok(Some(val)) => Ok(ReturnValue::Value(val.into())),
-			Err(_err) => Err(Error::Execution),
+			Err(err) => Err(trap_error(sandbox_primitives::trap_reason(&self.instance, &self.limits, &err))),
 		}
 	}
 }
@@ -307,7 +343,10 @@ impl<T> Instance<T> {
 #[cfg(test)]
 mod tests {
 	use wabt;
-	use crate::{Error, TypedValue, ReturnValue, HostError, EnvironmentDefinitionBuilder, Instance};
+	use crate::{
+		Error, TypedValue, ReturnValue, HostError, EnvironmentDefinitionBuilder, Instance,
+		InstanceLimits, ImportName, WasmFeature,
+	};
 	use assert_matches::assert_matches;
 
 	fn execute_sandboxed(code: &[u8], args: &[TypedValue]) -> Result<ReturnValue, HostError> {
@@ -478,4 +517,133 @@ mod tests {
 			Err(Error::Execution)
 		);
 	}
+
+	fn env_nop(_e: &mut (), _args: &[TypedValue]) -> Result<ReturnValue, HostError> {
+		Ok(ReturnValue::Unit)
+	}
+
+	fn limited_env(limits: InstanceLimits) -> EnvironmentDefinitionBuilder<()> {
+		let mut env_builder = EnvironmentDefinitionBuilder::new();
+		env_builder.add_host_func("env", "nop", env_nop);
+		env_builder.set_limits(limits);
+		env_builder
+	}
+
+	#[test]
+	fn instructions_consume_fuel() {
+		// Every iteration of the loop executes at least six instructions.
+		let env_builder = limited_env(InstanceLimits {
+			fuel: Some(20),
+			..Default::default()
+		});
+
+		let code = wabt::wat2wasm(r#"
+		(module
+			(import "env" "nop" (func $nop))
+
+			(func (export "loop") (param $n i32)
+				(loop $continue
+					(call $nop)
+					(br_if $continue
+						(tee_local $n
+							(i32.sub
+								(get_local $n)
+								(i32.const 1)
+							)
+						)
+					)
+				)
+			)
+		)
+		"#).unwrap();
+
+		let mut instance = Instance::new(&code, &env_builder, &mut ()).unwrap();
+		assert_matches!(instance.invoke(b"loop", &[TypedValue::I32(5)], &mut ()), Err(Error::OutOfFuel));
+		// Every invocation gets all the fuel.
+		assert_matches!(instance.invoke(b"loop", &[TypedValue::I32(1)], &mut ()), Ok(ReturnValue::Unit));
+		assert_matches!(instance.invoke(b"loop", &[TypedValue::I32(1)], &mut ()), Ok(ReturnValue::Unit));
+	}
+
+	#[test]
+	fn fuel_module_imports_are_rejected() {
+		let env_builder = limited_env(InstanceLimits {
+			fuel: Some(10),
+			..Default::default()
+		});
+
+		let code = wabt::wat2wasm(r#"
+		(module
+			(import "__sandbox" "fuel" (func $fuel (param i32)))
+
+			(func (export "call")
+				(call $fuel
+					(i32.const 0)
+				)
+			)
+		)
+		"#).unwrap();
+
+		assert_matches!(Instance::new(&code, &env_builder, &mut ()).err(), Some(Error::Module));
+	}
+
+	#[test]
+	fn stack_height_is_limited() {
+		let env_builder = limited_env(InstanceLimits {
+			max_stack_height: Some(64),
+			..Default::default()
+		});
+
+		let code = wabt::wat2wasm(r#"
+		(module
+			(func $recurse (local i32)
+				(call $recurse)
+			)
+			(func (export "recurse")
+				(call $recurse)
+			)
+			(func (export "trap")
+				unreachable
+			)
+			(func (export "ok")
+			)
+		)
+		"#).unwrap();
+
+		let mut instance = Instance::new(&code, &env_builder, &mut ()).unwrap();
+		assert_matches!(instance.invoke(b"recurse", &[], &mut ()), Err(Error::StackOverflow));
+		assert_matches!(instance.invoke(b"ok", &[], &mut ()), Ok(ReturnValue::Unit));
+		assert_matches!(instance.invoke(b"trap", &[], &mut ()), Err(Error::Execution));
+	}
+
+	#[test]
+	fn denied_imports_and_features_are_rejected() {
+		let code = wabt::wat2wasm(r#"
+		(module
+			(import "env" "nop" (func $nop))
+
+			(func (export "call") (result f32)
+				(call $nop)
+				(f32.const 1)
+			)
+		)
+		"#).unwrap();
+
+		let env_builder = limited_env(InstanceLimits {
+			denied_imports: vec![ImportName { module_name: b"env".to_vec(), field_name: b"nop".to_vec() }],
+			..Default::default()
+		});
+		assert_matches!(Instance::new(&code, &env_builder, &mut ()).err(), Some(Error::ForbiddenImport));
+
+		let env_builder = limited_env(InstanceLimits {
+			denied_features: vec![WasmFeature::StartFunction, WasmFeature::FloatingPoint],
+			..Default::default()
+		});
+		assert_matches!(Instance::new(&code, &env_builder, &mut ()).err(), Some(Error::ForbiddenFeature));
+
+		let env_builder = limited_env(InstanceLimits {
+			denied_features: vec![WasmFeature::StartFunction, WasmFeature::GrowMemory],
+			..Default::default()
+		});
+		assert!(Instance::new(&code, &env_builder, &mut ()).is_ok());
+	}
 }
@@ -58,6 +58,21 @@ mod ffi {
 			imports_len: usize,
 			state: usize,
 		) -> u32;
+		pub fn ext_sandbox_instantiate_with_limits(
+			dispatch_thunk: extern "C" fn(
+				serialized_args_ptr: *const u8,
+				serialized_args_len: usize,
+				state: usize,
+				f: HostFuncIndex,
+			) -> u64,
+			wasm_ptr: *const u8,
+			wasm_len: usize,
+			imports_ptr: *const u8,
+			imports_len: usize,
+			limits_ptr: *const u8,
+			limits_len: usize,
+			state: usize,
+		) -> u32;
 		pub fn ext_sandbox_invoke(
 			instance_idx: u32,
 			export_ptr: *const u8,
@@ -148,6 +163,7 @@ impl Memory {
 
 pub struct EnvironmentDefinitionBuilder<T> {
 	env_def: sandbox_primitives::EnvironmentDefinition,
+	limits: Option<sandbox_primitives::InstanceLimits>,
 	retained_memories: Vec<Memory>,
 	_marker: marker::PhantomData<T>,
 }
@@ -158,6 +174,7 @@ impl<T> EnvironmentDefinitionBuilder<T> {
 			env_def: sandbox_primitives::EnvironmentDefinition {
 				entries: Vec::new(),
 			},
+			limits: None,
 			retained_memories: Vec::new(),
 			_marker: marker::PhantomData::<T>,
 		}
@@ -200,6 +217,19 @@ impl<T> EnvironmentDefinitionBuilder<T> {
 		let mem = sandbox_primitives::ExternEntity::Memory(mem.handle.memory_idx as u32);
 		self.add_entry(module, field, mem);
 	}
+
+	pub fn set_limits(&mut self, limits: sandbox_primitives::InstanceLimits) {
+		self.limits = Some(limits);
+	}
+}
+
+fn trap_error(code: u32) -> Error {
+	match code {
+		sandbox_primitives::ERR_EXECUTION => Error::Execution,
+		sandbox_primitives::ERR_OUT_OF_FUEL => Error::OutOfFuel,
+		sandbox_primitives::ERR_STACK_OVERFLOW => Error::StackOverflow,
+		_ => unreachable!(),
+	}
 }
 
 pub struct Instance<T> {
@@ -257,18 +287,37 @@ impl<T> Instance<T> {
 			// It's very important to instantiate thunk with the right type.
 			let dispatch_thunk = dispatch_thunk::<T>;
 
-			ffi::ext_sandbox_instantiate(
-				dispatch_thunk,
-				code.as_ptr(),
-				code.len(),
-				serialized_env_def.as_ptr(),
-				serialized_env_def.len(),
-				state as *const T as usize,
-			)
+			match env_def_builder.limits {
+				Some(ref limits) => {
+					let serialized_limits: Vec<u8> = limits.encode();
+					ffi::ext_sandbox_instantiate_with_limits(
+						dispatch_thunk,
+						code.as_ptr(),
+						code.len(),
+						serialized_env_def.as_ptr(),
+						serialized_env_def.len(),
+						serialized_limits.as_ptr(),
+						serialized_limits.len(),
+						state as *const T as usize,
+					)
+				}
+				None => ffi::ext_sandbox_instantiate(
+					dispatch_thunk,
+					code.as_ptr(),
+					code.len(),
+					serialized_env_def.as_ptr(),
+					serialized_env_def.len(),
+					state as *const T as usize,
+				),
+			}
 		};
 		let instance_idx = match result {
 			sandbox_primitives::ERR_MODULE => return Err(Error::Module),
-			sandbox_primitives::ERR_EXECUTION => return Err(Error::Execution),
+			sandbox_primitives::ERR_FORBIDDEN_IMPORT => return Err(Error::ForbiddenImport),
+			sandbox_primitives::ERR_FORBIDDEN_FEATURE => return Err(Error::ForbiddenFeature),
+			code @ sandbox_primitives::ERR_EXECUTION |
+			code @ sandbox_primitives::ERR_OUT_OF_FUEL |
+			code @ sandbox_primitives::ERR_STACK_OVERFLOW => return Err(trap_error(code)),
 			instance_idx => instance_idx,
 		};
 		// We need to retain memories to keep them alive while the Instance is alive.
@@ -307,8 +356,7 @@ impl<T> Instance<T> {
 					.ok_or(Error::Execution)?;
 				Ok(return_val)
 			}
-			sandbox_primitives::ERR_EXECUTION => Err(Error::Execution),
-			_ => unreachable!(),
+			code => Err(trap_error(code)),
 		}
 	}
 }
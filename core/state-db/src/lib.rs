@@ -237,6 +237,21 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 	}
 
 
+	pub fn insert_base_block<E: fmt::Debug>(&mut self, hash: &BlockHash, number: u64, parent_hash: &BlockHash, mut changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		match self.mode {
+			PruningMode::ArchiveAll => {
+				changeset.deleted.clear();
+				Ok(CommitSet {
+					data: changeset,
+					meta: Default::default(),
+				})
+			},
+			PruningMode::Constrained(_) | PruningMode::ArchiveCanonical => {
+				self.non_canonical.insert_base(hash, number, parent_hash, changeset)
+			}
+		}
+	}
+
 	/*
 	规范化 一个 block
 	*/
@@ -394,6 +409,12 @@ impl<BlockHash: Hash, Key: Hash> StateDb<BlockHash, Key> {
 		self.db.write().insert_block(hash, number, parent_hash, changeset)
 	}
 
+	/// Add a new non-canonical block which has no known ancestors, e.g. a block which state has
+	/// been downloaded from the network. All previously inserted blocks must be canonicalized.
+	pub fn insert_base_block<E: fmt::Debug>(&self, hash: &BlockHash, number: u64, parent_hash: &BlockHash, changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		self.db.write().insert_base_block(hash, number, parent_hash, changeset)
+	}
+
 	/// Finalize a previously inserted block.
 	/*
 	完成先前插入的块
@@ -230,6 +230,16 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		Ok(commit)
 	}
 
+	/// Insert a new block which has no known ancestors. Its parent is assumed to be canonicalized.
+	/// All previously inserted blocks must be canonicalized.
+	pub fn insert_base<E: fmt::Debug>(&mut self, hash: &BlockHash, number: u64, parent_hash: &BlockHash, changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		if !self.levels.is_empty() || number == 0 {
+			return Err(Error::InvalidBlockNumber);
+		}
+		self.last_canonicalized = None;
+		self.insert(hash, number, parent_hash, changeset)
+	}
+
 	fn discard_journals(&self, level_index: usize, discarded_journals: &mut Vec<Vec<u8>>, hash: &BlockHash) {
 		if let Some(level) = self.levels.get(level_index) {
 			level.iter().for_each(|overlay| {
@@ -477,6 +487,27 @@ mod tests {
 		assert!(db.data_eq(&make_db(&[1, 3, 4])));
 	}
 
+	#[test]
+	fn insert_base_after_canonicalized() {
+		let h1 = H256::random();
+		let h2 = H256::random();
+		let h3 = H256::random();
+		let mut db = make_db(&[1, 2]);
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		db.commit(&overlay.insert::<io::Error>(&h1, 1, &H256::default(), make_changeset(&[3], &[])).unwrap());
+		assert!(overlay.insert_base::<io::Error>(&h2, 10, &H256::random(), make_changeset(&[4], &[])).is_err());
+		db.commit(&overlay.canonicalize::<io::Error>(&h1).unwrap());
+		overlay.apply_pending();
+
+		db.commit(&overlay.insert_base::<io::Error>(&h2, 10, &H256::random(), make_changeset(&[4], &[])).unwrap());
+		db.commit(&overlay.insert::<io::Error>(&h3, 11, &h2, make_changeset(&[5], &[])).unwrap());
+		assert_eq!(overlay.last_canonicalized_block_number(), Some(9));
+		db.commit(&overlay.canonicalize::<io::Error>(&h2).unwrap());
+		overlay.apply_pending();
+		assert_eq!(overlay.last_canonicalized_hash(), Some(h2));
+		assert!(db.data_eq(&make_db(&[1, 2, 3, 4])));
+	}
+
 	#[test]
 	fn restore_from_journal() {
 		let h1 = H256::random();
@@ -30,6 +30,8 @@ pub struct BasicExternalities {
 	inner: HashMap<Vec<u8>, Vec<u8>>,
 	changes: OverlayedChanges,
 	code: Option<Vec<u8>>,
+	/// Storage and code at the start of the open storage transactions.
+	transactions: Vec<(HashMap<Vec<u8>, Vec<u8>>, Option<Vec<u8>>)>,
 }
 
 impl BasicExternalities {
@@ -44,7 +46,6 @@ impl BasicExternalities {
 		super::set_changes_trie_config(
 			&mut overlay,
 			inner.get(&CHANGES_TRIE_CONFIG.to_vec()).cloned(),
-			false,
 		).expect("changes trie configuration is correct in test env; qed");
 
 		inner.insert(HEAP_PAGES.to_vec(), 8u64.encode());
@@ -53,6 +54,7 @@ impl BasicExternalities {
 			inner,
 			changes: overlay,
 			code: Some(code.to_vec()),
+			transactions: Vec::new(),
 		}
 	}
 
@@ -98,6 +100,7 @@ impl From< HashMap<Vec<u8>, Vec<u8>> > for BasicExternalities {
 			inner: hashmap,
 			changes: Default::default(),
 			code: None,
+			transactions: Vec::new(),
 		}
 	}
 }
@@ -141,6 +144,25 @@ impl<H: Hasher> Externalities<H> for BasicExternalities where H::Out: Ord {
 		self.inner.retain(|key, _| !key.starts_with(prefix));
 	}
 
+	fn storage_start_transaction(&mut self) {
+		self.changes.start_transaction();
+		self.transactions.push((self.inner.clone(), self.code.clone()));
+	}
+
+	fn storage_rollback_transaction(&mut self) -> Result<(), ()> {
+		let (inner, code) = self.transactions.pop().ok_or(())?;
+		self.changes.rollback_transaction();
+		self.inner = inner;
+		self.code = code;
+		Ok(())
+	}
+
+	fn storage_commit_transaction(&mut self) -> Result<(), ()> {
+		self.transactions.pop().ok_or(())?;
+		self.changes.commit_transaction();
+		Ok(())
+	}
+
 	fn chain_id(&self) -> u64 { 42 }
 
 	fn storage_root(&mut self) -> H::Out {
@@ -49,6 +49,16 @@ pub fn prepare_input<'a, B, S, H>(
 		_ => return Ok(None),
 	};
 
+	// digests are built relative to the block where the active configuration has been set
+	let zero = storage.configuration_ranges(parent, parent.number + 1)?
+		.into_iter()
+		.last()
+		.and_then(|range| match range.end {
+			None => Some(range.zero),
+			Some(_) => None,
+		})
+		.unwrap_or(0);
+
 	let mut input = Vec::new();
 	input.extend(prepare_extrinsics_input(
 		backend,
@@ -57,6 +67,7 @@ pub fn prepare_input<'a, B, S, H>(
 	input.extend(prepare_digest_input::<_, H>(
 		parent,
 		config,
+		zero,
 		storage)?);
 
 	Ok(Some(input))
@@ -102,6 +113,7 @@ fn prepare_extrinsics_input<B, H>(
 fn prepare_digest_input<'a, S, H>(
 	parent: &'a AnchorBlockId<H::Out>,
 	config: &Configuration,
+	zero: u64,
 	storage: &'a S
 ) -> Result<impl Iterator<Item=InputPair> + 'a, String>
 	where
@@ -111,7 +123,7 @@ fn prepare_digest_input<'a, S, H>(
 		H::Out: 'a,
 {
 	let mut digest_map = BTreeMap::<Vec<u8>, BTreeSet<u64>>::new();
-	for digest_build_block in digest_build_iterator(config, parent.number + 1) {
+	for digest_build_block in digest_build_iterator(config, zero, parent.number + 1) {
 		let trie_root = storage.root(parent, digest_build_block)?;
 		let trie_root = trie_root.ok_or_else(|| format!("No changes trie root for block {}", digest_build_block))?;
 		let trie_storage = TrieBackendEssence::<_, H>::new(storage, trie_root);
@@ -217,6 +229,7 @@ mod test {
 				}),
 			].into_iter().collect(),
 			changes_trie_config: Some(Configuration { digest_interval: 4, digest_levels: 2 }),
+			transactions: Default::default(),
 		};
 
 		(backend, storage, changes)
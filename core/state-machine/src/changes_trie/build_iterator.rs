@@ -20,10 +20,11 @@
 use crate::changes_trie::Configuration;
 
 /// Returns iterator of OTHER blocks that are required for inclusion into
-/// changes trie of given block.
-pub fn digest_build_iterator(config: &Configuration, block: u64) -> DigestBuildIterator {
+/// changes trie of given block. `zero` is the number of the block where the
+/// configuration has been set.
+pub fn digest_build_iterator(config: &Configuration, zero: u64, block: u64) -> DigestBuildIterator {
 	// prepare digest build parameters
-	let (_, _, digest_step) = match config.digest_level_at_block(block) {
+	let (_, _, digest_step) = match config.digest_level_at_block(zero, block) {
 		Some((current_level, digest_interval, digest_step)) =>
 			(current_level, digest_interval, digest_step),
 		None => return DigestBuildIterator::empty(),
@@ -99,7 +100,7 @@ mod tests {
 	use super::*;
 
 	fn digest_build_iterator(digest_interval: u64, digest_levels: u32, block: u64) -> DigestBuildIterator {
-		super::digest_build_iterator(&Configuration { digest_interval, digest_levels }, block)
+		super::digest_build_iterator(&Configuration { digest_interval, digest_levels }, 0, block)
 	}
 
 	fn digest_build_iterator_basic(digest_interval: u64, digest_levels: u32, block: u64) -> (u64, u64, u64) {
@@ -190,4 +191,13 @@ mod tests {
 			],
 		);
 	}
+
+	#[test]
+	fn digest_iterator_returns_blocks_after_configuration_zero() {
+		let config = Configuration { digest_interval: 4, digest_levels: 2 };
+		assert_eq!(super::digest_build_iterator(&config, 10, 12).collect::<Vec<_>>(), Vec::<u64>::new());
+		assert_eq!(super::digest_build_iterator(&config, 10, 14).collect::<Vec<_>>(), vec![11, 12, 13]);
+		assert_eq!(super::digest_build_iterator(&config, 10, 26).collect::<Vec<_>>(),
+			vec![23, 24, 25, 14, 18, 22]);
+	}
 }
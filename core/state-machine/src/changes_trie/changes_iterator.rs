@@ -22,7 +22,7 @@ use std::collections::VecDeque;
 use parity_codec::{Decode, Encode};
 use hash_db::{HashDB, Hasher};
 use trie::{Recorder, MemoryDB};
-use crate::changes_trie::{AnchorBlockId, Configuration, RootsStorage, Storage};
+use crate::changes_trie::{AnchorBlockId, Configuration, ConfigurationRange, RootsStorage, Storage};
 use crate::changes_trie::input::{DigestIndex, ExtrinsicIndex, DigestIndexValue, ExtrinsicIndexValue};
 use crate::changes_trie::storage::{TrieBackendAdapter, InMemoryStorage};
 use crate::proving_backend::ProvingBackendEssence;
@@ -30,9 +30,10 @@ use crate::trie_backend_essence::{TrieBackendEssence};
 
 /// Return changes of given key at given blocks range.
 /// `max` is the number of best known block.
+/// Only changes made while the given configuration has been active are returned.
 /// Changes are returned in descending order (i.e. last block comes first).
 pub fn key_changes<'a, S: Storage<H>, H: Hasher>(
-	config: &'a Configuration,
+	config: &'a ConfigurationRange,
 	storage: &'a S,
 	begin: u64,
 	end: &'a AnchorBlockId<H::Out>,
@@ -61,8 +62,9 @@ pub fn key_changes<'a, S: Storage<H>, H: Hasher>(
 
 /// Returns proof of changes of given key at given blocks range.
 /// `max` is the number of best known block.
+/// Only changes made while the given configuration has been active are proved.
 pub fn key_changes_proof<S: Storage<H>, H: Hasher>(
-	config: &Configuration,
+	config: &ConfigurationRange,
 	storage: &S,
 	begin: u64,
 	end: &AnchorBlockId<H::Out>,
@@ -101,7 +103,7 @@ pub fn key_changes_proof<S: Storage<H>, H: Hasher>(
 /// `max` is the number of best known block.
 /// Changes are returned in descending order (i.e. last block comes first).
 pub fn key_changes_proof_check<S: RootsStorage<H>, H: Hasher>(
-	config: &Configuration,
+	config: &ConfigurationRange,
 	roots_storage: &S,
 	proof: Vec<Vec<u8>>,
 	begin: u64,
@@ -137,8 +139,11 @@ pub fn key_changes_proof_check<S: RootsStorage<H>, H: Hasher>(
 
 /// Surface iterator - only traverses top-level digests from given range and tries to find
 /// all digest changes for the key.
+///
+/// Internally all block numbers are relative to the zero block of the configuration.
 pub struct SurfaceIterator<'a> {
 	config: &'a Configuration,
+	zero: u64,
 	begin: u64,
 	max: u64,
 	current: Option<u64>,
@@ -178,7 +183,7 @@ impl<'a> Iterator for SurfaceIterator<'a> {
 			}
 		}
 
-		Some(Ok((current, digest_level)))
+		Some(Ok((self.zero + current, digest_level)))
 	}
 }
 
@@ -318,10 +323,41 @@ impl<'a, RS: 'a + RootsStorage<H>, S: Storage<H>, H: Hasher> Iterator for Provin
 }
 
 /// Returns surface iterator for given range of blocks.
-fn surface_iterator<'a>(config: &'a Configuration, max: u64, begin: u64, end: u64) -> Result<SurfaceIterator<'a>, String> {
-	let (current, current_begin, digest_step, digest_level) = lower_bound_max_digest(config, max, begin, end)?;
+fn surface_iterator<'a>(
+	config: &'a ConfigurationRange,
+	max: u64,
+	begin: u64,
+	end: u64,
+) -> Result<SurfaceIterator<'a>, String> {
+	if end > max || begin > end {
+		return Err("invalid changes range".into());
+	}
+
+	// only blocks where the configuration is active are traversed
+	let zero = config.zero;
+	let begin = ::std::cmp::max(begin, zero + 1);
+	let (max, end) = match config.end {
+		Some(config_end) => (::std::cmp::min(max, config_end), ::std::cmp::min(end, config_end)),
+		None => (max, end),
+	};
+	if begin > end {
+		return Ok(SurfaceIterator {
+			config: &config.config,
+			zero,
+			begin: 0,
+			max: 0,
+			current: None,
+			current_begin: 0,
+			digest_step: 0,
+			digest_level: 0,
+		});
+	}
+
+	let (begin, max, end) = (begin - zero, max - zero, end - zero);
+	let (current, current_begin, digest_step, digest_level) = lower_bound_max_digest(&config.config, max, begin, end)?;
 	Ok(SurfaceIterator {
-		config,
+		config: &config.config,
+		zero,
 		begin,
 		max,
 		current: Some(current),
@@ -394,8 +430,12 @@ mod tests {
 	use crate::changes_trie::storage::InMemoryStorage;
 	use super::*;
 
-	fn prepare_for_drilldown() -> (Configuration, InMemoryStorage<Blake2Hasher>) {
-		let config = Configuration { digest_interval: 4, digest_levels: 2 };
+	fn prepare_for_drilldown() -> (ConfigurationRange, InMemoryStorage<Blake2Hasher>) {
+		let config = ConfigurationRange {
+			config: Configuration { digest_interval: 4, digest_levels: 2 },
+			zero: 0,
+			end: None,
+		};
 		let backend = InMemoryStorage::with_inputs(vec![
 			// digest: 1..4 => [(3, 0)]
 			(1, vec![]),
@@ -462,6 +502,31 @@ mod tests {
 		assert_eq!(drilldown_result, Ok(vec![(6, 3)]));
 	}
 
+	#[test]
+	fn drilldown_iterator_only_traverses_configuration_range() {
+		let (config, storage) = prepare_for_drilldown();
+
+		// configuration that has been replaced at block 4
+		let old_config = ConfigurationRange { end: Some(4), ..config.clone() };
+		let drilldown_result = key_changes::<InMemoryStorage<Blake2Hasher>, Blake2Hasher>(
+			&old_config, &storage, 0, &AnchorBlockId { hash: Default::default(), number: 16 }, 16, &[42])
+			.and_then(Result::from_iter);
+		assert_eq!(drilldown_result, Ok(vec![(3, 0)]));
+
+		// configuration that has been set at block 4
+		let new_config = ConfigurationRange { zero: 4, ..config.clone() };
+		let drilldown_result = key_changes::<InMemoryStorage<Blake2Hasher>, Blake2Hasher>(
+			&new_config, &storage, 0, &AnchorBlockId { hash: Default::default(), number: 8 }, 8, &[42])
+			.and_then(Result::from_iter);
+		assert_eq!(drilldown_result, Ok(vec![(8, 2), (8, 1), (6, 3)]));
+
+		// range that doesn't intersect with the configuration range
+		let drilldown_result = key_changes::<InMemoryStorage<Blake2Hasher>, Blake2Hasher>(
+			&old_config, &storage, 5, &AnchorBlockId { hash: Default::default(), number: 8 }, 8, &[42])
+			.and_then(Result::from_iter);
+		assert_eq!(drilldown_result, Ok(vec![]));
+	}
+
 	#[test]
 	fn drilldown_iterator_fails_when_storage_fails() {
 		let (config, storage) = prepare_for_drilldown();
@@ -34,6 +34,12 @@
 //!
 //! Changes trie only contains the top level storage changes. Sub-level changes
 //! are propagated through its storage root on the top level storage.
+//!
+//! The configuration of changes tries may be changed by the runtime. The new
+//! configuration is active starting from the block that follows the block where
+//! it has been set. Digests are built relative to this block (zero block of the
+//! configuration), so digests never include blocks of the previous configurations.
+//! The digest hierarchy of the previous configuration is left incomplete.
 
 mod build;
 mod build_iterator;
@@ -45,6 +51,7 @@ mod storage;
 pub use self::storage::InMemoryStorage;
 pub use self::changes_iterator::{key_changes, key_changes_proof, key_changes_proof_check};
 pub use self::prune::{prune, oldest_non_pruned_trie};
+pub use self::storage::configuration_ranges;
 
 use hash_db::Hasher;
 use crate::backend::Backend;
@@ -66,11 +73,32 @@ pub struct AnchorBlockId<Hash: ::std::fmt::Debug> {
 	pub number: u64,
 }
 
+/// Changes trie configuration that is active at a range of blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigurationRange {
+	/// Active configuration.
+	pub config: Configuration,
+	/// Number of the block where the configuration has been set (0 for the genesis
+	/// configuration). The configuration is active starting from the next block.
+	pub zero: u64,
+	/// Number of the last block where the configuration is active. `None` if the
+	/// configuration is still active.
+	pub end: Option<u64>,
+}
+
 /// Changes trie storage. Provides access to trie roots and trie nodes.
 pub trait RootsStorage<H: Hasher>: Send + Sync {
 	/// Get changes trie root for the block with given number which is an ancestor (or the block
 	/// itself) of the anchor_block (i.e. anchor_block.number >= block).
 	fn root(&self, anchor: &AnchorBlockId<H::Out>, block: u64) -> Result<Option<H::Out>, String>;
+	/// Get configurations of changes tries that are active at the blocks `begin..=anchor.number`
+	/// of the anchor_block chain and at the block that follows the anchor_block. Ranges are
+	/// ordered by the block number, ranges where changes tries are disabled are omitted.
+	fn configuration_ranges(
+		&self,
+		anchor: &AnchorBlockId<H::Out>,
+		begin: u64,
+	) -> Result<Vec<ConfigurationRange>, String>;
 }
 
 /// Changes trie storage. Provides access to trie roots and trie nodes.
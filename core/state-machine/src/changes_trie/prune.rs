@@ -21,32 +21,40 @@ use trie::Recorder;
 use log::warn;
 use crate::proving_backend::ProvingBackendEssence;
 use crate::trie_backend_essence::TrieBackendEssence;
-use crate::changes_trie::{AnchorBlockId, Configuration, Storage};
+use crate::changes_trie::{AnchorBlockId, Configuration, ConfigurationRange, Storage};
 use crate::changes_trie::storage::TrieBackendAdapter;
 
 /// Get number of oldest block for which changes trie is not pruned
-/// given changes trie configuration, pruning parameter and number of
+/// given changes trie configuration range, pruning parameter and number of
 /// best finalized block.
+/// When all changes tries of the configuration range are pruned, the number
+/// of the block that follows the range is returned.
 pub fn oldest_non_pruned_trie(
-	config: &Configuration,
+	config: &ConfigurationRange,
 	min_blocks_to_keep: u64,
 	best_finalized_block: u64,
 ) -> u64 {
-	let max_digest_interval = config.max_digest_interval();
-	let max_digest_block = best_finalized_block - best_finalized_block % max_digest_interval;
-	match pruning_range(config, min_blocks_to_keep, max_digest_block) {
-		Some((_, last_pruned_block)) => last_pruned_block + 1,
-		None => 1,
+	let block = match config.end {
+		Some(end) if best_finalized_block >= end.saturating_add(min_blocks_to_keep) => return end + 1,
+		Some(end) => ::std::cmp::min(end, best_finalized_block),
+		None => best_finalized_block,
+	};
+	if block <= config.zero {
+		return config.zero + 1;
 	}
+
+	config.zero + oldest_non_pruned_relative_trie(&config.config, min_blocks_to_keep, block - config.zero)
 }
 
 /// Prune obsolete changes tries. Pruning happens at the same block, where highest
 /// level digest is created. Pruning guarantees to save changes tries for last
 /// `min_blocks_to_keep` blocks. We only prune changes tries at `max_digest_interval`
 /// ranges.
+/// Changes tries of the configuration range that is no longer active are all pruned
+/// at once, `min_blocks_to_keep` blocks after the end of the range.
 /// Returns MemoryDB that contains all deleted changes tries nodes.
 pub fn prune<S: Storage<H>, H: Hasher, F: FnMut(H::Out)>(
-	config: &Configuration,
+	config: &ConfigurationRange,
 	storage: &S,
 	min_blocks_to_keep: u64,
 	current_block: &AnchorBlockId<H::Out>,
@@ -54,9 +62,30 @@ pub fn prune<S: Storage<H>, H: Hasher, F: FnMut(H::Out)>(
 )
 {
 	// select range for pruning
-	let (first, last) = match pruning_range(config, min_blocks_to_keep, current_block.number) {
-		Some((first, last)) => (first, last),
-		None => return,
+	let zero = config.zero;
+	let (first, last) = match config.end {
+		Some(end) => {
+			if end.checked_add(min_blocks_to_keep) != Some(current_block.number) {
+				return;
+			}
+
+			let first = zero + oldest_non_pruned_relative_trie(&config.config, min_blocks_to_keep, end - zero);
+			if first > end {
+				return;
+			}
+
+			(first, end)
+		},
+		None => {
+			if current_block.number <= zero {
+				return;
+			}
+
+			match pruning_range(&config.config, min_blocks_to_keep, current_block.number - zero) {
+				Some((first, last)) => (zero + first, zero + last),
+				None => return,
+			}
+		},
 	};
 
 	// delete changes trie for every block in range
@@ -91,12 +120,28 @@ pub fn prune<S: Storage<H>, H: Hasher, F: FnMut(H::Out)>(
 	}
 }
 
+/// Get number of oldest block for which changes trie is not pruned, assuming that
+/// the configuration has been active since genesis.
+fn oldest_non_pruned_relative_trie(
+	config: &Configuration,
+	min_blocks_to_keep: u64,
+	best_finalized_block: u64,
+) -> u64 {
+	let max_digest_interval = config.max_digest_interval();
+	let max_digest_block = best_finalized_block - best_finalized_block % max_digest_interval;
+	match pruning_range(config, min_blocks_to_keep, max_digest_block) {
+		Some((_, last_pruned_block)) => last_pruned_block + 1,
+		None => 1,
+	}
+}
+
 /// Select blocks range (inclusive from both ends) for pruning changes tries in.
+/// Block numbers are relative to the zero block of the configuration.
 fn pruning_range(config: &Configuration, min_blocks_to_keep: u64, block: u64) -> Option<(u64, u64)> {
 	// compute number of changes tries we actually want to keep
 	let (prune_interval, blocks_to_keep) = if config.is_digest_build_enabled() {
 		// we only CAN prune at block where max-level-digest is created
-		let max_digest_interval = match config.digest_level_at_block(block) {
+		let max_digest_interval = match config.digest_level_at_block(0, block) {
 			Some((digest_level, digest_interval, _)) if digest_level == config.digest_levels =>
 				digest_interval,
 			_ => return None,
@@ -161,11 +206,24 @@ mod tests {
 		}
 	}
 
+	fn range(config: Configuration, zero: u64, end: Option<u64>) -> ConfigurationRange {
+		ConfigurationRange { config, zero, end }
+	}
+
 	fn prune_by_collect<S: Storage<H>, H: Hasher>(
 		config: &Configuration,
 		storage: &S,
 		min_blocks_to_keep: u64,
 		current_block: u64,
+	) -> HashSet<H::Out> {
+		prune_range_by_collect(&range(config.clone(), 0, None), storage, min_blocks_to_keep, current_block)
+	}
+
+	fn prune_range_by_collect<S: Storage<H>, H: Hasher>(
+		config: &ConfigurationRange,
+		storage: &S,
+		min_blocks_to_keep: u64,
+		current_block: u64,
 	) -> HashSet<H::Out> {
 		let mut pruned_trie_nodes = HashSet::new();
 		prune(config, storage, min_blocks_to_keep, &AnchorBlockId { hash: Default::default(), number: current_block },
@@ -239,6 +297,41 @@ mod tests {
 		assert!(storage.into_mdb().drain().is_empty());
 	}
 
+	#[test]
+	fn prune_works_with_configuration_ranges() {
+		fn prepare_storage() -> InMemoryStorage<Blake2Hasher> {
+			let mut mdb1 = MemoryDB::<Blake2Hasher>::default();
+			let root1 = insert_into_memory_db::<Blake2Hasher, _>(&mut mdb1, vec![(vec![10], vec![20])]).unwrap();
+			let mut mdb2 = MemoryDB::<Blake2Hasher>::default();
+			let root2 = insert_into_memory_db::<Blake2Hasher, _>(&mut mdb2, vec![(vec![11], vec![21])]).unwrap();
+			let storage = InMemoryStorage::new();
+			storage.insert(11, root1, mdb1);
+			storage.insert(12, root2, mdb2);
+
+			storage
+		}
+
+		// configuration that has been set at block 10 => digests are created at 14, 18, ...
+		let config = Configuration { digest_interval: 4, digest_levels: 1 };
+		let storage = prepare_storage();
+		assert!(prune_range_by_collect(&range(config.clone(), 10, None), &storage, 0, 16).is_empty());
+		assert!(prune_range_by_collect(&range(config.clone(), 10, None), &storage, 0, 17).is_empty());
+		let non_empty = prune_range_by_collect(&range(config.clone(), 10, None), &storage, 0, 18);
+		assert!(!non_empty.is_empty());
+		storage.remove_from_storage(&non_empty);
+		assert!(storage.into_mdb().drain().is_empty());
+
+		// configuration that has been replaced at block 13 => all its tries are pruned
+		// min_blocks_to_keep blocks later
+		let storage = prepare_storage();
+		assert!(prune_range_by_collect(&range(config.clone(), 10, Some(13)), &storage, 4, 13).is_empty());
+		assert!(prune_range_by_collect(&range(config.clone(), 10, Some(13)), &storage, 4, 16).is_empty());
+		let non_empty = prune_range_by_collect(&range(config.clone(), 10, Some(13)), &storage, 4, 17);
+		assert!(!non_empty.is_empty());
+		storage.remove_from_storage(&non_empty);
+		assert!(storage.into_mdb().drain().is_empty());
+	}
+
 	#[test]
 	fn pruning_range_works() {
 		// DIGESTS ARE NOT CREATED + NO TRIES ARE PRUNED
@@ -282,20 +375,30 @@ mod tests {
 
 	#[test]
 	fn oldest_non_pruned_trie_works() {
+		let genesis = |interval, levels| range(config(interval, levels), 0, None);
+
 		// when digests are not created at all
-		assert_eq!(oldest_non_pruned_trie(&config(0, 0), 100, 10), 1);
-		assert_eq!(oldest_non_pruned_trie(&config(0, 0), 100, 110), 11);
+		assert_eq!(oldest_non_pruned_trie(&genesis(0, 0), 100, 10), 1);
+		assert_eq!(oldest_non_pruned_trie(&genesis(0, 0), 100, 110), 11);
 
 		// when only l1 digests are created
-		assert_eq!(oldest_non_pruned_trie(&config(100, 1), 100, 50), 1);
-		assert_eq!(oldest_non_pruned_trie(&config(100, 1), 100, 110), 1);
-		assert_eq!(oldest_non_pruned_trie(&config(100, 1), 100, 210), 101);
+		assert_eq!(oldest_non_pruned_trie(&genesis(100, 1), 100, 50), 1);
+		assert_eq!(oldest_non_pruned_trie(&genesis(100, 1), 100, 110), 1);
+		assert_eq!(oldest_non_pruned_trie(&genesis(100, 1), 100, 210), 101);
 
 		// when l2 digests are created
-		assert_eq!(oldest_non_pruned_trie(&config(100, 2), 100, 50), 1);
-		assert_eq!(oldest_non_pruned_trie(&config(100, 2), 100, 110), 1);
-		assert_eq!(oldest_non_pruned_trie(&config(100, 2), 100, 210), 1);
-		assert_eq!(oldest_non_pruned_trie(&config(100, 2), 100, 10110), 1);
-		assert_eq!(oldest_non_pruned_trie(&config(100, 2), 100, 20110), 10001);
+		assert_eq!(oldest_non_pruned_trie(&genesis(100, 2), 100, 50), 1);
+		assert_eq!(oldest_non_pruned_trie(&genesis(100, 2), 100, 110), 1);
+		assert_eq!(oldest_non_pruned_trie(&genesis(100, 2), 100, 210), 1);
+		assert_eq!(oldest_non_pruned_trie(&genesis(100, 2), 100, 10110), 1);
+		assert_eq!(oldest_non_pruned_trie(&genesis(100, 2), 100, 20110), 10001);
+
+		// when configuration has been set at block 1000
+		assert_eq!(oldest_non_pruned_trie(&range(config(100, 1), 1000, None), 100, 500), 1001);
+		assert_eq!(oldest_non_pruned_trie(&range(config(100, 1), 1000, None), 100, 1210), 1101);
+
+		// when configuration has been replaced at block 1150
+		assert_eq!(oldest_non_pruned_trie(&range(config(100, 1), 1000, Some(1150)), 100, 1210), 1001);
+		assert_eq!(oldest_non_pruned_trie(&range(config(100, 1), 1000, Some(1150)), 100, 1250), 1151);
 	}
 }
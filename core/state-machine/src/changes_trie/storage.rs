@@ -16,12 +16,12 @@
 
 //! Changes trie storage utilities.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use hash_db::Hasher;
 use trie::DBValue;
 use trie::MemoryDB;
 use parking_lot::RwLock;
-use crate::changes_trie::{AnchorBlockId, RootsStorage, Storage};
+use crate::changes_trie::{AnchorBlockId, Configuration, ConfigurationRange, RootsStorage, Storage};
 use crate::trie_backend_essence::TrieBackendStorage;
 
 #[cfg(test)]
@@ -45,6 +45,7 @@ pub struct TrieBackendAdapter<'a, H: Hasher, S: 'a + Storage<H>> {
 struct InMemoryStorageData<H: Hasher> {
 	roots: HashMap<u64, H::Out>,
 	mdb: MemoryDB<H>,
+	configurations: BTreeMap<u64, Option<Configuration>>,
 }
 
 impl<H: Hasher> InMemoryStorage<H> {
@@ -54,6 +55,7 @@ impl<H: Hasher> InMemoryStorage<H> {
 			data: RwLock::new(InMemoryStorageData {
 				roots: HashMap::new(),
 				mdb,
+				configurations: BTreeMap::new(),
 			}),
 		}
 	}
@@ -78,6 +80,7 @@ impl<H: Hasher> InMemoryStorage<H> {
 			data: RwLock::new(InMemoryStorageData {
 				roots,
 				mdb,
+				configurations: BTreeMap::new(),
 			}),
 		}
 	}
@@ -106,12 +109,52 @@ impl<H: Hasher> InMemoryStorage<H> {
 		data.roots.insert(block, changes_trie_root);
 		data.mdb.consolidate(trie);
 	}
+
+	/// Set changes trie configuration that is active starting from the block that follows
+	/// given block. `None` disables changes tries.
+	pub fn set_configuration(&self, block: u64, config: Option<Configuration>) {
+		self.data.write().configurations.insert(block, config);
+	}
+}
+
+/// Build configuration ranges of a single chain from the configuration changes: numbers of
+/// blocks where the configuration has been set, ordered by the block number, together with
+/// the configuration. Returns ranges that are active at blocks starting from `begin`.
+pub fn configuration_ranges<I>(changes: I, begin: u64) -> Vec<ConfigurationRange>
+	where I: IntoIterator<Item=(u64, Option<Configuration>)>
+{
+	let mut ranges = Vec::new();
+	let mut current: Option<(u64, Option<Configuration>)> = None;
+	for (block, config) in changes {
+		if let Some((zero, Some(current_config))) = current.take() {
+			if block >= begin {
+				ranges.push(ConfigurationRange { config: current_config, zero, end: Some(block) });
+			}
+		}
+		current = Some((block, config));
+	}
+	if let Some((zero, Some(config))) = current {
+		ranges.push(ConfigurationRange { config, zero, end: None });
+	}
+	ranges
 }
 
 impl<H: Hasher> RootsStorage<H> for InMemoryStorage<H> {
 	fn root(&self, _anchor_block: &AnchorBlockId<H::Out>, block: u64) -> Result<Option<H::Out>, String> {
 		Ok(self.data.read().roots.get(&block).cloned())
 	}
+
+	fn configuration_ranges(
+		&self,
+		anchor: &AnchorBlockId<H::Out>,
+		begin: u64,
+	) -> Result<Vec<ConfigurationRange>, String> {
+		let data = self.data.read();
+		Ok(configuration_ranges(
+			data.configurations.range(..=anchor.number).map(|(block, config)| (*block, config.clone())),
+			begin,
+		))
+	}
 }
 
 impl<H: Hasher> Storage<H> for InMemoryStorage<H> {
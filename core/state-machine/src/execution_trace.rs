@@ -0,0 +1,337 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Opt-in tracing of the runtime execution.
+//!
+//! While `trace` runs, the executor reports the calls to the host functions and `Ext`
+//! reports the storage accesses of the current thread. The collected statistics are split
+//! into segments (e.g. one per extrinsic) by `start_segment`. When tracing is disabled,
+//! reporting is a check of a thread-local flag.
+//!
+//! The keys of the storage entries are hashes, so the executor also reports what the runtime
+//! hashes. A key hashed from data starting with a known prefix (e.g. `"Balances FreeBalance"`
+//! followed by the account) is reported in the group of that prefix.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+/// The group of the accesses to storage entries whose key isn't hashed from a known prefix.
+pub const UNNAMED_KEYS_GROUP: &str = "other";
+
+/// Names of the groups of storage entries by the prefix their keys are hashed from, e.g.
+/// `"Balances FreeBalance"` for the entries of that map and `"Balances TotalIssuance"` for
+/// that storage value (whose key is the hash of the prefix alone).
+pub type StorageKeyNames = HashMap<Vec<u8>, String>;
+
+/// Number of calls and time spent in them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallStats {
+	/// Number of calls.
+	pub calls: u64,
+	/// Total time spent in the calls, in nanoseconds.
+	pub time_ns: u64,
+}
+
+/// Accesses to the storage entries of a key group.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageAccessStats {
+	/// Number of reads.
+	pub reads: u64,
+	/// Total size of the read values.
+	pub read_bytes: u64,
+	/// Number of writes, including removals.
+	pub writes: u64,
+	/// Total size of the written values.
+	pub write_bytes: u64,
+}
+
+/// Statistics of a part of the execution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceSegment {
+	/// Name of the segment, given to `start_segment`.
+	pub name: String,
+	/// Time from the start to the end of the segment, in nanoseconds.
+	pub time_ns: u64,
+	/// Calls to the host functions, by the function name.
+	pub host_functions: BTreeMap<String, CallStats>,
+	/// Storage accesses, by the key group. See `record_hash`.
+	pub storage: BTreeMap<String, StorageAccessStats>,
+}
+
+impl TraceSegment {
+	fn new(name: String) -> Self {
+		TraceSegment {
+			name,
+			time_ns: 0,
+			host_functions: BTreeMap::new(),
+			storage: BTreeMap::new(),
+		}
+	}
+
+	/// Time of the segment that hasn't been spent in the host functions, in nanoseconds.
+	pub fn runtime_time_ns(&self) -> u64 {
+		let host_time: u64 = self.host_functions.values().map(|stats| stats.time_ns).sum();
+		self.time_ns.saturating_sub(host_time)
+	}
+}
+
+/// Statistics collected by `trace`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionTrace {
+	/// The segments in the order of execution.
+	pub segments: Vec<TraceSegment>,
+}
+
+struct Collector {
+	trace: ExecutionTrace,
+	segment_start: Option<Instant>,
+	key_names: StorageKeyNames,
+	/// Groups of the hashes reported by `record_hash`.
+	hashed_keys: HashMap<Vec<u8>, String>,
+}
+
+impl Collector {
+	fn current_segment(&mut self) -> &mut TraceSegment {
+		if self.segment_start.is_none() {
+			self.segment_start = Some(Instant::now());
+			self.trace.segments.push(TraceSegment::new("unnamed".into()));
+		}
+		self.trace.segments.last_mut().expect("segment is pushed when there is no current one; qed")
+	}
+
+	/// The group the accesses to the storage entry under `key` are reported in.
+	///
+	/// Well known keys (including the keys of child storages) are their own group, and so
+	/// are the keys hashed from a named prefix. The other keys make up the `UNNAMED_KEYS_GROUP`.
+	fn key_group(&self, key: &[u8]) -> String {
+		if key.starts_with(b":") {
+			return String::from_utf8_lossy(key).into_owned();
+		}
+		self.hashed_keys.get(key).cloned().unwrap_or_else(|| UNNAMED_KEYS_GROUP.into())
+	}
+
+	fn end_segment(&mut self) {
+		if let Some(start) = self.segment_start.take() {
+			if let Some(segment) = self.trace.segments.last_mut() {
+				segment.time_ns = duration_ns(start.elapsed());
+			}
+		}
+	}
+}
+
+thread_local! {
+	static COLLECTOR: RefCell<Option<Collector>> = RefCell::new(None);
+}
+
+fn duration_ns(duration: Duration) -> u64 {
+	duration.as_secs() * 1_000_000_000 + duration.subsec_nanos() as u64
+}
+
+fn with_collector<F: FnOnce(&mut Collector)>(f: F) {
+	COLLECTOR.with(|collector| if let Some(collector) = collector.borrow_mut().as_mut() {
+		f(collector)
+	})
+}
+
+/// Execute `f` with tracing enabled on the current thread and return the collected statistics.
+///
+/// Nested calls aren't supported: the inner call gets the statistics collected so far.
+pub fn trace<R, F: FnOnce() -> R>(f: F) -> (R, ExecutionTrace) {
+	trace_with_key_names(Default::default(), f)
+}
+
+/// Execute `f` with tracing enabled the way `trace` does, reporting the storage accesses
+/// by the names of the prefixes their keys are hashed from. See `record_hash`.
+pub fn trace_with_key_names<R, F: FnOnce() -> R>(key_names: StorageKeyNames, f: F) -> (R, ExecutionTrace) {
+	COLLECTOR.with(|collector| *collector.borrow_mut() = Some(Collector {
+		trace: Default::default(),
+		segment_start: None,
+		key_names,
+		hashed_keys: HashMap::new(),
+	}));
+	let result = f();
+	let trace = COLLECTOR.with(|collector| collector.borrow_mut().take())
+		.map(|mut collector| {
+			collector.end_segment();
+			collector.trace
+		})
+		.unwrap_or_default();
+	(result, trace)
+}
+
+/// Whether tracing is enabled on the current thread.
+pub fn is_enabled() -> bool {
+	COLLECTOR.with(|collector| collector.borrow().is_some())
+}
+
+/// End the current segment and start a new one with the given name.
+pub fn start_segment<S: Into<String>>(name: S) {
+	with_collector(|collector| {
+		collector.end_segment();
+		collector.segment_start = Some(Instant::now());
+		collector.trace.segments.push(TraceSegment::new(name.into()));
+	})
+}
+
+/// End the current segment. Statistics reported after that go to a new unnamed segment.
+pub fn end_segment() {
+	with_collector(|collector| collector.end_segment())
+}
+
+/// Report a call to a host function that took `elapsed`.
+pub fn record_host_function(name: &str, elapsed: Duration) {
+	with_collector(|collector| {
+		let stats = collector.current_segment().host_functions.entry(name.into()).or_default();
+		stats.calls += 1;
+		stats.time_ns += duration_ns(elapsed);
+	})
+}
+
+/// Report that the runtime hashed `data` to `hash`. If `data` starts with a named prefix (the
+/// longest one if several do), the accesses to the storage entry under `hash` are reported in
+/// the group of that name.
+pub fn record_hash(data: &[u8], hash: &[u8]) {
+	with_collector(|collector| {
+		let name = collector.key_names.iter()
+			.filter(|(prefix, _)| data.starts_with(prefix))
+			.max_by_key(|(prefix, _)| prefix.len())
+			.map(|(_, name)| name.clone());
+		if let Some(name) = name {
+			collector.hashed_keys.insert(hash.to_vec(), name);
+		}
+	})
+}
+
+/// Report a read of the storage entry under `key`. `value_len` is `None` if there was no entry.
+pub fn record_storage_read(key: &[u8], value_len: Option<usize>) {
+	with_collector(|collector| {
+		let group = collector.key_group(key);
+		let stats = collector.current_segment().storage.entry(group).or_default();
+		stats.reads += 1;
+		stats.read_bytes += value_len.unwrap_or(0) as u64;
+	})
+}
+
+/// Report a write of the storage entry under `key`. `value_len` is `None` for a removal.
+pub fn record_storage_write(key: &[u8], value_len: Option<usize>) {
+	with_collector(|collector| {
+		let group = collector.key_group(key);
+		let stats = collector.current_segment().storage.entry(group).or_default();
+		stats.writes += 1;
+		stats.write_bytes += value_len.unwrap_or(0) as u64;
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn nothing_is_collected_without_tracing() {
+		assert!(!is_enabled());
+		start_segment("test");
+		record_host_function("ext_print_num", Duration::from_millis(1));
+
+		let ((), trace) = trace(|| assert!(is_enabled()));
+		assert!(!is_enabled());
+		assert_eq!(trace, ExecutionTrace::default());
+	}
+
+	#[test]
+	fn statistics_are_collected_by_segment() {
+		let ((), trace) = trace(|| {
+			record_storage_read(b":code", Some(10));
+			start_segment("first");
+			record_host_function("ext_get_storage", Duration::from_nanos(5));
+			record_host_function("ext_get_storage", Duration::from_nanos(7));
+			record_storage_read(&[1; 32], Some(4));
+			record_storage_read(&[1; 16], None);
+			record_storage_write(&[2; 32], Some(8));
+			start_segment("second");
+			record_storage_write(&[2; 32], None);
+			end_segment();
+		});
+
+		let names: Vec<_> = trace.segments.iter().map(|segment| segment.name.as_str()).collect();
+		assert_eq!(names, vec!["unnamed", "first", "second"]);
+		assert_eq!(trace.segments[0].storage[":code"].read_bytes, 10);
+
+		let first = &trace.segments[1];
+		assert_eq!(first.host_functions["ext_get_storage"], CallStats { calls: 2, time_ns: 12 });
+		assert_eq!(
+			first.storage[UNNAMED_KEYS_GROUP],
+			StorageAccessStats { reads: 2, read_bytes: 4, writes: 1, write_bytes: 8 },
+		);
+		assert!(first.runtime_time_ns() <= first.time_ns);
+
+		assert_eq!(
+			trace.segments[2].storage[UNNAMED_KEYS_GROUP],
+			StorageAccessStats { writes: 1, ..Default::default() },
+		);
+	}
+
+	#[test]
+	fn storage_accesses_are_reported_by_name() {
+		let mut key_names = StorageKeyNames::new();
+		key_names.insert(b"Balances TotalIssuance".to_vec(), "Balances TotalIssuance".into());
+
+		let ((), trace) = trace_with_key_names(key_names, || {
+			record_hash(b"Balances TotalIssuance", &[1; 16]);
+			record_storage_read(&[1; 16], Some(8));
+			record_storage_write(&[1; 16], Some(8));
+			record_storage_read(&[1; 32], None);
+			record_storage_read(b":heappages", Some(8));
+		});
+
+		let groups: Vec<_> = trace.segments[0].storage.keys().map(String::as_str).collect();
+		assert_eq!(groups, vec![":heappages", "Balances TotalIssuance", UNNAMED_KEYS_GROUP]);
+		assert_eq!(
+			trace.segments[0].storage["Balances TotalIssuance"],
+			StorageAccessStats { reads: 1, read_bytes: 8, writes: 1, write_bytes: 8 },
+		);
+	}
+
+	#[test]
+	fn map_entries_are_grouped_by_prefix() {
+		let mut key_names = StorageKeyNames::new();
+		key_names.insert(b"Balances FreeBalance".to_vec(), "Balances FreeBalance".into());
+		key_names.insert(b"Balances FreeBalanceOf".to_vec(), "Balances FreeBalanceOf".into());
+		key_names.insert(b"Balances Locks".to_vec(), "Balances Locks".into());
+
+		let ((), trace) = trace_with_key_names(key_names, || {
+			record_hash(b"Balances FreeBalance\x01", &[1; 32]);
+			record_hash(b"Balances FreeBalance\x02", &[2; 32]);
+			record_hash(b"Balances FreeBalanceOf\x01", &[3; 32]);
+			record_hash(b"System AccountNonce\x01", &[4; 32]);
+			for key in 1..5 {
+				record_storage_read(&[key; 32], Some(16));
+			}
+		});
+
+		let storage = &trace.segments[0].storage;
+		let groups: Vec<_> = storage.keys().map(String::as_str).collect();
+		assert_eq!(groups, vec!["Balances FreeBalance", "Balances FreeBalanceOf", UNNAMED_KEYS_GROUP]);
+		assert_eq!(storage["Balances FreeBalance"].reads, 2);
+		assert_eq!(storage["Balances FreeBalanceOf"].reads, 1);
+		assert_eq!(storage[UNNAMED_KEYS_GROUP].reads, 1);
+	}
+}
@@ -17,10 +17,11 @@
 //! Conrete externalities implementation.
 
 use std::{error, fmt, cmp::Ord};
-use log::warn;
+use log::{error, warn};
 use crate::backend::Backend;
 use crate::changes_trie::{AnchorBlockId, Storage as ChangesTrieStorage, compute_changes_trie_root};
 use crate::{Externalities, OverlayedChanges, OffchainExt, ChildStorageKey};
+use crate::execution_trace;
 use hash_db::Hasher;
 use primitives::storage::well_known_keys::is_child_storage_key;
 use trie::{MemoryDB, TrieDBMut, TrieMut, default_child_trie_root};
@@ -134,6 +135,31 @@ where
 		self.storage_transaction = None;
 	}
 
+	/// Roll back the storage transactions the runtime has left open when `method` returned.
+	///
+	/// A runtime that traps in a transaction leaves it open, but one that returns normally
+	/// should have closed all of them, so this is reported as an error of the runtime then.
+	pub(crate) fn rollback_open_transactions(&mut self, method: &str, trapped: bool) {
+		let open_transactions = self.overlay.rollback_open_transactions();
+		if open_transactions == 0 {
+			return;
+		}
+		if trapped {
+			warn!(
+				target: "state-machine",
+				"Rolled back {} storage transaction(s) left open by the failed call to {}",
+				open_transactions, method,
+			);
+		} else {
+			error!(
+				target: "state-machine",
+				"Rolled back {} storage transaction(s) the runtime left open on returning from {}",
+				open_transactions, method,
+			);
+		}
+		self.mark_dirty();
+	}
+
 }
 
 #[cfg(test)]
@@ -169,34 +195,44 @@ where
 {
 	fn storage(&self, key: &[u8]) -> Option<Vec<u8>> {
 		let _guard = panic_handler::AbortGuard::new(true);
-		self.overlay.storage(key).map(|x| x.map(|x| x.to_vec())).unwrap_or_else(||
-			self.backend.storage(key).expect(EXT_NOT_ALLOWED_TO_FAIL))
+		let value = self.overlay.storage(key).map(|x| x.map(|x| x.to_vec())).unwrap_or_else(||
+			self.backend.storage(key).expect(EXT_NOT_ALLOWED_TO_FAIL));
+		execution_trace::record_storage_read(key, value.as_ref().map(Vec::len));
+		value
 	}
 
 	fn storage_hash(&self, key: &[u8]) -> Option<H::Out> {
 		let _guard = panic_handler::AbortGuard::new(true);
-		self.overlay.storage(key).map(|x| x.map(|x| H::hash(x))).unwrap_or_else(||
-			self.backend.storage_hash(key).expect(EXT_NOT_ALLOWED_TO_FAIL))
+		let hash = self.overlay.storage(key).map(|x| x.map(|x| H::hash(x))).unwrap_or_else(||
+			self.backend.storage_hash(key).expect(EXT_NOT_ALLOWED_TO_FAIL));
+		execution_trace::record_storage_read(key, None);
+		hash
 	}
 
 	fn original_storage(&self, key: &[u8]) -> Option<Vec<u8>> {
 		let _guard = panic_handler::AbortGuard::new(true);
-		self.backend.storage(key).expect(EXT_NOT_ALLOWED_TO_FAIL)
+		let value = self.backend.storage(key).expect(EXT_NOT_ALLOWED_TO_FAIL);
+		execution_trace::record_storage_read(key, value.as_ref().map(Vec::len));
+		value
 	}
 
 	fn original_storage_hash(&self, key: &[u8]) -> Option<H::Out> {
 		let _guard = panic_handler::AbortGuard::new(true);
+		execution_trace::record_storage_read(key, None);
 		self.backend.storage_hash(key).expect(EXT_NOT_ALLOWED_TO_FAIL)
 	}
 
 	fn child_storage(&self, storage_key: ChildStorageKey<H>, key: &[u8]) -> Option<Vec<u8>> {
 		let _guard = panic_handler::AbortGuard::new(true);
-		self.overlay.child_storage(storage_key.as_ref(), key).map(|x| x.map(|x| x.to_vec())).unwrap_or_else(||
-			self.backend.child_storage(storage_key.as_ref(), key).expect(EXT_NOT_ALLOWED_TO_FAIL))
+		let value = self.overlay.child_storage(storage_key.as_ref(), key).map(|x| x.map(|x| x.to_vec())).unwrap_or_else(||
+			self.backend.child_storage(storage_key.as_ref(), key).expect(EXT_NOT_ALLOWED_TO_FAIL));
+		execution_trace::record_storage_read(storage_key.as_ref(), value.as_ref().map(Vec::len));
+		value
 	}
 
 	fn exists_storage(&self, key: &[u8]) -> bool {
 		let _guard = panic_handler::AbortGuard::new(true);
+		execution_trace::record_storage_read(key, None);
 		match self.overlay.storage(key) {
 			Some(x) => x.is_some(),
 			_ => self.backend.exists_storage(key).expect(EXT_NOT_ALLOWED_TO_FAIL),
@@ -205,6 +241,7 @@ where
 
 	fn exists_child_storage(&self, storage_key: ChildStorageKey<H>, key: &[u8]) -> bool {
 		let _guard = panic_handler::AbortGuard::new(true);
+		execution_trace::record_storage_read(storage_key.as_ref(), None);
 
 		match self.overlay.child_storage(storage_key.as_ref(), key) {
 			Some(x) => x.is_some(),
@@ -219,6 +256,7 @@ where
 			return;
 		}
 
+		execution_trace::record_storage_write(&key, value.as_ref().map(Vec::len));
 		self.mark_dirty();
 		self.overlay.set_storage(key, value);
 	}
@@ -226,6 +264,7 @@ where
 	fn place_child_storage(&mut self, storage_key: ChildStorageKey<H>, key: Vec<u8>, value: Option<Vec<u8>>) {
 		let _guard = panic_handler::AbortGuard::new(true);
 
+		execution_trace::record_storage_write(storage_key.as_ref(), value.as_ref().map(Vec::len));
 		self.mark_dirty();
 		self.overlay.set_child_storage(storage_key.into_owned(), key, value);
 	}
@@ -233,6 +272,7 @@ where
 	fn kill_child_storage(&mut self, storage_key: ChildStorageKey<H>) {
 		let _guard = panic_handler::AbortGuard::new(true);
 
+		execution_trace::record_storage_write(storage_key.as_ref(), None);
 		self.mark_dirty();
 		self.overlay.clear_child_storage(storage_key.as_ref());
 		self.backend.for_keys_in_child_storage(storage_key.as_ref(), |key| {
@@ -247,6 +287,7 @@ where
 			return;
 		}
 
+		execution_trace::record_storage_write(prefix, None);
 		self.mark_dirty();
 		self.overlay.clear_prefix(prefix);
 		self.backend.for_keys_with_prefix(prefix, |key| {
@@ -254,6 +295,19 @@ where
 		});
 	}
 
+	fn storage_start_transaction(&mut self) {
+		self.overlay.start_transaction();
+	}
+
+	fn storage_rollback_transaction(&mut self) -> Result<(), ()> {
+		self.mark_dirty();
+		if self.overlay.rollback_transaction() { Ok(()) } else { Err(()) }
+	}
+
+	fn storage_commit_transaction(&mut self) -> Result<(), ()> {
+		if self.overlay.commit_transaction() { Ok(()) } else { Err(()) }
+	}
+
 	fn chain_id(&self) -> u64 {
 		42
 	}
@@ -383,6 +437,7 @@ mod tests {
 				digest_interval: 0,
 				digest_levels: 0,
 			}),
+			transactions: Default::default(),
 		}
 	}
 
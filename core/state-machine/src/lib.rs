@@ -28,6 +28,7 @@ use primitives::{
 };
 
 pub mod backend;
+pub mod execution_trace;
 mod changes_trie;
 mod ext;
 mod testing;
@@ -36,6 +37,7 @@ mod overlayed_changes;
 mod proving_backend;
 mod trie_backend;
 mod trie_backend_essence;
+mod shadow;
 
 use overlayed_changes::OverlayedChangeSet;
 pub use trie::{TrieMut, TrieDBMut, DBValue, MemoryDB};
@@ -48,6 +50,8 @@ pub use changes_trie::{
 	Storage as ChangesTrieStorage,
 	RootsStorage as ChangesTrieRootsStorage,
 	InMemoryStorage as InMemoryChangesTrieStorage,
+	ConfigurationRange as ChangesTrieConfigurationRange,
+	configuration_ranges as changes_trie_configuration_ranges,
 	key_changes, key_changes_proof, key_changes_proof_check,
 	prune as prune_changes_tries,
 	oldest_non_pruned_trie as oldest_non_pruned_changes_trie
@@ -59,6 +63,7 @@ pub use proving_backend::{
 };
 pub use trie_backend_essence::{TrieBackendStorage, Storage};
 pub use trie_backend::TrieBackend;
+pub use shadow::{ExecutionMismatch, StorageMismatch, MismatchReporter};
 
 /// A wrapper around a child storage key.
 ///
@@ -221,6 +226,19 @@ pub trait Externalities<H: Hasher> {
 	/// Get the change trie root of the current storage overlay at a block with given parent.
 	fn storage_changes_root(&mut self, parent: H::Out, parent_num: u64) -> Option<H::Out> where H::Out: Ord;
 
+	/// Start a new storage transaction, nested in the open ones. The changes to the storage
+	/// made until the transaction is closed can be rolled back as a whole.
+	fn storage_start_transaction(&mut self);
+
+	/// Discard the changes to the storage made since the start of the innermost open storage
+	/// transaction and close it. Returns an error if there's no open transaction.
+	fn storage_rollback_transaction(&mut self) -> Result<(), ()>;
+
+	/// Keep the changes to the storage made since the start of the innermost open storage
+	/// transaction and close it. The changes can still be rolled back with the enclosing
+	/// transaction. Returns an error if there's no open transaction.
+	fn storage_commit_transaction(&mut self) -> Result<(), ()>;
+
 	/// Submit extrinsic.
 	///
 	/// Returns an error in case the API is not available.
@@ -271,6 +289,9 @@ pub enum ExecutionStrategy {
 	Both,
 	/// First native, then if that fails or is not possible, wasm.
 	NativeElseWasm,
+	/// Use the given wasm module and run the native variant (if compatible) alongside it. Log
+	/// any discrepancy in the result or the storage changes; the wasm result is always used.
+	Shadow,
 }
 
 type DefaultHandler<R, E> = fn(
@@ -289,6 +310,9 @@ pub enum ExecutionManager<F> {
 	Both(F),
 	/// First native, then if that fails or is not possible, wasm.
 	NativeElseWasm,
+	/// Use the given wasm module and run the native variant (if compatible) alongside it. Log
+	/// any discrepancy and pass it to the reporter, if any.
+	Shadow(Option<MismatchReporter>),
 }
 
 impl<'a, F> From<&'a ExecutionManager<F>> for ExecutionStrategy {
@@ -298,6 +322,7 @@ impl<'a, F> From<&'a ExecutionManager<F>> for ExecutionStrategy {
 			ExecutionManager::AlwaysWasm => ExecutionStrategy::AlwaysWasm,
 			ExecutionManager::NativeElseWasm => ExecutionStrategy::NativeElseWasm,
 			ExecutionManager::Both(_) => ExecutionStrategy::Both,
+			ExecutionManager::Shadow(_) => ExecutionStrategy::Shadow,
 		}
 	}
 }
@@ -317,6 +342,7 @@ impl ExecutionStrategy {
 				);
 				wasm_result
 			}),
+			ExecutionStrategy::Shadow => ExecutionManager::Shadow(None),
 		}
 	}
 }
@@ -428,6 +454,7 @@ impl<'a, H, B, T, O, Exec> StateMachine<'a, H, B, T, O, Exec> where
 			use_native,
 			native_call,
 		);
+		externalities.rollback_open_transactions(self.method, result.is_err());
 		let (storage_delta, changes_delta) = if compute_tx {
 			let (storage_delta, changes_delta) = externalities.transaction();
 			(Some(storage_delta), changes_delta)
@@ -489,6 +516,48 @@ impl<'a, H, B, T, O, Exec> StateMachine<'a, H, B, T, O, Exec> where
 		}
 	}
 
+	fn execute_call_with_shadow_strategy<R, NC>(
+		&mut self,
+		compute_tx: bool,
+		native_call: Option<NC>,
+		orig_prospective: OverlayedChangeSet,
+		reporter: Option<MismatchReporter>,
+	) -> (CallResult<R, Exec::Error>, Option<B::Transaction>, Option<MemoryDB<H>>) where
+		R: Decode + Encode + PartialEq,
+		NC: FnOnce() -> result::Result<R, &'static str> + UnwindSafe,
+	{
+		let (result, _, storage_delta, changes_delta) = self.execute_aux(compute_tx, false, None::<NC>);
+		let wasm_prospective = std::mem::replace(&mut self.overlay.prospective, orig_prospective);
+
+		let (native_result, was_native, _, _) = self.execute_aux(false, true, native_call);
+		let native_prospective = std::mem::replace(&mut self.overlay.prospective, wasm_prospective);
+
+		if was_native {
+			let results_match = match (&result, &native_result) {
+				(Ok(wasm), Ok(native)) => wasm == native,
+				(Err(_), Err(_)) => true,
+				_ => false,
+			};
+			let mismatch = ExecutionMismatch {
+				method: self.method.into(),
+				results: if results_match {
+					None
+				} else {
+					Some((format!("{:?}", result), format!("{:?}", native_result)))
+				},
+				storage: shadow::diff_change_sets(&self.overlay.prospective, &native_prospective),
+			};
+			if !mismatch.is_empty() {
+				warn!(target: "shadow-execution", "{}", mismatch);
+				if let Some(reporter) = reporter {
+					reporter(&mismatch);
+				}
+			}
+		}
+
+		(result, storage_delta, changes_delta)
+	}
+
 	/// Execute a call using the given state backend, overlayed changes, and call executor.
 	/// Produces a state-backend-specific "transaction" which can be used to apply the changes
 	/// to the backing store, such as the disk.
@@ -514,17 +583,13 @@ impl<'a, H, B, T, O, Exec> StateMachine<'a, H, B, T, O, Exec> where
 		// `OverlayedChanges` constructor is that we need proofs for this read as a part of
 		// proof-of-execution on light clients. And the proof is recorded by the backend which
 		// is created after OverlayedChanges
+		//
+		// the configuration is read from the backend (i.e. the state of the parent block), so
+		// the configuration that is changed by the runtime is only applied to the next block
 
-		let backend = self.backend.clone();
-		let init_overlay = |overlay: &mut OverlayedChanges, final_check: bool| {
-			let changes_trie_config = try_read_overlay_value(
-				overlay,
-				backend,
-				well_known_keys::CHANGES_TRIE_CONFIG
-			)?;
-			set_changes_trie_config(overlay, changes_trie_config, final_check)
-		};
-		init_overlay(self.overlay, false)?;
+		let changes_trie_config = self.backend.storage(well_known_keys::CHANGES_TRIE_CONFIG)
+			.map_err(|err| Box::new(ExecutionError::Backend(format!("{}", err))) as Box<Error>)?;
+		set_changes_trie_config(self.overlay, changes_trie_config)?;
 
 		let result = {
 			let orig_prospective = self.overlay.prospective.clone();
@@ -536,6 +601,9 @@ impl<'a, H, B, T, O, Exec> StateMachine<'a, H, B, T, O, Exec> where
 				ExecutionManager::NativeElseWasm => {
 					self.execute_call_with_native_else_wasm_strategy(compute_tx, native_call.take(), orig_prospective)
 				},
+				ExecutionManager::Shadow(reporter) => {
+					self.execute_call_with_shadow_strategy(compute_tx, native_call.take(), orig_prospective, reporter)
+				},
 				ExecutionManager::AlwaysWasm => {
 					let (result, _, storage_delta, changes_delta) = self.execute_aux(compute_tx, false, native_call);
 					(result, storage_delta, changes_delta)
@@ -548,10 +616,6 @@ impl<'a, H, B, T, O, Exec> StateMachine<'a, H, B, T, O, Exec> where
 			result.map(move |out| (out, storage_delta, changes_delta))
 		};
 
-		if result.is_ok() {
-			init_overlay(self.overlay, true)?;
-		}
-
 		result.map_err(|e| Box::new(e) as _)
 	}
 }
@@ -731,6 +795,56 @@ where
 	Ok((result, proving_backend.extract_proof()))
 }
 
+/// Generate proof of up to `max_entries` consecutive key-value pairs of the storage
+/// (or of the given child storage), starting with `start` (inclusive).
+pub fn prove_range_read<B, H>(
+	backend: B,
+	child_storage_key: Option<&[u8]>,
+	start: &[u8],
+	max_entries: usize,
+) -> Result<Vec<Vec<u8>>, Box<Error>>
+where
+	B: Backend<H>,
+	H: Hasher,
+	H::Out: Ord
+{
+	let trie_backend = backend.try_into_trie_backend()
+		.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<Error>)?;
+	prove_range_read_on_trie_backend(&trie_backend, child_storage_key, start, max_entries)
+}
+
+/// Generate storage range proof on pre-created trie backend.
+pub fn prove_range_read_on_trie_backend<S, H>(
+	trie_backend: &TrieBackend<S, H>,
+	child_storage_key: Option<&[u8]>,
+	start: &[u8],
+	max_entries: usize,
+) -> Result<Vec<Vec<u8>>, Box<Error>>
+where
+	S: trie_backend_essence::TrieBackendStorage<H>,
+	H: Hasher,
+	H::Out: Ord
+{
+	proving_backend::prove_range(trie_backend.essence(), child_storage_key, start, max_entries)
+		.map_err(|e| Box::new(e) as Box<Error>)
+}
+
+/// Check storage range proof, generated by `prove_range_read` call. `root` is the root
+/// of the proven trie: the child trie root if the proof has been generated for a child storage.
+///
+/// Returns proven key-value pairs and whether there are no more pairs after them.
+pub fn read_range_proof_check<H>(
+	root: H::Out,
+	proof: Vec<Vec<u8>>,
+	start: &[u8],
+) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Box<Error>>
+where
+	H: Hasher,
+	H::Out: Ord
+{
+	proving_backend::check_range_proof::<H>(root, proof, start)
+}
+
 /// Check storage read proof, generated by `prove_read` call.
 pub fn read_proof_check<H>(
 	root: H::Out,
@@ -788,43 +902,25 @@ where
 
 /// Sets overlayed changes' changes trie configuration. Returns error if configuration
 /// differs from previous OR config decode has failed.
-pub(crate) fn set_changes_trie_config(overlay: &mut OverlayedChanges, config: Option<Vec<u8>>, final_check: bool) -> Result<(), Box<Error>> {
+pub(crate) fn set_changes_trie_config(overlay: &mut OverlayedChanges, config: Option<Vec<u8>>) -> Result<(), Box<Error>> {
 	let config = match config {
 		Some(v) => Some(Decode::decode(&mut &v[..])
 			.ok_or_else(|| Box::new("Failed to decode changes trie configuration".to_owned()) as Box<Error>)?),
 		None => None,
 	};
 
-	if final_check && overlay.changes_trie_config.is_some() != config.is_some() {
-		return Err(Box::new("Changes trie configuration change is not supported".to_owned()));
-	}
-
 	if let Some(config) = config {
 		if !overlay.set_changes_trie_config(config) {
-			return Err(Box::new("Changes trie configuration change is not supported".to_owned()));
+			return Err(Box::new("Changes trie configuration can't be changed within a block".to_owned()));
 		}
 	}
 	Ok(())
 }
 
-/// Reads storage value from overlay or from the backend.
-fn try_read_overlay_value<H, B>(overlay: &OverlayedChanges, backend: &B, key: &[u8])
-	-> Result<Option<Vec<u8>>, Box<Error>>
-where
-	H: Hasher,
-
-	B: Backend<H>,
-{
-	match overlay.storage(key).map(|x| x.map(|x| x.to_vec())) {
-		Some(value) => Ok(value),
-		None => backend.storage(key)
-			.map_err(|err| Box::new(ExecutionError::Backend(format!("{}", err))) as Box<Error>),
-	}
-}
-
 #[cfg(test)]
 mod tests {
 	use std::collections::HashMap;
+	use std::sync::Arc;
 	use parity_codec::Encode;
 	use overlayed_changes::OverlayedValue;
 	use super::*;
@@ -957,6 +1053,44 @@ mod tests {
 		assert!(consensus_failed);
 	}
 
+	#[test]
+	fn shadow_execution_strategy_reports_divergence_and_uses_wasm() {
+		let execute = |native_succeeds| {
+			let mismatches = Arc::new(std::sync::Mutex::new(Vec::new()));
+			let reported = mismatches.clone();
+			let result = new(
+				&trie_backend::tests::test_trie(),
+				Some(&InMemoryChangesTrieStorage::new()),
+				NeverOffchainExt::new(),
+				&mut Default::default(),
+				&DummyCodeExecutor {
+					change_changes_trie_config: false,
+					native_available: true,
+					native_succeeds,
+					fallback_succeeds: false,
+				},
+				"test",
+				&[],
+			).execute_using_consensus_failure_handler::<DefaultHandler<NeverNativeValue, u8>, NeverNativeValue, fn() -> _>(
+				ExecutionManager::Shadow(Some(Arc::new(move |mismatch: &ExecutionMismatch| {
+					reported.lock().unwrap().push(mismatch.clone());
+				}))),
+				true,
+				None,
+			);
+			assert!(result.is_err());
+			let mismatches = mismatches.lock().unwrap().clone();
+			mismatches
+		};
+
+		assert!(execute(false).is_empty());
+		let mismatches = execute(true);
+		assert_eq!(mismatches.len(), 1);
+		assert_eq!(mismatches[0].method, "test");
+		assert!(mismatches[0].results.is_some());
+		assert!(mismatches[0].storage.is_empty());
+	}
+
 	#[test]
 	fn prove_execution_and_proof_check_works() {
 		let executor = DummyCodeExecutor {
@@ -1103,12 +1237,13 @@ mod tests {
 	}
 
 	#[test]
-	fn cannot_change_changes_trie_config() {
+	fn changes_trie_config_change_is_applied_to_next_block() {
+		let mut overlay = OverlayedChanges::default();
 		assert!(new(
 			&trie_backend::tests::test_trie(),
 			Some(&InMemoryChangesTrieStorage::new()),
 			NeverOffchainExt::new(),
-			&mut Default::default(),
+			&mut overlay,
 			&DummyCodeExecutor {
 				change_changes_trie_config: true,
 				native_available: false,
@@ -1119,16 +1254,19 @@ mod tests {
 			&[],
 		).execute(
 			ExecutionStrategy::NativeWhenPossible
-		).is_err());
+		).is_ok());
+		assert!(overlay.changes_trie_config.is_none());
+		assert!(overlay.storage(well_known_keys::CHANGES_TRIE_CONFIG).is_some());
 	}
 
 	#[test]
-	fn cannot_change_changes_trie_config_with_native_else_wasm() {
+	fn changes_trie_config_change_is_applied_to_next_block_with_native_else_wasm() {
+		let mut overlay = OverlayedChanges::default();
 		assert!(new(
 			&trie_backend::tests::test_trie(),
 			Some(&InMemoryChangesTrieStorage::new()),
 			NeverOffchainExt::new(),
-			&mut Default::default(),
+			&mut overlay,
 			&DummyCodeExecutor {
 				change_changes_trie_config: true,
 				native_available: false,
@@ -1139,6 +1277,8 @@ mod tests {
 			&[],
 		).execute(
 			ExecutionStrategy::NativeElseWasm
-		).is_err());
+		).is_ok());
+		assert!(overlay.changes_trie_config.is_none());
+		assert!(overlay.storage(well_known_keys::CHANGES_TRIE_CONFIG).is_some());
 	}
 }
@@ -26,12 +26,17 @@ use primitives::storage::well_known_keys::EXTRINSIC_INDEX;
 ///
 /// A transaction shares all prospective changes within an inner overlay
 /// that can be cleared.
+///
+/// The runtime can additionally open nested storage transactions, whose changes to the
+/// prospective change set can be rolled back as a whole.
 #[derive(Debug, Default, Clone)]
 pub struct OverlayedChanges {
 	/// Changes that are not yet committed.
 	pub(crate) prospective: OverlayedChangeSet,
 	/// Committed changes.
 	pub(crate) committed: OverlayedChangeSet,
+	/// Undo information of the open storage transactions, the innermost last.
+	pub(crate) transactions: Vec<TransactionJournal>,
 	/// Changes trie configuration. None by default, but could be installed by the
 	/// runtime if it supports change tries.
 	pub(crate) changes_trie_config: Option<ChangesTrieConfig>,
@@ -58,6 +63,89 @@ pub struct OverlayedChangeSet {
 	pub children: HashMap<Vec<u8>, (Option<HashSet<u32>>, HashMap<Vec<u8>, Option<Vec<u8>>>)>,
 }
 
+/// The entries of the prospective change set as they were before an open storage transaction
+/// first changed them. `None` if there was no entry.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct TransactionJournal {
+	/// Top level storage entries.
+	top: HashMap<Vec<u8>, Option<OverlayedValue>>,
+	/// Child storage change sets.
+	children: HashMap<Vec<u8>, ChildJournal>,
+}
+
+/// A child storage change set as it was before an open storage transaction first changed it.
+#[derive(Debug, Clone)]
+struct ChildJournal {
+	/// Whether the change set existed.
+	existed: bool,
+	/// The extrinsics that had changed the child storage.
+	extrinsics: Option<HashSet<u32>>,
+	/// Entries of the change set.
+	values: HashMap<Vec<u8>, Option<Option<Vec<u8>>>>,
+}
+
+impl TransactionJournal {
+	fn record_top(&mut self, prospective: &OverlayedChangeSet, key: &[u8]) {
+		if !self.top.contains_key(key) {
+			self.top.insert(key.to_vec(), prospective.top.get(key).cloned());
+		}
+	}
+
+	fn record_child(&mut self, prospective: &OverlayedChangeSet, storage_key: &[u8], key: Option<&[u8]>) {
+		let child = prospective.children.get(storage_key);
+		let journal = self.children.entry(storage_key.to_vec()).or_insert_with(|| ChildJournal {
+			existed: child.is_some(),
+			extrinsics: child.and_then(|(extrinsics, _)| extrinsics.clone()),
+			values: HashMap::new(),
+		});
+		if let Some(key) = key {
+			if !journal.values.contains_key(key) {
+				journal.values.insert(key.to_vec(), child.and_then(|(_, values)| values.get(key).cloned()));
+			}
+		}
+	}
+
+	/// Restore the recorded entries of `prospective`.
+	fn rollback(self, prospective: &mut OverlayedChangeSet) {
+		for (key, value) in self.top {
+			match value {
+				Some(value) => { prospective.top.insert(key, value); },
+				None => { prospective.top.remove(&key); },
+			}
+		}
+		for (storage_key, child) in self.children {
+			if !child.existed {
+				prospective.children.remove(&storage_key);
+				continue;
+			}
+			let entry = prospective.children.entry(storage_key).or_default();
+			entry.0 = child.extrinsics;
+			for (key, value) in child.values {
+				match value {
+					Some(value) => { entry.1.insert(key, value); },
+					None => { entry.1.remove(&key); },
+				}
+			}
+		}
+	}
+
+	/// Keep the changes of an inner transaction as a part of this one. The entries recorded
+	/// by this transaction are older and take precedence.
+	fn merge(&mut self, inner: TransactionJournal) {
+		for (key, value) in inner.top {
+			self.top.entry(key).or_insert(value);
+		}
+		for (storage_key, inner_child) in inner.children {
+			match self.children.get_mut(&storage_key) {
+				Some(child) => for (key, value) in inner_child.values {
+					child.values.entry(key).or_insert(value);
+				},
+				None => { self.children.insert(storage_key, inner_child); },
+			}
+		}
+	}
+}
+
 #[cfg(test)]
 impl FromIterator<(Vec<u8>, OverlayedValue)> for OverlayedChangeSet {
 	fn from_iter<T: IntoIterator<Item = (Vec<u8>, OverlayedValue)>>(iter: T) -> Self {
@@ -90,10 +178,11 @@ impl OverlayedChanges {
 	/// Sets the changes trie configuration.
 	///
 	/// Returns false if configuration has been set already and we now trying
-	/// to install different configuration. This isn't supported now.
+	/// to install different configuration. The configuration can only be changed
+	/// between blocks.
 	pub(crate) fn set_changes_trie_config(&mut self, config: ChangesTrieConfig) -> bool {
 		if let Some(ref old_config) = self.changes_trie_config {
-			// we do not support changes trie configuration' change now
+			// configuration is the same for all calls within the same block
 			if *old_config != config {
 				return false;
 			}
@@ -136,6 +225,9 @@ impl OverlayedChanges {
 	/// `None` can be used to delete a value specified by the given key.
 	pub(crate) fn set_storage(&mut self, key: Vec<u8>, val: Option<Vec<u8>>) {
 		let extrinsic_index = self.extrinsic_index();
+		if let Some(journal) = self.transactions.last_mut() {
+			journal.record_top(&self.prospective, &key);
+		}
 		let entry = self.prospective.top.entry(key).or_default();
 		entry.value = val;
 
@@ -150,6 +242,9 @@ impl OverlayedChanges {
 	/// `None` can be used to delete a value specified by the given key.
 	pub(crate) fn set_child_storage(&mut self, storage_key: Vec<u8>, key: Vec<u8>, val: Option<Vec<u8>>) {
 		let extrinsic_index = self.extrinsic_index();
+		if let Some(journal) = self.transactions.last_mut() {
+			journal.record_child(&self.prospective, &storage_key, Some(&key));
+		}
 		let map_entry = self.prospective.children.entry(storage_key).or_default();
 		map_entry.1.insert(key, val);
 
@@ -167,6 +262,15 @@ impl OverlayedChanges {
 	/// [`discard_prospective`]: #method.discard_prospective
 	pub(crate) fn clear_child_storage(&mut self, storage_key: &[u8]) {
 		let extrinsic_index = self.extrinsic_index();
+		if let Some(journal) = self.transactions.last_mut() {
+			journal.record_child(&self.prospective, storage_key, None);
+			let keys = self.prospective.children.get(storage_key).into_iter()
+				.chain(self.committed.children.get(storage_key))
+				.flat_map(|(_, values)| values.keys());
+			for key in keys {
+				journal.record_child(&self.prospective, storage_key, Some(key));
+			}
+		}
 		let map_entry = self.prospective.children.entry(storage_key.to_vec()).or_default();
 
 		if let Some(extrinsic) = extrinsic_index {
@@ -191,6 +295,13 @@ impl OverlayedChanges {
 	/// [`discard_prospective`]: #method.discard_prospective
 	pub(crate) fn clear_prefix(&mut self, prefix: &[u8]) {
 		let extrinsic_index = self.extrinsic_index();
+		if let Some(journal) = self.transactions.last_mut() {
+			let keys = self.prospective.top.keys().chain(self.committed.top.keys())
+				.filter(|key| key.starts_with(prefix));
+			for key in keys {
+				journal.record_top(&self.prospective, key);
+			}
+		}
 
 		// Iterate over all prospective and mark all keys that share
 		// the given prefix as removed (None).
@@ -220,13 +331,57 @@ impl OverlayedChanges {
 		}
 	}
 
+	/// Start a new storage transaction, nested in the open ones.
+	pub(crate) fn start_transaction(&mut self) {
+		self.transactions.push(Default::default());
+	}
+
+	/// Discard the prospective changes made since the start of the innermost open storage
+	/// transaction and close it. Returns `false` if there's no open transaction.
+	pub(crate) fn rollback_transaction(&mut self) -> bool {
+		match self.transactions.pop() {
+			Some(journal) => {
+				journal.rollback(&mut self.prospective);
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Keep the prospective changes made since the start of the innermost open storage
+	/// transaction and close it. The changes can still be rolled back with the enclosing
+	/// transaction. Returns `false` if there's no open transaction.
+	pub(crate) fn commit_transaction(&mut self) -> bool {
+		match self.transactions.pop() {
+			Some(journal) => {
+				if let Some(outer) = self.transactions.last_mut() {
+					outer.merge(journal);
+				}
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Roll back the storage transactions that are still open, e.g. after the runtime
+	/// has trapped in the middle of one. Returns the number of transactions rolled back.
+	pub(crate) fn rollback_open_transactions(&mut self) -> usize {
+		let open_transactions = self.transactions.len();
+		while self.rollback_transaction() {}
+		open_transactions
+	}
+
 	/// Discard prospective changes to state.
 	pub fn discard_prospective(&mut self) {
+		self.transactions.clear();
 		self.prospective.clear();
 	}
 
 	/// Commit prospective changes to state.
+	///
+	/// Open storage transactions are closed and their changes are kept.
 	pub fn commit_prospective(&mut self) {
+		self.transactions.clear();
 		if self.committed.is_empty() {
 			::std::mem::swap(&mut self.prospective, &mut self.committed);
 		} else {
@@ -251,13 +406,20 @@ impl OverlayedChanges {
 		}
 	}
 
-	/// Consume `OverlayedChanges` and take committed set.
+	/// Consume `OverlayedChanges` and take committed set: top level storage changes
+	/// and child storage changes grouped by the child storage key.
 	///
 	/// Panics:
 	/// Will panic if there are any uncommitted prospective changes.
-	pub fn into_committed(self) -> impl Iterator<Item=(Vec<u8>, Option<Vec<u8>>)> {
+	pub fn into_committed(self) -> (
+		impl Iterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
+		impl Iterator<Item=(Vec<u8>, impl Iterator<Item=(Vec<u8>, Option<Vec<u8>>)>)>,
+	) {
 		assert!(self.prospective.is_empty());
-		self.committed.top.into_iter().map(|(k, v)| (k, v.value))
+		(
+			self.committed.top.into_iter().map(|(k, v)| (k, v.value)),
+			self.committed.children.into_iter().map(|(sk, (_, v))| (sk, v.into_iter())),
+		)
 	}
 
 	/// Inserts storage entry responsible for current extrinsic index.
@@ -471,4 +633,57 @@ mod tests {
 		assert_eq!(overlay.prospective,
 			Default::default());
 	}
+
+	#[test]
+	fn nested_transactions_are_rolled_back_and_committed() {
+		let mut overlayed = OverlayedChanges::default();
+		overlayed.set_storage(b"a".to_vec(), Some(b"1".to_vec()));
+		overlayed.commit_prospective();
+		overlayed.set_storage(b"b".to_vec(), Some(b"2".to_vec()));
+
+		overlayed.start_transaction();
+		overlayed.set_storage(b"b".to_vec(), Some(b"3".to_vec()));
+		overlayed.set_child_storage(b"child".to_vec(), b"c".to_vec(), Some(b"4".to_vec()));
+
+		overlayed.start_transaction();
+		overlayed.clear_prefix(b"");
+		overlayed.set_storage(b"d".to_vec(), Some(b"5".to_vec()));
+		overlayed.clear_child_storage(b"child");
+		assert!(overlayed.storage(b"a").unwrap().is_none());
+		assert!(overlayed.rollback_transaction());
+
+		assert_eq!(overlayed.storage(b"a").unwrap(), Some(&b"1"[..]));
+		assert_eq!(overlayed.storage(b"b").unwrap(), Some(&b"3"[..]));
+		assert!(overlayed.storage(b"d").is_none());
+		assert_eq!(overlayed.child_storage(b"child", b"c").unwrap(), Some(&b"4"[..]));
+
+		overlayed.start_transaction();
+		overlayed.set_storage(b"d".to_vec(), Some(b"5".to_vec()));
+		assert!(overlayed.commit_transaction());
+		assert!(overlayed.rollback_transaction());
+		assert!(!overlayed.rollback_transaction());
+		assert!(!overlayed.commit_transaction());
+
+		assert_eq!(overlayed.storage(b"b").unwrap(), Some(&b"2"[..]));
+		assert!(overlayed.storage(b"d").is_none());
+		assert!(overlayed.child_storage(b"child", b"c").is_none());
+		assert!(overlayed.prospective.children.is_empty());
+	}
+
+	#[test]
+	fn open_transactions_are_counted_when_rolled_back() {
+		let mut overlayed = OverlayedChanges::default();
+		assert_eq!(overlayed.rollback_open_transactions(), 0);
+
+		overlayed.set_storage(b"a".to_vec(), Some(b"1".to_vec()));
+		overlayed.start_transaction();
+		overlayed.set_storage(b"a".to_vec(), Some(b"2".to_vec()));
+		overlayed.start_transaction();
+		overlayed.set_storage(b"b".to_vec(), Some(b"3".to_vec()));
+
+		assert_eq!(overlayed.rollback_open_transactions(), 2);
+		assert_eq!(overlayed.storage(b"a").unwrap(), Some(&b"1"[..]));
+		assert!(overlayed.storage(b"b").is_none());
+		assert_eq!(overlayed.rollback_open_transactions(), 0);
+	}
 }
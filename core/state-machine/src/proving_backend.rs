@@ -16,12 +16,12 @@
 
 //! Proving state machine backend.
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 use log::debug;
 use hash_db::Hasher;
-use hash_db::HashDB;
+use hash_db::{HashDB, HashDBRef};
 use trie::{
-	MemoryDB, PrefixedMemoryDB, TrieError, default_child_trie_root,
+	MemoryDB, PrefixedMemoryDB, TrieDB, Trie, TrieError, DBValue, default_child_trie_root,
 	read_trie_value_with, read_child_trie_value_with, record_all_keys
 };
 pub use trie::Recorder;
@@ -216,12 +216,134 @@ where
 	db
 }
 
+/// Trie nodes database which remembers all nodes that have been read through it.
+struct RecordingDB<'a, H: Hasher> {
+	db: &'a HashDBRef<H, DBValue>,
+	recorded: RefCell<HashMap<H::Out, DBValue>>,
+}
+
+impl<'a, H: Hasher> HashDBRef<H, DBValue> for RecordingDB<'a, H> {
+	fn get(&self, key: &H::Out, prefix: &[u8]) -> Option<DBValue> {
+		let value = self.db.get(key, prefix);
+		if let Some(ref value) = value {
+			self.recorded.borrow_mut().insert(key.clone(), value.clone());
+		}
+		value
+	}
+
+	fn contains(&self, key: &H::Out, prefix: &[u8]) -> bool {
+		HashDBRef::get(self, key, prefix).is_some()
+	}
+}
+
+/// Call `f` for key-value pairs of the trie with given root, in lexicographic order
+/// of keys, starting with `start` (inclusive). Stops when `f` returns false.
+fn for_key_values_from<H, F>(
+	db: &HashDBRef<H, DBValue>,
+	root: &H::Out,
+	start: &[u8],
+	mut f: F,
+) -> Result<(), Box<TrieError<H::Out>>>
+where
+	H: Hasher,
+	F: FnMut(Vec<u8>, Vec<u8>) -> bool,
+{
+	let trie = TrieDB::<H>::new(db, root)?;
+	let mut iter = trie.iter()?;
+	iter.seek(start)?;
+
+	for x in iter {
+		let (key, value) = x?;
+		if !f(key, value.to_vec()) {
+			break;
+		}
+	}
+
+	Ok(())
+}
+
+/// Generate proof of up to `max_entries` consecutive key-value pairs of the storage
+/// (or of the given child storage), starting with `start` (inclusive).
+pub fn prove_range<S, H>(
+	backend: &TrieBackendEssence<S, H>,
+	child_storage_key: Option<&[u8]>,
+	start: &[u8],
+	max_entries: usize,
+) -> Result<Vec<Vec<u8>>, String>
+where
+	S: TrieBackendStorage<H>,
+	H: Hasher,
+{
+	let root = match child_storage_key {
+		Some(storage_key) => {
+			let child_root = backend.storage(storage_key)?
+				.unwrap_or(default_child_trie_root::<H>(storage_key));
+			if child_root.len() != H::Out::default().as_ref().len() {
+				return Err(format!("Invalid child trie root at {:?}", storage_key));
+			}
+
+			let mut root = H::Out::default();
+			root.as_mut().copy_from_slice(&child_root);
+			root
+		},
+		None => backend.root().clone(),
+	};
+
+	let mut read_overlay = S::Overlay::default();
+	let eph = Ephemeral::new(backend.backend_storage(), &mut read_overlay);
+	let recording = RecordingDB {
+		db: &eph,
+		recorded: RefCell::new(HashMap::new()),
+	};
+
+	let mut entries = 0;
+	for_key_values_from::<H, _>(&recording, &root, start, |_, _| {
+		entries += 1;
+		entries < max_entries
+	}).map_err(|e| format!("Trie lookup error: {}", e))?;
+
+	Ok(recording.recorded.into_inner().into_iter().map(|(_, node)| node.to_vec()).collect())
+}
+
+/// Check proof generated by `prove_range` against the root of the proven trie.
+///
+/// Returns consecutive key-value pairs starting with `start` that are covered by the
+/// proof, and whether these are the last pairs of the trie.
+pub fn check_range_proof<H>(
+	root: H::Out,
+	proof: Vec<Vec<u8>>,
+	start: &[u8],
+) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Box<Error>>
+where
+	H: Hasher,
+{
+	let db = create_proof_check_backend_storage::<H>(proof);
+	let trie = TrieDB::<H>::new(&db, &root)
+		.map_err(|_| Box::new(ExecutionError::InvalidProof) as Box<Error>)?;
+	let mut iter = trie.iter()
+		.map_err(|_| Box::new(ExecutionError::InvalidProof) as Box<Error>)?;
+	iter.seek(start)
+		.map_err(|_| Box::new(ExecutionError::InvalidProof) as Box<Error>)?;
+
+	// the proof ends where the first node is missing: everything before it is proven.
+	let mut entries = Vec::new();
+	for x in iter {
+		match x {
+			Ok((key, value)) => entries.push((key, value.to_vec())),
+			Err(_) => return Ok((entries, false)),
+		}
+	}
+
+	Ok((entries, true))
+}
+
 #[cfg(test)]
 mod tests {
 	use crate::backend::{InMemory};
 	use crate::trie_backend::tests::test_trie;
 	use super::*;
-	use primitives::{Blake2Hasher};
+	use primitives::{Blake2Hasher, H256};
+	use parity_codec::Decode;
 	use crate::ChildStorageKey;
 
 	fn test_proving<'a>(trie_backend: &'a TrieBackend<PrefixedMemoryDB<Blake2Hasher>, Blake2Hasher>) -> ProvingBackend<'a, PrefixedMemoryDB<Blake2Hasher>, Blake2Hasher> {
@@ -244,7 +366,6 @@ mod tests {
 
 	#[test]
 	fn proof_is_invalid_when_does_not_contains_root() {
-		use primitives::H256;
 		assert!(create_proof_check_backend::<Blake2Hasher>(H256::from_low_u64_be(1), vec![]).is_err());
 	}
 
@@ -353,4 +474,40 @@ mod tests {
 		);
 	}
 
+
+	#[test]
+	fn range_proof_recorded_and_checked() {
+		let trie_backend = test_trie();
+		let root = trie_backend.root().clone();
+
+		let mut start = Vec::new();
+		let mut pairs = Vec::new();
+		loop {
+			let proof = prove_range(trie_backend.essence(), None, &start, 16).unwrap();
+			let (entries, complete) = check_range_proof::<Blake2Hasher>(root, proof, &start).unwrap();
+			assert!(!entries.is_empty() || complete);
+			if let Some((last_key, _)) = entries.last() {
+				start = last_key.clone();
+				start.push(0);
+			}
+			pairs.extend(entries);
+			if complete {
+				break;
+			}
+		}
+		assert_eq!(pairs, trie_backend.pairs());
+
+		let child_root = H256::decode(&mut &trie_backend.storage(b":child_storage:default:sub1").unwrap().unwrap()[..]).unwrap();
+		let proof = prove_range(trie_backend.essence(), Some(b":child_storage:default:sub1"), &[], 16).unwrap();
+		let (entries, complete) = check_range_proof::<Blake2Hasher>(child_root, proof, &[]).unwrap();
+		assert!(complete);
+		assert_eq!(entries, vec![(b"value3".to_vec(), vec![142]), (b"value4".to_vec(), vec![124])]);
+	}
+
+	#[test]
+	fn range_proof_is_invalid_for_other_root() {
+		let trie_backend = test_trie();
+		let proof = prove_range(trie_backend.essence(), None, &[], 16).unwrap();
+		assert!(check_range_proof::<Blake2Hasher>(H256::from_low_u64_be(1), proof, &[]).is_err());
+	}
 }
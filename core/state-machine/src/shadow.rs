@@ -0,0 +1,200 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Comparison of the wasm and the native execution of a call for `ExecutionStrategy::Shadow`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use primitives::hexdisplay::HexDisplay;
+use crate::overlayed_changes::OverlayedChangeSet;
+
+/// Receives the divergences found by `ExecutionManager::Shadow`, in addition to the log.
+pub type MismatchReporter = Arc<dyn Fn(&ExecutionMismatch) + Send + Sync>;
+
+/// A storage entry that was changed differently by the wasm and the native execution.
+///
+/// A change is `None` if the execution didn't touch the entry and `Some(None)` if it
+/// removed the entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageMismatch {
+	/// Key of the child storage, `None` for the top-level storage.
+	pub child_storage_key: Option<Vec<u8>>,
+	/// Key of the entry.
+	pub key: Vec<u8>,
+	/// Change made by the wasm execution.
+	pub wasm: Option<Option<Vec<u8>>>,
+	/// Change made by the native execution.
+	pub native: Option<Option<Vec<u8>>>,
+}
+
+/// A divergence between the wasm and the native execution of a call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionMismatch {
+	/// The called runtime method.
+	pub method: String,
+	/// The wasm and the native result, if they differ.
+	pub results: Option<(String, String)>,
+	/// The storage entries that were changed differently, ordered by the keys.
+	pub storage: Vec<StorageMismatch>,
+}
+
+impl ExecutionMismatch {
+	/// Whether the executions diverged at all.
+	pub fn is_empty(&self) -> bool {
+		self.results.is_none() && self.storage.is_empty()
+	}
+}
+
+struct DisplayChange<'a>(&'a Option<Option<Vec<u8>>>);
+
+impl<'a> fmt::Display for DisplayChange<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.0 {
+			None => write!(f, "<unchanged>"),
+			Some(None) => write!(f, "<removed>"),
+			Some(Some(value)) => write!(f, "0x{}", HexDisplay::from(value)),
+		}
+	}
+}
+
+impl fmt::Display for ExecutionMismatch {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Native and wasm execution of {} diverged.", self.method)?;
+		if let Some((wasm, native)) = &self.results {
+			write!(f, "\n  result: wasm {}, native {}", wasm, native)?;
+		}
+		for entry in &self.storage {
+			write!(f, "\n  ")?;
+			if let Some(child_storage_key) = &entry.child_storage_key {
+				write!(f, "child 0x{} ", HexDisplay::from(child_storage_key))?;
+			}
+			write!(
+				f,
+				"key 0x{}: wasm {}, native {}",
+				HexDisplay::from(&entry.key),
+				DisplayChange(&entry.wasm),
+				DisplayChange(&entry.native),
+			)?;
+		}
+		Ok(())
+	}
+}
+
+fn diff_entries<'a, F, G>(
+	child_storage_key: Option<&[u8]>,
+	keys: BTreeSet<&'a Vec<u8>>,
+	wasm: F,
+	native: G,
+	diff: &mut Vec<StorageMismatch>,
+) where
+	F: Fn(&[u8]) -> Option<Option<Vec<u8>>>,
+	G: Fn(&[u8]) -> Option<Option<Vec<u8>>>,
+{
+	for key in keys {
+		let (wasm, native) = (wasm(key), native(key));
+		if wasm != native {
+			diff.push(StorageMismatch {
+				child_storage_key: child_storage_key.map(|k| k.to_vec()),
+				key: key.clone(),
+				wasm,
+				native,
+			});
+		}
+	}
+}
+
+/// The entries of the change sets that differ, ordered by the child storage and the key.
+///
+/// Only the values are compared: the indices of the extrinsics that changed an entry are
+/// the same for both executions of a call.
+pub(crate) fn diff_change_sets(wasm: &OverlayedChangeSet, native: &OverlayedChangeSet) -> Vec<StorageMismatch> {
+	let mut diff = Vec::new();
+
+	let keys = wasm.top.keys().chain(native.top.keys()).collect();
+	diff_entries(
+		None,
+		keys,
+		|key| wasm.top.get(key).map(|entry| entry.value.clone()),
+		|key| native.top.get(key).map(|entry| entry.value.clone()),
+		&mut diff,
+	);
+
+	let empty = HashMap::new();
+	let child_storage_keys: BTreeSet<_> = wasm.children.keys().chain(native.children.keys()).collect();
+	for child_storage_key in child_storage_keys {
+		let wasm = wasm.children.get(child_storage_key).map_or(&empty, |(_, map)| map);
+		let native = native.children.get(child_storage_key).map_or(&empty, |(_, map)| map);
+		let keys = wasm.keys().chain(native.keys()).collect();
+		diff_entries(
+			Some(child_storage_key),
+			keys,
+			|key| wasm.get(key).cloned(),
+			|key| native.get(key).cloned(),
+			&mut diff,
+		);
+	}
+
+	diff
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::overlayed_changes::OverlayedValue;
+
+	fn value(value: Option<&[u8]>) -> OverlayedValue {
+		OverlayedValue { value: value.map(|v| v.to_vec()), extrinsics: None }
+	}
+
+	#[test]
+	fn change_sets_are_compared_by_value() {
+		let mut wasm: OverlayedChangeSet = vec![
+			(b"same".to_vec(), value(Some(b"1"))),
+			(b"different".to_vec(), value(Some(b"2"))),
+			(b"wasm only".to_vec(), value(None)),
+		].into_iter().collect();
+		let mut native: OverlayedChangeSet = vec![
+			(b"same".to_vec(), value(Some(b"1"))),
+			(b"different".to_vec(), value(Some(b"3"))),
+		].into_iter().collect();
+		wasm.children.insert(b"child".to_vec(), (None, vec![(b"key".to_vec(), None)].into_iter().collect()));
+		native.children.insert(b"child".to_vec(), (None, vec![(b"key".to_vec(), None)].into_iter().collect()));
+		native.children.insert(b"other".to_vec(), (None, vec![(b"key".to_vec(), Some(b"4".to_vec()))].into_iter().collect()));
+
+		assert_eq!(diff_change_sets(&wasm, &wasm), vec![]);
+		assert_eq!(diff_change_sets(&wasm, &native), vec![
+			StorageMismatch {
+				child_storage_key: None,
+				key: b"different".to_vec(),
+				wasm: Some(Some(b"2".to_vec())),
+				native: Some(Some(b"3".to_vec())),
+			},
+			StorageMismatch {
+				child_storage_key: None,
+				key: b"wasm only".to_vec(),
+				wasm: Some(None),
+				native: None,
+			},
+			StorageMismatch {
+				child_storage_key: Some(b"other".to_vec()),
+				key: b"key".to_vec(),
+				wasm: None,
+				native: Some(Some(b"4".to_vec())),
+			},
+		]);
+	}
+}
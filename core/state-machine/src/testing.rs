@@ -32,6 +32,8 @@ pub struct TestExternalities<H: Hasher> {
 	changes_trie_storage: ChangesTrieInMemoryStorage<H>,
 	changes: OverlayedChanges,
 	code: Option<Vec<u8>>,
+	/// Storage and code at the start of the open storage transactions.
+	transactions: Vec<(HashMap<Vec<u8>, Vec<u8>>, Option<Vec<u8>>)>,
 }
 
 impl<H: Hasher> TestExternalities<H> {
@@ -46,7 +48,6 @@ impl<H: Hasher> TestExternalities<H> {
 		super::set_changes_trie_config(
 			&mut overlay,
 			inner.get(&CHANGES_TRIE_CONFIG.to_vec()).cloned(),
-			false,
 		).expect("changes trie configuration is correct in test env; qed");
 
 		inner.insert(HEAP_PAGES.to_vec(), 8u64.encode());
@@ -56,6 +57,7 @@ impl<H: Hasher> TestExternalities<H> {
 			changes_trie_storage: ChangesTrieInMemoryStorage::new(),
 			changes: overlay,
 			code: Some(code.to_vec()),
+			transactions: Vec::new(),
 		}
 	}
 
@@ -102,6 +104,7 @@ impl<H: Hasher> From< HashMap<Vec<u8>, Vec<u8>> > for TestExternalities<H> {
 			changes_trie_storage: ChangesTrieInMemoryStorage::new(),
 			changes: Default::default(),
 			code: None,
+			transactions: Vec::new(),
 		}
 	}
 }
@@ -151,6 +154,25 @@ impl<H: Hasher> Externalities<H> for TestExternalities<H> where H::Out: Ord {
 		self.inner.retain(|key, _| !key.starts_with(prefix));
 	}
 
+	fn storage_start_transaction(&mut self) {
+		self.changes.start_transaction();
+		self.transactions.push((self.inner.clone(), self.code.clone()));
+	}
+
+	fn storage_rollback_transaction(&mut self) -> Result<(), ()> {
+		let (inner, code) = self.transactions.pop().ok_or(())?;
+		self.changes.rollback_transaction();
+		self.inner = inner;
+		self.code = code;
+		Ok(())
+	}
+
+	fn storage_commit_transaction(&mut self) -> Result<(), ()> {
+		self.transactions.pop().ok_or(())?;
+		self.changes.commit_transaction();
+		Ok(())
+	}
+
 	fn chain_id(&self) -> u64 { 42 }
 
 	fn storage_root(&mut self) -> H::Out {
@@ -29,6 +29,7 @@ pub struct GenesisConfig {
 	pub changes_trie_config: Option<ChangesTrieConfiguration>,
 	pub authorities: Vec<AuthorityId>,
 	pub balances: Vec<(AccountId, u64)>,
+	pub secondary_slots: bool,
 }
 
 impl GenesisConfig {
@@ -45,6 +46,7 @@ impl GenesisConfig {
 			},
 			authorities: authorities.clone(),
 			balances: endowed_accounts.into_iter().map(|a| (a, balance)).collect(),
+			secondary_slots: true,
 		}
 	}
 
@@ -65,6 +67,10 @@ impl GenesisConfig {
 				.map(|(i, account)| ((i as u32).to_keyed_vec(well_known_keys::AUTHORITY_PREFIX), vec![].and(account)))
 			)
 			.collect();
+		// secondary slots are enabled when the key is missing, which keeps the default genesis unchanged.
+		if !self.secondary_slots {
+			map.insert(twox_128(b"sys:secondary_slots").to_vec(), false.encode());
+		}
 		if let Some(ref changes_trie_config) = self.changes_trie_config {
 			map.insert(well_known_keys::CHANGES_TRIE_CONFIG.to_vec(), changes_trie_config.encode());
 		}
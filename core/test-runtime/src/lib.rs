@@ -34,7 +34,7 @@ use substrate_client::{
 	impl_runtime_apis,
 };
 use runtime_primitives::{
-	ApplyResult, transaction_validity::TransactionValidity,
+	ApplyResult, transaction_validity::TransactionValidity, weights::TransactionFee,
 	create_runtime_str,
 	traits::{
 		BlindCheckable, BlakeTwo256, Block as BlockT, Extrinsic as ExtrinsicT,
@@ -365,6 +365,12 @@ cfg_if! {
 				}
 			}
 
+			impl client_api::TransactionPaymentApi<Block> for Runtime {
+				fn query_fee(_tx: <Block as BlockT>::Extrinsic) -> Option<TransactionFee> {
+					None
+				}
+			}
+
 			impl block_builder_api::BlockBuilder<Block> for Runtime {
 				fn apply_extrinsic(extrinsic: <Block as BlockT>::Extrinsic) -> ApplyResult {
 					system::execute_transaction(extrinsic)
@@ -454,6 +460,10 @@ cfg_if! {
 						slot_duration: 1,
 						expected_block_time: 1,
 						threshold: std::u64::MAX,
+						epoch_length: 6,
+						genesis_authorities: system::authorities(),
+						randomness: [0; 32],
+						secondary_slots: system::secondary_slots(),
 					}
 				}
 			}
@@ -503,6 +513,12 @@ cfg_if! {
 				}
 			}
 
+			impl client_api::TransactionPaymentApi<Block> for Runtime {
+				fn query_fee(_tx: <Block as BlockT>::Extrinsic) -> Option<TransactionFee> {
+					None
+				}
+			}
+
 			impl block_builder_api::BlockBuilder<Block> for Runtime {
 				fn apply_extrinsic(extrinsic: <Block as BlockT>::Extrinsic) -> ApplyResult {
 					system::execute_transaction(extrinsic)
@@ -596,6 +612,10 @@ cfg_if! {
 						slot_duration: 1,
 						expected_block_time: 1,
 						threshold: core::u64::MAX,
+						epoch_length: 6,
+						genesis_authorities: system::authorities(),
+						randomness: [0; 32],
+						secondary_slots: system::secondary_slots(),
 					}
 				}
 			}
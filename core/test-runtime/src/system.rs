@@ -21,11 +21,11 @@ use rstd::prelude::*;
 use runtime_io::{storage_root, enumerated_trie_root, storage_changes_root, twox_128, blake2_256};
 use runtime_support::storage::{self, StorageValue, StorageMap};
 use runtime_support::storage_items;
-use runtime_primitives::traits::{Hash as HashT, BlakeTwo256, Digest as DigestT};
+use runtime_primitives::traits::{Hash as HashT, BlakeTwo256, Digest as DigestT, DigestItem as DigestItemT};
 use runtime_primitives::generic;
 use runtime_primitives::{ApplyError, ApplyOutcome, ApplyResult, transaction_validity::TransactionValidity};
 use parity_codec::{KeyedVec, Encode};
-use super::{AccountId, BlockNumber, Extrinsic, Transfer, H256 as Hash, Block, Header, Digest};
+use super::{AccountId, BlockNumber, Extrinsic, Transfer, H256 as Hash, Block, Header, Digest, DigestItem};
 use primitives::{Blake2Hasher, storage::well_known_keys};
 use primitives::sr25519::Public as AuthorityId;
 
@@ -38,6 +38,9 @@ storage_items! {
 	Number: b"sys:num" => BlockNumber;
 	ParentHash: b"sys:pha" => required Hash;
 	NewAuthorities: b"sys:new_auth" => Vec<AuthorityId>;
+	PreRuntimeDigests: b"sys:pre_runtime" => default Vec<DigestItem>;
+	// Whether BABE authors may claim the slots nobody claimed. Set at genesis.
+	SecondarySlots: b"sys:secondary_slots" => bool;
 }
 
 pub fn balance_of_key(who: AccountId) -> Vec<u8> {
@@ -63,13 +66,27 @@ pub fn authorities() -> Vec<AuthorityId> {
 		.collect()
 }
 
+/// Whether secondary BABE slots are enabled. They are unless the genesis disabled them.
+pub fn secondary_slots() -> bool {
+	<SecondarySlots>::get().unwrap_or(true)
+}
+
 pub fn initialize_block(header: &Header) {
 	// populate environment.
 	<Number>::put(&header.number);
 	<ParentHash>::put(&header.parent_hash);
+	<PreRuntimeDigests>::put(pre_runtime_digests(header));
 	storage::unhashed::put(well_known_keys::EXTRINSIC_INDEX, &0u32);
 }
 
+/// The pre-runtime digests of the header, which are kept in the final digest.
+fn pre_runtime_digests(header: &Header) -> Vec<DigestItem> {
+	header.digest.logs.iter()
+		.filter(|log| log.as_pre_runtime().is_some())
+		.cloned()
+		.collect()
+}
+
 pub fn get_block_number() -> Option<BlockNumber> {
 	Number::get()
 }
@@ -99,7 +116,7 @@ pub fn polish_block(block: &mut Block) {
 	header.state_root = storage_root().into();
 
 	// check digest
-	let mut digest = Digest::default();
+	let mut digest = Digest { logs: pre_runtime_digests(header) };
 	if let Some(storage_changes_root) = storage_changes_root(header.parent_hash.into(), header.number - 1) {
 		digest.push(generic::DigestItem::ChangesTrieRoot(storage_changes_root.into()));
 	}
@@ -132,7 +149,7 @@ pub fn execute_block(block: Block) {
 	assert!(storage_root == header.state_root, "Storage root must match that calculated.");
 
 	// check digest
-	let mut digest = Digest::default();
+	let mut digest = Digest { logs: pre_runtime_digests(header) };
 	if let Some(storage_changes_root) = storage_changes_root(header.parent_hash.into(), header.number - 1) {
 		digest.push(generic::DigestItem::ChangesTrieRoot(storage_changes_root.into()));
 	}
@@ -215,7 +232,7 @@ pub fn finalize_block() -> Header {
 	let storage_root = BlakeTwo256::storage_root();
 	let storage_changes_root = BlakeTwo256::storage_changes_root(parent_hash, number - 1);
 
-	let mut digest = Digest::default();
+	let mut digest = Digest { logs: <PreRuntimeDigests>::take() };
 	if let Some(storage_changes_root) = storage_changes_root {
 		digest.push(generic::DigestItem::ChangesTrieRoot(storage_changes_root));
 	}
@@ -0,0 +1,56 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use criterion::{Criterion, criterion_group, criterion_main};
+use substrate_transaction_pool::{ChainApi, txpool::ChainApi as _};
+use test_client::{
+	TestClientBuilder, NativeExecutor, LocalExecutor, AccountKeyring,
+	client::ExecutionStrategy, runtime::{Extrinsic, Transfer},
+};
+use sr_primitives::generic::BlockId;
+
+fn transfer(nonce: u64) -> Extrinsic {
+	Transfer {
+		from: AccountKeyring::Alice.into(),
+		to: AccountKeyring::Bob.into(),
+		amount: 1,
+		nonce,
+	}.into_signed_tx()
+}
+
+fn validate_transactions(c: &mut Criterion, name: &str, fast_instance_reuse: bool) {
+	c.bench_function(name, move |b| {
+		let executor = NativeExecutor::<LocalExecutor>::new(None)
+			.with_fast_instance_reuse(fast_instance_reuse);
+		let client = TestClientBuilder::new()
+			.set_execution_strategy(ExecutionStrategy::AlwaysWasm)
+			.build_with_native_executor(executor);
+		let api = ChainApi::new(Arc::new(client));
+		let block_id = BlockId::number(0);
+		let uxt = transfer(0);
+
+		b.iter(|| api.validate_transaction(&block_id, uxt.clone()).unwrap())
+	});
+}
+
+fn transaction_pool_benchmark(c: &mut Criterion) {
+	validate_transactions(c, "validate transaction in wasm", false);
+	validate_transactions(c, "validate transaction in wasm with fast instance reuse", true);
+}
+
+criterion_group!(benches, transaction_pool_benchmark);
+criterion_main!(benches);
@@ -156,6 +156,94 @@ impl<H, N, V> ForkTree<H, N, V> where
 		self.node_iter().map(|node| (&node.hash, &node.number, &node.data))
 	}
 
+	/// Find the deepest node which is an ancestor of the given block, or the
+	/// block itself, and whose data satisfies the given `predicate`. The given
+	/// function `is_descendent_of` should return `true` if the second hash
+	/// (target) is a descendent of the first hash (base).
+	pub fn find_node_where<F, P, E>(
+		&self,
+		hash: &H,
+		number: &N,
+		is_descendent_of: &F,
+		predicate: &P,
+	) -> Result<Option<(&H, &N, &V)>, Error<E>>
+		where E: std::error::Error,
+			  F: Fn(&H, &H) -> Result<bool, E>,
+			  P: Fn(&V) -> bool,
+	{
+		for root in self.roots.iter() {
+			if let Some(node) = root.find_node_where(hash, number, is_descendent_of, predicate)? {
+				return Ok(Some((&node.hash, &node.number, &node.data)));
+			}
+		}
+
+		Ok(None)
+	}
+
+	/// Prune the tree after the given block was finalized. The deepest node which
+	/// is an ancestor of the block, or the block itself, becomes the only root and
+	/// only its descendents which descend from the block are kept. Without such a
+	/// node only the roots which descend from the block are kept. The given function
+	/// `is_descendent_of` should return `true` if the second hash (target) is a
+	/// descendent of the first hash (base).
+	pub fn prune<F, E>(
+		&mut self,
+		hash: &H,
+		number: &N,
+		is_descendent_of: &F,
+	) -> Result<(), Error<E>>
+		where E: std::error::Error,
+			  F: Fn(&H, &H) -> Result<bool, E>,
+			  H: Clone,
+	{
+		let new_root = self.find_node_where(hash, number, is_descendent_of, &|_| true)?
+			.map(|(root_hash, _, _)| root_hash.clone());
+
+		// decide which nodes to keep before changing the tree, so that it stays
+		// intact if checking the ancestry fails.
+		let keep = {
+			let candidates = match new_root {
+				Some(ref root_hash) => &self.node_iter()
+					.find(|node| node.hash == *root_hash)
+					.expect("the node was just found in the tree; qed")
+					.children,
+				None => &self.roots,
+			};
+
+			let mut keep = Vec::with_capacity(candidates.len());
+			for node in candidates {
+				keep.push(node.number > *number && is_descendent_of(hash, &node.hash)?);
+			}
+			keep
+		};
+
+		let roots = std::mem::replace(&mut self.roots, Vec::new());
+		let (new_root, candidates) = match new_root {
+			Some(root_hash) => {
+				let mut root = Node::take(roots, &root_hash)
+					.expect("the node was just found in the tree; qed");
+				let children = std::mem::replace(&mut root.children, Vec::new());
+				(Some(root), children)
+			},
+			None => (None, roots),
+		};
+
+		let kept = candidates.into_iter()
+			.zip(keep)
+			.filter_map(|(node, keep)| if keep { Some(node) } else { None })
+			.collect();
+
+		self.roots = match new_root {
+			Some(mut root) => {
+				root.children = kept;
+				vec![root]
+			},
+			None => kept,
+		};
+
+		Ok(())
+	}
+
 	/// Finalize a root in the tree and return it, return `None` in case no root
 	/// with the given hash exists. All other roots are pruned, and the children
 	/// of the finalized node become the new roots.
@@ -404,6 +492,51 @@ mod node_implementation {
 				Ok(Some((hash, number, data)))
 			}
 		}
+
+		/// Take the node with the given hash out of the given nodes and their descendents.
+		pub fn take(nodes: Vec<Node<H, N, V>>, hash: &H) -> Option<Node<H, N, V>> {
+			for node in nodes {
+				if node.hash == *hash {
+					return Some(node);
+				}
+
+				if let Some(node) = Node::take(node.children, hash) {
+					return Some(node);
+				}
+			}
+
+			None
+		}
+
+		pub fn find_node_where<F, P, E>(
+			&self,
+			hash: &H,
+			number: &N,
+			is_descendent_of: &F,
+			predicate: &P,
+		) -> Result<Option<&Node<H, N, V>>, Error<E>>
+			where E: std::error::Error,
+				  F: Fn(&H, &H) -> Result<bool, E>,
+				  P: Fn(&V) -> bool,
+		{
+			// nodes after the block can't be its ancestors.
+			if *number < self.number {
+				return Ok(None);
+			}
+
+			// the children are deeper than this node, so they are checked first.
+			for node in self.children.iter() {
+				if let Some(node) = node.find_node_where(hash, number, is_descendent_of, predicate)? {
+					return Ok(Some(node));
+				}
+			}
+
+			if (self.hash == *hash || is_descendent_of(&self.hash, hash)?) && predicate(&self.data) {
+				Ok(Some(self))
+			} else {
+				Ok(None)
+			}
+		}
 	}
 }
 
@@ -788,6 +921,74 @@ mod test {
 		assert_eq!(tree.roots().count(), 0);
 	}
 
+	#[test]
+	fn find_node_where_finds_deepest_ancestor() {
+		let (tree, is_descendent_of) = test_fork_tree();
+
+		let find = |hash, number| tree.find_node_where(&hash, &number, &is_descendent_of, &|_| true)
+			.unwrap()
+			.map(|(hash, _, _)| *hash);
+
+		assert_eq!(find("E", 5), Some("E"));
+		assert_eq!(find("G", 3), Some("G"));
+		assert_eq!(find("K", 3), Some("K"));
+		assert_eq!(find("A", 1), Some("A"));
+		assert_eq!(find("Z", 1), None);
+
+		let mut tree = ForkTree::new();
+		tree.import("A", 1, 1, &is_descendent_of).unwrap();
+		tree.import("B", 2, 2, &is_descendent_of).unwrap();
+		tree.import("C", 3, 3, &is_descendent_of).unwrap();
+
+		assert_eq!(
+			tree.find_node_where(&"E", &5, &is_descendent_of, &|data| *data < 3).unwrap(),
+			Some((&"B", &2, &2)),
+		);
+	}
+
+	#[test]
+	fn prune_keeps_the_finalized_branch() {
+		let (mut tree, is_descendent_of) = test_fork_tree();
+		let hashes = |tree: &ForkTree<&str, u64, ()>| tree.iter()
+			.map(|(hash, _, _)| *hash)
+			.collect::<Vec<_>>();
+
+		// "L" isn't in the tree, it is a child of "F" at the height of "G" and "H".
+		let is_descendent_of = |base: &&str, block: &&str| -> Result<bool, TestError> {
+			match (*base, *block) {
+				("A", "L") | ("F", "L") => Ok(true),
+				(_, "L") | ("L", _) => Ok(false),
+				_ => is_descendent_of(base, block),
+			}
+		};
+
+		// finalizing a node keeps the node and its descendents.
+		tree.prune(&"H", &3, &is_descendent_of).unwrap();
+		assert_eq!(hashes(&tree), vec!["H", "I"]);
+
+		// finalizing a block after a node keeps the node, but not its descendents on other forks.
+		let (mut tree, _) = test_fork_tree();
+		tree.prune(&"L", &3, &is_descendent_of).unwrap();
+		assert_eq!(hashes(&tree), vec!["F"]);
+
+		// without an ancestor in the tree only the roots descending from the block are kept.
+		let (mut tree, _) = test_fork_tree();
+		tree.finalize_root(&"A");
+		tree.prune(&"0", &1, &is_descendent_of).unwrap();
+		assert_eq!(tree.roots().count(), 3);
+		tree.prune(&"L", &3, &is_descendent_of).unwrap();
+		assert_eq!(hashes(&tree), vec!["F"]);
+
+		// the tree stays intact if the ancestry can't be checked.
+		let (mut tree, _) = test_fork_tree();
+		tree.finalize_root(&"A");
+		assert_eq!(
+			tree.prune(&"L", &3, &|_: &&str, _: &&str| -> Result<bool, TestError> { Err(TestError) }),
+			Err(Error::Client(TestError)),
+		);
+		assert_eq!(tree.roots().count(), 3);
+	}
+
 	#[test]
 	fn iter_iterates_in_preorder() {
 		let (tree, ..) = test_fork_tree();
@@ -13,7 +13,8 @@ use primitives::bytes;
 use primitives::{ed25519, sr25519, OpaqueMetadata};
 use runtime_primitives::{
 	ApplyResult, transaction_validity::TransactionValidity, generic, create_runtime_str,
-	traits::{self, NumberFor, BlakeTwo256, Block as BlockT, StaticLookup, Verify}
+	traits::{self, NumberFor, BlakeTwo256, Block as BlockT, StaticLookup, Verify},
+	weights::TransactionFee,
 };
 use client::{
 	block_builder::api::{CheckInherentsResult, InherentData, self as block_builder_api},
@@ -33,6 +34,7 @@ pub use balances::Call as BalancesCall;
 pub use runtime_primitives::{Permill, Perbill};
 pub use timestamp::BlockPeriod;
 pub use support::{StorageValue, construct_runtime};
+use support::dispatch::GetDispatchInfo;
 
 /// The type that is used for identifying authorities.
 pub type AuthorityId = <AuthoritySignature as Verify>::Signer;
@@ -90,12 +92,16 @@ pub mod opaque {
 	pub type SessionKey = AuthorityId;
 }
 
+/// The difficulty of the proof-of-work seal, when running with proof-of-work
+/// consensus. Roughly the number of hashes needed to seal a block.
+pub const POW_DIFFICULTY: consensus_pow::Difficulty = 1_000_000;
+
 /// This runtime version.
 pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("node-template"),
 	impl_name: create_runtime_str!("node-template"),
 	authoring_version: 3,
-	spec_version: 3,
+	spec_version: 4,
 	impl_version: 0,
 	apis: RUNTIME_API_VERSIONS,
 };
@@ -144,6 +150,10 @@ impl consensus::Trait for Runtime {
 	// The aura module handles offline-reports internally
 	// rather than using an explicit report system.
 	type InherentOfflineReport = ();
+	/// Equivocation reports are checked against Aura seals.
+	type SlotSeal = aura::SealChecker<Runtime, AuthoritySignature>;
+	// There is no staking module to punish equivocations with.
+	type EquivocationHandler = ();
 	/// The ubiquitous log type.
 	type Log = Log;
 }
@@ -163,7 +173,21 @@ impl indices::Trait for Runtime {
 impl timestamp::Trait for Runtime {
 	/// A timestamp: seconds since the unix epoch.
 	type Moment = u64;
-	type OnTimestampSet = Aura;
+	type OnTimestampSet = AuraSlots;
+}
+
+/// Passes the timestamp on to Aura, but only on chains with Aura authorities.
+///
+/// Chains sealed by other means, like the proof-of-work one, start without
+/// authorities so that Aura does not limit them to one block per slot.
+pub struct AuraSlots;
+
+impl timestamp::OnTimestampSet<u64> for AuraSlots {
+	fn on_timestamp_set(moment: u64) {
+		if !Consensus::authorities().is_empty() {
+			<Aura as timestamp::OnTimestampSet<u64>>::on_timestamp_set(moment);
+		}
+	}
 }
 
 impl balances::Trait for Runtime {
@@ -178,6 +202,8 @@ impl balances::Trait for Runtime {
 
 	type TransactionPayment = ();
 	type DustRemoval = ();
+	/// The weight of a transaction is charged like its length.
+	type WeightToFee = balances::WeightToByteFee<Runtime>;
 	type TransferPayment = ();
 }
 
@@ -198,7 +224,7 @@ construct_runtime!(
 		NodeBlock = opaque::Block,
 		UncheckedExtrinsic = UncheckedExtrinsic
 	{
-		System: system::{default, Log(ChangesTrieRoot)},
+		System: system::{default, Log(ChangesTrieRoot, ChangesTrieSignal)},
 		Timestamp: timestamp::{Module, Call, Storage, Config<T>, Inherent},
 		Consensus: consensus::{Module, Call, Storage, Config<T>, Log(AuthoritiesChange), Inherent},
 		Aura: aura::{Module},
@@ -281,6 +307,19 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl runtime_api::TransactionPaymentApi<Block> for Runtime {
+		fn query_fee(tx: <Block as BlockT>::Extrinsic) -> Option<TransactionFee> {
+			if tx.signature.is_none() {
+				return None;
+			}
+			let weight = system::extrinsic_weight(tx.function.get_dispatch_info());
+			Some(TransactionFee {
+				weight,
+				fee: Balances::compute_fee(tx.encode().len(), weight, tx.tip()),
+			})
+		}
+	}
+
 	impl consensus_aura::AuraApi<Block> for Runtime {
 		fn slot_duration() -> u64 {
 			Aura::slot_duration()
@@ -298,4 +337,10 @@ impl_runtime_apis! {
 			Consensus::authorities()
 		}
 	}
+
+	impl consensus_pow::DifficultyApi<Block> for Runtime {
+		fn difficulty() -> consensus_pow::Difficulty {
+			POW_DIFFICULTY
+		}
+	}
 }
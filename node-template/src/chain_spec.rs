@@ -22,6 +22,41 @@ pub enum Alternative {
 	Development,
 	/// Whatever the current runtime is, with simple Alice/Bob auths.
 	LocalTestnet,
+	/// Whatever the current runtime is, sealed with proof-of-work instead of Aura.
+	PowDevelopment,
+	/// Whatever the current runtime is, with a block sealed for every transaction.
+	InstantSealDevelopment,
+	/// Whatever the current runtime is, with blocks sealed through the `engine` RPC.
+	ManualSealDevelopment,
+}
+
+/// The id of the chain which runs proof-of-work consensus.
+pub const POW_DEV_ID: &str = "pow_dev";
+/// The id of the chain which seals a block for every transaction.
+pub const INSTANT_SEAL_DEV_ID: &str = "instant_seal_dev";
+/// The id of the chain which seals blocks through the `engine` RPC.
+pub const MANUAL_SEAL_DEV_ID: &str = "manual_seal_dev";
+
+/// The consensus engine which seals the blocks of a chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sealing {
+	/// Blocks are authored in Aura slots.
+	Aura,
+	/// Blocks are sealed with proof-of-work.
+	Pow,
+	/// Blocks are sealed on request, instantly or through the `engine` RPC.
+	Manual,
+}
+
+impl Sealing {
+	/// The consensus engine of the chain with the given id.
+	pub fn of(chain_id: &str) -> Self {
+		match chain_id {
+			POW_DEV_ID => Sealing::Pow,
+			INSTANT_SEAL_DEV_ID | MANUAL_SEAL_DEV_ID => Sealing::Manual,
+			_ => Sealing::Aura,
+		}
+	}
 }
 
 fn authority_key(s: &str) -> AuthorityId {
@@ -78,6 +113,53 @@ impl Alternative {
 				None,
 				None
 			),
+			Alternative::PowDevelopment => ChainSpec::from_genesis(
+				"Proof-of-Work Development",
+				POW_DEV_ID,
+				// Without authorities the runtime does not hold mined blocks to Aura slots.
+				|| testnet_genesis(vec![], vec![
+					account_key("Alice")
+				],
+					account_key("Alice")
+				),
+				vec![],
+				None,
+				None,
+				None,
+				None
+			),
+			Alternative::InstantSealDevelopment => ChainSpec::from_genesis(
+				"Instant Seal Development",
+				INSTANT_SEAL_DEV_ID,
+				|| testnet_genesis(vec![
+					authority_key("Alice")
+				], vec![
+					account_key("Alice")
+				],
+					account_key("Alice")
+				),
+				vec![],
+				None,
+				None,
+				None,
+				None
+			),
+			Alternative::ManualSealDevelopment => ChainSpec::from_genesis(
+				"Manual Seal Development",
+				MANUAL_SEAL_DEV_ID,
+				|| testnet_genesis(vec![
+					authority_key("Alice")
+				], vec![
+					account_key("Alice")
+				],
+					account_key("Alice")
+				),
+				vec![],
+				None,
+				None,
+				None,
+				None
+			),
 		})
 	}
 
@@ -85,6 +167,9 @@ impl Alternative {
 		match s {
 			"dev" => Some(Alternative::Development),
 			"" | "local" => Some(Alternative::LocalTestnet),
+			"pow-dev" => Some(Alternative::PowDevelopment),
+			"instant-seal" => Some(Alternative::InstantSealDevelopment),
+			"manual-seal" => Some(Alternative::ManualSealDevelopment),
 			_ => None,
 		}
 	}
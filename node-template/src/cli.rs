@@ -1,11 +1,11 @@
-use crate::service;
+use crate::{service, pow_service, seal_service};
 use futures::{future, Future, sync::oneshot};
 use std::cell::RefCell;
 use tokio::runtime::Runtime;
 pub use substrate_cli::{VersionInfo, IntoExit, error};
 use substrate_cli::{informant, parse_and_execute, NoCustom};
 use substrate_service::{ServiceFactory, Roles as ServiceRoles};
-use crate::chain_spec;
+use crate::chain_spec::{self, Sealing};
 use std::ops::Deref;
 use log::info;
 
@@ -26,17 +26,38 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 			info!("Roles: {:?}", config.roles);
 			let runtime = Runtime::new().map_err(|e| format!("{:?}", e))?;
 			let executor = runtime.executor();
-			match config.roles {
-				ServiceRoles::LIGHT => run_until_exit(
+			let sealing = Sealing::of(config.chain_spec.id());
+			match (config.roles, sealing) {
+				(ServiceRoles::LIGHT, Sealing::Aura) => run_until_exit(
 					runtime,
-				 	service::Factory::new_light(config, executor).map_err(|e| format!("{:?}", e))?,
+					service::Factory::new_light(config, executor).map_err(|e| format!("{:?}", e))?,
 					exit
 				),
-				_ => run_until_exit(
+				(_, Sealing::Aura) => run_until_exit(
 					runtime,
 					service::Factory::new_full(config, executor).map_err(|e| format!("{:?}", e))?,
 					exit
 				),
+				(ServiceRoles::LIGHT, Sealing::Pow) => run_until_exit(
+					runtime,
+					pow_service::PowFactory::new_light(config, executor).map_err(|e| format!("{:?}", e))?,
+					exit
+				),
+				(_, Sealing::Pow) => run_until_exit(
+					runtime,
+					pow_service::PowFactory::new_full(config, executor).map_err(|e| format!("{:?}", e))?,
+					exit
+				),
+				(ServiceRoles::LIGHT, Sealing::Manual) => run_until_exit(
+					runtime,
+					seal_service::SealFactory::new_light(config, executor).map_err(|e| format!("{:?}", e))?,
+					exit
+				),
+				(_, Sealing::Manual) => run_until_exit(
+					runtime,
+					seal_service::SealFactory::new_full(config, executor).map_err(|e| format!("{:?}", e))?,
+					exit
+				),
 			}.map_err(|e| format!("{:?}", e))
 		}
 	).map_err(Into::into).map(|_| ())
@@ -5,6 +5,8 @@
 
 mod chain_spec;
 mod service;
+mod pow_service;
+mod seal_service;
 mod cli;
 
 pub use substrate_cli::{VersionInfo, IntoExit, error};
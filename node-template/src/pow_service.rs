@@ -0,0 +1,124 @@
+//! Service and ServiceFactory implementation running proof-of-work consensus
+//! instead of Aura. Selected by running with the `pow-dev` chain.
+//!
+//! The `pow-dev` chain starts without Aura authorities, so the runtime does not
+//! pass its timestamps on to the Aura module and blocks may be mined as fast as
+//! the difficulty allows.
+
+#![warn(unused_extern_crates)]
+
+use std::sync::Arc;
+use std::time::Duration;
+use log::info;
+use transaction_pool::{self, txpool::{Pool as TransactionPool}};
+use node_template_runtime::{self, GenesisConfig, opaque::Block, RuntimeApi};
+use substrate_service::{
+	FactoryFullConfiguration, LightComponents, FullComponents, FullBackend,
+	FullClient, LightClient, LightBackend, FullExecutor, LightExecutor,
+	TaskExecutor,
+};
+use basic_authorship::ProposerFactory;
+use consensus_pow::{import_queue, start_mine, Blake2Algorithm, PowBlockImport, PowImportQueue};
+use substrate_client::{self as client, BlockchainEvents, HeaviestChain};
+use primitives::ed25519::Pair;
+use substrate_service::construct_service_factory;
+use crate::service::{Executor, NodeConfig, NodeProtocol};
+
+/// Maximum time spent building a block before mining it.
+const BUILD_TIME: Duration = Duration::from_secs(1);
+
+construct_service_factory! {
+	struct PowFactory {
+		Block = Block,
+		RuntimeApi = RuntimeApi,
+		NetworkProtocol = NodeProtocol { |config| Ok(NodeProtocol::new()) },
+		RuntimeDispatch = Executor,
+		FullTransactionPoolApi = transaction_pool::ChainApi<client::Client<FullBackend<Self>, FullExecutor<Self>, Block, RuntimeApi>, Block>
+			{ |config, client| Ok(TransactionPool::new(config, transaction_pool::ChainApi::new(client))) },
+		LightTransactionPoolApi = transaction_pool::ChainApi<client::Client<LightBackend<Self>, LightExecutor<Self>, Block, RuntimeApi>, Block>
+			{ |config, client| Ok(TransactionPool::new(config, transaction_pool::ChainApi::new(client))) },
+		Genesis = GenesisConfig,
+		Configuration = NodeConfig,
+		FullService = FullComponents<Self>
+			{ |config: FactoryFullConfiguration<Self>, executor: TaskExecutor|
+				FullComponents::<PowFactory>::new(config, executor)
+			},
+		AuthoritySetup = {
+			|service: Self::FullService, executor: TaskExecutor, key: Option<Arc<Pair>>| {
+				if key.is_some() {
+					info!("Mining blocks with proof-of-work");
+					let proposer = Arc::new(ProposerFactory {
+						client: service.client(),
+						transaction_pool: service.transaction_pool(),
+						inherents_pool: service.inherents_pool(),
+					});
+					let client = service.client();
+					let algorithm = Blake2Algorithm::new(client.clone());
+					let block_import = Arc::new(PowBlockImport::new(
+						client.clone(),
+						client.clone(),
+						algorithm.clone(),
+					));
+					executor.spawn(start_mine(
+						block_import,
+						service.select_chain(),
+						client.import_notification_stream(),
+						algorithm,
+						proposer,
+						service.network(),
+						service.config.custom.inherent_data_providers.clone(),
+						BUILD_TIME,
+						service.on_exit(),
+					)?);
+				}
+
+				Ok(service)
+			}
+		},
+		LightService = LightComponents<Self>
+			{ |config, executor| <LightComponents<PowFactory>>::new(config, executor) },
+		FullImportQueue = PowImportQueue<
+			Self::Block,
+		>
+			{ |config: &mut FactoryFullConfiguration<Self> , client: Arc<FullClient<Self>>, _select_chain: Self::SelectChain| {
+					let algorithm = Blake2Algorithm::new(client.clone());
+					let block_import = Arc::new(PowBlockImport::new(
+						client.clone(),
+						client.clone(),
+						algorithm.clone(),
+					));
+					import_queue(
+						block_import,
+						None,
+						client,
+						algorithm,
+						config.custom.inherent_data_providers.clone(),
+					).map_err(Into::into)
+				}
+			},
+		LightImportQueue = PowImportQueue<
+			Self::Block,
+		>
+			{ |config: &mut FactoryFullConfiguration<Self>, client: Arc<LightClient<Self>>| {
+					let algorithm = Blake2Algorithm::new(client.clone());
+					let block_import = Arc::new(PowBlockImport::new(
+						client.clone(),
+						client.clone(),
+						algorithm.clone(),
+					));
+					import_queue(
+						block_import,
+						None,
+						client,
+						algorithm,
+						config.custom.inherent_data_providers.clone(),
+					).map_err(Into::into)
+				}
+			},
+		SelectChain = HeaviestChain<FullBackend<Self>, Self::Block>
+			{ |config: &FactoryFullConfiguration<Self>, client: Arc<FullClient<Self>>| {
+				Ok(HeaviestChain::new(client.backend().clone()))
+			}
+		},
+	}
+}
@@ -0,0 +1,126 @@
+//! Service and ServiceFactory implementation sealing blocks on request instead
+//! of running Aura, for development and testing. Selected by running with the
+//! `instant-seal` or the `manual-seal` chain.
+//!
+//! On both chains, blocks are authored and finalized through the
+//! `engine_createBlock` and `engine_finalizeBlock` RPC calls, which are only
+//! exposed when running with `--rpc-unsafe-methods`. On the
+//! `instant-seal` chain, a block is also authored for every transaction which
+//! enters the pool.
+//!
+//! The runtime is unchanged, so the Aura module still requires every block to
+//! be in a later slot than its parent. Block timestamps therefore advance by a
+//! slot per block instead of following the clock.
+
+#![warn(unused_extern_crates)]
+
+use std::sync::Arc;
+use futures::Future;
+use log::{info, warn};
+use transaction_pool::{self, txpool::{Pool as TransactionPool}};
+use node_template_runtime::{self, GenesisConfig, opaque::Block, RuntimeApi};
+use substrate_service::{
+	FactoryFullConfiguration, LightComponents, FullComponents, FullBackend,
+	FullClient, LightClient, LightBackend, FullExecutor, LightExecutor,
+	TaskExecutor,
+};
+use basic_authorship::ProposerFactory;
+use consensus::SlotDuration;
+use consensus_common::import_queue::SharedBlockImport;
+use consensus_manual_seal::{
+	import_queue, run_instant_seal, run_manual_seal, IncrementingTimestampProvider,
+	ManualSeal, ManualSealImportQueue,
+};
+use substrate_client::{self as client, LongestChain};
+use primitives::ed25519::Pair;
+use substrate_service::construct_service_factory;
+use crate::chain_spec::INSTANT_SEAL_DEV_ID;
+use crate::service::{Executor, NodeConfig, NodeProtocol};
+
+construct_service_factory! {
+	struct SealFactory {
+		Block = Block,
+		RuntimeApi = RuntimeApi,
+		NetworkProtocol = NodeProtocol { |config| Ok(NodeProtocol::new()) },
+		RuntimeDispatch = Executor,
+		FullTransactionPoolApi = transaction_pool::ChainApi<client::Client<FullBackend<Self>, FullExecutor<Self>, Block, RuntimeApi>, Block>
+			{ |config, client| Ok(TransactionPool::new(config, transaction_pool::ChainApi::new(client))) },
+		LightTransactionPoolApi = transaction_pool::ChainApi<client::Client<LightBackend<Self>, LightExecutor<Self>, Block, RuntimeApi>, Block>
+			{ |config, client| Ok(TransactionPool::new(config, transaction_pool::ChainApi::new(client))) },
+		Genesis = GenesisConfig,
+		Configuration = NodeConfig,
+		FullService = FullComponents<Self>
+			{ |config: FactoryFullConfiguration<Self>, executor: TaskExecutor|
+				FullComponents::<SealFactory>::new(config, executor)
+			},
+		AuthoritySetup = {
+			|service: Self::FullService, executor: TaskExecutor, key: Option<Arc<Pair>>| {
+				if key.is_some() {
+					let client = service.client();
+					let inherent_data_providers = service.config.custom.inherent_data_providers.clone();
+					let slot_duration = SlotDuration::get_or_compute(&*client)?.get();
+					inherent_data_providers
+						.register_provider(IncrementingTimestampProvider::new(slot_duration).map_err(String::from)?)
+						.map_err(String::from)?;
+
+					let proposer = Arc::new(ProposerFactory {
+						client: client.clone(),
+						transaction_pool: service.transaction_pool(),
+						inherents_pool: service.inherents_pool(),
+					});
+					let block_import: SharedBlockImport<Block> = client.clone();
+					let seal = ManualSeal::new(
+						client,
+						block_import,
+						proposer,
+						service.select_chain(),
+						inherent_data_providers,
+					);
+
+					if service.config.chain_spec.id() == INSTANT_SEAL_DEV_ID {
+						info!("Sealing a block for every transaction");
+						let pool_imports = service.transaction_pool().import_notification_stream();
+						executor.spawn(run_instant_seal(seal.clone(), pool_imports)
+							.select(service.on_exit())
+							.then(|_| Ok(())));
+					}
+
+					if service.config.rpc_unsafe_methods {
+						info!("Sealing blocks through the engine RPC");
+					} else {
+						warn!("The engine RPC is only exposed with `--rpc-unsafe-methods`");
+					}
+					executor.spawn(run_manual_seal(seal, service.engine_command_stream())
+						.select(service.on_exit())
+						.then(|_| Ok(())));
+				}
+
+				Ok(service)
+			}
+		},
+		LightService = LightComponents<Self>
+			{ |config, executor| <LightComponents<SealFactory>>::new(config, executor) },
+		FullImportQueue = ManualSealImportQueue<
+			Self::Block,
+		>
+			{ |config: &mut FactoryFullConfiguration<Self> , client: Arc<FullClient<Self>>, _select_chain: Self::SelectChain| {
+					Ok(import_queue::<Self::Block>(client, None))
+				}
+			},
+		LightImportQueue = ManualSealImportQueue<
+			Self::Block,
+		>
+			{ |config: &mut FactoryFullConfiguration<Self>, client: Arc<LightClient<Self>>| {
+					Ok(import_queue::<Self::Block>(client, None))
+				}
+			},
+		SelectChain = LongestChain<FullBackend<Self>, Self::Block>
+			{ |config: &FactoryFullConfiguration<Self>, client: Arc<FullClient<Self>>| {
+				Ok(LongestChain::new(
+					client.backend().clone(),
+					client.import_lock()
+				))
+			}
+		},
+	}
+}
@@ -31,7 +31,7 @@ native_executor_instance!(
 
 #[derive(Default)]
 pub struct NodeConfig {
-	inherent_data_providers: InherentDataProviders,
+	pub inherent_data_providers: InherentDataProviders,
 }
 
 construct_simple_protocol! {
@@ -174,6 +174,7 @@ fn staging_testnet_config_genesis() -> GenesisConfig {
 			key: endowed_accounts[0].clone(),
 		}),
 		grandpa: Some(GrandpaConfig {
+			current_set_id: 0,
 			authorities: initial_authorities.iter().map(|x| (x.2.clone(), 1)).collect(),
 		}),
 	}
@@ -342,6 +343,7 @@ pub fn testnet_genesis(
 			key: root_key,
 		}),
 		grandpa: Some(GrandpaConfig {
+			current_set_id: 0,
 			authorities: initial_authorities.iter().map(|x| (x.2.clone(), 1)).collect(),
 		}),
 	}
@@ -21,7 +21,8 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use client::{self, LongestChain};
+use client::{self, ChainSelection, HeaviestChain, LongestChain, runtime_api::ApiExt};
+use babe_primitives::BabeApi;
 use consensus::{import_queue, start_aura, AuraImportQueue,
 	SlotDuration, NothingExtra
 };
@@ -29,17 +30,20 @@ use grandpa;
 use node_executor;
 use primitives::{Pair as PairT, ed25519};
 use node_primitives::Block;
+use sr_primitives::generic::BlockId;
+use sr_primitives::traits::ProvideRuntimeApi;
 use node_runtime::{GenesisConfig, RuntimeApi};
 // 注意： 就这个 LightComponents, FullComponents 就是 core/service/components.rs 中定义的那两个
 use substrate_service::{
 	FactoryFullConfiguration, LightComponents, FullComponents, FullBackend,
 	FullClient, LightClient, LightBackend, FullExecutor, LightExecutor, TaskExecutor,
+	FinalityProofProvider, CodeExecutor,
 };
 use transaction_pool::{self, txpool::{Pool as TransactionPool}};
 use inherents::InherentDataProviders;
 use network::construct_simple_protocol;
 use substrate_service::construct_service_factory;
-use log::info;
+use log::{info, warn};
 use substrate_service::TelemetryOnConnect;
 
 construct_simple_protocol! {
@@ -64,6 +68,57 @@ impl<F> Default for NodeConfig<F> where F: substrate_service::ServiceFactory {
 	}
 }
 
+/// Submit an encoded GRANDPA equivocation report in a `report_misbehavior` transaction signed
+/// by the authority key, so that it is included even if this node doesn't author blocks.
+fn submit_grandpa_equivocation_report(
+	client: &FullClient<Factory>,
+	transaction_pool: &TransactionPool<transaction_pool::ChainApi<FullClient<Factory>, Block>>,
+	key: &ed25519::Pair,
+	report: Vec<u8>,
+) -> Result<(), String> {
+	use parity_codec::{Compact, Decode, Encode};
+	use primitives::{blake2_256, sr25519, storage::StorageKey};
+	use sr_primitives::{OpaqueExtrinsic, generic::Era};
+	use node_runtime::{AccountNonce, Call, GrandpaCall, Runtime, StorageMap, UncheckedExtrinsic};
+
+	let info = client.info().map_err(|e| format!("{:?}", e))?.chain;
+	let at = BlockId::hash(info.best_hash);
+
+	// the authority key signs for the account with the same public key.
+	let account = sr25519::Public(key.public().0);
+	let nonce_key = StorageKey(blake2_256(&<AccountNonce<Runtime>>::key_for(&account)).to_vec());
+	let index = client.storage(&at, &nonce_key)
+		.map_err(|e| format!("{:?}", e))?
+		.and_then(|data| Decode::decode(&mut &data.0[..]))
+		.unwrap_or(0);
+
+	let payload = (
+		Compact(index),
+		Call::Grandpa(GrandpaCall::report_misbehavior(report)),
+		Era::immortal(),
+		info.genesis_hash,
+		Compact(0u128),
+	);
+	let signature = payload.using_encoded(|payload| if payload.len() > 256 {
+		key.sign(&blake2_256(payload)[..])
+	} else {
+		key.sign(payload)
+	});
+	let xt = UncheckedExtrinsic::new_signed(
+		index,
+		payload.1,
+		account.into(),
+		signature.into(),
+		Era::immortal(),
+		0,
+	).encode();
+	let xt: Vec<u8> = Decode::decode(&mut &xt[..]).ok_or("Extrinsic is not length-prefixed")?;
+
+	transaction_pool.submit_one(&at, OpaqueExtrinsic(xt))
+		.map(|_| ())
+		.map_err(|e| format!("{:?}", e))
+}
+
 /*
 TODO 重要的入口
 
@@ -124,7 +179,7 @@ construct_service_factory! {
 					info!("Running Grandpa session as Authority {}", key.public());
 				}
 
-				let local_key = if service.config.disable_grandpa {
+				let local_key = if service.config.disable_grandpa || service.config.grandpa_observer {
 					None
 				} else {
 					local_key
@@ -138,8 +193,14 @@ construct_service_factory! {
 					name: Some(service.config.name.clone())
 				};
 
-				match config.local_key {
-					None => {
+				match (config.local_key.is_some(), service.config.disable_grandpa) {
+					(_, true) => {
+						grandpa::setup_disabled_grandpa(
+							service.client(),
+							&service.config.custom.inherent_data_providers,
+						)?;
+					},
+					(false, false) => {
 						executor.spawn(grandpa::run_grandpa_observer(
 							config,
 							link_half,
@@ -147,7 +208,21 @@ construct_service_factory! {
 							service.on_exit(),
 						)?);
 					},
-					Some(_) => {
+					(true, false) => {
+						let submit_equivocation_report = config.local_key.clone().map(|key| {
+							let client = service.client();
+							let transaction_pool = service.transaction_pool();
+							Arc::new(move |report: Vec<u8>| {
+								if let Err(e) = submit_grandpa_equivocation_report(
+									&client,
+									&transaction_pool,
+									&key,
+									report,
+								) {
+									warn!("Failed to submit GRANDPA equivocation report: {}", e);
+								}
+							}) as Arc<dyn Fn(Vec<u8>) + Send + Sync>
+						});
 						let telemetry_on_connect = TelemetryOnConnect {
 						  on_exit: Box::new(service.on_exit()),
 						  telemetry_connection_sinks: service.telemetry_on_connect_stream(),
@@ -160,6 +235,7 @@ construct_service_factory! {
 						  inherent_data_providers: service.config.custom.inherent_data_providers.clone(),
 						  on_exit: service.on_exit(),
 						  telemetry_on_connect: Some(telemetry_on_connect),
+						  submit_equivocation_report,
 						};
 						executor.spawn(grandpa::run_grandpa_voter(grandpa_config)?);
 					},
@@ -209,14 +285,28 @@ construct_service_factory! {
 				).map_err(Into::into)
 			}
 		},
-		SelectChain = LongestChain<FullBackend<Self>, Self::Block>
+		SelectChain = ChainSelection<FullBackend<Self>, Self::Block>
 			{ |config: &FactoryFullConfiguration<Self>, client: Arc<FullClient<Self>>| {
-				Ok(LongestChain::new(
-					client.backend().clone(),
-					client.import_lock()
-				))
+				// BABE weighs chains by their blocks authored in primary slots,
+				// other engines follow the longest chain.
+				let best_hash = client.info()?.chain.best_hash;
+				let weighs_chains = client.runtime_api()
+					.has_api::<BabeApi<Block>>(&BlockId::hash(best_hash))?;
+
+				if weighs_chains {
+					Ok(ChainSelection::Heaviest(HeaviestChain::new(client.backend().clone())))
+				} else {
+					Ok(ChainSelection::Longest(LongestChain::new(
+						client.backend().clone(),
+						client.import_lock()
+					)))
+				}
 			}
 		},
+		FinalityProofProvider = { |client: Arc<FullClient<Self>>| {
+			let executor = CodeExecutor::<Self>::new(None);
+			Ok(Some(Arc::new(grandpa::FinalityProofProvider::new(client, executor)) as Arc<FinalityProofProvider<Block>>))
+		}},
 	}
 }
 
@@ -257,11 +347,11 @@ mod tests {
 			}
 		};
 		let extrinsic_factory = |service: &<Factory as service::ServiceFactory>::FullService| {
-			let payload = (0, Call::Balances(BalancesCall::transfer(RawAddress::Id(bob.public().0.into()), 69.into())), Era::immortal(), service.client().genesis_hash());
+			let payload = (0, Call::Balances(BalancesCall::transfer(RawAddress::Id(bob.public().0.into()), 69.into())), Era::immortal(), service.client().genesis_hash(), Compact(0u128));
 			let signature = alice.sign(&payload.encode()).into();
 			let id = alice.public().0.into();
 			let xt = UncheckedExtrinsic {
-				signature: Some((RawAddress::Id(id), signature, payload.0, Era::immortal())),
+				signature: Some((RawAddress::Id(id), signature, payload.0, Era::immortal(), payload.4)),
 				function: payload.1,
 			}.encode();
 			let v: Vec<u8> = Decode::decode(&mut xt.as_slice()).unwrap();
@@ -78,9 +78,9 @@ mod tests {
 
 	fn sign(xt: CheckedExtrinsic) -> UncheckedExtrinsic {
 		match xt.signed {
-			Some((signed, index)) => {
+			Some((signed, index, tip)) => {
 				let era = Era::mortal(256, 0);
-				let payload = (index.into(), xt.function, era, GENESIS_HASH);
+				let payload = (index.into(), xt.function, era, GENESIS_HASH, tip.into());
 				let key = AccountKeyring::from_public(&signed).unwrap();
 				let signature = payload.using_encoded(|b| {
 					if b.len() > 256 {
@@ -90,7 +90,7 @@ mod tests {
 					}
 				}).into();
 				UncheckedExtrinsic {
-					signature: Some((indices::address::Address::Id(signed), signature, payload.0, era)),
+					signature: Some((indices::address::Address::Id(signed), signature, payload.0, era, payload.4)),
 					function: payload.1,
 				}
 			}
@@ -103,7 +103,7 @@ mod tests {
 
 	fn xt() -> UncheckedExtrinsic {
 		sign(CheckedExtrinsic {
-			signed: Some((alice(), 0)),
+			signed: Some((alice(), 0, 0)),
 			function: Call::Balances(balances::Call::transfer::<Runtime>(bob().into(), 69)),
 		})
 	}
@@ -320,6 +320,7 @@ mod tests {
 			contract: Some(Default::default()),
 			sudo: Some(Default::default()),
 			grandpa: Some(GrandpaConfig {
+				current_set_id: 0,
 				authorities: vec![],
 			}),
 		}.build_storage().unwrap().0)
@@ -395,7 +396,7 @@ mod tests {
 					function: Call::Timestamp(timestamp::Call::set(42)),
 				},
 				CheckedExtrinsic {
-					signed: Some((alice(), 0)),
+					signed: Some((alice(), 0, 0)),
 					function: Call::Balances(balances::Call::transfer(bob().into(), 69)),
 				},
 			]
@@ -417,7 +418,7 @@ mod tests {
 					function: Call::Timestamp(timestamp::Call::set(42)),
 				},
 				CheckedExtrinsic {
-					signed: Some((alice(), 0)),
+					signed: Some((alice(), 0, 0)),
 					function: Call::Balances(balances::Call::transfer(bob().into(), 69)),
 				},
 			]
@@ -432,11 +433,11 @@ mod tests {
 					function: Call::Timestamp(timestamp::Call::set(52)),
 				},
 				CheckedExtrinsic {
-					signed: Some((bob(), 0)),
+					signed: Some((bob(), 0, 0)),
 					function: Call::Balances(balances::Call::transfer(alice().into(), 5)),
 				},
 				CheckedExtrinsic {
-					signed: Some((alice(), 1)),
+					signed: Some((alice(), 1, 0)),
 					function: Call::Balances(balances::Call::transfer(bob().into(), 15)),
 				}
 			]
@@ -461,7 +462,7 @@ mod tests {
 					function: Call::Timestamp(timestamp::Call::set(42)),
 				},
 				CheckedExtrinsic {
-					signed: Some((alice(), 0)),
+					signed: Some((alice(), 0, 0)),
 					function: Call::Consensus(consensus::Call::remark(vec![0; 120000])),
 				}
 			]
@@ -721,19 +722,19 @@ mod tests {
 					function: Call::Timestamp(timestamp::Call::set(42)),
 				},
 				CheckedExtrinsic {
-					signed: Some((charlie(), 0)),
+					signed: Some((charlie(), 0, 0)),
 					function: Call::Contract(
 						contract::Call::put_code::<Runtime>(10_000, transfer_code)
 					),
 				},
 				CheckedExtrinsic {
-					signed: Some((charlie(), 1)),
+					signed: Some((charlie(), 1, 0)),
 					function: Call::Contract(
 						contract::Call::create::<Runtime>(10, 10_000, transfer_ch, Vec::new())
 					),
 				},
 				CheckedExtrinsic {
-					signed: Some((charlie(), 2)),
+					signed: Some((charlie(), 2, 0)),
 					function: Call::Contract(
 						contract::Call::call::<Runtime>(indices::address::Address::Id(addr.clone()), 10, 10_000, vec![0x00, 0x01, 0x02, 0x03])
 					),
@@ -32,7 +32,16 @@ use client::{
 	runtime_api as client_api, impl_runtime_apis
 };
 use runtime_primitives::{ApplyResult, generic, create_runtime_str};
+use parity_codec::Encode;
+use support::dispatch::GetDispatchInfo;
+#[cfg(feature = "runtime-benchmarks")]
+use parity_codec::Decode;
+#[cfg(feature = "runtime-benchmarks")]
+use support::dispatch::Dispatchable;
+#[cfg(feature = "runtime-benchmarks")]
+use benchmarking::Benchmarking;
 use runtime_primitives::transaction_validity::TransactionValidity;
+use runtime_primitives::weights::TransactionFee;
 use runtime_primitives::traits::{
 	BlakeTwo256, Block as BlockT, DigestFor, NumberFor, StaticLookup, AuthorityIdFor, Convert,
 };
@@ -49,8 +58,10 @@ pub use runtime_primitives::BuildStorage;
 pub use consensus::Call as ConsensusCall;
 pub use timestamp::Call as TimestampCall;
 pub use balances::Call as BalancesCall;
+pub use grandpa::Call as GrandpaCall;
+pub use system::AccountNonce;
 pub use runtime_primitives::{Permill, Perbill};
-pub use support::StorageValue;
+pub use support::{StorageValue, StorageMap};
 pub use staking::StakerStatus;
 
 /// Runtime version.
@@ -58,8 +69,8 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("node"),
 	impl_name: create_runtime_str!("substrate-node"),
 	authoring_version: 10,
-	spec_version: 77,
-	impl_version: 77,
+	spec_version: 86,
+	impl_version: 86,
 	apis: RUNTIME_API_VERSIONS,
 };
 
@@ -118,6 +129,7 @@ impl balances::Trait for Runtime {
 	type Event = Event;
 	type TransactionPayment = ();
 	type DustRemoval = ();
+	type WeightToFee = balances::WeightToByteFee<Runtime>;
 	type TransferPayment = ();
 }
 
@@ -128,6 +140,8 @@ impl consensus::Trait for Runtime {
 	// The Aura module handles offline-reports internally
 	// rather than using an explicit report system.
 	type InherentOfflineReport = ();
+	type SlotSeal = aura::SealChecker<Runtime, AuthoritySignature>;
+	type EquivocationHandler = Staking;
 }
 
 impl timestamp::Trait for Runtime {
@@ -199,6 +213,8 @@ impl sudo::Trait for Runtime {
 
 impl grandpa::Trait for Runtime {
 	type SessionKey = AuthorityId;
+	type Signature = AuthoritySignature;
+	type EquivocationHandler = Staking;
 	type Log = Log;
 	type Event = Event;
 }
@@ -213,7 +229,7 @@ construct_runtime!(
 		NodeBlock = node_primitives::Block,
 		UncheckedExtrinsic = UncheckedExtrinsic
 	{
-		System: system::{default, Log(ChangesTrieRoot)},
+		System: system::{default, Log(ChangesTrieRoot, ChangesTrieSignal)},
 		Aura: aura::{Module, Inherent(Timestamp)},
 		Timestamp: timestamp::{Module, Call, Storage, Config<T>, Inherent},
 		Consensus: consensus::{Module, Call, Storage, Config<T>, Log(AuthoritiesChange), Inherent},
@@ -227,7 +243,7 @@ construct_runtime!(
 		CouncilMotions: council_motions::{Module, Call, Storage, Event<T>, Origin},
 		CouncilSeats: council_seats::{Config<T>},
 		FinalityTracker: finality_tracker::{Module, Call, Inherent},
-		Grandpa: grandpa::{Module, Call, Storage, Config<T>, Log(), Event<T>},
+		Grandpa: grandpa::{Module, Call, Storage, Config<T>, Log(), Event<T>, Inherent},
 		Treasury: treasury,
 		Contract: contract::{Module, Call, Storage, Config<T>, Event<T>},
 		Sudo: sudo,
@@ -304,6 +320,19 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl client_api::TransactionPaymentApi<Block> for Runtime {
+		fn query_fee(tx: <Block as BlockT>::Extrinsic) -> Option<TransactionFee> {
+			if tx.signature.is_none() {
+				return None;
+			}
+			let weight = system::extrinsic_weight(tx.function.get_dispatch_info());
+			Some(TransactionFee {
+				weight,
+				fee: Balances::compute_fee(tx.encode().len(), weight, tx.tip()),
+			})
+		}
+	}
+
 	impl offchain_primitives::OffchainWorkerApi<Block> for Runtime {
 		fn offchain_worker(number: NumberFor<Block>) {
 			Executive::offchain_worker(number)
@@ -355,4 +384,30 @@ impl_runtime_apis! {
 			Consensus::authorities()
 		}
 	}
+
+	#[cfg(feature = "runtime-benchmarks")]
+	impl benchmarking::Benchmark<Block> for Runtime {
+		fn benchmark_cases() -> Vec<benchmarking::BenchmarkCase> {
+			let mut cases = Balances::benchmark_cases();
+			cases.extend(Staking::benchmark_cases());
+			cases
+		}
+
+		fn setup_benchmark(module: Vec<u8>, case: Vec<u8>, params: Vec<u32>) -> Result<Vec<u8>, Vec<u8>> {
+			let setup = match &module[..] {
+				b"balances" => Balances::setup_benchmark(&case, &params)
+					.map(|(signer, call)| (signer, Call::Balances(call))),
+				b"staking" => Staking::setup_benchmark(&case, &params)
+					.map(|(signer, call)| (signer, Call::Staking(call))),
+				_ => Err("unknown benchmark module"),
+			};
+			setup.map(|setup| setup.encode()).map_err(|e| e.as_bytes().to_vec())
+		}
+
+		fn dispatch_benchmark(call: Vec<u8>) -> Result<(), Vec<u8>> {
+			let (signer, call): (Option<AccountId>, Call) = Decode::decode(&mut &call[..])
+				.ok_or_else(|| b"invalid benchmark call".to_vec())?;
+			call.dispatch(signer.into()).map_err(|e| e.as_bytes().to_vec())
+		}
+	}
 }
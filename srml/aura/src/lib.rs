@@ -36,8 +36,9 @@
 //!  struct and associated method).
 //! - [Timestamp](../srml_timestamp/index.html): The Timestamp module is used in Aura to track
 //! consensus rounds (via `slots`).
-//! - [Consensus](../srml_consensus/index.html): The Consensus module does not relate directly to Aura,
-//!  but serves to manage offline reporting by implementing `ProvideInherent` in a similar way.
+//! - [Consensus](../srml_consensus/index.html): The Consensus module serves to manage offline reporting by
+//!  implementing `ProvideInherent` in a similar way. Its equivocation reports are checked against Aura
+//!  seals by the [`SealChecker`](./struct.SealChecker.html) struct.
 //!
 //! ## References
 //!
@@ -53,7 +54,8 @@ pub use timestamp;
 use rstd::{result, prelude::*};
 use srml_support::storage::StorageValue;
 use srml_support::{decl_storage, decl_module};
-use primitives::traits::{As, Zero};
+use primitives::traits::{As, Zero, Header as HeaderT, Digest, DigestItem, Verify};
+use substrate_consensus_aura_primitives::AURA_ENGINE_ID;
 use timestamp::OnTimestampSet;
 #[cfg(feature = "std")]
 use timestamp::TimestampInherentData;
@@ -146,7 +148,7 @@ impl HandleReport for () {
 	fn handle_report(_report: AuraReport) { }
 }
 
-pub trait Trait: timestamp::Trait {
+pub trait Trait: timestamp::Trait + consensus::Trait {
 	/// The logic for handling reports.
 	type HandleReport: HandleReport;
 }
@@ -155,6 +157,10 @@ decl_storage! {
 	trait Store for Module<T: Trait> as Aura {
 		/// The last timestamp.
 		LastTimestamp get(last) build(|_| T::Moment::sa(0)): T::Moment;
+
+		/// The authority sets of the slots equivocations can still be reported for, each
+		/// with the first slot it authored, oldest first.
+		AuthoritySets get(authority_sets): Vec<(u64, Vec<T::SessionKey>)>;
 	}
 }
 
@@ -226,9 +232,44 @@ impl<T: Trait> Module<T> {
 	}
 }
 
+impl<T: Trait> Module<T> {
+	/// Note the authority set of the block authored in `slot`, forgetting the sets which
+	/// were replaced before the slots equivocations can be reported for.
+	fn note_authority_set(slot: u64) {
+		let authorities = <consensus::Module<T>>::authorities();
+		let mut sets = Self::authority_sets();
+		if sets.last().map_or(false, |(_, last)| *last == authorities) {
+			return;
+		}
+
+		sets.push((slot, authorities));
+		let window_start = slot.saturating_sub(consensus::EQUIVOCATION_SLOT_WINDOW);
+		let expired = sets.iter().skip(1).take_while(|(start, _)| *start <= window_start).count();
+		sets.drain(..expired);
+		<AuthoritySets<T>>::put(sets);
+	}
+
+	/// The authority set which authored the blocks of `slot`.
+	///
+	/// This is the current set if none has been noted yet, and `None` if the slot is older
+	/// than the noted sets.
+	pub fn authorities_at(slot: u64) -> Option<Vec<T::SessionKey>> {
+		let sets = Self::authority_sets();
+		if sets.is_empty() {
+			return Some(<consensus::Module<T>>::authorities());
+		}
+
+		sets.into_iter().rev().find(|(start, _)| *start <= slot).map(|(_, authorities)| authorities)
+	}
+}
+
 impl<T: Trait> OnTimestampSet<T::Moment> for Module<T> {
 	fn on_timestamp_set(moment: T::Moment) {
-		Self::on_timestamp_set::<T::HandleReport>(moment, T::Moment::sa(Self::slot_duration()))
+		let slot_duration = Self::slot_duration();
+		Self::on_timestamp_set::<T::HandleReport>(moment.clone(), T::Moment::sa(slot_duration));
+		if let Some(slot) = moment.as_().checked_div(slot_duration) {
+			Self::note_authority_set(slot);
+		}
 	}
 }
 
@@ -255,6 +296,43 @@ impl<T: staking::Trait + Trait> HandleReport for StakingSlasher<T> {
 	}
 }
 
+/// A type for checking Aura seals of headers reported to the consensus module.
+///
+/// `S` is the signature type of the authorities, whose signer is their session key. The
+/// author of a slot is taken from the authority set active in that slot, see
+/// [`Module::authorities_at`](./struct.Module.html#method.authorities_at).
+pub struct SealChecker<T, S>(::rstd::marker::PhantomData<(T, S)>);
+
+impl<T, S> consensus::CheckSlotSeal<T::Header, T::SessionKey> for SealChecker<T, S> where
+	T: Trait,
+	S: Verify<Signer = T::SessionKey> + Decode,
+{
+	fn check_seal(mut header: T::Header) -> result::Result<(u64, T::SessionKey), &'static str> {
+		let seal = header.digest_mut().pop().ok_or("Header is unsealed")?;
+		let (slot, signature) = match seal.as_consensus() {
+			Some((id, seal)) if id == AURA_ENGINE_ID => <(u64, S)>::decode(&mut &seal[..])
+				.ok_or("Aura seal could not be decoded")?,
+			_ => return Err("Header is not sealed by Aura"),
+		};
+
+		let authorities = <Module<T>>::authorities_at(slot)
+			.ok_or("The authorities of the slot of the Aura seal are unknown")?;
+		if authorities.is_empty() {
+			return Err("No authorities to check the Aura seal against");
+		}
+
+		// authorities take turns in the order of the set active in the slot.
+		let author = authorities[(slot % authorities.len() as u64) as usize].clone();
+		let pre_hash = header.hash();
+
+		if signature.verify(&(slot, pre_hash).encode()[..], &author) {
+			Ok((slot, author))
+		} else {
+			Err("Bad Aura seal signature")
+		}
+	}
+}
+
 impl<T: Trait> ProvideInherent for Module<T> {
 	type Call = timestamp::Call<T>;
 	type Error = MakeFatalError<RuntimeString>;
@@ -36,6 +36,8 @@ impl consensus::Trait for Test {
 	type Log = DigestItem;
 	type SessionKey = UintAuthorityId;
 	type InherentOfflineReport = ();
+	type SlotSeal = ();
+	type EquivocationHandler = ();
 }
 
 impl system::Trait for Test {
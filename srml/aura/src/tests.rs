@@ -19,11 +19,15 @@
 #![cfg(test)]
 
 use lazy_static::lazy_static;
-use crate::mock::{System, Aura, new_test_ext};
-use primitives::traits::Header;
+use crate::mock::{System, Aura, Test, new_test_ext};
+use primitives::{generic, testing::{self, UintAuthorityId}};
+use primitives::traits::{Header, Digest, Verify, Lazy};
 use runtime_io::with_externalities;
 use parking_lot::Mutex;
-use crate::{AuraReport, HandleReport};
+use parity_codec::{Encode, Decode};
+use consensus::CheckSlotSeal;
+use substrate_consensus_aura_primitives::AURA_ENGINE_ID;
+use crate::{AuraReport, HandleReport, SealChecker};
 
 #[test]
 fn aura_report_gets_skipped_correctly() {
@@ -90,3 +94,77 @@ fn aura_reports_offline() {
 		assert_eq!(SLASH_COUNTS.lock().as_slice(), &[0, 0, 1, 1]);
 	});
 }
+
+/// A signature which is valid iff it was made by the given authority over the given message.
+#[derive(Encode, Decode)]
+struct TestSignature(u64, Vec<u8>);
+
+impl Verify for TestSignature {
+	type Signer = UintAuthorityId;
+
+	fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &UintAuthorityId) -> bool {
+		self.0 == signer.0 && &self.1[..] == msg.get()
+	}
+}
+
+fn sealed_header(slot: u64, signer: u64) -> testing::Header {
+	let mut header = testing::Header {
+		parent_hash: Default::default(),
+		number: 1,
+		state_root: Default::default(),
+		extrinsics_root: Default::default(),
+		digest: Default::default(),
+	};
+	let to_sign = (slot, header.hash()).encode();
+	let seal = (slot, TestSignature(signer, to_sign)).encode();
+	header.digest_mut().push(generic::DigestItem::Consensus(AURA_ENGINE_ID, seal));
+	header
+}
+
+#[test]
+fn seal_checker_recovers_slot_and_author() {
+	with_externalities(&mut new_test_ext(vec![0, 1, 2, 3]), || {
+		type Checker = SealChecker<Test, TestSignature>;
+
+		assert_eq!(Checker::check_seal(sealed_header(6, 2)), Ok((6, UintAuthorityId(2))));
+
+		// slot 7 belongs to authority 3.
+		assert_eq!(Checker::check_seal(sealed_header(7, 2)), Err("Bad Aura seal signature"));
+
+		let mut unsealed = sealed_header(6, 2);
+		unsealed.digest_mut().pop();
+		assert_eq!(Checker::check_seal(unsealed), Err("Header is unsealed"));
+	});
+}
+
+#[test]
+fn seal_checker_uses_the_authorities_of_the_slot() {
+	use timestamp::OnTimestampSet;
+
+	with_externalities(&mut new_test_ext(vec![0, 1, 2, 3]), || {
+		type Checker = SealChecker<Test, TestSignature>;
+		let slot_duration = Aura::slot_duration();
+
+		<Aura as OnTimestampSet<u64>>::on_timestamp_set(6 * slot_duration);
+		consensus::Module::<Test>::set_authorities(&[UintAuthorityId(4), UintAuthorityId(5)]);
+		<Aura as OnTimestampSet<u64>>::on_timestamp_set(8 * slot_duration);
+		assert_eq!(Aura::authority_sets().len(), 2);
+
+		// slot 7 was authored by the set before the change.
+		assert_eq!(Checker::check_seal(sealed_header(7, 3)), Ok((7, UintAuthorityId(3))));
+		assert_eq!(Checker::check_seal(sealed_header(9, 5)), Ok((9, UintAuthorityId(5))));
+		assert_eq!(
+			Checker::check_seal(sealed_header(5, 1)),
+			Err("The authorities of the slot of the Aura seal are unknown"),
+		);
+
+		// the first set is forgotten once equivocations can't be reported for its slots.
+		consensus::Module::<Test>::set_authorities(&[UintAuthorityId(0), UintAuthorityId(1)]);
+		<Aura as OnTimestampSet<u64>>::on_timestamp_set((9 + consensus::EQUIVOCATION_SLOT_WINDOW) * slot_duration);
+		assert_eq!(Aura::authority_sets().len(), 2);
+		assert_eq!(
+			Checker::check_seal(sealed_header(7, 3)),
+			Err("The authorities of the slot of the Aura seal are unknown"),
+		);
+	});
+}
@@ -21,12 +21,15 @@
 pub use timestamp;
 
 use rstd::{result, prelude::*};
-use srml_support::{decl_storage, decl_module};
-use primitives::traits::As;
+use srml_support::{decl_storage, decl_module, StorageValue};
+use primitives::{generic, traits::{As, Header as HeaderT, Digest as DigestT, DigestItem as DigestItemT}};
 use timestamp::{OnTimestampSet, Trait};
 #[cfg(feature = "std")]
 use timestamp::TimestampInherentData;
-use parity_codec::Decode;
+use parity_codec::{Encode, Decode};
+use babe_primitives::{
+	BABE_ENGINE_ID, AuthorityId, BabePreDigest, ConsensusLog, Epoch, Randomness as EpochRandomness,
+};
 use inherents::{RuntimeString, InherentIdentifier, InherentData, ProvideInherent, MakeFatalError};
 #[cfg(feature = "std")]
 use inherents::{InherentDataProviders, ProvideInherentData};
@@ -107,11 +110,62 @@ decl_storage! {
 	trait Store for Module<T: Trait> as Babe {
 		// The last timestamp.
 		LastTimestamp get(last): T::Moment;
+
+		/// The number of slots of an epoch.
+		pub EpochDuration get(epoch_duration) config(): u64;
+
+		/// Whether slots of the current epoch without a VRF claim may be authored by a
+		/// secondary author.
+		pub SecondarySlots get(secondary_slots) config(): bool;
+
+		/// Whether secondary slots are enabled in the next epoch.
+		pub NextSecondarySlots get(next_secondary_slots) build(|config: &GenesisConfig<T>| {
+			config.secondary_slots
+		}): bool;
+
+		/// Whether secondary slots are enabled in the epoch after the next one, if it changes.
+		PendingSecondarySlots: Option<bool>;
+
+		/// The index of the current epoch.
+		pub EpochIndex get(epoch_index): u64;
+
+		/// The first slot of the current epoch, set by the first block.
+		pub EpochStartSlot get(epoch_start_slot): u64;
+
+		/// The slot of the current block, zero before the first block.
+		pub CurrentSlot get(current_slot): u64;
+
+		/// The authorities of the current epoch.
+		pub Authorities get(authorities) config(): Vec<AuthorityId>;
+
+		/// The authorities of the next epoch.
+		pub NextAuthorities get(next_authorities) build(|config: &GenesisConfig<T>| {
+			config.authorities.clone()
+		}): Vec<AuthorityId>;
+
+		/// The authorities of the epoch after the next one, if they change.
+		PendingAuthorities: Option<Vec<AuthorityId>>;
+
+		/// The randomness of the current epoch.
+		pub Randomness get(randomness): EpochRandomness;
+
+		/// The randomness of the next epoch.
+		pub NextRandomness get(next_randomness): EpochRandomness;
+
+		/// The VRF outputs of the blocks since the last epoch change, which make up the
+		/// randomness of the epoch after the next one.
+		UnderConstruction: Vec<EpochRandomness>;
 	}
 }
 
 decl_module! {
-	pub struct Module<T: Trait> for enum Call where origin: T::Origin { }
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn on_initialize() {
+			if let Some(pre_digest) = Self::pre_digest() {
+				Self::do_initialize(pre_digest);
+			}
+		}
+	}
 }
 
 impl<T: Trait> Module<T> {
@@ -121,6 +175,187 @@ impl<T: Trait> Module<T> {
 		// the majority of their slot.
 		<timestamp::Module<T>>::minimum_period().as_().saturating_mul(2)
 	}
+
+	/// Change the authorities of the epoch after the next one.
+	///
+	/// The next epoch has already been announced, so its authorities can't change anymore.
+	pub fn schedule_change(authorities: Vec<AuthorityId>) {
+		<PendingAuthorities<T>>::put(authorities);
+	}
+
+	/// Enable or disable secondary slots from the epoch after the next one.
+	///
+	/// The next epoch has already been announced, so its setting can't change anymore.
+	pub fn schedule_secondary_slots(enabled: bool) {
+		<PendingSecondarySlots<T>>::put(enabled);
+	}
+
+	/// Whether secondary slots are enabled at `slot`, if it is in the current or the next epoch.
+	fn secondary_slots_at(slot: u64) -> Option<bool> {
+		if Self::current_slot() == 0 {
+			// no epoch has started yet.
+			return None;
+		}
+
+		let duration = Self::epoch_duration();
+		let next_start_slot = Self::epoch_start_slot().saturating_add(duration);
+		if slot < Self::epoch_start_slot() {
+			None
+		} else if slot < next_start_slot {
+			Some(Self::secondary_slots())
+		} else if slot < next_start_slot.saturating_add(duration) {
+			Some(Self::next_secondary_slots())
+		} else {
+			None
+		}
+	}
+
+	fn pre_digest() -> Option<BabePreDigest> {
+		let digest = <system::Module<T>>::digest();
+		let pre_digest = digest.logs().iter()
+			.filter_map(|log| log.as_pre_runtime())
+			.filter(|(id, _)| *id == BABE_ENGINE_ID)
+			.filter_map(|(_, mut data)| BabePreDigest::decode(&mut data))
+			.next();
+		pre_digest
+	}
+
+	fn do_initialize(pre_digest: BabePreDigest) {
+		let slot_number = pre_digest.slot_number();
+		if Self::current_slot() == 0 {
+			// the genesis epoch starts with the first block.
+			<EpochStartSlot<T>>::put(slot_number);
+			Self::announce_next_epoch();
+		} else if slot_number >= Self::epoch_start_slot().saturating_add(Self::epoch_duration()) {
+			Self::enact_epoch_change();
+		}
+
+		if let BabePreDigest::Secondary { .. } = pre_digest {
+			assert!(Self::secondary_slots(), "Secondary blocks are not allowed while secondary slots are disabled");
+		}
+
+		<CurrentSlot<T>>::put(slot_number);
+		// secondary blocks have no VRF output, so only primary blocks contribute to the randomness.
+		if let BabePreDigest::Primary { vrf_output, .. } = pre_digest {
+			<UnderConstruction<T>>::mutate(|outputs| outputs.push(vrf_output));
+		}
+	}
+
+	fn enact_epoch_change() {
+		let epoch_index = <EpochIndex<T>>::mutate(|index| { *index += 1; *index });
+		<EpochStartSlot<T>>::mutate(|slot| *slot = slot.saturating_add(Self::epoch_duration()));
+		<Authorities<T>>::put(Self::next_authorities());
+		if let Some(pending) = <PendingAuthorities<T>>::take() {
+			<NextAuthorities<T>>::put(pending);
+		}
+		<SecondarySlots<T>>::put(Self::next_secondary_slots());
+		if let Some(pending) = <PendingSecondarySlots<T>>::take() {
+			<NextSecondarySlots<T>>::put(pending);
+		}
+
+		let randomness = Self::next_randomness();
+		<Randomness<T>>::put(randomness);
+		// the VRF outputs of the last epoch make up the randomness of the epoch after this one.
+		let outputs = <UnderConstruction<T>>::take();
+		<NextRandomness<T>>::put(compute_randomness(randomness, epoch_index + 1, outputs));
+
+		Self::announce_next_epoch();
+	}
+
+	fn announce_next_epoch() {
+		let duration = Self::epoch_duration();
+		let next = Epoch {
+			epoch_index: Self::epoch_index() + 1,
+			start_slot: Self::epoch_start_slot().saturating_add(duration),
+			duration,
+			authorities: Self::next_authorities(),
+			randomness: Self::next_randomness(),
+			secondary_slots: Self::next_secondary_slots(),
+		};
+		Self::deposit_consensus(ConsensusLog::NextEpochData(next));
+	}
+
+	fn deposit_consensus<U: Encode>(new: U) {
+		// the logs of a runtime are binary compatible with `generic::DigestItem` and
+		// always support consensus digests.
+		let log: generic::DigestItem<(), (), ()> = generic::DigestItem::Consensus(BABE_ENGINE_ID, new.encode());
+		let log = Decode::decode(&mut &log.encode()[..])
+			.expect("runtime logs can decode every consensus digest; qed");
+		<system::Module<T>>::deposit_log(log);
+	}
+}
+
+/// Compute the randomness of an epoch from the randomness of the epoch two epochs
+/// before it and the VRF outputs of the epoch before it.
+fn compute_randomness(
+	last_epoch_randomness: EpochRandomness,
+	epoch_index: u64,
+	vrf_outputs: Vec<EpochRandomness>,
+) -> EpochRandomness {
+	let mut s = Vec::with_capacity(40 + vrf_outputs.len() * babe_primitives::VRF_OUTPUT_LENGTH);
+	s.extend_from_slice(&last_epoch_randomness);
+	s.extend_from_slice(&epoch_index.to_le_bytes());
+	for vrf_output in vrf_outputs {
+		s.extend_from_slice(&vrf_output);
+	}
+	runtime_io::blake2_256(&s)
+}
+
+/// A type for checking BABE seals of headers reported to the consensus module.
+///
+/// Only the signature of the seal is checked; whether the author was entitled to
+/// claim the slot doesn't matter to an equivocation. Secondary seals are rejected
+/// in the current and the next epoch if they disable secondary slots, since such
+/// blocks can never be imported.
+pub struct SealChecker<T>(::rstd::marker::PhantomData<T>);
+
+impl<T> consensus::CheckSlotSeal<T::Header, T::SessionKey> for SealChecker<T> where
+	T: Trait + consensus::Trait,
+	T::SessionKey: From<AuthorityId>,
+{
+	fn check_seal(mut header: T::Header) -> result::Result<(u64, T::SessionKey), &'static str> {
+		let seal_item = header.digest_mut().pop().ok_or("Header is unsealed")?;
+		let mut seal = match seal_item.as_consensus() {
+			Some((id, seal)) if id == BABE_ENGINE_ID => seal,
+			_ => return Err("Header is not sealed by BABE"),
+		};
+
+		let pre_digest = header.digest().logs().iter()
+			.filter_map(|log| log.as_pre_runtime())
+			.filter(|(id, _)| *id == BABE_ENGINE_ID)
+			.filter_map(|(_, mut data)| BabePreDigest::decode(&mut data))
+			.next()
+			.ok_or("Header has no BABE pre-digest")?;
+		let pre_hash = header.hash();
+
+		// the seal layouts of primary and secondary blocks, as produced by the authorship code.
+		let (slot, signer, signature, to_sign) = match pre_digest {
+			BabePreDigest::Primary { .. } => {
+				let (_vrf_output, proof, signature, signer, slot):
+					([u8; 32], [u8; 64], [u8; 64], [u8; 32], u64) = Decode::decode(&mut seal)
+					.ok_or("BABE seal could not be decoded")?;
+				(slot, signer, signature, (slot, pre_hash, proof).encode())
+			}
+			BabePreDigest::Secondary { .. } => {
+				let (signer, signature, slot): ([u8; 32], [u8; 64], u64) = Decode::decode(&mut seal)
+					.ok_or("BABE seal could not be decoded")?;
+				(slot, signer, signature, (slot, pre_hash).encode())
+			}
+		};
+
+		if slot != pre_digest.slot_number() {
+			return Err("BABE seal and pre-digest disagree on the slot");
+		}
+		if !pre_digest.is_primary() && <Module<T>>::secondary_slots_at(slot) == Some(false) {
+			return Err("Secondary slots are disabled");
+		}
+
+		if runtime_io::sr25519_verify(&signature, &to_sign[..], &signer) {
+			Ok((slot, AuthorityId::from_raw(signer).into()))
+		} else {
+			Err("Bad BABE seal signature")
+		}
+	}
 }
 
 impl<T: Trait> OnTimestampSet<T::Moment> for Module<T> {
@@ -153,3 +388,245 @@ impl<T: Trait> ProvideInherent for Module<T> {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use srml_support::impl_outer_origin;
+	use runtime_io::with_externalities;
+	use substrate_primitives::{H256, Blake2Hasher, Pair, sr25519};
+	use primitives::BuildStorage;
+	use primitives::traits::{BlakeTwo256, IdentityLookup, OnInitialize, Header as _};
+	use primitives::testing::{Digest, DigestItem, Header};
+
+	impl_outer_origin! {
+		pub enum Origin for Test {}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+	impl system::Trait for Test {
+		type Origin = Origin;
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type Digest = Digest;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type Log = DigestItem;
+	}
+	impl timestamp::Trait for Test {
+		type Moment = u64;
+		type OnTimestampSet = Babe;
+	}
+	impl consensus::Trait for Test {
+		type Log = DigestItem;
+		type SessionKey = AuthorityId;
+		type InherentOfflineReport = ();
+		type SlotSeal = SealChecker<Test>;
+		type EquivocationHandler = ();
+	}
+	type System = system::Module<Test>;
+	type Babe = Module<Test>;
+
+	fn authorities() -> Vec<AuthorityId> {
+		vec![AuthorityId::from_raw([1; 32]), AuthorityId::from_raw([2; 32])]
+	}
+
+	fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+		new_test_ext_with(true)
+	}
+
+	fn new_test_ext_with(secondary_slots: bool) -> runtime_io::TestExternalities<Blake2Hasher> {
+		let mut t = system::GenesisConfig::<Test>::default().build_storage().unwrap().0;
+		t.extend(GenesisConfig::<Test> {
+			epoch_duration: 3,
+			secondary_slots,
+			authorities: authorities(),
+		}.build_storage().unwrap().0);
+		t.into()
+	}
+
+	/// Execute a block claimed at `slot_number`, returning the consensus digests of BABE.
+	fn execute_block(number: u64, slot_number: u64, vrf_output: EpochRandomness) -> Vec<ConsensusLog> {
+		execute_block_with(number, BabePreDigest::Primary { vrf_output, slot_number })
+	}
+
+	fn execute_block_with(number: u64, pre_digest: BabePreDigest) -> Vec<ConsensusLog> {
+		System::initialize(&number, &Default::default(), &Default::default());
+		System::deposit_log(DigestItem::PreRuntime(BABE_ENGINE_ID, pre_digest.encode()));
+		Babe::on_initialize(number);
+
+		System::finalize().digest.logs.into_iter()
+			.filter_map(|log| match log {
+				DigestItem::Consensus(BABE_ENGINE_ID, data) => ConsensusLog::decode(&mut &data[..]),
+				_ => None,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn first_block_announces_next_epoch() {
+		with_externalities(&mut new_test_ext(), || {
+			assert_eq!(execute_block(1, 10, [1; 32]), vec![ConsensusLog::NextEpochData(Epoch {
+				epoch_index: 1,
+				start_slot: 13,
+				duration: 3,
+				authorities: authorities(),
+				randomness: [0; 32],
+				secondary_slots: true,
+			})]);
+			assert_eq!(Babe::epoch_start_slot(), 10);
+			assert_eq!(Babe::current_slot(), 10);
+
+			assert_eq!(execute_block(2, 12, [2; 32]), vec![]);
+			assert_eq!(Babe::epoch_index(), 0);
+		});
+	}
+
+	#[test]
+	fn epoch_change_uses_vrf_outputs_of_previous_epoch() {
+		with_externalities(&mut new_test_ext(), || {
+			let next_authorities = vec![AuthorityId::from_raw([3; 32])];
+
+			execute_block(1, 10, [1; 32]);
+			execute_block(2, 11, [2; 32]);
+			execute_block_with(3, BabePreDigest::Secondary { slot_number: 12 });
+			Babe::schedule_change(next_authorities.clone());
+
+			// the secondary block doesn't contribute to the randomness.
+			let randomness = compute_randomness([0; 32], 2, vec![[1; 32], [2; 32]]);
+			assert_eq!(execute_block(4, 14, [3; 32]), vec![ConsensusLog::NextEpochData(Epoch {
+				epoch_index: 2,
+				start_slot: 16,
+				duration: 3,
+				authorities: next_authorities.clone(),
+				randomness,
+				secondary_slots: true,
+			})]);
+			assert_eq!(Babe::epoch_index(), 1);
+			assert_eq!(Babe::epoch_start_slot(), 13);
+			assert_eq!(Babe::authorities(), authorities());
+			assert_eq!(Babe::next_authorities(), next_authorities);
+			assert_eq!(Babe::next_randomness(), randomness);
+
+			// epochs without blocks are not skipped.
+			execute_block(5, 30, [4; 32]);
+			assert_eq!(Babe::epoch_index(), 2);
+			assert_eq!(Babe::epoch_start_slot(), 16);
+			assert_eq!(Babe::authorities(), next_authorities);
+			assert_eq!(Babe::randomness(), randomness);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Secondary blocks are not allowed while secondary slots are disabled")]
+	fn secondary_blocks_are_rejected_when_disabled() {
+		with_externalities(&mut new_test_ext_with(false), || {
+			execute_block(1, 10, [1; 32]);
+			execute_block_with(2, BabePreDigest::Secondary { slot_number: 11 });
+		});
+	}
+
+	#[test]
+	fn secondary_slots_setting_changes_with_the_epochs() {
+		with_externalities(&mut new_test_ext(), || {
+			execute_block(1, 10, [1; 32]);
+			Babe::schedule_secondary_slots(false);
+
+			// the setting applies from the epoch after the next one.
+			assert_eq!(execute_block(2, 13, [2; 32]), vec![ConsensusLog::NextEpochData(Epoch {
+				epoch_index: 2,
+				start_slot: 16,
+				duration: 3,
+				authorities: authorities(),
+				randomness: compute_randomness([0; 32], 2, vec![[1; 32]]),
+				secondary_slots: false,
+			})]);
+			execute_block_with(3, BabePreDigest::Secondary { slot_number: 14 });
+			assert!(Babe::secondary_slots());
+
+			execute_block(4, 16, [3; 32]);
+			assert!(!Babe::secondary_slots());
+			assert!(!Babe::next_secondary_slots());
+		});
+	}
+
+	fn sealed_header(pre_digest: BabePreDigest, seal: impl FnOnce(H256) -> Vec<u8>) -> Header {
+		let mut header = Header {
+			parent_hash: Default::default(),
+			number: 1,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Digest {
+				logs: vec![DigestItem::PreRuntime(BABE_ENGINE_ID, pre_digest.encode())],
+			},
+		};
+		let seal = seal(header.hash());
+		header.digest_mut().push(DigestItem::Consensus(BABE_ENGINE_ID, seal));
+		header
+	}
+
+	#[test]
+	fn seal_checker_recovers_slot_and_author() {
+		use consensus::CheckSlotSeal;
+
+		with_externalities(&mut new_test_ext(), || {
+			let pair = sr25519::Pair::from_seed([1; 32]);
+			let public = pair.public();
+
+			let secondary = sealed_header(BabePreDigest::Secondary { slot_number: 7 }, |pre_hash| {
+				let signature = pair.sign(&(7u64, pre_hash).encode()[..]);
+				(public.clone(), signature, 7u64).encode()
+			});
+			assert_eq!(SealChecker::<Test>::check_seal(secondary), Ok((7, public.clone())));
+
+			let primary = sealed_header(
+				BabePreDigest::Primary { vrf_output: [1; 32], slot_number: 8 },
+				|pre_hash| {
+					let proof = [2u8; 64];
+					let signature = pair.sign(&(8u64, pre_hash, proof).encode()[..]);
+					([1u8; 32], proof, signature.0, public.0, 8u64).encode()
+				},
+			);
+			assert_eq!(SealChecker::<Test>::check_seal(primary), Ok((8, public.clone())));
+
+			// the signature has to cover the header.
+			let forged = sealed_header(BabePreDigest::Secondary { slot_number: 7 }, |_| {
+				let signature = pair.sign(&(7u64, H256::default()).encode()[..]);
+				(public.clone(), signature, 7u64).encode()
+			});
+			assert_eq!(SealChecker::<Test>::check_seal(forged), Err("Bad BABE seal signature"));
+
+			// and the slot of the pre-digest.
+			let mismatched = sealed_header(BabePreDigest::Secondary { slot_number: 6 }, |pre_hash| {
+				let signature = pair.sign(&(7u64, pre_hash).encode()[..]);
+				(public.clone(), signature, 7u64).encode()
+			});
+			assert_eq!(
+				SealChecker::<Test>::check_seal(mismatched),
+				Err("BABE seal and pre-digest disagree on the slot"),
+			);
+		});
+
+		with_externalities(&mut new_test_ext_with(false), || {
+			let pair = sr25519::Pair::from_seed([1; 32]);
+			let public = pair.public();
+			let secondary = |slot: u64| sealed_header(BabePreDigest::Secondary { slot_number: slot }, |pre_hash| {
+				let signature = pair.sign(&(slot, pre_hash).encode()[..]);
+				(public.clone(), signature, slot).encode()
+			});
+			execute_block(1, 10, [1; 32]);
+
+			// secondary slots are disabled in the current and the next epoch.
+			assert_eq!(SealChecker::<Test>::check_seal(secondary(11)), Err("Secondary slots are disabled"));
+			assert_eq!(SealChecker::<Test>::check_seal(secondary(14)), Err("Secondary slots are disabled"));
+			// the setting of other epochs is unknown.
+			assert_eq!(SealChecker::<Test>::check_seal(secondary(7)), Ok((7, public.clone())));
+		});
+	}
+}
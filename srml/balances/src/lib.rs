@@ -72,7 +72,10 @@
 //! imbalances between total issuance in the system and account balances. Must be used when a function
 //! creates new funds (e.g. a reward) or destroys some funds (e.g. a system fee).
 //! - [`MakePayment`](../srml_support/traits/trait.MakePayment.html): Simple trait designed
-//! for hooking into a transaction payment.
+//! for hooking into a transaction payment. The fee is the sum of a base fee, a fee per byte and a
+//! fee for the dispatch weight, scaled by a multiplier that rises after the blocks that are fuller
+//! than a quarter of the weight limit and falls (but not below one) after the emptier ones. The tip
+//! of the transaction is added on top, unscaled.
 //! - [`IsDeadAccount`](../srml_system/trait.IsDeadAccount.html): Determiner to say whether a
 //! given account is unused.
 //!
@@ -86,6 +89,7 @@
 //! ### Public Functions
 //!
 //! - `vesting_balance` - Get the amount that is currently being vested and cannot be transferred out of this account.
+//! - `compute_fee` - Get the fee of a transaction of the given length, dispatch weight and tip.
 //!
 //! ## Usage
 //!
@@ -145,7 +149,7 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use rstd::prelude::*;
-use rstd::{cmp, result};
+use rstd::{cmp, result, marker::PhantomData};
 use parity_codec::{Codec, Encode, Decode};
 use srml_support::{StorageValue, StorageMap, Parameter, decl_event, decl_storage, decl_module};
 use srml_support::traits::{
@@ -153,18 +157,38 @@ use srml_support::traits::{
 	WithdrawReason, WithdrawReasons, LockIdentifier, LockableCurrency, ExistenceRequirement,
 	Imbalance, SignedImbalance, ReservableCurrency
 };
-use srml_support::dispatch::Result;
+use srml_support::dispatch::{Result, Weight};
 use primitives::traits::{
 	Zero, SimpleArithmetic, As, StaticLookup, Member, CheckedAdd, CheckedSub,
-	MaybeSerializeDebug, Saturating
+	MaybeSerializeDebug, Saturating, Convert
 };
 use system::{IsDeadAccount, OnNewAccount, ensure_signed};
+#[cfg(feature = "runtime-benchmarks")]
+use srml_benchmarking::{decl_benchmarks, account};
 
 mod mock;
 mod tests;
 
 pub use self::imbalances::{PositiveImbalance, NegativeImbalance};
 
+/// The fee multiplier that leaves the fees unchanged, in billionths.
+pub const FEE_MULTIPLIER_ONE: u64 = 1_000_000_000;
+
+/// The highest fee multiplier, in billionths: the fees grow at most a hundredfold.
+pub const MAX_FEE_MULTIPLIER: u64 = 100 * FEE_MULTIPLIER_ONE;
+
+/// The fullness of the blocks the fee multiplier aims for, in billionths of the weight limit.
+const TARGET_BLOCK_FULLNESS: u64 = 250_000_000;
+
+/// How fast the fee multiplier follows the fullness of the blocks, in billionths.
+///
+/// After every block the multiplier changes by this fraction of the difference between the
+/// fullness of the block and the target, e.g. by 3% after a full block.
+const FEE_ADJUSTMENT: u64 = 40_000_000;
+
+/// The units of dispatch weight charged like a byte of the transaction by `WeightToByteFee`.
+pub const WEIGHT_PER_BYTE: Weight = 1_000;
+
 pub trait Subtrait<I: Instance = DefaultInstance>: system::Trait {
 	/// The balance of an account.
 	type Balance: Parameter + Member + SimpleArithmetic + Codec + Default + Copy + MaybeSerializeDebug;
@@ -202,6 +226,10 @@ pub trait Trait<I: Instance = DefaultInstance>: system::Trait {
 	/// Handler for the unbalanced reduction when removing a dust account.
 	type DustRemoval: OnUnbalanced<NegativeImbalance<Self, I>>;
 
+	/// Conversion of the dispatch weight of a transaction into the weight portion of its fee.
+	/// `()` makes the fee independent of the weight.
+	type WeightToFee: Convert<Weight, Self::Balance>;
+
 	/// The overarching event type.
 	type Event: From<Event<Self, I>> + Into<<Self as system::Trait>::Event>;
 }
@@ -212,6 +240,17 @@ impl<T: Trait<I>, I: Instance> Subtrait<I> for T {
 	type OnNewAccount = T::OnNewAccount;
 }
 
+/// A `WeightToFee` which charges every `WEIGHT_PER_BYTE` units of weight like a byte of the
+/// transaction, i.e. the `TransactionByteFee`.
+pub struct WeightToByteFee<T, I: Instance = DefaultInstance>(PhantomData<(T, I)>);
+
+impl<T: Trait<I>, I: Instance> Convert<Weight, T::Balance> for WeightToByteFee<T, I> {
+	fn convert(weight: Weight) -> T::Balance {
+		let bytes = <T::Balance as As<u64>>::sa((weight / WEIGHT_PER_BYTE) as u64);
+		<Module<T, I>>::transaction_byte_fee().saturating_mul(bytes)
+	}
+}
+
 decl_event!(
 	pub enum Event<T, I: Instance = DefaultInstance> where
 		<T as system::Trait>::AccountId,
@@ -272,6 +311,9 @@ decl_storage! {
 		pub TransactionBaseFee get(transaction_base_fee) config(): T::Balance;
 		/// The fee to be paid for making a transaction; the per-byte portion.
 		pub TransactionByteFee get(transaction_byte_fee) config(): T::Balance;
+		/// The multiplier of the transaction fees, in billionths. It follows the fullness of the
+		/// blocks and stays between `FEE_MULTIPLIER_ONE` and `MAX_FEE_MULTIPLIER`.
+		pub FeeMultiplier get(fee_multiplier): u64 = FEE_MULTIPLIER_ONE;
 
 		/// Information regarding the vesting of a given account.
 		pub Vesting get(vesting) build(|config: &GenesisConfig<T, I>| {
@@ -335,6 +377,17 @@ decl_module! {
 	pub struct Module<T: Trait<I>, I: Instance = DefaultInstance> for enum Call where origin: T::Origin {
 		fn deposit_event<T, I>() = default;
 
+		fn on_finalize() {
+			let limit = system::MAXIMUM_BLOCK_WEIGHT as u64;
+			let weight = <system::Module<T>>::all_extrinsics_weight() as u64;
+			let fullness = weight.min(limit) * FEE_MULTIPLIER_ONE / limit;
+			let multiplier = Self::fee_multiplier();
+			let next = Self::next_fee_multiplier(multiplier, fullness);
+			if next != multiplier {
+				<FeeMultiplier<T, I>>::put(next);
+			}
+		}
+
 		/// Transfer some liquid free balance to another account.
 		///
 		/// `transfer` will set the `FreeBalance` of the sender and receiver.
@@ -373,10 +426,77 @@ decl_module! {
 	}
 }
 
+#[cfg(feature = "runtime-benchmarks")]
+decl_benchmarks! {
+	impl<T: Trait<I>, I: Instance> for Module<T, I> as balances {
+		/// Transfer to a new account if `existing` is 0, to an existing one otherwise.
+		fn transfer(existing in 0 ..= 1) {
+			let sender = account::<T::AccountId>("sender", 0);
+			let recipient = account::<T::AccountId>("recipient", 0);
+			let value = Self::existential_deposit() + T::Balance::sa(1_000);
+			let fees = Self::transfer_fee() + Self::creation_fee();
+			<Self as Currency<_>>::make_free_balance_be(&sender, value + value + fees);
+			if existing == 1 {
+				<Self as Currency<_>>::make_free_balance_be(&recipient, value);
+			}
+			Ok((Some(sender), Call::transfer(T::Lookup::unlookup(recipient), value)))
+		}
+	}
+}
+
 impl<T: Trait<I>, I: Instance> Module<T, I> {
 
 	// PUBLIC IMMUTABLES
 
+	/// The fee of a transaction of `encoded_len` bytes and of the given dispatch `weight`, with the
+	/// current fee multiplier applied, plus the `tip` of the sender.
+	pub fn compute_fee(encoded_len: usize, weight: Weight, tip: T::Balance) -> T::Balance {
+		let encoded_len = <T::Balance as As<u64>>::sa(encoded_len as u64);
+		let fee = Self::transaction_base_fee()
+			.saturating_add(Self::transaction_byte_fee().saturating_mul(encoded_len))
+			.saturating_add(T::WeightToFee::convert(weight));
+		Self::apply_fee_multiplier(fee, Self::fee_multiplier()).saturating_add(tip)
+	}
+
+	/// The balance of a `tip` given in the extrinsic, saturating at the maximum balance.
+	pub fn tip_to_balance(tip: u128) -> T::Balance {
+		let high = <T::Balance as As<u64>>::sa((tip >> 64) as u64);
+		let low = <T::Balance as As<u64>>::sa(tip as u64);
+		let shift = <T::Balance as As<u64>>::sa(1 << 32);
+		high.saturating_mul(shift).saturating_mul(shift).saturating_add(low)
+	}
+
+	/// `fee` times `multiplier` (in billionths), saturating at the maximum balance.
+	pub fn apply_fee_multiplier(fee: T::Balance, multiplier: u64) -> T::Balance {
+		let one = <T::Balance as As<u64>>::sa(FEE_MULTIPLIER_ONE);
+		let whole = <T::Balance as As<u64>>::sa(multiplier / FEE_MULTIPLIER_ONE);
+		let fraction = <T::Balance as As<u64>>::sa(multiplier % FEE_MULTIPLIER_ONE);
+
+		// `fee * whole + fee * fraction / one`, with the fee split at `one` so that the
+		// product with the fraction doesn't overflow.
+		fee.saturating_mul(whole)
+			.saturating_add((fee / one).saturating_mul(fraction))
+			.saturating_add((fee % one).saturating_mul(fraction) / one)
+	}
+
+	/// The fee multiplier following `multiplier` after a block of the given `fullness` (in
+	/// billionths of the weight limit).
+	pub fn next_fee_multiplier(multiplier: u64, fullness: u64) -> u64 {
+		let diff = if fullness > TARGET_BLOCK_FULLNESS {
+			fullness - TARGET_BLOCK_FULLNESS
+		} else {
+			TARGET_BLOCK_FULLNESS - fullness
+		};
+		let one = FEE_MULTIPLIER_ONE as u128;
+		let change = multiplier as u128 * diff as u128 * FEE_ADJUSTMENT as u128 / (one * one);
+		let change = change.min(u64::max_value() as u128) as u64;
+		if fullness > TARGET_BLOCK_FULLNESS {
+			multiplier.saturating_add(change).min(MAX_FEE_MULTIPLIER)
+		} else {
+			multiplier.saturating_sub(change).max(FEE_MULTIPLIER_ONE)
+		}
+	}
+
 	/// Get the amount that is currently being vested and cannot be transferred out of this account.
 	pub fn vesting_balance(who: &T::AccountId) -> T::Balance {
 		if let Some(v) = Self::vesting(who) {
@@ -666,6 +786,7 @@ impl<T: Subtrait<I>, I: Instance> Trait<I> for ElevatedTrait<T, I> {
 	type TransactionPayment = ();
 	type TransferPayment = ();
 	type DustRemoval = ();
+	type WeightToFee = ();
 }
 
 impl<T: Trait<I>, I: Instance> Currency<T::AccountId> for Module<T, I>
@@ -1012,9 +1133,8 @@ where
 }
 
 impl<T: Trait<I>, I: Instance> MakePayment<T::AccountId> for Module<T, I> {
-	fn make_payment(transactor: &T::AccountId, encoded_len: usize) -> Result {
-		let encoded_len = <T::Balance as As<u64>>::sa(encoded_len as u64);
-		let transaction_fee = Self::transaction_base_fee() + Self::transaction_byte_fee() * encoded_len;
+	fn make_payment(transactor: &T::AccountId, encoded_len: usize, weight: Weight, tip: u128) -> Result {
+		let transaction_fee = Self::compute_fee(encoded_len, weight, Self::tip_to_balance(tip));
 		let imbalance = Self::withdraw(
 			transactor,
 			transaction_fee,
@@ -19,10 +19,10 @@
 #![cfg(test)]
 
 use primitives::BuildStorage;
-use primitives::{traits::{IdentityLookup}, testing::{Digest, DigestItem, Header}};
+use primitives::{traits::{IdentityLookup, Convert}, testing::{Digest, DigestItem, Header}};
 use substrate_primitives::{H256, Blake2Hasher};
 use runtime_io;
-use srml_support::impl_outer_origin;
+use srml_support::{impl_outer_origin, dispatch::Weight};
 use crate::{GenesisConfig, Module, Trait};
 
 impl_outer_origin!{
@@ -52,9 +52,18 @@ impl Trait for Runtime {
 	type Event = ();
 	type TransactionPayment = ();
 	type DustRemoval = ();
+	type WeightToFee = WeightToFee;
 	type TransferPayment = ();
 }
 
+/// Charges one unit of balance per unit of weight.
+pub struct WeightToFee;
+impl Convert<Weight, u64> for WeightToFee {
+	fn convert(weight: Weight) -> u64 {
+		weight as u64
+	}
+}
+
 pub struct ExtBuilder {
 	transaction_base_fee: u64,
 	transaction_byte_fee: u64,
@@ -21,6 +21,7 @@
 use super::*;
 use mock::{Balances, ExtBuilder, Runtime, System};
 use runtime_io::with_externalities;
+use primitives::traits::OnFinalize;
 use srml_support::{
 	assert_noop, assert_ok, assert_err,
 	traits::{LockableCurrency, LockIdentifier, WithdrawReason, WithdrawReasons,
@@ -103,20 +104,115 @@ fn lock_reasons_should_work() {
 		Balances::set_lock(ID_1, &1, 10, u64::max_value(), WithdrawReason::Transfer.into());
 		assert_noop!(<Balances as Currency<_>>::transfer(&1, &2, 1), "account liquidity restrictions prevent withdrawal");
 		assert_ok!(<Balances as ReservableCurrency<_>>::reserve(&1, 1));
-		assert_ok!(<Balances as MakePayment<_>>::make_payment(&1, 1));
+		assert_ok!(<Balances as MakePayment<_>>::make_payment(&1, 1, 0, 0));
 
 		Balances::set_lock(ID_1, &1, 10, u64::max_value(), WithdrawReason::Reserve.into());
 		assert_ok!(<Balances as Currency<_>>::transfer(&1, &2, 1));
 		assert_noop!(<Balances as ReservableCurrency<_>>::reserve(&1, 1), "account liquidity restrictions prevent withdrawal");
-		assert_ok!(<Balances as MakePayment<_>>::make_payment(&1, 1));
+		assert_ok!(<Balances as MakePayment<_>>::make_payment(&1, 1, 0, 0));
 
 		Balances::set_lock(ID_1, &1, 10, u64::max_value(), WithdrawReason::TransactionPayment.into());
 		assert_ok!(<Balances as Currency<_>>::transfer(&1, &2, 1));
 		assert_ok!(<Balances as ReservableCurrency<_>>::reserve(&1, 1));
-		assert_noop!(<Balances as MakePayment<_>>::make_payment(&1, 1), "account liquidity restrictions prevent withdrawal");
+		assert_noop!(<Balances as MakePayment<_>>::make_payment(&1, 1, 0, 0), "account liquidity restrictions prevent withdrawal");
 	});
 }
 
+#[test]
+fn transaction_fee_includes_weight() {
+	with_externalities(&mut ExtBuilder::default().monied(true).transaction_fees(2, 1).build(), || {
+		assert_ok!(<Balances as MakePayment<_>>::make_payment(&1, 3, 0, 0));
+		assert_eq!(Balances::free_balance(&1), 10 - 2 - 3);
+		assert_ok!(<Balances as MakePayment<_>>::make_payment(&1, 0, 2, 0));
+		assert_eq!(Balances::free_balance(&1), 5 - 2 - 2);
+		assert_noop!(
+			<Balances as MakePayment<_>>::make_payment(&1, 0, 1, 0),
+			"too few free funds in account"
+		);
+	});
+}
+
+#[test]
+fn weight_is_charged_like_bytes() {
+	with_externalities(&mut ExtBuilder::default().transaction_fees(2, 3).build(), || {
+		let convert = <WeightToByteFee<Runtime> as Convert<Weight, u64>>::convert;
+		assert_eq!(convert(0), 0);
+		assert_eq!(convert(WEIGHT_PER_BYTE - 1), 0);
+		assert_eq!(convert(10 * WEIGHT_PER_BYTE), 30);
+	});
+}
+
+#[test]
+fn fee_multiplier_follows_block_fullness() {
+	let one = FEE_MULTIPLIER_ONE;
+	assert_eq!(Balances::next_fee_multiplier(one, TARGET_BLOCK_FULLNESS), one);
+	assert_eq!(Balances::next_fee_multiplier(one, one), 1_030_000_000);
+	assert_eq!(Balances::next_fee_multiplier(2 * one, 0), 2 * one - 20_000_000);
+	assert_eq!(Balances::next_fee_multiplier(one + 1_000, 0), one);
+	assert_eq!(Balances::next_fee_multiplier(MAX_FEE_MULTIPLIER - 1, one), MAX_FEE_MULTIPLIER);
+	assert_eq!(Balances::next_fee_multiplier(u64::max_value(), one), MAX_FEE_MULTIPLIER);
+}
+
+#[test]
+fn fee_multiplier_saturates_large_fees() {
+	let one = FEE_MULTIPLIER_ONE;
+	assert_eq!(Balances::apply_fee_multiplier(102, one), 102);
+	assert_eq!(Balances::apply_fee_multiplier(102, one + one / 2), 153);
+	assert_eq!(Balances::apply_fee_multiplier(3 * one + 7, 2 * one + 1), 6 * one + 14 + 3);
+	assert_eq!(Balances::apply_fee_multiplier(u64::max_value() / 3, 3 * one), u64::max_value());
+	assert_eq!(Balances::apply_fee_multiplier(u64::max_value() / 2, MAX_FEE_MULTIPLIER), u64::max_value());
+
+	with_externalities(&mut ExtBuilder::default().transaction_fees(u64::max_value() / 2, u64::max_value() / 2).build(), || {
+		assert_eq!(Balances::compute_fee(3, 0, 0), u64::max_value());
+	});
+}
+
+#[test]
+fn fee_multiplier_is_updated_on_finalize() {
+	with_externalities(&mut ExtBuilder::default().transaction_fees(100, 1).build(), || {
+		assert_eq!(Balances::compute_fee(2, 0, 0), 102);
+
+		System::note_applied_extrinsic(&Ok(()), 0, system::MAXIMUM_BLOCK_WEIGHT);
+		<Balances as OnFinalize<u64>>::on_finalize(1);
+		assert_eq!(Balances::fee_multiplier(), 1_030_000_000);
+		assert_eq!(Balances::compute_fee(2, 0, 0), 105);
+
+		// empty blocks bring the multiplier back, but not below one.
+		for _ in 0..200 {
+			<Balances as OnFinalize<u64>>::on_finalize(1);
+		}
+		assert_eq!(Balances::fee_multiplier(), FEE_MULTIPLIER_ONE);
+		assert_eq!(Balances::compute_fee(2, 0, 0), 102);
+	});
+}
+
+#[test]
+fn tip_is_charged_on_top_of_the_multiplied_fee() {
+	with_externalities(&mut ExtBuilder::default().monied(true).transaction_fees(100, 1).build(), || {
+		<FeeMultiplier<Runtime>>::put(2 * FEE_MULTIPLIER_ONE);
+		assert_eq!(Balances::compute_fee(2, 0, 0), 204);
+		assert_eq!(Balances::compute_fee(2, 0, 3), 207);
+		assert_eq!(Balances::compute_fee(2, 0, u64::max_value()), u64::max_value());
+	});
+	with_externalities(&mut ExtBuilder::default().monied(true).transaction_fees(2, 1).build(), || {
+		assert_ok!(<Balances as MakePayment<_>>::make_payment(&1, 3, 0, 4));
+		assert_eq!(Balances::free_balance(&1), 10 - 2 - 3 - 4);
+		assert_noop!(
+			<Balances as MakePayment<_>>::make_payment(&1, 0, 0, 1 << 64),
+			"too few free funds in account"
+		);
+	});
+}
+
+#[test]
+fn tip_conversion_saturates() {
+	assert_eq!(Balances::tip_to_balance(0), 0);
+	assert_eq!(Balances::tip_to_balance(42), 42);
+	assert_eq!(Balances::tip_to_balance(u64::max_value() as u128), u64::max_value());
+	assert_eq!(Balances::tip_to_balance(1 << 64), u64::max_value());
+	assert_eq!(Balances::tip_to_balance(u128::max_value()), u64::max_value());
+}
+
 #[test]
 fn lock_block_number_should_work() {
 	with_externalities(&mut ExtBuilder::default().existential_deposit(1).monied(true).build(), || {
@@ -618,3 +714,22 @@ fn extra_balance_should_transfer() {
 		}
 	);
 }
+
+#[test]
+#[cfg(feature = "runtime-benchmarks")]
+fn transfer_benchmark_can_be_dispatched() {
+	use srml_benchmarking::Benchmarking;
+	use srml_support::dispatch::Dispatchable;
+
+	for existing in 0..2 {
+		with_externalities(&mut ExtBuilder::default().existential_deposit(1).build(), || {
+			let (sender, call) = Balances::setup_benchmark(b"transfer", &[existing]).unwrap();
+			let recipient = srml_benchmarking::account::<u64>("recipient", 0);
+			let balance_before = Balances::free_balance(&recipient);
+			assert_eq!(balance_before > 0, existing == 1);
+
+			assert_ok!(call.dispatch(sender.into()));
+			assert_eq!(Balances::free_balance(&recipient), balance_before + 1_001);
+		});
+	}
+}
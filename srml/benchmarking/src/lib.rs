@@ -0,0 +1,204 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Benchmarking of the dispatchable calls of the runtime modules.
+//!
+//! A module declares benchmark cases for its calls with `decl_benchmarks!`. Every case has
+//! a number of parameters, each with a range of values, and prepares the state the call is
+//! then dispatched on, e.g. a case of `staking::nominate` bonds an account and returns the
+//! call nominating `n` validators.
+//!
+//! The runtime exposes the cases of all its modules through the `Benchmark` API. The node
+//! runs the preparation and the dispatch as separate calls, so that only the dispatch is
+//! measured, and fits a linear model of the execution time and the storage accesses in the
+//! parameters of every case.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use parity_codec::{Encode, Decode};
+use client::decl_runtime_apis;
+use rstd::prelude::*;
+
+#[doc(hidden)]
+pub use rstd;
+
+/// Name of the `Benchmark::benchmark_cases` function exported by the runtime.
+pub const BENCHMARK_CASES_CALL: &str = "Benchmark_benchmark_cases";
+/// Name of the `Benchmark::setup_benchmark` function exported by the runtime.
+pub const SETUP_BENCHMARK_CALL: &str = "Benchmark_setup_benchmark";
+/// Name of the `Benchmark::dispatch_benchmark` function exported by the runtime.
+pub const DISPATCH_BENCHMARK_CALL: &str = "Benchmark_dispatch_benchmark";
+
+/// A parameter of a benchmark case and the inclusive range of its values.
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BenchmarkParameter {
+	/// Name of the parameter.
+	pub name: Vec<u8>,
+	/// The lowest value of the parameter.
+	pub min: u32,
+	/// The highest value of the parameter.
+	pub max: u32,
+}
+
+/// A benchmark case of a dispatchable call.
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BenchmarkCase {
+	/// Name of the module declaring the case.
+	pub module: Vec<u8>,
+	/// Name of the case, usually the name of the benchmarked call.
+	pub name: Vec<u8>,
+	/// Parameters of the case, in the order `setup_benchmark` expects their values in.
+	pub params: Vec<BenchmarkParameter>,
+}
+
+decl_runtime_apis! {
+	/// The API to benchmark the dispatchable calls of the runtime modules.
+	///
+	/// Only meant for the benchmarking of the node: `setup_benchmark` changes the state
+	/// arbitrarily and `dispatch_benchmark` dispatches calls with any origin.
+	pub trait Benchmark {
+		/// The benchmark cases declared by the modules of the runtime.
+		fn benchmark_cases() -> Vec<BenchmarkCase>;
+
+		/// Prepare the state for the case `case` of module `module` with the given values of
+		/// the parameters. Returns the encoded call to pass to `dispatch_benchmark` or the
+		/// reason of the failure.
+		fn setup_benchmark(module: Vec<u8>, case: Vec<u8>, params: Vec<u32>) -> Result<Vec<u8>, Vec<u8>>;
+
+		/// Dispatch a call returned by `setup_benchmark`.
+		fn dispatch_benchmark(call: Vec<u8>) -> Result<(), Vec<u8>>;
+	}
+}
+
+/// The benchmark cases of the calls of a module. Implemented by `decl_benchmarks!`.
+pub trait Benchmarking<AccountId, Call> {
+	/// The benchmark cases of the module.
+	fn benchmark_cases() -> Vec<BenchmarkCase>;
+
+	/// Prepare the state for the case `case` with the given values of the parameters.
+	///
+	/// Returns the account that signs the call, or `None` if the call is dispatched by root,
+	/// and the call.
+	fn setup_benchmark(case: &[u8], params: &[u32]) -> Result<(Option<AccountId>, Call), &'static str>;
+}
+
+/// An account for the preparation of the benchmarks, different for every `name` and `index`.
+pub fn account<AccountId: Decode + Default>(name: &str, index: u32) -> AccountId {
+	let entropy = (name, index).using_encoded(runtime_io::blake2_256);
+	AccountId::decode(&mut &entropy[..]).unwrap_or_default()
+}
+
+#[doc(hidden)]
+pub fn in_range(value: u32, min: u32, max: u32) -> bool {
+	min <= value && value <= max
+}
+
+/// Declare the benchmark cases of the calls of a module.
+///
+/// Every case names its parameters with the inclusive ranges of their values. The body of
+/// the case prepares the state and evaluates to the signer of the call (`None` for root)
+/// and the call, or to the reason of the failure:
+///
+/// ```nocompile
+/// decl_benchmarks! {
+/// 	impl<T: Trait> for Module<T> as staking {
+/// 		/// Nominate `n` validators.
+/// 		fn nominate(n in 1 ..= 16) {
+/// 			let controller = bond::<T>()?;
+/// 			let targets = (0..n).map(|i| T::Lookup::unlookup(account("validator", i))).collect();
+/// 			Ok((Some(controller), Call::nominate(targets)))
+/// 		}
+/// 	}
+/// }
+/// ```
+///
+/// The name after `as` is the name of the module the runtime knows the cases by.
+#[macro_export]
+macro_rules! decl_benchmarks {
+	(
+		impl<$trait_instance:ident: $trait_name:path $(, $generic:ident: $generic_bound:path)*>
+			for $module:ident<$($module_arg:ident),*> as $module_name:ident
+		{
+			$(
+				$(#[doc = $doc_attr:tt])*
+				fn $case:ident($($param:ident in $min:tt ..= $max:tt),*) $body:block
+			)*
+		}
+	) => {
+		impl<$trait_instance: $trait_name $(, $generic: $generic_bound)*>
+			$crate::Benchmarking<$trait_instance::AccountId, Call<$($module_arg),*>>
+			for $module<$($module_arg),*>
+		{
+			fn benchmark_cases() -> $crate::rstd::vec::Vec<$crate::BenchmarkCase> {
+				[$(
+					$crate::BenchmarkCase {
+						module: stringify!($module_name).as_bytes().to_vec(),
+						name: stringify!($case).as_bytes().to_vec(),
+						params: [$(
+							$crate::BenchmarkParameter {
+								name: stringify!($param).as_bytes().to_vec(),
+								min: $min,
+								max: $max,
+							}
+						),*].to_vec(),
+					}
+				),*].to_vec()
+			}
+
+			fn setup_benchmark(
+				case: &[u8],
+				params: &[u32],
+			) -> $crate::rstd::result::Result<
+				(Option<$trait_instance::AccountId>, Call<$($module_arg),*>),
+				&'static str
+			> {
+				$(
+					if case == stringify!($case).as_bytes() {
+						let mut params = params.iter().cloned();
+						$(
+							let $param: u32 = params.next().ok_or("missing benchmark parameter")?;
+							if !$crate::in_range($param, $min, $max) {
+								return Err(concat!("benchmark parameter ", stringify!($param), " out of range"));
+							}
+						)*
+						if params.next().is_some() {
+							return Err("too many benchmark parameters");
+						}
+						return $body;
+					}
+				)*
+				Err("unknown benchmark case")
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use substrate_primitives::H256;
+
+	#[test]
+	fn accounts_are_distinct() {
+		let alice: H256 = account("alice", 0);
+		assert_eq!(alice, account("alice", 0));
+		assert_ne!(alice, account("alice", 1));
+		assert_ne!(alice, account("bob", 0));
+		assert_ne!(alice, H256::default());
+	}
+}
@@ -23,13 +23,14 @@
 //! ## Overview
 //!
 //! The consensus module manages the authority set for the native code. It provides support for reporting offline
-//! behavior among validators and logging changes in the validator authority set.
+//! behavior and slot equivocations among validators and logging changes in the validator authority set.
 //!
 //! ## Interface
 //!
 //! ### Dispatchable Functions
 //!
-//! - `report_misbehavior` - Report some misbehavior. The origin of this call must be signed.
+//! - `report_misbehavior` - Report an authority which signed two different headers for the same slot.
+//!  The origin of this call must be signed or an inherent.
 //! - `note_offline` - Note that the previous block's validator missed its opportunity to propose a block.
 //!  The origin of this call must be an inherent.
 //! - `remark` - Make some on-chain remark. The origin of this call must be signed.
@@ -109,6 +110,28 @@
 //! # }
 //! ```
 //!
+//! Equivocation reports are checked against the header seals by `Trait::SlotSeal` and the
+//! session key of the offending authority is passed to `Trait::EquivocationHandler`, which
+//! staking implements by slashing the validator:
+//!
+//! ```
+//! # use srml_consensus as consensus;
+//! # trait Trait: consensus::Trait {
+//! # }
+//! #
+//! # srml_support::decl_module! {
+//! #     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+//! #     }
+//! # }
+//! #
+//! impl<T: Trait> consensus::OnEquivocationReport<T::SessionKey> for Module<T> {
+//! 	fn handle_report(offender: T::SessionKey) {
+//! 		// Get the validator owning the session key from the session module
+//! 		// Slash validator
+//! 	}
+//! }
+//! ```
+//!
 //! ## Related Modules
 //!
 //! - [Staking](../srml_staking/index.html): This module uses `srml-consensus` to monitor offline
@@ -131,15 +154,22 @@ use serde::Serialize;
 use rstd::prelude::*;
 use parity_codec as codec;
 use codec::{Encode, Decode};
-use srml_support::{storage, Parameter, decl_storage, decl_module};
-use srml_support::storage::StorageValue;
+use srml_support::{storage, Parameter, decl_storage, decl_module, ensure};
+use srml_support::storage::{StorageValue, StorageMap};
 use srml_support::storage::unhashed::StorageVec;
-use primitives::traits::{MaybeSerializeDebug, Member};
+use srml_support::dispatch::SimpleDispatchInfo;
+use primitives::traits::{MaybeSerializeDebug, Member, Header as HeaderT};
 use substrate_primitives::storage::well_known_keys;
 use system::{ensure_signed, ensure_none};
 use inherents::{
 	ProvideInherent, InherentData, InherentIdentifier, RuntimeString, MakeFatalError
 };
+#[cfg(feature = "std")]
+use inherents::ProvideInherentData;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use parking_lot::Mutex;
 
 #[cfg(any(feature = "std", test))]
 use substrate_primitives::sr25519::Public as AuthorityId;
@@ -150,6 +180,13 @@ mod tests;
 /// The identifier for consensus inherents.
 pub const INHERENT_IDENTIFIER: InherentIdentifier = *b"offlrep0";
 
+/// The identifier for the equivocation reports passed to block authors.
+pub const EQUIVOCATION_INHERENT_IDENTIFIER: InherentIdentifier = *b"equivrep";
+
+/// The number of slots an equivocation can be reported for, counted back from the
+/// latest reported slot. Nodes don't detect equivocations in older slots either.
+pub const EQUIVOCATION_SLOT_WINDOW: u64 = 1000;
+
 /// The error type used by this inherent.
 pub type InherentError = RuntimeString;
 
@@ -171,6 +208,132 @@ impl<T> OnOfflineReport<T> for () {
 	fn handle_report(_: T) {}
 }
 
+/// Two different headers signed by the same authority for the same slot.
+///
+/// This is what `report_misbehavior` expects to decode from its report.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct SlotEquivocationProof<Header> {
+	/// The slot in which both headers were authored.
+	pub slot: u64,
+	/// The first header seen in that slot, including its seal.
+	pub first_header: Header,
+	/// The conflicting header, including its seal.
+	pub second_header: Header,
+}
+
+/// Checking the seal of headers produced by a slot-based consensus engine.
+pub trait CheckSlotSeal<Header, SessionKey> {
+	/// Check the seal of a sealed header, returning the slot it was authored
+	/// in and the session key of the authority which signed it.
+	fn check_seal(header: Header) -> Result<(u64, SessionKey), &'static str>;
+}
+
+impl<Header, SessionKey> CheckSlotSeal<Header, SessionKey> for () {
+	fn check_seal(_: Header) -> Result<(u64, SessionKey), &'static str> {
+		Err("Slot seals can't be checked by this runtime")
+	}
+}
+
+/// Handling proven equivocations in a generic way.
+///
+/// The offender is identified by its session key, which the handler resolves against the
+/// validators the key belonged to when the equivocation happened.
+pub trait OnEquivocationReport<Offender> {
+	fn handle_report(offender: Offender);
+}
+
+impl<T> OnEquivocationReport<T> for () {
+	fn handle_report(_: T) {}
+}
+
+/// Submits an encoded equivocation report in a transaction, e.g. a signed `report_misbehavior`
+/// extrinsic, so that nodes which don't author blocks can report as well.
+#[cfg(feature = "std")]
+pub type SubmitEquivocationReport = Arc<dyn Fn(Vec<u8>) + Send + Sync>;
+
+/// Equivocation reports found by the node, kept until a block author includes them.
+///
+/// This is registered as the inherent data provider for its inherent identifier, which is
+/// `EQUIVOCATION_INHERENT_IDENTIFIER` for the `SlotEquivocationProof`s of this module; the
+/// runtime only includes the reports it can verify and hasn't seen before. New reports are
+/// also handed to the `SubmitEquivocationReport`, if any.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct EquivocationReports {
+	identifier: &'static InherentIdentifier,
+	reports: Arc<Mutex<Vec<Vec<u8>>>>,
+	submit: Option<SubmitEquivocationReport>,
+}
+
+#[cfg(feature = "std")]
+impl Default for EquivocationReports {
+	fn default() -> Self {
+		Self::with_identifier(&EQUIVOCATION_INHERENT_IDENTIFIER)
+	}
+}
+
+#[cfg(feature = "std")]
+impl EquivocationReports {
+	/// The maximum number of reports kept around. The oldest ones are dropped first.
+	pub const MAX_REPORTS: usize = 16;
+
+	/// Create an empty set of slot equivocation reports.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Create an empty set of reports, provided under the given inherent identifier.
+	pub fn with_identifier(identifier: &'static InherentIdentifier) -> Self {
+		EquivocationReports {
+			identifier,
+			reports: Default::default(),
+			submit: None,
+		}
+	}
+
+	/// Also submit every new report with `submit`.
+	pub fn with_submit(mut self, submit: SubmitEquivocationReport) -> Self {
+		self.submit = Some(submit);
+		self
+	}
+
+	/// Queue an encoded report.
+	pub fn push(&self, report: Vec<u8>) {
+		{
+			let mut reports = self.reports.lock();
+			if reports.contains(&report) {
+				return
+			}
+
+			if reports.len() >= Self::MAX_REPORTS {
+				reports.remove(0);
+			}
+			reports.push(report.clone());
+		}
+
+		if let Some(ref submit) = self.submit {
+			submit(report);
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ProvideInherentData for EquivocationReports {
+	fn inherent_identifier(&self) -> &'static InherentIdentifier {
+		self.identifier
+	}
+
+	fn provide_inherent_data(&self, inherent_data: &mut InherentData) -> Result<(), RuntimeString> {
+		let reports = self.reports.lock();
+		inherent_data.put_data(*self.identifier, &*reports)
+	}
+
+	fn error_to_string(&self, error: &[u8]) -> Option<String> {
+		RuntimeString::decode(&mut &error[..]).map(Into::into)
+	}
+}
+
 /// Describes the offline-reporting extrinsic.
 pub trait InherentOfflineReport {
 	/// The report data type passed to the runtime during block authorship.
@@ -263,6 +426,13 @@ pub trait Trait: system::Trait {
 	/// Defines the offline-report type of the trait.
 	/// Set to `()` if offline-reports aren't needed for this runtime.
 	type InherentOfflineReport: InherentOfflineReport;
+
+	/// Checks the seals of headers in equivocation reports.
+	/// Set to `()` if equivocation reports aren't needed for this runtime.
+	type SlotSeal: CheckSlotSeal<Self::Header, Self::SessionKey>;
+
+	/// Handles the session key of an authority proven to have equivocated.
+	type EquivocationHandler: OnEquivocationReport<Self::SessionKey>;
 }
 
 decl_storage! {
@@ -270,6 +440,11 @@ decl_storage! {
 		// Actual authorities set at the block execution start. Is `Some` iff
 		// the set has been changed.
 		OriginalAuthorities: Option<Vec<T::SessionKey>>;
+		// Slot equivocations which have been reported already, by slot and authority.
+		ReportedEquivocations get(is_equivocation_reported): map (u64, T::SessionKey) => bool;
+		// The keys of `ReportedEquivocations` ordered by slot, pruned once they fall out of
+		// the `EQUIVOCATION_SLOT_WINDOW`.
+		ReportedEquivocationSlots get(reported_equivocation_slots): Vec<(u64, T::SessionKey)>;
 	}
 	add_extra_genesis {
 		config(authorities): Vec<T::SessionKey>;
@@ -291,12 +466,25 @@ decl_storage! {
 
 decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
-		/// Report some misbehavior.
-		fn report_misbehavior(origin, _report: Vec<u8>) {
-			ensure_signed(origin)?;
+		/// Report an authority which signed two different headers for the same slot.
+		///
+		/// The report is an encoded `SlotEquivocationProof`, which is verified before the
+		/// offender is handed over to the `EquivocationHandler`.
+		fn report_misbehavior(origin, report: Vec<u8>) {
+			let origin: Result<system::RawOrigin<T::AccountId>, T::Origin> = origin.into();
+			match origin {
+				Ok(system::RawOrigin::Signed(_)) | Ok(system::RawOrigin::None) => {},
+				_ => return Err("bad origin: expected to be signed or an inherent origin"),
+			}
+
+			let (offender, equivocation) = Self::check_misbehavior_report(&report)?;
+			Self::note_equivocation(equivocation);
+
+			T::EquivocationHandler::handle_report(offender);
 		}
 
 		/// Note that the previous block's validator missed its opportunity to propose a block.
+		#[weight = SimpleDispatchInfo::Free]
 		fn note_offline(origin, offline: <T::InherentOfflineReport as InherentOfflineReport>::Inherent) {
 			ensure_none(origin)?;
 
@@ -387,6 +575,57 @@ impl<T: Trait> Module<T> {
 			AuthorityStorageVec::<T::SessionKey>::items()));
 	}
 
+	/// Check an encoded `SlotEquivocationProof`, returning the session key of the offending
+	/// authority and the `(slot, authority)` pair which equivocated.
+	fn check_misbehavior_report(report: &[u8]) -> Result<(T::SessionKey, (u64, T::SessionKey)), &'static str> {
+		let proof = SlotEquivocationProof::<T::Header>::decode(&mut &report[..])
+			.ok_or("Misbehavior report could not be decoded")?;
+
+		ensure!(
+			proof.first_header.hash() != proof.second_header.hash(),
+			"Equivocation proof contains the same header twice"
+		);
+
+		let (first_slot, first_author) = T::SlotSeal::check_seal(proof.first_header)?;
+		let (second_slot, second_author) = T::SlotSeal::check_seal(proof.second_header)?;
+
+		ensure!(
+			first_slot == proof.slot && second_slot == proof.slot,
+			"Equivocation proof headers were not authored in the reported slot"
+		);
+		ensure!(first_author == second_author, "Equivocation proof headers have different authors");
+
+		let equivocation = (proof.slot, first_author);
+		ensure!(!Self::is_equivocation_reported(&equivocation), "Equivocation was already reported");
+		// older equivocations might have been reported already and pruned.
+		let latest_slot = Self::reported_equivocation_slots().last().map_or(0, |(slot, _)| *slot);
+		ensure!(
+			proof.slot.saturating_add(EQUIVOCATION_SLOT_WINDOW) >= latest_slot,
+			"Equivocation is too old to be reported"
+		);
+
+		Ok((equivocation.1.clone(), equivocation))
+	}
+
+	/// Note that an equivocation has been reported, forgetting the ones which are out of
+	/// the `EQUIVOCATION_SLOT_WINDOW` now.
+	fn note_equivocation(equivocation: (u64, T::SessionKey)) {
+		let mut reported = Self::reported_equivocation_slots();
+		let position = reported.iter().position(|(slot, _)| *slot > equivocation.0)
+			.unwrap_or(reported.len());
+		reported.insert(position, equivocation.clone());
+		<ReportedEquivocations<T>>::insert(equivocation, true);
+
+		let latest_slot = reported.last().map_or(0, |(slot, _)| *slot);
+		let expired = reported.iter()
+			.take_while(|(slot, _)| slot.saturating_add(EQUIVOCATION_SLOT_WINDOW) < latest_slot)
+			.count();
+		for expired in reported.drain(..expired) {
+			<ReportedEquivocations<T>>::remove(expired);
+		}
+		<ReportedEquivocationSlots<T>>::put(reported);
+	}
+
 	/// Deposit one of this module's logs.
 	fn deposit_log(log: Log<T>) {
 		<system::Module<T>>::deposit_log(<T as Trait>::Log::from(log).into());
@@ -403,19 +642,26 @@ impl<T: Trait> ProvideInherent for Module<T> {
 	const INHERENT_IDENTIFIER: InherentIdentifier = INHERENT_IDENTIFIER;
 
 	/// Creates an inherent from the `InherentData`.
+	///
+	/// Offline reports take precedence; otherwise the first pending equivocation report
+	/// which can be verified is included.
 	fn create_inherent(data: &InherentData) -> Option<Self::Call> {
-		if let Ok(Some(data)) =
+		if let Ok(Some(offline)) =
 			data.get_data::<<T::InherentOfflineReport as InherentOfflineReport>::Inherent>(
 				&INHERENT_IDENTIFIER
 			)
 		{
-			if <T::InherentOfflineReport as InherentOfflineReport>::is_empty(&data) {
-				None
-			} else {
-				Some(Call::note_offline(data))
+			if !<T::InherentOfflineReport as InherentOfflineReport>::is_empty(&offline) {
+				return Some(Call::note_offline(offline))
 			}
-		} else {
-			None
+		}
+
+		match data.get_data::<Vec<Vec<u8>>>(&EQUIVOCATION_INHERENT_IDENTIFIER) {
+			Ok(Some(reports)) => reports
+				.into_iter()
+				.find(|report| Self::check_misbehavior_report(report).is_ok())
+				.map(Call::report_misbehavior),
+			_ => None,
 		}
 	}
 
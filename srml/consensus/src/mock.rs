@@ -18,16 +18,43 @@
 
 #![cfg(test)]
 
-use primitives::{BuildStorage, traits::IdentityLookup, testing::{Digest, DigestItem, Header, UintAuthorityId}};
+use std::cell::RefCell;
+use primitives::{BuildStorage, traits::{IdentityLookup, Header as HeaderT, Digest as DigestT}};
+use primitives::testing::{Digest, DigestItem, Header, UintAuthorityId};
 use srml_support::impl_outer_origin;
 use runtime_io;
+use parity_codec::Decode;
 use substrate_primitives::{H256, Blake2Hasher};
-use crate::{GenesisConfig, Trait, Module};
+use crate::{GenesisConfig, Trait, Module, CheckSlotSeal, OnEquivocationReport};
 
 impl_outer_origin!{
 	pub enum Origin for Test {}
 }
 
+thread_local! {
+	pub static EQUIVOCATIONS: RefCell<Vec<UintAuthorityId>> = RefCell::new(Vec::new());
+}
+
+/// Test seals are an `Other` digest item holding the encoded `(slot, author)`.
+pub struct TestSlotSeal;
+impl CheckSlotSeal<Header, UintAuthorityId> for TestSlotSeal {
+	fn check_seal(mut header: Header) -> Result<(u64, UintAuthorityId), &'static str> {
+		match header.digest_mut().pop() {
+			Some(DigestItem::Other(seal)) => <(u64, u64)>::decode(&mut &seal[..])
+				.map(|(slot, author)| (slot, UintAuthorityId(author)))
+				.ok_or("Bad seal"),
+			_ => Err("Header is unsealed"),
+		}
+	}
+}
+
+pub struct TestEquivocationHandler;
+impl OnEquivocationReport<UintAuthorityId> for TestEquivocationHandler {
+	fn handle_report(offender: UintAuthorityId) {
+		EQUIVOCATIONS.with(|e| e.borrow_mut().push(offender));
+	}
+}
+
 // Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Test;
@@ -35,6 +62,8 @@ impl Trait for Test {
 	type Log = DigestItem;
 	type SessionKey = UintAuthorityId;
 	type InherentOfflineReport = crate::InstantFinalityReportVec<()>;
+	type SlotSeal = TestSlotSeal;
+	type EquivocationHandler = TestEquivocationHandler;
 }
 impl system::Trait for Test {
 	type Origin = Origin;
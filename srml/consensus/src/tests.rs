@@ -20,9 +20,28 @@
 
 use primitives::{generic, testing::{self, UintAuthorityId}, traits::OnFinalize};
 use runtime_io::with_externalities;
-use crate::mock::{Consensus, System, new_test_ext};
+use parity_codec::Encode;
+use srml_support::{assert_ok, assert_noop};
+use crate::mock::{Consensus, System, Origin, EQUIVOCATIONS, new_test_ext};
+use crate::{Call, SlotEquivocationProof};
 use inherents::{InherentData, ProvideInherent};
 
+fn sealed_header(number: u64, slot: u64, author: u64) -> testing::Header {
+	let mut header = testing::Header {
+		parent_hash: Default::default(),
+		number,
+		state_root: Default::default(),
+		extrinsics_root: Default::default(),
+		digest: Default::default(),
+	};
+	header.digest.logs.push(generic::DigestItem::Other((slot, author).encode()));
+	header
+}
+
+fn equivocation_report(slot: u64, first: testing::Header, second: testing::Header) -> Vec<u8> {
+	SlotEquivocationProof { slot, first_header: first, second_header: second }.encode()
+}
+
 #[test]
 fn authorities_change_logged() {
 	with_externalities(&mut new_test_ext(vec![1, 2, 3]), || {
@@ -129,3 +148,108 @@ fn set_and_kill_storage_work() {
 		);
 	});
 }
+
+#[test]
+fn equivocation_report_is_checked_and_handled() {
+	with_externalities(&mut new_test_ext(vec![1, 2, 3]), || {
+		System::initialize(&1, &Default::default(), &Default::default());
+
+		let report = equivocation_report(5, sealed_header(1, 5, 2), sealed_header(2, 5, 2));
+		assert_ok!(Consensus::report_misbehavior(Origin::signed(1), report.clone()));
+		assert_eq!(EQUIVOCATIONS.with(|e| e.borrow().clone()), vec![UintAuthorityId(2)]);
+		assert!(Consensus::is_equivocation_reported(&(5, UintAuthorityId(2))));
+
+		// the same equivocation can't be punished twice.
+		assert_noop!(
+			Consensus::report_misbehavior(Origin::signed(1), report),
+			"Equivocation was already reported"
+		);
+	});
+}
+
+#[test]
+fn old_equivocations_are_pruned_and_rejected() {
+	with_externalities(&mut new_test_ext(vec![1, 2, 3]), || {
+		System::initialize(&1, &Default::default(), &Default::default());
+
+		let old = equivocation_report(5, sealed_header(1, 5, 2), sealed_header(2, 5, 2));
+		assert_ok!(Consensus::report_misbehavior(Origin::signed(1), old.clone()));
+
+		let slot = 6 + crate::EQUIVOCATION_SLOT_WINDOW;
+		let report = equivocation_report(slot, sealed_header(1, slot, 3), sealed_header(2, slot, 3));
+		assert_ok!(Consensus::report_misbehavior(Origin::signed(1), report));
+		assert!(!Consensus::is_equivocation_reported(&(5, UintAuthorityId(2))));
+		assert_eq!(Consensus::reported_equivocation_slots(), vec![(slot, UintAuthorityId(3))]);
+
+		// the pruned equivocation can't be punished again.
+		assert_noop!(
+			Consensus::report_misbehavior(Origin::signed(1), old),
+			"Equivocation is too old to be reported"
+		);
+		assert_eq!(EQUIVOCATIONS.with(|e| e.borrow().clone()), vec![UintAuthorityId(2), UintAuthorityId(3)]);
+	});
+}
+
+#[test]
+fn equivocation_of_a_rotated_out_authority_is_handled() {
+	with_externalities(&mut new_test_ext(vec![1, 2, 3]), || {
+		System::initialize(&1, &Default::default(), &Default::default());
+
+		Consensus::set_authorities(&[UintAuthorityId(4), UintAuthorityId(5)]);
+
+		let report = equivocation_report(5, sealed_header(1, 5, 2), sealed_header(2, 5, 2));
+		assert_ok!(Consensus::report_misbehavior(Origin::signed(1), report));
+		assert_eq!(EQUIVOCATIONS.with(|e| e.borrow().clone()), vec![UintAuthorityId(2)]);
+	});
+}
+
+#[test]
+fn invalid_equivocation_reports_are_rejected() {
+	with_externalities(&mut new_test_ext(vec![1, 2, 3]), || {
+		System::initialize(&1, &Default::default(), &Default::default());
+
+		assert_noop!(
+			Consensus::report_misbehavior(Origin::signed(1), vec![1, 2, 3]),
+			"Misbehavior report could not be decoded"
+		);
+		assert_noop!(
+			Consensus::report_misbehavior(
+				Origin::signed(1),
+				equivocation_report(5, sealed_header(1, 5, 2), sealed_header(1, 5, 2)),
+			),
+			"Equivocation proof contains the same header twice"
+		);
+		assert_noop!(
+			Consensus::report_misbehavior(
+				Origin::signed(1),
+				equivocation_report(5, sealed_header(1, 5, 2), sealed_header(2, 6, 2)),
+			),
+			"Equivocation proof headers were not authored in the reported slot"
+		);
+		assert_noop!(
+			Consensus::report_misbehavior(
+				Origin::signed(1),
+				equivocation_report(5, sealed_header(1, 5, 2), sealed_header(2, 5, 3)),
+			),
+			"Equivocation proof headers have different authors"
+		);
+		assert!(EQUIVOCATIONS.with(|e| e.borrow().is_empty()));
+	});
+}
+
+#[test]
+fn only_verifiable_equivocation_reports_are_included() {
+	with_externalities(&mut new_test_ext(vec![1, 2, 3]), || {
+		System::initialize(&1, &Default::default(), &Default::default());
+
+		let invalid = equivocation_report(5, sealed_header(1, 5, 2), sealed_header(2, 5, 3));
+		let valid = equivocation_report(5, sealed_header(1, 5, 2), sealed_header(2, 5, 2));
+		let mut data = InherentData::new();
+		data.put_data(super::EQUIVOCATION_INHERENT_IDENTIFIER, &vec![invalid, valid.clone()]).unwrap();
+
+		assert_eq!(Consensus::create_inherent(&data), Some(Call::report_misbehavior(valid.clone())));
+
+		assert_ok!(Consensus::report_misbehavior(Origin::NONE, valid));
+		assert!(Consensus::create_inherent(&data).is_none());
+	});
+}
@@ -86,6 +86,7 @@ impl balances::Trait for Test {
 	type Event = MetaEvent;
 	type TransactionPayment = ();
 	type DustRemoval = ();
+	type WeightToFee = ();
 	type TransferPayment = ();
 }
 impl timestamp::Trait for Test {
@@ -96,6 +97,8 @@ impl consensus::Trait for Test {
 	type Log = DigestItem;
 	type SessionKey = UintAuthorityId;
 	type InherentOfflineReport = ();
+	type SlotSeal = ();
+	type EquivocationHandler = ();
 }
 impl Trait for Test {
 	type Currency = Balances;
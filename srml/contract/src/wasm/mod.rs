@@ -24,6 +24,7 @@ use crate::gas::GasMeter;
 
 use rstd::prelude::*;
 use parity_codec::{Encode, Decode};
+use runtime_primitives::traits::As;
 use sandbox;
 
 #[macro_use]
@@ -131,6 +132,7 @@ impl<'a, T: Trait> crate::exec::Vm<T> for WasmVm<'a, T> {
 		runtime::Env::impls(&mut |name, func_ptr| {
 			imports.add_host_func("env", name, func_ptr);
 		});
+		imports.set_limits(instance_limits(self.schedule, gas_meter));
 
 		let mut runtime = Runtime::new(
 			ext,
@@ -169,6 +171,29 @@ impl<'a, T: Trait> crate::exec::Vm<T> for WasmVm<'a, T> {
 	}
 }
 
+/// The limits the sandbox enforces on the code of a contract.
+///
+/// They back up the checks and the instrumentation done by `prepare`. The code is already
+/// instrumented for the stack height limit, so only the fuel is metered by the sandbox: a
+/// contract can't execute more instructions than the regular operations the gas left pays for.
+fn instance_limits<T: Trait>(schedule: &Schedule<T::Gas>, gas_meter: &GasMeter<T>) -> sandbox::InstanceLimits {
+	let regular_op_cost: u64 = schedule.regular_op_cost.as_();
+	let gas_left: u64 = gas_meter.gas_left().as_();
+	let mut denied_imports = Vec::new();
+	if !schedule.enable_println {
+		denied_imports.push(sandbox::ImportName {
+			module_name: b"env".to_vec(),
+			field_name: b"ext_println".to_vec(),
+		});
+	}
+	sandbox::InstanceLimits {
+		fuel: Some(gas_left / regular_op_cost.max(1)),
+		max_stack_height: None,
+		denied_features: [sandbox::WasmFeature::FloatingPoint, sandbox::WasmFeature::InternalMemory].to_vec(),
+		denied_imports,
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
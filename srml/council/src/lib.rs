@@ -80,6 +80,7 @@ mod tests {
 		type TransactionPayment = ();
 		type TransferPayment = ();
 		type DustRemoval = ();
+		type WeightToFee = ();
 	}
 	impl democracy::Trait for Test {
 		type Currency = balances::Module<Self>;
@@ -18,9 +18,10 @@
 
 use rstd::prelude::*;
 use rstd::result;
+use rstd::marker::PhantomData;
 use substrate_primitives::u32_trait::Value as U32;
 use primitives::traits::{Hash, EnsureOrigin};
-use srml_support::dispatch::{Dispatchable, Parameter};
+use srml_support::dispatch::{Dispatchable, Parameter, Weight, WeighData, GetDispatchInfo, SimpleDispatchInfo};
 use srml_support::{StorageValue, StorageMap, decl_module, decl_event, decl_storage, ensure};
 use super::{Trait as CouncilTrait, Module as Council};
 use system::{self, ensure_signed};
@@ -33,7 +34,7 @@ pub trait Trait: CouncilTrait {
 	type Origin: From<Origin>;
 
 	/// The outer call dispatch type.
-	type Proposal: Parameter + Dispatchable<Origin=<Self as Trait>::Origin>;
+	type Proposal: Parameter + Dispatchable<Origin=<Self as Trait>::Origin> + GetDispatchInfo;
 
 	/// The outer event type.
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
@@ -66,6 +67,7 @@ decl_event!(
 decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: <T as system::Trait>::Origin {
 		fn deposit_event<T>() = default;
+		#[weight = ProposeWeight]
 		fn propose(origin, #[compact] threshold: u32, proposal: Box<<T as Trait>::Proposal>) {
 			let who = ensure_signed(origin)?;
 
@@ -89,6 +91,7 @@ decl_module! {
 			}
 		}
 
+		#[weight = VoteWeight::<T>(PhantomData)]
 		fn vote(origin, proposal: T::Hash, #[compact] index: ProposalIndex, approve: bool) {
 			let who = ensure_signed(origin)?;
 
@@ -153,6 +156,32 @@ decl_module! {
 	}
 }
 
+/// The weight of `propose`. A proposal with a threshold below 2 is dispatched right away.
+struct ProposeWeight;
+
+impl<'a, P: GetDispatchInfo> WeighData<(&'a u32, &'a Box<P>)> for ProposeWeight {
+	fn weigh_data(&self, (threshold, proposal): (&'a u32, &'a Box<P>)) -> Weight {
+		let weight = SimpleDispatchInfo::Fixed(2_500_000).weigh_data(());
+		if *threshold < 2 {
+			weight.saturating_add(proposal.get_dispatch_info().weight)
+		} else {
+			weight
+		}
+	}
+}
+
+/// The weight of `vote`. The vote which approves the proposal dispatches it, so the weight of
+/// the proposal is always included.
+struct VoteWeight<T>(PhantomData<T>);
+
+impl<'a, T: Trait> WeighData<(&'a T::Hash, &'a ProposalIndex, &'a bool)> for VoteWeight<T> {
+	fn weigh_data(&self, (proposal, _, _): (&'a T::Hash, &'a ProposalIndex, &'a bool)) -> Weight {
+		let weight = SimpleDispatchInfo::Fixed(500_000).weigh_data(());
+		<ProposalOf<T>>::get(proposal)
+			.map_or(weight, |proposal| weight.saturating_add(proposal.get_dispatch_info().weight))
+	}
+}
+
 decl_storage! {
 	trait Store for Module<T: Trait> as CouncilMotions {
 		/// The (hashes of) the active proposals.
@@ -240,6 +269,24 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn motions_weights_include_the_dispatched_proposal() {
+		with_externalities(&mut new_test_ext(true), || {
+			System::set_block_number(1);
+			let proposal = set_balance_proposal(42);
+			let proposal_weight = proposal.get_dispatch_info().weight;
+			let hash: H256 = proposal.blake2_256().into();
+			let weight = |call: super::Call<Test>| call.get_dispatch_info().weight;
+
+			assert_eq!(weight(super::Call::propose(1, Box::new(proposal.clone()))), 2_500_000 + proposal_weight);
+			assert_eq!(weight(super::Call::propose(3, Box::new(proposal.clone()))), 2_500_000);
+
+			assert_eq!(weight(super::Call::vote(hash, 0, true)), 500_000);
+			assert_ok!(CouncilMotions::propose(Origin::signed(1), 3, Box::new(proposal)));
+			assert_eq!(weight(super::Call::vote(hash, 0, true)), 500_000 + proposal_weight);
+		});
+	}
+
 	#[test]
 	fn motions_ignoring_non_council_proposals_works() {
 		with_externalities(&mut new_test_ext(true), || {
@@ -20,7 +20,7 @@ use rstd::prelude::*;
 use primitives::traits::{Zero, One, As, StaticLookup};
 use runtime_io::print;
 use srml_support::{
-	StorageValue, StorageMap, dispatch::Result, decl_storage, decl_event, ensure,
+	StorageValue, StorageMap, dispatch::{Result, SimpleDispatchInfo}, decl_storage, decl_event, ensure,
 	traits::{Currency, ReservableCurrency, OnUnbalanced}
 };
 use democracy;
@@ -103,6 +103,7 @@ decl_module! {
 
 		/// Set candidate approvals. Approval slots stay valid as long as candidates in those slots
 		/// are registered.
+		#[weight = SimpleDispatchInfo::Fixed(2_500_000)]
 		fn set_approvals(origin, votes: Vec<bool>, #[compact] index: VoteIndex) -> Result {
 			let who = ensure_signed(origin)?;
 			Self::do_set_approvals(who, votes, index)
@@ -110,6 +111,7 @@ decl_module! {
 
 		/// Set candidate approvals from a proxy. Approval slots stay valid as long as candidates in those slots
 		/// are registered.
+		#[weight = SimpleDispatchInfo::Fixed(2_500_000)]
 		fn proxy_set_approvals(origin, votes: Vec<bool>, #[compact] index: VoteIndex) -> Result {
 			let who = <democracy::Module<T>>::proxy(ensure_signed(origin)?).ok_or("not a proxy")?;
 			Self::do_set_approvals(who, votes, index)
@@ -120,6 +122,7 @@ decl_module! {
 		/// the voter gave their last approval set.
 		///
 		/// May be called by anyone. Returns the voter deposit to `signed`.
+		#[weight = SimpleDispatchInfo::Fixed(2_500_000)]
 		fn reap_inactive_voter(
 			origin,
 			#[compact] reporter_index: u32,
@@ -169,6 +172,7 @@ decl_module! {
 		}
 
 		/// Remove a voter. All votes are cancelled and the voter deposit is returned.
+		#[weight = SimpleDispatchInfo::Fixed(1_250_000)]
 		fn retract_voter(origin, #[compact] index: u32) {
 			let who = ensure_signed(origin)?;
 
@@ -186,6 +190,7 @@ decl_module! {
 		/// Submit oneself for candidacy.
 		///
 		/// Account must have enough transferrable funds in it to pay the bond.
+		#[weight = SimpleDispatchInfo::Fixed(2_500_000)]
 		fn submit_candidacy(origin, #[compact] slot: u32) {
 			let who = ensure_signed(origin)?;
 
@@ -216,6 +221,9 @@ decl_module! {
 		/// Claim that `signed` is one of the top Self::carry_count() + current_vote().1 candidates.
 		/// Only works if the `block_number >= current_vote().0` and `< current_vote().0 + presentation_duration()``
 		/// `signed` should have at least
+		///
+		/// The total is recomputed from the approvals of all the voters.
+		#[weight = SimpleDispatchInfo::Fixed(10_000_000)]
 		fn present_winner(
 			origin,
 			candidate: <T::Lookup as StaticLookup>::Source,
@@ -20,7 +20,7 @@ use rstd::prelude::*;
 use rstd::borrow::Borrow;
 use primitives::traits::{Hash, As, Zero};
 use runtime_io::print;
-use srml_support::dispatch::Result;
+use srml_support::dispatch::{Result, SimpleDispatchInfo};
 use srml_support::{StorageValue, StorageMap, IsSubType, decl_module, decl_storage, decl_event, ensure};
 use {system, democracy};
 use super::{Trait as CouncilTrait, Module as Council};
@@ -34,6 +34,7 @@ decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
 		fn deposit_event<T>() = default;
 
+		#[weight = SimpleDispatchInfo::Fixed(2_500_000)]
 		fn propose(origin, proposal: Box<T::Proposal>) {
 			let who = ensure_signed(origin)?;
 
@@ -55,6 +56,7 @@ decl_module! {
 			<CouncilVoteOf<T>>::insert((proposal_hash, who.clone()), true);
 		}
 
+		#[weight = SimpleDispatchInfo::Fixed(500_000)]
 		fn vote(origin, proposal: T::Hash, approve: bool) {
 			let who = ensure_signed(origin)?;
 
@@ -66,6 +68,7 @@ decl_module! {
 			<CouncilVoteOf<T>>::insert((proposal, who), approve);
 		}
 
+		#[weight = SimpleDispatchInfo::Fixed(2_500_000)]
 		fn veto(origin, proposal_hash: T::Hash) {
 			let who = ensure_signed(origin)?;
 
@@ -31,7 +31,7 @@ use srml_support::{StorageValue, StorageMap, Parameter, Dispatchable, IsSubType,
 use srml_support::{decl_module, decl_storage, decl_event, ensure};
 use srml_support::traits::{Currency, ReservableCurrency, LockableCurrency, WithdrawReason, LockIdentifier,
 	OnFreeBalanceZero};
-use srml_support::dispatch::Result;
+use srml_support::dispatch::{Result, SimpleDispatchInfo};
 use system::ensure_signed;
 
 mod vote_threshold;
@@ -93,6 +93,7 @@ decl_module! {
 		/*
 		提出要采取的敏感措施
 		*/
+		#[weight = SimpleDispatchInfo::Fixed(5_000_000)]
 		fn propose(
 			origin,
 			proposal: Box<T::Proposal>,
@@ -119,6 +120,7 @@ decl_module! {
 		/*
 		提出要采取的敏感措施
 		*/
+		#[weight = SimpleDispatchInfo::Fixed(5_000_000)]
 		fn second(origin, #[compact] proposal: PropIndex) {
 			let who = ensure_signed(origin)?;
 			let mut deposit = Self::deposit_of(proposal)
@@ -135,6 +137,7 @@ decl_module! {
 		在公民投票中投票。 如果是`vote.is_aye（）`，投票就是制定提案;
 		否则就是保持现状的投票
 		*/
+		#[weight = SimpleDispatchInfo::Fixed(200_000)]
 		fn vote(origin, #[compact] ref_index: ReferendumIndex, vote: Vote) -> Result {
 			let who = ensure_signed(origin)?;
 			Self::do_vote(who, ref_index, vote)
@@ -142,6 +145,7 @@ decl_module! {
 
 		/// Vote in a referendum on behalf of a stash. If `vote.is_aye()`, the vote is to enact the proposal;
 		/// otherwise it is a vote to keep the status quo.
+		#[weight = SimpleDispatchInfo::Fixed(200_000)]
 		fn proxy_vote(origin, #[compact] ref_index: ReferendumIndex, vote: Vote) -> Result {
 			let who = Self::proxy(ensure_signed(origin)?).ok_or("not a proxy")?;
 			Self::do_vote(who, ref_index, vote)
@@ -207,6 +211,7 @@ decl_module! {
 		/*
 		TODO 委托投票
 		*/
+		#[weight = SimpleDispatchInfo::Fixed(500_000)]
 		pub fn delegate(origin, to: T::AccountId, lock_periods: LockPeriods) {
 			let who = ensure_signed(origin)?;
 			<Delegations<T>>::insert(who.clone(), (to.clone(), lock_periods.clone()));
@@ -219,6 +224,7 @@ decl_module! {
 		/*
 		TODO 解除委托投票
 		*/
+		#[weight = SimpleDispatchInfo::Fixed(500_000)]
 		fn undelegate(origin) {
 			let who = ensure_signed(origin)?;
 			ensure!(<Delegations<T>>::exists(&who), "not delegated");
@@ -594,6 +600,7 @@ mod tests {
 		type TransactionPayment = ();
 		type TransferPayment = ();
 		type DustRemoval = ();
+		type WeightToFee = ();
 	}
 	impl Trait for Test {
 		type Currency = balances::Module<Self>;
@@ -523,6 +523,7 @@ mod tests {
 		type TransactionPayment = ();
 		type TransferPayment = ();
 		type DustRemoval = ();
+		type WeightToFee = ();
 	}
 	impl Trait for Test {
 		type Event = ();
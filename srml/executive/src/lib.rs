@@ -79,10 +79,11 @@ use rstd::marker::PhantomData;
 use rstd::result;
 use primitives::traits::{
 	self, Header, Zero, One, Checkable, Applyable, CheckEqual, OnFinalize,
-	OnInitialize, Digest, NumberFor, Block as BlockT, OffchainWorker,
+	OnInitialize, Digest, DigestItem, NumberFor, Block as BlockT, OffchainWorker,
 	ValidateUnsigned,
 };
 use srml_support::{Dispatchable, traits::MakePayment};
+use srml_support::dispatch::GetDispatchInfo;
 use parity_codec::{Codec, Encode};
 use system::extrinsics_root;
 use primitives::{ApplyOutcome, ApplyError};
@@ -126,7 +127,7 @@ impl<
 where
 	Block::Extrinsic: Checkable<Context> + Codec,
 	<Block::Extrinsic as Checkable<Context>>::Checked: Applyable<Index=System::Index, AccountId=System::AccountId>,
-	<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call: Dispatchable,
+	<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call: Dispatchable + GetDispatchInfo,
 	<<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call as Dispatchable>::Origin: From<Option<System::AccountId>>,
 	UnsignedValidator: ValidateUnsigned<Call=<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call>
 {
@@ -146,17 +147,27 @@ impl<
 where
 	Block::Extrinsic: Checkable<Context> + Codec,
 	<Block::Extrinsic as Checkable<Context>>::Checked: Applyable<Index=System::Index, AccountId=System::AccountId>,
-	<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call: Dispatchable,
+	<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call: Dispatchable + GetDispatchInfo,
 	<<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call as Dispatchable>::Origin: From<Option<System::AccountId>>,
 	UnsignedValidator: ValidateUnsigned<Call=<<Block::Extrinsic as Checkable<Context>>::Checked as Applyable>::Call>
 {
 	/// Start the execution of a particular block.
 	pub fn initialize_block(header: &System::Header) {
-		Self::initialize_block_impl(header.number(), header.parent_hash(), header.extrinsics_root());
+		Self::initialize_block_impl(header.number(), header.parent_hash(), header.extrinsics_root(), header.digest());
 	}
 
-	fn initialize_block_impl(block_number: &System::BlockNumber, parent_hash: &System::Hash, extrinsics_root: &System::Hash) {
+	fn initialize_block_impl(
+		block_number: &System::BlockNumber,
+		parent_hash: &System::Hash,
+		extrinsics_root: &System::Hash,
+		digest: &System::Digest,
+	) {
 		<system::Module<System>>::initialize(block_number, parent_hash, extrinsics_root);
+		// the pre-runtime digests are put into the header by the block author, keep them
+		// so that the modules can read them and the final digest still contains them.
+		for log in digest.logs().iter().filter(|log| log.as_pre_runtime().is_some()) {
+			<system::Module<System>>::deposit_log(log.clone());
+		}
 		<AllModules as OnInitialize<System::BlockNumber>>::on_initialize(*block_number);
 	}
 
@@ -246,10 +257,14 @@ where
 		// Verify that the signature is good.
 		let xt = uxt.check(&Default::default()).map_err(internal::ApplyError::BadSignature)?;
 
-		// Check the size of the block if that extrinsic is applied.
+		// Check the size and the weight of the block if that extrinsic is applied.
 		if <system::Module<System>>::all_extrinsics_len() + encoded_len as u32 > internal::MAX_TRANSACTIONS_SIZE {
 			return Err(internal::ApplyError::FullBlock);
 		}
+		let weight = system::extrinsic_weight(xt.call().get_dispatch_info());
+		if <system::Module<System>>::all_extrinsics_weight().saturating_add(weight) > system::MAXIMUM_BLOCK_WEIGHT {
+			return Err(internal::ApplyError::FullBlock);
+		}
 
 		if let (Some(sender), Some(index)) = (xt.sender(), xt.index()) {
 			// check index
@@ -259,7 +274,7 @@ where
 			) }
 
 			// pay any fees
-			Payment::make_payment(sender, encoded_len).map_err(|_| internal::ApplyError::CantPay)?;
+			Payment::make_payment(sender, encoded_len, weight, xt.tip()).map_err(|_| internal::ApplyError::CantPay)?;
 
 			// AUDIT: Under no circumstances may this function panic from here onwards.
 
@@ -276,7 +291,7 @@ where
 		// Decode parameters and dispatch
 		let (f, s) = xt.deconstruct();
 		let r = f.dispatch(s.into());
-		<system::Module<System>>::note_applied_extrinsic(&r, encoded_len as u32);
+		<system::Module<System>>::note_applied_extrinsic(&r, encoded_len as u32, weight);
 
 		r.map(|_| internal::ApplyOutcome::Success).or_else(|e| match e {
 			primitives::BLOCK_FULL => Err(internal::ApplyError::FullBlock),
@@ -332,7 +347,8 @@ where
 		match (xt.sender(), xt.index()) {
 			(Some(sender), Some(index)) => {
 				// pay any fees
-				if Payment::make_payment(sender, encoded_len).is_err() {
+				let weight = system::extrinsic_weight(xt.call().get_dispatch_info());
+				if Payment::make_payment(sender, encoded_len, weight, xt.tip()).is_err() {
 					return TransactionValidity::Invalid(ApplyError::CantPay as i8)
 				}
 
@@ -416,6 +432,7 @@ mod tests {
 		type Event = MetaEvent;
 		type TransactionPayment = ();
 		type DustRemoval = ();
+		type WeightToFee = ();
 		type TransferPayment = ();
 	}
 
@@ -528,6 +545,25 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn pre_runtime_digests_are_kept() {
+		let mut t = new_test_ext();
+		let pre_runtime = DigestItem::PreRuntime(*b"test", vec![1, 2, 3]);
+		with_externalities(&mut t, || {
+			Executive::initialize_block(&Header::new(
+				1,
+				H256::default(),
+				H256::default(),
+				[69u8; 32].into(),
+				Digest { logs: vec![pre_runtime.clone()] },
+			));
+			assert_eq!(<system::Module<Runtime>>::digest().logs, vec![pre_runtime.clone()]);
+
+			let header = Executive::finalize_block();
+			assert_eq!(header.digest.logs, vec![pre_runtime]);
+		});
+	}
+
 	#[test]
 	fn block_size_limit_enforced() {
 		let run_test = |should_fail: bool| {
@@ -559,6 +595,27 @@ mod tests {
 		run_test(true);
 	}
 
+	#[test]
+	fn block_weight_limit_enforced() {
+		let mut t = new_test_ext();
+		let weight = Call::<Runtime>::transfer(33, 0).get_dispatch_info().weight;
+		with_externalities(&mut t, || {
+			Executive::initialize_block(&Header::new(1, H256::default(), H256::default(), [69u8; 32].into(), Digest::default()));
+			// leave room for two transfers only.
+			let initial_weight = system::MAXIMUM_BLOCK_WEIGHT - 2 * weight;
+			<system::Module<Runtime>>::note_applied_extrinsic(&Ok(()), 0, initial_weight);
+			for nonce in 0..2 {
+				let xt = primitives::testing::TestXt(Some(1), nonce as u64, Call::transfer(33, 0));
+				assert!(Executive::apply_extrinsic(xt).is_ok());
+				assert_eq!(<system::Module<Runtime>>::all_extrinsics_weight(), initial_weight + weight * (nonce + 1));
+			}
+
+			let xt = primitives::testing::TestXt(Some(1), 2, Call::transfer(33, 0));
+			assert_eq!(Executive::apply_extrinsic(xt), Err(ApplyError::FullBlock));
+			assert_eq!(<system::Module<Runtime>>::extrinsic_index(), Some(3));
+		});
+	}
+
 	#[test]
 	fn validate_unsigned() {
 		let xt = primitives::testing::TestXt(None, 0, Call::set_balance(33, 69, 69));
@@ -26,6 +26,7 @@ use inherents::{
 	InherentData, MakeFatalError,
 };
 use srml_support::StorageValue;
+use srml_support::dispatch::SimpleDispatchInfo;
 use primitives::traits::{As, One, Zero};
 use rstd::{prelude::*, result, cmp, vec};
 use parity_codec::Decode;
@@ -116,6 +117,7 @@ decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
 		/// Hint that the author of this block thinks the best finalized
 		/// block is the given number.
+		#[weight = SimpleDispatchInfo::Free]
 		fn final_hint(origin, #[compact] hint: T::BlockNumber) {
 			ensure_none(origin)?;
 			assert!(!<Self as Store>::Update::exists(), "Final hint must be updated only once in the block");
@@ -19,7 +19,10 @@
 //! This manages the GRANDPA authority set ready for the native code.
 //! These authorities are only for GRANDPA finality, not for consensus overall.
 //!
-//! In the future, it will also handle misbehavior reports, and on-chain
+//! It also handles misbehavior reports: voters which cast two conflicting votes
+//! in the same round can be reported with both signed votes, and are handed over
+//! to the `EquivocationHandler` once the signatures have been checked against the
+//! authority set they voted in. In the future, it will also handle on-chain
 //! finality notifications.
 //!
 //! For full integration with GRANDPA, the `GrandpaApi` should be implemented.
@@ -35,20 +38,27 @@ use serde::Serialize;
 use rstd::prelude::*;
 use parity_codec as codec;
 use codec::{Encode, Decode};
-use fg_primitives::ScheduledChange;
-use srml_support::{Parameter, decl_event, decl_storage, decl_module};
+use fg_primitives::{ScheduledChange, EquivocationReport, localized_payload};
+use srml_support::{Parameter, decl_event, decl_storage, decl_module, ensure};
 use srml_support::dispatch::Result;
-use srml_support::storage::StorageValue;
+use srml_support::storage::{StorageValue, StorageMap};
 use srml_support::storage::unhashed::StorageVec;
-use primitives::traits::CurrentHeight;
+use primitives::traits::{CurrentHeight, Verify};
 use substrate_primitives::ed25519;
-use system::ensure_signed;
 use primitives::traits::MaybeSerializeDebug;
 use ed25519::Public as AuthorityId;
+use consensus::OnEquivocationReport;
+use inherents::{ProvideInherent, InherentData, InherentIdentifier, RuntimeString, MakeFatalError};
 
 mod mock;
 mod tests;
 
+/// The identifier for the GRANDPA equivocation reports passed to block authors.
+pub const EQUIVOCATION_INHERENT_IDENTIFIER: InherentIdentifier = *b"afgequiv";
+
+/// The number of past authority sets kept around to check equivocation reports against.
+pub const MAX_HISTORICAL_SETS: u64 = 32;
+
 struct AuthorityStorageVec<S: codec::Codec + Default>(rstd::marker::PhantomData<S>);
 impl<S: codec::Codec + Default> StorageVec for AuthorityStorageVec<S> {
 	type Item = (S, u64);
@@ -131,10 +141,30 @@ pub trait Trait: system::Trait {
 	/// The session key type used by authorities.
 	type SessionKey: Parameter + Default + MaybeSerializeDebug;
 
+	/// The signature type of the votes cast by the authorities.
+	type Signature: Verify<Signer = Self::SessionKey> + Parameter;
+
+	/// Handler for proven equivocations. It is given the session key of the offender,
+	/// which may no longer be in the current authority set.
+	type EquivocationHandler: OnEquivocationReport<Self::SessionKey>;
+
 	/// The event type of this module.
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
 }
 
+/// A GRANDPA equivocation report, as expected by `report_misbehavior`.
+pub type EquivocationReportOf<T> = EquivocationReport<
+	<T as system::Trait>::Hash,
+	<T as system::Trait>::BlockNumber,
+	<T as Trait>::SessionKey,
+	<T as Trait>::Signature,
+>;
+
+/// Equivocations found by the GRANDPA voter are kept in the same store as slot equivocations,
+/// provided under `EQUIVOCATION_INHERENT_IDENTIFIER`.
+#[cfg(feature = "std")]
+pub use consensus::{EquivocationReports, SubmitEquivocationReport};
+
 /// A stored pending change, old format.
 // TODO: remove shim
 // https://github.com/paritytech/substrate/issues/1614
@@ -189,6 +219,14 @@ decl_storage! {
 		PendingChange get(pending_change): Option<StoredPendingChange<T::BlockNumber, T::SessionKey>>;
 		// next block number where we can force a change.
 		NextForced get(next_forced): Option<T::BlockNumber>;
+		// The id of the current authority set, incremented on every applied change. It has to
+		// match the set id of the finality gadget, so chains which are started from a later set
+		// set it at genesis.
+		CurrentSetId get(current_set_id) config(): u64;
+		// The last `MAX_HISTORICAL_SETS` authority sets which were replaced, by set id.
+		HistoricalSets get(historical_set): map u64 => Option<Vec<(T::SessionKey, u64)>>;
+		// The `(set_id, round, authority)` equivocations which have already been reported.
+		ReportedEquivocations get(is_equivocation_reported): map (u64, u64, T::SessionKey) => bool;
 	}
 	add_extra_genesis {
 		config(authorities): Vec<(T::SessionKey, u64)>;
@@ -215,10 +253,22 @@ decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
 		fn deposit_event<T>() = default;
 
-		/// Report some misbehavior.
-		fn report_misbehavior(origin, _report: Vec<u8>) {
-			ensure_signed(origin)?;
-			// FIXME: https://github.com/paritytech/substrate/issues/1112
+		/// Report a voter which cast two conflicting votes in the same round.
+		///
+		/// The report is an encoded `EquivocationReport`, whose signatures are checked
+		/// against the authority set the votes were cast in before the offender is handed
+		/// over to the `EquivocationHandler`.
+		fn report_misbehavior(origin, report: Vec<u8>) {
+			let origin: rstd::result::Result<system::RawOrigin<T::AccountId>, T::Origin> = origin.into();
+			match origin {
+				Ok(system::RawOrigin::Signed(_)) | Ok(system::RawOrigin::None) => {},
+				_ => return Err("bad origin: expected to be signed or an inherent origin"),
+			}
+
+			let (offender, equivocation) = Self::check_misbehavior_report(&report)?;
+			<ReportedEquivocations<T>>::insert(equivocation, true);
+
+			T::EquivocationHandler::handle_report(offender);
 		}
 
 		fn on_finalize(block_number: T::BlockNumber) {
@@ -242,6 +292,7 @@ decl_module! {
 					Self::deposit_event(
 						RawEvent::NewAuthorities(pending_change.next_authorities.clone())
 					);
+					Self::note_set_change();
 					<AuthorityStorageVec<T::SessionKey>>::set_items(pending_change.next_authorities);
 					<PendingChange<T>>::kill();
 				}
@@ -303,12 +354,80 @@ impl<T: Trait> Module<T> {
 		}
 	}
 
+	/// Keep the outgoing authority set around for checking equivocation reports,
+	/// and move on to the next set id.
+	fn note_set_change() {
+		let set_id = Self::current_set_id();
+		<HistoricalSets<T>>::insert(set_id, Self::grandpa_authorities());
+		if set_id >= MAX_HISTORICAL_SETS {
+			<HistoricalSets<T>>::remove(set_id - MAX_HISTORICAL_SETS);
+		}
+		<CurrentSetId<T>>::put(set_id + 1);
+	}
+
+	/// Check an encoded `EquivocationReport`, returning the session key of the offender
+	/// and the `(set_id, round, authority)` which equivocated.
+	fn check_misbehavior_report(report: &[u8])
+		-> rstd::result::Result<(T::SessionKey, (u64, u64, T::SessionKey)), &'static str>
+	{
+		let report = EquivocationReportOf::<T>::decode(&mut &report[..])
+			.ok_or("Misbehavior report could not be decoded")?;
+
+		ensure!(report.first.0 != report.second.0, "Equivocation report contains the same vote twice");
+		ensure!(
+			report.first.0.same_kind(&report.second.0),
+			"Equivocation report votes were cast in different steps"
+		);
+
+		let equivocation = (report.set_id, report.round, report.offender);
+		ensure!(!Self::is_equivocation_reported(&equivocation), "Equivocation was already reported");
+
+		let voters = if equivocation.0 == Self::current_set_id() {
+			Self::grandpa_authorities()
+		} else {
+			Self::historical_set(equivocation.0).ok_or("Equivocation was reported for an unknown set")?
+		};
+		ensure!(
+			voters.iter().any(|(voter, _)| *voter == equivocation.2),
+			"Equivocating authority was not a voter of the reported set"
+		);
+
+		for (vote, signature) in [report.first, report.second].iter() {
+			let payload = localized_payload(equivocation.1, equivocation.0, vote);
+			ensure!(signature.verify(&payload[..], &equivocation.2), "Bad equivocation report signature");
+		}
+
+		Ok((equivocation.2.clone(), equivocation))
+	}
+
 	/// Deposit one of this module's logs.
 	fn deposit_log(log: Log<T>) {
 		<system::Module<T>>::deposit_log(<T as Trait>::Log::from(log).into());
 	}
 }
 
+impl<T: Trait> ProvideInherent for Module<T> {
+	type Call = Call<T>;
+	type Error = MakeFatalError<RuntimeString>;
+	const INHERENT_IDENTIFIER: InherentIdentifier = EQUIVOCATION_INHERENT_IDENTIFIER;
+
+	/// Include the first pending equivocation report which can be verified.
+	fn create_inherent(data: &InherentData) -> Option<Self::Call> {
+		match data.get_data::<Vec<Vec<u8>>>(&EQUIVOCATION_INHERENT_IDENTIFIER) {
+			Ok(Some(reports)) => reports
+				.into_iter()
+				.find(|report| Self::check_misbehavior_report(report).is_ok())
+				.map(Call::report_misbehavior),
+			_ => None,
+		}
+	}
+
+	fn check_inherent(_: &Self::Call, _: &InherentData) -> rstd::result::Result<(), Self::Error> {
+		// reports are checked when they are dispatched.
+		Ok(())
+	}
+}
+
 impl<T: Trait> Module<T> where AuthorityId: core::convert::From<<T as Trait>::SessionKey> {
 	/// See if the digest contains any standard scheduled change.
 	pub fn scrape_digest_change(log: &Log<T>)
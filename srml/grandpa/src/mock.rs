@@ -18,7 +18,8 @@
 
 #![cfg(test)]
 
-use primitives::{BuildStorage, traits::IdentityLookup, testing::{Digest, DigestItem, Header}};
+use std::cell::RefCell;
+use primitives::{BuildStorage, traits::{IdentityLookup, Verify, Lazy}, testing::{Digest, DigestItem, Header}};
 use primitives::generic::DigestItem as GenDigestItem;
 use runtime_io;
 use srml_support::{impl_outer_origin, impl_outer_event};
@@ -36,12 +37,36 @@ impl From<RawLog<u64, u64>> for DigestItem {
 	}
 }
 
+thread_local! {
+	pub static EQUIVOCATIONS: RefCell<Vec<u64>> = RefCell::new(Vec::new());
+}
+
+/// A test signature is the signer along with the exact message it signed.
+#[derive(Clone, PartialEq, Eq, Debug, Decode, Encode)]
+pub struct TestSignature(pub u64, pub Vec<u8>);
+impl Verify for TestSignature {
+	type Signer = u64;
+
+	fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &u64) -> bool {
+		self.0 == *signer && &self.1[..] == msg.get()
+	}
+}
+
+pub struct TestEquivocationHandler;
+impl consensus::OnEquivocationReport<u64> for TestEquivocationHandler {
+	fn handle_report(offender: u64) {
+		EQUIVOCATIONS.with(|e| e.borrow_mut().push(offender));
+	}
+}
+
 // Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
 #[derive(Clone, PartialEq, Eq, Debug, Decode, Encode)]
 pub struct Test;
 impl Trait for Test {
 	type Log = DigestItem;
 	type SessionKey = u64;
+	type Signature = TestSignature;
+	type EquivocationHandler = TestEquivocationHandler;
 	type Event = TestEvent;
 }
 impl system::Trait for Test {
@@ -71,6 +96,7 @@ impl_outer_event!{
 pub fn new_test_ext(authorities: Vec<(u64, u64)>) -> runtime_io::TestExternalities<Blake2Hasher> {
 	let mut t = system::GenesisConfig::<Test>::default().build_storage().unwrap().0;
 	t.extend(GenesisConfig::<Test> {
+		current_set_id: 0,
 		authorities,
 	}.build_storage().unwrap().0);
 	t.into()
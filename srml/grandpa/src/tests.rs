@@ -21,12 +21,30 @@
 use primitives::{testing, traits::OnFinalize};
 use primitives::traits::Header;
 use runtime_io::with_externalities;
-use crate::mock::{Grandpa, System, new_test_ext};
+use crate::mock::{Grandpa, System, Origin, TestSignature, EQUIVOCATIONS, new_test_ext};
 use system::{EventRecord, Phase};
 use crate::{RawLog, RawEvent};
 use codec::{Decode, Encode};
+use fg_primitives::Vote;
+use srml_support::assert_noop;
+use substrate_primitives::H256;
 use super::*;
 
+fn signed_vote(set_id: u64, round: u64, voter: u64, vote: Vote<H256, u64>) -> (Vote<H256, u64>, TestSignature) {
+	let signature = TestSignature(voter, localized_payload(round, set_id, &vote));
+	(vote, signature)
+}
+
+fn equivocation_report(set_id: u64, round: u64, voter: u64, first: Vote<H256, u64>, second: Vote<H256, u64>) -> Vec<u8> {
+	EquivocationReport {
+		set_id,
+		round,
+		offender: voter,
+		first: signed_vote(set_id, round, voter, first),
+		second: signed_vote(set_id, round, voter, second),
+	}.encode()
+}
+
 #[test]
 fn authorities_change_logged() {
 	with_externalities(&mut new_test_ext(vec![(1, 1), (2, 1), (3, 1)]), || {
@@ -196,3 +214,164 @@ fn dispatch_forced_change() {
 		let _ = header;
 	});
 }
+
+#[test]
+fn equivocation_reports_are_handled() {
+	with_externalities(&mut new_test_ext(vec![(1, 1), (2, 1), (3, 1)]), || {
+		System::initialize(&1, &Default::default(), &Default::default());
+
+		let report = equivocation_report(
+			0,
+			4,
+			2,
+			Vote::Prevote(H256::repeat_byte(1), 1),
+			Vote::Prevote(H256::repeat_byte(2), 1),
+		);
+
+		assert!(Grandpa::report_misbehavior(Origin::signed(1), report.clone()).is_ok());
+		assert_eq!(EQUIVOCATIONS.with(|e| e.borrow().clone()), vec![2]);
+		assert!(Grandpa::is_equivocation_reported(&(0, 4, 2)));
+
+		// the same equivocation can't be punished twice, even with other votes.
+		assert_noop!(
+			Grandpa::report_misbehavior(Origin::signed(1), report),
+			"Equivocation was already reported"
+		);
+		assert_noop!(
+			Grandpa::report_misbehavior(
+				Origin::signed(1),
+				equivocation_report(
+					0,
+					4,
+					2,
+					Vote::Precommit(H256::repeat_byte(1), 1),
+					Vote::Precommit(H256::repeat_byte(2), 1),
+				),
+			),
+			"Equivocation was already reported"
+		);
+	});
+}
+
+#[test]
+fn invalid_equivocation_reports_are_rejected() {
+	with_externalities(&mut new_test_ext(vec![(1, 1), (2, 1), (3, 1)]), || {
+		System::initialize(&1, &Default::default(), &Default::default());
+
+		let first = Vote::Prevote(H256::repeat_byte(1), 1);
+		let second = Vote::Prevote(H256::repeat_byte(2), 1);
+
+		assert_noop!(
+			Grandpa::report_misbehavior(Origin::signed(1), vec![1, 2, 3]),
+			"Misbehavior report could not be decoded"
+		);
+		assert_noop!(
+			Grandpa::report_misbehavior(
+				Origin::signed(1),
+				equivocation_report(0, 4, 2, first.clone(), first.clone()),
+			),
+			"Equivocation report contains the same vote twice"
+		);
+		assert_noop!(
+			Grandpa::report_misbehavior(
+				Origin::signed(1),
+				equivocation_report(0, 4, 2, first.clone(), Vote::Precommit(H256::repeat_byte(2), 1)),
+			),
+			"Equivocation report votes were cast in different steps"
+		);
+		assert_noop!(
+			Grandpa::report_misbehavior(
+				Origin::signed(1),
+				equivocation_report(1, 4, 2, first.clone(), second.clone()),
+			),
+			"Equivocation was reported for an unknown set"
+		);
+		assert_noop!(
+			Grandpa::report_misbehavior(
+				Origin::signed(1),
+				equivocation_report(0, 4, 4, first.clone(), second.clone()),
+			),
+			"Equivocating authority was not a voter of the reported set"
+		);
+
+		// votes signed for another round.
+		let mut report = EquivocationReport::<H256, u64, u64, TestSignature>::decode(
+			&mut &equivocation_report(0, 4, 2, first, second)[..]
+		).unwrap();
+		report.round = 5;
+		assert_noop!(
+			Grandpa::report_misbehavior(Origin::signed(1), report.encode()),
+			"Bad equivocation report signature"
+		);
+
+		assert!(EQUIVOCATIONS.with(|e| e.borrow().is_empty()));
+	});
+}
+
+#[test]
+fn equivocations_are_checked_against_historical_sets() {
+	with_externalities(&mut new_test_ext(vec![(1, 1), (2, 1), (3, 1)]), || {
+		System::initialize(&1, &Default::default(), &Default::default());
+		Grandpa::schedule_change(vec![(3, 1), (2, 1)], 0, None).unwrap();
+		Grandpa::on_finalize(1);
+		let header = System::finalize();
+
+		System::initialize(&2, &header.hash(), &Default::default());
+		assert_eq!(Grandpa::current_set_id(), 1);
+		assert_eq!(Grandpa::historical_set(0), Some(vec![(1, 1), (2, 1), (3, 1)]));
+
+		// voter 1 equivocated in the previous set and is handed over even though it
+		// isn't an authority anymore.
+		assert!(Grandpa::report_misbehavior(
+			Origin::signed(1),
+			equivocation_report(
+				0,
+				1,
+				1,
+				Vote::Precommit(H256::repeat_byte(1), 1),
+				Vote::Precommit(H256::repeat_byte(2), 1),
+			),
+		).is_ok());
+		assert_eq!(EQUIVOCATIONS.with(|e| e.borrow().clone()), vec![1]);
+
+		// a voter of the current set is still checked against the set it voted in.
+		assert_noop!(
+			Grandpa::report_misbehavior(
+				Origin::signed(1),
+				equivocation_report(
+					1,
+					1,
+					1,
+					Vote::Precommit(H256::repeat_byte(1), 1),
+					Vote::Precommit(H256::repeat_byte(2), 1),
+				),
+			),
+			"Equivocating authority was not a voter of the reported set"
+		);
+	});
+}
+
+#[test]
+fn equivocation_reports_are_included_as_inherents() {
+	use inherents::{InherentData, ProvideInherent};
+
+	with_externalities(&mut new_test_ext(vec![(1, 1), (2, 1), (3, 1)]), || {
+		System::initialize(&1, &Default::default(), &Default::default());
+
+		let valid = equivocation_report(
+			0,
+			1,
+			3,
+			Vote::Prevote(H256::repeat_byte(1), 1),
+			Vote::Prevote(H256::repeat_byte(2), 1),
+		);
+
+		let mut data = InherentData::new();
+		data.put_data(EQUIVOCATION_INHERENT_IDENTIFIER, &vec![vec![1, 2, 3], valid.clone()]).unwrap();
+		assert_eq!(Grandpa::create_inherent(&data), Some(Call::report_misbehavior(valid)));
+
+		let mut data = InherentData::new();
+		data.put_data(EQUIVOCATION_INHERENT_IDENTIFIER, &vec![vec![1, 2, 3]]).unwrap();
+		assert_eq!(Grandpa::create_inherent(&data), None);
+	});
+}
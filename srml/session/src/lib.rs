@@ -233,6 +233,20 @@ decl_storage! {
 		NextKeyFor build(|config: &GenesisConfig<T>| {
 			config.keys.clone()
 		}): map T::AccountId => Option<T::SessionKey>;
+		/// The validator which last used a given session key as an authority.
+		///
+		/// Kept after the validator rotates out, so that misbehavior reported for a past
+		/// session can still be attributed.
+		pub KeyOwner get(key_owner) build(|config: &GenesisConfig<T>| {
+			config.validators.iter()
+				.filter_map(|v| config.keys.iter()
+					.find(|(account, _)| account == v)
+					.map(|(_, key)| key.clone())
+					.or_else(|| T::ConvertAccountIdToSessionKey::convert(v.clone()))
+					.map(|key| (key, v.clone()))
+				)
+				.collect::<Vec<_>>()
+		}): map T::SessionKey => Option<T::AccountId>;
 		/// The next session length.
 		NextSessionLength: Option<T::BlockNumber>;
 	}
@@ -316,12 +330,12 @@ impl<T: Trait> Module<T> {
 		let v = Self::validators();
 		<consensus::Module<T>>::set_authority_count(v.len() as u32);
 		for (i, v) in v.into_iter().enumerate() {
-			<consensus::Module<T>>::set_authority(
-				i as u32,
-				&<NextKeyFor<T>>::get(&v)
-					.or_else(|| T::ConvertAccountIdToSessionKey::convert(v))
-					.unwrap_or_default()
-			);
+			let key = <NextKeyFor<T>>::get(&v)
+				.or_else(|| T::ConvertAccountIdToSessionKey::convert(v.clone()));
+			<consensus::Module<T>>::set_authority(i as u32, &key.clone().unwrap_or_default());
+			if let Some(key) = key {
+				<KeyOwner<T>>::insert(key, v);
+			}
 		};
 	}
 
@@ -381,6 +395,8 @@ mod tests {
 		type Log = DigestItem;
 		type SessionKey = UintAuthorityId;
 		type InherentOfflineReport = ();
+		type SlotSeal = ();
+		type EquivocationHandler = ();
 	}
 	impl system::Trait for Test {
 		type Origin = Origin;
@@ -458,6 +474,24 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn key_owners_are_kept_after_rotating_out() {
+		with_externalities(&mut new_test_ext(), || {
+			assert_eq!(Session::key_owner(UintAuthorityId(3)), Some(3));
+
+			NEXT_VALIDATORS.with(|v| *v.borrow_mut() = vec![1, 2]);
+			assert_ok!(Session::set_key(Origin::signed(2), UintAuthorityId(5)));
+			assert_ok!(Session::force_new_session(false));
+			Session::check_rotate_session(1);
+			assert_eq!(Consensus::authorities(), vec![UintAuthorityId(1), UintAuthorityId(5)]);
+
+			assert_eq!(Session::key_owner(UintAuthorityId(5)), Some(2));
+			assert_eq!(Session::key_owner(UintAuthorityId(2)), Some(2));
+			assert_eq!(Session::key_owner(UintAuthorityId(3)), Some(3));
+			assert_eq!(Session::key_owner(UintAuthorityId(4)), None);
+		});
+	}
+
 	#[test]
 	fn should_work_with_early_exit() {
 		with_externalities(&mut new_test_ext(), || {
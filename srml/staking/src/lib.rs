@@ -249,6 +249,8 @@ use rstd::{prelude::*, result, collections::btree_map::BTreeMap};
 use parity_codec::{HasCompact, Encode, Decode};
 use srml_support::{StorageValue, StorageMap, EnumerableStorageMap, dispatch::Result};
 use srml_support::{decl_module, decl_event, decl_storage, ensure};
+#[cfg(feature = "runtime-benchmarks")]
+use srml_benchmarking::{decl_benchmarks, account};
 use srml_support::traits::{
 	Currency, OnFreeBalanceZero, OnDilution, LockIdentifier, LockableCurrency, WithdrawReasons,
 	OnUnbalanced, Imbalance,
@@ -767,6 +769,33 @@ decl_module! {
 	}
 }
 
+#[cfg(feature = "runtime-benchmarks")]
+decl_benchmarks! {
+	impl<T: Trait> for Module<T> as staking {
+		/// Bond the funds of a new stash account.
+		fn bond() {
+			let stash = account::<T::AccountId>("stash", 0);
+			let controller = account::<T::AccountId>("controller", 0);
+			let value = T::Currency::minimum_balance() + BalanceOf::<T>::sa(1_000);
+			T::Currency::make_free_balance_be(&stash, value);
+			Ok((Some(stash), Call::bond(T::Lookup::unlookup(controller), value, RewardDestination::Staked)))
+		}
+
+		/// Nominate `n` validators, up to `MAX_NOMINATIONS`.
+		fn nominate(n in 1 ..= 16) {
+			let stash = account::<T::AccountId>("stash", 0);
+			let controller = account::<T::AccountId>("controller", 0);
+			let value = T::Currency::minimum_balance() + BalanceOf::<T>::sa(1_000);
+			T::Currency::make_free_balance_be(&stash, value);
+			<Bonded<T>>::insert(&stash, controller.clone());
+			Self::update_ledger(&controller, &StakingLedger { stash, total: value, active: value, unlocking: vec![] });
+
+			let targets = (0..n).map(|i| T::Lookup::unlookup(account("validator", i))).collect();
+			Ok((Some(controller), Call::nominate(targets)))
+		}
+	}
+}
+
 decl_event!(
 	pub enum Event<T> where Balance = BalanceOf<T>, <T as system::Trait>::AccountId {
 		/// All validators have been rewarded by the given balance.
@@ -776,6 +805,9 @@ decl_event!(
 		OfflineWarning(AccountId, u32),
 		/// One validator (and its nominators) has been slashed by the given amount.
 		OfflineSlash(AccountId, Balance),
+		/// One validator (and its nominators) has been slashed by the given amount for
+		/// authoring two different blocks in the same slot.
+		EquivocationSlash(AccountId, Balance),
 	}
 );
 
@@ -1136,6 +1168,36 @@ impl<T: Trait> Module<T> {
 			Self::deposit_event(event);
 		}
 	}
+
+	/// Call when a validator has been proven to equivocate, i.e. to author two
+	/// different blocks in the same slot.
+	///
+	/// Unlike being offline, equivocating is not excused by any grace: the validator
+	/// is slashed as if it had exhausted the maximum unstake threshold and is removed
+	/// from the validator set.
+	///
+	/// NOTE: This is called with the controller (not the stash) account id.
+	pub fn on_equivocating_validator(controller: T::AccountId) {
+		if let Some(l) = Self::ledger(&controller) {
+			let stash = l.stash;
+
+			// Early exit if validator is invulnerable.
+			if Self::invulnerables().contains(&stash) {
+				return
+			}
+
+			let slash_exposure = Self::stakers(&stash).total;
+			let slash = (Self::offline_slash() * slash_exposure)
+				.checked_shl(MAX_UNSTAKE_THRESHOLD)
+				.map(|x| x.min(slash_exposure))
+				.unwrap_or(slash_exposure);
+			let _ = Self::slash_validator(&stash, slash);
+			<Validators<T>>::remove(&stash);
+			let _ = Self::apply_force_new_era(false);
+
+			Self::deposit_event(RawEvent::EquivocationSlash(stash, slash));
+		}
+	}
 }
 
 impl<T: Trait> OnSessionChange<T::Moment> for Module<T> {
@@ -1164,3 +1226,11 @@ impl<T: Trait> consensus::OnOfflineReport<Vec<u32>> for Module<T> {
 		}
 	}
 }
+
+impl<T: Trait> consensus::OnEquivocationReport<T::SessionKey> for Module<T> {
+	fn handle_report(offender: T::SessionKey) {
+		if let Some(controller) = <session::Module<T>>::key_owner(offender) {
+			Self::on_equivocating_validator(controller);
+		}
+	}
+}
@@ -50,6 +50,8 @@ impl consensus::Trait for Test {
 	type Log = DigestItem;
 	type SessionKey = UintAuthorityId;
 	type InherentOfflineReport = ();
+	type SlotSeal = ();
+	type EquivocationHandler = ();
 }
 impl system::Trait for Test {
 	type Origin = Origin;
@@ -72,6 +74,7 @@ impl balances::Trait for Test {
 	type TransactionPayment = ();
 	type TransferPayment = ();
 	type DustRemoval = ();
+	type WeightToFee = ();
 }
 impl session::Trait for Test {
 	type ConvertAccountIdToSessionKey = ConvertUintAuthorityId;
@@ -221,6 +221,48 @@ fn offline_should_slash_and_kick() {
 	});
 }
 
+#[test]
+fn equivocation_should_slash_and_kick_without_grace() {
+	// Test that an equivocating validator gets slashed and kicked, regardless of its grace
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		let _ = Balances::make_free_balance_be(&11, 1000);
+		assert!(<Validators<Test>>::exists(&11));
+		assert_ok!(Staking::set_offline_slash_grace(5));
+
+		Staking::on_equivocating_validator(10);
+
+		// Equivocation doesn't count as an offline report
+		assert_eq!(Staking::slash_count(&11), 0);
+		// Balance has been reduced by 2^MAX_UNSTAKE_THRESHOLD * offline_slash() * amount_at_stake,
+		// bounded by the amount at stake.
+		let exposure = Staking::stakers(11).total;
+		let slash = (2_u64.pow(MAX_UNSTAKE_THRESHOLD) * (Staking::offline_slash() * exposure)).min(exposure);
+		assert_eq!(Balances::free_balance(&11), 1000 - slash);
+		assert!(!<Validators<Test>>::exists(&11));
+		assert!(Staking::forcing_new_era().is_some());
+	});
+}
+
+#[test]
+fn equivocation_report_should_slash_the_owner_of_the_session_key() {
+	use consensus::OnEquivocationReport;
+	use primitives::testing::UintAuthorityId;
+
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		let _ = Balances::make_free_balance_be(&11, 1000);
+		assert_eq!(Session::key_owner(UintAuthorityId(10)), Some(10));
+
+		// keys which were never used by a validator are ignored.
+		<Staking as OnEquivocationReport<UintAuthorityId>>::handle_report(UintAuthorityId(99));
+		assert_eq!(Balances::free_balance(&11), 1000);
+		assert!(<Validators<Test>>::exists(&11));
+
+		<Staking as OnEquivocationReport<UintAuthorityId>>::handle_report(UintAuthorityId(10));
+		assert!(Balances::free_balance(&11) < 1000);
+		assert!(!<Validators<Test>>::exists(&11));
+	});
+}
+
 #[test]
 fn offline_grace_should_delay_slashing() {
 	// Tests that with grace, slashing is delayed
@@ -2032,3 +2074,30 @@ fn phragmen_large_scale_test_2() {
 		assert_total_expo(5, nom_budget / 2 + c_budget);
 	})
 }
+
+#[test]
+#[cfg(feature = "runtime-benchmarks")]
+fn benchmark_cases_can_be_dispatched() {
+	use srml_benchmarking::Benchmarking;
+	use srml_support::dispatch::Dispatchable;
+
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		let cases: Vec<_> = Staking::benchmark_cases().into_iter().map(|case| case.name).collect();
+		assert_eq!(cases, vec![b"bond".to_vec(), b"nominate".to_vec()]);
+
+		let (stash, call) = Staking::setup_benchmark(b"bond", &[]).unwrap();
+		assert_ok!(call.dispatch(Origin::signed(stash.unwrap())));
+	});
+
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		let (controller, call) = Staking::setup_benchmark(b"nominate", &[3]).unwrap();
+		let controller = controller.unwrap();
+		assert_ok!(call.dispatch(Origin::signed(controller)));
+		let stash = Staking::ledger(&controller).unwrap().stash;
+		assert_eq!(Staking::nominators(&stash).len(), 3);
+
+		assert!(Staking::setup_benchmark(b"nominate", &[17]).is_err());
+		assert!(Staking::setup_benchmark(b"nominate", &[]).is_err());
+		assert!(Staking::setup_benchmark(b"chill", &[]).is_err());
+	});
+}
@@ -23,6 +23,7 @@
 extern crate proc_macro;
 
 mod storage;
+mod transactional;
 
 use proc_macro::TokenStream;
 
@@ -140,3 +141,24 @@ use proc_macro::TokenStream;
 pub fn decl_storage(input: TokenStream) -> TokenStream {
 	storage::transformation::decl_storage_impl(input)
 }
+
+/// Execute the annotated function in a new storage transaction.
+///
+/// The function must return a `Result`. Its changes to the storage are kept if it returns
+/// `Ok` and discarded if it returns `Err`:
+///
+/// ```nocompile
+/// #[transactional]
+/// fn transfer(from: &AccountId, to: &AccountId, value: Balance) -> Result {
+/// 	<FreeBalance<T>>::mutate(to, |balance| *balance += value);
+/// 	let remaining = <FreeBalance<T>>::get(from).checked_sub(&value).ok_or("insufficient balance")?;
+/// 	<FreeBalance<T>>::insert(from, remaining);
+/// 	Ok(())
+/// }
+/// ```
+///
+/// Dispatchable functions of `decl_module!` accept the attribute as well.
+#[proc_macro_attribute]
+pub fn transactional(attr: TokenStream, input: TokenStream) -> TokenStream {
+	transactional::transactional_impl(attr, input)
+}
@@ -0,0 +1,50 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implementation of the `transactional` attribute.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{ItemFn, ReturnType, parse_macro_input, parse_quote};
+use srml_support_procedural_tools::generate_crate_path;
+
+pub fn transactional_impl(attr: TokenStream, input: TokenStream) -> TokenStream {
+	if !attr.is_empty() {
+		return syn::Error::new(Span::call_site(), "`transactional` doesn't take any arguments")
+			.to_compile_error()
+			.into();
+	}
+
+	let mut item = parse_macro_input!(input as ItemFn);
+	let output = match &item.decl.output {
+		ReturnType::Type(_, ty) => ty.clone(),
+		ReturnType::Default => {
+			return syn::Error::new(item.ident.span(), "a transactional function must return a `Result`")
+				.to_compile_error()
+				.into();
+		},
+	};
+
+	let scrate = generate_crate_path("srml-support");
+	let block = &item.block;
+	let block = parse_quote!({
+		#scrate::storage::with_transaction(|| -> #output #block)
+	});
+	item.block = Box::new(block);
+
+	quote!( #item ).into()
+}
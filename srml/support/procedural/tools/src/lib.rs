@@ -71,6 +71,25 @@ pub fn generate_hidden_includes(unique_id: &str, def_crate: &str) -> TokenStream
 	}
 }
 
+/// Generates the path of the `def_crate` crate. Unlike `generate_crate_access`, the path can be
+/// used in any scope, e.g. within a function body, as it doesn't require any hidden includes.
+pub fn generate_crate_path(def_crate: &str) -> TokenStream {
+	if ::std::env::var("CARGO_PKG_NAME").unwrap() == def_crate {
+		quote::quote!( crate )
+	} else {
+		match crate_name(def_crate) {
+			Ok(name) => {
+				let name = Ident::new(&name, Span::call_site());
+				quote::quote!( ::#name )
+			},
+			Err(e) => {
+				let err = Error::new(Span::call_site(), &e).to_compile_error();
+				quote!( #err )
+			}
+		}
+	}
+}
+
 // fn to remove white spaces around string types
 // (basically whitespaces around tokens)
 pub fn clean_type_string(input: &str) -> String {
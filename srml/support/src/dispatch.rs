@@ -22,6 +22,9 @@ pub use crate::rstd::prelude::{Vec, Clone, Eq, PartialEq};
 pub use std::fmt;
 pub use crate::rstd::result;
 pub use crate::codec::{Codec, Decode, Encode, Input, Output, HasCompact, EncodeAsRef};
+pub use crate::runtime_primitives::weights::{
+	Weight, DispatchInfo, WeighData, GetDispatchInfo, SimpleDispatchInfo,
+};
 pub use srml_metadata::{
 	FunctionMetadata, DecodeDifferent, DecodeDifferentArray,
 	FunctionArgumentMetadata, OuterDispatchMetadata, OuterDispatchCall
@@ -158,6 +161,86 @@ impl<T> Parameter for T where T: Codec + Clone + Eq {}
 /// # fn main() {}
 /// ```
 ///
+/// ### Weight Example
+///
+/// A function can be annotated with its weight, i.e. the resources its dispatch consumes, as any
+/// value implementing [`WeighData`](./dispatch/trait.WeighData.html) for the references to its
+/// parameters. Functions without an annotation get the default `SimpleDispatchInfo`:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate srml_support;
+/// # use srml_support::dispatch::{Result, SimpleDispatchInfo};
+/// # use srml_system::{self as system, Trait, ensure_signed};
+/// decl_module! {
+/// 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+///
+/// 		#[weight = SimpleDispatchInfo::Fixed(1_000_000)]
+/// 		fn my_heavy_function(origin) -> Result {
+///				// Your implementation
+/// 			Ok(())
+/// 		}
+///
+/// 		#[weight = SimpleDispatchInfo::Free]
+/// 		fn my_free_function(origin) -> Result {
+///				// Your implementation
+/// 			Ok(())
+/// 		}
+///		}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The weight of a call is exposed through [`GetDispatchInfo`](./dispatch/trait.GetDispatchInfo.html),
+/// which is implemented by `Call` and by the outer call of the runtime.
+///
+/// ### Transactional Example
+///
+/// A function annotated with `#[transactional]` is executed in a storage transaction: if it
+/// returns an error, all its changes to the storage are discarded. The annotation follows the
+/// weight annotation, if any:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate srml_support;
+/// # use srml_support::dispatch::Result;
+/// # use srml_system::{self as system, Trait, ensure_signed};
+/// decl_module! {
+/// 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+///
+/// 		#[transactional]
+/// 		fn my_atomic_function(origin) -> Result {
+/// 			let _sender = ensure_signed(origin)?;
+/// 			// Changes made before an error is returned are discarded.
+/// 			Ok(())
+/// 		}
+/// 	}
+/// }
+/// # fn main() {}
+/// ```
+///
+/// All the calls of a module are dispatched in a storage transaction if the module itself is
+/// annotated with `#[transactional]`, which must be its first attribute:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate srml_support;
+/// # use srml_support::dispatch::Result;
+/// # use srml_system::{self as system, Trait, ensure_signed};
+/// decl_module! {
+/// 	#[transactional]
+/// 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+///
+/// 		fn my_atomic_function(origin) -> Result {
+/// 			let _sender = ensure_signed(origin)?;
+/// 			// Changes made before an error is returned are discarded.
+/// 			Ok(())
+/// 		}
+/// 	}
+/// }
+/// # fn main() {}
+/// ```
+///
 /// ## Multiple Module Instances Example
 ///
 /// A Substrate module can be built such that multiple instances of the same module can be used within a single
@@ -405,6 +488,45 @@ macro_rules! decl_module {
 			$($rest)*
 		);
 	};
+	// Wrap the body of a `#[transactional]` function and normalize it as any other function.
+	(@normalize
+		$(#[$attr:meta])*
+		pub struct $mod_type:ident<$trait_instance:ident: $trait_name:ident$(<I>, $instance:ident: $instantiable:path $(= $module_default_instance:path)?)?>
+		for enum $call_type:ident where origin: $origin_type:ty, system = $system:ident
+		{ $( $deposit_event:tt )* }
+		{ $( $on_initialize:tt )* }
+		{ $( $on_finalize:tt )* }
+		{ $( $offchain:tt )* }
+		[ $($t:tt)* ]
+		$(#[doc = $doc_attr:tt])*
+		$(#[weight = $weight:expr])?
+		#[transactional]
+		$fn_vis:vis fn $fn_name:ident(
+			$( $params:tt )*
+		) $( -> $result:ty )* { $( $impl:tt )* }
+		$($rest:tt)*
+	) => {
+		$crate::decl_module!(@normalize
+			$(#[$attr])*
+			pub struct $mod_type<$trait_instance: $trait_name$(<I>, $instance: $instantiable $(= $module_default_instance)?)?>
+			for enum $call_type where origin: $origin_type, system = $system
+			{ $( $deposit_event )* }
+			{ $( $on_initialize )* }
+			{ $( $on_finalize )* }
+			{ $( $offchain )* }
+			[ $($t)* ]
+			$(#[doc = $doc_attr])*
+			$(#[weight = $weight])?
+			$fn_vis fn $fn_name(
+				$( $params )*
+			) -> $crate::dispatch::Result {
+				$crate::storage::with_transaction(|| {
+					$crate::decl_module!(@transactional_body [ $( $result )* ] { $( $impl )* })
+				})
+			}
+			$($rest)*
+		);
+	};
 	(@normalize
 		$(#[$attr:meta])*
 		pub struct $mod_type:ident<$trait_instance:ident: $trait_name:ident$(<I>, $instance:ident: $instantiable:path $(= $module_default_instance:path)?)?>
@@ -415,6 +537,7 @@ macro_rules! decl_module {
 		{ $( $offchain:tt )* }
 		[ $($t:tt)* ]
 		$(#[doc = $doc_attr:tt])*
+		$(#[weight = $weight:expr])?
 		$fn_vis:vis fn $fn_name:ident(
 			$origin:ident $(, $(#[$codec_attr:ident])* $param_name:ident : $param:ty)*
 		) $( -> $result:ty )* { $( $impl:tt )* }
@@ -431,6 +554,7 @@ macro_rules! decl_module {
 			[
 				$($t)*
 				$(#[doc = $doc_attr])*
+				#[weight = $crate::decl_module!(@weight $( $weight )?)]
 				$fn_vis fn $fn_name(
 					$origin $( , $(#[$codec_attr])* $param_name : $param )*
 				) $( -> $result )* { $( $impl )* }
@@ -449,6 +573,7 @@ macro_rules! decl_module {
 		{ $( $offchain:tt )* }
 		[ $($t:tt)* ]
 		$(#[doc = $doc_attr:tt])*
+		$(#[weight = $weight:expr])?
 		$fn_vis:vis fn $fn_name:ident(
 			$origin:ident : T::Origin $(, $(#[$codec_attr:ident])* $param_name:ident : $param:ty)*
 		) $( -> $result:ty )* { $( $impl:tt )* }
@@ -470,6 +595,7 @@ macro_rules! decl_module {
 		{ $( $offchain:tt )* }
 		[ $($t:tt)* ]
 		$(#[doc = $doc_attr:tt])*
+		$(#[weight = $weight:expr])?
 		$fn_vis:vis fn $fn_name:ident(
 			origin : $origin:ty $(, $(#[$codec_attr:ident])* $param_name:ident : $param:ty)*
 		) $( -> $result:ty )* { $( $impl:tt )* }
@@ -491,6 +617,7 @@ macro_rules! decl_module {
 		{ $( $offchain:tt )* }
 		[ $($t:tt)* ]
 		$(#[doc = $doc_attr:tt])*
+		$(#[weight = $weight:expr])?
 		$fn_vis:vis fn $fn_name:ident(
 			$( $(#[$codec_attr:ident])* $param_name:ident : $param:ty),*
 		) $( -> $result:ty )* { $( $impl:tt )* }
@@ -507,6 +634,7 @@ macro_rules! decl_module {
 			[
 				$($t)*
 				$(#[doc = $doc_attr])*
+				#[weight = $crate::decl_module!(@weight $( $weight )?)]
 				$fn_vis fn $fn_name(
 					root $( , $(#[$codec_attr])* $param_name : $param )*
 				) $( -> $result )* { $( $impl )* }
@@ -515,7 +643,9 @@ macro_rules! decl_module {
 			$($rest)*
 		);
 	};
+	// A module annotated with `#[transactional]` dispatches all its calls in a storage transaction.
 	(@normalize
+		#[transactional]
 		$(#[$attr:meta])*
 		pub struct $mod_type:ident<$trait_instance:ident: $trait_name:ident$(<I>, I: $instantiable:path $(= $module_default_instance:path)?)?>
 		for enum $call_type:ident where origin: $origin_type:ty, system = $system:ident
@@ -535,9 +665,51 @@ macro_rules! decl_module {
 			{ $( $on_initialize )* }
 			{ $( $on_finalize )* }
 			{ $( $offchain )* }
+			{ transactional }
+		);
+	};
+	(@normalize
+		$(#[$attr:meta])*
+		pub struct $mod_type:ident<$trait_instance:ident: $trait_name:ident$(<I>, I: $instantiable:path $(= $module_default_instance:path)?)?>
+		for enum $call_type:ident where origin: $origin_type:ty, system = $system:ident
+		{ $( $deposit_event:tt )* }
+		{ $( $on_initialize:tt )* }
+		{ $( $on_finalize:tt )* }
+		{ $( $offchain:tt )* }
+		[ $($t:tt)* ]
+	) => {
+		$crate::decl_module!(@imp
+			$(#[$attr])*
+			pub struct $mod_type<$trait_instance: $trait_name$(<I>, I: $instantiable $(= $module_default_instance)?)?>
+			for enum $call_type where origin: $origin_type, system = $system {
+				$($t)*
+			}
+			{ $( $deposit_event )* }
+			{ $( $on_initialize )* }
+			{ $( $on_finalize )* }
+			{ $( $offchain )* }
+			{}
 		);
 	};
 
+	// The weight of a function without a `#[weight = ...]` annotation.
+	(@weight) => { $crate::dispatch::SimpleDispatchInfo::default() };
+	(@weight $weight:expr) => { $weight };
+
+	// The body of a `#[transactional]` function, returning `Ok(())` if the function has no
+	// result type.
+	(@transactional_body [] { $( $impl:tt )* }) => {{
+		{ $( $impl )* }
+		Ok(())
+	}};
+	(@transactional_body [ $result:ty ] { $( $impl:tt )* }) => {{ $( $impl )* }};
+
+	// The dispatch of a call, in a storage transaction if the module is `#[transactional]`.
+	(@dispatch [] { $( $dispatch:tt )* }) => {{ $( $dispatch )* }};
+	(@dispatch [ transactional ] { $( $dispatch:tt )* }) => {
+		$crate::storage::with_transaction(|| { $( $dispatch )* })
+	};
+
 	// Implementation of Call enum's .dispatch() method.
 	// TODO: this probably should be a different macro?
 
@@ -866,6 +1038,7 @@ macro_rules! decl_module {
 		for enum $call_type:ident where origin: $origin_type:ty, system = $system:ident {
 			$(
 				$(#[doc = $doc_attr:tt])*
+				#[weight = $weight:expr]
 				$fn_vis:vis fn $fn_name:ident(
 					$from:ident $( , $(#[$codec_attr:ident])* $param_name:ident : $param:ty)*
 				) $( -> $result:ty )* { $( $impl:tt )* }
@@ -876,6 +1049,7 @@ macro_rules! decl_module {
 		{ $( $on_initialize:tt )* }
 		{ $( $on_finalize:tt )* }
 		{ $( $offchain:tt )* }
+		{ $( $transactional:ident )? }
 	) => {
 		// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
 		#[derive(Clone, Copy, PartialEq, Eq)]
@@ -1007,14 +1181,34 @@ macro_rules! decl_module {
 			type Trait = $trait_instance;
 			type Origin = $origin_type;
 			fn dispatch(self, _origin: Self::Origin) -> $crate::dispatch::Result {
-				match self {
+				$crate::decl_module!(@dispatch [ $( $transactional )? ] {
+					match self {
+						$(
+							$call_type::$fn_name( $( $param_name ),* ) => {
+								$crate::decl_module!(
+									@call
+									$from
+									$mod_type<$trait_instance $(, $fn_instance)?> $fn_name _origin $system [ $( $param_name ),* ]
+								)
+							},
+						)*
+						$call_type::__PhantomItem(_, _) => { unreachable!("__PhantomItem should never be used.") },
+					}
+				})
+			}
+		}
+		impl<$trait_instance: $trait_name $(<I>, $instance: $instantiable)?> $crate::dispatch::GetDispatchInfo
+			for $call_type<$trait_instance $(, $instance)?>
+		{
+			fn get_dispatch_info(&self) -> $crate::dispatch::DispatchInfo {
+				match *self {
 					$(
-						$call_type::$fn_name( $( $param_name ),* ) => {
-							$crate::decl_module!(
-								@call
-								$from
-								$mod_type<$trait_instance $(, $fn_instance)?> $fn_name _origin $system [ $( $param_name ),* ]
-							)
+						$call_type::$fn_name( $( ref $param_name ),* ) => {
+							let weight = <dyn $crate::dispatch::WeighData<( $( & $param, )* )>>::weigh_data(
+								&$weight,
+								( $( $param_name, )* )
+							);
+							$crate::dispatch::DispatchInfo { weight }
 						},
 					)*
 					$call_type::__PhantomItem(_, _) => { unreachable!("__PhantomItem should never be used.") },
@@ -1074,6 +1268,15 @@ macro_rules! impl_outer_dispatch {
 				}
 			}
 		}
+		impl $crate::dispatch::GetDispatchInfo for $call_type {
+			fn get_dispatch_info(&self) -> $crate::dispatch::DispatchInfo {
+				match *self {
+					$(
+						$call_type::$camelcase(ref call) => $crate::dispatch::GetDispatchInfo::get_dispatch_info(call),
+					)*
+				}
+			}
+		}
 		$(
 			impl $crate::dispatch::IsSubType<$camelcase> for $call_type {
 				fn is_aux_sub_type(&self) -> Option<&<$camelcase as $crate::dispatch::Callable>::Call> {
@@ -1227,10 +1430,13 @@ mod tests {
 	decl_module! {
 		pub struct Module<T: Trait> for enum Call where origin: T::Origin {
 			/// Hi, this is a comment.
+			#[weight = SimpleDispatchInfo::Free]
 			fn aux_0(_origin) -> Result { unreachable!() }
 			fn aux_1(_origin, #[compact] _data: u32) -> Result { unreachable!() }
 			fn aux_2(_origin, _data: i32, _data2: String) -> Result { unreachable!() }
+			#[weight = SimpleDispatchInfo::Max]
 			fn aux_3() -> Result { unreachable!() }
+			#[weight = WeightByData]
 			fn aux_4(_data: i32) -> Result { unreachable!() }
 			fn aux_5(_origin, _data: i32, #[compact] _data2: u32) -> Result { unreachable!() }
 
@@ -1240,6 +1446,14 @@ mod tests {
 		}
 	}
 
+	struct WeightByData;
+
+	impl<'a> WeighData<(&'a i32,)> for WeightByData {
+		fn weigh_data(&self, (data,): (&'a i32,)) -> Weight {
+			*data as Weight * 100
+		}
+	}
+
 	const EXPECTED_METADATA: &'static [FunctionMetadata] = &[
 				FunctionMetadata {
 					name: DecodeDifferent::Encode("aux_0"),
@@ -1344,6 +1558,15 @@ mod tests {
 		assert_eq!(decoded, call);
 	}
 
+	#[test]
+	fn weights_are_exposed_by_the_call() {
+		let weight = |call: Call<TraitImpl>| call.get_dispatch_info().weight;
+		assert_eq!(weight(Call::aux_0()), 0);
+		assert_eq!(weight(Call::aux_1(1)), SimpleDispatchInfo::default().weigh_data(()));
+		assert_eq!(weight(Call::aux_3()), Weight::max_value());
+		assert_eq!(weight(Call::aux_4(3)), 300);
+	}
+
 	#[test]
 	#[should_panic(expected = "on_initialize")]
 	fn on_initialize_should_work() {
@@ -64,7 +64,7 @@ pub use self::double_map::StorageDoubleMapWithHasher;
 pub use runtime_io::print;
 
 #[doc(inline)]
-pub use srml_support_procedural::decl_storage;
+pub use srml_support_procedural::{decl_storage, transactional};
 
 /// Return Err of the expression: `return Err($expression);`.
 ///
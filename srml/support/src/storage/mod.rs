@@ -56,6 +56,23 @@ impl<'a> Input for IncrementalChildInput<'a> {
 	}
 }
 
+/// Execute `f` in a new storage transaction, nested in the open ones.
+///
+/// The changes `f` makes to the storage are kept if it returns `Ok` and discarded if it
+/// returns `Err`, so the caller doesn't need to check everything before mutating the state.
+/// Functions can be made transactional with the `#[transactional]` attribute and the
+/// dispatchable functions of `decl_module!` accept it as well.
+pub fn with_transaction<R, E, F: FnOnce() -> Result<R, E>>(f: F) -> Result<R, E> {
+	runtime_io::start_transaction();
+	let result = f();
+	if result.is_ok() {
+		runtime_io::commit_transaction();
+	} else {
+		runtime_io::rollback_transaction();
+	}
+	result
+}
+
 /// The underlying runtime storage.
 pub struct RuntimeStorage;
 
@@ -21,6 +21,7 @@ use crate::codec::{Codec, Encode, Decode};
 use crate::runtime_primitives::traits::{
 	MaybeSerializeDebug, SimpleArithmetic
 };
+use crate::runtime_primitives::weights::Weight;
 
 /// The account with the given id was killed.
 pub trait OnFreeBalanceZero<AccountId> {
@@ -66,12 +67,13 @@ pub enum UpdateBalanceOutcome {
 /// It operates over a single generic `AccountId` type.
 pub trait MakePayment<AccountId> {
 	/// Make transaction payment from `who` for an extrinsic of encoded length
-	/// `encoded_len` bytes. Return `Ok` iff the payment was successful.
-	fn make_payment(who: &AccountId, encoded_len: usize) -> Result<(), &'static str>;
+	/// `encoded_len` bytes and of the given dispatch `weight`, plus the `tip` the
+	/// sender offers on top of the fee. Return `Ok` iff the payment was successful.
+	fn make_payment(who: &AccountId, encoded_len: usize, weight: Weight, tip: u128) -> Result<(), &'static str>;
 }
 
 impl<T> MakePayment<T> for () {
-	fn make_payment(_: &T, _: usize) -> Result<(), &'static str> { Ok(()) }
+	fn make_payment(_: &T, _: usize, _: Weight, _: u128) -> Result<(), &'static str> { Ok(()) }
 }
 
 /// Handler for when some currency "account" decreased in balance for
@@ -0,0 +1,162 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+use runtime_io::{with_externalities, Blake2Hasher};
+use srml_support::{StorageValue, StorageMap, transactional};
+use srml_support::dispatch::Result;
+use srml_support::storage::with_transaction;
+
+pub trait Trait {
+	type Origin;
+	type BlockNumber;
+}
+
+srml_support::decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		#[transactional]
+		fn set_value(_origin, value: u32) {
+			<Value<T>>::put(value);
+			if value == 0 {
+				return Err("zero value");
+			}
+		}
+
+		#[weight = srml_support::dispatch::SimpleDispatchInfo::Free]
+		#[transactional]
+		fn set_entry(_origin, key: u32, value: u32) -> Result {
+			<Map<T>>::insert(key, value);
+			<Module<T>>::increment(value)?;
+			Ok(())
+		}
+	}
+}
+
+srml_support::decl_storage!{
+	trait Store for Module<T: Trait> as Transactional {
+		pub Value: u32;
+		pub Map: map u32 => u32;
+	}
+}
+
+impl<T: Trait> Module<T> {
+	#[transactional]
+	fn increment(value: u32) -> Result {
+		let value = <Value<T>>::get().checked_add(value).ok_or("overflow")?;
+		<Value<T>>::put(value);
+		if value > 100 {
+			return Err("too large");
+		}
+		Ok(())
+	}
+}
+
+struct Test;
+
+impl Trait for Test {
+	type Origin = ();
+	type BlockNumber = u32;
+}
+
+type Transactional = Module<Test>;
+
+fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+	Default::default()
+}
+
+#[test]
+fn transactional_functions_discard_changes_on_error() {
+	with_externalities(&mut new_test_ext(), || {
+		<Value<Test>>::put(50);
+		assert_eq!(Transactional::increment(60), Err("too large"));
+		assert_eq!(<Value<Test>>::get(), 50);
+
+		assert_eq!(Transactional::increment(u32::max_value()), Err("overflow"));
+		assert_eq!(Transactional::increment(10), Ok(()));
+		assert_eq!(<Value<Test>>::get(), 60);
+	});
+}
+
+#[test]
+fn transactional_dispatches_are_atomic() {
+	with_externalities(&mut new_test_ext(), || {
+		assert_eq!(Transactional::set_value((), 0), Err("zero value"));
+		assert_eq!(<Value<Test>>::get(), 0);
+		assert_eq!(Transactional::set_value((), 90), Ok(()));
+
+		assert_eq!(Transactional::set_entry((), 1, 20), Err("too large"));
+		assert!(!<Map<Test>>::exists(1));
+		assert_eq!(<Value<Test>>::get(), 90);
+
+		assert_eq!(Transactional::set_entry((), 1, 5), Ok(()));
+		assert_eq!(<Map<Test>>::get(1), 5);
+		assert_eq!(<Value<Test>>::get(), 95);
+	});
+}
+
+#[test]
+fn transactions_can_be_nested() {
+	with_externalities(&mut new_test_ext(), || {
+		let result: Result = with_transaction(|| {
+			<Value<Test>>::put(10);
+			assert!(Transactional::increment(200).is_err());
+			assert_eq!(<Value<Test>>::get(), 10);
+			Transactional::increment(5)?;
+			Err("outer failure")
+		});
+		assert_eq!(result, Err("outer failure"));
+		assert_eq!(<Value<Test>>::get(), 0);
+	});
+}
+
+mod atomic {
+	use srml_support::StorageMap;
+	use srml_support::dispatch::Result;
+	use super::Trait;
+
+	srml_support::decl_module! {
+		#[transactional]
+		pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+			fn set_entry(_origin, key: u32, value: u32) -> Result {
+				<Map<T>>::insert(key, value);
+				if value == 0 {
+					return Err("zero value");
+				}
+				Ok(())
+			}
+		}
+	}
+
+	srml_support::decl_storage!{
+		trait Store for Module<T: Trait> as AtomicTransactional {
+			pub Map: map u32 => u32;
+		}
+	}
+}
+
+#[test]
+fn all_dispatches_of_a_transactional_module_are_atomic() {
+	use srml_support::dispatch::Dispatchable;
+
+	with_externalities(&mut new_test_ext(), || {
+		let call = atomic::Call::<Test>::set_entry(1, 0);
+		assert_eq!(call.dispatch(()), Err("zero value"));
+		assert!(!<atomic::Map<Test>>::exists(1));
+
+		let call = atomic::Call::<Test>::set_entry(1, 5);
+		assert_eq!(call.dispatch(()), Ok(()));
+		assert_eq!(<atomic::Map<Test>>::get(1), 5);
+	});
+}
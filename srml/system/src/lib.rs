@@ -36,7 +36,8 @@
 //!
 //! ### Dispatchable Functions
 //!
-//! The System module does not implement any dispatchable functions.
+//! - `set_changes_trie_config` - Set the new changes trie configuration (root only). The new
+//! configuration is applied starting from the next block.
 //!
 //! ### Public Functions
 //!
@@ -84,14 +85,15 @@ use primitives::traits::{self, CheckEqual, SimpleArithmetic, SimpleBitOps, One,
 use primitives::traits::Zero;
 use substrate_primitives::storage::well_known_keys;
 use srml_support::{storage, StorageValue, StorageMap, Parameter, decl_module, decl_event, decl_storage};
+use srml_support::dispatch::{Weight, DispatchInfo};
 use safe_mix::TripletMix;
 use parity_codec::{Encode, Decode};
 
-#[cfg(any(feature = "std", test))]
-use runtime_io::{twox_128, TestExternalities, Blake2Hasher};
+use substrate_primitives::ChangesTrieConfiguration;
+use primitives::generic::ChangesTrieSignal;
 
 #[cfg(any(feature = "std", test))]
-use substrate_primitives::ChangesTrieConfiguration;
+use runtime_io::{twox_128, TestExternalities, Blake2Hasher};
 
 /// Handler for when a new account has been created.
 pub trait OnNewAccount<AccountId> {
@@ -115,6 +117,20 @@ impl<AccountId> IsDeadAccount<AccountId> for () {
 	}
 }
 
+/// Maximum total weight of the extrinsics of a block.
+///
+/// Leaves room for about 100_000 calls of the default weight, so that it's the size limit which
+/// bounds blocks of simple transactions.
+pub const MAXIMUM_BLOCK_WEIGHT: Weight = 1_000_000_000;
+
+/// The weight of an extrinsic as counted towards the block limit.
+///
+/// The weight is capped at the limit, so that a call of the maximum weight still fits
+/// into an otherwise empty block.
+pub fn extrinsic_weight(info: DispatchInfo) -> Weight {
+	info.weight.min(MAXIMUM_BLOCK_WEIGHT)
+}
+
 /// Compute the trie root of a list of extrinsics.
 pub fn extrinsics_root<H: Hash, E: parity_codec::Encode>(extrinsics: &[E]) -> H::Output {
 	extrinsics_data_root::<H>(extrinsics.iter().map(parity_codec::Encode::encode).collect())
@@ -195,6 +211,19 @@ decl_module! {
 				<Events<T>>::put(vec![event]);
 			}
 		}
+
+		/// Set the new changes trie configuration. The configuration is applied starting
+		/// from the next block. `None` disables changes tries.
+		fn set_changes_trie_config(changes_trie_config: Option<ChangesTrieConfiguration>) {
+			match changes_trie_config {
+				Some(ref changes_trie_config) =>
+					storage::unhashed::put(well_known_keys::CHANGES_TRIE_CONFIG, changes_trie_config),
+				None => storage::unhashed::kill(well_known_keys::CHANGES_TRIE_CONFIG),
+			}
+
+			let log = RawLog::ChangesTrieSignal(ChangesTrieSignal::NewConfiguration(changes_trie_config));
+			Self::deposit_log(<T as Trait>::Log::from(log).into());
+		}
 	}
 }
 
@@ -265,6 +294,9 @@ pub enum RawLog<Hash> {
 	/// Changes trie has been computed for this block. Contains the root of
 	/// changes trie.
 	ChangesTrieRoot(Hash),
+	/// Changes trie signal (e.g. configuration change), that is applied
+	/// starting from the next block.
+	ChangesTrieSignal(ChangesTrieSignal),
 }
 
 impl<Hash: Member> RawLog<Hash> {
@@ -272,6 +304,15 @@ impl<Hash: Member> RawLog<Hash> {
 	pub fn as_changes_trie_root(&self) -> Option<&Hash> {
 		match *self {
 			RawLog::ChangesTrieRoot(ref item) => Some(item),
+			_ => None,
+		}
+	}
+
+	/// Try to cast the log entry as ChangesTrieSignal log entry.
+	pub fn as_changes_trie_signal(&self) -> Option<&ChangesTrieSignal> {
+		match *self {
+			RawLog::ChangesTrieSignal(ref item) => Some(item),
+			_ => None,
 		}
 	}
 }
@@ -282,6 +323,7 @@ impl From<RawLog<substrate_primitives::H256>> for primitives::testing::DigestIte
 	fn from(log: RawLog<substrate_primitives::H256>) -> primitives::testing::DigestItem {
 		match log {
 			RawLog::ChangesTrieRoot(root) => primitives::generic::DigestItem::ChangesTrieRoot(root),
+			RawLog::ChangesTrieSignal(signal) => primitives::generic::DigestItem::ChangesTrieSignal(signal),
 		}
 	}
 }
@@ -303,6 +345,8 @@ decl_storage! {
 		ExtrinsicCount: Option<u32>;
 		/// Total length in bytes for all extrinsics put together, for the current block.
 		AllExtrinsicsLen: Option<u32>;
+		/// Total weight of all extrinsics put together, for the current block.
+		AllExtrinsicsWeight: Option<Weight>;
 		/// Map of block numbers to block hashes.
 		pub BlockHash get(block_hash) build(|_| vec![(T::BlockNumber::zero(), hash69())]): map T::BlockNumber => T::Hash;
 		/// Extrinsics data for the current block (maps an extrinsic's index to its data).
@@ -393,6 +437,11 @@ impl<T: Trait> Module<T> {
 		<AllExtrinsicsLen<T>>::get().unwrap_or_default()
 	}
 
+	/// Gets the total weight of all executed extrinsics.
+	pub fn all_extrinsics_weight() -> Weight {
+		<AllExtrinsicsWeight<T>>::get().unwrap_or_default()
+	}
+
 	/// Start the execution of a particular block.
 	pub fn initialize(number: &T::BlockNumber, parent_hash: &T::Hash, txs_root: &T::Hash) {
 		// populate environment
@@ -414,6 +463,7 @@ impl<T: Trait> Module<T> {
 	pub fn finalize() -> T::Header {
 		<ExtrinsicCount<T>>::kill();
 		<AllExtrinsicsLen<T>>::kill();
+		<AllExtrinsicsWeight<T>>::kill();
 
 		let number = <Number<T>>::take();
 		let parent_hash = <ParentHash<T>>::take();
@@ -537,7 +587,7 @@ impl<T: Trait> Module<T> {
 	}
 
 	/// To be called immediately after an extrinsic has been applied.
-	pub fn note_applied_extrinsic(r: &Result<(), &'static str>, encoded_len: u32) {
+	pub fn note_applied_extrinsic(r: &Result<(), &'static str>, encoded_len: u32, weight: Weight) {
 		Self::deposit_event(match r {
 			Ok(_) => Event::ExtrinsicSuccess,
 			Err(_) => Event::ExtrinsicFailed,
@@ -545,9 +595,11 @@ impl<T: Trait> Module<T> {
 
 		let next_extrinsic_index = Self::extrinsic_index().unwrap_or_default() + 1u32;
 		let total_length = encoded_len.saturating_add(Self::all_extrinsics_len());
+		let total_weight = weight.saturating_add(Self::all_extrinsics_weight());
 
 		storage::unhashed::put(well_known_keys::EXTRINSIC_INDEX, &next_extrinsic_index);
 		<AllExtrinsicsLen<T>>::put(&total_length);
+		<AllExtrinsicsWeight<T>>::put(&total_weight);
 	}
 
 	/// To be called immediately after `note_applied_extrinsic` of the last extrinsic of the block
@@ -601,7 +653,7 @@ mod tests {
 	use runtime_io::with_externalities;
 	use substrate_primitives::H256;
 	use primitives::BuildStorage;
-	use primitives::traits::{BlakeTwo256, IdentityLookup};
+	use primitives::traits::{BlakeTwo256, IdentityLookup, DigestItem as DigestItemT};
 	use primitives::testing::{Digest, DigestItem, Header};
 	use srml_support::impl_outer_origin;
 
@@ -654,8 +706,8 @@ mod tests {
 
 			System::initialize(&2, &[0u8; 32].into(), &[0u8; 32].into());
 			System::deposit_event(42u16);
-			System::note_applied_extrinsic(&Ok(()), 0);
-			System::note_applied_extrinsic(&Err(""), 0);
+			System::note_applied_extrinsic(&Ok(()), 0, 0);
+			System::note_applied_extrinsic(&Err(""), 0, 0);
 			System::note_finished_extrinsics();
 			System::deposit_event(3u16);
 			System::finalize();
@@ -667,4 +719,29 @@ mod tests {
 			]);
 		});
 	}
+
+	#[test]
+	fn set_changes_trie_config_works() {
+		with_externalities(&mut new_test_ext(), || {
+			let config = ChangesTrieConfiguration { digest_interval: 4, digest_levels: 2 };
+
+			System::initialize(&1, &[0u8; 32].into(), &[0u8; 32].into());
+			System::set_changes_trie_config(Some(config.clone())).unwrap();
+			assert_eq!(storage::unhashed::get(well_known_keys::CHANGES_TRIE_CONFIG), Some(config.clone()));
+			let header = System::finalize();
+			assert_eq!(
+				header.digest.logs.iter().filter_map(|item| item.as_changes_trie_signal()).collect::<Vec<_>>(),
+				vec![&ChangesTrieSignal::NewConfiguration(Some(config))],
+			);
+
+			System::initialize(&2, &[0u8; 32].into(), &[0u8; 32].into());
+			System::set_changes_trie_config(None).unwrap();
+			assert!(!storage::unhashed::exists(well_known_keys::CHANGES_TRIE_CONFIG));
+			let header = System::finalize();
+			assert_eq!(
+				header.digest.logs.iter().filter_map(|item| item.as_changes_trie_signal()).collect::<Vec<_>>(),
+				vec![&ChangesTrieSignal::NewConfiguration(None)],
+			);
+		});
+	}
 }
@@ -93,6 +93,7 @@ use parity_codec::Decode;
 #[cfg(feature = "std")]
 use inherents::ProvideInherentData;
 use srml_support::{StorageValue, Parameter, decl_storage, decl_module};
+use srml_support::dispatch::SimpleDispatchInfo;
 use srml_support::for_each_tuple;
 use runtime_primitives::traits::{As, SimpleArithmetic, Zero};
 use system::ensure_none;
@@ -220,6 +221,7 @@ decl_module! {
 		/// The timestamp should be greater than the previous one by the amount specified by `minimum_period`.
 		///
 		/// The dispatch origin for this call must be `Inherent`.
+		#[weight = SimpleDispatchInfo::Free]
 		fn set(origin, #[compact] now: T::Moment) {
 			ensure_none(origin)?;
 			assert!(!<Self as Store>::DidUpdate::exists(), "Timestamp must be updated only once in the block");
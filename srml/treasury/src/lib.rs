@@ -335,6 +335,7 @@ mod tests {
 		type TransactionPayment = ();
 		type TransferPayment = ();
 		type DustRemoval = ();
+		type WeightToFee = ();
 	}
 	impl Trait for Test {
 		type Currency = balances::Module<Test>;
@@ -207,7 +207,7 @@ fn execute<C: Crypto<Seed=[u8; 32]>>(matches: clap::ArgMatches) where
 			println!("Using a genesis hash of {}", HexDisplay::from(&genesis_hash.as_ref()));
 
 			let era = Era::immortal();
-			let raw_payload = (Compact(index), function, era, genesis_hash);
+			let raw_payload = (Compact(index), function, era, genesis_hash, Compact(0u128));
 			let signature = raw_payload.using_encoded(|payload| if payload.len() > 256 {
 				signer.sign(&blake2_256(payload)[..])
 			} else {
@@ -220,6 +220,7 @@ fn execute<C: Crypto<Seed=[u8; 32]>>(matches: clap::ArgMatches) where
 				signer.public().into(),
 				signature.into(),
 				era,
+				0,
 			);
 			println!("0x{}", hex::encode(&extrinsic.encode()));
 		}